@@ -0,0 +1,119 @@
+/*
+ * C ABI of the revm shared library.
+ *
+ * The ABI is versioned with REVM_ABI_VERSION. Any incompatible change of the
+ * functions or structs below bumps the version, compatible additions do not.
+ * Consumers should compare revm_abi_version() against the header they were
+ * built with before creating a handle.
+ *
+ * All byte arrays are big-endian. Addresses are 20 bytes, hashes and 256-bit
+ * integers are 32 bytes.
+ *
+ * Handles are not thread safe, but different handles can be used from
+ * different threads concurrently.
+ */
+#ifndef REVM_H
+#define REVM_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define REVM_ABI_VERSION 1
+
+/* Status codes returned by library functions and database callbacks. */
+#define REVM_OK 0
+/* Only valid as return value of the `basic` callback. */
+#define REVM_NOT_FOUND 1
+#define REVM_ERR_NULL_POINTER -1
+#define REVM_ERR_INVALID_JSON -2
+#define REVM_ERR_DATABASE -3
+#define REVM_ERR_TRANSACTION -4
+#define REVM_ERR_PANIC -5
+
+/*
+ * Account returned from the `basic` callback.
+ *
+ * `code` may be NULL, in which case `code_by_hash` is called with `code_hash`
+ * when the code is needed. If `code` is set `code_hash` is ignored and computed
+ * by the library. Memory pointed by `code` is copied right after the callback
+ * returns and only needs to stay valid until the next callback is made.
+ */
+typedef struct RevmAccountInfo {
+    uint8_t balance[32];
+    uint64_t nonce;
+    uint8_t code_hash[32];
+    const uint8_t *code;
+    size_t code_len;
+} RevmAccountInfo;
+
+/*
+ * Database callbacks. `ctx` is passed as first argument to every callback and
+ * is never dereferenced by the library.
+ *
+ * Callbacks return REVM_OK on success. Any other value, except REVM_NOT_FOUND
+ * from `basic`, aborts execution and is reported as REVM_ERR_DATABASE with
+ * the returned code in the last error message.
+ *
+ * Callbacks are only called from inside revm_evm_transact() and only for
+ * values not already cached by the handle.
+ */
+typedef struct RevmDatabase {
+    void *ctx;
+    int32_t (*basic)(void *ctx, const uint8_t address[20], RevmAccountInfo *out);
+    /* Code is copied right after return, it only needs to stay valid until the next callback. */
+    int32_t (*code_by_hash)(void *ctx, const uint8_t code_hash[32], const uint8_t **code,
+                            size_t *code_len);
+    int32_t (*storage)(void *ctx, const uint8_t address[20], const uint8_t index[32],
+                       uint8_t out[32]);
+    int32_t (*block_hash)(void *ctx, const uint8_t number[32], uint8_t out[32]);
+} RevmDatabase;
+
+typedef struct RevmEvm RevmEvm;
+
+/* Version of the ABI implemented by the loaded library. */
+uint32_t revm_abi_version(void);
+
+/*
+ * Creates new EVM handle reading state through `db`. The struct is copied.
+ * Returns NULL if `db` or any of its callbacks is NULL.
+ */
+RevmEvm *revm_evm_new(const RevmDatabase *db);
+
+/* Frees the handle. NULL is ignored. */
+void revm_evm_free(RevmEvm *evm);
+
+/*
+ * Sets environment from JSON object with optional `cfg`, `block` and `tx`
+ * fields in the serde format of revm `Env`. Fields that are not present keep
+ * their previous values.
+ */
+int32_t revm_evm_set_env(RevmEvm *evm, const char *env_json);
+
+/*
+ * Executes transaction from the environment.
+ *
+ * On REVM_OK `*result_json` is set to JSON object with `result` and `state`
+ * fields and must be freed with revm_string_free(). If `commit` is true the
+ * changed state is kept in the handle and seen by following transactions.
+ */
+int32_t revm_evm_transact(RevmEvm *evm, bool commit, char **result_json);
+
+/*
+ * Message describing the last error on this handle, or NULL. Valid until the
+ * next call that takes the handle.
+ */
+const char *revm_evm_last_error(const RevmEvm *evm);
+
+/* Frees string returned by the library. NULL is ignored. */
+void revm_string_free(char *string);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* REVM_H */
@@ -0,0 +1,165 @@
+//! Database backed by C callbacks.
+
+use core::ffi::c_void;
+use core::fmt;
+use core::ptr;
+use revm::db::DatabaseRef;
+use revm::primitives::{keccak256, AccountInfo, Bytecode, Bytes, B160, B256, KECCAK_EMPTY, U256};
+
+use crate::{REVM_NOT_FOUND, REVM_OK};
+
+/// Account filled by the `basic` callback.
+#[repr(C)]
+pub struct RevmAccountInfo {
+    pub balance: [u8; 32],
+    pub nonce: u64,
+    pub code_hash: [u8; 32],
+    pub code: *const u8,
+    pub code_len: usize,
+}
+
+/// Database callbacks, see `include/revm.h`.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct RevmDatabase {
+    pub ctx: *mut c_void,
+    pub basic: Option<
+        unsafe extern "C" fn(
+            ctx: *mut c_void,
+            address: *const u8,
+            out: *mut RevmAccountInfo,
+        ) -> i32,
+    >,
+    pub code_by_hash: Option<
+        unsafe extern "C" fn(
+            ctx: *mut c_void,
+            code_hash: *const u8,
+            code: *mut *const u8,
+            code_len: *mut usize,
+        ) -> i32,
+    >,
+    pub storage: Option<
+        unsafe extern "C" fn(
+            ctx: *mut c_void,
+            address: *const u8,
+            index: *const u8,
+            out: *mut u8,
+        ) -> i32,
+    >,
+    pub block_hash:
+        Option<unsafe extern "C" fn(ctx: *mut c_void, number: *const u8, out: *mut u8) -> i32>,
+}
+
+impl RevmDatabase {
+    pub fn is_complete(&self) -> bool {
+        self.basic.is_some()
+            && self.code_by_hash.is_some()
+            && self.storage.is_some()
+            && self.block_hash.is_some()
+    }
+}
+
+/// Error code returned by a callback.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CallbackError {
+    pub callback: &'static str,
+    pub code: i32,
+}
+
+impl fmt::Display for CallbackError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "database callback `{}` returned {}",
+            self.callback, self.code
+        )
+    }
+}
+
+fn check(callback: &'static str, code: i32) -> Result<(), CallbackError> {
+    match code {
+        REVM_OK => Ok(()),
+        code => Err(CallbackError { callback, code }),
+    }
+}
+
+/// Copies code returned from C, the pointer is only valid until the next callback.
+unsafe fn copy_code(code: *const u8, len: usize) -> Bytecode {
+    if code.is_null() || len == 0 {
+        return Bytecode::new();
+    }
+    let code = core::slice::from_raw_parts(code, len);
+    Bytecode::new_raw(Bytes::copy_from_slice(code))
+}
+
+/// Database calling into [`RevmDatabase`] callbacks.
+///
+/// Callbacks are checked to be set when the handle is created.
+pub struct CallbackDatabase(pub RevmDatabase);
+
+impl DatabaseRef for CallbackDatabase {
+    type Error = CallbackError;
+
+    fn basic(&self, address: B160) -> Result<Option<AccountInfo>, Self::Error> {
+        let mut out = RevmAccountInfo {
+            balance: [0; 32],
+            nonce: 0,
+            code_hash: KECCAK_EMPTY.0,
+            code: ptr::null(),
+            code_len: 0,
+        };
+        let code = unsafe { (self.0.basic.unwrap())(self.0.ctx, address.0.as_ptr(), &mut out) };
+        if code == REVM_NOT_FOUND {
+            return Ok(None);
+        }
+        check("basic", code)?;
+
+        let bytecode = (!out.code.is_null()).then(|| unsafe { copy_code(out.code, out.code_len) });
+        let code_hash = match &bytecode {
+            // hash is computed from code if code is given.
+            Some(code) if !code.is_empty() => keccak256(&code.original_bytes()),
+            Some(_) => KECCAK_EMPTY,
+            None => B256(out.code_hash),
+        };
+        Ok(Some(AccountInfo {
+            balance: U256::from_be_bytes(out.balance),
+            nonce: out.nonce,
+            code_hash,
+            code: bytecode,
+        }))
+    }
+
+    fn code_by_hash(&self, code_hash: B256) -> Result<Bytecode, Self::Error> {
+        let mut code = ptr::null();
+        let mut len = 0;
+        let ret = unsafe {
+            (self.0.code_by_hash.unwrap())(self.0.ctx, code_hash.0.as_ptr(), &mut code, &mut len)
+        };
+        check("code_by_hash", ret)?;
+        Ok(unsafe { copy_code(code, len) })
+    }
+
+    fn storage(&self, address: B160, index: U256) -> Result<U256, Self::Error> {
+        let index = index.to_be_bytes::<32>();
+        let mut out = [0u8; 32];
+        let ret = unsafe {
+            (self.0.storage.unwrap())(
+                self.0.ctx,
+                address.0.as_ptr(),
+                index.as_ptr(),
+                out.as_mut_ptr(),
+            )
+        };
+        check("storage", ret)?;
+        Ok(U256::from_be_bytes(out))
+    }
+
+    fn block_hash(&self, number: U256) -> Result<B256, Self::Error> {
+        let number = number.to_be_bytes::<32>();
+        let mut out = [0u8; 32];
+        let ret =
+            unsafe { (self.0.block_hash.unwrap())(self.0.ctx, number.as_ptr(), out.as_mut_ptr()) };
+        check("block_hash", ret)?;
+        Ok(B256(out))
+    }
+}
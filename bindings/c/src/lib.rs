@@ -0,0 +1,210 @@
+//! Shared library exposing revm over a stable C ABI.
+//!
+//! The interface is documented in `include/revm.h`, which is the source of truth for
+//! consumers. It is designed to be used from cgo: state is served through plain
+//! function pointers with an opaque context, and results are returned as JSON so that
+//! no Rust layout leaks through the ABI.
+
+mod database;
+
+pub use database::{CallbackDatabase, CallbackError, RevmAccountInfo, RevmDatabase};
+
+use core::ffi::c_char;
+use core::ptr;
+use revm::db::CacheDB;
+use revm::primitives::{EVMError, Env};
+use revm::{DatabaseCommit, EVM};
+use serde_json::Value;
+use std::ffi::{CStr, CString};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+/// Version of the ABI, bumped on incompatible changes.
+pub const REVM_ABI_VERSION: u32 = 1;
+
+pub const REVM_OK: i32 = 0;
+pub const REVM_NOT_FOUND: i32 = 1;
+pub const REVM_ERR_NULL_POINTER: i32 = -1;
+pub const REVM_ERR_INVALID_JSON: i32 = -2;
+pub const REVM_ERR_DATABASE: i32 = -3;
+pub const REVM_ERR_TRANSACTION: i32 = -4;
+pub const REVM_ERR_PANIC: i32 = -5;
+
+/// EVM handle. State read through callbacks and committed by transactions is cached
+/// for the lifetime of the handle.
+pub struct RevmEvm {
+    evm: EVM<CacheDB<CallbackDatabase>>,
+    last_error: Option<CString>,
+}
+
+impl RevmEvm {
+    fn fail(&mut self, code: i32, message: impl ToString) -> i32 {
+        // interior nul bytes can't be represented, message is best effort.
+        self.last_error = CString::new(message.to_string().replace('\0', " ")).ok();
+        code
+    }
+
+    fn set_env(&mut self, env_json: &CStr) -> i32 {
+        let update: Value = match env_json
+            .to_str()
+            .map_err(|e| e.to_string())
+            .and_then(|json| serde_json::from_str(json).map_err(|e| e.to_string()))
+        {
+            Ok(update) => update,
+            Err(e) => return self.fail(REVM_ERR_INVALID_JSON, e),
+        };
+        let mut env = serde_json::to_value(&self.evm.env).expect("Env is serializable");
+        merge(&mut env, update);
+        match serde_json::from_value::<Env>(env) {
+            Ok(env) => {
+                self.evm.env = env;
+                REVM_OK
+            }
+            Err(e) => self.fail(REVM_ERR_INVALID_JSON, e),
+        }
+    }
+
+    fn transact(&mut self, commit: bool) -> Result<CString, i32> {
+        let result = match self.evm.transact() {
+            Ok(result) => result,
+            Err(EVMError::Database(e)) => return Err(self.fail(REVM_ERR_DATABASE, e)),
+            Err(e) => return Err(self.fail(REVM_ERR_TRANSACTION, format!("{e:?}"))),
+        };
+        let json = serde_json::to_string(&result).expect("result is serializable");
+        if commit {
+            self.evm.db().unwrap().commit(result.state);
+        }
+        Ok(CString::new(json).expect("JSON has no nul bytes"))
+    }
+}
+
+/// Recursively merges `update` objects into `target`, other values are replaced.
+fn merge(target: &mut Value, update: Value) {
+    match (target, update) {
+        (Value::Object(target), Value::Object(update)) => {
+            for (key, value) in update {
+                match target.get_mut(&key) {
+                    Some(existing) => merge(existing, value),
+                    None => {
+                        target.insert(key, value);
+                    }
+                }
+            }
+        }
+        (target, update) => *target = update,
+    }
+}
+
+/// Runs `f` catching panics so they don't unwind into the caller.
+fn guard(evm: &mut RevmEvm, f: impl FnOnce(&mut RevmEvm) -> i32) -> i32 {
+    match catch_unwind(AssertUnwindSafe(|| f(evm))) {
+        Ok(code) => code,
+        Err(panic) => {
+            let message = panic
+                .downcast_ref::<&str>()
+                .map(|s| s.to_string())
+                .or_else(|| panic.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "unknown panic".to_string());
+            evm.fail(REVM_ERR_PANIC, message)
+        }
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn revm_abi_version() -> u32 {
+    REVM_ABI_VERSION
+}
+
+/// # Safety
+///
+/// `db` must be NULL or point to a valid [`RevmDatabase`].
+#[no_mangle]
+pub unsafe extern "C" fn revm_evm_new(db: *const RevmDatabase) -> *mut RevmEvm {
+    let Some(db) = db.as_ref() else {
+        return ptr::null_mut();
+    };
+    if !db.is_complete() {
+        return ptr::null_mut();
+    }
+    let mut evm = EVM::new();
+    evm.database(CacheDB::new(CallbackDatabase(*db)));
+    Box::into_raw(Box::new(RevmEvm {
+        evm,
+        last_error: None,
+    }))
+}
+
+/// # Safety
+///
+/// `evm` must be NULL or a handle returned by [`revm_evm_new`] that was not freed.
+#[no_mangle]
+pub unsafe extern "C" fn revm_evm_free(evm: *mut RevmEvm) {
+    if !evm.is_null() {
+        drop(Box::from_raw(evm));
+    }
+}
+
+/// # Safety
+///
+/// `evm` must be a valid handle and `env_json` a nul terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn revm_evm_set_env(evm: *mut RevmEvm, env_json: *const c_char) -> i32 {
+    let Some(evm) = evm.as_mut() else {
+        return REVM_ERR_NULL_POINTER;
+    };
+    if env_json.is_null() {
+        return evm.fail(REVM_ERR_NULL_POINTER, "env_json is NULL");
+    }
+    let env_json = CStr::from_ptr(env_json);
+    guard(evm, |evm| {
+        evm.last_error = None;
+        evm.set_env(env_json)
+    })
+}
+
+/// # Safety
+///
+/// `evm` must be a valid handle and `result_json` a valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn revm_evm_transact(
+    evm: *mut RevmEvm,
+    commit: bool,
+    result_json: *mut *mut c_char,
+) -> i32 {
+    let Some(evm) = evm.as_mut() else {
+        return REVM_ERR_NULL_POINTER;
+    };
+    if result_json.is_null() {
+        return evm.fail(REVM_ERR_NULL_POINTER, "result_json is NULL");
+    }
+    *result_json = ptr::null_mut();
+    guard(evm, |evm| {
+        evm.last_error = None;
+        match evm.transact(commit) {
+            Ok(json) => {
+                *result_json = json.into_raw();
+                REVM_OK
+            }
+            Err(code) => code,
+        }
+    })
+}
+
+/// # Safety
+///
+/// `evm` must be NULL or a valid handle.
+#[no_mangle]
+pub unsafe extern "C" fn revm_evm_last_error(evm: *const RevmEvm) -> *const c_char {
+    evm.as_ref()
+        .and_then(|evm| evm.last_error.as_ref())
+        .map_or(ptr::null(), |error| error.as_ptr())
+}
+
+/// # Safety
+///
+/// `string` must be NULL or a string returned by this library that was not freed.
+#[no_mangle]
+pub unsafe extern "C" fn revm_string_free(string: *mut c_char) {
+    if !string.is_null() {
+        drop(CString::from_raw(string));
+    }
+}
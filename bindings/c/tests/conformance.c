@@ -0,0 +1,199 @@
+/*
+ * Conformance tests of the C ABI. Run with `make test`.
+ */
+#include "revm.h"
+
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+static int failures = 0;
+
+#define CHECK(cond)                                                         \
+    do {                                                                    \
+        if (!(cond)) {                                                      \
+            fprintf(stderr, "%s:%d: check failed: %s\n", __FILE__, __LINE__, \
+                    #cond);                                                 \
+            failures++;                                                     \
+        }                                                                   \
+    } while (0)
+
+/* PUSH1 0 SLOAD PUSH1 1 ADD PUSH1 0 SSTORE STOP: increments slot 0. */
+static const uint8_t COUNTER_CODE[] = {0x60, 0x00, 0x54, 0x60, 0x01, 0x01,
+                                       0x60, 0x00, 0x55, 0x00};
+
+/* Address 0x..1002 holds the counter, 0x..1003 makes the database fail. */
+typedef struct TestDb {
+    int basic_calls;
+    int storage_calls;
+} TestDb;
+
+static int32_t test_basic(void *ctx, const uint8_t address[20], RevmAccountInfo *out) {
+    TestDb *db = ctx;
+    db->basic_calls++;
+    if (address[18] == 0x10 && address[19] == 0x02) {
+        out->code = COUNTER_CODE;
+        out->code_len = sizeof(COUNTER_CODE);
+        return REVM_OK;
+    }
+    if (address[18] == 0x10 && address[19] == 0x03) {
+        return 42;
+    }
+    return REVM_NOT_FOUND;
+}
+
+static int32_t test_code_by_hash(void *ctx, const uint8_t code_hash[32], const uint8_t **code,
+                                 size_t *code_len) {
+    (void)ctx;
+    (void)code_hash;
+    *code = NULL;
+    *code_len = 0;
+    return REVM_OK;
+}
+
+static int32_t test_storage(void *ctx, const uint8_t address[20], const uint8_t index[32],
+                            uint8_t out[32]) {
+    TestDb *db = ctx;
+    (void)address;
+    (void)index;
+    db->storage_calls++;
+    memset(out, 0, 32);
+    out[31] = 0x10;
+    return REVM_OK;
+}
+
+static int32_t test_block_hash(void *ctx, const uint8_t number[32], uint8_t out[32]) {
+    (void)ctx;
+    (void)number;
+    memset(out, 0, 32);
+    return REVM_OK;
+}
+
+static RevmEvm *new_evm(TestDb *db) {
+    RevmDatabase callbacks = {
+        .ctx = db,
+        .basic = test_basic,
+        .code_by_hash = test_code_by_hash,
+        .storage = test_storage,
+        .block_hash = test_block_hash,
+    };
+    return revm_evm_new(&callbacks);
+}
+
+static const char *CALL_COUNTER =
+    "{\"tx\": {\"caller\": \"0x0000000000000000000000000000000000000001\","
+    " \"transact_to\": {\"Call\": \"0x0000000000000000000000000000000000001002\"},"
+    " \"gas_limit\": 100000}}";
+
+static void test_version(void) {
+    CHECK(revm_abi_version() == REVM_ABI_VERSION);
+}
+
+static void test_null_arguments(void) {
+    TestDb db = {0};
+    RevmDatabase incomplete = {.ctx = &db, .basic = test_basic};
+    char *json = NULL;
+
+    CHECK(revm_evm_new(NULL) == NULL);
+    CHECK(revm_evm_new(&incomplete) == NULL);
+    CHECK(revm_evm_set_env(NULL, "{}") == REVM_ERR_NULL_POINTER);
+    CHECK(revm_evm_transact(NULL, false, &json) == REVM_ERR_NULL_POINTER);
+    CHECK(revm_evm_last_error(NULL) == NULL);
+    revm_evm_free(NULL);
+    revm_string_free(NULL);
+}
+
+static void test_invalid_env(void) {
+    TestDb db = {0};
+    RevmEvm *evm = new_evm(&db);
+    CHECK(evm != NULL);
+    CHECK(revm_evm_last_error(evm) == NULL);
+    CHECK(revm_evm_set_env(evm, "{") == REVM_ERR_INVALID_JSON);
+    CHECK(revm_evm_last_error(evm) != NULL);
+    CHECK(revm_evm_set_env(evm, "{\"tx\": {\"gas_limit\": \"many\"}}") == REVM_ERR_INVALID_JSON);
+    CHECK(revm_evm_set_env(evm, "{\"block\": {\"number\": \"0x10\"}}") == REVM_OK);
+    CHECK(revm_evm_last_error(evm) == NULL);
+    revm_evm_free(evm);
+}
+
+static void test_transact(void) {
+    TestDb db = {0};
+    RevmEvm *evm = new_evm(&db);
+    char *json = NULL;
+
+    CHECK(revm_evm_set_env(evm, CALL_COUNTER) == REVM_OK);
+    CHECK(revm_evm_transact(evm, false, &json) == REVM_OK);
+    CHECK(json != NULL);
+    CHECK(strstr(json, "\"Success\"") != NULL);
+    CHECK(strstr(json, "\"present_value\":\"0x11\"") != NULL);
+    revm_string_free(json);
+
+    /* Without commit the same value is written again. */
+    CHECK(revm_evm_transact(evm, false, &json) == REVM_OK);
+    CHECK(strstr(json, "\"present_value\":\"0x11\"") != NULL);
+    revm_string_free(json);
+
+    /* Storage is cached by the handle. */
+    CHECK(db.storage_calls == 1);
+    revm_evm_free(evm);
+}
+
+static void test_commit(void) {
+    TestDb db = {0};
+    RevmEvm *evm = new_evm(&db);
+    char *json = NULL;
+
+    CHECK(revm_evm_set_env(evm, CALL_COUNTER) == REVM_OK);
+    CHECK(revm_evm_transact(evm, true, &json) == REVM_OK);
+    revm_string_free(json);
+    CHECK(revm_evm_transact(evm, true, &json) == REVM_OK);
+    CHECK(strstr(json, "\"present_value\":\"0x12\"") != NULL);
+    revm_string_free(json);
+    revm_evm_free(evm);
+}
+
+static void test_database_error(void) {
+    TestDb db = {0};
+    RevmEvm *evm = new_evm(&db);
+    char *json = NULL;
+
+    CHECK(revm_evm_set_env(
+              evm, "{\"tx\": {\"transact_to\": {\"Call\": "
+                   "\"0x0000000000000000000000000000000000001003\"}}}") == REVM_OK);
+    CHECK(revm_evm_transact(evm, false, &json) == REVM_ERR_DATABASE);
+    CHECK(json == NULL);
+    CHECK(revm_evm_last_error(evm) != NULL);
+    CHECK(strstr(revm_evm_last_error(evm), "42") != NULL);
+    revm_evm_free(evm);
+}
+
+static void test_invalid_transaction(void) {
+    TestDb db = {0};
+    RevmEvm *evm = new_evm(&db);
+    char *json = NULL;
+
+    /* Caller doesn't exist, so it can't pay for gas. */
+    CHECK(revm_evm_set_env(evm, CALL_COUNTER) == REVM_OK);
+    CHECK(revm_evm_set_env(evm, "{\"tx\": {\"gas_price\": \"0x1\"}}") == REVM_OK);
+    CHECK(revm_evm_transact(evm, false, &json) == REVM_ERR_TRANSACTION);
+    CHECK(json == NULL);
+    CHECK(revm_evm_last_error(evm) != NULL);
+    revm_evm_free(evm);
+}
+
+int main(void) {
+    test_version();
+    test_null_arguments();
+    test_invalid_env();
+    test_transact();
+    test_commit();
+    test_database_error();
+    test_invalid_transaction();
+
+    if (failures) {
+        fprintf(stderr, "%d checks failed\n", failures);
+        return EXIT_FAILURE;
+    }
+    printf("all checks passed\n");
+    return EXIT_SUCCESS;
+}
@@ -0,0 +1,73 @@
+//! Database implemented by a JavaScript object.
+//!
+//! The object is called synchronously from the interpreter and needs to provide:
+//!
+//! ```ts
+//! interface Database {
+//!   basic(address: string): AccountInfo | null | undefined
+//!   codeByHash(codeHash: string): Buffer
+//!   storage(address: string, slot: string): string
+//!   blockHash(number: string): string
+//! }
+//! ```
+//!
+//! An exception thrown by any of the callbacks aborts execution and is rethrown from `execute`.
+
+use crate::types::{self, hex_address, hex_hash, hex_u256};
+use napi::bindgen_prelude::{Buffer, FromNapiValue};
+use napi::{Env, JsFunction, JsObject, JsString, NapiRaw, Result};
+use revm::primitives::{AccountInfo, Bytecode, Bytes, B160, B256, U256};
+use revm::Database;
+
+pub struct JsDatabase {
+    env: Env,
+    object: JsObject,
+}
+
+impl JsDatabase {
+    pub fn new(env: Env, object: JsObject) -> Self {
+        Self { env, object }
+    }
+
+    fn call<T: FromNapiValue>(&self, method: &str, args: &[JsString]) -> Result<T> {
+        let function: JsFunction = self.object.get_named_property(method)?;
+        let ret = function.call(Some(&self.object), args)?;
+        // Safety: value was just returned from the call made on this env.
+        unsafe { T::from_napi_value(self.env.raw(), ret.raw()) }
+    }
+
+    fn string(&self, value: String) -> Result<JsString> {
+        self.env.create_string_from_std(value)
+    }
+}
+
+impl Database for JsDatabase {
+    type Error = napi::Error;
+
+    fn basic(&mut self, address: B160) -> Result<Option<AccountInfo>> {
+        let info: Option<types::AccountInfo> =
+            self.call("basic", &[self.string(hex_address(address))?])?;
+        info.map(TryFrom::try_from).transpose()
+    }
+
+    fn code_by_hash(&mut self, code_hash: B256) -> Result<Bytecode> {
+        let code: Buffer = self.call("codeByHash", &[self.string(hex_hash(code_hash))?])?;
+        Ok(Bytecode::new_raw(Bytes::from_owner(code)))
+    }
+
+    fn storage(&mut self, address: B160, index: U256) -> Result<U256> {
+        let value: String = self.call(
+            "storage",
+            &[
+                self.string(hex_address(address))?,
+                self.string(hex_u256(index))?,
+            ],
+        )?;
+        types::parse(&value)
+    }
+
+    fn block_hash(&mut self, number: U256) -> Result<B256> {
+        let hash: String = self.call("blockHash", &[self.string(hex_u256(number))?])?;
+        types::parse(&hash)
+    }
+}
@@ -0,0 +1,222 @@
+//! Node.js bindings for revm.
+//!
+//! [`execute`] runs a single transaction against state served by a JavaScript
+//! [database](database) and returns the result without committing it anywhere.
+//! Calldata and code are borrowed from the passed `Buffer`s for the duration of the call,
+//! return data and log data are handed to JavaScript as `Buffer`s backed by the allocation
+//! made by the interpreter.
+
+mod database;
+mod types;
+
+use database::JsDatabase;
+use napi::{Env, Error, JsObject, Result};
+use napi_derive::napi;
+use revm::inspectors::TracerEip3155;
+use revm::primitives::{
+    Bytes, CreateScheme, EVMError, Env as EvmEnv, ExecutionResult as EvmResult, Output, SpecId,
+    TransactTo, U256,
+};
+use std::cell::RefCell;
+use std::io::Write;
+use std::rc::Rc;
+use types::{
+    buffer, hex_address, hex_hash, hex_u256, parse, parse_opt, u64, AccountChange, Block,
+    ExecuteOptions, ExecutionResult, Log, StorageChange, Transaction,
+};
+
+/// Executes transaction and returns its result and changed state.
+#[napi]
+pub fn execute(
+    env: Env,
+    database: JsObject,
+    tx: Transaction,
+    block: Option<Block>,
+    options: Option<ExecuteOptions>,
+) -> Result<ExecutionResult> {
+    let options = options.unwrap_or(ExecuteOptions {
+        spec: None,
+        chain_id: None,
+        tracer: None,
+        skip_state: None,
+    });
+
+    let mut evm = revm::new();
+    evm.env = build_env(tx, block, &options)?;
+    evm.database(JsDatabase::new(env, database));
+
+    let trace = TraceBuffer::default();
+    let result = match options.tracer.as_deref().unwrap_or("none") {
+        "none" => evm.transact(),
+        "eip3155" => evm.inspect(TracerEip3155::new(Box::new(trace.clone()), false, false)),
+        tracer => return Err(Error::from_reason(format!("unknown tracer: {tracer}"))),
+    }
+    .map_err(|err| match err {
+        EVMError::Database(err) => err,
+        err => Error::from_reason(format!("{err:?}")),
+    })?;
+
+    let state = if options.skip_state.unwrap_or_default() {
+        Vec::new()
+    } else {
+        result
+            .state
+            .into_iter()
+            .filter(|(_, account)| account.is_touched())
+            .map(|(address, account)| AccountChange {
+                address: hex_address(address),
+                balance: hex_u256(account.info.balance),
+                nonce: account.info.nonce as i64,
+                code_hash: hex_hash(account.info.code_hash),
+                selfdestructed: account.is_selfdestructed(),
+                code: match account.is_newly_created() {
+                    true => account.info.code.map(|code| buffer(code.original_bytes())),
+                    false => None,
+                },
+                storage: account
+                    .storage
+                    .into_iter()
+                    .filter(|(_, slot)| slot.is_changed())
+                    .map(|(slot, value)| StorageChange {
+                        slot: hex_u256(slot),
+                        original_value: hex_u256(value.original_value),
+                        present_value: hex_u256(value.present_value),
+                    })
+                    .collect(),
+            })
+            .collect()
+    };
+    let trace = (options.tracer.as_deref() == Some("eip3155")).then(|| trace.into_string());
+
+    let mut output = ExecutionResult {
+        status: String::new(),
+        reason: None,
+        gas_used: 0,
+        gas_refunded: 0,
+        output: buffer(Bytes::new()),
+        created_address: None,
+        logs: Vec::new(),
+        state,
+        trace,
+    };
+    match result.result {
+        EvmResult::Success {
+            reason,
+            gas_used,
+            gas_refunded,
+            logs,
+            output: out,
+        } => {
+            output.status = "success".into();
+            output.reason = Some(format!("{reason:?}"));
+            output.gas_used = gas_used as i64;
+            output.gas_refunded = gas_refunded as i64;
+            output.logs = logs
+                .into_iter()
+                .map(|log| Log {
+                    address: hex_address(log.address),
+                    topics: log.topics.into_iter().map(hex_hash).collect(),
+                    data: buffer(log.data),
+                })
+                .collect();
+            match out {
+                Output::Call(data) => output.output = buffer(data),
+                Output::Create(data, address) => {
+                    output.output = buffer(data);
+                    output.created_address = address.map(hex_address);
+                }
+            }
+        }
+        EvmResult::Revert {
+            gas_used,
+            output: out,
+        } => {
+            output.status = "revert".into();
+            output.gas_used = gas_used as i64;
+            output.output = buffer(out);
+        }
+        EvmResult::Halt { reason, gas_used } => {
+            output.status = "halt".into();
+            output.reason = Some(format!("{reason:?}"));
+            output.gas_used = gas_used as i64;
+        }
+    }
+    Ok(output)
+}
+
+fn build_env(tx: Transaction, block: Option<Block>, options: &ExecuteOptions) -> Result<EvmEnv> {
+    let mut env = EvmEnv::default();
+    if let Some(spec) = &options.spec {
+        env.cfg.spec_id = SpecId::from(spec.as_str());
+    }
+    if let Some(chain_id) = options.chain_id {
+        let chain_id = u64(chain_id, "chain id")?;
+        env.cfg.chain_id = U256::from(chain_id);
+        env.tx.chain_id = Some(chain_id);
+    }
+
+    if let Some(block) = block {
+        if let Some(number) = parse_opt(block.number)? {
+            env.block.number = number;
+        }
+        if let Some(coinbase) = parse_opt(block.coinbase)? {
+            env.block.coinbase = coinbase;
+        }
+        if let Some(timestamp) = parse_opt(block.timestamp)? {
+            env.block.timestamp = timestamp;
+        }
+        if let Some(gas_limit) = parse_opt(block.gas_limit)? {
+            env.block.gas_limit = gas_limit;
+        }
+        if let Some(basefee) = parse_opt(block.basefee)? {
+            env.block.basefee = basefee;
+        }
+        if let Some(difficulty) = parse_opt(block.difficulty)? {
+            env.block.difficulty = difficulty;
+        }
+        env.block.prevrandao = parse_opt(block.prevrandao)?.or(env.block.prevrandao);
+    }
+
+    env.tx.caller = parse(&tx.caller)?;
+    env.tx.transact_to = match tx.to {
+        Some(to) => TransactTo::Call(parse(&to)?),
+        None => TransactTo::Create(CreateScheme::Create),
+    };
+    if let Some(value) = parse_opt(tx.value)? {
+        env.tx.value = value;
+    }
+    if let Some(data) = tx.data {
+        // bytes hold the reference to the JavaScript buffer, calldata is not copied.
+        env.tx.data = Bytes::from_owner(data);
+    }
+    if let Some(gas_limit) = tx.gas_limit {
+        env.tx.gas_limit = u64(gas_limit, "gas limit")?;
+    }
+    if let Some(gas_price) = parse_opt(tx.gas_price)? {
+        env.tx.gas_price = gas_price;
+    }
+    env.tx.gas_priority_fee = parse_opt(tx.gas_priority_fee)?;
+    env.tx.nonce = tx.nonce.map(|nonce| u64(nonce, "nonce")).transpose()?;
+    Ok(env)
+}
+
+/// Shared writer collecting EIP-3155 trace lines.
+#[derive(Clone, Default)]
+struct TraceBuffer(Rc<RefCell<Vec<u8>>>);
+
+impl TraceBuffer {
+    fn into_string(self) -> String {
+        String::from_utf8_lossy(&self.0.borrow()).into_owned()
+    }
+}
+
+impl Write for TraceBuffer {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
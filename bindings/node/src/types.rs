@@ -0,0 +1,160 @@
+//! Objects exchanged with JavaScript.
+//!
+//! Addresses, hashes and 256-bit integers are passed as strings. Integers accept both
+//! decimal and `0x` prefixed hex, everything returned to JavaScript is `0x` prefixed hex.
+
+use core::str::FromStr;
+use napi::bindgen_prelude::Buffer;
+use napi::{Error, Result};
+use napi_derive::napi;
+use revm::primitives::{keccak256, Bytecode, Bytes, B160, B256, KECCAK_EMPTY, U256};
+
+/// Account returned from `Database.basic`.
+#[napi(object)]
+pub struct AccountInfo {
+    pub balance: String,
+    pub nonce: i64,
+    /// Hash of the account code. Computed from `code` if omitted.
+    pub code_hash: Option<String>,
+    /// Account code. If omitted `Database.codeByHash` is called when the code is needed.
+    pub code: Option<Buffer>,
+}
+
+impl TryFrom<AccountInfo> for revm::primitives::AccountInfo {
+    type Error = Error;
+
+    fn try_from(info: AccountInfo) -> Result<Self> {
+        let code = info
+            .code
+            .map(|code| Bytecode::new_raw(Bytes::from_owner(code)));
+        let code_hash = match (info.code_hash, &code) {
+            (Some(hash), _) => parse(&hash)?,
+            (None, Some(code)) if !code.is_empty() => keccak256(code.original_bytes().as_ref()),
+            (None, _) => KECCAK_EMPTY,
+        };
+        Ok(Self {
+            balance: parse(&info.balance)?,
+            nonce: u64(info.nonce, "nonce")?,
+            code_hash,
+            code,
+        })
+    }
+}
+
+/// Transaction to execute.
+#[napi(object)]
+pub struct Transaction {
+    pub caller: String,
+    /// Call target, contract creation if omitted.
+    pub to: Option<String>,
+    pub value: Option<String>,
+    /// Calldata, or init code for contract creation.
+    pub data: Option<Buffer>,
+    pub gas_limit: Option<i64>,
+    pub gas_price: Option<String>,
+    pub gas_priority_fee: Option<String>,
+    /// Nonce is checked against the caller account only if set.
+    pub nonce: Option<i64>,
+}
+
+/// Block the transaction is executed in.
+#[napi(object)]
+pub struct Block {
+    pub number: Option<String>,
+    pub coinbase: Option<String>,
+    pub timestamp: Option<String>,
+    pub gas_limit: Option<String>,
+    pub basefee: Option<String>,
+    pub difficulty: Option<String>,
+    pub prevrandao: Option<String>,
+}
+
+/// Execution options.
+#[napi(object)]
+pub struct ExecuteOptions {
+    /// Hardfork name as used by the ethereum tests, `Shanghai` by default.
+    pub spec: Option<String>,
+    pub chain_id: Option<i64>,
+    /// `none` (default) or `eip3155`.
+    pub tracer: Option<String>,
+    /// Only compute the result, skip returning changed state.
+    pub skip_state: Option<bool>,
+}
+
+#[napi(object)]
+pub struct Log {
+    pub address: String,
+    pub topics: Vec<String>,
+    pub data: Buffer,
+}
+
+#[napi(object)]
+pub struct StorageChange {
+    pub slot: String,
+    pub original_value: String,
+    pub present_value: String,
+}
+
+/// Account touched by the transaction.
+#[napi(object)]
+pub struct AccountChange {
+    pub address: String,
+    pub balance: String,
+    pub nonce: i64,
+    pub code_hash: String,
+    /// Code of newly created contracts.
+    pub code: Option<Buffer>,
+    pub selfdestructed: bool,
+    pub storage: Vec<StorageChange>,
+}
+
+/// Outcome of [`execute`](crate::execute).
+#[napi(object)]
+pub struct ExecutionResult {
+    /// `success`, `revert` or `halt`.
+    pub status: String,
+    /// Success or halt reason.
+    pub reason: Option<String>,
+    pub gas_used: i64,
+    pub gas_refunded: i64,
+    /// Return data, or revert data if reverted.
+    pub output: Buffer,
+    pub created_address: Option<String>,
+    pub logs: Vec<Log>,
+    pub state: Vec<AccountChange>,
+    /// Newline delimited EIP-3155 trace if `eip3155` tracer was selected.
+    pub trace: Option<String>,
+}
+
+/// Parses address, hash or integer from JavaScript string.
+pub fn parse<T: FromStr>(value: &str) -> Result<T> {
+    T::from_str(value).map_err(|_| Error::from_reason(format!("invalid value: {value}")))
+}
+
+pub fn parse_opt<T: FromStr>(value: Option<String>) -> Result<Option<T>> {
+    value.as_deref().map(parse).transpose()
+}
+
+pub fn u64(value: i64, name: &str) -> Result<u64> {
+    u64::try_from(value).map_err(|_| Error::from_reason(format!("negative {name}: {value}")))
+}
+
+pub fn hex_address(address: B160) -> String {
+    format!("{address:#x}")
+}
+
+pub fn hex_hash(hash: B256) -> String {
+    format!("{hash:#x}")
+}
+
+pub fn hex_u256(value: U256) -> String {
+    format!("{value:#x}")
+}
+
+/// Moves bytes into a JavaScript buffer.
+///
+/// Allocation is handed over to JavaScript without copying if the bytes are its only
+/// owner, as return data and log data made by the interpreter are. Shared bytes are copied.
+pub fn buffer(bytes: Bytes) -> Buffer {
+    Buffer::from(Vec::from(bytes))
+}
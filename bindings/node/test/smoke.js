@@ -0,0 +1,44 @@
+// Smoke test of the built bindings, run with `npm test` after `npm run build`.
+const assert = require('assert')
+const { execute } = require('..')
+
+const CALLER = '0x1000000000000000000000000000000000000000'
+const CONTRACT = '0x2000000000000000000000000000000000000000'
+// CALLDATACOPY(0, 0, CALLDATASIZE) RETURN(0, CALLDATASIZE): returns its calldata.
+const ECHO = Buffer.from('366000600037366000f3', 'hex')
+
+const db = {
+  basic: (address) => {
+    switch (address) {
+      case CALLER:
+        return { balance: '0xde0b6b3a7640000', nonce: 0 }
+      case CONTRACT:
+        return { balance: '0x0', nonce: 1, code: ECHO }
+      default:
+        return null
+    }
+  },
+  codeByHash: () => {
+    throw new Error('code is returned with the account')
+  },
+  storage: () => '0x0',
+  blockHash: () => '0x' + '00'.repeat(32),
+}
+
+const data = Buffer.from('a9059cbb0102', 'hex')
+const tx = { caller: CALLER, to: CONTRACT, data, gasLimit: 100000 }
+
+const result = execute(db, tx, undefined, { tracer: 'eip3155' })
+assert.strictEqual(result.status, 'success')
+assert.ok(result.output.equals(data))
+assert.ok(result.gasUsed > 21000)
+assert.ok(result.trace.length > 0)
+const caller = result.state.find((account) => account.address === CALLER)
+assert.strictEqual(caller.nonce, 1)
+
+// exceptions of the database are rethrown.
+const failing = { ...db, basic: () => { throw new Error('database failed') } }
+assert.throws(() => execute(failing, tx), /database failed/)
+assert.throws(() => execute(db, tx, undefined, { tracer: 'call' }), /unknown tracer/)
+
+console.log('ok')
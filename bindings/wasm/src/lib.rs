@@ -0,0 +1,149 @@
+//! WebAssembly bindings for revm.
+//!
+//! [execute] runs a single transaction on top of the state passed with it and returns
+//! the result and the accounts it changed, nothing is kept between calls. Objects are
+//! exchanged as JSON compatible values, see [types].
+
+pub mod types;
+
+use revm::primitives::{AccountInfo, Bytecode, CreateScheme, Env, SpecId, TransactTo, U256};
+use revm::InMemoryDB;
+use serde::de::DeserializeOwned;
+use types::{Block, ExecuteOptions, ExecutionResult, State, Transaction};
+use wasm_bindgen::prelude::*;
+
+/// Executes transaction `tx` in `block` on top of `state` and returns its result.
+///
+/// `block`, `state` and `options` can be omitted.
+#[wasm_bindgen]
+pub fn execute(
+    tx: JsValue,
+    block: JsValue,
+    state: JsValue,
+    options: JsValue,
+) -> Result<JsValue, JsError> {
+    let result = run(
+        from_js(&tx)?,
+        from_js(&block)?,
+        from_js(&state)?,
+        from_js(&options)?,
+    )
+    .map_err(|err| JsError::new(&err))?;
+    let json = serde_json::to_string(&result)?;
+    js_sys::JSON::parse(&json).map_err(|_| JsError::new("invalid result"))
+}
+
+/// Executes transaction `tx` in `block` on top of `state`.
+pub fn run(
+    tx: Transaction,
+    block: Option<Block>,
+    state: Option<State>,
+    options: Option<ExecuteOptions>,
+) -> Result<ExecutionResult, String> {
+    let mut db = InMemoryDB::default();
+    for (address, account) in state.unwrap_or_default() {
+        let code = Bytecode::new_raw(account.code);
+        let info = AccountInfo::new(account.balance, account.nonce, code);
+        db.insert_account_info(address, info);
+        for (index, value) in account.storage {
+            // empty database can't fail.
+            let _ = db.insert_account_storage(address, index, value);
+        }
+    }
+
+    let mut evm = revm::new();
+    evm.env = build_env(tx, block.unwrap_or_default(), options.unwrap_or_default());
+    evm.database(db);
+    let result = evm.transact().map_err(|err| format!("{err:?}"))?;
+    Ok(result.into())
+}
+
+fn build_env(tx: Transaction, block: Block, options: ExecuteOptions) -> Env {
+    let mut env = Env::default();
+    if let Some(spec) = &options.spec {
+        env.cfg.spec_id = SpecId::from(spec.as_str());
+    }
+    if let Some(chain_id) = options.chain_id {
+        env.cfg.chain_id = U256::from(chain_id);
+        env.tx.chain_id = Some(chain_id);
+    }
+
+    if let Some(number) = block.number {
+        env.block.number = number;
+    }
+    if let Some(coinbase) = block.coinbase {
+        env.block.coinbase = coinbase;
+    }
+    if let Some(timestamp) = block.timestamp {
+        env.block.timestamp = timestamp;
+    }
+    if let Some(gas_limit) = block.gas_limit {
+        env.block.gas_limit = gas_limit;
+    }
+    if let Some(basefee) = block.basefee {
+        env.block.basefee = basefee;
+    }
+    if let Some(difficulty) = block.difficulty {
+        env.block.difficulty = difficulty;
+    }
+    env.block.prevrandao = block.prevrandao.or(env.block.prevrandao);
+
+    env.tx.caller = tx.caller;
+    env.tx.transact_to = match tx.to {
+        Some(to) => TransactTo::Call(to),
+        None => TransactTo::Create(CreateScheme::Create),
+    };
+    env.tx.value = tx.value;
+    env.tx.data = tx.data;
+    if let Some(gas_limit) = tx.gas_limit {
+        env.tx.gas_limit = gas_limit;
+    }
+    env.tx.gas_price = tx.gas_price;
+    env.tx.gas_priority_fee = tx.gas_priority_fee;
+    env.tx.nonce = tx.nonce;
+    env
+}
+
+/// Value passed from JavaScript, `undefined` is read as `null`.
+fn from_js<T: DeserializeOwned>(value: &JsValue) -> Result<T, JsError> {
+    let json = js_sys::JSON::stringify(value).map_err(|_| JsError::new("invalid value"))?;
+    let json = json.as_string().unwrap_or_else(|| "null".into());
+    Ok(serde_json::from_str(&json)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn runs_transaction_on_given_state() {
+        // SSTORE(0, CALLVALUE) MSTORE(0, SLOAD(1)) RETURN(0, 32)
+        let state: State = serde_json::from_str(
+            r#"{
+                "0x1000000000000000000000000000000000000000": { "balance": "1000000" },
+                "0x2000000000000000000000000000000000000000": {
+                    "code": "0x3460005560015460005260206000f3",
+                    "storage": { "0x1": "0x2a" }
+                }
+            }"#,
+        )
+        .unwrap();
+        let tx: Transaction = serde_json::from_str(
+            r#"{
+                "caller": "0x1000000000000000000000000000000000000000",
+                "to": "0x2000000000000000000000000000000000000000",
+                "value": "7",
+                "gasLimit": 100000
+            }"#,
+        )
+        .unwrap();
+
+        let result = run(tx, None, Some(state), None).unwrap();
+        let json = serde_json::to_value(&result).unwrap();
+        assert_eq!(json["status"], "success");
+        assert_eq!(json["output"], format!("0x{:064x}", 0x2a));
+        let contract = &json["state"]["0x2000000000000000000000000000000000000000"];
+        assert_eq!(contract["storage"]["0x0"], "0x7");
+        assert_eq!(contract["balance"], "0x7");
+    }
+}
@@ -0,0 +1,185 @@
+//! Objects exchanged with JavaScript.
+//!
+//! Addresses, hashes, 256-bit integers and bytes are passed as strings, integers accept
+//! both decimal and `0x` prefixed hex. Everything returned to JavaScript is `0x` prefixed
+//! hex, except gas which is a number.
+
+use revm::primitives::{
+    hex, utilities::serde_hex_bytes, Bytes, ExecutionResult as EvmResult, Output, ResultAndState,
+    B160, B256, U256,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Transaction to execute.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Transaction {
+    pub caller: B160,
+    /// Call target, contract creation if omitted.
+    pub to: Option<B160>,
+    #[serde(default)]
+    pub value: U256,
+    /// Calldata, or init code for contract creation.
+    #[serde(default, with = "serde_hex_bytes")]
+    pub data: Bytes,
+    pub gas_limit: Option<u64>,
+    #[serde(default)]
+    pub gas_price: U256,
+    pub gas_priority_fee: Option<U256>,
+    /// Nonce is checked against the caller account only if set.
+    pub nonce: Option<u64>,
+}
+
+/// Block the transaction is executed in.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Block {
+    pub number: Option<U256>,
+    pub coinbase: Option<B160>,
+    pub timestamp: Option<U256>,
+    pub gas_limit: Option<U256>,
+    pub basefee: Option<U256>,
+    pub difficulty: Option<U256>,
+    pub prevrandao: Option<B256>,
+}
+
+/// Execution options.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ExecuteOptions {
+    /// Hardfork name as used by the ethereum tests, `Shanghai` by default.
+    pub spec: Option<String>,
+    pub chain_id: Option<u64>,
+}
+
+/// Account of the state the transaction executes on, accounts that are not given are
+/// empty.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Account {
+    pub balance: U256,
+    pub nonce: u64,
+    #[serde(with = "serde_hex_bytes")]
+    pub code: Bytes,
+    pub storage: BTreeMap<U256, U256>,
+}
+
+/// State by account address.
+pub type State = BTreeMap<B160, Account>;
+
+/// Result of the transaction and accounts it changed.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExecutionResult {
+    /// `success`, `revert` or `halt`.
+    pub status: &'static str,
+    /// Success or halt reason.
+    pub reason: Option<String>,
+    pub gas_used: u64,
+    pub gas_refunded: u64,
+    #[serde(with = "serde_hex_bytes")]
+    pub output: Bytes,
+    pub created_address: Option<B160>,
+    pub logs: Vec<Log>,
+    pub state: BTreeMap<B160, AccountChange>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Log {
+    pub address: B160,
+    pub topics: Vec<B256>,
+    #[serde(with = "serde_hex_bytes")]
+    pub data: Bytes,
+}
+
+/// Account touched by the transaction.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccountChange {
+    pub balance: U256,
+    pub nonce: u64,
+    pub code_hash: B256,
+    /// Code of created contracts.
+    pub code: Option<String>,
+    pub selfdestructed: bool,
+    /// New values of changed storage slots.
+    pub storage: BTreeMap<U256, U256>,
+}
+
+impl From<ResultAndState> for ExecutionResult {
+    fn from(ResultAndState { result, state, .. }: ResultAndState) -> Self {
+        let state = state
+            .into_iter()
+            .filter(|(_, account)| account.is_touched())
+            .map(|(address, account)| {
+                let code = match account.is_newly_created() {
+                    true => account.info.code.as_ref(),
+                    false => None,
+                };
+                let change = AccountChange {
+                    balance: account.info.balance,
+                    nonce: account.info.nonce,
+                    code_hash: account.info.code_hash,
+                    code: code.map(|code| format!("0x{}", hex::encode(code.original_bytes()))),
+                    selfdestructed: account.is_selfdestructed(),
+                    storage: account
+                        .storage
+                        .into_iter()
+                        .filter(|(_, slot)| slot.is_changed())
+                        .map(|(index, slot)| (index, slot.present_value))
+                        .collect(),
+                };
+                (address, change)
+            })
+            .collect();
+
+        let mut output = ExecutionResult {
+            status: "",
+            reason: None,
+            gas_used: result.gas_used(),
+            gas_refunded: 0,
+            output: Bytes::new(),
+            created_address: None,
+            logs: Vec::new(),
+            state,
+        };
+        match result {
+            EvmResult::Success {
+                reason,
+                gas_refunded,
+                logs,
+                output: out,
+                ..
+            } => {
+                output.status = "success";
+                output.reason = Some(format!("{reason:?}"));
+                output.gas_refunded = gas_refunded;
+                output.logs = logs
+                    .into_iter()
+                    .map(|log| Log {
+                        address: log.address,
+                        topics: log.topics,
+                        data: log.data,
+                    })
+                    .collect();
+                match out {
+                    Output::Call(data) => output.output = data,
+                    Output::Create(data, address) => {
+                        output.output = data;
+                        output.created_address = address;
+                    }
+                }
+            }
+            EvmResult::Revert { output: out, .. } => {
+                output.status = "revert";
+                output.output = out;
+            }
+            EvmResult::Halt { reason, .. } => {
+                output.status = "halt";
+                output.reason = Some(format!("{reason:?}"));
+            }
+        }
+        output
+    }
+}
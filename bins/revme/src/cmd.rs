@@ -1,4 +1,4 @@
-use crate::{runner, statetest};
+use crate::{replay, runner, statetest};
 use structopt::{clap::AppSettings, StructOpt};
 
 #[derive(StructOpt, Debug)]
@@ -7,14 +7,18 @@ use structopt::{clap::AppSettings, StructOpt};
 pub enum MainCmd {
     Statetest(statetest::Cmd),
     Run(runner::Cmd),
+    Replay(replay::Cmd),
 }
 
 use thiserror::Error as ThisError;
 
 #[derive(Debug, ThisError)]
+#[allow(clippy::enum_variant_names)]
 pub enum Error {
     #[error("Statetest: {0}")]
     Statetest(statetest::Error),
+    #[error("Replay: {0}")]
+    Replay(replay::Error),
     #[error("Generic system error")]
     SystemError,
 }
@@ -23,6 +27,7 @@ impl MainCmd {
     pub fn run(&self) -> Result<(), Error> {
         match self {
             Self::Statetest(cmd) => cmd.run().map_err(Error::Statetest),
+            Self::Replay(cmd) => cmd.run().map_err(Error::Replay),
             _ => Ok(()),
         }
     }
@@ -1,8 +1,9 @@
 mod cmd;
 mod exec;
+mod replay;
 mod runner;
-mod statetest;
 use cmd::Error;
+use revme::statetest;
 use structopt::StructOpt;
 mod cli_env;
 
@@ -0,0 +1,57 @@
+use revm::replay::ReplayBundle;
+use std::path::PathBuf;
+use structopt::StructOpt;
+use thiserror::Error;
+
+/// Execute replay bundles and check they have the expected outcome.
+#[derive(StructOpt, Debug)]
+pub struct Cmd {
+    #[structopt(required = true)]
+    path: Vec<PathBuf>,
+}
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("{path:?}: {error}")]
+    Io {
+        path: PathBuf,
+        error: std::io::Error,
+    },
+    #[error("{path:?}: {error}")]
+    Json {
+        path: PathBuf,
+        error: serde_json::Error,
+    },
+    #[error("{path:?}: expected {expected}, got {actual}")]
+    Mismatch {
+        path: PathBuf,
+        expected: String,
+        actual: String,
+    },
+}
+
+impl Cmd {
+    pub fn run(&self) -> Result<(), Error> {
+        for path in &self.path {
+            let json = std::fs::read_to_string(path).map_err(|error| Error::Io {
+                path: path.clone(),
+                error,
+            })?;
+            let bundle: ReplayBundle =
+                serde_json::from_str(&json).map_err(|error| Error::Json {
+                    path: path.clone(),
+                    error,
+                })?;
+            if let Err(mismatch) = bundle.check() {
+                let to_json = |expected| serde_json::to_string(expected).unwrap_or_default();
+                return Err(Error::Mismatch {
+                    path: path.clone(),
+                    expected: to_json(&mismatch.expected),
+                    actual: to_json(&mismatch.actual),
+                });
+            }
+            println!("{path:?}: ok");
+        }
+        Ok(())
+    }
+}
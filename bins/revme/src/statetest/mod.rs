@@ -2,6 +2,7 @@ mod cmd;
 pub mod merkle_trie;
 pub mod models;
 mod runner;
+pub mod suite;
 
 pub use cmd::Cmd;
 pub use runner::TestError as Error;
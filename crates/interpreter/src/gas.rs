@@ -5,6 +5,7 @@ pub use calc::*;
 pub use constants::*;
 
 #[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Gas {
     /// Gas Limit
     limit: u64,
@@ -16,6 +17,8 @@ pub struct Gas {
     memory: u64,
     /// Refunded gas. This gas is used only at the end of execution.
     refunded: i64,
+    /// If the frame is not limited by gas it is given, see [Gas::new_unmetered].
+    unmetered: bool,
 }
 impl Gas {
     pub fn new(limit: u64) -> Self {
@@ -25,9 +28,24 @@ impl Gas {
             memory: 0,
             refunded: 0,
             all_used_gas: 0,
+            unmetered: false,
         }
     }
 
+    /// Gas of a frame that is executed without gas charging. Spend is recorded as usual but
+    /// `cap` only stops runaway execution: calls and creates of the frame are given all of its
+    /// remaining gas, whatever gas they ask for, so they don't run out of gas before the cap.
+    pub fn new_unmetered(cap: u64) -> Self {
+        Self {
+            unmetered: true,
+            ..Self::new(cap)
+        }
+    }
+
+    pub fn is_unmetered(&self) -> bool {
+        self.unmetered
+    }
+
     pub fn limit(&self) -> u64 {
         self.limit
     }
@@ -61,7 +61,7 @@ pub fn create2_cost(len: usize) -> Option<u64> {
     let base = CREATE;
     // ceil(len / 32.0)
     let len = len as u64;
-    let sha_addup_base = (len / 32) + u64::from((len % 32) != 0);
+    let sha_addup_base = len.div_ceil(32);
     let sha_addup = KECCAK256WORD.checked_mul(sha_addup_base)?;
     let gas = base.checked_add(sha_addup)?;
 
@@ -231,9 +231,10 @@ pub fn sstore_cost<SPEC: Spec>(
     }
 }
 
-pub fn selfdestruct_cost<SPEC: Spec>(res: SelfDestructResult) -> u64 {
+/// `state_clear` is true if EIP-161 applies, as it does from Spurious Dragon on mainnet.
+pub fn selfdestruct_cost<SPEC: Spec>(res: SelfDestructResult, state_clear: bool) -> u64 {
     // EIP-161: State trie clearing (invariant-preserving alternative)
-    let should_charge_topup = if SPEC::enabled(SPURIOUS_DRAGON) {
+    let should_charge_topup = if state_clear {
         res.had_value && !res.target_exists
     } else {
         !res.target_exists
@@ -255,12 +256,14 @@ pub fn selfdestruct_cost<SPEC: Spec>(res: SelfDestructResult) -> u64 {
     gas
 }
 
+/// `state_clear` is true if EIP-161 applies, as it does from Spurious Dragon on mainnet.
 pub fn call_cost<SPEC: Spec>(
     value: U256,
     is_new: bool,
     is_cold: bool,
     is_call_or_callcode: bool,
     is_call_or_staticcall: bool,
+    state_clear: bool,
 ) -> u64 {
     let transfers_value = value != U256::default();
 
@@ -279,7 +282,7 @@ pub fn call_cost<SPEC: Spec>(
 
     call_gas
         + xfer_cost(is_call_or_callcode, transfers_value)
-        + new_cost::<SPEC>(is_call_or_staticcall, is_new, transfers_value)
+        + new_cost(is_call_or_staticcall, is_new, transfers_value, state_clear)
 }
 
 pub fn hot_cold_cost<SPEC: Spec>(is_cold: bool, regular_value: u64) -> u64 {
@@ -302,10 +305,15 @@ fn xfer_cost(is_call_or_callcode: bool, transfers_value: bool) -> u64 {
     }
 }
 
-fn new_cost<SPEC: Spec>(is_call_or_staticcall: bool, is_new: bool, transfers_value: bool) -> u64 {
+fn new_cost(
+    is_call_or_staticcall: bool,
+    is_new: bool,
+    transfers_value: bool,
+    state_clear: bool,
+) -> u64 {
     if is_call_or_staticcall {
         // EIP-161: State trie clearing (invariant-preserving alternative)
-        if SPEC::enabled(SPURIOUS_DRAGON) {
+        if state_clear {
             if transfers_value && is_new {
                 NEWACCOUNT
             } else {
@@ -334,6 +342,7 @@ pub fn initial_tx_gas<SPEC: Spec>(
     input: &Bytes,
     is_create: bool,
     access_list: &[(B160, Vec<U256>)],
+    authorization_list_len: u64,
 ) -> u64 {
     let mut initial_gas = 0;
     let zero_data_len = input.iter().filter(|v| **v == 0).count() as u64;
@@ -353,6 +362,11 @@ pub fn initial_tx_gas<SPEC: Spec>(
         initial_gas += accessed_slots * ACCESS_LIST_STORAGE_KEY;
     }
 
+    // EIP-7702: Set EOA account code
+    if SPEC::enabled(PRAGUE) {
+        initial_gas += authorization_list_len * PER_EMPTY_ACCOUNT_COST;
+    }
+
     // base stipend
     initial_gas += if is_create {
         if SPEC::enabled(HOMESTEAD) {
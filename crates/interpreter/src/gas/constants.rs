@@ -31,6 +31,11 @@ pub const TRANSACTION_NON_ZERO_DATA_FRONTIER: u64 = 68;
 // berlin eip2929 constants
 pub const ACCESS_LIST_ADDRESS: u64 = 2400;
 pub const ACCESS_LIST_STORAGE_KEY: u64 = 1900;
+
+/// EIP-7702: Intrinsic cost of every authorization of a set code transaction.
+pub const PER_EMPTY_ACCOUNT_COST: u64 = 25000;
+/// EIP-7702: Cost of an authorization of an existing account, the rest is refunded.
+pub const PER_AUTH_BASE_COST: u64 = 12500;
 pub const COLD_SLOAD_COST: u64 = 2100;
 pub const COLD_ACCOUNT_ACCESS_COST: u64 = 2600;
 pub const WARM_STORAGE_READ_COST: u64 = 100;
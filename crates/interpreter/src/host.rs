@@ -2,7 +2,7 @@ mod dummy_host;
 
 use crate::primitives::Bytecode;
 use crate::{
-    primitives::{Bytes, Env, B160, B256, U256},
+    primitives::{Bytes, Env, EnvValue, B160, B256, U256},
     CallInputs, CreateInputs, Gas, InstructionResult, Interpreter, SelfDestructResult,
 };
 pub use alloc::vec::Vec;
@@ -18,10 +18,14 @@ pub trait Host {
     ) -> InstructionResult;
 
     fn env(&mut self) -> &mut Env;
+    /// Value environment opcode of `value` returns in the frame executing code of `address`
+    /// called by `caller`, None for the value of [Env].
+    fn env_override(&mut self, value: EnvValue, address: B160, caller: B160) -> Option<U256>;
 
     /// load account. Returns (is_cold,is_new_account)
     fn load_account(&mut self, address: B160) -> Option<(bool, bool)>;
-    /// Get environmental block hash.
+    /// Get hash of block `number`, it is less than number of the current block. Zero if
+    /// the block is too old to be available.
     fn block_hash(&mut self, number: U256) -> Option<B256>;
     /// Get balance of address and if account is cold loaded.
     fn balance(&mut self, address: B160) -> Option<(U256, bool)>;
@@ -29,6 +33,9 @@ pub trait Host {
     fn code(&mut self, address: B160) -> Option<(Bytecode, bool)>;
     /// Get code hash of address and if account is cold loaded.
     fn code_hash(&mut self, address: B160) -> Option<(B256, bool)>;
+    /// EIP-7702: Address the code of `address` delegates to and if it is cold loaded,
+    /// `Some(None)` if code is not a delegation.
+    fn delegation(&mut self, address: B160) -> Option<Option<(B160, bool)>>;
     /// Get storage value of address at index and if account is cold loaded.
     fn sload(&mut self, address: B160, index: U256) -> Option<(U256, bool)>;
     /// Set storage value of account address at index.
@@ -39,8 +46,14 @@ pub trait Host {
         index: U256,
         value: U256,
     ) -> Option<(U256, U256, U256, bool)>;
+    /// Get transient storage value of address at index.
+    fn tload(&mut self, address: B160, index: U256) -> U256;
+    /// Set transient storage value of address at index.
+    fn tstore(&mut self, address: B160, index: U256, value: U256);
     /// Create a log owned by address with given topics and data.
     fn log(&mut self, address: B160, topics: Vec<B256>, data: Bytes);
+    /// Total size of data of logs created in the transaction, reverted ones included.
+    fn log_data_size(&mut self) -> usize;
     /// Mark an address to be deleted, with funds transferred to target.
     fn selfdestruct(&mut self, address: B160, target: B160) -> Option<SelfDestructResult>;
     /// Invoke a create operation.
@@ -1,6 +1,6 @@
 use crate::primitives::{hash_map::Entry, Bytecode, Bytes, HashMap, U256};
 use crate::{
-    primitives::{Env, Log, B160, B256, KECCAK_EMPTY},
+    primitives::{Env, EnvValue, Log, B160, B256, KECCAK_EMPTY},
     CallInputs, CreateInputs, Gas, Host, InstructionResult, Interpreter, SelfDestructResult,
 };
 use alloc::vec::Vec;
@@ -8,6 +8,7 @@ use alloc::vec::Vec;
 pub struct DummyHost {
     pub env: Env,
     pub storage: HashMap<U256, U256>,
+    pub transient_storage: HashMap<U256, U256>,
     pub log: Vec<Log>,
 }
 
@@ -16,11 +17,13 @@ impl DummyHost {
         Self {
             env,
             storage: HashMap::new(),
+            transient_storage: HashMap::new(),
             log: Vec::new(),
         }
     }
     pub fn clear(&mut self) {
         self.storage.clear();
+        self.transient_storage.clear();
         self.log.clear();
     }
 }
@@ -46,6 +49,10 @@ impl Host for DummyHost {
         Some((true, true))
     }
 
+    fn env_override(&mut self, _value: EnvValue, _address: B160, _caller: B160) -> Option<U256> {
+        None
+    }
+
     fn block_hash(&mut self, _number: U256) -> Option<B256> {
         Some(B256::zero())
     }
@@ -62,6 +69,10 @@ impl Host for DummyHost {
         Some((KECCAK_EMPTY, false))
     }
 
+    fn delegation(&mut self, _address: B160) -> Option<Option<(B160, bool)>> {
+        Some(None)
+    }
+
     fn sload(&mut self, __address: B160, index: U256) -> Option<(U256, bool)> {
         match self.storage.entry(index) {
             Entry::Occupied(entry) => Some((*entry.get(), false)),
@@ -89,6 +100,17 @@ impl Host for DummyHost {
         Some((U256::ZERO, present, value, is_cold))
     }
 
+    fn tload(&mut self, _address: B160, index: U256) -> U256 {
+        self.transient_storage
+            .get(&index)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    fn tstore(&mut self, _address: B160, index: U256, value: U256) {
+        self.transient_storage.insert(index, value);
+    }
+
     fn log(&mut self, address: B160, topics: Vec<B256>, data: Bytes) {
         self.log.push(Log {
             address,
@@ -97,6 +119,10 @@ impl Host for DummyHost {
         })
     }
 
+    fn log_data_size(&mut self) -> usize {
+        self.log.iter().map(|log| log.data.len()).sum()
+    }
+
     fn selfdestruct(&mut self, _address: B160, _target: B160) -> Option<SelfDestructResult> {
         panic!("Selfdestruct is not supported for this host")
     }
@@ -15,6 +15,10 @@ pub enum InstructionResult {
     CallTooDeep = 0x21,
     OutOfFund = 0x22,
 
+    // actions
+    /// Call or create opcode is suspended, see `Interpreter::suspend_calls`.
+    CallOrCreate = 0x30,
+
     // error codes
     OutOfGas = 0x50,
     MemoryOOG = 0x51,
@@ -40,6 +44,20 @@ pub enum InstructionResult {
     CreateContractStartingWithEF,
     /// EIP-3860: Limit and meter initcode. Initcode size limit exceeded.
     CreateInitcodeSizeLimit,
+    /// Malformed or invalid EOF init code, or init code returning it.
+    InvalidEOFCode,
+    /// Call, create or selfdestruct beneficiary denied by `CfgEnv::address_filter`.
+    AddressDenied,
+    /// Stack of the frame exceeds limit of `ResourceLimiter`.
+    StackLimit,
+    /// Call depth exceeds limit of `ResourceLimiter`.
+    CallDepthLimit,
+    /// Returned data exceeds limit of `ResourceLimiter` or `CfgEnv::limit_return_data_size`.
+    ReturnDataLimit,
+    /// Memory and stack of all frames exceed limit of `ResourceLimiter`.
+    AllocationLimit,
+    /// Data of logs in the transaction exceeds `CfgEnv::limit_log_data_size`.
+    LogDataLimit,
 
     // Fatal external error. Returned by database.
     FatalExternalError,
@@ -70,6 +88,13 @@ impl InstructionResult {
                 | Self::CreateContractSizeLimit
                 | Self::CreateContractStartingWithEF
                 | Self::CreateInitcodeSizeLimit
+                | Self::InvalidEOFCode
+                | Self::AddressDenied
+                | Self::StackLimit
+                | Self::CallDepthLimit
+                | Self::ReturnDataLimit
+                | Self::AllocationLimit
+                | Self::LogDataLimit
                 | Self::FatalExternalError
         )
     }
@@ -83,6 +108,8 @@ pub enum SuccessOrHalt {
     FatalExternalError,
     // this is internal opcode.
     InternalContinue,
+    // call or create is executed by the caller of the interpreter.
+    InternalCallOrCreate,
 }
 
 impl SuccessOrHalt {
@@ -122,6 +149,7 @@ impl From<InstructionResult> for SuccessOrHalt {
     fn from(result: InstructionResult) -> Self {
         match result {
             InstructionResult::Continue => Self::InternalContinue, // used only in interpreter loop
+            InstructionResult::CallOrCreate => Self::InternalCallOrCreate,
             InstructionResult::Stop => Self::Success(Eval::Stop),
             InstructionResult::Return => Self::Success(Eval::Return),
             InstructionResult::SelfDestruct => Self::Success(Eval::SelfDestruct),
@@ -165,6 +193,13 @@ impl From<InstructionResult> for SuccessOrHalt {
                 Self::Halt(Halt::CreateContractSizeLimit)
             }
             InstructionResult::CreateInitcodeSizeLimit => Self::Halt(Halt::CreateInitcodeSizeLimit),
+            InstructionResult::InvalidEOFCode => Self::Halt(Halt::InvalidEOFCode),
+            InstructionResult::AddressDenied => Self::Halt(Halt::AddressDenied),
+            InstructionResult::StackLimit => Self::Halt(Halt::StackLimit),
+            InstructionResult::CallDepthLimit => Self::Halt(Halt::CallDepthLimit),
+            InstructionResult::ReturnDataLimit => Self::Halt(Halt::ReturnDataLimit),
+            InstructionResult::AllocationLimit => Self::Halt(Halt::AllocationLimit),
+            InstructionResult::LogDataLimit => Self::Halt(Halt::LogDataLimit),
             InstructionResult::FatalExternalError => Self::FatalExternalError,
         }
     }
@@ -10,11 +10,12 @@ mod memory;
 pub mod opcode;
 mod stack;
 mod system;
+mod table;
 
 use crate::{interpreter::Interpreter, primitives::Spec, Host};
-pub use opcode::{OpCode, OPCODE_JUMPMAP};
+pub use table::{Instruction, InstructionTable};
 
-pub use crate::{return_ok, return_revert, InstructionResult};
+pub use crate::InstructionResult;
 pub fn return_stop(interpreter: &mut Interpreter, _host: &mut dyn Host) {
     interpreter.instruction_result = InstructionResult::Stop;
 }
@@ -144,6 +145,8 @@ pub fn eval<H: Host, S: Spec>(opcode: u8, interp: &mut Interpreter, host: &mut H
         opcode::REVERT => control::revert::<S>(interp, host),
         opcode::INVALID => return_invalid(interp, host),
         opcode::BASEFEE => host_env::basefee::<S>(interp, host),
+        opcode::BLOBHASH => host_env::blob_hash::<S>(interp, host),
+        opcode::BLOBBASEFEE => host_env::blob_basefee::<S>(interp, host),
         opcode::ORIGIN => host_env::origin(interp, host),
         opcode::CALLER => system::caller(interp, host),
         opcode::CALLVALUE => system::callvalue(interp, host),
@@ -175,7 +178,14 @@ pub fn eval<H: Host, S: Spec>(opcode: u8, interp: &mut Interpreter, host: &mut H
         opcode::DELEGATECALL => host::delegate_call::<S>(interp, host), //check
         opcode::STATICCALL => host::static_call::<S>(interp, host), //check
         opcode::CHAINID => host_env::chainid::<S>(interp, host),
+        opcode::TLOAD => host::tload::<S>(interp, host),
+        opcode::TSTORE => host::tstore::<S>(interp, host),
         opcode::MCOPY => memory::mcopy::<S>(interp, host),
+        opcode::RJUMP => control::rjump::<S>(interp, host),
+        opcode::RJUMPI => control::rjumpi::<S>(interp, host),
+        opcode::RJUMPV => control::rjumpv::<S>(interp, host),
+        opcode::CALLF => control::callf::<S>(interp, host),
+        opcode::RETF => control::retf::<S>(interp, host),
         _ => return_not_found(interp, host),
     }
 }
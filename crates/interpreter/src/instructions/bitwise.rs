@@ -97,7 +97,7 @@ pub fn byte(interpreter: &mut Interpreter, _host: &mut dyn Host) {
     let o1 = as_usize_saturated!(op1);
     if o1 < 32 {
         let o2 = &*op2;
-        ret = (o2 << (8 * o1)) >> (8 * 31);
+        ret = (*o2 << (8 * o1)) >> (8 * 31);
     }
 
     *op2 = ret;
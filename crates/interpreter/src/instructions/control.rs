@@ -1,8 +1,118 @@
 use crate::{
     gas, interpreter::Interpreter, primitives::Spec, primitives::SpecId::*, primitives::U256, Host,
-    InstructionResult,
+    InstructionResult, CALL_STACK_LIMIT, STACK_LIMIT,
 };
 
+/// Opcodes of EOF are undefined in legacy code.
+macro_rules! check_eof {
+    ($interp:expr, $spec:ident) => {
+        if $interp.contract.eof.is_none() {
+            $interp.instruction_result = InstructionResult::OpcodeNotFound;
+            return;
+        }
+        check!($interp, $spec::enabled(OSAKA));
+    };
+}
+
+/// Read big endian u16 immediate at `offset` from the instruction pointer.
+fn read_u16(interpreter: &Interpreter, offset: usize) -> u16 {
+    // Safety: EOF code is validated, immediates are inside the code.
+    unsafe {
+        let at = interpreter.instruction_pointer.add(offset);
+        u16::from_be_bytes([*at, *at.add(1)])
+    }
+}
+
+/// Move instruction pointer by `offset` from `after` bytes after it.
+fn relative_jump(interpreter: &mut Interpreter, after: usize, offset: i16) {
+    // Safety: EOF validation checks that relative jumps land on instructions.
+    interpreter.instruction_pointer = unsafe {
+        interpreter
+            .instruction_pointer
+            .add(after)
+            .offset(offset as isize)
+    };
+}
+
+/// EIP-4200: RJUMP
+pub fn rjump<SPEC: Spec>(interpreter: &mut Interpreter, _host: &mut dyn Host) {
+    check_eof!(interpreter, SPEC);
+    gas!(interpreter, gas::BASE);
+    let offset = read_u16(interpreter, 0) as i16;
+    relative_jump(interpreter, 2, offset);
+}
+
+/// EIP-4200: RJUMPI
+pub fn rjumpi<SPEC: Spec>(interpreter: &mut Interpreter, _host: &mut dyn Host) {
+    check_eof!(interpreter, SPEC);
+    gas!(interpreter, 4);
+    pop!(interpreter, condition);
+    let offset = if condition == U256::ZERO {
+        0
+    } else {
+        read_u16(interpreter, 0) as i16
+    };
+    relative_jump(interpreter, 2, offset);
+}
+
+/// EIP-4200: RJUMPV, jumps by the offset at `case` of the table, continues after the table
+/// if there is none.
+pub fn rjumpv<SPEC: Spec>(interpreter: &mut Interpreter, _host: &mut dyn Host) {
+    check_eof!(interpreter, SPEC);
+    gas!(interpreter, 4);
+    pop!(interpreter, case);
+    // Safety: count is part of the validated immediate.
+    let count = unsafe { *interpreter.instruction_pointer } as usize;
+    let offset = match usize::try_from(case) {
+        Ok(case) if case < count => read_u16(interpreter, 1 + case * 2) as i16,
+        _ => 0,
+    };
+    relative_jump(interpreter, 1 + count * 2, offset);
+}
+
+/// EIP-4750: CALLF
+pub fn callf<SPEC: Spec>(interpreter: &mut Interpreter, _host: &mut dyn Host) {
+    check_eof!(interpreter, SPEC);
+    gas!(interpreter, gas::LOW);
+    let Some(eof) = interpreter.contract.eof.as_ref() else {
+        return;
+    };
+    let section = read_u16(interpreter, 0) as usize;
+    let types = eof.types[section];
+    let start = eof.code_sections[section].start;
+    // EIP-5450: stack can't overflow inside the called section.
+    if interpreter.stack.len() + types.max_stack_height as usize - types.inputs as usize
+        > STACK_LIMIT as usize
+    {
+        interpreter.instruction_result = InstructionResult::StackOverflow;
+        return;
+    }
+    if interpreter.return_stack.len() as u64 >= CALL_STACK_LIMIT {
+        interpreter.instruction_result = InstructionResult::CallTooDeep;
+        return;
+    }
+    let return_to = interpreter.program_counter() + 2;
+    interpreter
+        .return_stack
+        .push((interpreter.code_section, return_to));
+    interpreter.code_section = section;
+    interpreter.instruction_pointer = unsafe { interpreter.contract.bytecode.as_ptr().add(start) };
+}
+
+/// EIP-4750: RETF, stops execution if there is no section to return to.
+pub fn retf<SPEC: Spec>(interpreter: &mut Interpreter, _host: &mut dyn Host) {
+    check_eof!(interpreter, SPEC);
+    gas!(interpreter, gas::VERYLOW);
+    let Some((section, return_to)) = interpreter.return_stack.pop() else {
+        interpreter.instruction_result = InstructionResult::Stop;
+        return;
+    };
+    interpreter.code_section = section;
+    // Safety: offset is right after a `CALLF` in the code.
+    interpreter.instruction_pointer =
+        unsafe { interpreter.contract.bytecode.as_ptr().add(return_to) };
+}
+
 pub fn jump(interpreter: &mut Interpreter, _host: &mut dyn Host) {
     gas!(interpreter, gas::MID);
     pop!(interpreter, dest);
@@ -42,10 +152,19 @@ pub fn pc(interpreter: &mut Interpreter, _host: &mut dyn Host) {
     push!(interpreter, U256::from(interpreter.program_counter() - 1));
 }
 
-pub fn ret(interpreter: &mut Interpreter, _host: &mut dyn Host) {
+pub fn ret(interpreter: &mut Interpreter, host: &mut dyn Host) {
     // zero gas cost gas!(interp,gas::ZERO);
     pop!(interpreter, start, len);
     let len = as_usize_or_fail!(interpreter, len, InstructionResult::InvalidOperandOOG);
+    if host
+        .env()
+        .cfg
+        .limit_return_data_size
+        .is_some_and(|limit| len > limit)
+    {
+        interpreter.instruction_result = InstructionResult::ReturnDataLimit;
+        return;
+    }
     if len == 0 {
         interpreter.return_range = usize::MAX..usize::MAX;
     } else {
@@ -56,12 +175,21 @@ pub fn ret(interpreter: &mut Interpreter, _host: &mut dyn Host) {
     interpreter.instruction_result = InstructionResult::Return;
 }
 
-pub fn revert<SPEC: Spec>(interpreter: &mut Interpreter, _host: &mut dyn Host) {
+pub fn revert<SPEC: Spec>(interpreter: &mut Interpreter, host: &mut dyn Host) {
     // zero gas cost gas!(interp,gas::ZERO);
     // EIP-140: REVERT instruction
     check!(interpreter, SPEC::enabled(BYZANTIUM));
     pop!(interpreter, start, len);
     let len = as_usize_or_fail!(interpreter, len, InstructionResult::InvalidOperandOOG);
+    if host
+        .env()
+        .cfg
+        .limit_return_data_size
+        .is_some_and(|limit| len > limit)
+    {
+        interpreter.instruction_result = InstructionResult::ReturnDataLimit;
+        return;
+    }
     if len == 0 {
         interpreter.return_range = usize::MAX..usize::MAX;
     } else {
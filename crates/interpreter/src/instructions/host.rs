@@ -1,12 +1,12 @@
-use crate::primitives::{Bytes, Spec, SpecId::*, B160, B256, U256};
+use crate::primitives::{AddressFilterAction, Bytes, Spec, SpecId::*, B160, B256, U256};
 use crate::MAX_INITCODE_SIZE;
 use crate::{
     alloc::boxed::Box,
     alloc::vec::Vec,
     gas::{self, COLD_ACCOUNT_ACCESS_COST, WARM_STORAGE_READ_COST},
-    interpreter::Interpreter,
-    return_ok, return_revert, CallContext, CallInputs, CallScheme, CreateInputs, CreateScheme,
-    Host, InstructionResult, Transfer,
+    interpreter::{Interpreter, InterpreterAction},
+    CallContext, CallInputs, CallScheme, CreateInputs, CreateScheme, Host, InstructionResult,
+    Transfer,
 };
 use core::cmp::min;
 
@@ -134,18 +134,16 @@ pub fn blockhash(interpreter: &mut Interpreter, host: &mut dyn Host) {
     gas!(interpreter, gas::BLOCKHASH);
     pop_top!(interpreter, number);
 
-    if let Some(diff) = host.env().block.number.checked_sub(*number) {
-        let diff = as_usize_saturated!(diff);
-        // blockhash should push zero if number is same as current block number.
-        if diff <= 256 && diff != 0 {
-            let ret = host.block_hash(*number);
-            if ret.is_none() {
-                interpreter.instruction_result = InstructionResult::FatalExternalError;
-                return;
-            }
-            *number = U256::from_be_bytes(*ret.unwrap());
+    // blockhash should push zero if number is same as current block number. Host decides
+    // how old blocks are available.
+    if *number < host.env().block.number {
+        let ret = host.block_hash(*number);
+        if ret.is_none() {
+            interpreter.instruction_result = InstructionResult::FatalExternalError;
             return;
         }
+        *number = U256::from_be_bytes(*ret.unwrap());
+        return;
     }
     *number = U256::ZERO;
 }
@@ -180,12 +178,37 @@ pub fn sstore<SPEC: Spec>(interpreter: &mut Interpreter, host: &mut dyn Host) {
     refund!(interpreter, gas::sstore_refund::<SPEC>(original, old, new));
 }
 
+pub fn tload<SPEC: Spec>(interpreter: &mut Interpreter, host: &mut dyn Host) {
+    // EIP-1153: Transient storage opcodes
+    check!(interpreter, SPEC::enabled(CANCUN));
+    gas!(interpreter, gas::WARM_STORAGE_READ_COST);
+    pop_top!(interpreter, index);
+
+    *index = host.tload(interpreter.contract.address, *index);
+}
+
+pub fn tstore<SPEC: Spec>(interpreter: &mut Interpreter, host: &mut dyn Host) {
+    // EIP-1153: Transient storage opcodes
+    check!(interpreter, SPEC::enabled(CANCUN));
+    check_staticcall!(interpreter);
+    gas!(interpreter, gas::WARM_STORAGE_READ_COST);
+
+    pop!(interpreter, index, value);
+    host.tstore(interpreter.contract.address, index, value);
+}
+
 pub fn log<const N: u8>(interpreter: &mut Interpreter, host: &mut dyn Host) {
     check_staticcall!(interpreter);
 
     pop!(interpreter, offset, len);
     let len = as_usize_or_fail!(interpreter, len, InstructionResult::InvalidOperandOOG);
     gas_or_fail!(interpreter, gas::log_cost(N, len as u64));
+    if let Some(limit) = host.env().cfg.limit_log_data_size {
+        if host.log_data_size().saturating_add(len) > limit {
+            interpreter.instruction_result = InstructionResult::LogDataLimit;
+            return;
+        }
+    }
     let data = if len == 0 {
         Bytes::new()
     } else {
@@ -214,6 +237,18 @@ pub fn selfdestruct<SPEC: Spec>(interpreter: &mut Interpreter, host: &mut dyn Ho
     check_staticcall!(interpreter);
     pop_address!(interpreter, target);
 
+    match host.env().cfg.address_filter.check(&target) {
+        Some(AddressFilterAction::Halt) => {
+            interpreter.instruction_result = InstructionResult::AddressDenied;
+            return;
+        }
+        Some(AddressFilterAction::NoOp) => {
+            interpreter.instruction_result = InstructionResult::Stop;
+            return;
+        }
+        None => (),
+    }
+
     let res = host.selfdestruct(interpreter.contract.address, target);
     if res.is_none() {
         interpreter.instruction_result = InstructionResult::FatalExternalError;
@@ -225,7 +260,11 @@ pub fn selfdestruct<SPEC: Spec>(interpreter: &mut Interpreter, host: &mut dyn Ho
     if !SPEC::enabled(LONDON) && !res.previously_destroyed {
         refund!(interpreter, gas::SELFDESTRUCT)
     }
-    gas!(interpreter, gas::selfdestruct_cost::<SPEC>(res));
+    let state_clear = host.env().cfg.is_state_clear_enabled();
+    gas!(
+        interpreter,
+        gas::selfdestruct_cost::<SPEC>(res, state_clear)
+    );
 
     interpreter.instruction_result = InstructionResult::SelfDestruct;
 }
@@ -277,7 +316,7 @@ pub fn prepare_create_inputs<const IS_CREATE2: bool, SPEC: Spec>(
     let mut gas_limit = interpreter.gas().remaining();
 
     // EIP-150: Gas cost changes for IO-heavy operations
-    if SPEC::enabled(TANGERINE) {
+    if SPEC::enabled(TANGERINE) || interpreter.gas.is_unmetered() {
         // take remaining gas and deduce l64 part of it.
         gas_limit -= gas_limit / 64
     }
@@ -303,36 +342,16 @@ pub fn create<const IS_CREATE2: bool, SPEC: Spec>(
         return;
     };
 
-    let (return_reason, address, gas, return_data) = host.create(&mut create_input);
-
-    interpreter.return_data_buffer = match return_reason {
-        // Save data to return data buffer if the create reverted
-        return_revert!() => return_data,
-        // Otherwise clear it
-        _ => Bytes::new(),
-    };
-
-    match return_reason {
-        return_ok!() => {
-            push_b256!(interpreter, address.unwrap_or_default().into());
-            if crate::USE_GAS {
-                interpreter.gas.erase_cost(gas.remaining());
-                interpreter.gas.record_refund(gas.refunded());
-            }
-        }
-        return_revert!() => {
-            push_b256!(interpreter, B256::zero());
-            if crate::USE_GAS {
-                interpreter.gas.erase_cost(gas.remaining());
-            }
-        }
-        InstructionResult::FatalExternalError => {
-            interpreter.instruction_result = InstructionResult::FatalExternalError;
-        }
-        _ => {
-            push_b256!(interpreter, B256::zero());
-        }
+    if interpreter.suspend_calls {
+        interpreter.next_action = Some(InterpreterAction::Create {
+            inputs: create_input,
+        });
+        interpreter.instruction_result = InstructionResult::CallOrCreate;
+        return;
     }
+
+    let (return_reason, address, gas, return_data) = host.create(&mut create_input);
+    interpreter.insert_create_output(return_reason, address, gas, return_data);
 }
 
 pub fn call<SPEC: Spec>(interpreter: &mut Interpreter, host: &mut dyn Host) {
@@ -458,6 +477,7 @@ fn prepare_call_inputs<SPEC: Spec>(
     let (is_cold, exist) = res.unwrap();
     let is_new = !exist;
 
+    let state_clear = host.env().cfg.is_state_clear_enabled();
     gas!(
         interpreter,
         gas::call_cost::<SPEC>(
@@ -466,11 +486,27 @@ fn prepare_call_inputs<SPEC: Spec>(
             is_cold,
             matches!(scheme, CallScheme::Call | CallScheme::CallCode),
             matches!(scheme, CallScheme::Call | CallScheme::StaticCall),
+            state_clear,
         )
     );
 
+    // EIP-7702: Code of the delegated address is executed, it is accessed too.
+    if SPEC::enabled(PRAGUE) {
+        let Some(delegation) = host.delegation(to) else {
+            interpreter.instruction_result = InstructionResult::FatalExternalError;
+            return;
+        };
+        if let Some((_, is_cold)) = delegation {
+            gas!(interpreter, gas::hot_cold_cost::<SPEC>(is_cold, 0));
+        }
+    }
+
     // take l64 part of gas_limit
-    let mut gas_limit = if SPEC::enabled(TANGERINE) {
+    let mut gas_limit = if interpreter.gas.is_unmetered() {
+        // unmetered frames are only limited by the cap of their spend, not by gas they forward.
+        let gas = interpreter.gas().remaining();
+        gas - gas / 64
+    } else if SPEC::enabled(TANGERINE) {
         //EIP-150: Gas cost changes for IO-heavy operations
         let gas = interpreter.gas().remaining();
         min(gas - gas / 64, local_gas_limit)
@@ -481,7 +517,10 @@ fn prepare_call_inputs<SPEC: Spec>(
     gas!(interpreter, gas_limit);
 
     // add call stipend if there is value to be transferred.
-    if matches!(scheme, CallScheme::Call | CallScheme::CallCode) && transfer.value != U256::ZERO {
+    if matches!(scheme, CallScheme::Call | CallScheme::CallCode)
+        && transfer.value != U256::ZERO
+        && !interpreter.gas.is_unmetered()
+    {
         gas_limit = gas_limit.saturating_add(gas::CALL_STIPEND);
     }
     let is_static = matches!(scheme, CallScheme::StaticCall) || interpreter.is_static;
@@ -524,39 +563,21 @@ pub fn call_inner<SPEC: Spec>(
         return;
     };
 
+    if interpreter.suspend_calls {
+        interpreter.next_action = Some(InterpreterAction::Call {
+            inputs: call_input,
+            return_memory: out_offset..out_offset.saturating_add(out_len),
+        });
+        interpreter.instruction_result = InstructionResult::CallOrCreate;
+        return;
+    }
+
     // Call host to interact with target contract
     let (reason, gas, return_data) = host.call(&mut call_input);
-
-    interpreter.return_data_buffer = return_data;
-
-    let target_len = min(out_len, interpreter.return_data_buffer.len());
-
-    match reason {
-        return_ok!() => {
-            // return unspend gas.
-            if crate::USE_GAS {
-                interpreter.gas.erase_cost(gas.remaining());
-                interpreter.gas.record_refund(gas.refunded());
-            }
-            interpreter
-                .memory
-                .set(out_offset, &interpreter.return_data_buffer[..target_len]);
-            push!(interpreter, U256::from(1));
-        }
-        return_revert!() => {
-            if crate::USE_GAS {
-                interpreter.gas.erase_cost(gas.remaining());
-            }
-            interpreter
-                .memory
-                .set(out_offset, &interpreter.return_data_buffer[..target_len]);
-            push!(interpreter, U256::ZERO);
-        }
-        InstructionResult::FatalExternalError => {
-            interpreter.instruction_result = InstructionResult::FatalExternalError;
-        }
-        _ => {
-            push!(interpreter, U256::ZERO);
-        }
-    }
+    interpreter.insert_call_output(
+        reason,
+        gas,
+        return_data,
+        out_offset..out_offset.saturating_add(out_len),
+    );
 }
@@ -1,36 +1,62 @@
 use crate::{
-    gas, interpreter::Interpreter, primitives::Spec, primitives::SpecId::*, Host, InstructionResult,
+    gas,
+    interpreter::Interpreter,
+    primitives::{Env, EnvValue, Spec, SpecId::*, B256, U256},
+    Host, InstructionResult,
 };
 
+/// Value of the environment, or the one host overrides it with for the executing frame.
+fn env_value<H: Host + ?Sized>(
+    interpreter: &Interpreter,
+    host: &mut H,
+    value: EnvValue,
+    default: impl FnOnce(&Env) -> U256,
+) -> U256 {
+    let contract = &interpreter.contract;
+    host.env_override(value, contract.address, contract.caller)
+        .unwrap_or_else(|| default(host.env()))
+}
+
 pub fn chainid<SPEC: Spec>(interpreter: &mut Interpreter, host: &mut dyn Host) {
     // EIP-1344: ChainID opcode
     check!(interpreter, SPEC::enabled(ISTANBUL));
     gas!(interpreter, gas::BASE);
-    push!(interpreter, host.env().cfg.chain_id);
+    let chain_id = env_value(interpreter, host, EnvValue::ChainId, |env| env.cfg.chain_id);
+    push!(interpreter, chain_id);
 }
 
 pub fn coinbase(interpreter: &mut Interpreter, host: &mut dyn Host) {
     gas!(interpreter, gas::BASE);
-    push_b256!(interpreter, host.env().block.coinbase.into());
+    let coinbase = env_value(interpreter, host, EnvValue::Coinbase, |env| {
+        U256::from_be_bytes(B256::from(env.block.coinbase).0)
+    });
+    push!(interpreter, coinbase);
 }
 
 pub fn timestamp(interpreter: &mut Interpreter, host: &mut dyn Host) {
     gas!(interpreter, gas::BASE);
-    push!(interpreter, host.env().block.timestamp);
+    let timestamp = env_value(interpreter, host, EnvValue::Timestamp, |env| {
+        env.block.timestamp
+    });
+    push!(interpreter, timestamp);
 }
 
 pub fn number(interpreter: &mut Interpreter, host: &mut dyn Host) {
     gas!(interpreter, gas::BASE);
-    push!(interpreter, host.env().block.number);
+    let number = env_value(interpreter, host, EnvValue::Number, |env| env.block.number);
+    push!(interpreter, number);
 }
 
 pub fn difficulty<H: Host, SPEC: Spec>(interpreter: &mut Interpreter, host: &mut H) {
     gas!(interpreter, gas::BASE);
-    if SPEC::enabled(MERGE) {
-        push_b256!(interpreter, host.env().block.prevrandao.unwrap());
-    } else {
-        push!(interpreter, host.env().block.difficulty);
-    }
+    let value = env_value(interpreter, host, EnvValue::PrevRandao, |env| {
+        if SPEC::enabled(MERGE) {
+            U256::from_be_bytes(env.block.prevrandao.unwrap().0)
+        } else {
+            env.block.difficulty
+        }
+    });
+    push!(interpreter, value);
 }
 
 pub fn gaslimit(interpreter: &mut Interpreter, host: &mut dyn Host) {
@@ -47,7 +73,32 @@ pub fn basefee<SPEC: Spec>(interpreter: &mut Interpreter, host: &mut dyn Host) {
     gas!(interpreter, gas::BASE);
     // EIP-3198: BASEFEE opcode
     check!(interpreter, SPEC::enabled(LONDON));
-    push!(interpreter, host.env().block.basefee);
+    let basefee = env_value(interpreter, host, EnvValue::BaseFee, |env| {
+        env.block.basefee
+    });
+    push!(interpreter, basefee);
+}
+
+pub fn blob_hash<SPEC: Spec>(interpreter: &mut Interpreter, host: &mut dyn Host) {
+    // EIP-4844: Shard Blob Transactions
+    check!(interpreter, SPEC::enabled(CANCUN));
+    gas!(interpreter, gas::VERYLOW);
+    pop_top!(interpreter, index);
+    let blob_hashes = &host.env().tx.blob_hashes;
+    *index = match usize::try_from(*index) {
+        Ok(i) if i < blob_hashes.len() => U256::from_be_bytes(blob_hashes[i].0),
+        _ => U256::ZERO,
+    };
+}
+
+pub fn blob_basefee<SPEC: Spec>(interpreter: &mut Interpreter, host: &mut dyn Host) {
+    // EIP-7516: BLOBBASEFEE opcode
+    check!(interpreter, SPEC::enabled(CANCUN));
+    gas!(interpreter, gas::BASE);
+    push!(
+        interpreter,
+        U256::from(host.env().block.get_blob_gasprice().unwrap_or_default())
+    );
 }
 
 pub fn origin(interpreter: &mut Interpreter, host: &mut dyn Host) {
@@ -31,6 +31,7 @@ pub const MIN_NEGATIVE_VALUE: U256 = U256::from_limbs([
 
 const FLIPH_BITMASK_U64: u64 = 0x7FFFFFFFFFFFFFFF;
 
+#[cfg_attr(not(test), allow(dead_code))]
 #[cfg_attr(test, derive(PropTestArbitrary))]
 #[cfg_attr(any(test, feature = "arbitrary"), derive(Arbitrary))]
 #[derive(Copy, Clone, Eq, PartialEq, Debug)]
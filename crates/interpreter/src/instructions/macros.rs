@@ -1,5 +1,3 @@
-pub use crate::InstructionResult;
-
 macro_rules! check_staticcall {
     ($interp:expr) => {
         if $interp.is_static {
@@ -21,7 +19,7 @@ macro_rules! check {
 macro_rules! gas {
     ($interp:expr, $gas:expr) => {
         if crate::USE_GAS {
-            if !$interp.gas.record_cost(($gas)) {
+            if !$interp.gas.record_cost($gas) {
                 $interp.instruction_result = InstructionResult::OutOfGas;
                 return;
             }
@@ -48,6 +48,8 @@ pub const JUMPI: u8 = 0x57;
 pub const PC: u8 = 0x58;
 pub const MSIZE: u8 = 0x59;
 pub const JUMPDEST: u8 = 0x5b;
+pub const TLOAD: u8 = 0x5c;
+pub const TSTORE: u8 = 0x5d;
 pub const MCOPY: u8 = 0x5e;
 pub const PUSH0: u8 = 0x5f;
 pub const PUSH1: u8 = 0x60;
@@ -114,12 +116,19 @@ pub const SWAP13: u8 = 0x9c;
 pub const SWAP14: u8 = 0x9d;
 pub const SWAP15: u8 = 0x9e;
 pub const SWAP16: u8 = 0x9f;
+pub const RJUMP: u8 = 0xe0;
+pub const RJUMPI: u8 = 0xe1;
+pub const RJUMPV: u8 = 0xe2;
+pub const CALLF: u8 = 0xe3;
+pub const RETF: u8 = 0xe4;
 pub const RETURN: u8 = 0xf3;
 pub const REVERT: u8 = 0xfd;
 pub const INVALID: u8 = 0xfe;
 pub const ADDRESS: u8 = 0x30;
 pub const BALANCE: u8 = 0x31;
 pub const BASEFEE: u8 = 0x48;
+pub const BLOBHASH: u8 = 0x49;
+pub const BLOBBASEFEE: u8 = 0x4a;
 pub const ORIGIN: u8 = 0x32;
 pub const CALLER: u8 = 0x33;
 pub const CALLVALUE: u8 = 0x34;
@@ -256,8 +265,8 @@ pub const OPCODE_JUMPMAP: [Option<&'static str>; 256] = [
     /* 0x46 */ Some("CHAINID"),
     /* 0x47 */ Some("SELFBALANCE"),
     /* 0x48 */ Some("BASEFEE"),
-    /* 0x49 */ None,
-    /* 0x4a */ None,
+    /* 0x49 */ Some("BLOBHASH"),
+    /* 0x4a */ Some("BLOBBASEFEE"),
     /* 0x4b */ None,
     /* 0x4c */ None,
     /* 0x4d */ None,
@@ -275,8 +284,8 @@ pub const OPCODE_JUMPMAP: [Option<&'static str>; 256] = [
     /* 0x59 */ Some("MSIZE"),
     /* 0x5a */ Some("GAS"),
     /* 0x5b */ Some("JUMPDEST"),
-    /* 0x5c */ None,
-    /* 0x5d */ None,
+    /* 0x5c */ Some("TLOAD"),
+    /* 0x5d */ Some("TSTORE"),
     /* 0x5e */ Some("MCOPY"),
     /* 0x5f */ Some("PUSH0"),
     /* 0x60 */ Some("PUSH1"),
@@ -407,11 +416,11 @@ pub const OPCODE_JUMPMAP: [Option<&'static str>; 256] = [
     /* 0xdd */ None,
     /* 0xde */ None,
     /* 0xdf */ None,
-    /* 0xe0 */ None,
-    /* 0xe1 */ None,
-    /* 0xe2 */ None,
-    /* 0xe3 */ None,
-    /* 0xe4 */ None,
+    /* 0xe0 */ Some("RJUMP"),
+    /* 0xe1 */ Some("RJUMPI"),
+    /* 0xe2 */ Some("RJUMPV"),
+    /* 0xe3 */ Some("CALLF"),
+    /* 0xe4 */ Some("RETF"),
     /* 0xe5 */ None,
     /* 0xe6 */ None,
     /* 0xe7 */ None,
@@ -636,8 +645,18 @@ macro_rules! gas_opcodee {
             } else {
                 0
             }),
-            /* 0x49 */ OpInfo::none(),
-            /* 0x4a */ OpInfo::none(),
+            /* 0x49  BLOBHASH */
+            OpInfo::gas(if SpecId::enabled($spec_id, SpecId::CANCUN) {
+                gas::VERYLOW
+            } else {
+                0
+            }),
+            /* 0x4a  BLOBBASEFEE */
+            OpInfo::gas(if SpecId::enabled($spec_id, SpecId::CANCUN) {
+                gas::BASE
+            } else {
+                0
+            }),
             /* 0x4b */ OpInfo::none(),
             /* 0x4c */ OpInfo::none(),
             /* 0x4d */ OpInfo::none(),
@@ -657,8 +676,18 @@ macro_rules! gas_opcodee {
             /* 0x5b  JUMPDEST */
             // gas::JUMPDEST gas is calculated in function call,
             OpInfo::jumpdest(),
-            /* 0x5c */ OpInfo::none(),
-            /* 0x5d */ OpInfo::none(),
+            /* 0x5c  TLOAD */
+            OpInfo::gas(if SpecId::enabled($spec_id, SpecId::CANCUN) {
+                gas::WARM_STORAGE_READ_COST
+            } else {
+                0
+            }),
+            /* 0x5d  TSTORE */
+            OpInfo::gas(if SpecId::enabled($spec_id, SpecId::CANCUN) {
+                gas::WARM_STORAGE_READ_COST
+            } else {
+                0
+            }),
             /* 0x5e  MCOPY */ OpInfo::dynamic_gas(),
             /* 0x5f PUSH0 */
             OpInfo::gas(if SpecId::enabled($spec_id, SpecId::SHANGHAI) {
@@ -794,11 +823,36 @@ macro_rules! gas_opcodee {
             /* 0xdd */ OpInfo::none(),
             /* 0xde */ OpInfo::none(),
             /* 0xdf */ OpInfo::none(),
-            /* 0xe0 */ OpInfo::none(),
-            /* 0xe1 */ OpInfo::none(),
-            /* 0xe2 */ OpInfo::none(),
-            /* 0xe3 */ OpInfo::none(),
-            /* 0xe4 */ OpInfo::none(),
+            /* 0xe0  RJUMP */
+            OpInfo::gas_block_end(if SpecId::enabled($spec_id, SpecId::OSAKA) {
+                gas::BASE
+            } else {
+                0
+            }),
+            /* 0xe1  RJUMPI */
+            OpInfo::gas_block_end(if SpecId::enabled($spec_id, SpecId::OSAKA) {
+                4
+            } else {
+                0
+            }),
+            /* 0xe2  RJUMPV */
+            OpInfo::gas_block_end(if SpecId::enabled($spec_id, SpecId::OSAKA) {
+                4
+            } else {
+                0
+            }),
+            /* 0xe3  CALLF */
+            OpInfo::gas_block_end(if SpecId::enabled($spec_id, SpecId::OSAKA) {
+                gas::LOW
+            } else {
+                0
+            }),
+            /* 0xe4  RETF */
+            OpInfo::gas_block_end(if SpecId::enabled($spec_id, SpecId::OSAKA) {
+                gas::VERYLOW
+            } else {
+                0
+            }),
             /* 0xe5 */ OpInfo::none(),
             /* 0xe6 */ OpInfo::none(),
             /* 0xe7 */ OpInfo::none(),
@@ -904,6 +958,14 @@ pub const fn spec_opcode_gas(spec_id: SpecId) -> &'static [OpInfo; 256] {
             gas_opcodee!(CANCUN, SpecId::CANCUN);
             CANCUN
         }
+        SpecId::PRAGUE => {
+            gas_opcodee!(PRAGUE, SpecId::PRAGUE);
+            PRAGUE
+        }
+        SpecId::OSAKA => {
+            gas_opcodee!(OSAKA, SpecId::OSAKA);
+            OSAKA
+        }
         SpecId::LATEST => {
             gas_opcodee!(LATEST, SpecId::LATEST);
             LATEST
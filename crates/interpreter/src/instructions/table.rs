@@ -0,0 +1,128 @@
+use crate::{interpreter::Interpreter, Host};
+use core::fmt;
+
+/// Handler of an opcode, it charges its own gas and sets
+/// [Interpreter::instruction_result] to stop execution.
+pub type Instruction = fn(&mut Interpreter, &mut dyn Host);
+
+/// Handlers replacing the built-in ones of the spec, for opcodes unused by Ethereum or
+/// opcodes that behave differently on other chains.
+///
+/// Opcodes without a handler run the built-in one. Static gas of the opcode is not charged
+/// by the interpreter, so handlers have to charge it themselves.
+#[derive(Clone)]
+pub struct InstructionTable {
+    instructions: [Option<Instruction>; 256],
+}
+
+impl Default for InstructionTable {
+    fn default() -> Self {
+        Self {
+            instructions: [None; 256],
+        }
+    }
+}
+
+impl fmt::Debug for InstructionTable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.opcodes()).finish()
+    }
+}
+
+impl InstructionTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Table with `instruction` handling `opcode`.
+    pub fn with(mut self, opcode: u8, instruction: Instruction) -> Self {
+        self.insert(opcode, instruction);
+        self
+    }
+
+    /// Handle `opcode` with `instruction`, returns the previous handler.
+    pub fn insert(&mut self, opcode: u8, instruction: Instruction) -> Option<Instruction> {
+        self.instructions[opcode as usize].replace(instruction)
+    }
+
+    /// Run the built-in handler of `opcode` again, returns the removed handler.
+    pub fn remove(&mut self, opcode: u8) -> Option<Instruction> {
+        self.instructions[opcode as usize].take()
+    }
+
+    /// Handler of `opcode`, none if it is the built-in one.
+    #[inline(always)]
+    pub fn get(&self, opcode: u8) -> Option<Instruction> {
+        self.instructions[opcode as usize]
+    }
+
+    /// Opcodes that have a handler.
+    pub fn opcodes(&self) -> impl Iterator<Item = u8> + '_ {
+        (0..=u8::MAX).filter(|opcode| self.get(*opcode).is_some())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.instructions.iter().all(Option::is_none)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        opcode, primitives::LatestSpec, Contract, DummyHost, InstructionResult, Interpreter,
+    };
+    use alloc::boxed::Box;
+    use revm_primitives::{Bytecode, Bytes, Env, U256};
+
+    fn run(code: &[u8], table: &InstructionTable) -> Interpreter {
+        let contract = Contract::new(
+            Bytes::new(),
+            Bytecode::new_raw(code.to_vec().into()),
+            Default::default(),
+            Default::default(),
+            U256::ZERO,
+        );
+        let mut interpreter = Interpreter::new(Box::new(contract), 1_000_000, false);
+        let mut host = DummyHost::new(Env::default());
+        interpreter.run_with_table::<_, LatestSpec>(table, &mut host);
+        interpreter
+    }
+
+    #[test]
+    fn replaces_instructions() {
+        // 0x0c is unused, it pushes chain id. ADD subtracts.
+        let table = InstructionTable::new()
+            .with(0x0c, |interp, host| {
+                if !interp.gas.record_cost(2) {
+                    interp.instruction_result = InstructionResult::OutOfGas;
+                    return;
+                }
+                if interp.stack.push(host.env().cfg.chain_id).is_err() {
+                    interp.instruction_result = InstructionResult::StackOverflow;
+                }
+            })
+            .with(opcode::ADD, |interp, _| {
+                let (Ok(a), Ok(b)) = (interp.stack.pop(), interp.stack.pop()) else {
+                    interp.instruction_result = InstructionResult::StackUnderflow;
+                    return;
+                };
+                let _ = interp.stack.push(a.wrapping_sub(b));
+            });
+        assert_eq!(table.opcodes().collect::<Vec<_>>(), [opcode::ADD, 0x0c]);
+
+        // PUSH1 1 PUSH1 3 ADD 0x0c STOP
+        let interpreter = run(&[0x60, 1, 0x60, 3, opcode::ADD, 0x0c, 0x00], &table);
+        assert_eq!(interpreter.instruction_result, InstructionResult::Stop);
+        assert_eq!(interpreter.stack.data()[0], U256::from(2));
+        assert_eq!(interpreter.stack.data()[1], U256::from(1));
+
+        let mut table = table;
+        table.remove(0x0c);
+        let interpreter = run(&[0x0c], &table);
+        assert_eq!(
+            interpreter.instruction_result,
+            InstructionResult::OpcodeNotFound
+        );
+    }
+}
@@ -1,19 +1,24 @@
 pub mod analysis;
 mod contract;
+mod eof;
 pub(crate) mod memory;
+#[cfg(feature = "serde")]
+mod serde_impl;
 mod stack;
 
 pub use analysis::BytecodeLocked;
 pub use contract::Contract;
+pub use eof::{decode_valid_eof, stack_io, validate_eof, EofError, EofErrorKind};
 pub use memory::Memory;
 pub use stack::Stack;
 
-use crate::primitives::{Bytes, Spec};
+use crate::primitives::{Bytes, Spec, B160, B256, U256};
 use crate::{
-    alloc::boxed::Box,
-    instructions::{eval, InstructionResult},
-    Gas, Host,
+    alloc::{boxed::Box, vec::Vec},
+    instructions::{eval, InstructionResult, InstructionTable},
+    return_ok, return_revert, CallInputs, CreateInputs, Gas, Host,
 };
+use core::cmp::min;
 use core::ops::Range;
 
 pub const STACK_LIMIT: u64 = 1024;
@@ -44,11 +49,41 @@ pub struct Interpreter {
     pub is_static: bool,
     /// Contract information and invoking data
     pub contract: Box<Contract>,
+    /// Code section that is executing, for EOF code.
+    pub code_section: usize,
+    /// Code section and offset to return to of every `CALLF` that did not return yet.
+    pub return_stack: Vec<(usize, usize)>,
+    /// If set, call and create opcodes don't execute the frame through [Host::call] and
+    /// [Host::create]. They stop with [InstructionResult::CallOrCreate] and leave the frame in
+    /// [Interpreter::next_action], the caller executes it and gives back its result with
+    /// [Interpreter::insert_call_output] or [Interpreter::insert_create_output].
+    pub suspend_calls: bool,
+    /// Call or create the interpreter is suspended at.
+    pub next_action: Option<InterpreterAction>,
     /// Memory limit. See [`crate::CfgEnv`].
     #[cfg(feature = "memory_limit")]
     pub memory_limit: u64,
 }
 
+// Safety: instruction pointer points into the bytecode of the contract, which is owned by
+// the interpreter and moves with it.
+unsafe impl Send for Interpreter {}
+
+/// Frame that call and create opcodes of a suspended interpreter start, see
+/// [Interpreter::suspend_calls].
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum InterpreterAction {
+    Call {
+        inputs: Box<CallInputs>,
+        /// Memory the output of the call is copied to.
+        return_memory: Range<usize>,
+    },
+    Create {
+        inputs: Box<CreateInputs>,
+    },
+}
+
 impl Interpreter {
     /// Current opcode
     pub fn current_opcode(&self) -> u8 {
@@ -60,12 +95,16 @@ impl Interpreter {
         #[cfg(not(feature = "memory_limit"))]
         {
             Self {
-                instruction_pointer: contract.bytecode.as_ptr(),
+                instruction_pointer: contract.entry_point(),
                 return_range: Range::default(),
                 memory: Memory::new(),
                 stack: Stack::new(),
                 return_data_buffer: Bytes::new(),
                 contract,
+                code_section: 0,
+                return_stack: Vec::new(),
+                suspend_calls: false,
+                next_action: None,
                 instruction_result: InstructionResult::Continue,
                 is_static,
                 gas: Gas::new(gas_limit),
@@ -86,12 +125,16 @@ impl Interpreter {
         memory_limit: u64,
     ) -> Self {
         Self {
-            instruction_pointer: contract.bytecode.as_ptr(),
+            instruction_pointer: contract.entry_point(),
             return_range: Range::default(),
             memory: Memory::new(),
             stack: Stack::new(),
             return_data_buffer: Bytes::new(),
             contract,
+            code_section: 0,
+            return_stack: Vec::new(),
+            suspend_calls: false,
+            next_action: None,
             instruction_result: InstructionResult::Continue,
             is_static,
             gas: Gas::new(gas_limit),
@@ -165,6 +208,136 @@ impl Interpreter {
         self.instruction_result
     }
 
+    /// Execute next instruction, handlers of `table` replace the built-in ones.
+    #[inline(always)]
+    pub fn step_with_table<H: Host, SPEC: Spec>(&mut self, table: &InstructionTable, host: &mut H) {
+        let opcode = unsafe { *self.instruction_pointer };
+        // Safety: same as in `step`.
+        self.instruction_pointer = unsafe { self.instruction_pointer.offset(1) };
+        match table.get(opcode) {
+            Some(instruction) => instruction(self, host),
+            None => eval::<H, SPEC>(opcode, self, host),
+        }
+    }
+
+    /// Same as [Interpreter::run] with instructions of `table`.
+    pub fn run_with_table<H: Host, SPEC: Spec>(
+        &mut self,
+        table: &InstructionTable,
+        host: &mut H,
+    ) -> InstructionResult {
+        while self.instruction_result == InstructionResult::Continue {
+            self.step_with_table::<H, SPEC>(table, host)
+        }
+        self.instruction_result
+    }
+
+    /// Same as [Interpreter::run_inspect] with instructions of `table`.
+    pub fn run_inspect_with_table<H: Host, SPEC: Spec>(
+        &mut self,
+        table: &InstructionTable,
+        host: &mut H,
+    ) -> InstructionResult {
+        while self.instruction_result == InstructionResult::Continue {
+            let ret = host.step(self);
+            if ret != InstructionResult::Continue {
+                return ret;
+            }
+            self.step_with_table::<H, SPEC>(table, host);
+
+            let ret = host.step_end(self, self.instruction_result);
+            if ret != InstructionResult::Continue {
+                return ret;
+            }
+        }
+        self.instruction_result
+    }
+
+    /// Finish call opcode with result of the call, it copies `return_data` to
+    /// `return_memory` and pushes success of the call.
+    pub fn insert_call_output(
+        &mut self,
+        result: InstructionResult,
+        gas: Gas,
+        return_data: Bytes,
+        return_memory: Range<usize>,
+    ) {
+        self.instruction_result = InstructionResult::Continue;
+        self.return_data_buffer = return_data;
+        let target_len = min(return_memory.len(), self.return_data_buffer.len());
+
+        let success = match result {
+            return_ok!() => {
+                // return unspend gas.
+                if crate::USE_GAS {
+                    self.gas.erase_cost(gas.remaining());
+                    self.gas.record_refund(gas.refunded());
+                }
+                self.memory
+                    .set(return_memory.start, &self.return_data_buffer[..target_len]);
+                U256::from(1)
+            }
+            return_revert!() => {
+                if crate::USE_GAS {
+                    self.gas.erase_cost(gas.remaining());
+                }
+                self.memory
+                    .set(return_memory.start, &self.return_data_buffer[..target_len]);
+                U256::ZERO
+            }
+            InstructionResult::FatalExternalError => {
+                self.instruction_result = InstructionResult::FatalExternalError;
+                return;
+            }
+            _ => U256::ZERO,
+        };
+        if let Err(e) = self.stack.push(success) {
+            self.instruction_result = e;
+        }
+    }
+
+    /// Finish create opcode with result of the create, it pushes the created address or
+    /// zero if it failed.
+    pub fn insert_create_output(
+        &mut self,
+        result: InstructionResult,
+        address: Option<B160>,
+        gas: Gas,
+        return_data: Bytes,
+    ) {
+        self.instruction_result = InstructionResult::Continue;
+        self.return_data_buffer = match result {
+            // Save data to return data buffer if the create reverted
+            return_revert!() => return_data,
+            // Otherwise clear it
+            _ => Bytes::new(),
+        };
+
+        let address = match result {
+            return_ok!() => {
+                if crate::USE_GAS {
+                    self.gas.erase_cost(gas.remaining());
+                    self.gas.record_refund(gas.refunded());
+                }
+                address.unwrap_or_default().into()
+            }
+            return_revert!() => {
+                if crate::USE_GAS {
+                    self.gas.erase_cost(gas.remaining());
+                }
+                B256::zero()
+            }
+            InstructionResult::FatalExternalError => {
+                self.instruction_result = InstructionResult::FatalExternalError;
+                return;
+            }
+            _ => B256::zero(),
+        };
+        if let Err(e) = self.stack.push_b256(address) {
+            self.instruction_result = e;
+        }
+    }
+
     /// Copy and get the return value of the interpreter, if any.
     pub fn return_value(&self) -> Bytes {
         // if start is usize max it means that our return len is zero and we need to return empty
@@ -126,6 +126,11 @@ impl BytecodeLocked {
         self.bytecode.as_ref()
     }
 
+    /// Code without padding.
+    pub fn original_bytes(&self) -> Bytes {
+        self.bytecode.slice(..self.len)
+    }
+
     pub fn original_bytecode_slice(&self) -> &[u8] {
         &self.bytecode.as_ref()[..self.len]
     }
@@ -1,6 +1,7 @@
 use super::analysis::{to_analysed, BytecodeLocked};
-use crate::primitives::{Bytecode, Bytes, B160, U256};
-use crate::CallContext;
+use crate::primitives::{Bytecode, Bytes, Eof, B160, U256};
+use crate::{decode_valid_eof, CallContext};
+use alloc::sync::Arc;
 use revm_primitives::{Env, TransactTo};
 
 #[derive(Clone, Default)]
@@ -16,6 +17,8 @@ pub struct Contract {
     pub caller: B160,
     /// Value send to contract.
     pub value: U256,
+    /// Container of the code if it is executed as EOF, see [Contract::load_eof].
+    pub eof: Option<Arc<Eof>>,
 }
 
 impl Contract {
@@ -28,6 +31,7 @@ impl Contract {
             address,
             caller,
             value,
+            eof: None,
         }
     }
 
@@ -46,6 +50,24 @@ impl Contract {
         )
     }
 
+    /// Execute code as EOF if it is a valid container. Called by the EVM when EOF is enabled,
+    /// invalid containers are executed as legacy code and halt on the 0xEF byte.
+    pub fn load_eof(&mut self) {
+        if Eof::is_eof(self.bytecode.original_bytecode_slice()) {
+            self.eof = decode_valid_eof(self.bytecode.original_bytes()).map(Arc::new);
+        }
+    }
+
+    /// Pointer to the first instruction, start of the first code section for EOF.
+    pub fn entry_point(&self) -> *const u8 {
+        let offset = self
+            .eof
+            .as_ref()
+            .map_or(0, |eof| eof.code_sections[0].start);
+        // Safety: sections are inside the code.
+        unsafe { self.bytecode.as_ptr().add(offset) }
+    }
+
     pub fn is_valid_jump(&self, possition: usize) -> bool {
         self.bytecode.jump_map().is_valid(possition)
     }
@@ -0,0 +1,347 @@
+//! Validation of EOF code sections, EIP-3670, EIP-4200, EIP-4750 and EIP-5450.
+//!
+//! Valid code has only defined instructions without truncated immediates, relative jumps
+//! land on instructions, every instruction is reachable and has the same stack height on
+//! every path to it, so stack of EOF code never underflows and its height is known ahead.
+use crate::opcode;
+use crate::primitives::{Bytes, Eof, TypesSection};
+use crate::STACK_LIMIT;
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// Why code section is invalid.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EofErrorKind {
+    /// Undefined opcode, or one that is not allowed in EOF code.
+    UndefinedInstruction,
+    TruncatedImmediate,
+    /// Relative jump to immediate data or out of the section.
+    InvalidJumpTarget,
+    /// `CALLF` to section that does not exist.
+    InvalidCallTarget,
+    /// `RJUMPV` without jump table.
+    EmptyJumpTable,
+    StackUnderflow,
+    /// Instruction is reached with different stack heights.
+    StackHeightMismatch,
+    /// `RETF` with stack height different from outputs of the section.
+    InvalidReturnHeight,
+    /// Execution can run past the end of the section.
+    NoTerminatingInstruction,
+    UnreachableCode,
+    /// Maximum stack height differs from the one in types section.
+    InvalidMaxStackHeight,
+    MaxStackHeightAboveLimit,
+}
+
+/// Invalid instruction of EOF code.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EofError {
+    pub section: usize,
+    /// Offset of the instruction in the section.
+    pub pc: usize,
+    pub kind: EofErrorKind,
+}
+
+/// Validate code sections of `eof`.
+pub fn validate_eof(eof: &Eof) -> Result<(), EofError> {
+    for section in 0..eof.code_sections.len() {
+        validate_section(eof, section)?;
+    }
+    Ok(())
+}
+
+/// Decode the container and validate its code, none if either fails.
+pub fn decode_valid_eof(code: Bytes) -> Option<Eof> {
+    Eof::decode(code)
+        .ok()
+        .filter(|eof| validate_eof(eof).is_ok())
+}
+
+fn validate_section(eof: &Eof, section: usize) -> Result<(), EofError> {
+    let code = eof.code(section).unwrap_or_default();
+    let types = &eof.types;
+    let error = |pc, kind| EofError { section, pc, kind };
+
+    // offsets of instructions, others are immediates.
+    let mut is_instruction = vec![false; code.len()];
+    let mut jumps = Vec::new();
+    let mut pc = 0;
+    while pc < code.len() {
+        let op = code[pc];
+        if !is_eof_opcode(op) {
+            return Err(error(pc, EofErrorKind::UndefinedInstruction));
+        }
+        is_instruction[pc] = true;
+        let immediate = match op {
+            opcode::PUSH1..=opcode::PUSH32 => (op - opcode::PUSH1 + 1) as usize,
+            opcode::RJUMP | opcode::RJUMPI | opcode::CALLF => 2,
+            opcode::RJUMPV => match code.get(pc + 1) {
+                Some(0) => return Err(error(pc, EofErrorKind::EmptyJumpTable)),
+                Some(count) => 1 + *count as usize * 2,
+                None => return Err(error(pc, EofErrorKind::TruncatedImmediate)),
+            },
+            _ => 0,
+        };
+        let next = pc + 1 + immediate;
+        if next > code.len() {
+            return Err(error(pc, EofErrorKind::TruncatedImmediate));
+        }
+        match op {
+            opcode::RJUMP | opcode::RJUMPI | opcode::RJUMPV => {
+                jumps.extend(relative_targets(code, pc).map(|target| (pc, target)))
+            }
+            opcode::CALLF if read_u16(code, pc + 1) as usize >= types.len() => {
+                return Err(error(pc, EofErrorKind::InvalidCallTarget));
+            }
+            _ => {}
+        }
+        pc = next;
+    }
+    for (pc, target) in jumps {
+        if !usize::try_from(target).is_ok_and(|target| is_instruction.get(target) == Some(&true)) {
+            return Err(error(pc, EofErrorKind::InvalidJumpTarget));
+        }
+    }
+
+    // EIP-5450: stack height of every instruction, following all paths from the start.
+    let TypesSection {
+        inputs,
+        outputs,
+        max_stack_height,
+    } = types[section];
+    let mut heights: Vec<Option<usize>> = vec![None; code.len()];
+    heights[0] = Some(inputs as usize);
+    let mut max_height = inputs as usize;
+    let mut work = vec![0];
+    while let Some(pc) = work.pop() {
+        let op = code[pc];
+        let height = heights[pc].expect("instructions in work have height");
+        let (pops, pushes) = match op {
+            opcode::CALLF => {
+                let target = &types[read_u16(code, pc + 1) as usize];
+                (target.inputs, target.outputs)
+            }
+            _ => stack_io(op),
+        };
+        if height < pops as usize {
+            return Err(error(pc, EofErrorKind::StackUnderflow));
+        }
+        if op == opcode::RETF && height != outputs as usize {
+            return Err(error(pc, EofErrorKind::InvalidReturnHeight));
+        }
+        let new_height = height - pops as usize + pushes as usize;
+        max_height = max_height.max(new_height);
+
+        let next = next_instruction(code, pc);
+        let mut successors: Vec<usize> = Vec::new();
+        match op {
+            opcode::STOP | opcode::RETURN | opcode::REVERT | opcode::INVALID | opcode::RETF => {}
+            opcode::RJUMP => successors.extend(relative_targets(code, pc).map(|t| t as usize)),
+            opcode::RJUMPI | opcode::RJUMPV => {
+                successors.push(next);
+                successors.extend(relative_targets(code, pc).map(|t| t as usize));
+            }
+            _ => successors.push(next),
+        }
+        for successor in successors {
+            if successor >= code.len() {
+                return Err(error(pc, EofErrorKind::NoTerminatingInstruction));
+            }
+            match heights[successor] {
+                None => {
+                    heights[successor] = Some(new_height);
+                    work.push(successor);
+                }
+                Some(known) if known != new_height => {
+                    return Err(error(successor, EofErrorKind::StackHeightMismatch));
+                }
+                Some(_) => {}
+            }
+        }
+    }
+    if let Some(pc) = (0..code.len()).find(|pc| is_instruction[*pc] && heights[*pc].is_none()) {
+        return Err(error(pc, EofErrorKind::UnreachableCode));
+    }
+    if max_height >= STACK_LIMIT as usize {
+        return Err(error(0, EofErrorKind::MaxStackHeightAboveLimit));
+    }
+    if max_height != max_stack_height as usize {
+        return Err(error(0, EofErrorKind::InvalidMaxStackHeight));
+    }
+    Ok(())
+}
+
+/// Opcodes allowed in EOF code, defined ones without the ones EOF deprecates.
+fn is_eof_opcode(op: u8) -> bool {
+    opcode::OPCODE_JUMPMAP[op as usize].is_some()
+        && !matches!(
+            op,
+            opcode::CALLCODE | opcode::SELFDESTRUCT | opcode::JUMP | opcode::JUMPI | opcode::PC
+        )
+}
+
+fn read_u16(code: &[u8], at: usize) -> u16 {
+    u16::from_be_bytes([code[at], code[at + 1]])
+}
+
+/// Offset of the instruction after the one at `pc`, code is checked for truncation.
+fn next_instruction(code: &[u8], pc: usize) -> usize {
+    pc + 1
+        + match code[pc] {
+            op @ opcode::PUSH1..=opcode::PUSH32 => (op - opcode::PUSH1 + 1) as usize,
+            opcode::RJUMP | opcode::RJUMPI | opcode::CALLF => 2,
+            opcode::RJUMPV => 1 + code[pc + 1] as usize * 2,
+            _ => 0,
+        }
+}
+
+/// Targets of the relative jump at `pc`, relative to the next instruction.
+fn relative_targets(code: &[u8], pc: usize) -> impl Iterator<Item = isize> + '_ {
+    let next = next_instruction(code, pc) as isize;
+    let offsets = match code[pc] {
+        opcode::RJUMPV => pc + 2..pc + 2 + code[pc + 1] as usize * 2,
+        _ => pc + 1..pc + 3,
+    };
+    offsets
+        .step_by(2)
+        .map(move |at| next + read_u16(code, at) as i16 as isize)
+}
+
+/// Stack items the opcode takes and pushes, for opcodes other than `CALLF`.
+pub const fn stack_io(op: u8) -> (u8, u8) {
+    match op {
+        opcode::ADDMOD | opcode::MULMOD => (3, 1),
+        0x01..=0x0b | 0x10..=0x14 | 0x16..=0x18 | 0x1a..=0x1d | opcode::KECCAK256 => (2, 1),
+        opcode::ISZERO
+        | opcode::NOT
+        | opcode::BALANCE
+        | opcode::CALLDATALOAD
+        | opcode::EXTCODESIZE
+        | opcode::EXTCODEHASH
+        | opcode::BLOCKHASH
+        | opcode::BLOBHASH
+        | opcode::MLOAD
+        | opcode::SLOAD
+        | opcode::TLOAD => (1, 1),
+        opcode::CALLDATACOPY | opcode::CODECOPY | opcode::RETURNDATACOPY | opcode::MCOPY => (3, 0),
+        opcode::EXTCODECOPY => (4, 0),
+        opcode::POP | opcode::JUMP | opcode::SELFDESTRUCT | opcode::RJUMPI | opcode::RJUMPV => {
+            (1, 0)
+        }
+        opcode::MSTORE
+        | opcode::MSTORE8
+        | opcode::SSTORE
+        | opcode::TSTORE
+        | opcode::JUMPI
+        | opcode::RETURN
+        | opcode::REVERT => (2, 0),
+        0x30 | 0x32..=0x34 | 0x36 | 0x38 | 0x3a | 0x3d | 0x41..=0x48 | opcode::BLOBBASEFEE => {
+            (0, 1)
+        }
+        opcode::PC | opcode::MSIZE | opcode::GAS | opcode::PUSH0..=opcode::PUSH32 => (0, 1),
+        opcode::DUP1..=opcode::DUP16 => {
+            let n = op - opcode::DUP1 + 1;
+            (n, n + 1)
+        }
+        opcode::SWAP1..=opcode::SWAP16 => {
+            let n = op - opcode::SWAP1 + 2;
+            (n, n)
+        }
+        opcode::LOG0..=opcode::LOG4 => (op - opcode::LOG0 + 2, 0),
+        opcode::CREATE => (3, 1),
+        opcode::CREATE2 => (4, 1),
+        opcode::CALL | opcode::CALLCODE => (7, 1),
+        opcode::DELEGATECALL | opcode::STATICCALL => (6, 1),
+        _ => (0, 0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primitives::hex_literal::hex;
+
+    /// Container with one code section and stack height of it.
+    fn container(code: &[u8], max_stack_height: u16) -> Eof {
+        let mut raw = hex!("ef0001 010004 020001").to_vec();
+        raw.extend_from_slice(&(code.len() as u16).to_be_bytes());
+        raw.extend_from_slice(&hex!("030000 00 0000"));
+        raw.extend_from_slice(&max_stack_height.to_be_bytes());
+        raw.extend_from_slice(code);
+        Eof::decode(raw.into()).unwrap()
+    }
+
+    fn kind(code: &[u8], max_stack_height: u16) -> Option<EofErrorKind> {
+        validate_eof(&container(code, max_stack_height))
+            .err()
+            .map(|e| e.kind)
+    }
+
+    #[test]
+    fn validates_code() {
+        // PUSH1 1 RJUMPI +1 STOP(skipped) STOP
+        assert_eq!(kind(&hex!("6001 e10001 00 00"), 1), None);
+        // RJUMPV with two cases, the first one back to itself is not consistent.
+        assert_eq!(
+            kind(&hex!("6000 e202fffa0000 00"), 1),
+            Some(EofErrorKind::StackHeightMismatch)
+        );
+        assert_eq!(kind(&hex!("6000 e2020000 0000 00"), 1), None);
+        assert_eq!(
+            kind(&hex!("6001 56"), 1),
+            Some(EofErrorKind::UndefinedInstruction)
+        );
+        assert_eq!(
+            kind(&hex!("0c"), 0),
+            Some(EofErrorKind::UndefinedInstruction)
+        );
+        assert_eq!(
+            kind(&hex!("61ff"), 1),
+            Some(EofErrorKind::TruncatedImmediate)
+        );
+        // jump into PUSH1 immediate.
+        assert_eq!(
+            kind(&hex!("e00001 6000 00"), 0),
+            Some(EofErrorKind::InvalidJumpTarget)
+        );
+        assert_eq!(kind(&hex!("01 00"), 0), Some(EofErrorKind::StackUnderflow));
+        assert_eq!(
+            kind(&hex!("6000"), 1),
+            Some(EofErrorKind::NoTerminatingInstruction)
+        );
+        assert_eq!(kind(&hex!("00 00"), 0), Some(EofErrorKind::UnreachableCode));
+        assert_eq!(
+            kind(&hex!("6000 00"), 2),
+            Some(EofErrorKind::InvalidMaxStackHeight)
+        );
+        assert_eq!(
+            kind(&hex!("e30001 00"), 0),
+            Some(EofErrorKind::InvalidCallTarget)
+        );
+        // backward jump with the same height.
+        assert_eq!(kind(&hex!("5b e0fffc"), 0), None);
+    }
+
+    #[test]
+    fn validates_functions() {
+        // section 0: PUSH1 1 PUSH1 2 CALLF 1 POP STOP, section 1: ADD RETF
+        let raw = hex!("ef0001 010008 0200020009 0002 030000 00 00000002 02010002 6001 6002 e30001 50 00 01 e4");
+        let eof = Eof::decode(Bytes::copy_from_slice(&raw)).unwrap();
+        assert_eq!(validate_eof(&eof), Ok(()));
+
+        // RETF leaving two items when section returns one.
+        let raw = hex!(
+            "ef0001 010008 0200020009 0001 030000 00 00000002 02010002 6001 6002 e30001 50 00 e4"
+        );
+        let eof = Eof::decode(Bytes::copy_from_slice(&raw)).unwrap();
+        assert_eq!(
+            validate_eof(&eof),
+            Err(EofError {
+                section: 1,
+                pc: 0,
+                kind: EofErrorKind::InvalidReturnHeight
+            })
+        );
+    }
+}
@@ -0,0 +1,177 @@
+//! Serialization of an interpreter between steps, so a suspended execution can be resumed
+//! in another process.
+//!
+//! Instruction pointer is stored as the program counter and contract with its original code,
+//! the code is analysed again and the pointer checked to be inside of it when the
+//! interpreter is deserialized.
+use super::analysis::to_analysed;
+use super::{Contract, Interpreter, InterpreterAction, Memory, Stack};
+use crate::primitives::{utilities::serde_hex_bytes, Bytecode, Bytes, B160, B256, U256};
+use crate::{Gas, InstructionResult};
+use alloc::{boxed::Box, vec::Vec};
+use core::ops::Range;
+use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "Contract")]
+struct ContractData {
+    #[serde(with = "serde_hex_bytes")]
+    input: Bytes,
+    /// Code without padding.
+    #[serde(with = "serde_hex_bytes")]
+    code: Bytes,
+    hash: B256,
+    address: B160,
+    caller: B160,
+    value: U256,
+    /// Code is executed as EOF.
+    eof: bool,
+}
+
+impl Serialize for Contract {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        ContractData {
+            input: self.input.clone(),
+            code: self.bytecode.original_bytes(),
+            hash: self.bytecode.hash(),
+            address: self.address,
+            caller: self.caller,
+            value: self.value,
+            eof: self.eof.is_some(),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Contract {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let data = ContractData::deserialize(deserializer)?;
+        // Safety: hash was taken from the code when the contract was serialized.
+        let bytecode = unsafe { Bytecode::new_raw_with_hash(data.code, data.hash) };
+        let mut contract = Contract {
+            input: data.input,
+            bytecode: to_analysed(bytecode).try_into().expect("it is analyzed"),
+            address: data.address,
+            caller: data.caller,
+            value: data.value,
+            eof: None,
+        };
+        if data.eof {
+            contract.load_eof();
+            if contract.eof.is_none() {
+                return Err(D::Error::custom("code is not a valid EOF container"));
+            }
+        }
+        Ok(contract)
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename = "Interpreter")]
+struct InterpreterRef<'a> {
+    program_counter: usize,
+    instruction_result: InstructionResult,
+    gas: &'a Gas,
+    memory: &'a Memory,
+    stack: &'a [U256],
+    #[serde(with = "serde_hex_bytes")]
+    return_data_buffer: &'a Bytes,
+    return_range: &'a Range<usize>,
+    is_static: bool,
+    contract: &'a Contract,
+    code_section: usize,
+    return_stack: &'a [(usize, usize)],
+    suspend_calls: bool,
+    next_action: &'a Option<InterpreterAction>,
+    #[cfg(feature = "memory_limit")]
+    memory_limit: u64,
+}
+
+#[derive(Deserialize)]
+#[serde(rename = "Interpreter")]
+struct InterpreterData {
+    program_counter: usize,
+    instruction_result: InstructionResult,
+    gas: Gas,
+    memory: Memory,
+    stack: Vec<U256>,
+    #[serde(with = "serde_hex_bytes")]
+    return_data_buffer: Bytes,
+    return_range: Range<usize>,
+    is_static: bool,
+    contract: Contract,
+    code_section: usize,
+    return_stack: Vec<(usize, usize)>,
+    suspend_calls: bool,
+    next_action: Option<InterpreterAction>,
+    #[cfg(feature = "memory_limit")]
+    memory_limit: u64,
+}
+
+impl Serialize for Interpreter {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        InterpreterRef {
+            program_counter: self.program_counter(),
+            instruction_result: self.instruction_result,
+            gas: &self.gas,
+            memory: &self.memory,
+            stack: self.stack.data(),
+            return_data_buffer: &self.return_data_buffer,
+            return_range: &self.return_range,
+            is_static: self.is_static,
+            contract: &self.contract,
+            code_section: self.code_section,
+            return_stack: &self.return_stack,
+            suspend_calls: self.suspend_calls,
+            next_action: &self.next_action,
+            #[cfg(feature = "memory_limit")]
+            memory_limit: self.memory_limit,
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Interpreter {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let data = InterpreterData::deserialize(deserializer)?;
+        let contract = Box::new(data.contract);
+        // padded code ends with STOP, a running interpreter never points past it.
+        let code_len = contract.bytecode.bytecode().len();
+        if data.program_counter >= code_len {
+            return Err(D::Error::custom("program counter is outside of the code"));
+        }
+        let sections = contract
+            .eof
+            .as_ref()
+            .map_or(1, |eof| eof.code_sections.len());
+        let is_valid_return = |&(section, pc): &(usize, usize)| section < sections && pc < code_len;
+        if data.code_section >= sections || !data.return_stack.iter().all(is_valid_return) {
+            return Err(D::Error::custom("code section is not in the code"));
+        }
+        // stack is built again as its capacity is assumed to be the limit.
+        let mut stack = Stack::new();
+        for item in data.stack {
+            stack
+                .push(item)
+                .map_err(|_| D::Error::custom("stack is over its limit"))?;
+        }
+        Ok(Self {
+            // Safety: program counter is checked to be inside of the code.
+            instruction_pointer: unsafe { contract.bytecode.as_ptr().add(data.program_counter) },
+            instruction_result: data.instruction_result,
+            gas: data.gas,
+            memory: data.memory,
+            stack,
+            return_data_buffer: data.return_data_buffer,
+            return_range: data.return_range,
+            is_static: data.is_static,
+            contract,
+            code_section: data.code_section,
+            return_stack: data.return_stack,
+            suspend_calls: data.suspend_calls,
+            next_action: data.next_action,
+            #[cfg(feature = "memory_limit")]
+            memory_limit: data.memory_limit,
+        })
+    }
+}
@@ -223,7 +223,8 @@ impl Stack {
         } else {
             // Safety: check for out of bounds is done above and it makes this safe to do.
             unsafe {
-                *self.data.get_unchecked_mut(len) = *self.data.get_unchecked(len - N);
+                let data = self.data.as_mut_ptr();
+                *data.add(len) = *data.add(len - N);
                 self.data.set_len(len + 1);
             }
             None
@@ -0,0 +1,139 @@
+//! EIP-4844 point evaluation precompile, verifies that the blob of a versioned hash
+//! evaluates to `y` at `z`.
+//!
+//! It runs with the trusted setup of the Ethereum KZG ceremony. Chains with their own setup
+//! can register [with_settings] at [ADDRESS] with
+//! [CustomPrecompiles](crate::CustomPrecompiles).
+use crate::{primitives::constants::VERSIONED_HASH_VERSION_KZG, Error, PrecompileAddress};
+use crate::{Precompile, PrecompileResult, B160};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+pub use c_kzg::KzgSettings;
+use c_kzg::{Bytes32, Bytes48, KzgProof, BYTES_PER_G1_POINT, BYTES_PER_G2_POINT};
+use once_cell::sync::OnceCell;
+use sha2::{Digest, Sha256};
+
+pub const POINT_EVALUATION: PrecompileAddress =
+    PrecompileAddress(ADDRESS, Precompile::Standard(run));
+
+pub const ADDRESS: B160 = crate::u64_to_b160(0x0A);
+pub const GAS_COST: u64 = 50_000;
+
+/// `FIELD_ELEMENTS_PER_BLOB` and `BLS_MODULUS` as 32 byte big endian numbers.
+const RETURN_VALUE: [u8; 64] = {
+    let mut value = [0; 64];
+    value[30] = 0x10;
+    let modulus = [
+        0x73, 0xed, 0xa7, 0x53, 0x29, 0x9d, 0x7d, 0x48, 0x33, 0x39, 0xd8, 0x08, 0x09, 0xa1, 0xd8,
+        0x05, 0x53, 0xbd, 0xa4, 0x02, 0xff, 0xfe, 0x5b, 0xfe, 0xff, 0xff, 0xff, 0xff, 0x00, 0x00,
+        0x00, 0x01,
+    ];
+    let mut i = 0;
+    while i < 32 {
+        value[32 + i] = modulus[i];
+        i += 1;
+    }
+    value
+};
+
+/// G1 and G2 points of the ceremony, 4096 and 65 of them.
+const G1_POINTS: &[u8] = include_bytes!("kzg_point_evaluation/g1_points.bin");
+const G2_POINTS: &[u8] = include_bytes!("kzg_point_evaluation/g2_points.bin");
+
+/// Trusted setup of the Ethereum KZG ceremony, loaded on first use.
+pub fn ethereum_settings() -> &'static KzgSettings {
+    static SETTINGS: OnceCell<KzgSettings> = OnceCell::new();
+    SETTINGS.get_or_init(|| {
+        let g1: Vec<[u8; BYTES_PER_G1_POINT]> = G1_POINTS
+            .chunks_exact(BYTES_PER_G1_POINT)
+            .map(|point| point.try_into().unwrap())
+            .collect();
+        let g2: Vec<[u8; BYTES_PER_G2_POINT]> = G2_POINTS
+            .chunks_exact(BYTES_PER_G2_POINT)
+            .map(|point| point.try_into().unwrap())
+            .collect();
+        KzgSettings::load_trusted_setup(&g1, &g2).expect("bundled trusted setup is valid")
+    })
+}
+
+/// Precompile verifying with `settings` instead of the Ethereum trusted setup.
+pub fn with_settings(
+    settings: Arc<KzgSettings>,
+) -> impl Fn(&[u8], u64) -> PrecompileResult + Send + Sync {
+    move |input, gas_limit| verify(input, gas_limit, &settings)
+}
+
+fn run(input: &[u8], gas_limit: u64) -> PrecompileResult {
+    verify(input, gas_limit, ethereum_settings())
+}
+
+/// Input is `versioned_hash | z | y | commitment | proof`, 192 bytes.
+fn verify(input: &[u8], gas_limit: u64, settings: &KzgSettings) -> PrecompileResult {
+    if gas_limit < GAS_COST {
+        return Err(Error::OutOfGas);
+    }
+    if input.len() != 192 {
+        return Err(Error::BlobInvalidInputLength);
+    }
+    let commitment = &input[96..144];
+    if kzg_to_versioned_hash(commitment) != input[..32] {
+        return Err(Error::BlobMismatchedVersion);
+    }
+
+    let bytes32 = |range: core::ops::Range<usize>| Bytes32::new(input[range].try_into().unwrap());
+    let bytes48 = |range: core::ops::Range<usize>| Bytes48::new(input[range].try_into().unwrap());
+    let verified = KzgProof::verify_kzg_proof(
+        &bytes48(96..144),
+        &bytes32(32..64),
+        &bytes32(64..96),
+        &bytes48(144..192),
+        settings,
+    );
+    if !matches!(verified, Ok(true)) {
+        return Err(Error::BlobVerifyKzgProofFailed);
+    }
+    Ok((GAS_COST, RETURN_VALUE.to_vec()))
+}
+
+/// Versioned hash of the commitment, its sha256 with the version as first byte.
+pub fn kzg_to_versioned_hash(commitment: &[u8]) -> [u8; 32] {
+    let mut hash: [u8; 32] = Sha256::digest(commitment).into();
+    hash[0] = VERSIONED_HASH_VERSION_KZG;
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn point_evaluation() {
+        // test vector of the `verify_kzg_proof` tests of c-kzg.
+        let commitment = hex::decode("8f59a8d2a1a625a17f3fea0fe5eb8c896db3764f3185481bc22f91b4aaffcca25f26936857bc3a7c2539ea8ec3a952b7").unwrap();
+        let z = hex::decode("73eda753299d7d483339d80809a1d80553bda402fffe5bfeffffffff00000000")
+            .unwrap();
+        let y = hex::decode("1522a4a7f34e1ea350ae07c29c96c7e79655aa926122e95fe69fcbd932ca49e9")
+            .unwrap();
+        let proof = hex::decode("a62ad71d14c5719385c0686f1871430475bf3a00f0aa3f7b8dd99a9abc2160744faf0070725e00b60ad9a026a15b1a8c").unwrap();
+
+        let mut input = kzg_to_versioned_hash(&commitment).to_vec();
+        input.extend_from_slice(&z);
+        input.extend_from_slice(&y);
+        input.extend_from_slice(&commitment);
+        input.extend_from_slice(&proof);
+        let (gas, output) = run(&input, GAS_COST).unwrap();
+        assert_eq!(gas, GAS_COST);
+        assert_eq!(output, RETURN_VALUE);
+
+        assert_eq!(run(&input, GAS_COST - 1), Err(Error::OutOfGas));
+        assert_eq!(
+            run(&input[1..], GAS_COST),
+            Err(Error::BlobInvalidInputLength)
+        );
+        input[0] = 0;
+        assert_eq!(run(&input, GAS_COST), Err(Error::BlobMismatchedVersion));
+        input[0] = VERSIONED_HASH_VERSION_KZG;
+        input[64] ^= 1;
+        assert_eq!(run(&input, GAS_COST), Err(Error::BlobVerifyKzgProofFailed));
+    }
+}
@@ -4,6 +4,8 @@ mod blake2;
 mod bn128;
 mod hash;
 mod identity;
+#[cfg(feature = "c-kzg")]
+pub mod kzg_point_evaluation;
 mod modexp;
 mod secp256k1;
 
@@ -13,6 +15,7 @@ pub use primitives::{
     Bytes, HashMap,
 };
 pub use revm_primitives as primitives;
+pub use secp256k1::recover_address;
 
 pub type B160 = [u8; 20];
 pub type B256 = [u8; 32];
@@ -24,7 +27,7 @@ use alloc::vec::Vec;
 use core::fmt;
 
 pub fn calc_linear_cost_u32(len: usize, base: u64, word: u64) -> u64 {
-    (len as u64 + 32 - 1) / 32 * word + base
+    (len as u64).div_ceil(32) * word + base
 }
 
 #[derive(Debug)]
@@ -58,7 +61,7 @@ pub struct Precompiles {
 
 impl Default for Precompiles {
     fn default() -> Self {
-        Self::new(SpecId::LATEST).clone() //berlin
+        Self::new(SpecId::LATEST).clone() //cancun
     }
 }
 
@@ -66,6 +69,11 @@ impl Default for Precompiles {
 pub enum Precompile {
     Standard(StandardPrecompileFn),
     Custom(CustomPrecompileFn),
+    /// Registered at runtime with [CustomPrecompiles].
+    Dynamic(DynPrecompileFn),
+    /// Registered at runtime with [CustomPrecompiles::insert_stateful], it is called with
+    /// state of the EVM.
+    Stateful(DynStatefulPrecompile),
 }
 
 impl fmt::Debug for Precompile {
@@ -73,6 +81,8 @@ impl fmt::Debug for Precompile {
         match self {
             Precompile::Standard(_) => f.write_str("Standard"),
             Precompile::Custom(_) => f.write_str("Custom"),
+            Precompile::Dynamic(_) => f.write_str("Dynamic"),
+            Precompile::Stateful(_) => f.write_str("Stateful"),
         }
     }
 }
@@ -91,7 +101,8 @@ pub enum SpecId {
     BYZANTIUM = 1,
     ISTANBUL = 2,
     BERLIN = 3,
-    LATEST = 4,
+    CANCUN = 4,
+    LATEST = 5,
 }
 
 impl SpecId {
@@ -104,10 +115,9 @@ impl SpecId {
             }
             BYZANTIUM | CONSTANTINOPLE | PETERSBURG => Self::BYZANTIUM,
             ISTANBUL | MUIR_GLACIER => Self::ISTANBUL,
-            BERLIN | LONDON | ARROW_GLACIER | GRAY_GLACIER | MERGE | SHANGHAI | CANCUN => {
-                Self::BERLIN
-            }
-            LATEST => Self::LATEST,
+            BERLIN | LONDON | ARROW_GLACIER | GRAY_GLACIER | MERGE | SHANGHAI => Self::BERLIN,
+            CANCUN | PRAGUE => Self::CANCUN,
+            LATEST | OSAKA => Self::LATEST,
         }
     }
 
@@ -190,8 +200,27 @@ impl Precompiles {
         })
     }
 
+    /// EIP-4844 point evaluation is added only with the `c-kzg` feature.
+    pub fn cancun() -> &'static Self {
+        static INSTANCE: OnceCell<Precompiles> = OnceCell::new();
+        INSTANCE.get_or_init(|| {
+            #[allow(unused_mut)]
+            let mut precompiles = Self::berlin().clone();
+            #[cfg(feature = "c-kzg")]
+            precompiles.fun.extend(
+                vec![
+                    // EIP-4844: Shard Blob Transactions.
+                    kzg_point_evaluation::POINT_EVALUATION,
+                ]
+                .into_iter()
+                .map(From::from),
+            );
+            precompiles
+        })
+    }
+
     pub fn latest() -> &'static Self {
-        Self::berlin()
+        Self::cancun()
     }
 
     pub fn new(spec: SpecId) -> &'static Self {
@@ -200,6 +229,7 @@ impl Precompiles {
             SpecId::BYZANTIUM => Self::byzantium(),
             SpecId::ISTANBUL => Self::istanbul(),
             SpecId::BERLIN => Self::berlin(),
+            SpecId::CANCUN => Self::cancun(),
             SpecId::LATEST => Self::latest(),
         }
     }
@@ -224,6 +254,17 @@ impl Precompiles {
     pub fn len(&self) -> usize {
         self.fun.len()
     }
+
+    /// Add custom precompiles, replacing ones at the same addresses.
+    pub fn extend_custom(&mut self, custom: &CustomPrecompiles) {
+        self.fun.extend(custom.iter().map(|(address, precompile)| {
+            let precompile = match precompile {
+                CustomPrecompile::Pure(fun) => Precompile::Dynamic(fun.clone()),
+                CustomPrecompile::Stateful(fun) => Precompile::Stateful(fun.clone()),
+            };
+            (address.0, precompile)
+        }));
+    }
 }
 
 /// const fn for making an address by concatenating the bytes from two given numbers,
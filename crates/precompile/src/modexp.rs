@@ -6,7 +6,7 @@ use core::{
     cmp::{max, min, Ordering},
     mem::size_of,
 };
-use num::{BigUint, One, Zero};
+use num::{BigUint, Zero};
 
 pub const BYZANTIUM: PrecompileAddress = PrecompileAddress(
     crate::u64_to_b160(5),
@@ -80,8 +80,8 @@ where
         return Err(Error::ModexpModOverflow);
     }
 
-    let (r, gas_cost) = if base_len == 0 && mod_len == 0 {
-        (BigUint::zero(), min_gas)
+    let (bytes, gas_cost) = if base_len == 0 && mod_len == 0 {
+        (Vec::new(), min_gas)
     } else {
         // set limit for exp overflow
         if exp_overflow {
@@ -108,34 +108,28 @@ where
             return Err(Error::OutOfGas);
         }
 
-        let read_big = |from: usize, to: usize| {
+        let read_bytes = |from: usize, to: usize| {
             let mut out = vec![0; to - from];
             let from = min(from, len);
             let to = min(to, len);
             out[..to - from].copy_from_slice(&input[from..to]);
-            BigUint::from_bytes_be(&out)
+            out
         };
 
-        let base = read_big(base_start, base_end);
-        let exponent = read_big(base_end, exp_end);
-        let modulus = read_big(exp_end, mod_end);
+        let base = read_bytes(base_start, base_end);
+        let exponent = read_bytes(base_end, exp_end);
+        let modulus = read_bytes(exp_end, mod_end);
 
-        if modulus.is_zero() || modulus.is_one() {
-            (BigUint::zero(), gas_cost)
-        } else {
-            (base.modpow(&exponent, &modulus), gas_cost)
-        }
+        (modexp(&base, &exponent, &modulus), gas_cost)
     };
 
     // write output to given memory, left padded and same length as the modulus.
-    let bytes = r.to_bytes_be();
-    // always true except in the case of zero-length modulus, which leads to
-    // output of length and value 1.
+    // result is less than the modulus, so it is never longer.
     match bytes.len().cmp(&mod_len) {
         Ordering::Equal => Ok((gas_cost, bytes)),
         Ordering::Less => {
             let mut ret = Vec::with_capacity(mod_len);
-            ret.extend(core::iter::repeat(0).take(mod_len - bytes.len()));
+            ret.extend(core::iter::repeat_n(0, mod_len - bytes.len()));
             ret.extend_from_slice(&bytes[..]);
             Ok((gas_cost, ret))
         }
@@ -143,6 +137,32 @@ where
     }
 }
 
+/// `base ^ exponent % modulus` of big-endian numbers, without leading zeros.
+#[cfg(not(feature = "aurora-engine-modexp"))]
+fn modexp(base: &[u8], exponent: &[u8], modulus: &[u8]) -> Vec<u8> {
+    use num::One;
+
+    let modulus = BigUint::from_bytes_be(modulus);
+    if modulus.is_zero() || modulus.is_one() {
+        return Vec::new();
+    }
+    let base = BigUint::from_bytes_be(base);
+    base.modpow(&BigUint::from_bytes_be(exponent), &modulus)
+        .to_bytes_be()
+}
+
+/// `base ^ exponent % modulus` of big-endian numbers, without leading zeros.
+#[cfg(feature = "aurora-engine-modexp")]
+fn modexp(base: &[u8], exponent: &[u8], modulus: &[u8]) -> Vec<u8> {
+    if modulus.iter().all(|byte| *byte == 0) {
+        return Vec::new();
+    }
+    let mut out = aurora_engine_modexp::modexp(base, exponent, modulus);
+    let zeros = out.iter().take_while(|byte| **byte == 0).count();
+    out.drain(..zeros);
+    out
+}
+
 fn byzantium_gas_calc(base_len: u64, exp_len: u64, mod_len: u64, exp_highp: &BigUint) -> u64 {
     // ouput of this function is bounded by 2^128
     fn mul_complexity(x: u64) -> U256 {
@@ -175,10 +195,7 @@ fn byzantium_gas_calc(base_len: u64, exp_len: u64, mod_len: u64, exp_highp: &Big
 fn berlin_gas_calc(base_length: u64, exp_length: u64, mod_length: u64, exp_highp: &BigUint) -> u64 {
     fn calculate_multiplication_complexity(base_length: u64, mod_length: u64) -> U256 {
         let max_length = max(base_length, mod_length);
-        let mut words = max_length / 8;
-        if max_length % 8 > 0 {
-            words += 1;
-        }
+        let words = max_length.div_ceil(8);
         let words = U256::from(words);
         words * words
     }
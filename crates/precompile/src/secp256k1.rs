@@ -1,4 +1,6 @@
-use crate::{Error, Precompile, PrecompileAddress, PrecompileResult, StandardPrecompileFn};
+use crate::{
+    Error, Precompile, PrecompileAddress, PrecompileResult, StandardPrecompileFn, B160, B256,
+};
 
 pub const ECRECOVER: PrecompileAddress = PrecompileAddress(
     crate::u64_to_b160(1),
@@ -59,6 +61,14 @@ mod secp256k1 {
     }
 }
 
+/// Address of the key that signed `msg`, `sig` is `r | s | recovery id`. None if the
+/// signature is invalid.
+pub fn recover_address(sig: &[u8; 65], msg: &B256) -> Option<B160> {
+    secp256k1::ecrecover(sig, msg)
+        .ok()
+        .map(|hash| hash[12..].try_into().unwrap())
+}
+
 fn ec_recover_run(i: &[u8], target_gas: u64) -> PrecompileResult {
     use alloc::vec::Vec;
     use core::cmp::min;
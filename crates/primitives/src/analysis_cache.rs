@@ -0,0 +1,218 @@
+//! Cache of analysed bytecode shared between executions.
+//!
+//! Jump destinations of a contract are analysed every time its code is loaded from the
+//! database. [AnalysisCache] keeps analysed bytecode by code hash, so calls to hot contracts
+//! reuse it. Analysed bytecode shares its code and jump map, cached entries are cheap to
+//! clone.
+use crate::{Bytecode, BytecodeState, HashMap, B256, KECCAK_EMPTY};
+use alloc::collections::BTreeMap;
+use core::fmt;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// Hits and misses of [AnalysisCache::get_or_analyse].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct AnalysisCacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    /// Entries removed to stay under capacity.
+    pub evictions: u64,
+}
+
+#[derive(Default)]
+struct Entries {
+    capacity: usize,
+    /// Analysed bytecode and tick it was last used at.
+    bytecodes: HashMap<B256, (Bytecode, u64)>,
+    /// Code hashes by tick, oldest first.
+    order: BTreeMap<u64, B256>,
+    tick: u64,
+    stats: AnalysisCacheStats,
+}
+
+impl Entries {
+    fn evict(&mut self) {
+        while self.bytecodes.len() > self.capacity {
+            let Some((_, hash)) = self.order.pop_first() else {
+                break;
+            };
+            self.bytecodes.remove(&hash);
+            self.stats.evictions += 1;
+        }
+    }
+}
+
+/// Analysed bytecode by code hash, least recently used entries are evicted above capacity.
+///
+/// It is a handle, clones share the same cache, so one cache can be set to
+/// [crate::CfgEnv::analysis_cache] of EVMs on different threads.
+#[derive(Clone, Default)]
+pub struct AnalysisCache {
+    entries: Arc<Mutex<Entries>>,
+}
+
+impl AnalysisCache {
+    /// Cache holding at most `capacity` bytecodes.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: Arc::new(Mutex::new(Entries {
+                capacity,
+                ..Default::default()
+            })),
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.lock().capacity
+    }
+
+    /// Change capacity, entries above it are evicted.
+    pub fn set_capacity(&self, capacity: usize) {
+        let mut entries = self.lock();
+        entries.capacity = capacity;
+        entries.evict();
+    }
+
+    pub fn len(&self) -> usize {
+        self.lock().bytecodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn stats(&self) -> AnalysisCacheStats {
+        self.lock().stats
+    }
+
+    /// Remove all entries, stats are kept.
+    pub fn clear(&self) {
+        let mut entries = self.lock();
+        entries.bytecodes.clear();
+        entries.order.clear();
+    }
+
+    /// Analysed `bytecode`, from the cache if code with its hash was analysed before,
+    /// otherwise `analyse` is run and the result cached. Analysed and empty bytecode is
+    /// returned as is.
+    pub fn get_or_analyse(
+        &self,
+        bytecode: Bytecode,
+        analyse: impl FnOnce(Bytecode) -> Bytecode,
+    ) -> Bytecode {
+        if bytecode.hash == KECCAK_EMPTY || matches!(bytecode.state, BytecodeState::Analysed { .. })
+        {
+            return bytecode;
+        }
+        let hash = bytecode.hash;
+        let mut entries = self.lock();
+        entries.tick += 1;
+        let tick = entries.tick;
+        if let Some((cached, used)) = entries.bytecodes.get_mut(&hash) {
+            let cached = cached.clone();
+            let last = core::mem::replace(used, tick);
+            entries.order.remove(&last);
+            entries.order.insert(tick, hash);
+            entries.stats.hits += 1;
+            return cached;
+        }
+        entries.stats.misses += 1;
+        let capacity = entries.capacity;
+        // analysis doesn't need the lock, other threads keep using the cache.
+        drop(entries);
+        let analysed = analyse(bytecode);
+        if capacity == 0 {
+            return analysed;
+        }
+        let mut entries = self.lock();
+        if let Some((_, used)) = entries.bytecodes.insert(hash, (analysed.clone(), tick)) {
+            // other thread analysed same code meanwhile.
+            entries.order.remove(&used);
+        }
+        entries.order.insert(tick, hash);
+        entries.evict();
+        analysed
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Entries> {
+        // entries are consistent between statements, poisoned lock can be used.
+        self.entries
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Caches are same if they are handles of the same cache.
+impl PartialEq for AnalysisCache {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.entries, &other.entries)
+    }
+}
+
+impl Eq for AnalysisCache {}
+
+impl fmt::Debug for AnalysisCache {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let entries = self.lock();
+        f.debug_struct("AnalysisCache")
+            .field("capacity", &entries.capacity)
+            .field("len", &entries.bytecodes.len())
+            .field("stats", &entries.stats)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Bytes, JumpMap};
+    use bitvec::vec::BitVec;
+    use core::cell::Cell;
+
+    fn analysed(bytecode: Bytecode) -> Bytecode {
+        let len = bytecode.bytecode.len();
+        Bytecode {
+            state: BytecodeState::Analysed {
+                len,
+                jump_map: JumpMap(Arc::new(BitVec::new())),
+            },
+            ..bytecode
+        }
+    }
+
+    #[test]
+    fn caches_by_code_hash() {
+        let cache = AnalysisCache::new(2);
+        let code = |byte: u8| Bytecode::new_raw(Bytes::from(alloc::vec![byte]));
+        let runs = Cell::new(0);
+        let analyse = |bytecode| {
+            runs.set(runs.get() + 1);
+            analysed(bytecode)
+        };
+
+        cache.get_or_analyse(code(1), analyse);
+        let hit = cache.get_or_analyse(code(1), analyse);
+        assert!(matches!(hit.state, BytecodeState::Analysed { .. }));
+        cache.get_or_analyse(code(2), analyse);
+        // code 1 is used again, code 2 is evicted by code 3.
+        cache.get_or_analyse(code(1), analyse);
+        cache.get_or_analyse(code(3), analyse);
+        cache.get_or_analyse(code(2), analyse);
+        assert_eq!(runs.get(), 4);
+        assert_eq!(cache.len(), 2);
+        assert_eq!(
+            cache.stats(),
+            AnalysisCacheStats {
+                hits: 2,
+                misses: 4,
+                evictions: 2,
+            }
+        );
+
+        // empty code is not cached.
+        cache.get_or_analyse(Bytecode::new(), analyse);
+        assert_eq!(runs.get(), 4);
+        cache.set_capacity(0);
+        assert!(cache.is_empty());
+        assert_eq!(cache.clone(), cache);
+    }
+}
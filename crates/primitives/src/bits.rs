@@ -20,6 +20,55 @@ construct_fixed_hash! {
     pub struct B160(20);
 }
 
+construct_fixed_hash! {
+    /// 2048 bits bloom filter of logs, of receipts and block headers.
+    #[derive(AsRef,Deref)]
+    pub struct Bloom(256);
+}
+
+impl Bloom {
+    /// Set the three bits of `input`, picked by the first six bytes of its hash.
+    pub fn accrue(&mut self, input: &[u8]) {
+        let hash = crate::keccak256(input);
+        for i in [0, 2, 4] {
+            let bit = (u16::from_be_bytes([hash[i], hash[i + 1]]) & 2047) as usize;
+            self.0[255 - bit / 8] |= 1 << (bit % 8);
+        }
+    }
+
+    /// Add address and topics of the log.
+    pub fn accrue_log(&mut self, log: &crate::Log) {
+        self.accrue(log.address.as_bytes());
+        for topic in &log.topics {
+            self.accrue(topic.as_bytes());
+        }
+    }
+
+    /// Add the bits of `bloom`, as bloom of a block has the bits of its receipts.
+    pub fn accrue_bloom(&mut self, bloom: &Bloom) {
+        *self |= *bloom;
+    }
+
+    /// If `input` may have been added, it was not if it returns false.
+    pub fn contains_input(&self, input: &[u8]) -> bool {
+        let mut bloom = Bloom::zero();
+        bloom.accrue(input);
+        self.contains_bloom(&bloom)
+    }
+
+    pub fn contains_bloom(&self, bloom: &Bloom) -> bool {
+        (*self & *bloom) == *bloom
+    }
+
+    pub fn from_logs<'a>(logs: impl IntoIterator<Item = &'a crate::Log>) -> Self {
+        let mut bloom = Bloom::zero();
+        for log in logs {
+            bloom.accrue_log(log);
+        }
+        bloom
+    }
+}
+
 impl From<u64> for B160 {
     fn from(fr: u64) -> Self {
         let x_bytes = fr.to_be_bytes();
@@ -126,6 +175,29 @@ impl<'de> serde::Deserialize<'de> for B160 {
     }
 }
 
+#[cfg(feature = "serde")]
+impl serde::Serialize for Bloom {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        let mut slice = [0u8; 2 + 2 * 256];
+        serialize::serialize_raw(&mut slice, &self.0, serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Bloom {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let mut bytes = [0u8; 256];
+        serialize::deserialize_check_len(deserializer, serialize::ExpectedLen::Exact(&mut bytes))?;
+        Ok(Bloom(bytes))
+    }
+}
+
 // code optained from: https://docs.rs/impl-serde/0.4.0/impl_serde/
 #[cfg(feature = "serde")]
 mod serialize {
@@ -366,6 +438,28 @@ mod tests {
         assert_eq!(b256, new_b256)
     }
 
+    #[test]
+    fn bloom() {
+        // keccak256 of empty input is c5d2 4601 86f7..., bits 1490, 1537 and 1783.
+        let mut bloom = Bloom::zero();
+        bloom.accrue(&[]);
+        let mut expected = Bloom::zero();
+        expected.0[255 - 1490 / 8] = 1 << (1490 % 8);
+        expected.0[255 - 1537 / 8] = 1 << (1537 % 8);
+        expected.0[255 - 1783 / 8] = 1 << (1783 % 8);
+        assert_eq!(bloom, expected);
+
+        let log = crate::Log {
+            address: B160([1; 20]),
+            topics: vec![B256([2; 32])],
+            data: Default::default(),
+        };
+        let bloom = Bloom::from_logs([&log]);
+        assert!(bloom.contains_input(&[1; 20]));
+        assert!(bloom.contains_input(&[2; 32]));
+        assert!(!bloom.contains_input(&[3; 32]));
+    }
+
     #[test]
     fn should_convert_to_ruint_u256() {
         let b256 = B256::random();
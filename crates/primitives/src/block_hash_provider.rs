@@ -0,0 +1,213 @@
+//! Hashes returned by `BLOCKHASH`.
+//!
+//! Which blocks are available and where their hashes come from depends on the chain. A
+//! [BlockHashProvider] set to [crate::CfgEnv::block_hashes] decides it, by default
+//! [DatabaseBlockHashes] follows the Ethereum rules: hashes of the last 256 blocks in every
+//! spec. EIP-2935 doesn't change `BLOCKHASH`, its history contract is only read by calling
+//! it, chains that serve the longer history from `BLOCKHASH` opt in with [HistoryStorage].
+use crate::{SpecId, B160, B256, U256};
+use alloc::sync::Arc;
+use core::fmt;
+use hex_literal::hex;
+
+/// Number of previous blocks `BLOCKHASH` returns hashes of.
+pub const BLOCK_HASH_HISTORY: u64 = 256;
+/// Number of block hashes kept by the EIP-2935 history contract.
+pub const HISTORY_SERVE_WINDOW: u64 = 8191;
+/// Address of the EIP-2935 history contract.
+pub const HISTORY_STORAGE_ADDRESS: B160 = B160(hex!("0000f90827f1c53a10cb7a02335b175320002935"));
+
+/// Access of a [BlockHashProvider] to hashes known to the database and to the state.
+///
+/// None is returned if the database failed, the error is reported by the EVM.
+pub trait BlockHashLookup {
+    /// Hash of block `number` from the database.
+    fn database(&mut self, number: u64) -> Option<B256>;
+
+    /// Current value of storage slot `index` of `address`.
+    fn storage(&mut self, address: B160, index: U256) -> Option<U256>;
+}
+
+/// Source of hashes of previous blocks.
+pub trait BlockHashProvider: Send + Sync {
+    /// Hash of block `number` requested in block `current`, `number` is less than
+    /// `current`. Zero hash is returned for blocks that are not available.
+    fn block_hash(
+        &self,
+        lookup: &mut dyn BlockHashLookup,
+        spec_id: SpecId,
+        number: u64,
+        current: u64,
+    ) -> Option<B256>;
+}
+
+/// Hashes of the last `window` blocks from the database, 256 by default.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DatabaseBlockHashes {
+    pub window: u64,
+}
+
+impl Default for DatabaseBlockHashes {
+    fn default() -> Self {
+        Self {
+            window: BLOCK_HASH_HISTORY,
+        }
+    }
+}
+
+impl BlockHashProvider for DatabaseBlockHashes {
+    fn block_hash(
+        &self,
+        lookup: &mut dyn BlockHashLookup,
+        _spec_id: SpecId,
+        number: u64,
+        current: u64,
+    ) -> Option<B256> {
+        if current - number > self.window {
+            return Some(B256::zero());
+        }
+        lookup.database(number)
+    }
+}
+
+/// Hashes of the last `window` blocks kept in storage of a history contract, hash of block
+/// `number` is in slot `number % window`.
+///
+/// Hashes not yet in the contract, as in the blocks after it is deployed, are read from the
+/// database for the last 256 blocks.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct HistoryStorage {
+    pub address: B160,
+    pub window: u64,
+}
+
+impl HistoryStorage {
+    /// History contract of EIP-2935.
+    pub const fn eip2935() -> Self {
+        Self {
+            address: HISTORY_STORAGE_ADDRESS,
+            window: HISTORY_SERVE_WINDOW,
+        }
+    }
+}
+
+impl BlockHashProvider for HistoryStorage {
+    fn block_hash(
+        &self,
+        lookup: &mut dyn BlockHashLookup,
+        spec_id: SpecId,
+        number: u64,
+        current: u64,
+    ) -> Option<B256> {
+        if self.window != 0 && current - number <= self.window {
+            let value = lookup.storage(self.address, U256::from(number % self.window))?;
+            if value != U256::ZERO {
+                return Some(B256(value.to_be_bytes()));
+            }
+        }
+        DatabaseBlockHashes::default().block_hash(lookup, spec_id, number, current)
+    }
+}
+
+/// [BlockHashProvider] of the EVM, [DatabaseBlockHashes] by default.
+#[derive(Clone)]
+pub struct BlockHashes(Arc<dyn BlockHashProvider>);
+
+impl BlockHashes {
+    pub fn new(provider: impl BlockHashProvider + 'static) -> Self {
+        Self(Arc::new(provider))
+    }
+
+    pub fn block_hash(
+        &self,
+        lookup: &mut dyn BlockHashLookup,
+        spec_id: SpecId,
+        number: u64,
+        current: u64,
+    ) -> Option<B256> {
+        self.0.block_hash(lookup, spec_id, number, current)
+    }
+}
+
+impl Default for BlockHashes {
+    fn default() -> Self {
+        Self::new(DatabaseBlockHashes::default())
+    }
+}
+
+/// Providers are same if they are the same instance.
+impl PartialEq for BlockHashes {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for BlockHashes {}
+
+impl fmt::Debug for BlockHashes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("BlockHashes").finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::HashMap;
+
+    #[derive(Default)]
+    struct Lookup {
+        storage: HashMap<U256, U256>,
+    }
+
+    impl BlockHashLookup for Lookup {
+        fn database(&mut self, number: u64) -> Option<B256> {
+            Some(B256::from_low_u64_be(number))
+        }
+
+        fn storage(&mut self, address: B160, index: U256) -> Option<U256> {
+            assert_eq!(address, HISTORY_STORAGE_ADDRESS);
+            Some(self.storage.get(&index).copied().unwrap_or_default())
+        }
+    }
+
+    #[test]
+    fn block_hashes() {
+        let mut lookup = Lookup::default();
+        lookup
+            .storage
+            .insert(U256::from(10_000 % HISTORY_SERVE_WINDOW), U256::from(7));
+        let hash = |provider: &dyn BlockHashProvider, lookup: &mut Lookup, spec_id, number| {
+            provider
+                .block_hash(lookup, spec_id, number, 11_000)
+                .unwrap()
+        };
+
+        // EIP-2935 doesn't change the 256 blocks window.
+        let default = DatabaseBlockHashes::default();
+        for spec_id in [SpecId::CANCUN, SpecId::PRAGUE] {
+            assert_eq!(hash(&default, &mut lookup, spec_id, 10_000), B256::zero());
+            assert_eq!(
+                hash(&default, &mut lookup, spec_id, 10_900),
+                B256::from_low_u64_be(10_900)
+            );
+        }
+
+        let history = HistoryStorage::eip2935();
+        assert_eq!(
+            hash(&history, &mut lookup, SpecId::PRAGUE, 10_000),
+            B256::from_low_u64_be(7)
+        );
+        // not in the contract but in the last 256 blocks.
+        assert_eq!(
+            hash(&history, &mut lookup, SpecId::PRAGUE, 10_900),
+            B256::from_low_u64_be(10_900)
+        );
+
+        let window = DatabaseBlockHashes { window: 1_000 };
+        assert_eq!(
+            hash(&window, &mut lookup, SpecId::CANCUN, 10_000),
+            B256::from_low_u64_be(10_000)
+        );
+    }
+}
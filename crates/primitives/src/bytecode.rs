@@ -1,8 +1,49 @@
-use crate::{keccak256, B256, KECCAK_EMPTY};
+use crate::eip7702::{DELEGATION_CODE_LEN, DELEGATION_MAGIC};
+use crate::{keccak256, Eof, EofDecodeError, B160, B256, KECCAK_EMPTY};
 use alloc::{sync::Arc, vec, vec::Vec};
 use bitvec::prelude::{bitvec, Lsb0};
 use bitvec::vec::BitVec;
 use bytes::Bytes;
+use core::fmt;
+
+/// Function computing hash of contract code, keccak256 by default.
+///
+/// Hash of empty code is always [KECCAK_EMPTY], as that is how accounts without code are
+/// recognized.
+#[derive(Clone, Copy)]
+pub struct CodeHasher(pub fn(&[u8]) -> B256);
+
+impl CodeHasher {
+    pub fn hash(&self, code: &[u8]) -> B256 {
+        if code.is_empty() {
+            KECCAK_EMPTY
+        } else {
+            (self.0)(code)
+        }
+    }
+}
+
+impl Default for CodeHasher {
+    fn default() -> Self {
+        Self(keccak256)
+    }
+}
+
+impl PartialEq for CodeHasher {
+    fn eq(&self, other: &Self) -> bool {
+        self.0 as usize == other.0 as usize
+    }
+}
+
+impl Eq for CodeHasher {}
+
+impl fmt::Debug for CodeHasher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("CodeHasher")
+            .field(&(self.0 as usize))
+            .finish()
+    }
+}
 
 /// A map of valid `jump` destinations.
 #[derive(Clone, Debug, Eq, PartialEq, Default)]
@@ -75,10 +116,20 @@ impl Bytecode {
         }
     }
 
+    /// Create new raw Bytecode hashed by `hasher`.
+    pub fn new_raw_with_hasher(bytecode: Bytes, hasher: &CodeHasher) -> Self {
+        Self {
+            hash: hasher.hash(&bytecode),
+            bytecode,
+            state: BytecodeState::Raw,
+        }
+    }
+
     /// Create new raw Bytecode with hash
     ///
     /// # Safety
-    /// Hash need to be appropriate keccak256 over bytecode.
+    /// Hash need to be appropriate keccak256 over bytecode, or hash of [CodeHasher] that is
+    /// used by the chain.
     pub unsafe fn new_raw_with_hash(bytecode: Bytes, hash: B256) -> Self {
         Self {
             bytecode,
@@ -122,6 +173,33 @@ impl Bytecode {
         self.hash
     }
 
+    /// If code starts with [EOF magic](crate::EOF_MAGIC), it may still be malformed.
+    pub fn is_eof(&self) -> bool {
+        Eof::is_eof(&self.bytecode[..self.len()])
+    }
+
+    /// EIP-7702: Code of an account delegating to `address`, hashed with keccak256.
+    pub fn new_delegation(address: B160) -> Self {
+        let mut code = Vec::with_capacity(DELEGATION_CODE_LEN);
+        code.extend_from_slice(&DELEGATION_MAGIC);
+        code.extend_from_slice(&address[..]);
+        Self::new_raw(code.into())
+    }
+
+    /// EIP-7702: Address the code delegates to, none if it is not a delegation designator.
+    pub fn delegation_address(&self) -> Option<B160> {
+        let code = &self.bytecode[..self.len()];
+        if code.len() != DELEGATION_CODE_LEN || !code.starts_with(&DELEGATION_MAGIC) {
+            return None;
+        }
+        Some(B160::from_slice(&code[DELEGATION_MAGIC.len()..]))
+    }
+
+    /// Decode code as EOF container.
+    pub fn decode_eof(&self) -> Result<Eof, EofDecodeError> {
+        Eof::decode(self.original_bytes())
+    }
+
     pub fn state(&self) -> &BytecodeState {
         &self.state
     }
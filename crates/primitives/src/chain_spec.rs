@@ -0,0 +1,251 @@
+//! Hardfork schedules of chains.
+//!
+//! [ChainSpec] maps block numbers and timestamps to the [SpecId] active at them, so
+//! historical blocks can be executed with the right rules. Chains that adopted EIP-161
+//! state clearing apart from Spurious Dragon schedule it on its own, see
+//! [ChainSpec::is_state_clear_at].
+use crate::SpecId;
+use alloc::collections::BTreeMap;
+
+/// When a hardfork activates. Forks before the merge activate at a block, forks after it at
+/// a timestamp.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+pub enum ForkCondition {
+    Block(u64),
+    Timestamp(u64),
+}
+
+impl ForkCondition {
+    /// If fork is active in block `number` with `timestamp`.
+    pub fn is_active(&self, number: u64, timestamp: u64) -> bool {
+        match *self {
+            Self::Block(block) => number >= block,
+            Self::Timestamp(time) => timestamp >= time,
+        }
+    }
+}
+
+/// Hardfork schedule of a chain.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+pub struct ChainSpec {
+    pub chain_id: u64,
+    /// Scheduled forks, forks that are not scheduled never activate.
+    forks: BTreeMap<SpecId, ForkCondition>,
+    /// Activation of EIP-161 state clearing, none if it activates with Spurious Dragon.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    state_clear: Option<ForkCondition>,
+}
+
+impl ChainSpec {
+    /// Schedule of a chain, with forks added by the builder.
+    pub fn builder(chain_id: u64) -> ChainSpecBuilder {
+        ChainSpecBuilder {
+            spec: Self {
+                chain_id,
+                forks: BTreeMap::new(),
+                state_clear: None,
+            },
+        }
+    }
+
+    /// Ethereum mainnet.
+    pub fn mainnet() -> Self {
+        use ForkCondition::*;
+        Self::builder(1)
+            .fork(SpecId::FRONTIER, Block(0))
+            .fork(SpecId::FRONTIER_THAWING, Block(200_000))
+            .fork(SpecId::HOMESTEAD, Block(1_150_000))
+            .fork(SpecId::DAO_FORK, Block(1_920_000))
+            .fork(SpecId::TANGERINE, Block(2_463_000))
+            .fork(SpecId::SPURIOUS_DRAGON, Block(2_675_000))
+            .fork(SpecId::BYZANTIUM, Block(4_370_000))
+            .fork(SpecId::CONSTANTINOPLE, Block(7_280_000))
+            .fork(SpecId::PETERSBURG, Block(7_280_000))
+            .fork(SpecId::ISTANBUL, Block(9_069_000))
+            .fork(SpecId::MUIR_GLACIER, Block(9_200_000))
+            .fork(SpecId::BERLIN, Block(12_244_000))
+            .fork(SpecId::LONDON, Block(12_965_000))
+            .fork(SpecId::ARROW_GLACIER, Block(13_773_000))
+            .fork(SpecId::GRAY_GLACIER, Block(15_050_000))
+            .fork(SpecId::MERGE, Block(15_537_394))
+            .fork(SpecId::SHANGHAI, Timestamp(1_681_338_455))
+            .fork(SpecId::CANCUN, Timestamp(1_710_338_135))
+            .fork(SpecId::PRAGUE, Timestamp(1_746_612_311))
+            .build()
+    }
+
+    /// Sepolia testnet, it started with London rules.
+    pub fn sepolia() -> Self {
+        use ForkCondition::*;
+        Self::builder(11_155_111)
+            .fork(SpecId::LONDON, Block(0))
+            .fork(SpecId::MERGE, Block(1_735_371))
+            .fork(SpecId::SHANGHAI, Timestamp(1_677_557_088))
+            .fork(SpecId::CANCUN, Timestamp(1_706_655_072))
+            .fork(SpecId::PRAGUE, Timestamp(1_741_159_776))
+            .build()
+    }
+
+    /// Holesky testnet, it started after the merge.
+    pub fn holesky() -> Self {
+        use ForkCondition::*;
+        Self::builder(17_000)
+            .fork(SpecId::MERGE, Block(0))
+            .fork(SpecId::SHANGHAI, Timestamp(1_696_000_704))
+            .fork(SpecId::CANCUN, Timestamp(1_707_305_664))
+            .fork(SpecId::PRAGUE, Timestamp(1_740_434_112))
+            .build()
+    }
+
+    /// Activation of `spec`, None if it is not scheduled.
+    pub fn fork(&self, spec: SpecId) -> Option<ForkCondition> {
+        self.forks.get(&spec).copied()
+    }
+
+    /// Activation of EIP-161 state clearing if it is scheduled apart from Spurious Dragon.
+    pub fn state_clear(&self) -> Option<ForkCondition> {
+        self.state_clear
+    }
+
+    /// If EIP-161 state clearing rules apply in block `number` with `timestamp`, value of
+    /// [CfgEnv::state_clear](crate::CfgEnv::state_clear) for the block.
+    pub fn is_state_clear_at(&self, number: u64, timestamp: u64) -> bool {
+        match self.state_clear {
+            Some(condition) => condition.is_active(number, timestamp),
+            None => SpecId::enabled(self.spec_at(number, timestamp), SpecId::SPURIOUS_DRAGON),
+        }
+    }
+
+    /// Scheduled forks ordered by spec.
+    pub fn forks(&self) -> impl Iterator<Item = (SpecId, ForkCondition)> + '_ {
+        self.forks
+            .iter()
+            .map(|(spec, condition)| (*spec, *condition))
+    }
+
+    /// Latest spec active in block `number` with `timestamp`, [SpecId::FRONTIER] if no fork
+    /// is active.
+    pub fn spec_at(&self, number: u64, timestamp: u64) -> SpecId {
+        self.forks
+            .iter()
+            .rev()
+            .find(|(_, condition)| condition.is_active(number, timestamp))
+            .map_or(SpecId::FRONTIER, |(spec, _)| *spec)
+    }
+}
+
+/// Builder of [ChainSpec], see [ChainSpec::builder].
+#[derive(Clone, Debug)]
+pub struct ChainSpecBuilder {
+    spec: ChainSpec,
+}
+
+impl ChainSpecBuilder {
+    /// Schedule `spec` at `condition`. Specs before it that are not scheduled are skipped,
+    /// their rules are included in the later specs.
+    pub fn fork(mut self, spec: SpecId, condition: ForkCondition) -> Self {
+        self.spec.forks.insert(spec, condition);
+        self
+    }
+
+    /// Remove `spec` from the schedule.
+    pub fn without_fork(mut self, spec: SpecId) -> Self {
+        self.spec.forks.remove(&spec);
+        self
+    }
+
+    /// Activate EIP-161 state clearing at `condition` instead of with Spurious Dragon.
+    pub fn state_clear(mut self, condition: ForkCondition) -> Self {
+        self.spec.state_clear = Some(condition);
+        self
+    }
+
+    /// State clearing never activates, for chains that didn't adopt EIP-161.
+    pub fn without_state_clear(self) -> Self {
+        self.state_clear(ForkCondition::Block(u64::MAX))
+    }
+
+    pub fn build(self) -> ChainSpec {
+        self.spec
+    }
+}
+
+impl From<ChainSpec> for ChainSpecBuilder {
+    /// Builder starting from schedule of `spec`, to change forks of a known chain.
+    fn from(spec: ChainSpec) -> Self {
+        Self { spec }
+    }
+}
+
+impl SpecId {
+    /// Spec active in block `number` with `timestamp` on chain of `chain_spec`.
+    pub fn from_block(chain_spec: &ChainSpec, number: u64, timestamp: u64) -> SpecId {
+        chain_spec.spec_at(number, timestamp)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spec_at_block() {
+        let mainnet = ChainSpec::mainnet();
+        assert_eq!(SpecId::from_block(&mainnet, 0, 0), SpecId::FRONTIER);
+        assert_eq!(
+            SpecId::from_block(&mainnet, 7_280_000, 0),
+            SpecId::PETERSBURG
+        );
+        assert_eq!(
+            SpecId::from_block(&mainnet, 15_537_393, 0),
+            SpecId::GRAY_GLACIER
+        );
+        assert_eq!(
+            SpecId::from_block(&mainnet, 17_034_870, 1_681_338_455),
+            SpecId::SHANGHAI
+        );
+        assert_eq!(
+            SpecId::from_block(&mainnet, 19_426_587, 1_710_338_135),
+            SpecId::CANCUN
+        );
+        assert_eq!(
+            SpecId::from_block(&ChainSpec::sepolia(), 100, 0),
+            SpecId::LONDON
+        );
+
+        let custom = ChainSpecBuilder::from(ChainSpec::holesky())
+            .without_fork(SpecId::PRAGUE)
+            .fork(SpecId::CANCUN, ForkCondition::Block(10))
+            .build();
+        assert_eq!(custom.chain_id, 17_000);
+        assert_eq!(custom.spec_at(9, u64::MAX), SpecId::SHANGHAI);
+        assert_eq!(custom.spec_at(10, 0), SpecId::CANCUN);
+        assert_eq!(custom.fork(SpecId::PRAGUE), None);
+    }
+
+    #[test]
+    fn state_clear_at_block() {
+        let mainnet = ChainSpec::mainnet();
+        assert!(!mainnet.is_state_clear_at(2_674_999, 0));
+        assert!(mainnet.is_state_clear_at(2_675_000, 0));
+
+        let late = ChainSpecBuilder::from(ChainSpec::sepolia())
+            .state_clear(ForkCondition::Block(50))
+            .build();
+        assert_eq!(late.spec_at(49, 0), SpecId::LONDON);
+        assert!(!late.is_state_clear_at(49, 0));
+        assert!(late.is_state_clear_at(50, 0));
+
+        let never = ChainSpecBuilder::from(mainnet)
+            .without_state_clear()
+            .build();
+        assert!(!never.is_state_clear_at(u64::MAX - 1, u64::MAX));
+    }
+}
@@ -11,3 +11,37 @@ pub const MAX_CODE_SIZE: usize = 0x6000;
 ///
 /// Limit of maximum initcode size is 2 * MAX_CODE_SIZE
 pub const MAX_INITCODE_SIZE: usize = 2 * MAX_CODE_SIZE;
+
+/// Gas an execution without gas charging can spend before it is stopped, it is the default
+/// of `CfgEnv::unmetered_gas_cap`.
+pub const UNMETERED_GAS_CAP: u64 = 1_000_000_000;
+
+/// EIP-1559: Bound on the change of base fee between blocks.
+pub const BASE_FEE_MAX_CHANGE_DENOMINATOR: u64 = 8;
+
+/// EIP-1559: Ratio of the gas limit to the gas target.
+pub const ELASTICITY_MULTIPLIER: u64 = 2;
+
+/// EIP-4844: Gas consumption of a single data blob.
+pub const GAS_PER_BLOB: u64 = 1 << 17;
+
+/// EIP-4844: Target number of the blob per block.
+pub const TARGET_BLOB_NUMBER_PER_BLOCK: u64 = 3;
+
+/// EIP-4844: Max number of blobs per block.
+pub const MAX_BLOB_NUMBER_PER_BLOCK: u64 = 2 * TARGET_BLOB_NUMBER_PER_BLOCK;
+
+/// EIP-4844: Target consumable blob gas for data blobs per block.
+pub const TARGET_BLOB_GAS_PER_BLOCK: u64 = TARGET_BLOB_NUMBER_PER_BLOCK * GAS_PER_BLOB;
+
+/// EIP-4844: Maximum consumable blob gas for data blobs per block.
+pub const MAX_BLOB_GAS_PER_BLOCK: u64 = MAX_BLOB_NUMBER_PER_BLOCK * GAS_PER_BLOB;
+
+/// EIP-4844: Minimum gas price for data blobs.
+pub const MIN_BLOB_GASPRICE: u64 = 1;
+
+/// EIP-4844: Controls the maximum rate of change for blob gas price.
+pub const BLOB_GASPRICE_UPDATE_FRACTION: u64 = 3338477;
+
+/// EIP-4844: First version byte of the versioned hash of a blob commitment.
+pub const VERSIONED_HASH_VERSION_KZG: u8 = 0x01;
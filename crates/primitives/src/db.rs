@@ -4,6 +4,7 @@ use crate::AccountInfo;
 use crate::U256;
 use crate::{Account, Bytecode};
 use crate::{B160, B256};
+use alloc::vec::Vec;
 use auto_impl::auto_impl;
 use hashbrown::HashMap as Map;
 
@@ -46,6 +47,90 @@ pub trait DatabaseRef {
     fn block_hash(&self, number: U256) -> Result<B256, Self::Error>;
 }
 
+/// Slots of an account storage from [DatabaseRange::storage_range].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StorageRange {
+    /// Non zero slots, in ascending order of index.
+    pub slots: Vec<(U256, U256)>,
+    /// Index of the first slot after the range, None if the range ends with the last slot.
+    pub next: Option<U256>,
+}
+
+/// Database that can list storage of an account, needed for `debug_storageRangeAt`.
+pub trait DatabaseRange: DatabaseRef {
+    /// At most `limit` non zero slots of `address` with index from `start`.
+    fn storage_range(
+        &self,
+        address: B160,
+        start: U256,
+        limit: usize,
+    ) -> Result<StorageRange, Self::Error>;
+}
+
+// auto_impl doesn't support error types of supertraits.
+impl<T: DatabaseRange + ?Sized> DatabaseRange for &T {
+    fn storage_range(
+        &self,
+        address: B160,
+        start: U256,
+        limit: usize,
+    ) -> Result<StorageRange, Self::Error> {
+        (**self).storage_range(address, start, limit)
+    }
+}
+
+impl<T: DatabaseRange + ?Sized> DatabaseRange for alloc::sync::Arc<T> {
+    fn storage_range(
+        &self,
+        address: B160,
+        start: U256,
+        limit: usize,
+    ) -> Result<StorageRange, Self::Error> {
+        (**self).storage_range(address, start, limit)
+    }
+}
+
+/// Database that answers many lookups at once, as RPC and key value stores do faster than
+/// one by one. Point lookups are used by default.
+pub trait DatabaseBatch: DatabaseRef {
+    /// Basic information of every account of `addresses`, in the same order.
+    fn basic_many(&self, addresses: &[B160]) -> Result<Vec<Option<AccountInfo>>, Self::Error> {
+        addresses
+            .iter()
+            .map(|address| self.basic(*address))
+            .collect()
+    }
+
+    /// Value of every storage slot of `slots`, in the same order.
+    fn storage_many(&self, slots: &[(B160, U256)]) -> Result<Vec<U256>, Self::Error> {
+        slots
+            .iter()
+            .map(|(address, index)| self.storage(*address, *index))
+            .collect()
+    }
+}
+
+impl<T: DatabaseBatch + ?Sized> DatabaseBatch for &T {
+    fn basic_many(&self, addresses: &[B160]) -> Result<Vec<Option<AccountInfo>>, Self::Error> {
+        (**self).basic_many(addresses)
+    }
+
+    fn storage_many(&self, slots: &[(B160, U256)]) -> Result<Vec<U256>, Self::Error> {
+        (**self).storage_many(slots)
+    }
+}
+
+impl<T: DatabaseBatch + ?Sized> DatabaseBatch for alloc::sync::Arc<T> {
+    fn basic_many(&self, addresses: &[B160]) -> Result<Vec<Option<AccountInfo>>, Self::Error> {
+        (**self).basic_many(addresses)
+    }
+
+    fn storage_many(&self, slots: &[(B160, U256)]) -> Result<Vec<U256>, Self::Error> {
+        (**self).storage_many(slots)
+    }
+}
+
 pub struct RefDBWrapper<'a, Error> {
     pub db: &'a dyn DatabaseRef<Error = Error>,
 }
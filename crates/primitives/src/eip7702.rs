@@ -0,0 +1,129 @@
+//! EIP-7702: Set EOA account code.
+//!
+//! Transactions of type [SET_CODE_TX_TYPE] carry a list of authorizations signed by EOAs,
+//! every valid one sets code of its signer (authority) to a delegation designator, making
+//! calls to the authority execute code of the delegated address.
+use crate::{keccak256, B160, B256, U256};
+use alloc::vec::Vec;
+#[cfg(any(test, feature = "arbitrary"))]
+use arbitrary::Arbitrary;
+
+pub const SET_CODE_TX_TYPE: u8 = 0x04;
+/// Prefix of the signed message of authorizations.
+pub const AUTHORIZATION_MAGIC: u8 = 0x05;
+/// Code of a delegating account is this prefix followed by the delegated address.
+pub const DELEGATION_MAGIC: [u8; 3] = [0xef, 0x01, 0x00];
+pub const DELEGATION_CODE_LEN: usize = DELEGATION_MAGIC.len() + 20;
+
+/// Half of the secp256k1 curve order, signatures with greater `s` are malleable (EIP-2).
+pub const SECP256K1N_HALF: U256 = U256::from_be_bytes([
+    0x7f, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+    0x5d, 0x57, 0x6e, 0x73, 0x57, 0xa4, 0x50, 0x1d, 0xdf, 0xe9, 0x2f, 0x46, 0x68, 0x1b, 0x20, 0xa0,
+]);
+
+/// Authorization to set code of the signer to a delegation to `address`.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(Arbitrary))]
+pub struct SignedAuthorization {
+    /// Chain the authorization is valid on, zero for all chains.
+    pub chain_id: U256,
+    /// Delegated address, zero address clears the delegation.
+    pub address: B160,
+    /// Nonce the signer has to have.
+    pub nonce: u64,
+    pub y_parity: u8,
+    pub r: U256,
+    pub s: U256,
+}
+
+impl SignedAuthorization {
+    /// Message the authority signed, `keccak256(MAGIC || rlp([chain_id, address, nonce]))`.
+    pub fn signature_hash(&self) -> B256 {
+        let mut payload = Vec::with_capacity(3 * 33);
+        rlp_uint(&mut payload, &self.chain_id.to_be_bytes::<32>());
+        rlp_string(&mut payload, &self.address[..]);
+        rlp_uint(&mut payload, &self.nonce.to_be_bytes());
+
+        let mut message = Vec::with_capacity(payload.len() + 3);
+        message.push(AUTHORIZATION_MAGIC);
+        rlp_list_header(&mut message, payload.len());
+        message.extend_from_slice(&payload);
+        keccak256(&message)
+    }
+
+    /// Signature as `r | s | recovery id`, none if `y_parity` is not 0 or 1, or `s` is
+    /// greater than [SECP256K1N_HALF].
+    pub fn signature(&self) -> Option<[u8; 65]> {
+        if self.y_parity > 1 || self.s > SECP256K1N_HALF {
+            return None;
+        }
+        let mut signature = [0; 65];
+        signature[..32].copy_from_slice(&self.r.to_be_bytes::<32>());
+        signature[32..64].copy_from_slice(&self.s.to_be_bytes::<32>());
+        signature[64] = self.y_parity;
+        Some(signature)
+    }
+}
+
+/// Big endian integer without leading zeros.
+fn rlp_uint(out: &mut Vec<u8>, be: &[u8]) {
+    let start = be.iter().position(|b| *b != 0).unwrap_or(be.len());
+    rlp_string(out, &be[start..]);
+}
+
+fn rlp_string(out: &mut Vec<u8>, bytes: &[u8]) {
+    match bytes {
+        [byte] if *byte < 0x80 => out.push(*byte),
+        // strings of this module are at most 32 bytes.
+        _ => {
+            out.push(0x80 + bytes.len() as u8);
+            out.extend_from_slice(bytes);
+        }
+    }
+}
+
+fn rlp_list_header(out: &mut Vec<u8>, len: usize) {
+    if len < 56 {
+        out.push(0xc0 + len as u8);
+    } else {
+        let be = (len as u64).to_be_bytes();
+        let start = be.iter().position(|b| *b != 0).unwrap();
+        out.push(0xf7 + (be.len() - start) as u8);
+        out.extend_from_slice(&be[start..]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hex_literal::hex;
+
+    #[test]
+    fn signature_hash() {
+        let authorization = SignedAuthorization {
+            chain_id: U256::from(1),
+            address: B160([0x42; 20]),
+            nonce: 0,
+            ..Default::default()
+        };
+        // keccak256(0x05 || 0xd7 01 94 <address> 80)
+        let mut message = hex!("05 d7 01 94").to_vec();
+        message.extend_from_slice(&[0x42; 20]);
+        message.push(0x80);
+        assert_eq!(authorization.signature_hash(), keccak256(&message));
+
+        assert_eq!(authorization.signature().unwrap()[64], 0);
+        let high_s = SignedAuthorization {
+            s: SECP256K1N_HALF + U256::from(1),
+            ..authorization.clone()
+        };
+        assert_eq!(high_s.signature(), None);
+        let bad_parity = SignedAuthorization {
+            y_parity: 2,
+            ..authorization
+        };
+        assert_eq!(bad_parity.signature(), None);
+    }
+}
@@ -1,9 +1,17 @@
+#[cfg(feature = "std")]
+use crate::AnalysisCache;
 use crate::{
-    alloc::vec::Vec, Account, EVMError, InvalidTransaction, Spec, SpecId, B160, B256, KECCAK_EMPTY,
-    MAX_INITCODE_SIZE, U256,
+    alloc::{sync::Arc, vec::Vec},
+    calc_blob_gasprice, calc_excess_blob_gas, calc_next_base_fee, create2_address, create_address,
+    keccak256, Account, BlockHashes, CodeHasher, CustomPrecompiles, EVMError, EnvOverrides,
+    HashSet, InvalidTransaction, SignedAuthorization, Spec, SpecId, B160, B256, GAS_PER_BLOB,
+    KECCAK_EMPTY, MAX_BLOB_NUMBER_PER_BLOCK, MAX_INITCODE_SIZE, U256, VERSIONED_HASH_VERSION_KZG,
 };
+#[cfg(any(test, feature = "arbitrary"))]
+use arbitrary::Arbitrary;
 use bytes::Bytes;
 use core::cmp::{min, Ordering};
+use core::fmt;
 
 #[derive(Clone, Debug, Default)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
@@ -28,6 +36,37 @@ pub struct BlockEnv {
     /// basefee is added in EIP1559 London upgrade
     pub basefee: U256,
     pub gas_limit: U256,
+    /// Excess blob gas and blob gas price, added in EIP-4844 Cancun upgrade.
+    /// Required since Cancun, set it with [BlockEnv::set_blob_excess_gas_and_price].
+    /// Zero excess blob gas if missing in serialized block, as in [Default].
+    #[cfg_attr(
+        feature = "serde",
+        serde(default = "BlobExcessGasAndPrice::default_for_block")
+    )]
+    pub blob_excess_gas_and_price: Option<BlobExcessGasAndPrice>,
+}
+
+/// EIP-4844: Excess blob gas of the block and blob gas price computed from it.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BlobExcessGasAndPrice {
+    pub excess_blob_gas: u64,
+    pub blob_gasprice: u64,
+}
+
+impl BlobExcessGasAndPrice {
+    pub fn new(excess_blob_gas: u64) -> Self {
+        Self {
+            excess_blob_gas,
+            blob_gasprice: calc_blob_gasprice(excess_blob_gas),
+        }
+    }
+
+    /// Zero excess blob gas of [BlockEnv::default].
+    #[cfg(feature = "serde")]
+    fn default_for_block() -> Option<Self> {
+        Some(Self::new(0))
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -45,10 +84,48 @@ pub struct TxEnv {
     pub chain_id: Option<u64>,
     pub nonce: Option<u64>,
     pub access_list: Vec<(B160, Vec<U256>)>,
+    /// EIP-4844: Versioned hashes of the blobs of the transaction.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub blob_hashes: Vec<B256>,
+    /// EIP-4844: Max fee per blob gas, set for blob transactions.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub max_fee_per_blob_gas: Option<U256>,
+    /// EIP-7702: Authorizations of a set code transaction, only set code transactions have
+    /// them.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub authorization_list: Vec<SignedAuthorization>,
+    /// Fields of OP stack transactions, used if [CfgEnv::optimism] is set.
+    #[cfg(feature = "optimism")]
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub optimism: OptimismFields,
+}
+
+/// Fields of OP stack transactions.
+#[cfg(feature = "optimism")]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OptimismFields {
+    /// Source hash of deposit transactions, none for other transactions.
+    pub source_hash: Option<B256>,
+    /// Amount minted to the caller before a deposit transaction is executed.
+    pub mint: Option<u128>,
+    /// If deposit transaction is a system transaction.
+    pub is_system_transaction: Option<bool>,
+    /// Encoded transaction the L1 data fee is computed from. Transactions without it, as
+    /// simulated calls, don't pay the L1 data fee.
+    pub enveloped_tx: Option<Bytes>,
+}
+
+impl TxEnv {
+    /// EIP-4844: Blob gas used by the transaction.
+    pub fn get_total_blob_gas(&self) -> u64 {
+        GAS_PER_BLOB * self.blob_hashes.len() as u64
+    }
 }
 
 #[derive(Clone, Debug)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(Arbitrary))]
 pub enum TransactTo {
     Call(B160),
     Create(CreateScheme),
@@ -66,6 +143,7 @@ impl TransactTo {
 /// Create scheme.
 #[derive(Clone, Copy, Eq, PartialEq, Debug)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(Arbitrary))]
 pub enum CreateScheme {
     /// Legacy create scheme of `CREATE`.
     Create,
@@ -76,6 +154,17 @@ pub enum CreateScheme {
     },
 }
 
+impl CreateScheme {
+    /// Address of contract created by `caller` whose nonce before creation is `nonce`.
+    /// `init_code_hash` is used only by `CREATE2`.
+    pub fn created_address(&self, caller: B160, nonce: u64, init_code_hash: B256) -> B160 {
+        match *self {
+            CreateScheme::Create => create_address(caller, nonce),
+            CreateScheme::Create2 { salt } => create2_address(caller, init_code_hash, salt),
+        }
+    }
+}
+
 #[derive(Clone, Debug, Eq, PartialEq)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CfgEnv {
@@ -89,6 +178,15 @@ pub struct CfgEnv {
     /// If some it will effects EIP-170: Contract code size limit. Usefull to increase this because of tests.
     /// By default it is 0x6000 (~25kb).
     pub limit_contract_code_size: Option<usize>,
+    /// If some, `RETURN` and `REVERT` with more data than this halt with
+    /// [crate::Halt::ReturnDataLimit]. Not part of consensus, by default there is no limit.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub limit_return_data_size: Option<usize>,
+    /// If some, logs emitted in the transaction, reverted ones included, can't have more
+    /// data than this in total, [crate::Halt::LogDataLimit] otherwise. Not part of consensus,
+    /// by default there is no limit.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub limit_log_data_size: Option<usize>,
     /// A hard memory limit in bytes beyond which [Memory] cannot be resized.
     ///
     /// In cases where the gas limit may be extraordinarily high, it is recommended to set this to
@@ -96,7 +194,8 @@ pub struct CfgEnv {
     /// EIP-1985.
     #[cfg(feature = "memory_limit")]
     pub memory_limit: u64,
-    /// Skip balance checks if true. Adds transaction cost to balance to ensure execution doesn't fail.
+    /// Skip balance checks if true. Adds transaction cost to balance to ensure execution doesn't fail,
+    /// so fees are still charged at the effective gas price.
     #[cfg(feature = "optional_balance_check")]
     pub disable_balance_check: bool,
     /// There are use cases where it's allowed to provide a gas limit that's higher than a block's gas limit. To that
@@ -104,6 +203,11 @@ pub struct CfgEnv {
     /// By default, it is set to `false`.
     #[cfg(feature = "optional_block_gas_limit")]
     pub disable_block_gas_limit: bool,
+    /// Skip the check of transaction nonce against nonce of the caller, for simulations of
+    /// transactions that are not the next one of the caller. Nonce of the caller is still increased.
+    /// By default, it is set to `false`.
+    #[cfg(feature = "optional_nonce_check")]
+    pub disable_nonce_check: bool,
     /// EIP-3607 rejects transactions from senders with deployed code. In development, it can be desirable to simulate
     /// calls from contracts, which this setting allows.
     /// By default, it is set to `false`.
@@ -118,9 +222,104 @@ pub struct CfgEnv {
     /// This is useful for testing method calls with zero gas price.
     #[cfg(feature = "optional_no_base_fee")]
     pub disable_base_fee: bool,
+    /// Execute without gas charging, for simulations that don't want to pick a gas limit.
+    /// Gas is still counted and reported as used, but frames don't run out of gas given to
+    /// them, the transaction gas limit and the block gas limit are ignored and the caller pays
+    /// no gas fee. Execution is stopped with out of gas only when it spends
+    /// `unmetered_gas_cap`.
+    /// By default, it is set to `false`.
+    #[cfg(feature = "optional_gas_charging")]
+    pub disable_gas_charging: bool,
+    /// Gas an execution without gas charging can spend, it stops runaway loops and memory
+    /// growth.
+    /// By default, it is set to [crate::UNMETERED_GAS_CAP].
+    #[cfg(feature = "optional_gas_charging")]
+    pub unmetered_gas_cap: u64,
+    /// Execute transactions as OP stack chains do: deposit transactions are accepted and
+    /// other transactions pay the L1 data fee.
+    /// By default, it is set to `false`.
+    #[cfg(feature = "optimism")]
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub optimism: bool,
+    /// Addresses that calls, creates and selfdestruct beneficiaries are checked against.
+    /// By default nothing is denied.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub address_filter: AddressFilter,
+    /// Hash of created contracts code and of init code in `CREATE2` address.
+    /// By default it is keccak256.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub code_hasher: CodeHasher,
+    /// Summarize changes of touched accounts in [crate::ResultAndState::touched].
+    /// By default, it is set to `false`.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub touched_summary: bool,
+    /// Return gas of every call frame in [crate::ResultAndState::gas_frame], a summary
+    /// lighter than tracing the execution.
+    /// By default, it is set to `false`.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub gas_frames: bool,
+    /// Return changed storage slots in [crate::ResultAndState::storage_diff].
+    /// By default, it is set to `false`.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub storage_diff: bool,
+    /// Creation at an address that already has code or nonce.
+    /// By default, it fails.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub create_collision: CreateCollisionPolicy,
+    /// Accounts and storage slots that are warm at the start of every transaction, in
+    /// addition to ones the spec makes warm. Unlike access list they don't cost intrinsic
+    /// gas, as for system contracts of some L2s.
+    /// By default it is empty.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub warm_accounts: Vec<(B160, Vec<U256>)>,
+    /// Whether EIP-161 state clearing rules apply: empty accounts are same as not existing,
+    /// created contracts start with nonce one and touched empty accounts are deleted at the
+    /// end of transaction. Chains that never adopted them can disable it.
+    /// By default it is none and rules apply from Spurious Dragon.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub state_clear: Option<bool>,
+    /// Precompiles added to the ones of the spec, for chains that have their own. They are
+    /// warm from the start of transaction same as standard precompiles.
+    /// By default it is empty.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub custom_precompiles: CustomPrecompiles,
+    /// Source of hashes returned by `BLOCKHASH`, for chains that keep longer history or
+    /// read it from elsewhere.
+    /// By default they are hashes of the last 256 blocks, see [crate::DatabaseBlockHashes].
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub block_hashes: BlockHashes,
+    /// Values environment opcodes return instead of the ones of [Env], per call frame.
+    /// By default values are not overridden.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub env_overrides: EnvOverrides,
+    /// Cache of analysed bytecode of called contracts, it can be shared between EVMs.
+    /// By default it is none and code is analysed every time it is loaded.
+    #[cfg(feature = "std")]
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub analysis_cache: Option<AnalysisCache>,
 }
 
 impl CfgEnv {
+    /// Address of contract created with `init_code`, same as one EVM creates it at.
+    pub fn created_address(
+        &self,
+        scheme: CreateScheme,
+        caller: B160,
+        nonce: u64,
+        init_code: &[u8],
+    ) -> B160 {
+        let init_code_hash = match scheme {
+            CreateScheme::Create => B256::zero(),
+            CreateScheme::Create2 { .. } => self.code_hasher.hash(init_code),
+        };
+        scheme.created_address(caller, nonce, init_code_hash)
+    }
+
+    pub fn is_state_clear_enabled(&self) -> bool {
+        self.state_clear
+            .unwrap_or_else(|| SpecId::enabled(self.spec_id, SpecId::SPURIOUS_DRAGON))
+    }
+
     #[cfg(feature = "optional_eip3607")]
     pub fn is_eip3607_disabled(&self) -> bool {
         self.disable_eip3607
@@ -170,10 +369,31 @@ impl CfgEnv {
     pub fn is_block_gas_limit_disabled(&self) -> bool {
         false
     }
+
+    #[cfg(feature = "optional_nonce_check")]
+    pub fn is_nonce_check_disabled(&self) -> bool {
+        self.disable_nonce_check
+    }
+
+    #[cfg(not(feature = "optional_nonce_check"))]
+    pub fn is_nonce_check_disabled(&self) -> bool {
+        false
+    }
+
+    #[cfg(feature = "optional_gas_charging")]
+    pub fn is_gas_charging_disabled(&self) -> bool {
+        self.disable_gas_charging
+    }
+
+    #[cfg(not(feature = "optional_gas_charging"))]
+    pub fn is_gas_charging_disabled(&self) -> bool {
+        false
+    }
 }
 
 #[derive(Clone, Default, Debug, Eq, PartialEq)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(Arbitrary))]
 pub enum AnalysisKind {
     Raw,
     Check,
@@ -181,6 +401,72 @@ pub enum AnalysisKind {
     Analyse,
 }
 
+/// What creation does if the created address already has code or nonce, see
+/// [CfgEnv::create_collision].
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(Arbitrary))]
+pub enum CreateCollisionPolicy {
+    /// Creation fails with `CreateCollision`, as consensus requires.
+    #[default]
+    Fail,
+    /// Contract is created over the account, its code, nonce and storage are replaced and
+    /// balance is kept. For test frameworks that deploy code to a chosen address. Creation
+    /// at a precompile address still fails.
+    Overwrite,
+}
+
+/// What happens when execution reaches an address denied by [AddressFilter].
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum AddressFilterAction {
+    /// Frame halts with `AddressDenied`, consuming its gas. Halts the transaction if it is
+    /// the first call.
+    #[default]
+    Halt,
+    /// Frame succeeds without executing code or transferring value, and without using gas.
+    /// Denied create doesn't create the account, denied selfdestruct behaves as `STOP`.
+    NoOp,
+}
+
+/// Allow or deny list of addresses that execution can reach.
+///
+/// Checked for call targets, addresses of created contracts and selfdestruct
+/// beneficiaries. Note that allow list needs to include precompiles if they are called.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AddressFilter {
+    /// If true only `addresses` can be reached, otherwise `addresses` can't be reached.
+    pub allow_list: bool,
+    pub addresses: HashSet<B160>,
+    pub action: AddressFilterAction,
+}
+
+impl AddressFilter {
+    /// Filter that denies given addresses.
+    pub fn deny(addresses: impl IntoIterator<Item = B160>, action: AddressFilterAction) -> Self {
+        Self {
+            allow_list: false,
+            addresses: addresses.into_iter().collect(),
+            action,
+        }
+    }
+
+    /// Filter that denies everything except given addresses.
+    pub fn allow(addresses: impl IntoIterator<Item = B160>, action: AddressFilterAction) -> Self {
+        Self {
+            allow_list: true,
+            addresses: addresses.into_iter().collect(),
+            action,
+        }
+    }
+
+    /// Returns action to take if address is denied.
+    pub fn check(&self, address: &B160) -> Option<AddressFilterAction> {
+        (self.addresses.contains(address) != self.allow_list).then_some(self.action)
+    }
+}
+
 impl Default for CfgEnv {
     fn default() -> CfgEnv {
         CfgEnv {
@@ -188,18 +474,41 @@ impl Default for CfgEnv {
             spec_id: SpecId::LATEST,
             perf_analyse_created_bytecodes: Default::default(),
             limit_contract_code_size: None,
+            limit_return_data_size: None,
+            limit_log_data_size: None,
             #[cfg(feature = "memory_limit")]
             memory_limit: 2u64.pow(32) - 1,
             #[cfg(feature = "optional_balance_check")]
             disable_balance_check: false,
             #[cfg(feature = "optional_block_gas_limit")]
             disable_block_gas_limit: false,
+            #[cfg(feature = "optional_nonce_check")]
+            disable_nonce_check: false,
             #[cfg(feature = "optional_eip3607")]
             disable_eip3607: false,
             #[cfg(feature = "optional_gas_refund")]
             disable_gas_refund: false,
             #[cfg(feature = "optional_no_base_fee")]
             disable_base_fee: false,
+            #[cfg(feature = "optional_gas_charging")]
+            disable_gas_charging: false,
+            #[cfg(feature = "optional_gas_charging")]
+            unmetered_gas_cap: crate::UNMETERED_GAS_CAP,
+            #[cfg(feature = "optimism")]
+            optimism: false,
+            address_filter: AddressFilter::default(),
+            code_hasher: CodeHasher::default(),
+            touched_summary: false,
+            gas_frames: false,
+            storage_diff: false,
+            create_collision: CreateCollisionPolicy::default(),
+            warm_accounts: Vec::new(),
+            state_clear: None,
+            custom_precompiles: CustomPrecompiles::default(),
+            block_hashes: BlockHashes::default(),
+            env_overrides: EnvOverrides::default(),
+            #[cfg(feature = "std")]
+            analysis_cache: None,
         }
     }
 }
@@ -214,6 +523,154 @@ impl Default for BlockEnv {
             difficulty: U256::ZERO,
             prevrandao: Some(B256::zero()),
             basefee: U256::ZERO,
+            blob_excess_gas_and_price: Some(BlobExcessGasAndPrice::new(0)),
+        }
+    }
+}
+
+impl BlockEnv {
+    /// Default block with given number and timestamp, prevrandao is set by `prevrandao`.
+    pub fn new(number: U256, timestamp: U256, prevrandao: &PrevrandaoStrategy) -> Self {
+        Self {
+            number,
+            timestamp,
+            ..Default::default()
+        }
+        .with_prevrandao(prevrandao)
+    }
+
+    /// EIP-4844: Set excess blob gas and compute blob gas price from it.
+    pub fn set_blob_excess_gas_and_price(&mut self, excess_blob_gas: u64) {
+        self.blob_excess_gas_and_price = Some(BlobExcessGasAndPrice::new(excess_blob_gas));
+    }
+
+    /// EIP-4844: Blob gas price, None if excess blob gas is not set.
+    pub fn get_blob_gasprice(&self) -> Option<u64> {
+        self.blob_excess_gas_and_price
+            .map(|blob| blob.blob_gasprice)
+    }
+
+    /// EIP-4844: Excess blob gas, None if it is not set.
+    pub fn get_blob_excess_gas(&self) -> Option<u64> {
+        self.blob_excess_gas_and_price
+            .map(|blob| blob.excess_blob_gas)
+    }
+
+    /// Set prevrandao derived from the rest of the block.
+    pub fn with_prevrandao(mut self, strategy: &PrevrandaoStrategy) -> Self {
+        self.prevrandao = Some(strategy.prevrandao(&self));
+        self
+    }
+
+    /// Child of this block, given gas and blob gas used by this block and time between blocks.
+    ///
+    /// Number is incremented, timestamp advanced by `slot_time`, base fee adjusted as in
+    /// EIP-1559 and excess blob gas as in EIP-4844, if this block has it. Coinbase,
+    /// difficulty and gas limit are kept.
+    pub fn next(
+        &self,
+        gas_used: U256,
+        blob_gas_used: u64,
+        slot_time: u64,
+        prevrandao: &PrevrandaoStrategy,
+    ) -> Self {
+        Self {
+            number: self.number.saturating_add(U256::from(1)),
+            timestamp: self.timestamp.saturating_add(U256::from(slot_time)),
+            basefee: calc_next_base_fee(gas_used, self.gas_limit, self.basefee),
+            blob_excess_gas_and_price: self.blob_excess_gas_and_price.map(|blob| {
+                BlobExcessGasAndPrice::new(calc_excess_blob_gas(
+                    blob.excess_blob_gas,
+                    blob_gas_used,
+                ))
+            }),
+            ..self.clone()
+        }
+        .with_prevrandao(prevrandao)
+    }
+
+    /// Child of the block with `parent` header, see [BlockEnv::next].
+    pub fn from_parent(
+        parent: &ParentHeader,
+        slot_time: u64,
+        prevrandao: &PrevrandaoStrategy,
+    ) -> Self {
+        let block = Self {
+            number: parent.number,
+            coinbase: parent.beneficiary,
+            timestamp: parent.timestamp,
+            difficulty: parent.difficulty,
+            prevrandao: None,
+            basefee: parent.base_fee_per_gas,
+            gas_limit: parent.gas_limit,
+            blob_excess_gas_and_price: parent.excess_blob_gas.map(BlobExcessGasAndPrice::new),
+        };
+        block.next(
+            parent.gas_used,
+            parent.blob_gas_used.unwrap_or_default(),
+            slot_time,
+            prevrandao,
+        )
+    }
+}
+
+/// Fields of a block header the env of its child block is derived from, see
+/// [BlockEnv::from_parent].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ParentHeader {
+    pub number: U256,
+    pub timestamp: U256,
+    pub beneficiary: B160,
+    pub difficulty: U256,
+    pub gas_limit: U256,
+    pub gas_used: U256,
+    /// Zero before London.
+    pub base_fee_per_gas: U256,
+    /// EIP-4844: None before Cancun, child block then has no excess blob gas either.
+    pub excess_blob_gas: Option<u64>,
+    pub blob_gas_used: Option<u64>,
+}
+
+/// Source of prevrandao for simulated blocks.
+#[derive(Clone, Default)]
+pub enum PrevrandaoStrategy {
+    /// Same value in every block.
+    Fixed(B256),
+    /// `keccak256(number)`, every block gets different value that is same in every run.
+    #[default]
+    NumberHash,
+    /// `keccak256(seed || number)`, for runs that need different values for same blocks.
+    NumberHashWithSeed(B256),
+    /// Value computed by the callback from the block.
+    Callback(Arc<dyn Fn(&BlockEnv) -> B256 + Send + Sync>),
+}
+
+impl PrevrandaoStrategy {
+    pub fn prevrandao(&self, block: &BlockEnv) -> B256 {
+        match self {
+            Self::Fixed(value) => *value,
+            Self::NumberHash => keccak256(&block.number.to_be_bytes::<32>()),
+            Self::NumberHashWithSeed(seed) => {
+                let mut input = [0; 64];
+                input[..32].copy_from_slice(seed.as_bytes());
+                input[32..].copy_from_slice(&block.number.to_be_bytes::<32>());
+                keccak256(&input)
+            }
+            Self::Callback(callback) => callback(block),
+        }
+    }
+}
+
+impl fmt::Debug for PrevrandaoStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Fixed(value) => f.debug_tuple("Fixed").field(value).finish(),
+            Self::NumberHash => f.write_str("NumberHash"),
+            Self::NumberHashWithSeed(seed) => {
+                f.debug_tuple("NumberHashWithSeed").field(seed).finish()
+            }
+            Self::Callback(_) => f.write_str("Callback"),
         }
     }
 }
@@ -231,22 +688,50 @@ impl Default for TxEnv {
             chain_id: None,
             nonce: None,
             access_list: Vec::new(),
+            blob_hashes: Vec::new(),
+            max_fee_per_blob_gas: None,
+            authorization_list: Vec::new(),
+            #[cfg(feature = "optimism")]
+            optimism: OptimismFields::default(),
         }
     }
 }
 
 impl Env {
     pub fn effective_gas_price(&self) -> U256 {
-        if self.tx.gas_priority_fee.is_none() {
-            self.tx.gas_price
-        } else {
-            min(
-                self.tx.gas_price,
-                self.block.basefee + self.tx.gas_priority_fee.unwrap(),
-            )
+        match self.tx.gas_priority_fee {
+            None => self.tx.gas_price,
+            Some(gas_priority_fee) => min(self.tx.gas_price, self.block.basefee + gas_priority_fee),
         }
     }
 
+    /// OP stack deposit transaction, minted by the L1 and not paying for gas.
+    #[cfg(feature = "optimism")]
+    pub fn is_deposit(&self) -> bool {
+        self.cfg.optimism && self.tx.optimism.source_hash.is_some()
+    }
+
+    #[cfg(not(feature = "optimism"))]
+    pub fn is_deposit(&self) -> bool {
+        false
+    }
+
+    /// EIP-4844: Fee of the blob gas at blob gas price of the block, None if the block has no
+    /// blob gas price.
+    pub fn calc_data_fee(&self) -> Option<U256> {
+        self.block.get_blob_gasprice().map(|blob_gasprice| {
+            U256::from(blob_gasprice).saturating_mul(U256::from(self.tx.get_total_blob_gas()))
+        })
+    }
+
+    /// EIP-4844: Max fee of the blob gas the transaction is willing to pay, None if it is not
+    /// a blob transaction.
+    pub fn calc_max_data_fee(&self) -> Option<U256> {
+        self.tx.max_fee_per_blob_gas.map(|max_fee_per_blob_gas| {
+            max_fee_per_blob_gas.saturating_mul(U256::from(self.tx.get_total_blob_gas()))
+        })
+    }
+
     /// Validate ENV data of the block.
     ///
     /// It can be skip if you are sure that PREVRANDAO is set.
@@ -256,6 +741,10 @@ impl Env {
         if SPEC::enabled(SpecId::MERGE) && self.block.prevrandao.is_none() {
             return Err(EVMError::PrevrandaoNotSet);
         }
+        // Excess blob gas is required for Cancun
+        if SPEC::enabled(SpecId::CANCUN) && self.block.blob_excess_gas_and_price.is_none() {
+            return Err(EVMError::ExcessBlobGasNotSet);
+        }
         Ok(())
     }
 
@@ -278,14 +767,20 @@ impl Env {
             }
             let basefee = self.block.basefee;
 
-            // check minimal cost against basefee
-            if !self.cfg.is_base_fee_check_disabled() && effective_gas_price < basefee {
+            // check minimal cost against basefee, deposits don't pay for gas.
+            if !self.cfg.is_base_fee_check_disabled()
+                && !self.is_deposit()
+                && effective_gas_price < basefee
+            {
                 return Err(InvalidTransaction::GasPriceLessThanBasefee);
             }
         }
 
         // Check if gas_limit is more than block_gas_limit
-        if !self.cfg.is_block_gas_limit_disabled() && U256::from(gas_limit) > self.block.gas_limit {
+        if !self.cfg.is_block_gas_limit_disabled()
+            && !self.cfg.is_gas_charging_disabled()
+            && U256::from(gas_limit) > self.block.gas_limit
+        {
             return Err(InvalidTransaction::CallerGasLimitMoreThanBlock);
         }
 
@@ -306,21 +801,103 @@ impl Env {
             return Err(InvalidTransaction::AccessListNotSupported);
         }
 
+        // EIP-4844: Shard Blob Transactions
+        if SPEC::enabled(SpecId::CANCUN) {
+            if let Some(max_fee_per_blob_gas) = self.tx.max_fee_per_blob_gas {
+                // excess blob gas is checked in block env validation.
+                let blob_gasprice = self.block.get_blob_gasprice().unwrap_or_default();
+                if U256::from(blob_gasprice) > max_fee_per_blob_gas {
+                    return Err(InvalidTransaction::BlobGasPriceGreaterThanMax);
+                }
+                if self.tx.blob_hashes.is_empty() {
+                    return Err(InvalidTransaction::EmptyBlobs);
+                }
+                if is_create {
+                    return Err(InvalidTransaction::BlobCreateTransaction);
+                }
+                if self
+                    .tx
+                    .blob_hashes
+                    .iter()
+                    .any(|hash| hash[0] != VERSIONED_HASH_VERSION_KZG)
+                {
+                    return Err(InvalidTransaction::BlobVersionNotSupported);
+                }
+                if self.tx.blob_hashes.len() as u64 > MAX_BLOB_NUMBER_PER_BLOCK {
+                    return Err(InvalidTransaction::TooManyBlobs);
+                }
+            } else if !self.tx.blob_hashes.is_empty() {
+                return Err(InvalidTransaction::BlobVersionedHashesNotSupported);
+            }
+        } else {
+            if !self.tx.blob_hashes.is_empty() {
+                return Err(InvalidTransaction::BlobVersionedHashesNotSupported);
+            }
+            if self.tx.max_fee_per_blob_gas.is_some() {
+                return Err(InvalidTransaction::MaxFeePerBlobGasNotSupported);
+            }
+        }
+
+        // EIP-7702: Set EOA account code
+        if !self.tx.authorization_list.is_empty() {
+            if !SPEC::enabled(SpecId::PRAGUE) {
+                return Err(InvalidTransaction::AuthorizationListNotSupported);
+            }
+            if is_create {
+                return Err(InvalidTransaction::SetCodeCreateTransaction);
+            }
+        }
+
         Ok(())
     }
 
     /// Validate transaction agains state.
+    ///
+    /// If balance check is disabled, balance of the account is increased to cover the
+    /// transaction cost.
     #[inline]
-    pub fn validate_tx_agains_state(&self, account: &Account) -> Result<(), InvalidTransaction> {
+    pub fn validate_tx_agains_state(
+        &self,
+        account: &mut Account,
+    ) -> Result<(), InvalidTransaction> {
+        self.validate_tx_against_state_with_cost(account, U256::ZERO)
+    }
+
+    /// Same as [Env::validate_tx_agains_state], balance also has to cover `additional_cost`,
+    /// like the L1 data fee of optimism transactions.
+    #[inline]
+    pub fn validate_tx_against_state_with_cost(
+        &self,
+        account: &mut Account,
+        additional_cost: U256,
+    ) -> Result<(), InvalidTransaction> {
+        // Deposits are already included on the L1, their nonce and balance are not checked.
+        if self.is_deposit() {
+            return Ok(());
+        }
+
         // EIP-3607: Reject transactions from senders with deployed code
         // This EIP is introduced after london but there was no collision in past
         // so we can leave it enabled always
-        if !self.cfg.is_eip3607_disabled() && account.info.code_hash != KECCAK_EMPTY {
+        // EIP-7702: Delegating accounts still send transactions, their code must be loaded.
+        let is_delegating = account
+            .info
+            .code
+            .as_ref()
+            .is_some_and(|code| code.delegation_address().is_some());
+        if !self.cfg.is_eip3607_disabled()
+            && account.info.code_hash != KECCAK_EMPTY
+            && !is_delegating
+        {
             return Err(InvalidTransaction::RejectCallerWithCode);
         }
 
         // Check that the transaction's nonce is correct
-        if let Some(tx) = self.tx.nonce {
+        if let Some(tx) = self
+            .tx
+            .nonce
+            .filter(|_| !self.cfg.is_nonce_check_disabled())
+        {
             let state = account.info.nonce;
             match tx.cmp(&state) {
                 Ordering::Greater => {
@@ -333,20 +910,173 @@ impl Env {
             }
         }
 
-        let balance_check = U256::from(self.tx.gas_limit)
+        // gas is not paid for if charging is disabled.
+        let gas_limit = if self.cfg.is_gas_charging_disabled() {
+            0
+        } else {
+            self.tx.gas_limit
+        };
+        let balance_check = U256::from(gas_limit)
             .checked_mul(self.tx.gas_price)
             .and_then(|gas_cost| gas_cost.checked_add(self.tx.value))
+            .and_then(|cost| cost.checked_add(self.calc_max_data_fee().unwrap_or_default()))
+            .and_then(|cost| cost.checked_add(additional_cost))
             .ok_or(InvalidTransaction::OverflowPaymentInTransaction)?;
 
-        // Check if account has enough balance for gas_limit*gas_price and value transfer.
+        // Check if account has enough balance for gas_limit*gas_price, max blob fee, value
+        // transfer and additional cost.
         // Transfer will be done inside `*_inner` functions.
-        if !self.cfg.is_balance_check_disabled() && balance_check > account.info.balance {
-            return Err(InvalidTransaction::LackOfFundForMaxFee {
-                fee: self.tx.gas_limit,
-                balance: account.info.balance,
-            });
+        if balance_check > account.info.balance {
+            if !self.cfg.is_balance_check_disabled() {
+                return Err(InvalidTransaction::LackOfFundForMaxFee {
+                    fee: balance_check,
+                    balance: account.info.balance,
+                });
+            }
+            account.info.balance = balance_check;
         }
 
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{MAX_BLOB_GAS_PER_BLOCK, TARGET_BLOB_GAS_PER_BLOCK};
+
+    #[test]
+    fn prevrandao_strategies() {
+        let fixed = PrevrandaoStrategy::Fixed(B256::repeat_byte(1));
+        let block = BlockEnv::new(U256::from(5), U256::from(60), &fixed);
+        assert_eq!(block.prevrandao, Some(B256::repeat_byte(1)));
+        assert_eq!(block.timestamp, U256::from(60));
+
+        let hash = PrevrandaoStrategy::NumberHash;
+        let first = BlockEnv::new(U256::from(1), U256::ZERO, &hash);
+        let second = BlockEnv::new(U256::from(2), U256::ZERO, &hash);
+        assert_ne!(first.prevrandao, second.prevrandao);
+        assert_eq!(first, BlockEnv::new(U256::from(1), U256::ZERO, &hash));
+        let seeded = PrevrandaoStrategy::NumberHashWithSeed(B256::repeat_byte(7));
+        assert_ne!(first.with_prevrandao(&seeded).prevrandao, second.prevrandao);
+
+        let callback = PrevrandaoStrategy::Callback(Arc::new(|block: &BlockEnv| {
+            B256::from_low_u64_be(block.timestamp.as_limbs()[0])
+        }));
+        let block = BlockEnv::default().with_prevrandao(&callback);
+        assert_eq!(block.prevrandao, Some(B256::from_low_u64_be(1)));
+    }
+
+    #[test]
+    fn next_block() {
+        let parent = BlockEnv {
+            gas_limit: U256::from(30_000_000),
+            basefee: U256::from(1_000_000_000),
+            ..BlockEnv::new(U256::from(10), U256::from(100), &Default::default())
+        };
+        let strategy = PrevrandaoStrategy::NumberHash;
+
+        let child = parent.next(U256::from(15_000_000), 0, 12, &strategy);
+        assert_eq!(child.number, U256::from(11));
+        assert_eq!(child.timestamp, U256::from(112));
+        assert_eq!(child.basefee, parent.basefee);
+        assert_eq!(child.gas_limit, parent.gas_limit);
+        assert_eq!(
+            child.prevrandao,
+            Some(keccak256(&child.number.to_be_bytes::<32>()))
+        );
+
+        // full block raises base fee by 12.5%, empty block lowers it by 12.5%.
+        let full = parent.next(U256::from(30_000_000), 0, 12, &strategy);
+        assert_eq!(full.basefee, U256::from(1_125_000_000));
+        let empty = parent.next(U256::ZERO, 0, 12, &strategy);
+        assert_eq!(empty.basefee, U256::from(875_000_000));
+    }
+
+    #[test]
+    fn next_block_blob_gas() {
+        let parent = BlockEnv {
+            blob_excess_gas_and_price: Some(BlobExcessGasAndPrice::new(GAS_PER_BLOB)),
+            ..Default::default()
+        };
+        let strategy = PrevrandaoStrategy::NumberHash;
+
+        // blob gas above target accumulates, below target is subtracted down to zero.
+        let child = parent.next(U256::ZERO, MAX_BLOB_GAS_PER_BLOCK, 12, &strategy);
+        let excess = GAS_PER_BLOB + MAX_BLOB_GAS_PER_BLOCK - TARGET_BLOB_GAS_PER_BLOCK;
+        assert_eq!(
+            child.blob_excess_gas_and_price,
+            Some(BlobExcessGasAndPrice::new(excess))
+        );
+        let child = parent.next(U256::ZERO, 0, 12, &strategy);
+        assert_eq!(child.get_blob_excess_gas(), Some(0));
+
+        let header = ParentHeader {
+            number: U256::from(10),
+            timestamp: U256::from(100),
+            gas_limit: U256::from(30_000_000),
+            gas_used: U256::from(30_000_000),
+            base_fee_per_gas: U256::from(1_000_000_000),
+            excess_blob_gas: Some(TARGET_BLOB_GAS_PER_BLOCK),
+            blob_gas_used: Some(TARGET_BLOB_GAS_PER_BLOCK),
+            ..Default::default()
+        };
+        let child = BlockEnv::from_parent(&header, 12, &strategy);
+        assert_eq!(child.number, U256::from(11));
+        assert_eq!(child.timestamp, U256::from(112));
+        assert_eq!(child.basefee, U256::from(1_125_000_000));
+        assert_eq!(child.get_blob_excess_gas(), Some(TARGET_BLOB_GAS_PER_BLOCK));
+        let header = ParentHeader {
+            excess_blob_gas: None,
+            ..header
+        };
+        let child = BlockEnv::from_parent(&header, 12, &strategy);
+        assert_eq!(child.blob_excess_gas_and_price, None);
+    }
+
+    #[test]
+    fn validate_blob_tx() {
+        let mut env = Env::default();
+        env.tx.transact_to = TransactTo::Call(B160([1; 20]));
+        env.tx.max_fee_per_blob_gas = Some(U256::from(1));
+        assert_eq!(
+            env.validate_tx::<crate::LatestSpec>(),
+            Err(InvalidTransaction::EmptyBlobs)
+        );
+
+        let mut hash = B256::repeat_byte(2);
+        hash.0[0] = VERSIONED_HASH_VERSION_KZG;
+        env.tx.blob_hashes = vec![hash; 2];
+        assert_eq!(env.validate_tx::<crate::LatestSpec>(), Ok(()));
+        assert_eq!(env.tx.get_total_blob_gas(), 2 * GAS_PER_BLOB);
+        assert_eq!(env.calc_data_fee(), Some(U256::from(2 * GAS_PER_BLOB)));
+        assert_eq!(
+            env.validate_tx::<crate::ShanghaiSpec>(),
+            Err(InvalidTransaction::BlobVersionedHashesNotSupported)
+        );
+
+        env.tx.blob_hashes.push(B256::repeat_byte(2));
+        assert_eq!(
+            env.validate_tx::<crate::LatestSpec>(),
+            Err(InvalidTransaction::BlobVersionNotSupported)
+        );
+        env.tx.blob_hashes = vec![hash; 7];
+        assert_eq!(
+            env.validate_tx::<crate::LatestSpec>(),
+            Err(InvalidTransaction::TooManyBlobs)
+        );
+
+        env.block.set_blob_excess_gas_and_price(10_000_000);
+        assert!(env.block.get_blob_gasprice().unwrap() > 1);
+        assert_eq!(
+            env.validate_tx::<crate::LatestSpec>(),
+            Err(InvalidTransaction::BlobGasPriceGreaterThanMax)
+        );
+
+        env.block.blob_excess_gas_and_price = None;
+        assert_eq!(
+            env.validate_block_env::<crate::LatestSpec, ()>(),
+            Err(EVMError::ExcessBlobGasNotSet)
+        );
+    }
+}
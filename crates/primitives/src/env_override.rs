@@ -0,0 +1,88 @@
+//! Values returned by environment opcodes.
+//!
+//! By default opcodes return values of [crate::Env]. An [EnvOverride] set to
+//! [crate::CfgEnv::env_overrides] can return other values for some call frames, as fork
+//! testing tools do to pretend a subcall runs at another time or on another chain.
+use crate::{B160, U256};
+use alloc::sync::Arc;
+use core::fmt;
+
+/// Value returned by an environment opcode.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum EnvValue {
+    /// `TIMESTAMP`.
+    Timestamp,
+    /// `NUMBER`.
+    Number,
+    /// `COINBASE`, address in the low 20 bytes.
+    Coinbase,
+    /// `PREVRANDAO`, `DIFFICULTY` before the merge.
+    PrevRandao,
+    /// `CHAINID`.
+    ChainId,
+    /// `BASEFEE`.
+    BaseFee,
+}
+
+/// Call frame executing an environment opcode.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EnvFrame {
+    /// Address whose code is executing.
+    pub address: B160,
+    pub caller: B160,
+    /// Depth of the frame, one for the transaction.
+    pub depth: u64,
+}
+
+/// Overrides of values returned by environment opcodes.
+pub trait EnvOverride: Send + Sync {
+    /// Value opcode of `value` returns in `frame`, None for the value of the environment.
+    fn env_value(&self, value: EnvValue, frame: &EnvFrame) -> Option<U256>;
+}
+
+impl<F> EnvOverride for F
+where
+    F: Fn(EnvValue, &EnvFrame) -> Option<U256> + Send + Sync,
+{
+    fn env_value(&self, value: EnvValue, frame: &EnvFrame) -> Option<U256> {
+        self(value, frame)
+    }
+}
+
+/// [EnvOverride] of the EVM, by default values are not overridden.
+#[derive(Clone, Default)]
+pub struct EnvOverrides(Option<Arc<dyn EnvOverride>>);
+
+impl EnvOverrides {
+    pub fn new(overrides: impl EnvOverride + 'static) -> Self {
+        Self(Some(Arc::new(overrides)))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_none()
+    }
+
+    pub fn env_value(&self, value: EnvValue, frame: &EnvFrame) -> Option<U256> {
+        self.0.as_ref()?.env_value(value, frame)
+    }
+}
+
+/// Overrides are same if they are the same instance.
+impl PartialEq for EnvOverrides {
+    fn eq(&self, other: &Self) -> bool {
+        match (&self.0, &other.0) {
+            (Some(a), Some(b)) => Arc::ptr_eq(a, b),
+            (a, b) => a.is_none() && b.is_none(),
+        }
+    }
+}
+
+impl Eq for EnvOverrides {}
+
+impl fmt::Debug for EnvOverrides {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("EnvOverrides")
+            .field(&self.0.as_ref().map(|_| ".."))
+            .finish()
+    }
+}
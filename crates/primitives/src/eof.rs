@@ -0,0 +1,216 @@
+//! EOF container format of EIP-3540, with the function sections of EIP-4750.
+//!
+//! Container is `magic | version | header | types | code sections | data`, header lists
+//! sizes of the sections. Code of the sections is checked by the interpreter, this module
+//! only checks that the container is well formed.
+use crate::Bytes;
+use alloc::vec::Vec;
+use core::ops::Range;
+
+/// First two bytes of EOF containers, 0xEF was reserved for it by EIP-3541.
+pub const EOF_MAGIC: [u8; 2] = [0xEF, 0x00];
+pub const EOF_VERSION: u8 = 1;
+
+const KIND_TYPES: u8 = 0x01;
+const KIND_CODE: u8 = 0x02;
+const KIND_DATA: u8 = 0x03;
+const TERMINATOR: u8 = 0x00;
+
+/// Maximum number of code sections.
+pub const EOF_MAX_CODE_SECTIONS: usize = 1024;
+
+/// Signature of a code section.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TypesSection {
+    /// Stack items the section takes.
+    pub inputs: u8,
+    /// Stack items the section returns.
+    pub outputs: u8,
+    /// Maximum height of the stack the section reaches, inputs included.
+    pub max_stack_height: u16,
+}
+
+/// Error of a malformed EOF container.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EofDecodeError {
+    /// Code does not start with [EOF_MAGIC].
+    MissingMagic,
+    UnknownVersion(u8),
+    /// Header is cut short or a section is missing in it.
+    InvalidHeader,
+    /// Types section is not four bytes for every code section.
+    InvalidTypesSize,
+    ZeroCodeSections,
+    TooManyCodeSections,
+    EmptyCodeSection,
+    /// First code section has inputs or outputs.
+    InvalidFirstSectionType,
+    /// Body is not as long as the header says.
+    InvalidBodySize,
+}
+
+/// Decoded EOF container, sections are ranges of [Eof::raw].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Eof {
+    pub raw: Bytes,
+    pub types: Vec<TypesSection>,
+    pub code_sections: Vec<Range<usize>>,
+    pub data: Range<usize>,
+}
+
+impl Eof {
+    /// If code is meant to be EOF, it may still be malformed.
+    pub fn is_eof(code: &[u8]) -> bool {
+        code.starts_with(&EOF_MAGIC)
+    }
+
+    pub fn decode(raw: Bytes) -> Result<Self, EofDecodeError> {
+        let mut reader = Reader { data: &raw, at: 0 };
+        if reader.bytes(2) != Some(&EOF_MAGIC[..]) {
+            return Err(EofDecodeError::MissingMagic);
+        }
+        match reader.u8() {
+            Some(EOF_VERSION) => {}
+            Some(version) => return Err(EofDecodeError::UnknownVersion(version)),
+            None => return Err(EofDecodeError::InvalidHeader),
+        }
+
+        reader.kind(KIND_TYPES)?;
+        let types_size = reader.u16().ok_or(EofDecodeError::InvalidHeader)? as usize;
+        reader.kind(KIND_CODE)?;
+        let code_count = reader.u16().ok_or(EofDecodeError::InvalidHeader)? as usize;
+        if code_count == 0 {
+            return Err(EofDecodeError::ZeroCodeSections);
+        }
+        if code_count > EOF_MAX_CODE_SECTIONS {
+            return Err(EofDecodeError::TooManyCodeSections);
+        }
+        let mut code_sizes = Vec::with_capacity(code_count);
+        for _ in 0..code_count {
+            match reader.u16().ok_or(EofDecodeError::InvalidHeader)? {
+                0 => return Err(EofDecodeError::EmptyCodeSection),
+                size => code_sizes.push(size as usize),
+            }
+        }
+        reader.kind(KIND_DATA)?;
+        let data_size = reader.u16().ok_or(EofDecodeError::InvalidHeader)? as usize;
+        reader.kind(TERMINATOR)?;
+        if types_size != code_count * 4 {
+            return Err(EofDecodeError::InvalidTypesSize);
+        }
+
+        let body_size = types_size + code_sizes.iter().sum::<usize>() + data_size;
+        if raw.len() - reader.at != body_size {
+            return Err(EofDecodeError::InvalidBodySize);
+        }
+        let types: Vec<_> = (0..code_count)
+            .map(|_| TypesSection {
+                inputs: reader.u8().unwrap(),
+                outputs: reader.u8().unwrap(),
+                max_stack_height: reader.u16().unwrap(),
+            })
+            .collect();
+        if types[0].inputs != 0 || types[0].outputs != 0 {
+            return Err(EofDecodeError::InvalidFirstSectionType);
+        }
+        let code_sections = code_sizes
+            .into_iter()
+            .map(|size| {
+                reader.at += size;
+                reader.at - size..reader.at
+            })
+            .collect();
+        let data = reader.at..raw.len();
+        Ok(Self {
+            raw,
+            types,
+            code_sections,
+            data,
+        })
+    }
+
+    /// Code of the section at `index`.
+    pub fn code(&self, index: usize) -> Option<&[u8]> {
+        self.code_sections
+            .get(index)
+            .map(|range| &self.raw[range.clone()])
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.raw[self.data.clone()]
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    at: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, len: usize) -> Option<&'a [u8]> {
+        let bytes = self.data.get(self.at..self.at + len)?;
+        self.at += len;
+        Some(bytes)
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        self.bytes(1).map(|bytes| bytes[0])
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        self.bytes(2)
+            .map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn kind(&mut self, kind: u8) -> Result<(), EofDecodeError> {
+        match self.u8() {
+            Some(read) if read == kind => Ok(()),
+            _ => Err(EofDecodeError::InvalidHeader),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hex_literal::hex;
+
+    #[test]
+    fn decode() {
+        // two code sections, `CALLF 1 STOP` and `RETF`, and two bytes of data.
+        let code =
+            hex!("ef0001 010008 0200020004 0001 030002 00 00000000 00000000 e3000100 e4 aabb");
+        let eof = Eof::decode(Bytes::copy_from_slice(&code)).unwrap();
+        assert_eq!(eof.types.len(), 2);
+        assert_eq!(eof.code(0), Some(&hex!("e3000100")[..]));
+        assert_eq!(eof.code(1), Some(&hex!("e4")[..]));
+        assert_eq!(eof.data(), hex!("aabb"));
+
+        let decode = |code: &[u8]| Eof::decode(Bytes::copy_from_slice(code));
+        assert_eq!(
+            decode(&code[..code.len() - 1]),
+            Err(EofDecodeError::InvalidBodySize)
+        );
+        assert_eq!(
+            decode(&hex!("ef0002")),
+            Err(EofDecodeError::UnknownVersion(2))
+        );
+        assert_eq!(
+            decode(&hex!("ef0001 010004 02")),
+            Err(EofDecodeError::InvalidHeader)
+        );
+        assert_eq!(decode(&hex!("6000")), Err(EofDecodeError::MissingMagic));
+        assert_eq!(
+            decode(&hex!("ef0001 010004 0200010000 030000 00 00000000")),
+            Err(EofDecodeError::EmptyCodeSection)
+        );
+        assert_eq!(
+            decode(&hex!("ef0001 010008 020001 0001 030000 00 00000000 00")),
+            Err(EofDecodeError::InvalidTypesSize)
+        );
+        assert_eq!(
+            decode(&hex!("ef0001 010004 020001 0001 030000 00 01000001 00")),
+            Err(EofDecodeError::InvalidFirstSectionType)
+        );
+    }
+}
@@ -0,0 +1,161 @@
+//! [Arbitrary] implementations and [proptest] strategies for fuzzing, with the `arbitrary`
+//! feature.
+//!
+//! Generated values are well-formed: code hashes match the code, blob gas price matches
+//! excess blob gas and gas values fit in `u64`, so fuzzers exercise execution instead of
+//! failing in validation. Options of [CfgEnv] that are not part of the consensus (hooks,
+//! caches and the `disable_*` switches) are left at their defaults.
+use crate::{
+    AccountInfo, BlobExcessGasAndPrice, BlockEnv, Bytecode, CfgEnv, HashMap, StorageSlot, TxEnv,
+    U256,
+};
+use alloc::vec::Vec;
+use arbitrary::{Arbitrary, Result, Unstructured};
+use proptest::prelude::*;
+
+impl<'a> Arbitrary<'a> for Bytecode {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(Bytecode::new_raw(u.arbitrary::<Vec<u8>>()?.into()))
+    }
+}
+
+impl<'a> Arbitrary<'a> for AccountInfo {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(AccountInfo::new(
+            u.arbitrary()?,
+            u.arbitrary()?,
+            u.arbitrary()?,
+        ))
+    }
+}
+
+impl<'a> Arbitrary<'a> for BlockEnv {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(BlockEnv {
+            number: u.arbitrary()?,
+            coinbase: u.arbitrary()?,
+            timestamp: u.arbitrary()?,
+            difficulty: u.arbitrary()?,
+            prevrandao: Some(u.arbitrary()?),
+            basefee: U256::from(u.arbitrary::<u64>()?),
+            gas_limit: U256::from(u.arbitrary::<u64>()?),
+            // bounded as blob gas price of large excess takes long to compute
+            blob_excess_gas_and_price: Some(BlobExcessGasAndPrice::new(
+                u.arbitrary::<u32>()?.into(),
+            )),
+        })
+    }
+}
+
+impl<'a> Arbitrary<'a> for TxEnv {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(TxEnv {
+            caller: u.arbitrary()?,
+            gas_limit: u.arbitrary()?,
+            gas_price: U256::from(u.arbitrary::<u64>()?),
+            gas_priority_fee: u.arbitrary::<Option<u64>>()?.map(U256::from),
+            transact_to: u.arbitrary()?,
+            value: u.arbitrary()?,
+            data: u.arbitrary::<Vec<u8>>()?.into(),
+            chain_id: u.arbitrary()?,
+            nonce: u.arbitrary()?,
+            access_list: u.arbitrary()?,
+            blob_hashes: u.arbitrary()?,
+            max_fee_per_blob_gas: u.arbitrary::<Option<u64>>()?.map(U256::from),
+            authorization_list: u.arbitrary()?,
+            #[cfg(feature = "optimism")]
+            optimism: crate::OptimismFields {
+                source_hash: u.arbitrary()?,
+                mint: u.arbitrary()?,
+                is_system_transaction: u.arbitrary()?,
+                enveloped_tx: u.arbitrary::<Option<Vec<u8>>>()?.map(Into::into),
+            },
+        })
+    }
+}
+
+impl<'a> Arbitrary<'a> for CfgEnv {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(CfgEnv {
+            chain_id: U256::from(u.arbitrary::<u64>()?),
+            spec_id: u.arbitrary()?,
+            perf_analyse_created_bytecodes: u.arbitrary()?,
+            limit_contract_code_size: u.arbitrary()?,
+            limit_return_data_size: u.arbitrary()?,
+            limit_log_data_size: u.arbitrary()?,
+            create_collision: u.arbitrary()?,
+            warm_accounts: u.arbitrary()?,
+            state_clear: u.arbitrary()?,
+            ..Default::default()
+        })
+    }
+}
+
+/// Storage slot index, mostly small so that generated storages share slots.
+pub fn storage_key() -> impl Strategy<Value = U256> {
+    prop_oneof![
+        3 => (0u64..16).prop_map(U256::from),
+        1 => any::<U256>(),
+    ]
+}
+
+/// Storage slot, unchanged or changed from its original value.
+pub fn storage_slot() -> impl Strategy<Value = StorageSlot> {
+    prop_oneof![
+        any::<U256>().prop_map(StorageSlot::new),
+        (any::<U256>(), any::<U256>()).prop_map(|(original_value, present_value)| StorageSlot {
+            original_value,
+            present_value,
+        }),
+    ]
+}
+
+/// Changed storage of an account, as in [Account](crate::Account), with up to `max_len` slots.
+pub fn storage(max_len: usize) -> impl Strategy<Value = HashMap<U256, StorageSlot>> {
+    prop::collection::vec((storage_key(), storage_slot()), 0..=max_len)
+        .prop_map(|slots| slots.into_iter().collect())
+}
+
+/// Storage of an account in a database, with up to `max_len` slots.
+pub fn plain_storage(max_len: usize) -> impl Strategy<Value = HashMap<U256, U256>> {
+    prop::collection::vec((storage_key(), any::<U256>()), 0..=max_len)
+        .prop_map(|slots| slots.into_iter().collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{keccak256, KECCAK_EMPTY};
+
+    #[test]
+    fn well_formed() {
+        let bytes: Vec<u8> = (0..4096u32).map(|i| (i * 31 + i / 7) as u8).collect();
+        let mut u = Unstructured::new(&bytes);
+
+        let info = AccountInfo::arbitrary(&mut u).unwrap();
+        let code = info.code.as_ref().unwrap();
+        let hash = if code.is_empty() {
+            KECCAK_EMPTY
+        } else {
+            keccak256(code.original_bytes().as_ref())
+        };
+        assert_eq!(info.code_hash, hash);
+
+        let block = BlockEnv::arbitrary(&mut u).unwrap();
+        let blob = block.blob_excess_gas_and_price.unwrap();
+        assert_eq!(blob, BlobExcessGasAndPrice::new(blob.excess_blob_gas));
+        assert!(block.prevrandao.is_some());
+
+        let tx = TxEnv::arbitrary(&mut u).unwrap();
+        assert!(tx.gas_price <= U256::from(u64::MAX));
+        CfgEnv::arbitrary(&mut u).unwrap();
+    }
+
+    proptest! {
+        #[test]
+        fn storage_len(storage in storage(8), plain in plain_storage(8)) {
+            prop_assert!(storage.len() <= 8);
+            prop_assert!(plain.len() <= 8);
+        }
+    }
+}
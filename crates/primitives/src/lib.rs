@@ -1,10 +1,19 @@
 #![cfg_attr(not(feature = "std"), no_std)]
 
+#[cfg(feature = "std")]
+pub mod analysis_cache;
 pub mod bits;
+pub mod block_hash_provider;
 pub mod bytecode;
+pub mod chain_spec;
 pub mod constants;
 pub mod db;
+pub mod eip7702;
 pub mod env;
+pub mod env_override;
+pub mod eof;
+#[cfg(any(test, feature = "arbitrary"))]
+pub mod fuzz;
 pub mod log;
 pub mod precompile;
 pub mod result;
@@ -14,6 +23,9 @@ pub mod utilities;
 
 extern crate alloc;
 
+#[cfg(feature = "std")]
+pub use analysis_cache::{AnalysisCache, AnalysisCacheStats};
+pub use bits::Bloom;
 pub use bits::B160;
 pub use bits::B256;
 pub use bytes;
@@ -26,9 +38,16 @@ pub type Address = B160;
 pub type Hash = B256;
 
 pub use bitvec;
+pub use block_hash_provider::{
+    BlockHashLookup, BlockHashProvider, BlockHashes, DatabaseBlockHashes, HistoryStorage,
+};
 pub use bytecode::*;
+pub use chain_spec::{ChainSpec, ChainSpecBuilder, ForkCondition};
 pub use constants::*;
+pub use eip7702::SignedAuthorization;
 pub use env::*;
+pub use env_override::{EnvFrame, EnvOverride, EnvOverrides, EnvValue};
+pub use eof::{Eof, EofDecodeError, TypesSection, EOF_MAGIC};
 pub use hashbrown::{hash_map, hash_set, HashMap, HashSet};
 pub use log::Log;
 pub use precompile::*;
@@ -1,10 +1,158 @@
+use crate::{Bytes, Env, HashMap, B160, B256, U256};
+use alloc::sync::Arc;
 use alloc::vec::Vec;
+use core::fmt;
 
 /// A precompile operation result.
 pub type PrecompileResult = Result<(u64, Vec<u8>), PrecompileError>;
 
 pub type StandardPrecompileFn = fn(&[u8], u64) -> PrecompileResult;
 pub type CustomPrecompileFn = fn(&[u8], u64) -> PrecompileResult;
+/// Precompile that can capture state, it gets input and gas limit.
+pub type DynPrecompileFn = Arc<dyn Fn(&[u8], u64) -> PrecompileResult + Send + Sync>;
+
+/// Precompile that reads and changes state through [PrecompileState], as system contracts
+/// of some L2s do.
+///
+/// State access is not charged by the EVM, gas the precompile returns has to include it.
+/// Changes are reverted if the precompile fails.
+pub trait StatefulPrecompile: Send + Sync {
+    fn call(
+        &self,
+        state: &mut dyn PrecompileState,
+        input: &[u8],
+        gas_limit: u64,
+    ) -> PrecompileResult;
+}
+
+impl<F> StatefulPrecompile for F
+where
+    F: Fn(&mut dyn PrecompileState, &[u8], u64) -> PrecompileResult + Send + Sync,
+{
+    fn call(
+        &self,
+        state: &mut dyn PrecompileState,
+        input: &[u8],
+        gas_limit: u64,
+    ) -> PrecompileResult {
+        self(state, input, gas_limit)
+    }
+}
+
+pub type DynStatefulPrecompile = Arc<dyn StatefulPrecompile>;
+
+/// Journaled state as seen by a [StatefulPrecompile].
+///
+/// Database errors are kept by the EVM and fail the transaction, [PrecompileError::Database]
+/// is returned to the precompile and it should return it.
+pub trait PrecompileState {
+    fn env(&self) -> &Env;
+
+    fn context(&self) -> &PrecompileContext;
+
+    fn sload(&mut self, address: B160, index: U256) -> Result<U256, PrecompileError>;
+
+    /// Fails with [PrecompileError::StateChangeDuringStaticCall] in static calls.
+    fn sstore(&mut self, address: B160, index: U256, value: U256) -> Result<(), PrecompileError>;
+
+    fn balance(&mut self, address: B160) -> Result<U256, PrecompileError>;
+
+    /// Emit log from address of the precompile. Fails with
+    /// [PrecompileError::StateChangeDuringStaticCall] in static calls.
+    fn log(&mut self, topics: Vec<B256>, data: Bytes) -> Result<(), PrecompileError>;
+}
+
+/// Call of a [StatefulPrecompile].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PrecompileContext {
+    /// Address the precompile runs as, its own unless it is called with `DELEGATECALL` or
+    /// `CALLCODE`.
+    pub address: B160,
+    pub caller: B160,
+    /// Value of the call, it is already transferred to the precompile.
+    pub value: U256,
+    pub is_static: bool,
+}
+
+/// Precompile registered in [CustomPrecompiles].
+#[derive(Clone)]
+pub enum CustomPrecompile {
+    /// Gets input and gas limit only.
+    Pure(DynPrecompileFn),
+    Stateful(DynStatefulPrecompile),
+}
+
+impl CustomPrecompile {
+    fn ptr_eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Pure(fun), Self::Pure(other)) => Arc::ptr_eq(fun, other),
+            (Self::Stateful(fun), Self::Stateful(other)) => Arc::ptr_eq(fun, other),
+            _ => false,
+        }
+    }
+}
+
+/// Precompiles registered at runtime, they are added to precompiles of the spec and
+/// replace ones at the same address.
+#[derive(Clone, Default)]
+pub struct CustomPrecompiles(HashMap<B160, CustomPrecompile>);
+
+impl CustomPrecompiles {
+    /// Register precompile at `address`, replacing the previous one.
+    pub fn insert(
+        &mut self,
+        address: B160,
+        fun: impl Fn(&[u8], u64) -> PrecompileResult + Send + Sync + 'static,
+    ) {
+        self.0
+            .insert(address, CustomPrecompile::Pure(Arc::new(fun)));
+    }
+
+    /// Register stateful precompile at `address`, replacing the previous one.
+    pub fn insert_stateful(
+        &mut self,
+        address: B160,
+        precompile: impl StatefulPrecompile + 'static,
+    ) {
+        self.0
+            .insert(address, CustomPrecompile::Stateful(Arc::new(precompile)));
+    }
+
+    pub fn remove(&mut self, address: &B160) -> Option<CustomPrecompile> {
+        self.0.remove(address)
+    }
+
+    pub fn get(&self, address: &B160) -> Option<&CustomPrecompile> {
+        self.0.get(address)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&B160, &CustomPrecompile)> {
+        self.0.iter()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+/// Precompiles are same if they are the same closures at the same addresses.
+impl PartialEq for CustomPrecompiles {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.len() == other.0.len()
+            && self
+                .0
+                .iter()
+                .all(|(address, fun)| other.0.get(address).is_some_and(|other| fun.ptr_eq(other)))
+    }
+}
+
+impl Eq for CustomPrecompiles {}
+
+impl fmt::Debug for CustomPrecompiles {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.0.keys()).finish()
+    }
+}
 
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub enum PrecompileError {
@@ -21,4 +169,15 @@ pub enum PrecompileError {
     Bn128FieldPointNotAMember,
     Bn128AffineGFailedToCreate,
     Bn128PairLength,
+    // Blob errors
+    /// Point evaluation input is not 192 bytes.
+    BlobInvalidInputLength,
+    /// Commitment doesn't match the versioned hash.
+    BlobMismatchedVersion,
+    BlobVerifyKzgProofFailed,
+    // Stateful precompile errors
+    /// Database failed, the EVM keeps the error.
+    Database,
+    /// State changed in a static call.
+    StateChangeDuringStaticCall,
 }
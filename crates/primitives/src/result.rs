@@ -1,6 +1,10 @@
-use crate::{Log, State, B160};
+use crate::{Log, State, StorageSlot, B160};
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::string::String;
 use alloc::vec::Vec;
 use bytes::Bytes;
+use core::fmt;
 use ruint::aliases::U256;
 
 pub type EVMResult<DBError> = core::result::Result<ResultAndState, EVMError<DBError>>;
@@ -12,6 +16,127 @@ pub struct ResultAndState {
     pub result: ExecutionResult,
     /// State that got updated
     pub state: State,
+    /// Change of every touched account, set if `CfgEnv::touched_summary` is enabled.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub touched: Option<TouchedAccounts>,
+    /// Where execution halted, set if the result is [ExecutionResult::Halt] and the frame
+    /// of the transaction halted in the interpreter.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub halt: Option<HaltContext>,
+    /// Gas of the call frame of the transaction and its subcalls, set if
+    /// `CfgEnv::gas_frames` is enabled.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub gas_frame: Option<Box<GasFrame>>,
+    /// Changed storage of every account, set if `CfgEnv::storage_diff` is enabled.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub storage_diff: Option<Box<StorageDiff>>,
+}
+
+/// Opcode execution halted at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HaltContext {
+    /// Address whose code was executing.
+    pub address: B160,
+    /// Depth of the frame, one for the transaction.
+    pub depth: u64,
+    pub pc: usize,
+    pub opcode: u8,
+}
+
+/// Gas of a call frame, with frames of its calls in order they were made.
+///
+/// Gas of the frame includes gas of its calls. Intrinsic gas of the transaction is not
+/// part of any frame.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GasFrame {
+    /// Called address or address of the created contract, zero if it wasn't created.
+    pub address: B160,
+    pub is_create: bool,
+    /// If the frame returned successfully.
+    pub success: bool,
+    /// Gas given to the frame.
+    pub gas_limit: u64,
+    /// Gas used by the frame, all of it if the frame halted.
+    pub gas_used: u64,
+    /// Refund of the frame, zero if it didn't return successfully.
+    pub gas_refunded: i64,
+    /// Part of the used gas paid for memory expansion of the frame, calls excluded.
+    pub memory_cost: u64,
+    pub calls: Vec<GasFrame>,
+}
+
+/// Storage changes of every account in the transaction, see [storage_diff].
+pub type StorageDiff = BTreeMap<B160, AccountStorageDiff>;
+
+/// Storage changes of an account in the transaction.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AccountStorageDiff {
+    /// Account was destroyed and every slot of it is cleared, `slots` is empty.
+    pub destroyed: bool,
+    /// Value at the start of the transaction and new value of every changed slot.
+    pub slots: BTreeMap<U256, StorageSlot>,
+}
+
+/// Storage changes in `state` of a transaction, before it is committed. Accounts that are
+/// not destroyed and have no changed slot are skipped, slots that were only read too.
+pub fn storage_diff(state: &State) -> StorageDiff {
+    state
+        .iter()
+        .filter(|(_, account)| account.is_touched())
+        .filter_map(|(address, account)| {
+            let diff = if account.is_selfdestructed() {
+                AccountStorageDiff {
+                    destroyed: true,
+                    slots: BTreeMap::new(),
+                }
+            } else {
+                AccountStorageDiff {
+                    destroyed: false,
+                    slots: account
+                        .storage
+                        .iter()
+                        .filter(|(_, slot)| slot.is_changed())
+                        .map(|(key, slot)| (*key, slot.clone()))
+                        .collect(),
+                }
+            };
+            (diff.destroyed || !diff.slots.is_empty()).then_some((*address, diff))
+        })
+        .collect()
+}
+
+/// Change of every touched account in the transaction.
+pub type TouchedAccounts = BTreeMap<B160, AccountChange>;
+
+/// How touched account changed in the transaction.
+///
+/// Variants are ordered by precedence, account gets the greatest variant that applies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum AccountChange {
+    /// Account was touched but it didn't change.
+    Touched,
+    /// Only balance or nonce changed.
+    BalanceOnly,
+    StorageChanged,
+    CodeChanged,
+    Created,
+    Destroyed,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -88,6 +213,101 @@ impl ExecutionResult {
 
         *gas_used
     }
+
+    /// Returns the output of `REVERT`, none if execution succeeded or halted.
+    pub fn revert_output(&self) -> Option<&Bytes> {
+        match self {
+            Self::Revert { output, .. } => Some(output),
+            _ => None,
+        }
+    }
+
+    /// Returns the reason of the revert if its output is `Error(string)` or `Panic(uint256)`.
+    pub fn decoded_revert_reason(&self) -> Option<RevertReason> {
+        RevertReason::decode(self.revert_output()?)
+    }
+}
+
+/// Reason of a revert, decoded from the output of `REVERT` as emitted by Solidity.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum RevertReason {
+    /// `Error(string)` of `revert("...")` and `require(..., "...")`.
+    Error(String),
+    /// `Panic(uint256)` of failed asserts, arithmetic and out of bounds errors.
+    Panic(U256),
+}
+
+impl RevertReason {
+    /// Selector of `Error(string)`.
+    pub const ERROR_SELECTOR: [u8; 4] = [0x08, 0xc3, 0x79, 0xa0];
+    /// Selector of `Panic(uint256)`.
+    pub const PANIC_SELECTOR: [u8; 4] = [0x4e, 0x48, 0x7b, 0x71];
+
+    /// Decode ABI encoded `Error(string)` or `Panic(uint256)`, none if `output` is neither
+    /// or the message is not UTF-8.
+    pub fn decode(output: &[u8]) -> Option<Self> {
+        if let Some(data) = output.strip_prefix(&Self::PANIC_SELECTOR) {
+            let code = data.get(..32)?;
+            return Some(Self::Panic(U256::from_be_bytes::<32>(
+                code.try_into().unwrap(),
+            )));
+        }
+        let data = output.strip_prefix(&Self::ERROR_SELECTOR)?;
+        let word = |at: usize| -> Option<usize> {
+            let word = data.get(at..at.checked_add(32)?)?;
+            // values that don't fit in usize are out of bounds anyway.
+            if word[..24].iter().any(|b| *b != 0) {
+                return None;
+            }
+            usize::try_from(u64::from_be_bytes(word[24..].try_into().unwrap())).ok()
+        };
+        let offset = word(0)?;
+        let len = word(offset)?;
+        let start = offset.checked_add(32)?;
+        let message = data.get(start..start.checked_add(len)?)?;
+        String::from_utf8(message.to_vec()).ok().map(Self::Error)
+    }
+
+    /// What the Solidity panic code means, none for errors and unknown codes.
+    pub fn panic_description(&self) -> Option<&'static str> {
+        let Self::Panic(code) = self else {
+            return None;
+        };
+        let description = match u64::try_from(*code).ok()? {
+            0x00 => "generic compiler panic",
+            0x01 => "assertion failed",
+            0x11 => "arithmetic underflow or overflow",
+            0x12 => "division or modulo by zero",
+            0x21 => "invalid enum value",
+            0x22 => "invalid storage byte array encoding",
+            0x31 => "pop on empty array",
+            0x32 => "array index out of bounds",
+            0x41 => "out of memory",
+            0x51 => "call to zero-initialized function",
+            _ => return None,
+        };
+        Some(description)
+    }
+}
+
+impl fmt::Display for RevertReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Error(message) => f.write_str(message),
+            Self::Panic(code) => {
+                // ruint pads hex to the full width, codes are short.
+                let code = match u64::try_from(*code) {
+                    Ok(code) => alloc::format!("{code:#x}"),
+                    Err(_) => alloc::format!("{code:#x}"),
+                };
+                match self.panic_description() {
+                    Some(description) => write!(f, "panic: {description} ({code})"),
+                    None => write!(f, "panic: {code}"),
+                }
+            }
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -125,6 +345,8 @@ pub enum EVMError<DBError> {
     Transaction(InvalidTransaction),
     /// REVM specific and related to environment.
     PrevrandaoNotSet,
+    /// EIP-4844: Excess blob gas is required since Cancun.
+    ExcessBlobGasNotSet,
     Database(DBError),
 }
 
@@ -145,7 +367,8 @@ pub enum InvalidTransaction {
     RejectCallerWithCode,
     /// Transaction account does not have enough amount of ether to cover transferred value and gas_limit*gas_price.
     LackOfFundForMaxFee {
-        fee: u64,
+        /// Cost the balance needs to cover.
+        fee: U256,
         balance: U256,
     },
     /// Overflow payment in transaction.
@@ -166,6 +389,25 @@ pub enum InvalidTransaction {
     /// Access list is not supported is not supported
     /// for blocks before Berlin hardfork.
     AccessListNotSupported,
+    /// EIP-4844: Max fee per blob gas is lower than blob gas price of the block.
+    BlobGasPriceGreaterThanMax,
+    /// EIP-4844: Blob transaction has no blobs.
+    EmptyBlobs,
+    /// EIP-4844: Blob transaction can't be a create transaction.
+    BlobCreateTransaction,
+    /// EIP-4844: Transaction has more than [crate::MAX_BLOB_NUMBER_PER_BLOCK] blobs.
+    TooManyBlobs,
+    /// EIP-4844: Versioned hash of the blob has unknown version.
+    BlobVersionNotSupported,
+    /// Blob hashes are not supported for blocks before Cancun hardfork, or set without
+    /// max fee per blob gas.
+    BlobVersionedHashesNotSupported,
+    /// Max fee per blob gas is not supported for blocks before Cancun hardfork.
+    MaxFeePerBlobGasNotSupported,
+    /// EIP-7702: Authorization list is not supported for blocks before Prague hardfork.
+    AuthorizationListNotSupported,
+    /// EIP-7702: Set code transaction can't be a create transaction.
+    SetCodeCreateTransaction,
 }
 
 /// When transaction return successfully without halts.
@@ -199,6 +441,18 @@ pub enum Halt {
     CreateContractStartingWithEF,
     /// EIP-3860: Limit and meter initcode. Initcode size limit exceeded.
     CreateInitcodeSizeLimit,
+    /// Malformed or invalid EOF init code, or init code returning it.
+    InvalidEOFCode,
+    /// Call, create or selfdestruct beneficiary denied by `CfgEnv::address_filter`.
+    AddressDenied,
+    /// Limits of `ResourceLimiter` inspector.
+    StackLimit,
+    CallDepthLimit,
+    /// Also returned when `CfgEnv::limit_return_data_size` is exceeded.
+    ReturnDataLimit,
+    AllocationLimit,
+    /// Data of logs emitted in the transaction exceeds `CfgEnv::limit_log_data_size`.
+    LogDataLimit,
 
     /* Internal Halts that can be only found inside Inspector */
     OverflowPayment,
@@ -223,3 +477,49 @@ pub enum OutOfGasError {
     // i.e. in `as_usize_or_fail`
     InvalidOperand,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+    use hex_literal::hex;
+
+    #[test]
+    fn decode_revert_reason() {
+        let error = hex!(
+            "08c379a0"
+            "0000000000000000000000000000000000000000000000000000000000000020"
+            "0000000000000000000000000000000000000000000000000000000000000004"
+            "6e6f706500000000000000000000000000000000000000000000000000000000"
+        );
+        let result = ExecutionResult::Revert {
+            gas_used: 0,
+            output: Bytes::copy_from_slice(&error),
+        };
+        assert_eq!(result.revert_output().map(|out| &out[..]), Some(&error[..]));
+        assert_eq!(
+            result.decoded_revert_reason(),
+            Some(RevertReason::Error("nope".into()))
+        );
+        assert_eq!(RevertReason::decode(&error[..error.len() - 32]), None);
+
+        let panic = hex!(
+            "4e487b71"
+            "0000000000000000000000000000000000000000000000000000000000000011"
+        );
+        let reason = RevertReason::decode(&panic).unwrap();
+        assert_eq!(reason, RevertReason::Panic(U256::from(0x11)));
+        assert_eq!(
+            reason.to_string(),
+            "panic: arithmetic underflow or overflow (0x11)"
+        );
+        assert_eq!(RevertReason::decode(&hex!("deadbeef")), None);
+
+        let halt = ExecutionResult::Halt {
+            reason: Halt::OpcodeNotFound,
+            gas_used: 0,
+        };
+        assert_eq!(halt.revert_output(), None);
+        assert_eq!(halt.decoded_revert_reason(), None);
+    }
+}
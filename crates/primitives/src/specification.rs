@@ -1,8 +1,12 @@
+#[cfg(any(test, feature = "arbitrary"))]
+use arbitrary::Arbitrary;
+
 /// SpecId and their activation block
 /// Information was obtained from: https://github.com/ethereum/execution-specs
 #[repr(u8)]
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Ord, PartialOrd, enumn::N)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(Arbitrary))]
 #[allow(non_camel_case_types)]
 pub enum SpecId {
     FRONTIER = 0,         // Frontier	            0
@@ -23,7 +27,11 @@ pub enum SpecId {
     MERGE = 15,           // Paris/Merge	        TBD (Depends on difficulty)
     SHANGHAI = 16,
     CANCUN = 17,
-    LATEST = 18,
+    PRAGUE = 18,
+    /// Last stable fork, default of [CfgEnv](crate::CfgEnv).
+    LATEST = 19,
+    /// EOF, experimental. Newer than [LATEST], it is enabled only if chosen explicitly.
+    OSAKA = 20,
 }
 
 impl SpecId {
@@ -51,6 +59,8 @@ impl From<&str> for SpecId {
             "Merge" => SpecId::MERGE,
             "Shanghai" => SpecId::SHANGHAI,
             "Cancun" => SpecId::CANCUN,
+            "Prague" => SpecId::PRAGUE,
+            "Osaka" => SpecId::OSAKA,
             _ => SpecId::LATEST,
         }
     }
@@ -100,4 +110,7 @@ spec!(LONDON, LondonSpec);
 spec!(MERGE, MergeSpec);
 // MERGE_EOF is pending EVM change
 spec!(SHANGHAI, ShanghaiSpec);
+spec!(CANCUN, CancunSpec);
+spec!(PRAGUE, PragueSpec);
 spec!(LATEST, LatestSpec);
+spec!(OSAKA, OsakaSpec);
@@ -1,4 +1,6 @@
 use crate::{Bytecode, B160, B256, KECCAK_EMPTY, U256};
+#[cfg(any(test, feature = "arbitrary"))]
+use arbitrary::Arbitrary;
 use bitflags::bitflags;
 use hashbrown::HashMap;
 
@@ -119,6 +121,7 @@ impl From<AccountInfo> for Account {
 
 #[derive(Debug, Clone, Default, Eq, PartialEq)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(Arbitrary))]
 pub struct StorageSlot {
     pub original_value: U256,
     /// When loaded with sload present value is set to original value
@@ -1,4 +1,7 @@
-use crate::{B160, B256, U256};
+use crate::{
+    B160, B256, BASE_FEE_MAX_CHANGE_DENOMINATOR, BLOB_GASPRICE_UPDATE_FRACTION,
+    ELASTICITY_MULTIPLIER, MIN_BLOB_GASPRICE, TARGET_BLOB_GAS_PER_BLOCK, U256,
+};
 use hex_literal::hex;
 use sha3::{Digest, Keccak256};
 
@@ -31,6 +34,69 @@ pub fn create2_address(caller: B160, code_hash: B256, salt: U256) -> B160 {
     B160(hasher.finalize().as_slice()[12..].try_into().unwrap())
 }
 
+/// EIP-1559: Base fee of the block following the block with given gas usage.
+pub fn calc_next_base_fee(gas_used: U256, gas_limit: U256, base_fee: U256) -> U256 {
+    let gas_target = gas_limit / U256::from(ELASTICITY_MULTIPLIER);
+    if gas_target.is_zero() || gas_used == gas_target {
+        return base_fee;
+    }
+    let denominator = U256::from(BASE_FEE_MAX_CHANGE_DENOMINATOR);
+    if gas_used > gas_target {
+        let delta = base_fee.saturating_mul(gas_used - gas_target) / gas_target / denominator;
+        base_fee.saturating_add(delta.max(U256::from(1)))
+    } else {
+        let delta = base_fee.saturating_mul(gas_target - gas_used) / gas_target / denominator;
+        base_fee.saturating_sub(delta)
+    }
+}
+
+/// EIP-4844: Excess blob gas of the block whose parent has given excess blob gas and used
+/// given blob gas.
+pub fn calc_excess_blob_gas(parent_excess_blob_gas: u64, parent_blob_gas_used: u64) -> u64 {
+    parent_excess_blob_gas
+        .saturating_add(parent_blob_gas_used)
+        .saturating_sub(TARGET_BLOB_GAS_PER_BLOCK)
+}
+
+/// EIP-4844: Blob gas price of the block with given excess blob gas.
+pub fn calc_blob_gasprice(excess_blob_gas: u64) -> u64 {
+    fake_exponential(
+        MIN_BLOB_GASPRICE,
+        excess_blob_gas,
+        BLOB_GASPRICE_UPDATE_FRACTION,
+    )
+}
+
+/// EIP-4844: Approximation of `factor * e ** (numerator / denominator)` using Taylor
+/// expansion, saturated to `u64::MAX`.
+///
+/// # Panics
+///
+/// If `denominator` is zero.
+pub fn fake_exponential(factor: u64, numerator: u64, denominator: u64) -> u64 {
+    assert_ne!(denominator, 0, "attempt to divide by zero");
+    let factor = factor as u128;
+    let numerator = numerator as u128;
+    let denominator = denominator as u128;
+
+    let mut i = 1;
+    let mut output: u128 = 0;
+    let mut numerator_accum = factor * denominator;
+    while numerator_accum > 0 {
+        let (Some(sum), Some(next)) = (
+            output.checked_add(numerator_accum),
+            numerator_accum.checked_mul(numerator),
+        ) else {
+            return u64::MAX;
+        };
+        output = sum;
+        // denominator is asserted as not zero at the start of the function.
+        numerator_accum = next / (denominator * i);
+        i += 1;
+    }
+    (output / denominator).try_into().unwrap_or(u64::MAX)
+}
+
 /// Serde functions to serde as [bytes::Bytes] hex string
 #[cfg(feature = "serde")]
 pub mod serde_hex_bytes {
@@ -59,3 +125,42 @@ pub mod serde_hex_bytes {
         .map_err(|e| serde::de::Error::custom(e.to_string()))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::GAS_PER_BLOB;
+
+    #[test]
+    fn fake_exponential_vectors() {
+        // test vectors of the reference implementation in EIP-4844.
+        for (factor, numerator, denominator, expected) in [
+            (1, 0, 1, 1),
+            (38493, 0, 1000, 38493),
+            (0, 1234, 2345, 0),
+            (1, 2, 1, 6),
+            (1, 4, 2, 6),
+            (1, 3, 1, 16),
+            (1, 6, 2, 18),
+            (1, 4, 1, 49),
+            (1, 8, 2, 50),
+            (10, 8, 2, 542),
+            (11, 8, 2, 596),
+            (1, 5, 1, 136),
+            (1, 5, 2, 11),
+            (2, 5, 2, 23),
+            (1, 50000000, 2225652, 5709098764),
+        ] {
+            assert_eq!(fake_exponential(factor, numerator, denominator), expected);
+        }
+        assert_eq!(fake_exponential(1, u64::MAX, 1), u64::MAX);
+    }
+
+    #[test]
+    fn blob_gas() {
+        assert_eq!(calc_blob_gasprice(0), MIN_BLOB_GASPRICE);
+        assert_eq!(calc_blob_gasprice(10 * 1024 * 1024), 23);
+        assert_eq!(calc_excess_blob_gas(0, 6 * GAS_PER_BLOB), 3 * GAS_PER_BLOB);
+        assert_eq!(calc_excess_blob_gas(GAS_PER_BLOB, GAS_PER_BLOB), 0);
+    }
+}
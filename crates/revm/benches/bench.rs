@@ -0,0 +1,45 @@
+//! Benchmarks of precompiles and interpreter workloads, see [revm::bench].
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use revm::bench::{precompile_inputs, Workload};
+use revm::precompile::Precompiles;
+
+fn precompiles(c: &mut Criterion) {
+    let precompiles = Precompiles::latest();
+    let mut group = c.benchmark_group("precompile");
+    for input in precompile_inputs() {
+        group.bench_function(input.name, |b| b.iter(|| input.run(precompiles)));
+    }
+    group.finish();
+}
+
+fn workloads(c: &mut Criterion) {
+    let mut group = c.benchmark_group("workload");
+    for mut workload in [
+        Workload::transfer(),
+        Workload::erc20_transfer(),
+        Workload::amm_swap(),
+    ] {
+        group.bench_function(workload.name, |b| b.iter(|| workload.run()));
+    }
+    for iterations in [100, 10_000] {
+        let mut workload = Workload::keccak_loop(iterations);
+        group.bench_with_input(
+            BenchmarkId::new(workload.name, iterations),
+            &iterations,
+            |b, _| b.iter(|| workload.run()),
+        );
+    }
+    group.finish();
+}
+
+fn precompile_calls(c: &mut Criterion) {
+    let mut group = c.benchmark_group("precompile_call");
+    for input in precompile_inputs() {
+        let mut workload = Workload::precompile(&input);
+        group.bench_function(input.name, |b| b.iter(|| workload.run()));
+    }
+    group.finish();
+}
+
+criterion_group!(benches, precompiles, workloads, precompile_calls);
+criterion_main!(benches);
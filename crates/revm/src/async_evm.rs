@@ -0,0 +1,341 @@
+//! Execution of transactions over state read from [DatabaseAsync].
+//!
+//! Transaction is driven frame by frame, see [crate::frame]. State that was read before is
+//! kept in a [CacheDB], a read that misses it fails with [Read]. The failed frame or opcode is
+//! rolled back and execution is suspended while the read is awaited, then it continues from
+//! the same opcode. Nothing is executed twice, and the result is same as with a synchronous
+//! database.
+//!
+//! Handling of the transaction is shared with [EVM](crate::EVM), only reads are different.
+use crate::db::{CacheDB, DatabaseAsync, DatabaseCommit, DatabaseRef, DbAccount};
+use crate::evm::evm_driver;
+use crate::evm_impl::Driver;
+use crate::frame::PauseAt;
+use crate::inspectors::NoOpInspector;
+use crate::primitives::{
+    AccountInfo, Bytecode, EVMError, EVMResult, Env, ExecutionResult, ResultAndState, B160, B256,
+    U256,
+};
+use crate::Inspector;
+
+/// Read that missed the cache of [AsyncEvm], execution waits until it is fetched.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Read {
+    Basic(B160),
+    Code(B256),
+    Storage(B160, U256),
+    BlockHash(U256),
+}
+
+/// Database under the cache of [AsyncEvm], every read from it fails with [Read].
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Unfetched;
+
+impl DatabaseRef for Unfetched {
+    type Error = Read;
+
+    fn basic(&self, address: B160) -> Result<Option<AccountInfo>, Self::Error> {
+        Err(Read::Basic(address))
+    }
+
+    fn code_by_hash(&self, code_hash: B256) -> Result<Bytecode, Self::Error> {
+        Err(Read::Code(code_hash))
+    }
+
+    fn storage(&self, address: B160, index: U256) -> Result<U256, Self::Error> {
+        Err(Read::Storage(address, index))
+    }
+
+    fn block_hash(&self, number: U256) -> Result<B256, Self::Error> {
+        Err(Read::BlockHash(number))
+    }
+}
+
+/// EVM that executes transactions over state fetched from [DatabaseAsync].
+///
+/// Execution is suspended on every read that misses the cache until the read is awaited.
+/// Everything read from the database is kept in the cache, as are changes applied by
+/// [AsyncEvm::transact_commit], so following transactions only await what they read for
+/// the first time.
+pub struct AsyncEvm<DB> {
+    pub env: Env,
+    db: DB,
+    cache: CacheDB<Unfetched>,
+}
+
+impl<DB: DatabaseAsync> AsyncEvm<DB> {
+    pub fn new(db: DB) -> Self {
+        Self::with_env(Env::default(), db)
+    }
+
+    pub fn with_env(env: Env, db: DB) -> Self {
+        Self {
+            env,
+            db,
+            cache: CacheDB::new(Unfetched),
+        }
+    }
+
+    pub fn db(&mut self) -> &mut DB {
+        &mut self.db
+    }
+
+    /// State read and committed so far.
+    pub fn cache(&self) -> &CacheDB<Unfetched> {
+        &self.cache
+    }
+
+    /// Cached state, it can be modified to override what database returns.
+    pub fn cache_mut(&mut self) -> &mut CacheDB<Unfetched> {
+        &mut self.cache
+    }
+
+    /// Execute transaction without committing it, return changed state.
+    pub async fn transact(&mut self) -> EVMResult<DB::Error> {
+        self.execute::<false, _>(NoOpInspector {}).await
+    }
+
+    /// Execute transaction and commit changed state to the cache.
+    pub async fn transact_commit(&mut self) -> Result<ExecutionResult, EVMError<DB::Error>> {
+        let ResultAndState { result, state, .. } = self.transact().await?;
+        self.cache.commit(state);
+        Ok(result)
+    }
+
+    /// Execute transaction with given inspector, return changed state.
+    ///
+    /// Inspector sees every frame and opcode once, those retried after a read are not
+    /// reported again.
+    pub async fn inspect<INSP>(&mut self, inspector: INSP) -> EVMResult<DB::Error>
+    where
+        INSP: Inspector<CacheDB<Unfetched>>,
+    {
+        self.execute::<true, _>(inspector).await
+    }
+
+    async fn execute<const INSPECT: bool, INSP>(
+        &mut self,
+        mut inspector: INSP,
+    ) -> EVMResult<DB::Error>
+    where
+        INSP: Inspector<CacheDB<Unfetched>>,
+    {
+        let mut execution = loop {
+            match self.drive::<INSPECT, _>(&mut inspector, |driver| driver.start()) {
+                Ok(execution) => break execution,
+                Err(EVMError::Database(read)) => {
+                    self.fetch(read).await.map_err(EVMError::Database)?
+                }
+                Err(e) => return Err(from_read(e)),
+            }
+        };
+        loop {
+            let out = self.drive::<INSPECT, _>(&mut inspector, |driver| {
+                driver.resume(&mut execution, PauseAt::End)
+            });
+            match out {
+                Ok(result) => return Ok(result.expect("execution pauses only at its end")),
+                Err(EVMError::Database(read)) => {
+                    self.fetch(read).await.map_err(EVMError::Database)?
+                }
+                Err(e) => return Err(from_read(e)),
+            }
+        }
+    }
+
+    /// Run `f` with driver over the cache. Driver is not kept across awaits, so the future of
+    /// the execution is [Send] when the database and inspector are.
+    fn drive<const INSPECT: bool, R>(
+        &mut self,
+        inspector: &mut dyn Inspector<CacheDB<Unfetched>>,
+        f: impl FnOnce(&mut dyn Driver<Read>) -> R,
+    ) -> R {
+        f(&mut *evm_driver::<_, INSPECT>(
+            &mut self.env,
+            &mut self.cache,
+            inspector,
+        ))
+    }
+
+    /// Await the read and put its result into the cache.
+    async fn fetch(&mut self, read: Read) -> Result<(), DB::Error> {
+        match read {
+            Read::Basic(address) => match self.db.basic(address).await? {
+                Some(info) => self.cache.insert_account_info(address, info),
+                None => {
+                    self.cache
+                        .accounts
+                        .insert(address, DbAccount::new_not_existing());
+                }
+            },
+            Read::Code(code_hash) => {
+                let code = self.db.code_by_hash(code_hash).await?;
+                self.cache.contracts.insert(code_hash, code);
+            }
+            Read::Storage(address, index) => {
+                let value = self.db.storage(address, index).await?;
+                // account is always read before its storage.
+                if let Some(account) = self.cache.accounts.get_mut(&address) {
+                    account.storage.insert(index, value);
+                }
+            }
+            Read::BlockHash(number) => {
+                let hash = self.db.block_hash(number).await?;
+                self.cache.block_hashes.insert(number, hash);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Error that is not a read, reads are awaited instead of returned.
+fn from_read<E>(error: EVMError<Read>) -> EVMError<E> {
+    match error {
+        EVMError::Transaction(e) => EVMError::Transaction(e),
+        EVMError::PrevrandaoNotSet => EVMError::PrevrandaoNotSet,
+        EVMError::ExcessBlobGasNotSet => EVMError::ExcessBlobGasNotSet,
+        EVMError::Database(read) => unreachable!("read {read:?} is fetched"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{Database, DatabaseAsync};
+    use crate::interpreter::{InstructionResult, Interpreter};
+    use crate::primitives::{hex_literal::hex, TransactTo};
+    use crate::{EVMData, InMemoryDB, EVM};
+    use core::convert::Infallible;
+    use core::future::Future;
+
+    /// Counts reads of the wrapped database.
+    #[derive(Default)]
+    struct CountingDB {
+        db: InMemoryDB,
+        reads: usize,
+    }
+
+    impl DatabaseAsync for CountingDB {
+        type Error = Infallible;
+
+        fn basic(
+            &mut self,
+            address: B160,
+        ) -> impl Future<Output = Result<Option<AccountInfo>, Self::Error>> + Send {
+            self.reads += 1;
+            core::future::ready(Database::basic(&mut self.db, address))
+        }
+
+        fn code_by_hash(
+            &mut self,
+            code_hash: B256,
+        ) -> impl Future<Output = Result<Bytecode, Self::Error>> + Send {
+            self.reads += 1;
+            core::future::ready(Database::code_by_hash(&mut self.db, code_hash))
+        }
+
+        fn storage(
+            &mut self,
+            address: B160,
+            index: U256,
+        ) -> impl Future<Output = Result<U256, Self::Error>> + Send {
+            self.reads += 1;
+            core::future::ready(Database::storage(&mut self.db, address, index))
+        }
+
+        fn block_hash(
+            &mut self,
+            number: U256,
+        ) -> impl Future<Output = Result<B256, Self::Error>> + Send {
+            self.reads += 1;
+            core::future::ready(Database::block_hash(&mut self.db, number))
+        }
+    }
+
+    #[test]
+    fn fetches_missing_state() {
+        let contract = B160([0x10; 20]);
+        let mut db = CountingDB::default();
+        // return storage slot 0
+        let code = hex!("60005460005260206000f3").to_vec();
+        db.db.insert_account_info(
+            contract,
+            AccountInfo::new(U256::ZERO, 0, Bytecode::new_raw(code.into())),
+        );
+        db.db
+            .insert_account_storage(contract, U256::ZERO, U256::from(42))
+            .unwrap();
+
+        let mut evm = AsyncEvm::new(db);
+        evm.env.tx.caller = B160([0x20; 20]);
+        evm.env.tx.transact_to = TransactTo::Call(contract);
+
+        let result = futures::executor::block_on(evm.transact_commit()).unwrap();
+        let output = result.output().expect("call succeeded");
+        assert_eq!(
+            U256::from_be_bytes::<32>(output.to_vec().try_into().unwrap()),
+            U256::from(42)
+        );
+
+        // everything is cached now.
+        let reads = evm.db().reads;
+        assert!(reads > 0);
+        futures::executor::block_on(evm.transact()).unwrap();
+        assert_eq!(evm.db().reads, reads);
+    }
+
+    /// Counts executed opcodes.
+    #[derive(Default)]
+    struct Steps(usize);
+
+    impl<DB: Database> Inspector<DB> for Steps {
+        fn step(
+            &mut self,
+            _interp: &mut Interpreter,
+            _data: &mut EVMData<'_, DB>,
+        ) -> InstructionResult {
+            self.0 += 1;
+            InstructionResult::Continue
+        }
+    }
+
+    #[test]
+    fn suspends_on_reads_without_executing_again() {
+        let contract = B160([0x10; 20]);
+        let caller = B160([0x20; 20]);
+        let mut db = InMemoryDB::default();
+        // slot 0 holds the next slot to read, return value of slot 2
+        let code = hex!("600054545460005260206000f3").to_vec();
+        db.insert_account_info(
+            contract,
+            AccountInfo::new(U256::ZERO, 0, Bytecode::new_raw(code.into())),
+        );
+        for (index, value) in [(0, 1), (1, 2), (2, 42)] {
+            db.insert_account_storage(contract, U256::from(index), U256::from(value))
+                .unwrap();
+        }
+
+        let mut sync_evm = EVM::new();
+        sync_evm.env.tx.caller = caller;
+        sync_evm.env.tx.transact_to = TransactTo::Call(contract);
+        sync_evm.database(db.clone());
+        let mut sync_steps = Steps::default();
+        let expected = sync_evm.inspect(&mut sync_steps).unwrap();
+
+        let mut evm = AsyncEvm::with_env(sync_evm.env.clone(), CountingDB { db, reads: 0 });
+        let mut steps = Steps::default();
+        let out = futures::executor::block_on(evm.inspect(&mut steps)).unwrap();
+        assert!(out.result.is_success());
+        assert_eq!(out.result, expected.result);
+        assert_eq!(out.state, expected.state);
+        assert_eq!(steps.0, sync_steps.0);
+    }
+
+    #[test]
+    fn execution_is_send() {
+        fn assert_send<T: Send>(_: &T) {}
+        let mut evm = AsyncEvm::new(CountingDB::default());
+        assert_send(&evm.transact());
+        assert_send(&evm.inspect(Steps::default()));
+    }
+}
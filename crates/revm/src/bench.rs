@@ -0,0 +1,385 @@
+//! Workloads of benchmarks: precompile inputs and transactions of typical contracts.
+//!
+//! Module is only compiled with the `bench` feature. Criterion suite of the crate in
+//! `benches/bench.rs` runs all of them, with `cargo bench -p revm --features bench`. Forks benchmark their own precompiles with the same harness,
+//! either directly with [PrecompileInput::run] on [Precompiles] extended with the custom
+//! ones, or through the EVM with [Workload::precompile]:
+//!
+//! ```
+//! use revm::bench::{PrecompileInput, Workload};
+//! use revm::primitives::{Bytes, B160};
+//!
+//! let address = B160::from_low_u64_be(0x100);
+//! let input = PrecompileInput::new("double", address, Bytes::from(vec![1, 2]));
+//! let mut workload = Workload::precompile(&input);
+//! let custom = &mut workload.evm.env.cfg.custom_precompiles;
+//! custom.insert(address, |input, _| {
+//!     Ok((10, input.iter().map(|byte| byte * 2).collect()))
+//! });
+//! // closure given to `Bencher::iter` of criterion.
+//! let out = workload.run();
+//! assert_eq!(out.result.output().unwrap()[..], [2, 4]);
+//! ```
+//!
+//! Contracts are hand written and small, they have the access pattern of the real ones:
+//! [Workload::erc20_transfer] updates two balances of a mapping and logs, and
+//! [Workload::amm_swap] is a constant product pair, like the one of Uniswap V2, that
+//! updates its reserves and calls the token. State is never committed, so a workload runs
+//! the same transaction every time.
+use crate::precompile::{Precompile, PrecompileResult, Precompiles};
+use crate::primitives::{
+    hex_literal::hex, keccak256, AccountInfo, Bytecode, Bytes, ResultAndState, TransactTo, B160,
+    U256,
+};
+use crate::{InMemoryDB, EVM};
+use alloc::{vec, vec::Vec};
+
+/// Caller of every workload transaction.
+pub const CALLER: B160 = B160([0x10; 20]);
+/// Token of [Workload::erc20_transfer] and [Workload::amm_swap].
+pub const TOKEN: B160 = B160([0x20; 20]);
+/// Pair of [Workload::amm_swap].
+pub const PAIR: B160 = B160([0x30; 20]);
+/// Receiver of transfers.
+pub const RECEIVER: B160 = B160([0x40; 20]);
+
+/// `transfer(address to, uint256 amount)`: checks the selector, moves `amount` between
+/// balances of the mapping at slot zero and logs `Transfer`, reverts if the balance is
+/// too low.
+const ERC20_CODE: [u8; 129] = hex!(
+    "60003560e01c63a9059cbb14601357600080fd5b33600052600060205260406000208054602435808210607c"
+    "57809103825590506004356000526040600020805482019055600052600435337fddf252ad1be2c89b69c2b0"
+    "68fc378daa952ba7f163c4a11628f55a4df523b3ef60206000a3600160005260206000f35b600080fd"
+);
+
+/// Swap of `amountIn` at calldata offset 4, input token is already sent to the pair. Output
+/// of reserves at slot zero and one is `amountIn * 997 * r1 / (r0 * 1000 + amountIn * 997)`,
+/// it is transferred to the caller by calling `transfer` of [TOKEN], and `Swap` is logged.
+const PAIR_CODE: [u8; 151] = hex!(
+    "6000546001546004356103e50280836103e8020182820204905080820360015591600435016000555063a905"
+    "9cbb60e01b6000523360045280602452602060006044600060007320202020202020202020202020202020"
+    "202020205af115609257602052600435600052337fd78ad95fa46c994b6551d0da85fc275fe613ce37657fb8"
+    "d5e3d130840159d82260406000a2005b600080fd"
+);
+
+/// Loop body of [Workload::keccak_loop], after `PUSH4 iterations`: hash the first word of
+/// memory into it until the counter is zero.
+const KECCAK_LOOP: [u8; 23] = hex!("5b8015601a576020600020600052600190036005565b00");
+
+/// Input of a precompile.
+#[derive(Clone, Debug)]
+pub struct PrecompileInput {
+    pub name: &'static str,
+    pub address: B160,
+    pub input: Bytes,
+    /// Gas limit of the call, enough for the input.
+    pub gas_limit: u64,
+}
+
+impl PrecompileInput {
+    pub fn new(name: &'static str, address: B160, input: Bytes) -> Self {
+        Self {
+            name,
+            address,
+            input,
+            gas_limit: 1_000_000,
+        }
+    }
+
+    pub fn gas_limit(mut self, gas_limit: u64) -> Self {
+        self.gas_limit = gas_limit;
+        self
+    }
+
+    /// Run the precompile at the address in `precompiles`, None if there is none or it is
+    /// stateful, as it needs the EVM.
+    pub fn run(&self, precompiles: &Precompiles) -> Option<PrecompileResult> {
+        Some(match precompiles.get(&self.address)? {
+            Precompile::Standard(fun) => fun(&self.input, self.gas_limit),
+            Precompile::Custom(fun) => fun(&self.input, self.gas_limit),
+            Precompile::Dynamic(fun) => fun(&self.input, self.gas_limit),
+            Precompile::Stateful(_) => return None,
+        })
+    }
+}
+
+/// Input of every standard precompile of [Precompiles::latest], valid so the whole
+/// computation is done.
+pub fn precompile_inputs() -> Vec<PrecompileInput> {
+    let address = |index| {
+        let mut address = B160::zero();
+        address.0[19] = index;
+        address
+    };
+    // base 3, exponent and modulus of 32 bytes.
+    let mut modexp = [0; 192].to_vec();
+    for len in [31, 63, 95] {
+        modexp[len] = 32;
+    }
+    modexp[127] = 3;
+    modexp.extend_from_slice(&[0xff; 32]);
+    modexp.extend_from_slice(&hex!(
+        "30644e72e131a029b85045b68181585d97816a916871ca8d3c208c16d87cfd47"
+    ));
+    // 12 rounds of the EIP-152 test vector, message "abc".
+    let mut blake2f = hex!("0000000c").to_vec();
+    blake2f.extend_from_slice(&hex!(
+        "48c9bdf267e6096a3ba7ca8485ae67bb2bf894fe72f36e3cf1361d5f3af54fa5"
+        "d182e6ad7f520e511f6c3e2b8c68059b6bbd41fbabd9831f79217e1319cde05b"
+    ));
+    let mut message = [0; 128];
+    message[..3].copy_from_slice(b"abc");
+    blake2f.extend_from_slice(&message);
+    blake2f.extend_from_slice(&[3, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1]);
+    #[allow(unused_mut)]
+    let mut inputs = vec![
+        PrecompileInput::new(
+            "ecrecover",
+            address(1),
+            Bytes::copy_from_slice(&hex!(
+                "18c547e4f7b0f325ad1e56f57e26c745b09a3e503d86e00e5255ff7f715d3d1c"
+                "000000000000000000000000000000000000000000000000000000000000001c"
+                "73b1693892219d736caba55bdb67216e485557ea6b6af75f37096c9aa6a5a75f"
+                "eeb940b1d03b21e36b0e47e79769f095fe2ab855bd91e3a38756b7d75a9c4549"
+            )),
+        ),
+        PrecompileInput::new("sha256", address(2), Bytes::from(vec![0xab; 1024])),
+        PrecompileInput::new("ripemd160", address(3), Bytes::from(vec![0xab; 1024])),
+        PrecompileInput::new("identity", address(4), Bytes::from(vec![0xab; 1024])),
+        PrecompileInput::new("modexp", address(5), Bytes::from(modexp)),
+        PrecompileInput::new(
+            "bn128_add",
+            address(6),
+            Bytes::copy_from_slice(&hex!(
+                "18b18acfb4c2c30276db5411368e7185b311dd124691610c5d3b74034e093dc9"
+                "063c909c4720840cb5134cb9f59fa749755796819658d32efc0d288198f37266"
+                "07c2b7f58a84bd6145f00c9c2bc0bb1a187f20ff2c92963a88019e7c6a014eed"
+                "06614e20c147e940f2d70da3f74c9a17df361706a4485c742bd6788478fa17d7"
+            )),
+        ),
+        PrecompileInput::new(
+            "bn128_mul",
+            address(7),
+            Bytes::copy_from_slice(&hex!(
+                "2bd3e6d0f3b142924f5ca7b49ce5b9d54c4703d7ae5648e61d02268b1a0a9fb7"
+                "21611ce0a6af85915e2f1d70300909ce2e49dfad4a4619c8390cae66cefdb204"
+                "00000000000000000000000000000000000000000000000011138ce750fa15c2"
+            )),
+        ),
+        PrecompileInput::new(
+            "bn128_pairing",
+            address(8),
+            Bytes::copy_from_slice(&hex!(
+                "1c76476f4def4bb94541d57ebba1193381ffa7aa76ada664dd31c16024c43f59"
+                "3034dd2920f673e204fee2811c678745fc819b55d3e9d294e45c9b03a76aef41"
+                "209dd15ebff5d46c4bd888e51a93cf99a7329636c63514396b4a452003a35bf7"
+                "04bf11ca01483bfa8b34b43561848d28905960114c8ac04049af4b6315a41678"
+                "2bb8324af6cfc93537a2ad1a445cfd0ca2a71acd7ac41fadbf933c2a51be344d"
+                "120a2a4cf30c1bf9845f20c6fe39e07ea2cce61f0c9bb048165fe5e4de877550"
+                "111e129f1cf1097710d41c4ac70fcdfa5ba2023c6ff1cbeac322de49d1b6df7c"
+                "2032c61a830e3c17286de9462bf242fca2883585b93870a73853face6a6bf411"
+                "198e9393920d483a7260bfb731fb5d25f1aa493335a9e71297e485b7aef312c2"
+                "1800deef121f1e76426a00665e5c4479674322d4f75edadd46debd5cd992f6ed"
+                "090689d0585ff075ec9e99ad690c3395bc4b313370b38ef355acdadcd122975b"
+                "12c85ea5db8c6deb4aab71808dcb408fe3d1e7690c43d37b4ce6cc0166fa7daa"
+            )),
+        ),
+        PrecompileInput::new("blake2f", address(9), Bytes::from(blake2f)),
+    ];
+    #[cfg(feature = "c-kzg")]
+    {
+        use crate::precompile::kzg_point_evaluation::kzg_to_versioned_hash;
+        let commitment = hex!("8f59a8d2a1a625a17f3fea0fe5eb8c896db3764f3185481bc22f91b4aaffcca25f26936857bc3a7c2539ea8ec3a952b7");
+        let mut input = kzg_to_versioned_hash(&commitment).to_vec();
+        input.extend_from_slice(&hex!(
+            "73eda753299d7d483339d80809a1d80553bda402fffe5bfeffffffff00000000"
+            "1522a4a7f34e1ea350ae07c29c96c7e79655aa926122e95fe69fcbd932ca49e9"
+        ));
+        input.extend_from_slice(&commitment);
+        input.extend_from_slice(&hex!("a62ad71d14c5719385c0686f1871430475bf3a00f0aa3f7b8dd99a9abc2160744faf0070725e00b60ad9a026a15b1a8c"));
+        inputs.push(PrecompileInput::new(
+            "point_evaluation",
+            address(10),
+            Bytes::from(input),
+        ));
+    }
+    inputs
+}
+
+/// Transaction with the state it runs on.
+pub struct Workload {
+    pub name: &'static str,
+    /// EVM with the state in its database and the transaction in its environment.
+    pub evm: EVM<InMemoryDB>,
+}
+
+impl Workload {
+    /// Workload of transaction from [CALLER] with `transact_to` and `data` on top of `db`.
+    pub fn new(name: &'static str, db: InMemoryDB, transact_to: TransactTo, data: Bytes) -> Self {
+        let mut evm = EVM::new();
+        evm.database(db);
+        evm.env.tx.caller = CALLER;
+        evm.env.tx.transact_to = transact_to;
+        evm.env.tx.data = data;
+        evm.env.tx.gas_limit = 30_000_000;
+        Self { name, evm }
+    }
+
+    /// Execute the transaction, changes are not committed.
+    pub fn run(&mut self) -> ResultAndState {
+        self.evm.transact().expect("workload transaction is valid")
+    }
+
+    /// Value transfer to an account without code.
+    pub fn transfer() -> Self {
+        let mut workload = Self::new(
+            "transfer",
+            funded_db(),
+            TransactTo::Call(RECEIVER),
+            Bytes::new(),
+        );
+        workload.evm.env.tx.value = U256::from(10);
+        workload
+    }
+
+    /// ERC20 `transfer` of the [CALLER] balance to [RECEIVER].
+    pub fn erc20_transfer() -> Self {
+        let mut db = funded_db();
+        insert_code(&mut db, TOKEN, &ERC20_CODE);
+        set_token_balance(&mut db, CALLER, U256::from(1_000_000));
+        Self::new(
+            "erc20_transfer",
+            db,
+            TransactTo::Call(TOKEN),
+            transfer_call(RECEIVER, U256::from(1_000)),
+        )
+    }
+
+    /// Swap of a constant product pair, output is transferred by the token contract.
+    pub fn amm_swap() -> Self {
+        let mut db = funded_db();
+        insert_code(&mut db, TOKEN, &ERC20_CODE);
+        insert_code(&mut db, PAIR, &PAIR_CODE);
+        let reserve = U256::from(1_000_000_000_000u64);
+        set_token_balance(&mut db, PAIR, reserve);
+        for slot in [0, 1] {
+            db.insert_account_storage(PAIR, U256::from(slot), reserve)
+                .unwrap();
+        }
+        // selector is not checked by the pair.
+        let mut data = vec![0; 4];
+        data.extend_from_slice(&U256::from(1_000_000).to_be_bytes::<32>());
+        Self::new("amm_swap", db, TransactTo::Call(PAIR), data.into())
+    }
+
+    /// Loop of `iterations` keccak256 hashes of a word.
+    pub fn keccak_loop(iterations: u32) -> Self {
+        let mut code = vec![0x63];
+        code.extend_from_slice(&iterations.to_be_bytes());
+        code.extend_from_slice(&KECCAK_LOOP);
+        let mut db = funded_db();
+        insert_code(&mut db, TOKEN, &code);
+        Self::new("keccak_loop", db, TransactTo::Call(TOKEN), Bytes::new())
+    }
+
+    /// Transaction calling the precompile with the input.
+    pub fn precompile(input: &PrecompileInput) -> Self {
+        let mut workload = Self::new(
+            input.name,
+            funded_db(),
+            TransactTo::Call(input.address),
+            input.input.clone(),
+        );
+        workload.evm.env.tx.gas_limit = input.gas_limit + 1_000_000;
+        workload
+    }
+}
+
+fn funded_db() -> InMemoryDB {
+    let mut db = InMemoryDB::default();
+    db.insert_account_info(CALLER, AccountInfo::from_balance(U256::from(u64::MAX)));
+    db
+}
+
+fn insert_code(db: &mut InMemoryDB, address: B160, code: &[u8]) {
+    let code = Bytecode::new_raw(Bytes::copy_from_slice(code));
+    db.insert_account_info(address, AccountInfo::new(U256::ZERO, 1, code));
+}
+
+/// Slot of `owner` in the balance mapping of [TOKEN].
+fn balance_slot(owner: B160) -> U256 {
+    let mut key = [0; 64];
+    key[12..32].copy_from_slice(&owner.0);
+    U256::from_be_bytes(keccak256(&key).0)
+}
+
+fn set_token_balance(db: &mut InMemoryDB, owner: B160, balance: U256) {
+    db.insert_account_storage(TOKEN, balance_slot(owner), balance)
+        .unwrap();
+}
+
+/// Calldata of ERC20 `transfer(to, amount)`.
+fn transfer_call(to: B160, amount: U256) -> Bytes {
+    let mut data = hex!("a9059cbb").to_vec();
+    data.extend_from_slice(&[0; 12]);
+    data.extend_from_slice(&to.0);
+    data.extend_from_slice(&amount.to_be_bytes::<32>());
+    data.into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primitives::ExecutionResult;
+
+    #[test]
+    fn precompile_inputs_succeed() {
+        let precompiles = Precompiles::latest();
+        for input in precompile_inputs() {
+            let (_, output) = input.run(precompiles).unwrap().unwrap();
+            if input.name == "ecrecover" {
+                assert_eq!(
+                    output[12..],
+                    hex!("a94f5374fce5edbc8e2a8697c15331677e6ebf0b")
+                );
+            }
+            if input.name == "bn128_pairing" {
+                assert_eq!(U256::from_be_slice(&output), U256::from(1));
+            }
+        }
+    }
+
+    #[test]
+    fn workloads_succeed() {
+        for mut workload in [
+            Workload::transfer(),
+            Workload::erc20_transfer(),
+            Workload::amm_swap(),
+            Workload::keccak_loop(100),
+        ] {
+            let out = workload.run();
+            assert!(out.result.is_success(), "{}", workload.name);
+        }
+
+        let out = Workload::erc20_transfer().run();
+        let balance = |owner| out.state[&TOKEN].storage[&balance_slot(owner)].present_value;
+        assert_eq!(balance(CALLER), U256::from(999_000));
+        assert_eq!(balance(RECEIVER), U256::from(1_000));
+        assert_eq!(out.result.logs().len(), 1);
+
+        let out = Workload::amm_swap().run();
+        let ExecutionResult::Success { logs, .. } = &out.result else {
+            panic!("swap failed");
+        };
+        // 1e6 * 997 * 1e12 / (1e12 * 1000 + 1e6 * 997)
+        let amount_out = U256::from(996_999u64);
+        let balance = |owner| out.state[&TOKEN].storage[&balance_slot(owner)].present_value;
+        assert_eq!(balance(CALLER), amount_out);
+        let pair = &out.state[&PAIR].storage;
+        assert_eq!(
+            pair[&U256::from(1)].present_value,
+            U256::from(1_000_000_000_000u64) - amount_out
+        );
+        assert_eq!(logs.len(), 2);
+    }
+}
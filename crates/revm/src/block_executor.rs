@@ -0,0 +1,417 @@
+//! Execution of the transactions of a block, as a node applies them.
+//!
+//! [BlockExecutor] executes transactions one after another over a [CacheDB], committing each
+//! one, and checks that they fit in gas and blob gas left in the block. EIP-4788 beacon root
+//! is stored before the transactions, withdrawals and block rewards are credited after them.
+//!
+//! EVM keeps touched empty accounts in the changed state, the executor deletes them before
+//! committing if EIP-161 state clearing is enabled, so the database matches the state trie.
+use crate::db::{CacheDB, Database, DatabaseCommit, DatabaseRef};
+use crate::evm::evm_inner;
+use crate::inspectors::NoOpInspector;
+use crate::primitives::{
+    hash_map::Entry, Account, BlockEnv, Bloom, CfgEnv, EVMError, Env, ExecutionResult, SpecId,
+    State, TxEnv, B160, B256, MAX_BLOB_GAS_PER_BLOCK, U256,
+};
+use crate::receipt::{BlockReceipts, Receipt};
+use crate::simulate::merge_state;
+use crate::system_call::beacon_root_contract_call;
+use alloc::vec::Vec;
+
+/// Wei in one gwei, amounts of withdrawals are in gwei.
+const GWEI_TO_WEI: u64 = 1_000_000_000;
+
+/// EIP-4895: Withdrawal from the beacon chain.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+pub struct Withdrawal {
+    pub index: u64,
+    pub validator_index: u64,
+    pub address: B160,
+    /// Amount in gwei.
+    pub amount: u64,
+}
+
+/// Ommer included in a block before the merge, its miner is rewarded too.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Ommer {
+    pub coinbase: B160,
+    pub number: u64,
+}
+
+/// Reward of the miner of a block, zero since the merge.
+pub fn block_reward(spec_id: SpecId) -> U256 {
+    let ether: u128 = if SpecId::enabled(spec_id, SpecId::MERGE) {
+        0
+    } else if SpecId::enabled(spec_id, SpecId::PETERSBURG) {
+        2
+    } else if SpecId::enabled(spec_id, SpecId::BYZANTIUM) {
+        3
+    } else {
+        5
+    };
+    U256::from(ether * 1_000_000_000_000_000_000)
+}
+
+/// Error that stops execution of the block.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BlockExecutionError<DBError> {
+    /// Gas limit of transaction `index` is above gas left in the block.
+    BlockGasLimitExceeded {
+        index: usize,
+        gas_limit: u64,
+        gas_available: u64,
+    },
+    /// Blob gas of transaction `index` is above blob gas left in the block.
+    BlobGasLimitExceeded {
+        index: usize,
+        blob_gas: u64,
+        blob_gas_available: u64,
+    },
+    /// Transaction `index` is invalid or the database failed while executing it.
+    Transaction {
+        index: usize,
+        error: EVMError<DBError>,
+    },
+    /// Database failed while crediting withdrawals or rewards.
+    Database(DBError),
+    /// Database failed while executing a system call.
+    SystemCall(EVMError<DBError>),
+}
+
+/// Executed block.
+#[derive(Clone, Debug)]
+pub struct BlockOutcome {
+    /// Receipts of the transactions, in order.
+    pub receipts: Vec<Receipt>,
+    /// Results of the transactions, in order.
+    pub results: Vec<ExecutionResult>,
+    pub gas_used: u64,
+    pub blob_gas_used: u64,
+    /// Bloom of the block header.
+    pub logs_bloom: Bloom,
+    /// Accounts changed by the block, original values of storage slots are the ones before
+    /// the block. Deleted empty accounts are marked as selfdestructed.
+    pub state: State,
+}
+
+/// Executor of the transactions of one block, see the [module](self) docs.
+#[derive(Debug)]
+pub struct BlockExecutor<'a, ExtDB: DatabaseRef> {
+    db: &'a mut CacheDB<ExtDB>,
+    env: Env,
+    receipts: BlockReceipts,
+    results: Vec<ExecutionResult>,
+    blob_gas_used: u64,
+    state: State,
+}
+
+impl<'a, ExtDB: DatabaseRef> BlockExecutor<'a, ExtDB> {
+    /// Executor of `block` over `db`, with `cfg`.
+    pub fn new(db: &'a mut CacheDB<ExtDB>, cfg: CfgEnv, block: BlockEnv) -> Self {
+        Self {
+            db,
+            env: Env {
+                cfg,
+                block,
+                tx: TxEnv::default(),
+            },
+            receipts: BlockReceipts::new(),
+            results: Vec::new(),
+            blob_gas_used: 0,
+            state: State::new(),
+        }
+    }
+
+    /// Execute the EIP-4788 beacon root system call and commit its changes, see
+    /// [beacon_root_contract_call]. Gas it uses doesn't count against the block.
+    pub fn apply_beacon_root_contract_call(
+        &mut self,
+        parent_beacon_block_root: B256,
+    ) -> Result<Option<ExecutionResult>, BlockExecutionError<ExtDB::Error>> {
+        let out = beacon_root_contract_call(&mut self.env, self.db, parent_beacon_block_root)
+            .map_err(BlockExecutionError::SystemCall)?;
+        Ok(out.map(|out| {
+            self.commit(out.state);
+            out.result
+        }))
+    }
+
+    /// Execute `tx` and commit its changes. Nothing is changed if it is rejected.
+    pub fn execute_transaction(
+        &mut self,
+        tx: TxEnv,
+    ) -> Result<&Receipt, BlockExecutionError<ExtDB::Error>> {
+        let index = self.results.len();
+        if !self.env.cfg.is_block_gas_limit_disabled() {
+            let block_gas_limit = u64::try_from(self.env.block.gas_limit).unwrap_or(u64::MAX);
+            let gas_available = block_gas_limit.saturating_sub(self.gas_used());
+            if tx.gas_limit > gas_available {
+                return Err(BlockExecutionError::BlockGasLimitExceeded {
+                    index,
+                    gas_limit: tx.gas_limit,
+                    gas_available,
+                });
+            }
+        }
+        let blob_gas = tx.get_total_blob_gas();
+        let blob_gas_available = MAX_BLOB_GAS_PER_BLOCK - self.blob_gas_used;
+        if blob_gas > blob_gas_available {
+            return Err(BlockExecutionError::BlobGasLimitExceeded {
+                index,
+                blob_gas,
+                blob_gas_available,
+            });
+        }
+
+        self.env.tx = tx;
+        let out = evm_inner::<_, false>(&mut self.env, self.db, &mut NoOpInspector {})
+            .transact()
+            .map_err(|error| BlockExecutionError::Transaction { index, error })?;
+        self.blob_gas_used += blob_gas;
+        self.commit(out.state);
+        self.results.push(out.result);
+        Ok(self.receipts.push(&self.results[index]))
+    }
+
+    /// Execute `transactions` in order, stops at the first one that is rejected.
+    pub fn execute_transactions(
+        &mut self,
+        transactions: impl IntoIterator<Item = TxEnv>,
+    ) -> Result<(), BlockExecutionError<ExtDB::Error>> {
+        for tx in transactions {
+            self.execute_transaction(tx)?;
+        }
+        Ok(())
+    }
+
+    /// Credit `withdrawals` to their addresses.
+    pub fn apply_withdrawals(
+        &mut self,
+        withdrawals: &[Withdrawal],
+    ) -> Result<(), BlockExecutionError<ExtDB::Error>> {
+        self.increment_balances(withdrawals.iter().map(|withdrawal| {
+            let amount = U256::from(withdrawal.amount) * U256::from(GWEI_TO_WEI);
+            (withdrawal.address, amount)
+        }))
+    }
+
+    /// Credit [block_reward] to the coinbase and miners of `ommers`, nothing is credited
+    /// since the merge.
+    pub fn apply_block_rewards(
+        &mut self,
+        ommers: &[Ommer],
+    ) -> Result<(), BlockExecutionError<ExtDB::Error>> {
+        let reward = block_reward(self.env.cfg.spec_id);
+        if reward == U256::ZERO {
+            return Ok(());
+        }
+        let number = self.env.block.number;
+        let ommer_rewards = ommers.iter().map(|ommer| {
+            // (8 + ommer number - block number) / 8 of the block reward.
+            let factor = U256::from(ommer.number + 8).saturating_sub(number);
+            (ommer.coinbase, factor * reward / U256::from(8))
+        });
+        // miner gets 1/32 of the block reward for every included ommer.
+        let miner_reward = reward + reward / U256::from(32) * U256::from(ommers.len());
+        let coinbase = self.env.block.coinbase;
+        self.increment_balances(ommer_rewards.chain([(coinbase, miner_reward)]))
+    }
+
+    /// Gas used by the transactions so far.
+    pub fn gas_used(&self) -> u64 {
+        self.receipts.cumulative_gas_used()
+    }
+
+    pub fn receipts(&self) -> &[Receipt] {
+        self.receipts.receipts()
+    }
+
+    pub fn env(&self) -> &Env {
+        &self.env
+    }
+
+    /// Receipts and changes of the block.
+    pub fn finish(self) -> BlockOutcome {
+        BlockOutcome {
+            gas_used: self.gas_used(),
+            blob_gas_used: self.blob_gas_used,
+            logs_bloom: self.receipts.logs_bloom(),
+            receipts: self.receipts.into_receipts(),
+            results: self.results,
+            state: self.state,
+        }
+    }
+
+    fn increment_balances(
+        &mut self,
+        increments: impl IntoIterator<Item = (B160, U256)>,
+    ) -> Result<(), BlockExecutionError<ExtDB::Error>> {
+        let mut state = State::new();
+        for (address, amount) in increments {
+            let account = match state.entry(address) {
+                Entry::Occupied(entry) => entry.into_mut(),
+                Entry::Vacant(entry) => {
+                    let info = self
+                        .db
+                        .basic(address)
+                        .map_err(BlockExecutionError::Database)?;
+                    entry.insert(info.map_or_else(Account::new_not_existing, Account::from))
+                }
+            };
+            account.info.balance = account.info.balance.saturating_add(amount);
+            account.mark_touch();
+        }
+        self.commit(state);
+        Ok(())
+    }
+
+    fn commit(&mut self, mut changes: State) {
+        if self.env.cfg.is_state_clear_enabled() {
+            for account in changes.values_mut() {
+                if account.is_touched() && account.is_empty() {
+                    account.mark_selfdestruct();
+                }
+            }
+        }
+        merge_state(&mut self.state, &changes);
+        self.db.commit(changes);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primitives::{AccountInfo, Bytecode, InvalidTransaction, TransactTo};
+    use crate::InMemoryDB;
+
+    const ETHER: u128 = 1_000_000_000_000_000_000;
+
+    #[test]
+    fn executes_block() {
+        let caller = B160([0x10; 20]);
+        let receiver = B160([0x20; 20]);
+        let empty = B160([0x30; 20]);
+        let coinbase = B160([0x40; 20]);
+        let mut db = InMemoryDB::default();
+        db.insert_account_info(caller, AccountInfo::from_balance(U256::from(ETHER)));
+        db.insert_account_info(empty, AccountInfo::new(U256::ZERO, 0, Bytecode::new()));
+
+        let block = BlockEnv {
+            gas_limit: U256::from(50_000),
+            basefee: U256::from(7),
+            coinbase,
+            ..Default::default()
+        };
+        let cfg = CfgEnv {
+            spec_id: SpecId::SHANGHAI,
+            ..Default::default()
+        };
+        let transfer = |nonce, to| TxEnv {
+            caller,
+            gas_limit: 21_000,
+            gas_price: U256::from(10),
+            transact_to: TransactTo::Call(to),
+            value: U256::from(100),
+            nonce: Some(nonce),
+            ..Default::default()
+        };
+        let mut executor = BlockExecutor::new(&mut db, cfg, block);
+        // rejected transactions don't take a receipt index.
+        let mut invalid = transfer(2, receiver);
+        invalid.caller = receiver;
+        assert!(matches!(
+            executor.execute_transaction(invalid),
+            Err(BlockExecutionError::Transaction {
+                index: 0,
+                error: EVMError::Transaction(InvalidTransaction::NonceTooHigh { .. })
+            })
+        ));
+        executor
+            .execute_transactions([transfer(0, receiver), transfer(1, receiver)])
+            .unwrap();
+        let gas_used = if crate::USE_GAS { 42_000 } else { 0 };
+        assert_eq!(executor.receipts()[1].cumulative_gas_used, gas_used);
+
+        let mut over_limit = transfer(2, receiver);
+        over_limit.gas_limit = 50_001 - gas_used;
+        assert_eq!(
+            executor.execute_transaction(over_limit),
+            Err(BlockExecutionError::BlockGasLimitExceeded {
+                index: 2,
+                gas_limit: 50_001 - gas_used,
+                gas_available: 50_000 - gas_used,
+            })
+        );
+
+        executor
+            .apply_withdrawals(&[
+                Withdrawal {
+                    address: receiver,
+                    amount: 2,
+                    ..Default::default()
+                },
+                Withdrawal {
+                    address: empty,
+                    amount: 0,
+                    ..Default::default()
+                },
+            ])
+            .unwrap();
+        executor.apply_block_rewards(&[]).unwrap();
+        let outcome = executor.finish();
+
+        assert_eq!(outcome.gas_used, gas_used);
+        assert_eq!(outcome.receipts.len(), 2);
+        assert!(outcome.state[&empty].is_selfdestructed());
+        assert_eq!(
+            outcome.state[&receiver].info.balance,
+            U256::from(200 + 2 * GWEI_TO_WEI)
+        );
+        if crate::USE_GAS {
+            assert_eq!(
+                db.accounts[&caller].info.balance,
+                U256::from(ETHER - 200 - 10 * gas_used as u128)
+            );
+            // coinbase gets the priority fee only.
+            assert_eq!(
+                db.accounts[&coinbase].info.balance,
+                U256::from(3 * gas_used)
+            );
+        }
+        assert!(db.accounts[&empty].info().is_none());
+    }
+
+    #[test]
+    fn pre_merge_rewards() {
+        let miner = B160([0x10; 20]);
+        let uncle = B160([0x20; 20]);
+        let mut db = InMemoryDB::default();
+        let block = BlockEnv {
+            number: U256::from(100),
+            coinbase: miner,
+            ..Default::default()
+        };
+        let cfg = CfgEnv {
+            spec_id: SpecId::BYZANTIUM,
+            ..Default::default()
+        };
+
+        let mut executor = BlockExecutor::new(&mut db, cfg, block);
+        let ommer = Ommer {
+            coinbase: uncle,
+            number: 99,
+        };
+        executor.apply_block_rewards(&[ommer]).unwrap();
+        executor.finish();
+
+        let reward = 3 * ETHER;
+        assert_eq!(
+            db.accounts[&miner].info.balance,
+            U256::from(reward + reward / 32)
+        );
+        assert_eq!(db.accounts[&uncle].info.balance, U256::from(reward * 7 / 8));
+    }
+}
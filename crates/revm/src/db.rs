@@ -1,14 +1,41 @@
+pub mod access_order;
+pub mod code_overrides;
 pub mod in_memory_db;
+pub mod multi_version;
+pub mod recording;
+pub mod witness;
+
+#[cfg(feature = "std")]
+pub mod concurrent;
+#[cfg(feature = "std")]
+pub use concurrent::CachedReads;
+
+#[cfg(feature = "async")]
+mod async_db;
+#[cfg(feature = "async")]
+pub use async_db::DatabaseAsync;
+#[cfg(all(feature = "async", feature = "tokio"))]
+pub use async_db::WrapDatabaseAsync;
 
 #[cfg(feature = "ethersdb")]
 pub mod ethersdb;
 #[cfg(feature = "ethersdb")]
 pub use ethersdb::EthersDB;
 
+#[cfg(feature = "sled")]
+pub mod persistent;
+#[cfg(feature = "sled")]
+pub use persistent::{PersistentCacheDB, PersistentDBError};
+
 #[cfg(all(not(feature = "ethersdb"), feature = "web3db"))]
 compile_error!(
     "`web3db` feature is deprecated, drop-in replacement can be found with feature `ethersdb`"
 );
 
 pub use crate::primitives::db::*;
+pub use access_order::AccessRecorder;
+pub use code_overrides::CodeOverrides;
 pub use in_memory_db::*;
+pub use multi_version::{MultiVersionState, MultiVersionView, Versioned};
+pub use recording::{Recording, RecordingDatabase, ReplayDatabase};
+pub use witness::{ExecutionWitness, WitnessRecorder};
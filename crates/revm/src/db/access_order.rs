@@ -0,0 +1,152 @@
+//! Database recording order in which accounts and slots are first read.
+use crate::db::{Database, DatabaseCommit};
+use crate::primitives::{Account, AccountInfo, Bytecode, HashMap, HashSet, B160, B256, U256};
+use alloc::vec::Vec;
+
+/// First read of account or storage slot.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Access {
+    Account(B160),
+    Storage(B160, U256),
+}
+
+/// Records every account and storage slot read from the wrapped database, in the order
+/// they were read for the first time.
+///
+/// EVM reads database only on first access of the transaction, so this is also order of
+/// first accesses during execution.
+#[derive(Clone, Debug, Default)]
+pub struct AccessRecorder<DB> {
+    pub db: DB,
+    accesses: Vec<Access>,
+    seen: HashSet<Access>,
+}
+
+impl<DB> AccessRecorder<DB> {
+    pub fn new(db: DB) -> Self {
+        Self {
+            db,
+            accesses: Vec::new(),
+            seen: HashSet::new(),
+        }
+    }
+
+    /// Accesses in chronological order.
+    pub fn accesses(&self) -> &[Access] {
+        &self.accesses
+    }
+
+    /// Take recorded accesses and start recording anew.
+    pub fn take(&mut self) -> Vec<Access> {
+        self.seen.clear();
+        core::mem::take(&mut self.accesses)
+    }
+
+    /// Access list in format of [crate::primitives::TxEnv::access_list], accounts and their
+    /// slots are ordered by first access.
+    pub fn access_list(&self) -> Vec<(B160, Vec<U256>)> {
+        let mut list: Vec<(B160, Vec<U256>)> = Vec::new();
+        for access in &self.accesses {
+            let (address, slot) = match *access {
+                Access::Account(address) => (address, None),
+                Access::Storage(address, slot) => (address, Some(slot)),
+            };
+            let index = match list.iter().position(|(a, _)| *a == address) {
+                Some(index) => index,
+                None => {
+                    list.push((address, Vec::new()));
+                    list.len() - 1
+                }
+            };
+            list[index].1.extend(slot);
+        }
+        list
+    }
+
+    pub fn into_inner(self) -> DB {
+        self.db
+    }
+
+    fn record(&mut self, access: Access) {
+        if self.seen.insert(access) {
+            self.accesses.push(access);
+        }
+    }
+}
+
+impl<DB: Database> Database for AccessRecorder<DB> {
+    type Error = DB::Error;
+
+    fn basic(&mut self, address: B160) -> Result<Option<AccountInfo>, Self::Error> {
+        self.record(Access::Account(address));
+        self.db.basic(address)
+    }
+
+    fn code_by_hash(&mut self, code_hash: B256) -> Result<Bytecode, Self::Error> {
+        self.db.code_by_hash(code_hash)
+    }
+
+    fn storage(&mut self, address: B160, index: U256) -> Result<U256, Self::Error> {
+        self.record(Access::Storage(address, index));
+        self.db.storage(address, index)
+    }
+
+    fn block_hash(&mut self, number: U256) -> Result<B256, Self::Error> {
+        self.db.block_hash(number)
+    }
+}
+
+impl<DB: DatabaseCommit> DatabaseCommit for AccessRecorder<DB> {
+    fn commit(&mut self, changes: HashMap<B160, Account>) {
+        self.db.commit(changes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primitives::{hex_literal::hex, TransactTo};
+    use crate::InMemoryDB;
+
+    #[test]
+    fn records_first_access_order() {
+        let caller = B160([0x10; 20]);
+        let contract = B160([0x20; 20]);
+        // SLOAD(2) SLOAD(1) SLOAD(2) BALANCE(0xff..ff)
+        let code = hex!("60025460015460025473ffffffffffffffffffffffffffffffffffffffff31");
+        let mut db = InMemoryDB::default();
+        db.insert_account_info(
+            contract,
+            AccountInfo::new(U256::ZERO, 0, Bytecode::new_raw(code.to_vec().into())),
+        );
+
+        let mut evm = crate::new();
+        evm.database(AccessRecorder::new(db));
+        evm.env.tx.caller = caller;
+        evm.env.tx.transact_to = TransactTo::Call(contract);
+        evm.transact().unwrap();
+
+        let recorder = evm.db.as_ref().unwrap();
+        let other = B160([0xff; 20]);
+        // coinbase and caller are read before execution.
+        assert_eq!(
+            recorder.accesses(),
+            [
+                Access::Account(B160::zero()),
+                Access::Account(caller),
+                Access::Account(contract),
+                Access::Storage(contract, U256::from(2)),
+                Access::Storage(contract, U256::from(1)),
+                Access::Account(other),
+            ]
+        );
+        assert_eq!(
+            recorder.access_list()[2..],
+            [
+                (contract, vec![U256::from(2), U256::from(1)]),
+                (other, vec![])
+            ]
+        );
+    }
+}
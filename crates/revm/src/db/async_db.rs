@@ -0,0 +1,187 @@
+use crate::primitives::{AccountInfo, Bytecode, B160, B256, U256};
+use core::future::Future;
+
+/// Asynchronous version of [Database](crate::Database), used by [AsyncEvm](crate::AsyncEvm).
+///
+/// Useful for state that lives behind network, like RPC providers, where reads
+/// should be awaited instead of blocking the thread.
+pub trait DatabaseAsync {
+    type Error;
+    /// Get basic account information.
+    fn basic(
+        &mut self,
+        address: B160,
+    ) -> impl Future<Output = Result<Option<AccountInfo>, Self::Error>> + Send;
+    /// Get account code by its hash
+    fn code_by_hash(
+        &mut self,
+        code_hash: B256,
+    ) -> impl Future<Output = Result<Bytecode, Self::Error>> + Send;
+    /// Get storage value of address at index.
+    fn storage(
+        &mut self,
+        address: B160,
+        index: U256,
+    ) -> impl Future<Output = Result<U256, Self::Error>> + Send;
+
+    // History related
+    fn block_hash(
+        &mut self,
+        number: U256,
+    ) -> impl Future<Output = Result<B256, Self::Error>> + Send;
+}
+
+#[cfg(feature = "tokio")]
+pub use wrap::WrapDatabaseAsync;
+
+#[cfg(feature = "tokio")]
+mod wrap {
+    use super::DatabaseAsync;
+    use crate::primitives::{AccountInfo, Bytecode, B160, B256, U256};
+    use crate::Database;
+    use core::future::Future;
+    use tokio::runtime::{Handle, Runtime};
+
+    /// Runtime the futures of the database are driven by.
+    #[derive(Debug)]
+    enum HandleOrRuntime {
+        Handle(Handle),
+        Runtime(Runtime),
+    }
+
+    impl HandleOrRuntime {
+        fn block_on<F: Future + Send>(&self, f: F) -> F::Output
+        where
+            F::Output: Send,
+        {
+            match self {
+                // Handle can be of runtime the caller is executing in, block_in_place lets
+                // its worker thread block.
+                Self::Handle(handle) => tokio::task::block_in_place(move || handle.block_on(f)),
+                Self::Runtime(runtime) => runtime.block_on(f),
+            }
+        }
+    }
+
+    /// Wraps [DatabaseAsync] into blocking [Database], so that it can be used with
+    /// [EVM](crate::EVM) and [CacheDB](crate::db::CacheDB).
+    ///
+    /// Every read blocks current thread until future is resolved on tokio runtime. Blocking
+    /// inside of runtime needs the multi-threaded one.
+    #[derive(Debug)]
+    pub struct WrapDatabaseAsync<T> {
+        db: T,
+        rt: HandleOrRuntime,
+    }
+
+    impl<T> WrapDatabaseAsync<T> {
+        /// Use handle of current runtime, or create new runtime if called outside of one.
+        /// Returns `None` if runtime can't be created.
+        pub fn new(db: T) -> Option<Self> {
+            let rt = match Handle::try_current() {
+                Ok(handle) => HandleOrRuntime::Handle(handle),
+                Err(_) => HandleOrRuntime::Runtime(Runtime::new().ok()?),
+            };
+            Some(Self { db, rt })
+        }
+
+        pub fn with_handle(db: T, handle: Handle) -> Self {
+            Self {
+                db,
+                rt: HandleOrRuntime::Handle(handle),
+            }
+        }
+
+        pub fn with_runtime(db: T, runtime: Runtime) -> Self {
+            Self {
+                db,
+                rt: HandleOrRuntime::Runtime(runtime),
+            }
+        }
+
+        pub fn into_inner(self) -> T {
+            self.db
+        }
+    }
+
+    impl<T: DatabaseAsync> Database for WrapDatabaseAsync<T>
+    where
+        T::Error: Send,
+    {
+        type Error = T::Error;
+
+        fn basic(&mut self, address: B160) -> Result<Option<AccountInfo>, Self::Error> {
+            self.rt.block_on(self.db.basic(address))
+        }
+
+        fn code_by_hash(&mut self, code_hash: B256) -> Result<Bytecode, Self::Error> {
+            self.rt.block_on(self.db.code_by_hash(code_hash))
+        }
+
+        fn storage(&mut self, address: B160, index: U256) -> Result<U256, Self::Error> {
+            self.rt.block_on(self.db.storage(address, index))
+        }
+
+        fn block_hash(&mut self, number: U256) -> Result<B256, Self::Error> {
+            self.rt.block_on(self.db.block_hash(number))
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::db::{CacheDB, DatabaseRef, EmptyDB};
+
+        /// Async database answering from in-memory state after yielding to the runtime.
+        struct Delayed(CacheDB<EmptyDB>);
+
+        impl DatabaseAsync for Delayed {
+            type Error = core::convert::Infallible;
+
+            async fn basic(&mut self, address: B160) -> Result<Option<AccountInfo>, Self::Error> {
+                tokio::task::yield_now().await;
+                DatabaseRef::basic(&self.0, address)
+            }
+
+            async fn code_by_hash(&mut self, code_hash: B256) -> Result<Bytecode, Self::Error> {
+                DatabaseRef::code_by_hash(&self.0, code_hash)
+            }
+
+            async fn storage(&mut self, address: B160, index: U256) -> Result<U256, Self::Error> {
+                tokio::task::yield_now().await;
+                DatabaseRef::storage(&self.0, address, index)
+            }
+
+            async fn block_hash(&mut self, number: U256) -> Result<B256, Self::Error> {
+                DatabaseRef::block_hash(&self.0, number)
+            }
+        }
+
+        fn db() -> Delayed {
+            let mut db = CacheDB::new(EmptyDB::default());
+            let address = B160([0x10; 20]);
+            db.insert_account_info(address, AccountInfo::from_balance(U256::from(3)));
+            db.insert_account_storage(address, U256::from(1), U256::from(2))
+                .unwrap();
+            Delayed(db)
+        }
+
+        fn read(db: &mut impl Database<Error = core::convert::Infallible>) -> (U256, U256) {
+            let address = B160([0x10; 20]);
+            let balance = db.basic(address).unwrap().unwrap().balance;
+            (balance, db.storage(address, U256::from(1)).unwrap())
+        }
+
+        #[test]
+        fn blocks_outside_of_runtime() {
+            let mut db = WrapDatabaseAsync::new(db()).unwrap();
+            assert_eq!(read(&mut db), (U256::from(3), U256::from(2)));
+        }
+
+        #[tokio::test(flavor = "multi_thread")]
+        async fn blocks_inside_of_runtime() {
+            let mut db = WrapDatabaseAsync::new(db()).unwrap();
+            assert_eq!(read(&mut db), (U256::from(3), U256::from(2)));
+        }
+    }
+}
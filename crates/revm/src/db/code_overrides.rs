@@ -0,0 +1,184 @@
+//! Database replacing code of contracts wherever it is loaded.
+use crate::db::{Database, DatabaseCommit, DatabaseRef};
+use crate::primitives::{Account, AccountInfo, Bytecode, HashMap, B160, B256, U256};
+
+/// Replaces code of accounts by address or by code hash, every time code is loaded.
+///
+/// Overrides apply to every frame, including delegate calls, and to accounts that are
+/// loaded again in later transactions. Code hash of the account is not changed, so
+/// `EXTCODEHASH` and `CREATE2` addresses see the deployed code. Overridden code is not
+/// committed to the wrapped database.
+#[derive(Clone, Debug, Default)]
+pub struct CodeOverrides<DB> {
+    pub db: DB,
+    by_address: HashMap<B160, Bytecode>,
+    by_hash: HashMap<B256, Bytecode>,
+}
+
+impl<DB> CodeOverrides<DB> {
+    pub fn new(db: DB) -> Self {
+        Self {
+            db,
+            by_address: HashMap::new(),
+            by_hash: HashMap::new(),
+        }
+    }
+
+    /// Replace code of the account. Takes precedence over override by code hash.
+    pub fn override_address(&mut self, address: B160, code: Bytecode) -> &mut Self {
+        self.by_address.insert(address, code);
+        self
+    }
+
+    /// Replace code with given hash in every account that has it.
+    pub fn override_code_hash(&mut self, code_hash: B256, code: Bytecode) -> &mut Self {
+        self.by_hash.insert(code_hash, code);
+        self
+    }
+
+    pub fn remove_address(&mut self, address: B160) -> Option<Bytecode> {
+        self.by_address.remove(&address)
+    }
+
+    pub fn remove_code_hash(&mut self, code_hash: B256) -> Option<Bytecode> {
+        self.by_hash.remove(&code_hash)
+    }
+
+    pub fn into_inner(self) -> DB {
+        self.db
+    }
+
+    fn apply(&self, address: B160, info: Option<AccountInfo>) -> Option<AccountInfo> {
+        if let Some(code) = self.by_address.get(&address) {
+            let mut info = info.unwrap_or_default();
+            info.code = Some(code.clone());
+            return Some(info);
+        }
+        let mut info = info?;
+        if let Some(code) = self.by_hash.get(&info.code_hash) {
+            info.code = Some(code.clone());
+        }
+        Some(info)
+    }
+
+    fn is_overridden(&self, address: &B160, info: &AccountInfo) -> bool {
+        self.by_address.contains_key(address) || self.by_hash.contains_key(&info.code_hash)
+    }
+}
+
+impl<DB: Database> Database for CodeOverrides<DB> {
+    type Error = DB::Error;
+
+    fn basic(&mut self, address: B160) -> Result<Option<AccountInfo>, Self::Error> {
+        let info = self.db.basic(address)?;
+        Ok(self.apply(address, info))
+    }
+
+    fn code_by_hash(&mut self, code_hash: B256) -> Result<Bytecode, Self::Error> {
+        match self.by_hash.get(&code_hash) {
+            Some(code) => Ok(code.clone()),
+            None => self.db.code_by_hash(code_hash),
+        }
+    }
+
+    fn storage(&mut self, address: B160, index: U256) -> Result<U256, Self::Error> {
+        self.db.storage(address, index)
+    }
+
+    fn block_hash(&mut self, number: U256) -> Result<B256, Self::Error> {
+        self.db.block_hash(number)
+    }
+}
+
+impl<DB: DatabaseRef> DatabaseRef for CodeOverrides<DB> {
+    type Error = DB::Error;
+
+    fn basic(&self, address: B160) -> Result<Option<AccountInfo>, Self::Error> {
+        let info = self.db.basic(address)?;
+        Ok(self.apply(address, info))
+    }
+
+    fn code_by_hash(&self, code_hash: B256) -> Result<Bytecode, Self::Error> {
+        match self.by_hash.get(&code_hash) {
+            Some(code) => Ok(code.clone()),
+            None => self.db.code_by_hash(code_hash),
+        }
+    }
+
+    fn storage(&self, address: B160, index: U256) -> Result<U256, Self::Error> {
+        self.db.storage(address, index)
+    }
+
+    fn block_hash(&self, number: U256) -> Result<B256, Self::Error> {
+        self.db.block_hash(number)
+    }
+}
+
+impl<DB: DatabaseCommit> DatabaseCommit for CodeOverrides<DB> {
+    fn commit(&mut self, mut changes: HashMap<B160, Account>) {
+        for (address, account) in changes.iter_mut() {
+            // code of created contracts is not overridden.
+            if !account.is_newly_created() && self.is_overridden(address, &account.info) {
+                account.info.code = None;
+            }
+        }
+        self.db.commit(changes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primitives::{hex_literal::hex, TransactTo};
+    use crate::InMemoryDB;
+
+    fn returning(value: u8) -> Bytecode {
+        // MSTORE8(0, value) RETURN(0, 1)
+        Bytecode::new_raw(vec![0x60, value, 0x60, 0x00, 0x53, 0x60, 0x01, 0x60, 0x00, 0xf3].into())
+    }
+
+    #[test]
+    fn overrides_delegated_code() {
+        let caller = B160([0x10; 20]);
+        let proxy = B160([0x20; 20]);
+        let implementation = B160([0x30; 20]);
+
+        // DELEGATECALL(GAS, implementation, 0, 0, 0, 1) RETURN(0, 1)
+        let mut code = hex!("600160006000600073").to_vec();
+        code.extend_from_slice(&implementation.0);
+        code.extend_from_slice(&hex!("5af45060016000f3"));
+        let mut db = InMemoryDB::default();
+        let proxy_code = Bytecode::new_raw(code.into());
+        let proxy_hash = proxy_code.hash();
+        db.insert_account_info(proxy, AccountInfo::new(U256::ZERO, 0, proxy_code));
+        let deployed = returning(1);
+        let deployed_hash = deployed.hash();
+        db.insert_account_info(implementation, AccountInfo::new(U256::ZERO, 0, deployed));
+
+        let mut evm = crate::new();
+        evm.env.tx.caller = caller;
+        evm.env.tx.transact_to = TransactTo::Call(proxy);
+        let mut run = |db: CodeOverrides<InMemoryDB>| {
+            evm.database(db);
+            let result = evm.transact_commit().unwrap();
+            (result.output().unwrap()[0], evm.db.take().unwrap())
+        };
+
+        let mut overrides = CodeOverrides::new(db);
+        overrides.override_code_hash(deployed_hash, returning(2));
+        let (output, mut overrides) = run(overrides);
+        assert_eq!(output, 2);
+
+        // address override takes precedence, deployed code is kept in the database.
+        overrides.override_address(implementation, returning(3));
+        let (output, mut overrides) = run(overrides);
+        assert_eq!(output, 3);
+        overrides.override_address(proxy, returning(4));
+        let (output, overrides) = run(overrides);
+        assert_eq!(output, 4);
+        let db = overrides.into_inner();
+        assert_eq!(db.accounts[&proxy].info.code_hash, proxy_hash);
+        let (output, _) = run(CodeOverrides::new(db));
+        assert_eq!(output, 1);
+    }
+}
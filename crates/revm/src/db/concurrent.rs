@@ -0,0 +1,226 @@
+//! Read cache shared by EVMs running on different threads.
+//!
+//! [CachedReads] remembers every value read from the underlying database in maps split into
+//! shards behind their own locks, so threads reading different accounts rarely wait on each
+//! other. It is only a cache of the database: every EVM keeps its own changes, usually in a
+//! [CacheDB](crate::db::CacheDB) layered over a reference to the shared cache.
+use crate::db::DatabaseRef;
+use crate::primitives::{AccountInfo, Bytecode, HashMap, B160, B256, U256};
+use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::vec::Vec;
+
+/// Number of shards of [CachedReads::new].
+pub const DEFAULT_SHARDS: usize = 16;
+
+#[derive(Debug, Default)]
+struct Shard {
+    accounts: HashMap<B160, Option<AccountInfo>>,
+    storage: HashMap<(B160, U256), U256>,
+}
+
+/// Thread-safe cache of reads of `db`.
+///
+/// Threads that miss the same value at the same time both read it from the database.
+/// Errors of the database are not cached.
+#[derive(Debug)]
+pub struct CachedReads<ExtDB> {
+    shards: Vec<RwLock<Shard>>,
+    contracts: RwLock<HashMap<B256, Bytecode>>,
+    block_hashes: RwLock<HashMap<U256, B256>>,
+    pub db: ExtDB,
+}
+
+impl<ExtDB> CachedReads<ExtDB> {
+    pub fn new(db: ExtDB) -> Self {
+        Self::with_shards(db, DEFAULT_SHARDS)
+    }
+
+    /// Cache split into `shards` maps, more shards mean less contention between threads.
+    pub fn with_shards(db: ExtDB, shards: usize) -> Self {
+        Self {
+            shards: (0..shards.max(1)).map(|_| RwLock::default()).collect(),
+            contracts: RwLock::default(),
+            block_hashes: RwLock::default(),
+            db,
+        }
+    }
+
+    /// Number of cached accounts.
+    pub fn accounts_len(&self) -> usize {
+        self.shards
+            .iter()
+            .map(|shard| read(shard).accounts.len())
+            .sum()
+    }
+
+    /// Number of cached storage slots.
+    pub fn storage_len(&self) -> usize {
+        self.shards
+            .iter()
+            .map(|shard| read(shard).storage.len())
+            .sum()
+    }
+
+    /// Drop all cached values, for example after the underlying state changed.
+    pub fn clear(&mut self) {
+        for shard in &mut self.shards {
+            *shard.get_mut().unwrap_or_else(|e| e.into_inner()) = Shard::default();
+        }
+        self.contracts
+            .get_mut()
+            .unwrap_or_else(|e| e.into_inner())
+            .clear();
+        self.block_hashes
+            .get_mut()
+            .unwrap_or_else(|e| e.into_inner())
+            .clear();
+    }
+
+    fn shard(&self, address: &B160) -> &RwLock<Shard> {
+        // addresses are hashes, their last bytes are evenly distributed.
+        let index = u32::from_be_bytes(address.0[16..].try_into().unwrap()) as usize;
+        &self.shards[index % self.shards.len()]
+    }
+}
+
+/// Values are only ever inserted whole, so a lock poisoned by a panicking reader is usable.
+fn read<T>(lock: &RwLock<T>) -> RwLockReadGuard<'_, T> {
+    lock.read().unwrap_or_else(|e| e.into_inner())
+}
+
+fn write<T>(lock: &RwLock<T>) -> RwLockWriteGuard<'_, T> {
+    lock.write().unwrap_or_else(|e| e.into_inner())
+}
+
+impl<ExtDB: DatabaseRef> DatabaseRef for CachedReads<ExtDB> {
+    type Error = ExtDB::Error;
+
+    fn basic(&self, address: B160) -> Result<Option<AccountInfo>, Self::Error> {
+        let shard = self.shard(&address);
+        if let Some(account) = read(shard).accounts.get(&address) {
+            return Ok(account.clone());
+        }
+        let mut account = self.db.basic(address)?;
+        if let Some(code) = account.as_mut().and_then(|info| info.code.take()) {
+            // code is shared through the contracts cache.
+            write(&self.contracts).entry(code.hash()).or_insert(code);
+        }
+        write(shard).accounts.insert(address, account.clone());
+        Ok(account)
+    }
+
+    fn code_by_hash(&self, code_hash: B256) -> Result<Bytecode, Self::Error> {
+        if let Some(code) = read(&self.contracts).get(&code_hash) {
+            return Ok(code.clone());
+        }
+        let code = self.db.code_by_hash(code_hash)?;
+        write(&self.contracts).insert(code_hash, code.clone());
+        Ok(code)
+    }
+
+    fn storage(&self, address: B160, index: U256) -> Result<U256, Self::Error> {
+        let shard = self.shard(&address);
+        if let Some(value) = read(shard).storage.get(&(address, index)) {
+            return Ok(*value);
+        }
+        let value = self.db.storage(address, index)?;
+        write(shard).storage.insert((address, index), value);
+        Ok(value)
+    }
+
+    fn block_hash(&self, number: U256) -> Result<B256, Self::Error> {
+        if let Some(hash) = read(&self.block_hashes).get(&number) {
+            return Ok(*hash);
+        }
+        let hash = self.db.block_hash(number)?;
+        write(&self.block_hashes).insert(number, hash);
+        Ok(hash)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{CacheDB, DatabaseCommit, EmptyDB};
+    use crate::primitives::{hex_literal::hex, Env, TransactTo, TxEnv};
+    use crate::{evm_inner, inspectors::NoOpInspector, InMemoryDB};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::thread;
+
+    /// Counts storage reads that reach the database.
+    struct Counting {
+        db: InMemoryDB,
+        storage_reads: AtomicUsize,
+    }
+
+    impl DatabaseRef for Counting {
+        type Error = <EmptyDB as DatabaseRef>::Error;
+
+        fn basic(&self, address: B160) -> Result<Option<AccountInfo>, Self::Error> {
+            self.db.basic(address)
+        }
+
+        fn code_by_hash(&self, code_hash: B256) -> Result<Bytecode, Self::Error> {
+            self.db.code_by_hash(code_hash)
+        }
+
+        fn storage(&self, address: B160, index: U256) -> Result<U256, Self::Error> {
+            self.storage_reads.fetch_add(1, Ordering::Relaxed);
+            self.db.storage(address, index)
+        }
+
+        fn block_hash(&self, number: U256) -> Result<B256, Self::Error> {
+            self.db.block_hash(number)
+        }
+    }
+
+    #[test]
+    fn shares_reads_between_threads() {
+        let contract = B160([0x10; 20]);
+        // SSTORE(0, SLOAD(0) + 1)
+        let code = hex!("600054600101600055");
+        let mut db = InMemoryDB::default();
+        db.insert_account_info(
+            contract,
+            AccountInfo::new(U256::ZERO, 0, Bytecode::new_raw(code.to_vec().into())),
+        );
+        db.insert_account_storage(contract, U256::ZERO, U256::from(41))
+            .unwrap();
+        let cache = CachedReads::with_shards(
+            Counting {
+                db,
+                storage_reads: AtomicUsize::new(0),
+            },
+            4,
+        );
+
+        let run = |caller: u8| {
+            let mut db = CacheDB::new(&cache);
+            let mut env = Env {
+                tx: TxEnv {
+                    caller: B160([caller; 20]),
+                    transact_to: TransactTo::Call(contract),
+                    ..Default::default()
+                },
+                ..Default::default()
+            };
+            let out = evm_inner::<_, false>(&mut env, &mut db, &mut NoOpInspector {})
+                .transact()
+                .unwrap();
+            db.commit(out.state);
+            // changes stay in the cache db of the thread.
+            db.storage(contract, U256::ZERO).unwrap()
+        };
+        // warm the cache, then read it from other threads.
+        assert_eq!(run(1), U256::from(42));
+        let values: Vec<_> = thread::scope(|scope| {
+            let handles: Vec<_> = (2..6).map(|i| scope.spawn(move || run(i))).collect();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+
+        assert_eq!(values, vec![U256::from(42); 4]);
+        assert_eq!(cache.db.storage_reads.load(Ordering::Relaxed), 1);
+        assert_eq!(cache.storage(contract, U256::ZERO).unwrap(), U256::from(41));
+        assert_eq!(cache.storage_len(), 1);
+    }
+}
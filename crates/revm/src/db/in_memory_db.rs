@@ -1,14 +1,22 @@
-use super::{DatabaseCommit, DatabaseRef};
+use super::{DatabaseBatch, DatabaseCommit, DatabaseRange, DatabaseRef, StorageRange};
+use crate::instrument::phase;
 use crate::primitives::{
-    hash_map::Entry, keccak256, Account, AccountInfo, Bytecode, HashMap, Log, B160, B256,
+    hash_map::Entry, keccak256, Account, AccountInfo, Bytecode, HashMap, HashSet, Log, B160, B256,
     KECCAK_EMPTY, U256,
 };
 use crate::Database;
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::sync::Arc;
 use alloc::vec::Vec;
 use core::convert::Infallible;
+use core::hash::Hash;
+use core::mem;
 
 pub type InMemoryDB = CacheDB<EmptyDB>;
 
+/// Cache forked from a shared [CacheDB], see [CacheDB::fork].
+pub type CacheDBFork<ExtDB> = CacheDB<Arc<CacheDB<ExtDB>>>;
+
 impl Default for InMemoryDB {
     fn default() -> Self {
         CacheDB::new(EmptyDB {})
@@ -22,7 +30,10 @@ impl Default for InMemoryDB {
 /// Accounts and code are stored in two separate maps, the `accounts` map maps addresses to [DbAccount],
 /// whereas contracts are identified by their code hash, and are stored in the `contracts` map.
 /// The [DbAccount] holds the code hash of the contract, which is used to look up the contract in the `contracts` map.
+///
+/// With `serde` feature cache can be serialized, to persist state between restarts.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CacheDB<ExtDB: DatabaseRef> {
     /// Account info where None means it is not existing. Not existing state is needed for Pre TANGERINE forks.
     /// `code` is always `None`, and bytecode can be found in `contracts`.
@@ -33,6 +44,16 @@ pub struct CacheDB<ExtDB: DatabaseRef> {
     pub logs: Vec<Log>,
     /// All cached block hashes from the [DatabaseRef].
     pub block_hashes: HashMap<U256, B256>,
+    /// Accounts loaded from the [DatabaseRef] and not changed since, see
+    /// [CacheDB::prune_untouched]. Accounts changed directly in `accounts` are not tracked.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub loaded: HashSet<B160>,
+    /// Previous accounts of open snapshots, see [CacheDB::snapshot].
+    #[cfg_attr(feature = "serde", serde(skip))]
+    snapshots: Vec<Snapshot>,
+    /// Recency of clean entries, if cache has a budget, see [CacheDB::set_budget].
+    #[cfg_attr(feature = "serde", serde(skip))]
+    lru: Option<Lru>,
     /// The underlying database ([DatabaseRef]) that is used to load data.
     ///
     /// Note: this is read-only, data is never written to this database.
@@ -49,10 +70,22 @@ impl<ExtDB: DatabaseRef> CacheDB<ExtDB> {
             contracts,
             logs: Vec::default(),
             block_hashes: HashMap::new(),
+            loaded: HashSet::new(),
+            snapshots: Vec::new(),
+            lru: None,
             db,
         }
     }
 
+    /// Child cache on top of this one, sharing it instead of cloning it.
+    ///
+    /// Child reads what it doesn't have from the parent and caches it, changes are made in
+    /// the child only. Parent can't be changed while it is shared, many variants can be
+    /// simulated on the same base state with one fork each.
+    pub fn fork(self: &Arc<Self>) -> CacheDBFork<ExtDB> {
+        CacheDB::new(self.clone())
+    }
+
     /// Inserts the account's code into the cache.
     ///
     /// Accounts objects and code are stored separately in the cache, this will take the code from the account and instead map it to the code hash.
@@ -75,13 +108,20 @@ impl<ExtDB: DatabaseRef> CacheDB<ExtDB> {
     /// Insert account info but not override storage
     pub fn insert_account_info(&mut self, address: B160, mut info: AccountInfo) {
         self.insert_contract(&mut info);
+        self.mark_changed(address);
+        self.journal(address);
         self.accounts.entry(address).or_default().info = info;
     }
 
     /// Returns the account for the given address.
     ///
     /// If the account was not found in the cache, it will be loaded from the underlying database.
+    ///
+    /// Account can be changed through the returned reference, so it is not pruned by
+    /// [CacheDB::prune_untouched].
     pub fn load_account(&mut self, address: B160) -> Result<&mut DbAccount, ExtDB::Error> {
+        self.mark_changed(address);
+        self.journal(address);
         let db = &self.db;
         match self.accounts.entry(address) {
             Entry::Occupied(entry) => Ok(entry.into_mut()),
@@ -119,14 +159,587 @@ impl<ExtDB: DatabaseRef> CacheDB<ExtDB> {
         account.storage = storage.into_iter().collect();
         Ok(())
     }
+
+    /// Drop accounts that were loaded from the underlying database and never changed, with
+    /// their cached storage.
+    ///
+    /// Long-lived caches that loaded many accounts speculatively, by prefetching or by failed
+    /// simulations, can use it to free memory. Changed accounts are kept, so no state is lost
+    /// and dropped accounts are loaded again when needed.
+    pub fn prune_untouched(&mut self) -> PruneStats {
+        let mut stats = PruneStats::default();
+        if let Some(lru) = &mut self.lru {
+            lru.accounts = Recency::default();
+            lru.entries = 0;
+        }
+        for address in self.loaded.drain() {
+            if let Some(account) = self.accounts.remove(&address) {
+                stats.accounts += 1;
+                stats.storage_slots += account.storage.len();
+            }
+        }
+        stats
+    }
+
+    /// Start a snapshot of accounts and logs, changes made after it can be reverted with
+    /// [CacheDB::revert_to].
+    ///
+    /// Snapshots nest, reverting one reverts the ones taken after it. Accounts are copied
+    /// when they are first changed after the snapshot, so taking one is cheap.
+    /// Changes made directly to `accounts` are not tracked.
+    pub fn snapshot(&mut self) -> SnapshotId {
+        self.snapshots.push(Snapshot {
+            accounts: HashMap::new(),
+            logs: self.logs.len(),
+        });
+        SnapshotId(self.snapshots.len() - 1)
+    }
+
+    /// Revert accounts and logs to `snapshot` and close it, with snapshots taken after it.
+    /// Returns false if snapshot is already closed.
+    ///
+    /// Cached code and block hashes are kept.
+    pub fn revert_to(&mut self, snapshot: SnapshotId) -> bool {
+        if snapshot.0 >= self.snapshots.len() {
+            return false;
+        }
+        for taken in self.snapshots.drain(snapshot.0..).rev() {
+            for (address, account) in taken.accounts {
+                match account {
+                    Some(account) => self.accounts.insert(address, account),
+                    None => self.accounts.remove(&address),
+                };
+            }
+            self.logs.truncate(taken.logs);
+        }
+        true
+    }
+
+    /// Close `snapshot` and the ones taken after it, keeping the changes. Returns false if
+    /// snapshot is already closed.
+    pub fn discard_snapshot(&mut self, snapshot: SnapshotId) -> bool {
+        if snapshot.0 >= self.snapshots.len() {
+            return false;
+        }
+        let taken: Vec<_> = self.snapshots.drain(snapshot.0..).collect();
+        if let Some(parent) = self.snapshots.last_mut() {
+            // parent keeps the accounts as they were when it was taken.
+            for (address, account) in taken.into_iter().flat_map(|taken| taken.accounts) {
+                parent.accounts.entry(address).or_insert(account);
+            }
+        }
+        true
+    }
+
+    /// Limit memory used by entries loaded from the underlying database, least recently used
+    /// ones are evicted when the cache is over `budget`. None removes the limit.
+    ///
+    /// Only entries that can be loaded again are evicted: accounts that were not changed,
+    /// with their storage, and code loaded by hash. Entries loaded before the budget is set
+    /// are not tracked.
+    pub fn set_budget(&mut self, budget: Option<CacheBudget>) {
+        self.lru = budget.map(|budget| Lru {
+            budget,
+            ..Default::default()
+        });
+    }
+
+    pub fn with_budget(mut self, budget: CacheBudget) -> Self {
+        self.set_budget(Some(budget));
+        self
+    }
+
+    /// Entries evicted since the budget was set.
+    pub fn evictions(&self) -> EvictionStats {
+        self.lru
+            .as_ref()
+            .map(|lru| lru.evictions)
+            .unwrap_or_default()
+    }
+
+    /// Number of accounts, storage slots and contracts in the cache.
+    pub fn size_hint(&self) -> usize {
+        let stats = self.stats();
+        stats.accounts + stats.storage_slots + stats.contracts
+    }
+
+    /// Number of cached entries and estimated memory they use.
+    pub fn stats(&self) -> CacheStats {
+        let storage_slots = self.accounts.values().map(|a| a.storage.len()).sum();
+        let code_bytes = self.contracts.values().map(|code| code.bytes().len()).sum();
+        // every bucket of a hash map has a control byte.
+        let storage_heap: usize = self
+            .accounts
+            .values()
+            .map(|a| a.storage.capacity() * (mem::size_of::<(U256, U256)>() + 1))
+            .sum();
+        let logs_heap: usize = self
+            .logs
+            .iter()
+            .map(|log| log.topics.capacity() * mem::size_of::<B256>() + log.data.len())
+            .sum();
+        let heap_size = self.accounts.capacity() * (mem::size_of::<(B160, DbAccount)>() + 1)
+            + storage_heap
+            + self.contracts.capacity() * (mem::size_of::<(B256, Bytecode)>() + 1)
+            + code_bytes
+            + self.block_hashes.capacity() * (mem::size_of::<(U256, B256)>() + 1)
+            + self.loaded.capacity() * (mem::size_of::<B160>() + 1)
+            + self.logs.capacity() * mem::size_of::<Log>()
+            + logs_heap;
+        CacheStats {
+            accounts: self.accounts.len(),
+            storage_slots,
+            contracts: self.contracts.len(),
+            code_bytes,
+            block_hashes: self.block_hashes.len(),
+            logs: self.logs.len(),
+            heap_size,
+        }
+    }
+
+    /// Set gauges of the [CacheDB::stats], labeled with `name` to tell caches apart.
+    #[cfg(feature = "metrics")]
+    pub fn record_metrics(&self, name: &'static str) {
+        let stats = self.stats();
+        let gauges = [
+            ("revm.cache_db.accounts", stats.accounts),
+            ("revm.cache_db.storage_slots", stats.storage_slots),
+            ("revm.cache_db.contracts", stats.contracts),
+            ("revm.cache_db.code_bytes", stats.code_bytes),
+            ("revm.cache_db.block_hashes", stats.block_hashes),
+            ("revm.cache_db.logs", stats.logs),
+            ("revm.cache_db.heap_size", stats.heap_size),
+        ];
+        for (gauge, value) in gauges {
+            metrics::gauge!(gauge, value as f64, "cache" => name);
+        }
+    }
+
+    /// Account is about to be changed, it can't be evicted anymore.
+    fn mark_changed(&mut self, address: B160) {
+        self.loaded.remove(&address);
+        if let Some(lru) = &mut self.lru {
+            if lru.accounts.remove(&address) {
+                let slots = self.accounts.get(&address).map_or(0, |a| a.storage.len());
+                lru.entries -= 1 + slots;
+            }
+        }
+    }
+
+    /// Clean account was used and `new_slots` of its storage were loaded.
+    fn track_account(&mut self, address: B160, new_slots: usize) {
+        let Some(lru) = &mut self.lru else {
+            return;
+        };
+        if !self.loaded.contains(&address) {
+            return;
+        }
+        if lru.accounts.touch(address) {
+            lru.entries += 1;
+        }
+        lru.entries += new_slots;
+        while lru.entries > lru.budget.max_entries {
+            let Some(address) = lru.accounts.pop_oldest() else {
+                break;
+            };
+            self.loaded.remove(&address);
+            let slots = self
+                .accounts
+                .remove(&address)
+                .map_or(0, |a| a.storage.len());
+            lru.entries -= 1 + slots;
+            lru.evictions.accounts += 1;
+            lru.evictions.storage_slots += slots as u64;
+        }
+    }
+
+    /// Code was used, `loaded` is true if it was loaded from the underlying database.
+    fn track_contract(&mut self, code_hash: B256, loaded: bool) {
+        let Some(lru) = &mut self.lru else {
+            return;
+        };
+        if !loaded {
+            lru.contracts.refresh(&code_hash);
+            return;
+        }
+        if lru.contracts.touch(code_hash) {
+            lru.code_bytes += self.contracts.get(&code_hash).map_or(0, Bytecode::len);
+        }
+        while lru.code_bytes > lru.budget.max_code_bytes {
+            let Some(code_hash) = lru.contracts.pop_oldest() else {
+                break;
+            };
+            lru.code_bytes -= self
+                .contracts
+                .remove(&code_hash)
+                .map_or(0, |code| code.len());
+            lru.evictions.contracts += 1;
+        }
+    }
+
+    /// Save account before it is changed, if it is first changed since the last snapshot.
+    fn journal(&mut self, address: B160) {
+        if let Some(snapshot) = self.snapshots.last_mut() {
+            if let Entry::Vacant(entry) = snapshot.accounts.entry(address) {
+                entry.insert(self.accounts.get(&address).cloned());
+            }
+        }
+    }
+}
+
+impl<ExtDB: DatabaseRef> CacheDB<ExtDB> {
+    /// Load `accounts`, storage `slots` and code of the accounts that are not cached yet
+    /// with functions looking up many entries of the underlying database at once.
+    fn prefetch_with(
+        &mut self,
+        accounts: impl IntoIterator<Item = B160>,
+        slots: impl IntoIterator<Item = (B160, U256)>,
+        basic_many: impl FnOnce(&ExtDB, &[B160]) -> Result<Vec<Option<AccountInfo>>, ExtDB::Error>,
+        code_many: impl FnOnce(&ExtDB, &[B256]) -> Result<Vec<Bytecode>, ExtDB::Error>,
+        storage_many: impl FnOnce(&ExtDB, &[(B160, U256)]) -> Result<Vec<U256>, ExtDB::Error>,
+    ) -> Result<(), ExtDB::Error> {
+        let slots: HashSet<(B160, U256)> = slots.into_iter().collect();
+        let addresses: Vec<B160> = accounts
+            .into_iter()
+            .chain(slots.iter().map(|(address, _)| *address))
+            .filter(|address| !self.accounts.contains_key(address))
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect();
+
+        // empty batches are not looked up, it could be a round trip for remote databases.
+        let infos = if addresses.is_empty() {
+            Vec::new()
+        } else {
+            basic_many(&self.db, &addresses)?
+        };
+        for (address, info) in addresses.into_iter().zip(infos) {
+            let account = info
+                .map(|info| DbAccount {
+                    info,
+                    ..Default::default()
+                })
+                .unwrap_or_else(DbAccount::new_not_existing);
+            self.accounts.insert(address, account);
+            self.loaded.insert(address);
+            self.track_account(address, 0);
+        }
+
+        let code_hashes: Vec<B256> = self
+            .accounts
+            .values()
+            .filter(|account| account.info.code.is_none())
+            .map(|account| account.info.code_hash)
+            .filter(|code_hash| !self.contracts.contains_key(code_hash))
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect();
+        // storage of not existing and cleared accounts is zero, it is not loaded.
+        let slots: Vec<(B160, U256)> = slots
+            .into_iter()
+            .filter(|(address, slot)| {
+                self.accounts.get(address).is_some_and(|account| {
+                    !account.storage.contains_key(slot)
+                        && !matches!(
+                            account.account_state,
+                            AccountState::StorageCleared | AccountState::NotExisting
+                        )
+                })
+            })
+            .collect();
+
+        let codes = if code_hashes.is_empty() {
+            Vec::new()
+        } else {
+            code_many(&self.db, &code_hashes)?
+        };
+        for (code_hash, code) in code_hashes.into_iter().zip(codes) {
+            self.contracts.insert(code_hash, code);
+            self.track_contract(code_hash, true);
+        }
+        let values = if slots.is_empty() {
+            Vec::new()
+        } else {
+            storage_many(&self.db, &slots)?
+        };
+        for ((address, slot), value) in slots.into_iter().zip(values) {
+            if let Some(account) = self.accounts.get_mut(&address) {
+                account.storage.insert(slot, value);
+                self.track_account(address, 1);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<ExtDB: DatabaseBatch> CacheDB<ExtDB> {
+    /// Load `accounts` and storage `slots` that are not cached yet with batched lookups of
+    /// the underlying database, so execution finds them in the cache. Code of the accounts
+    /// is loaded one by one.
+    ///
+    /// Accounts of `slots` are loaded too. Entries are cached as if they were loaded by
+    /// execution, so cached entries are not overwritten and prefetched accounts can be
+    /// dropped by [CacheDB::prune_untouched]. Returns the first database error, entries
+    /// loaded before it are kept.
+    pub fn prefetch_batch(
+        &mut self,
+        accounts: impl IntoIterator<Item = B160>,
+        slots: impl IntoIterator<Item = (B160, U256)>,
+    ) -> Result<(), ExtDB::Error> {
+        self.prefetch_with(
+            accounts,
+            slots,
+            |db, addresses| db.basic_many(addresses),
+            |db, code_hashes| {
+                code_hashes
+                    .iter()
+                    .map(|code_hash| db.code_by_hash(*code_hash))
+                    .collect()
+            },
+            |db, slots| db.storage_many(slots),
+        )
+    }
+
+    /// Load accounts and slots of `access_list` with [CacheDB::prefetch_batch], before the
+    /// transaction of the access list is executed.
+    pub fn prefetch_access_list(
+        &mut self,
+        access_list: &[(B160, Vec<U256>)],
+    ) -> Result<(), ExtDB::Error> {
+        let slots = access_list
+            .iter()
+            .flat_map(|(address, slots)| slots.iter().map(|slot| (*address, *slot)));
+        self.prefetch_batch(access_list.iter().map(|(address, _)| *address), slots)
+    }
+}
+
+#[cfg(feature = "parallel")]
+impl<ExtDB> CacheDB<ExtDB>
+where
+    ExtDB: DatabaseRef + Sync,
+    ExtDB::Error: Send,
+{
+    /// Load `accounts` and storage `slots` that are not cached yet from the underlying
+    /// database, on one thread per available core, so execution finds them in the cache.
+    ///
+    /// Accounts of `slots` and code of the accounts are loaded too. Entries are cached as if
+    /// they were loaded by execution, so cached entries are not overwritten and prefetched
+    /// accounts can be dropped by [CacheDB::prune_untouched]. Returns the first database error,
+    /// entries loaded before it are kept.
+    pub fn prefetch(
+        &mut self,
+        accounts: impl IntoIterator<Item = B160>,
+        slots: impl IntoIterator<Item = (B160, U256)>,
+    ) -> Result<(), ExtDB::Error> {
+        let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+        self.prefetch_with(
+            accounts,
+            slots,
+            |db, addresses| load_parallel(addresses, threads, |address| db.basic(*address)),
+            |db, code_hashes| {
+                load_parallel(code_hashes, threads, |code_hash| {
+                    db.code_by_hash(*code_hash)
+                })
+            },
+            |db, slots| {
+                load_parallel(slots, threads, |(address, slot)| {
+                    db.storage(*address, *slot)
+                })
+            },
+        )
+    }
+}
+
+/// Call `load` for every item on up to `threads` threads, results are in order of `items`.
+#[cfg(feature = "parallel")]
+fn load_parallel<T, R, E>(
+    items: &[T],
+    threads: usize,
+    load: impl Fn(&T) -> Result<R, E> + Sync,
+) -> Result<Vec<R>, E>
+where
+    T: Sync,
+    R: Send,
+    E: Send,
+{
+    if items.len() < 2 || threads < 2 {
+        return items.iter().map(&load).collect();
+    }
+    let chunk_size = items.len().div_ceil(threads);
+    let load = &load;
+    std::thread::scope(|scope| {
+        let handles: Vec<_> = items
+            .chunks(chunk_size)
+            .map(|chunk| scope.spawn(move || chunk.iter().map(load).collect::<Result<Vec<_>, E>>()))
+            .collect();
+        let mut out = Vec::with_capacity(items.len());
+        for handle in handles {
+            out.extend(handle.join().expect("prefetch thread panicked")?);
+        }
+        Ok(out)
+    })
+}
+
+/// Snapshot of [CacheDB], see [CacheDB::snapshot].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SnapshotId(usize);
+
+/// Accounts before they were changed since the snapshot, none if they were not cached.
+#[derive(Clone, Debug, Default)]
+struct Snapshot {
+    accounts: HashMap<B160, Option<DbAccount>>,
+    logs: usize,
+}
+
+/// Memory budget of entries [CacheDB] loaded from the underlying database.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CacheBudget {
+    /// Unchanged accounts and their storage slots, every account and slot is one entry.
+    pub max_entries: usize,
+    /// Bytes of code loaded by hash.
+    pub max_code_bytes: usize,
+}
+
+/// Number of entries evicted because [CacheDB] was over its [CacheBudget].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct EvictionStats {
+    pub accounts: u64,
+    pub storage_slots: u64,
+    pub contracts: u64,
+}
+
+/// Recency of clean entries of a [CacheDB] with budget.
+#[derive(Clone, Debug)]
+struct Lru {
+    budget: CacheBudget,
+    accounts: Recency<B160>,
+    /// Tracked accounts and their storage slots.
+    entries: usize,
+    contracts: Recency<B256>,
+    code_bytes: usize,
+    evictions: EvictionStats,
+}
+
+impl Default for Lru {
+    fn default() -> Self {
+        Self {
+            budget: CacheBudget {
+                max_entries: usize::MAX,
+                max_code_bytes: usize::MAX,
+            },
+            accounts: Recency::default(),
+            entries: 0,
+            contracts: Recency::default(),
+            code_bytes: 0,
+            evictions: EvictionStats::default(),
+        }
+    }
+}
+
+/// Keys ordered by their last use.
+#[derive(Clone, Debug)]
+struct Recency<K> {
+    ticks: HashMap<K, u64>,
+    order: BTreeMap<u64, K>,
+    tick: u64,
+}
+
+impl<K> Default for Recency<K> {
+    fn default() -> Self {
+        Self {
+            ticks: HashMap::new(),
+            order: BTreeMap::new(),
+            tick: 0,
+        }
+    }
+}
+
+impl<K: Hash + Eq + Copy> Recency<K> {
+    /// Mark `key` as most recently used, returns true if it was not tracked.
+    fn touch(&mut self, key: K) -> bool {
+        self.tick += 1;
+        self.order.insert(self.tick, key);
+        match self.ticks.insert(key, self.tick) {
+            Some(tick) => {
+                self.order.remove(&tick);
+                false
+            }
+            None => true,
+        }
+    }
+
+    /// Mark `key` as most recently used if it is tracked.
+    fn refresh(&mut self, key: &K) {
+        if self.ticks.contains_key(key) {
+            self.touch(*key);
+        }
+    }
+
+    fn remove(&mut self, key: &K) -> bool {
+        match self.ticks.remove(key) {
+            Some(tick) => {
+                self.order.remove(&tick);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn pop_oldest(&mut self) -> Option<K> {
+        let (_, key) = self.order.pop_first()?;
+        self.ticks.remove(&key);
+        Some(key)
+    }
+}
+
+/// Entries of a [CacheDB], see [CacheDB::stats].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    pub accounts: usize,
+    pub storage_slots: usize,
+    pub contracts: usize,
+    /// Bytes of cached code, with padding of analysed code.
+    pub code_bytes: usize,
+    pub block_hashes: usize,
+    pub logs: usize,
+    /// Estimated heap memory used by the cache in bytes, allocated capacity included.
+    /// Jump tables of analysed code and allocator overhead are not counted.
+    pub heap_size: usize,
+}
+
+/// Count a lookup of `kind` in [CacheDB] as hit or miss of the cache, with the `metrics`
+/// feature. Lookups of [DatabaseRef] are not counted.
+#[cfg(feature = "metrics")]
+#[inline]
+fn record_lookup(kind: &'static str, hit: bool) {
+    if hit {
+        metrics::increment_counter!("revm.cache_db.hits", "kind" => kind);
+    } else {
+        metrics::increment_counter!("revm.cache_db.misses", "kind" => kind);
+    }
+}
+
+#[cfg(not(feature = "metrics"))]
+#[inline]
+fn record_lookup(_kind: &'static str, _hit: bool) {}
+
+/// Number of entries dropped by [CacheDB::prune_untouched].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PruneStats {
+    pub accounts: usize,
+    pub storage_slots: usize,
 }
 
 impl<ExtDB: DatabaseRef> DatabaseCommit for CacheDB<ExtDB> {
     fn commit(&mut self, changes: HashMap<B160, Account>) {
+        let commit = phase!("commit", accounts = changes.len());
         for (address, mut account) in changes {
             if !account.is_touched() {
                 continue;
             }
+            self.mark_changed(address);
+            self.journal(address);
             if account.is_selfdestructed() {
                 let db_account = self.accounts.entry(address).or_default();
                 db_account.storage.clear();
@@ -156,6 +769,7 @@ impl<ExtDB: DatabaseRef> DatabaseCommit for CacheDB<ExtDB> {
                     .map(|(key, value)| (key, value.present_value())),
             );
         }
+        commit.end();
     }
 }
 
@@ -164,28 +778,40 @@ impl<ExtDB: DatabaseRef> Database for CacheDB<ExtDB> {
 
     fn basic(&mut self, address: B160) -> Result<Option<AccountInfo>, Self::Error> {
         let basic = match self.accounts.entry(address) {
-            Entry::Occupied(entry) => entry.into_mut(),
-            Entry::Vacant(entry) => entry.insert(
-                self.db
+            Entry::Occupied(entry) => {
+                record_lookup("account", true);
+                entry.into_mut()
+            }
+            Entry::Vacant(entry) => {
+                record_lookup("account", false);
+                let account = self
+                    .db
                     .basic(address)?
                     .map(|info| DbAccount {
                         info,
                         ..Default::default()
                     })
-                    .unwrap_or_else(DbAccount::new_not_existing),
-            ),
+                    .unwrap_or_else(DbAccount::new_not_existing);
+                self.loaded.insert(address);
+                entry.insert(account)
+            }
         };
-        Ok(basic.info())
+        let info = basic.info();
+        self.track_account(address, 0);
+        Ok(info)
     }
 
     fn code_by_hash(&mut self, code_hash: B256) -> Result<Bytecode, Self::Error> {
-        match self.contracts.entry(code_hash) {
-            Entry::Occupied(entry) => Ok(entry.get().clone()),
+        let (code, loaded) = match self.contracts.entry(code_hash) {
+            Entry::Occupied(entry) => (entry.get().clone(), false),
             Entry::Vacant(entry) => {
                 // if you return code bytes when basic fn is called this function is not needed.
-                Ok(entry.insert(self.db.code_by_hash(code_hash)?).clone())
+                (entry.insert(self.db.code_by_hash(code_hash)?).clone(), true)
             }
-        }
+        };
+        record_lookup("code", !loaded);
+        self.track_contract(code_hash, loaded);
+        Ok(code)
     }
 
     /// Get the value in an account's storage slot.
@@ -195,23 +821,27 @@ impl<ExtDB: DatabaseRef> Database for CacheDB<ExtDB> {
         match self.accounts.entry(address) {
             Entry::Occupied(mut acc_entry) => {
                 let acc_entry = acc_entry.get_mut();
-                match acc_entry.storage.entry(index) {
-                    Entry::Occupied(entry) => Ok(*entry.get()),
+                let (value, new_slots) = match acc_entry.storage.entry(index) {
+                    Entry::Occupied(entry) => (*entry.get(), 0),
                     Entry::Vacant(entry) => {
                         if matches!(
                             acc_entry.account_state,
                             AccountState::StorageCleared | AccountState::NotExisting
                         ) {
-                            Ok(U256::ZERO)
+                            (U256::ZERO, 0)
                         } else {
                             let slot = self.db.storage(address, index)?;
                             entry.insert(slot);
-                            Ok(slot)
+                            (slot, 1)
                         }
                     }
-                }
+                };
+                record_lookup("storage", new_slots == 0);
+                self.track_account(address, new_slots);
+                Ok(value)
             }
             Entry::Vacant(acc_entry) => {
+                record_lookup("storage", false);
                 // acc needs to be loaded for us to access slots.
                 let info = self.db.basic(address)?;
                 let (account, value) = if info.is_some() {
@@ -222,7 +852,10 @@ impl<ExtDB: DatabaseRef> Database for CacheDB<ExtDB> {
                 } else {
                     (info.into(), U256::ZERO)
                 };
+                let new_slots = account.storage.len();
+                self.loaded.insert(address);
                 acc_entry.insert(account);
+                self.track_account(address, new_slots);
                 Ok(value)
             }
         }
@@ -230,8 +863,12 @@ impl<ExtDB: DatabaseRef> Database for CacheDB<ExtDB> {
 
     fn block_hash(&mut self, number: U256) -> Result<B256, Self::Error> {
         match self.block_hashes.entry(number) {
-            Entry::Occupied(entry) => Ok(*entry.get()),
+            Entry::Occupied(entry) => {
+                record_lookup("block_hash", true);
+                Ok(*entry.get())
+            }
             Entry::Vacant(entry) => {
+                record_lookup("block_hash", false);
                 let hash = self.db.block_hash(number)?;
                 entry.insert(hash);
                 Ok(hash)
@@ -284,7 +921,68 @@ impl<ExtDB: DatabaseRef> DatabaseRef for CacheDB<ExtDB> {
     }
 }
 
-#[derive(Debug, Clone, Default)]
+/// Cached slots take precedence over slots of the underlying database, which is read in
+/// pages of `limit + 1` slots while cached slots set to zero hide its slots.
+impl<ExtDB: DatabaseRange> DatabaseRange for CacheDB<ExtDB> {
+    fn storage_range(
+        &self,
+        address: B160,
+        start: U256,
+        limit: usize,
+    ) -> Result<StorageRange, Self::Error> {
+        let Some(account) = self.accounts.get(&address) else {
+            return self.db.storage_range(address, start, limit);
+        };
+        let mut cached = account
+            .storage_iter()
+            .filter(|(index, _)| *index >= start)
+            .peekable();
+        let mut db_slots = VecDeque::new();
+        let mut db_next = match account.account_state {
+            AccountState::StorageCleared | AccountState::NotExisting => None,
+            _ => Some(start),
+        };
+
+        let mut slots = Vec::new();
+        loop {
+            if db_slots.is_empty() {
+                if let Some(next) = db_next {
+                    let page = self.db.storage_range(address, next, limit + 1)?;
+                    db_slots.extend(page.slots);
+                    db_next = page.next;
+                }
+            }
+            let (index, value) = match (cached.peek(), db_slots.front()) {
+                (None, None) => break,
+                (Some((cached_index, _)), Some((db_index, _))) if cached_index <= db_index => {
+                    if cached_index == db_index {
+                        db_slots.pop_front();
+                    }
+                    cached.next().unwrap()
+                }
+                (Some(_), None) => cached.next().unwrap(),
+                _ => db_slots.pop_front().unwrap(),
+            };
+            if value == U256::ZERO {
+                continue;
+            }
+            if slots.len() == limit {
+                return Ok(StorageRange {
+                    slots,
+                    next: Some(index),
+                });
+            }
+            slots.push((index, value));
+        }
+        Ok(StorageRange { slots, next: None })
+    }
+}
+
+/// Cached entries are answered from memory, batches are not needed.
+impl<ExtDB: DatabaseRef> DatabaseBatch for CacheDB<ExtDB> {}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct DbAccount {
     pub info: AccountInfo,
     /// If account is selfdestructed or newly created, storage will be cleared.
@@ -307,6 +1005,17 @@ impl DbAccount {
             Some(self.info.clone())
         }
     }
+
+    /// Cached storage slots in ascending order of index, slots set to zero included.
+    pub fn storage_iter(&self) -> impl Iterator<Item = (U256, U256)> {
+        let mut slots: Vec<_> = self
+            .storage
+            .iter()
+            .map(|(index, value)| (*index, *value))
+            .collect();
+        slots.sort_unstable_by_key(|(index, _)| *index);
+        slots.into_iter()
+    }
 }
 
 impl From<Option<AccountInfo>> for DbAccount {
@@ -334,6 +1043,7 @@ impl From<AccountInfo> for DbAccount {
 }
 
 #[derive(Debug, Clone, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum AccountState {
     /// Before Spurious Dragon hardfork there was a difference between empty and not existing.
     /// And we are flaging it here.
@@ -357,6 +1067,7 @@ impl AccountState {
 
 /// An empty database that always returns default values when queried.
 #[derive(Debug, Default, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct EmptyDB();
 
 impl DatabaseRef for EmptyDB {
@@ -380,6 +1091,19 @@ impl DatabaseRef for EmptyDB {
     }
 }
 
+impl DatabaseRange for EmptyDB {
+    fn storage_range(
+        &self,
+        _address: B160,
+        _start: U256,
+        _limit: usize,
+    ) -> Result<StorageRange, Self::Error> {
+        Ok(StorageRange::default())
+    }
+}
+
+impl DatabaseBatch for EmptyDB {}
+
 /// Custom benchmarking DB that only has account info for the zero address.
 ///
 /// Any other address will return an empty account.
@@ -482,4 +1206,408 @@ mod tests {
         assert_eq!(new_state.storage(account, key0), Ok(U256::ZERO));
         assert_eq!(new_state.storage(account, key1), Ok(value1));
     }
+
+    #[test]
+    fn prune_untouched() {
+        use super::PruneStats;
+        use crate::primitives::{Account, B160};
+        use crate::DatabaseCommit;
+
+        let (read, changed, inserted) = (B160([1; 20]), B160([2; 20]), B160([3; 20]));
+        let mut base = CacheDB::new(EmptyDB::default());
+        base.insert_account_info(read, AccountInfo::from_balance(U256::from(1)));
+        base.insert_account_info(changed, AccountInfo::from_balance(U256::from(2)));
+        base.insert_account_storage(read, U256::from(1), U256::from(1))
+            .unwrap();
+
+        let mut db = CacheDB::new(base);
+        db.insert_account_info(inserted, AccountInfo::from_balance(U256::from(3)));
+        assert_eq!(db.storage(read, U256::from(1)), Ok(U256::from(1)));
+        assert_eq!(db.storage(read, U256::from(2)), Ok(U256::ZERO));
+        let mut account: Account = db.basic(changed).unwrap().unwrap().into();
+        account.info.nonce = 1;
+        account.mark_touch();
+        db.commit([(changed, account)].into());
+
+        assert_eq!(
+            db.prune_untouched(),
+            PruneStats {
+                accounts: 1,
+                storage_slots: 2
+            }
+        );
+        assert!(!db.accounts.contains_key(&read));
+        assert_eq!(db.basic(changed).unwrap().unwrap().nonce, 1);
+        assert_eq!(db.accounts[&inserted].info.balance, U256::from(3));
+        // pruned account is loaded again.
+        assert_eq!(db.storage(read, U256::from(1)), Ok(U256::from(1)));
+        assert_eq!(db.prune_untouched().accounts, 1);
+    }
+
+    #[test]
+    fn stats() {
+        use crate::primitives::{Bytecode, B160};
+
+        let mut db = CacheDB::new(EmptyDB::default());
+        let empty = db.stats();
+        // empty code is cached for empty and zero hash.
+        assert_eq!(empty.contracts, 2);
+        let code = Bytecode::new_raw(vec![0x60; 100].into());
+        db.insert_account_info(B160([1; 20]), AccountInfo::new(U256::ZERO, 0, code));
+        for slot in 0..10 {
+            db.insert_account_storage(B160([2; 20]), U256::from(slot), U256::from(1))
+                .unwrap();
+        }
+
+        let stats = db.stats();
+        assert_eq!(stats.accounts, 2);
+        assert_eq!(stats.storage_slots, 10);
+        assert_eq!(stats.contracts, 3);
+        assert_eq!(stats.code_bytes, empty.code_bytes + 100);
+        assert_eq!(db.size_hint(), 15);
+        assert!(stats.heap_size >= empty.heap_size + 10 * 64 + 133);
+    }
+
+    #[test]
+    fn fork() {
+        use crate::primitives::{Bytecode, B160};
+        use alloc::sync::Arc;
+
+        let (account, cleared) = (B160([1; 20]), B160([2; 20]));
+        let code = Bytecode::new_raw(vec![0x60, 0x00].into());
+        let mut base = CacheDB::new(EmptyDB::default());
+        base.insert_account_info(account, AccountInfo::new(U256::from(1), 0, code));
+        base.insert_account_storage(account, U256::from(1), U256::from(10))
+            .unwrap();
+        base.replace_account_storage(cleared, [(U256::from(1), U256::from(20))].into())
+            .unwrap();
+        let base = Arc::new(base);
+
+        let mut first = base.fork();
+        let mut second = base.fork();
+        first
+            .insert_account_storage(account, U256::from(1), U256::from(11))
+            .unwrap();
+        first.insert_account_info(cleared, AccountInfo::from_balance(U256::from(2)));
+        assert_eq!(first.storage(account, U256::from(1)), Ok(U256::from(11)));
+        assert_eq!(second.storage(account, U256::from(1)), Ok(U256::from(10)));
+        assert_eq!(
+            base.accounts[&account].storage[&U256::from(1)],
+            U256::from(10)
+        );
+
+        // storage of the parent is read through, cleared one stays cleared.
+        assert_eq!(first.storage(cleared, U256::from(1)), Ok(U256::from(20)));
+        assert_eq!(second.storage(cleared, U256::from(2)), Ok(U256::ZERO));
+        let code_hash = second.basic(account).unwrap().unwrap().code_hash;
+        assert_eq!(second.code_by_hash(code_hash).unwrap().len(), 2);
+        assert_eq!(second.basic(cleared).unwrap().unwrap().balance, U256::ZERO);
+    }
+
+    #[test]
+    fn evicts_least_recently_used() {
+        use super::{CacheBudget, EvictionStats};
+        use crate::primitives::{Account, Bytecode, B160};
+        use crate::DatabaseCommit;
+
+        let addresses: Vec<B160> = (1..=4).map(|i| B160([i; 20])).collect();
+        let code = Bytecode::new_raw(vec![0x60, 0x00, 0x00].into());
+        let mut base = CacheDB::new(EmptyDB::default());
+        for (i, address) in addresses.iter().enumerate() {
+            base.insert_account_info(*address, AccountInfo::from_balance(U256::from(i)));
+        }
+        base.insert_account_storage(addresses[0], U256::from(1), U256::from(7))
+            .unwrap();
+        base.contracts.insert(code.hash(), code.clone());
+
+        let mut db = CacheDB::new(base).with_budget(CacheBudget {
+            max_entries: 3,
+            max_code_bytes: 2,
+        });
+        // account and its slot are two entries.
+        assert_eq!(db.storage(addresses[0], U256::from(1)), Ok(U256::from(7)));
+        db.basic(addresses[1]).unwrap();
+        // first account is used again, second one is now the oldest.
+        db.basic(addresses[0]).unwrap();
+        db.basic(addresses[2]).unwrap();
+        assert!(!db.accounts.contains_key(&addresses[1]));
+        assert!(db.accounts.contains_key(&addresses[0]));
+
+        // changed accounts are not evicted.
+        let mut account: Account = db.basic(addresses[3]).unwrap().unwrap().into();
+        account.mark_touch();
+        db.commit([(addresses[3], account)].into());
+        assert!(!db.accounts.contains_key(&addresses[0]));
+        assert!(db.accounts.contains_key(&addresses[3]));
+
+        // code is larger than the budget, it is evicted at once.
+        assert_eq!(db.code_by_hash(code.hash()).unwrap().hash(), code.hash());
+        assert!(!db.contracts.contains_key(&code.hash()));
+        assert_eq!(
+            db.evictions(),
+            EvictionStats {
+                accounts: 2,
+                storage_slots: 1,
+                contracts: 1,
+            }
+        );
+        // evicted entries are loaded again.
+        assert_eq!(db.storage(addresses[0], U256::from(1)), Ok(U256::from(7)));
+    }
+
+    #[test]
+    fn snapshot_revert() {
+        use crate::primitives::{Account, B160};
+        use crate::DatabaseCommit;
+
+        let (alice, bob) = (B160([1; 20]), B160([2; 20]));
+        let mut db = CacheDB::new(EmptyDB::default());
+        db.insert_account_info(alice, AccountInfo::from_balance(U256::from(10)));
+        let transfer = |db: &mut CacheDB<EmptyDB>, amount: u64| {
+            let mut from: Account = db.basic(alice).unwrap().unwrap().into();
+            let mut to: Account = db.basic(bob).unwrap().unwrap_or_default().into();
+            from.info.balance -= U256::from(amount);
+            to.info.balance += U256::from(amount);
+            from.mark_touch();
+            to.mark_touch();
+            db.commit([(alice, from), (bob, to)].into());
+        };
+        let balance = |db: &mut CacheDB<EmptyDB>, address| {
+            db.basic(address).unwrap().map(|info| info.balance)
+        };
+
+        let first = db.snapshot();
+        transfer(&mut db, 1);
+        let second = db.snapshot();
+        transfer(&mut db, 2);
+        db.insert_account_storage(alice, U256::from(1), U256::from(1))
+            .unwrap();
+        let third = db.snapshot();
+        transfer(&mut db, 3);
+
+        assert!(db.revert_to(second));
+        assert_eq!(balance(&mut db, alice), Some(U256::from(9)));
+        assert_eq!(balance(&mut db, bob), Some(U256::from(1)));
+        assert_eq!(db.storage(alice, U256::from(1)), Ok(U256::ZERO));
+        assert!(!db.revert_to(third));
+
+        // changes of discarded snapshot are reverted with its parent.
+        let fourth = db.snapshot();
+        transfer(&mut db, 4);
+        assert!(db.discard_snapshot(fourth));
+        assert_eq!(balance(&mut db, alice), Some(U256::from(5)));
+        assert!(db.revert_to(first));
+        assert_eq!(balance(&mut db, alice), Some(U256::from(10)));
+        assert_eq!(balance(&mut db, bob), None);
+        assert!(!db.revert_to(first));
+    }
+
+    #[test]
+    fn storage_range() {
+        use crate::primitives::{db::DatabaseRange, B160};
+
+        let account = B160([1; 20]);
+        let mut base = CacheDB::new(EmptyDB::default());
+        base.insert_account_info(account, AccountInfo::from_balance(U256::from(1)));
+        for index in 1..=5 {
+            base.insert_account_storage(account, U256::from(index), U256::from(index))
+                .unwrap();
+        }
+        let mut db = CacheDB::new(base);
+        db.insert_account_storage(account, U256::from(2), U256::ZERO)
+            .unwrap();
+        db.insert_account_storage(account, U256::from(3), U256::from(30))
+            .unwrap();
+        db.insert_account_storage(account, U256::from(6), U256::from(6))
+            .unwrap();
+        let slots = |indices: &[(u64, u64)]| {
+            indices
+                .iter()
+                .map(|(index, value)| (U256::from(*index), U256::from(*value)))
+                .collect::<Vec<_>>()
+        };
+
+        let range = db.storage_range(account, U256::from(1), 3).unwrap();
+        assert_eq!(range.slots, slots(&[(1, 1), (3, 30), (4, 4)]));
+        assert_eq!(range.next, Some(U256::from(5)));
+        let range = db.storage_range(account, U256::from(5), 3).unwrap();
+        assert_eq!(range.slots, slots(&[(5, 5), (6, 6)]));
+        assert_eq!(range.next, None);
+
+        // cleared storage hides slots of the database.
+        db.replace_account_storage(account, [(U256::from(9), U256::from(9))].into())
+            .unwrap();
+        let range = db.storage_range(account, U256::ZERO, 3).unwrap();
+        assert_eq!(range.slots, slots(&[(9, 9)]));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_roundtrip() {
+        use crate::primitives::{Bytecode, SpecId, TransactTo, B160};
+        use crate::DatabaseCommit;
+
+        let contract = B160([0x10; 20]);
+        let mut db = CacheDB::new(EmptyDB::default());
+        // SSTORE(1, 2) SELFDESTRUCT(contract)
+        let mut code = vec![0x60, 0x02, 0x60, 0x01, 0x55, 0x73];
+        code.extend_from_slice(&contract.0);
+        code.push(0xff);
+        db.insert_account_info(
+            contract,
+            AccountInfo::new(U256::from(1), 0, Bytecode::new_raw(code.into())),
+        );
+        db.insert_account_storage(contract, U256::from(3), U256::from(4))
+            .unwrap();
+        db.insert_account_info(B160([0x20; 20]), AccountInfo::from_balance(U256::from(5)));
+        let mut evm = crate::new();
+        evm.database(&mut db);
+        // existing contract is destroyed only before Cancun.
+        evm.env.cfg.spec_id = SpecId::SHANGHAI;
+        evm.env.tx.caller = B160([0x20; 20]);
+        evm.env.tx.transact_to = TransactTo::Call(contract);
+        let state = evm.transact().unwrap().state;
+        db.commit(state);
+        assert_eq!(
+            db.accounts[&contract].account_state,
+            super::AccountState::NotExisting
+        );
+
+        let serialized = serde_json::to_string(&db).unwrap();
+        let deserialized: CacheDB<EmptyDB> = serde_json::from_str(&serialized).unwrap();
+        assert_eq!(deserialized.accounts, db.accounts);
+        assert_eq!(deserialized.contracts, db.contracts);
+        assert_eq!(deserialized.block_hashes, db.block_hashes);
+    }
+
+    #[test]
+    fn prefetch_batch() {
+        use crate::db::{DatabaseBatch, DatabaseRef};
+        use crate::primitives::{Bytecode, B160, B256};
+        use core::cell::RefCell;
+        use core::convert::Infallible;
+
+        /// Records sizes of batches.
+        struct Batches {
+            db: CacheDB<EmptyDB>,
+            batches: RefCell<Vec<usize>>,
+        }
+
+        impl DatabaseRef for Batches {
+            type Error = Infallible;
+
+            fn basic(&self, _address: B160) -> Result<Option<AccountInfo>, Self::Error> {
+                panic!("account is looked up alone")
+            }
+
+            fn code_by_hash(&self, code_hash: B256) -> Result<Bytecode, Self::Error> {
+                self.db.code_by_hash(code_hash)
+            }
+
+            fn storage(&self, _address: B160, _index: U256) -> Result<U256, Self::Error> {
+                panic!("slot is looked up alone")
+            }
+
+            fn block_hash(&self, number: U256) -> Result<B256, Self::Error> {
+                self.db.block_hash(number)
+            }
+        }
+
+        impl DatabaseBatch for Batches {
+            fn basic_many(
+                &self,
+                addresses: &[B160],
+            ) -> Result<Vec<Option<AccountInfo>>, Self::Error> {
+                self.batches.borrow_mut().push(addresses.len());
+                self.db.basic_many(addresses)
+            }
+
+            fn storage_many(&self, slots: &[(B160, U256)]) -> Result<Vec<U256>, Self::Error> {
+                self.batches.borrow_mut().push(slots.len());
+                self.db.storage_many(slots)
+            }
+        }
+
+        let mut base = CacheDB::new(EmptyDB::default());
+        let addresses: Vec<B160> = (1..=3).map(B160::from_low_u64_be).collect();
+        for (i, address) in addresses.iter().enumerate() {
+            base.insert_account_info(*address, AccountInfo::from_balance(U256::from(i + 1)));
+            base.insert_account_storage(*address, U256::from(1), U256::from(i + 10))
+                .unwrap();
+        }
+        let mut db = CacheDB::new(Batches {
+            db: base,
+            batches: RefCell::default(),
+        });
+        db.insert_account_info(addresses[0], AccountInfo::from_balance(U256::from(100)));
+
+        let access_list: Vec<(B160, Vec<U256>)> = addresses
+            .iter()
+            .map(|address| (*address, vec![U256::from(1), U256::from(2)]))
+            .collect();
+        db.prefetch_access_list(&access_list).unwrap();
+        // cached account is not looked up, slots of all accounts are.
+        assert_eq!(*db.db.batches.borrow(), [2, 6]);
+        assert_eq!(db.accounts[&addresses[0]].info.balance, U256::from(100));
+        assert_eq!(
+            db.accounts[&addresses[2]].storage[&U256::from(1)],
+            U256::from(12)
+        );
+
+        // nothing new is looked up.
+        db.prefetch_access_list(&access_list).unwrap();
+        assert_eq!(db.db.batches.borrow().len(), 2);
+        assert_eq!(
+            db.basic(addresses[1]).unwrap().unwrap().balance,
+            U256::from(2)
+        );
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn prefetch() {
+        use crate::primitives::{Bytecode, B160};
+
+        let mut base = CacheDB::new(EmptyDB::default());
+        let code = Bytecode::new_raw(vec![0x60, 0x00].into());
+        let mut addresses = Vec::new();
+        for i in 1..=20u64 {
+            let address = B160::from_low_u64_be(i);
+            base.insert_account_info(address, AccountInfo::new(U256::from(i), 0, code.clone()));
+            base.insert_account_storage(address, U256::from(i), U256::from(i * 2))
+                .unwrap();
+            addresses.push(address);
+        }
+        let mut base_accounts = base.accounts.clone();
+        // code is looked up by hash.
+        for account in base_accounts.values_mut() {
+            account.info.code = None;
+        }
+        base.accounts = base_accounts;
+
+        let mut db = CacheDB::new(base);
+        let cached = B160::from_low_u64_be(1);
+        db.insert_account_info(cached, AccountInfo::from_balance(U256::from(100)));
+        let missing = B160::from_low_u64_be(30);
+        let slots = addresses
+            .iter()
+            .map(|address| (*address, U256::from(address.to_low_u64_be())))
+            .chain([(missing, U256::from(1))]);
+        db.prefetch(addresses.clone(), slots.collect::<Vec<_>>())
+            .unwrap();
+
+        assert_eq!(db.accounts.len(), 21);
+        assert!(db.contracts.contains_key(&code.hash()));
+        // cached account is not overwritten, its storage is loaded.
+        assert_eq!(db.accounts[&cached].info.balance, U256::from(100));
+        assert_eq!(db.accounts[&cached].storage[&U256::from(1)], U256::from(2));
+        let address = B160::from_low_u64_be(7);
+        assert_eq!(
+            db.accounts[&address].storage[&U256::from(7)],
+            U256::from(14)
+        );
+        assert!(db.loaded.contains(&address));
+        assert!(db.accounts[&missing].storage.is_empty());
+        assert_eq!(db.prune_untouched().accounts, 20);
+    }
 }
@@ -0,0 +1,300 @@
+//! Versioned state of a block being executed.
+//!
+//! [MultiVersionState] keeps the changes of every transaction of the block indexed by the
+//! transaction that made them, so state can be read as it was before any of them without
+//! copying the state for every transaction.
+use crate::db::DatabaseRef;
+use crate::primitives::{AccountInfo, Bytecode, HashMap, State, B160, B256, U256};
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+
+/// Value read from [MultiVersionState].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Versioned<T> {
+    /// Not written by transactions before, value is the one of the base database.
+    Base,
+    /// Value written by the last transaction before that changed it.
+    Written(T),
+}
+
+/// Account changed by a transaction.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct AccountWrite {
+    /// None if account was destroyed.
+    pub(crate) info: Option<AccountInfo>,
+    /// Storage that is not written by the transaction reads as zero.
+    pub(crate) storage_cleared: bool,
+}
+
+/// Changes of a transaction, the same as they would be committed to the database.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Writes {
+    pub(crate) accounts: Vec<(B160, AccountWrite)>,
+    pub(crate) storage: Vec<((B160, U256), U256)>,
+}
+
+impl Writes {
+    /// Changes of `state`. Accounts that are touched but are the same as in `loaded` are
+    /// skipped, so readers don't depend on them.
+    pub(crate) fn new(state: &State, loaded: &HashMap<B160, Option<AccountInfo>>) -> Self {
+        let mut writes = Self::default();
+        for (address, account) in state {
+            if !account.is_touched() {
+                continue;
+            }
+            if account.is_selfdestructed() {
+                writes.accounts.push((
+                    *address,
+                    AccountWrite {
+                        info: None,
+                        storage_cleared: true,
+                    },
+                ));
+                continue;
+            }
+            let storage_cleared = account.is_newly_created();
+            let unchanged = !storage_cleared
+                && matches!(loaded.get(address), Some(Some(info)) if *info == account.info);
+            if !unchanged {
+                writes.accounts.push((
+                    *address,
+                    AccountWrite {
+                        info: Some(account.info.clone()),
+                        storage_cleared,
+                    },
+                ));
+            }
+            writes.storage.extend(
+                account
+                    .storage
+                    .iter()
+                    .filter(|(_, slot)| slot.is_changed())
+                    .map(|(slot, value)| ((*address, *slot), value.present_value())),
+            );
+        }
+        writes
+    }
+}
+
+/// Changes of the transactions of a block, indexed by the transaction that made them.
+///
+/// Transactions can be inserted in any order and replaced, reads at index `i` always see
+/// changes of transactions before `i` that are inserted at the time.
+#[derive(Clone, Debug, Default)]
+pub struct MultiVersionState {
+    accounts: HashMap<B160, BTreeMap<usize, AccountWrite>>,
+    storage: HashMap<(B160, U256), BTreeMap<usize, U256>>,
+    contracts: HashMap<B256, Bytecode>,
+    /// Locations written by every transaction, for removing its changes.
+    written: HashMap<usize, Writes>,
+}
+
+impl MultiVersionState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set changes of the transaction at `index` to `state`, replacing previous ones.
+    pub fn insert(&mut self, index: usize, state: &State) {
+        self.publish(index, Writes::new(state, &HashMap::new()));
+    }
+
+    /// Remove changes of the transaction at `index`.
+    pub fn remove(&mut self, index: usize) {
+        let Some(previous) = self.written.remove(&index) else {
+            return;
+        };
+        for (address, _) in previous.accounts {
+            if let Some(versions) = self.accounts.get_mut(&address) {
+                versions.remove(&index);
+            }
+        }
+        for (slot, _) in previous.storage {
+            if let Some(versions) = self.storage.get_mut(&slot) {
+                versions.remove(&index);
+            }
+        }
+    }
+
+    /// If changes of the transaction at `index` are inserted.
+    pub fn contains(&self, index: usize) -> bool {
+        self.written.contains_key(&index)
+    }
+
+    /// Account as seen by the transaction at `index`.
+    pub fn account(&self, index: usize, address: B160) -> Versioned<Option<AccountInfo>> {
+        match latest(self.accounts.get(&address), index) {
+            Some((_, write)) => Versioned::Written(write.info.clone()),
+            None => Versioned::Base,
+        }
+    }
+
+    /// Storage slot as seen by the transaction at `index`.
+    pub fn storage(&self, index: usize, address: B160, slot: U256) -> Versioned<U256> {
+        let cleared = self
+            .accounts
+            .get(&address)
+            .and_then(|versions| {
+                versions
+                    .range(..index)
+                    .rev()
+                    .find(|(_, write)| write.storage_cleared)
+            })
+            .map(|(at, _)| *at);
+        let written = latest(self.storage.get(&(address, slot)), index);
+        match (written, cleared) {
+            (Some((_, value)), None) => Versioned::Written(*value),
+            // slot written by the transaction that cleared storage has the new value.
+            (Some((at, value)), Some(cleared)) if at >= cleared => Versioned::Written(*value),
+            (_, Some(_)) => Versioned::Written(U256::ZERO),
+            (None, None) => Versioned::Base,
+        }
+    }
+
+    /// Code deployed by any inserted transaction.
+    pub fn code(&self, code_hash: &B256) -> Option<&Bytecode> {
+        self.contracts.get(code_hash)
+    }
+
+    /// State seen by the transaction at `index`, changes of transactions before it over `db`.
+    pub fn at<'a, DB: DatabaseRef>(&'a self, db: &'a DB, index: usize) -> MultiVersionView<'a, DB> {
+        MultiVersionView {
+            state: self,
+            db,
+            index,
+        }
+    }
+
+    /// Replace changes of the transaction at `index` with `writes`.
+    pub(crate) fn publish(&mut self, index: usize, writes: Writes) {
+        self.remove(index);
+        for (address, write) in &writes.accounts {
+            if let Some(AccountInfo {
+                code_hash,
+                code: Some(code),
+                ..
+            }) = &write.info
+            {
+                self.contracts
+                    .entry(*code_hash)
+                    .or_insert_with(|| code.clone());
+            }
+            self.accounts
+                .entry(*address)
+                .or_default()
+                .insert(index, write.clone());
+        }
+        for (slot, value) in &writes.storage {
+            self.storage.entry(*slot).or_default().insert(index, *value);
+        }
+        self.written.insert(index, writes);
+    }
+}
+
+fn latest<T>(versions: Option<&BTreeMap<usize, T>>, index: usize) -> Option<(usize, &T)> {
+    versions?
+        .range(..index)
+        .next_back()
+        .map(|(at, value)| (*at, value))
+}
+
+/// [MultiVersionState] as of a transaction over the base database, see
+/// [MultiVersionState::at].
+#[derive(Clone, Copy, Debug)]
+pub struct MultiVersionView<'a, DB> {
+    state: &'a MultiVersionState,
+    db: &'a DB,
+    index: usize,
+}
+
+impl<DB> MultiVersionView<'_, DB> {
+    /// Index of the transaction whose state this is.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+}
+
+impl<DB: DatabaseRef> DatabaseRef for MultiVersionView<'_, DB> {
+    type Error = DB::Error;
+
+    fn basic(&self, address: B160) -> Result<Option<AccountInfo>, Self::Error> {
+        match self.state.account(self.index, address) {
+            Versioned::Written(info) => Ok(info),
+            Versioned::Base => self.db.basic(address),
+        }
+    }
+
+    fn code_by_hash(&self, code_hash: B256) -> Result<Bytecode, Self::Error> {
+        match self.state.code(&code_hash) {
+            Some(code) => Ok(code.clone()),
+            None => self.db.code_by_hash(code_hash),
+        }
+    }
+
+    fn storage(&self, address: B160, index: U256) -> Result<U256, Self::Error> {
+        match self.state.storage(self.index, address, index) {
+            Versioned::Written(value) => Ok(value),
+            Versioned::Base => self.db.storage(address, index),
+        }
+    }
+
+    fn block_hash(&self, number: U256) -> Result<B256, Self::Error> {
+        self.db.block_hash(number)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::RefDBWrapper;
+    use crate::primitives::{hex_literal::hex, Env, TransactTo};
+    use crate::{InMemoryDB, EVM};
+
+    #[test]
+    fn reads_state_as_of_transaction() {
+        let counter = B160([0x10; 20]);
+        let mut db = InMemoryDB::default();
+        // SSTORE(0, SLOAD(0) + 1)
+        db.insert_account_info(
+            counter,
+            AccountInfo::new(
+                U256::ZERO,
+                0,
+                Bytecode::new_raw(hex!("600054600101600055").to_vec().into()),
+            ),
+        );
+
+        let mut state = MultiVersionState::new();
+        for index in 0..3 {
+            let mut env = Env::default();
+            env.tx.caller = B160([index as u8 + 1; 20]);
+            env.tx.transact_to = TransactTo::Call(counter);
+            let view = state.at(&db, index);
+            let mut evm = EVM::with_env(env);
+            evm.database(RefDBWrapper::new(&view));
+            let out = evm.transact().unwrap();
+            state.insert(index, &out.state);
+        }
+
+        for index in 0..=3 {
+            let view = state.at(&db, index);
+            assert_eq!(view.storage(counter, U256::ZERO), Ok(U256::from(index)));
+        }
+        assert_eq!(state.storage(0, counter, U256::ZERO), Versioned::Base);
+        let caller = B160([1; 20]);
+        assert_eq!(state.at(&db, 0).basic(caller), Ok(None));
+        assert_eq!(state.at(&db, 1).basic(caller).unwrap().unwrap().nonce, 1);
+
+        // replaced transaction is seen by the ones after it.
+        state.remove(1);
+        assert!(!state.contains(1));
+        assert_eq!(
+            state.storage(3, counter, U256::ZERO),
+            Versioned::Written(U256::from(3))
+        );
+        assert_eq!(
+            state.storage(2, counter, U256::ZERO),
+            Versioned::Written(U256::from(1))
+        );
+    }
+}
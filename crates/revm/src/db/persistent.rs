@@ -0,0 +1,399 @@
+//! [CacheDB](super::CacheDB) kept on disk in a [sled] store, so warm state survives restarts.
+//!
+//! Accounts, storage slots, code and block hashes loaded from the underlying [DatabaseRef] and
+//! changes committed by execution are written to separate trees of the store. Reads and
+//! commits behave the same as of [CacheDB](super::CacheDB): storage of cleared and not
+//! existing accounts is zero and never asked from the underlying database.
+use super::{AccountState, DatabaseCommit, DatabaseRef};
+use crate::primitives::{Account, AccountInfo, Bytecode, HashMap, B160, B256, KECCAK_EMPTY, U256};
+use crate::Database;
+use sled::transaction::{TransactionError, Transactional};
+use sled::{Batch, IVec, Tree};
+use std::path::Path;
+
+/// Length of encoded account, `state | nonce | balance | code_hash`.
+const ACCOUNT_LEN: usize = 1 + 8 + 32 + 32;
+
+#[derive(Debug)]
+pub enum PersistentDBError<E> {
+    /// Error of the underlying [DatabaseRef].
+    Database(E),
+    Store(sled::Error),
+}
+
+impl<E> From<sled::Error> for PersistentDBError<E> {
+    fn from(error: sled::Error) -> Self {
+        Self::Store(error)
+    }
+}
+
+/// A [Database] caching the underlying [DatabaseRef] in a [sled] store.
+///
+/// Writes are buffered by sled and persisted periodically, [PersistentCacheDB::flush] persists
+/// them right away. Changes of one [DatabaseCommit::commit] are written atomically.
+#[derive(Debug)]
+pub struct PersistentCacheDB<ExtDB: DatabaseRef> {
+    store: sled::Db,
+    accounts: Tree,
+    /// Keyed by `address | slot`, so storage of an account is a prefix range.
+    storage: Tree,
+    contracts: Tree,
+    block_hashes: Tree,
+    /// The underlying database ([DatabaseRef]) that is used to load data.
+    ///
+    /// Note: this is read-only, data is never written to this database.
+    pub db: ExtDB,
+}
+
+impl<ExtDB: DatabaseRef> PersistentCacheDB<ExtDB> {
+    /// Open the store at `path`, creating it if it does not exist.
+    ///
+    /// Store only caches `db`, it has to be opened with the same underlying state as before.
+    pub fn open(path: impl AsRef<Path>, db: ExtDB) -> Result<Self, sled::Error> {
+        Self::with_store(sled::open(path)?, db)
+    }
+
+    /// Use already opened `store`, with trees named `accounts`, `storage`, `contracts` and
+    /// `block_hashes`.
+    pub fn with_store(store: sled::Db, db: ExtDB) -> Result<Self, sled::Error> {
+        Ok(Self {
+            accounts: store.open_tree("accounts")?,
+            storage: store.open_tree("storage")?,
+            contracts: store.open_tree("contracts")?,
+            block_hashes: store.open_tree("block_hashes")?,
+            store,
+            db,
+        })
+    }
+
+    /// Persist all writes, returns number of flushed bytes.
+    pub fn flush(&self) -> Result<usize, sled::Error> {
+        self.store.flush()
+    }
+
+    /// Drop storage slots that read the same without them and flush the store, returns
+    /// number of dropped slots.
+    ///
+    /// Zero slots of accounts with cleared storage and slots of not existing accounts are
+    /// dropped. Space of dropped entries is reclaimed by sled in the background.
+    pub fn compact(&self) -> Result<usize, sled::Error> {
+        let mut batch = Batch::default();
+        let mut dropped = 0;
+        for entry in self.accounts.iter() {
+            let (address, account) = entry?;
+            let (state, _) = decode_account(&account);
+            let drop_all = match state {
+                AccountState::NotExisting => true,
+                AccountState::StorageCleared => false,
+                _ => continue,
+            };
+            for slot in self.storage.scan_prefix(&address) {
+                let (key, value) = slot?;
+                if drop_all || decode_u256(&value) == U256::ZERO {
+                    batch.remove(key);
+                    dropped += 1;
+                }
+            }
+        }
+        self.storage.apply_batch(batch)?;
+        self.flush()?;
+        Ok(dropped)
+    }
+
+    /// Cached account with its state, none if it is not cached.
+    pub fn cached_account(
+        &self,
+        address: B160,
+    ) -> Result<Option<(AccountState, AccountInfo)>, sled::Error> {
+        Ok(self
+            .accounts
+            .get(address)?
+            .map(|account| decode_account(&account)))
+    }
+
+    /// Load account from the store, or from the underlying database and store it.
+    fn load_account(
+        &mut self,
+        address: B160,
+    ) -> Result<(AccountState, AccountInfo), PersistentDBError<ExtDB::Error>> {
+        if let Some(account) = self.cached_account(address)? {
+            return Ok(account);
+        }
+        let account = match self
+            .db
+            .basic(address)
+            .map_err(PersistentDBError::Database)?
+        {
+            Some(mut info) => {
+                if let Some(code) = self.take_contract(&mut info) {
+                    self.contracts
+                        .insert(info.code_hash, code.original_bytes().to_vec())?;
+                }
+                (AccountState::None, info)
+            }
+            None => (AccountState::NotExisting, AccountInfo::default()),
+        };
+        self.accounts
+            .insert(address, encode_account(&account.0, &account.1).to_vec())?;
+        Ok(account)
+    }
+
+    /// Take code out of the account, it is stored by its hash. Returns code to store.
+    fn take_contract(&self, info: &mut AccountInfo) -> Option<Bytecode> {
+        let code = info.code.take().filter(|code| !code.is_empty());
+        if let Some(code) = &code {
+            info.code_hash = code.hash();
+        }
+        if info.code_hash == B256::zero() {
+            info.code_hash = KECCAK_EMPTY;
+        }
+        code
+    }
+
+    /// Write `changes` in one transaction, [DatabaseCommit::commit] panics on errors of it.
+    pub fn try_commit(&mut self, changes: HashMap<B160, Account>) -> Result<(), sled::Error> {
+        let mut accounts = Batch::default();
+        let mut storage = Batch::default();
+        let mut contracts = Batch::default();
+        for (address, mut account) in changes {
+            if !account.is_touched() {
+                continue;
+            }
+            let (old_state, _) = self
+                .cached_account(address)?
+                .unwrap_or((AccountState::None, AccountInfo::default()));
+            if account.is_selfdestructed() || account.is_newly_created() {
+                for key in self.storage.scan_prefix(address) {
+                    storage.remove(key?.0);
+                }
+            }
+            if account.is_selfdestructed() {
+                accounts.insert(
+                    &address[..],
+                    &encode_account(&AccountState::NotExisting, &AccountInfo::default())[..],
+                );
+                continue;
+            }
+
+            let state = if account.is_newly_created() || old_state.is_storage_cleared() {
+                AccountState::StorageCleared
+            } else {
+                AccountState::Touched
+            };
+            if let Some(code) = self.take_contract(&mut account.info) {
+                contracts.insert(&account.info.code_hash[..], code.original_bytes().to_vec());
+            }
+            accounts.insert(&address[..], &encode_account(&state, &account.info)[..]);
+            for (slot, value) in account.storage {
+                storage.insert(
+                    &storage_key(address, slot)[..],
+                    &value.present_value().to_be_bytes::<32>()[..],
+                );
+            }
+        }
+
+        (&self.accounts, &self.storage, &self.contracts)
+            .transaction(|(tx_accounts, tx_storage, tx_contracts)| {
+                tx_accounts.apply_batch(&accounts)?;
+                tx_storage.apply_batch(&storage)?;
+                tx_contracts.apply_batch(&contracts)?;
+                Ok(())
+            })
+            .map_err(|error: TransactionError<()>| match error {
+                TransactionError::Storage(error) => error,
+                TransactionError::Abort(()) => unreachable!("commit does not abort"),
+            })
+    }
+}
+
+impl<ExtDB: DatabaseRef> Database for PersistentCacheDB<ExtDB> {
+    type Error = PersistentDBError<ExtDB::Error>;
+
+    fn basic(&mut self, address: B160) -> Result<Option<AccountInfo>, Self::Error> {
+        let (state, info) = self.load_account(address)?;
+        Ok((!matches!(state, AccountState::NotExisting)).then_some(info))
+    }
+
+    fn code_by_hash(&mut self, code_hash: B256) -> Result<Bytecode, Self::Error> {
+        if code_hash == KECCAK_EMPTY || code_hash == B256::zero() {
+            return Ok(Bytecode::new());
+        }
+        if let Some(code) = self.contracts.get(code_hash)? {
+            // SAFETY: code is stored by its hash.
+            return Ok(unsafe { Bytecode::new_raw_with_hash(code.to_vec().into(), code_hash) });
+        }
+        let code = self
+            .db
+            .code_by_hash(code_hash)
+            .map_err(PersistentDBError::Database)?;
+        self.contracts
+            .insert(code_hash, code.original_bytes().to_vec())?;
+        Ok(code)
+    }
+
+    fn storage(&mut self, address: B160, index: U256) -> Result<U256, Self::Error> {
+        let key = storage_key(address, index);
+        if let Some(value) = self.storage.get(key)? {
+            return Ok(decode_u256(&value));
+        }
+        let (state, _) = self.load_account(address)?;
+        if matches!(
+            state,
+            AccountState::StorageCleared | AccountState::NotExisting
+        ) {
+            return Ok(U256::ZERO);
+        }
+        let value = self
+            .db
+            .storage(address, index)
+            .map_err(PersistentDBError::Database)?;
+        self.storage.insert(key, &value.to_be_bytes::<32>()[..])?;
+        Ok(value)
+    }
+
+    fn block_hash(&mut self, number: U256) -> Result<B256, Self::Error> {
+        let key = number.to_be_bytes::<32>();
+        if let Some(hash) = self.block_hashes.get(key)? {
+            return Ok(B256::from_slice(&hash));
+        }
+        let hash = self
+            .db
+            .block_hash(number)
+            .map_err(PersistentDBError::Database)?;
+        self.block_hashes.insert(key, &hash[..])?;
+        Ok(hash)
+    }
+}
+
+impl<ExtDB: DatabaseRef> DatabaseCommit for PersistentCacheDB<ExtDB> {
+    /// # Panics
+    ///
+    /// If writing to the store fails, use [PersistentCacheDB::try_commit] to handle it.
+    fn commit(&mut self, changes: HashMap<B160, Account>) {
+        self.try_commit(changes)
+            .expect("writing changes to the store failed")
+    }
+}
+
+fn storage_key(address: B160, slot: U256) -> [u8; 52] {
+    let mut key = [0; 52];
+    key[..20].copy_from_slice(&address[..]);
+    key[20..].copy_from_slice(&slot.to_be_bytes::<32>());
+    key
+}
+
+fn decode_u256(value: &IVec) -> U256 {
+    U256::from_be_bytes::<32>(value[..].try_into().expect("stored values are 32 bytes"))
+}
+
+fn encode_account(state: &AccountState, info: &AccountInfo) -> [u8; ACCOUNT_LEN] {
+    let mut out = [0; ACCOUNT_LEN];
+    out[0] = match state {
+        AccountState::NotExisting => 0,
+        AccountState::Touched => 1,
+        AccountState::StorageCleared => 2,
+        AccountState::None => 3,
+    };
+    out[1..9].copy_from_slice(&info.nonce.to_be_bytes());
+    out[9..41].copy_from_slice(&info.balance.to_be_bytes::<32>());
+    out[41..].copy_from_slice(&info.code_hash[..]);
+    out
+}
+
+fn decode_account(account: &[u8]) -> (AccountState, AccountInfo) {
+    let state = match account[0] {
+        0 => AccountState::NotExisting,
+        1 => AccountState::Touched,
+        2 => AccountState::StorageCleared,
+        _ => AccountState::None,
+    };
+    let info = AccountInfo {
+        nonce: u64::from_be_bytes(account[1..9].try_into().unwrap()),
+        balance: U256::from_be_bytes::<32>(account[9..41].try_into().unwrap()),
+        code_hash: B256::from_slice(&account[41..ACCOUNT_LEN]),
+        code: None,
+    };
+    (state, info)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{CacheDB, EmptyDB};
+    use crate::primitives::{AccountStatus, StorageSlot};
+
+    fn changes(address: B160, info: AccountInfo, status: AccountStatus) -> HashMap<B160, Account> {
+        let mut account = Account::from(info);
+        account.status = status | AccountStatus::Touched;
+        account.storage.insert(
+            U256::from(1),
+            StorageSlot {
+                original_value: U256::ZERO,
+                present_value: U256::from(10),
+            },
+        );
+        HashMap::from([(address, account)])
+    }
+
+    /// Open the store at `path` without underlying database. sled releases the lock of the
+    /// directory asynchronously after the last handle is dropped, opening is retried until it
+    /// is released.
+    fn reopen(path: &Path) -> PersistentCacheDB<EmptyDB> {
+        let mut attempts = 0;
+        loop {
+            match PersistentCacheDB::open(path, EmptyDB::default()) {
+                Ok(db) => return db,
+                Err(_) if attempts < 100 => {
+                    attempts += 1;
+                    std::thread::sleep(std::time::Duration::from_millis(10));
+                }
+                Err(e) => panic!("store is still locked: {e}"),
+            }
+        }
+    }
+
+    #[test]
+    fn persists_across_reopen() {
+        let path = std::env::temp_dir().join(format!("revm-persistent-{}", std::process::id()));
+        let address = B160([1; 20]);
+        let loaded = B160([2; 20]);
+        let mut base = CacheDB::new(EmptyDB::default());
+        base.insert_account_info(loaded, AccountInfo::from_balance(U256::from(7)));
+        base.insert_account_storage(loaded, U256::from(3), U256::from(4))
+            .unwrap();
+
+        let mut db = PersistentCacheDB::open(&path, base.clone()).unwrap();
+        let code = Bytecode::new_raw(vec![0x60, 0x00].into());
+        let info = AccountInfo::new(U256::from(5), 1, code.clone());
+        db.commit(changes(address, info, AccountStatus::Created));
+        assert_eq!(db.storage(loaded, U256::from(3)).unwrap(), U256::from(4));
+        db.flush().unwrap();
+        drop(db);
+
+        // underlying database is gone, everything is read from the store.
+        let mut db = reopen(&path);
+        let stored = db.basic(address).unwrap().unwrap();
+        assert_eq!((stored.balance, stored.nonce), (U256::from(5), 1));
+        assert_eq!(
+            db.code_by_hash(stored.code_hash).unwrap().original_bytes(),
+            code.original_bytes()
+        );
+        assert_eq!(db.storage(address, U256::from(1)).unwrap(), U256::from(10));
+        assert_eq!(db.storage(address, U256::from(2)).unwrap(), U256::ZERO);
+        assert_eq!(db.basic(loaded).unwrap().unwrap().balance, U256::from(7));
+        assert_eq!(db.storage(loaded, U256::from(3)).unwrap(), U256::from(4));
+
+        let mut destroyed = changes(
+            address,
+            AccountInfo::default(),
+            AccountStatus::SelfDestructed,
+        );
+        destroyed.get_mut(&address).unwrap().storage.clear();
+        db.commit(destroyed);
+        assert_eq!(db.basic(address).unwrap(), None);
+        assert_eq!(db.storage(address, U256::from(1)).unwrap(), U256::ZERO);
+
+        drop(db);
+        std::fs::remove_dir_all(&path).unwrap();
+    }
+}
@@ -0,0 +1,445 @@
+//! Database recording every query and response, and database replaying the recording.
+//!
+//! [RecordingDatabase] wraps the database of a transaction, for example one reading mainnet
+//! state through RPC, and [Recording::encode] writes what it was asked in a compact binary
+//! format. [ReplayDatabase] answers the same queries in the same order from the recording,
+//! so the transaction can be executed again without the original database, in regression
+//! tests or benchmarks.
+use crate::db::{Database, DatabaseCommit};
+use crate::primitives::{Account, AccountInfo, Bytecode, Bytes, HashMap, B160, B256, U256};
+use alloc::vec::Vec;
+
+/// First bytes of encoded [Recording], format version follows them.
+const MAGIC: &[u8; 7] = b"revmrec";
+const VERSION: u8 = 1;
+
+const BASIC_NONE: u8 = 0;
+const BASIC: u8 = 1;
+const CODE_BY_HASH: u8 = 2;
+const STORAGE: u8 = 3;
+const BLOCK_HASH: u8 = 4;
+
+/// Query of the database.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Query {
+    Basic(B160),
+    CodeByHash(B256),
+    Storage(B160, U256),
+    BlockHash(U256),
+}
+
+/// Query and response of the database.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Record {
+    Basic(B160, Option<AccountInfo>),
+    CodeByHash(B256, Bytecode),
+    Storage(B160, U256, U256),
+    BlockHash(U256, B256),
+}
+
+impl Record {
+    pub fn query(&self) -> Query {
+        match *self {
+            Self::Basic(address, _) => Query::Basic(address),
+            Self::CodeByHash(code_hash, _) => Query::CodeByHash(code_hash),
+            Self::Storage(address, index, _) => Query::Storage(address, index),
+            Self::BlockHash(number, _) => Query::BlockHash(number),
+        }
+    }
+}
+
+/// Error decoding [Recording].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RecordingDecodeError {
+    InvalidMagic,
+    UnsupportedVersion(u8),
+    UnknownRecord(u8),
+    UnexpectedEnd,
+}
+
+/// Queries of the database with their responses, in order they were made.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Recording {
+    pub records: Vec<Record>,
+}
+
+impl Recording {
+    /// Encode records, every one is its tag followed by fixed size fields, code is prefixed
+    /// with its hash and length.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::from(&MAGIC[..]);
+        out.push(VERSION);
+        for record in &self.records {
+            match record {
+                Record::Basic(address, None) => {
+                    out.push(BASIC_NONE);
+                    out.extend_from_slice(&address.0);
+                }
+                Record::Basic(address, Some(info)) => {
+                    out.push(BASIC);
+                    out.extend_from_slice(&address.0);
+                    out.extend_from_slice(&info.nonce.to_be_bytes());
+                    out.extend_from_slice(&info.balance.to_be_bytes::<32>());
+                    out.extend_from_slice(&info.code_hash.0);
+                    match &info.code {
+                        Some(code) => {
+                            out.push(1);
+                            encode_code(&mut out, code);
+                        }
+                        None => out.push(0),
+                    }
+                }
+                Record::CodeByHash(code_hash, code) => {
+                    out.push(CODE_BY_HASH);
+                    out.extend_from_slice(&code_hash.0);
+                    encode_code(&mut out, code);
+                }
+                Record::Storage(address, index, value) => {
+                    out.push(STORAGE);
+                    out.extend_from_slice(&address.0);
+                    out.extend_from_slice(&index.to_be_bytes::<32>());
+                    out.extend_from_slice(&value.to_be_bytes::<32>());
+                }
+                Record::BlockHash(number, hash) => {
+                    out.push(BLOCK_HASH);
+                    out.extend_from_slice(&number.to_be_bytes::<32>());
+                    out.extend_from_slice(&hash.0);
+                }
+            }
+        }
+        out
+    }
+
+    /// Decode records encoded by [Recording::encode].
+    pub fn decode(data: &[u8]) -> Result<Self, RecordingDecodeError> {
+        let mut reader = Reader(data);
+        if reader.take(MAGIC.len())? != MAGIC {
+            return Err(RecordingDecodeError::InvalidMagic);
+        }
+        let version = reader.byte()?;
+        if version != VERSION {
+            return Err(RecordingDecodeError::UnsupportedVersion(version));
+        }
+        let mut records = Vec::new();
+        while !reader.0.is_empty() {
+            let record = match reader.byte()? {
+                BASIC_NONE => Record::Basic(reader.address()?, None),
+                BASIC => {
+                    let address = reader.address()?;
+                    let nonce = u64::from_be_bytes(reader.take(8)?.try_into().unwrap());
+                    let balance = reader.u256()?;
+                    let code_hash = reader.b256()?;
+                    let code = match reader.byte()? {
+                        0 => None,
+                        _ => Some(reader.code()?),
+                    };
+                    Record::Basic(
+                        address,
+                        Some(AccountInfo {
+                            balance,
+                            nonce,
+                            code_hash,
+                            code,
+                        }),
+                    )
+                }
+                CODE_BY_HASH => Record::CodeByHash(reader.b256()?, reader.code()?),
+                STORAGE => Record::Storage(reader.address()?, reader.u256()?, reader.u256()?),
+                BLOCK_HASH => Record::BlockHash(reader.u256()?, reader.b256()?),
+                tag => return Err(RecordingDecodeError::UnknownRecord(tag)),
+            };
+            records.push(record);
+        }
+        Ok(Self { records })
+    }
+
+    /// Write encoded recording to file at `path`.
+    #[cfg(feature = "std")]
+    pub fn save(&self, path: impl AsRef<std::path::Path>) -> std::io::Result<()> {
+        std::fs::write(path, self.encode())
+    }
+
+    /// Read recording written by [Recording::save].
+    #[cfg(feature = "std")]
+    pub fn load(path: impl AsRef<std::path::Path>) -> std::io::Result<Self> {
+        let data = std::fs::read(path)?;
+        Self::decode(&data).map_err(|error| {
+            std::io::Error::new(std::io::ErrorKind::InvalidData, format!("{error:?}"))
+        })
+    }
+}
+
+/// Hash, length and original bytes of `code`, analysis is not kept.
+fn encode_code(out: &mut Vec<u8>, code: &Bytecode) {
+    let bytes = code.original_bytes();
+    out.extend_from_slice(&code.hash().0);
+    out.extend_from_slice(&(bytes.len() as u32).to_be_bytes());
+    out.extend_from_slice(&bytes);
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], RecordingDecodeError> {
+        if self.0.len() < len {
+            return Err(RecordingDecodeError::UnexpectedEnd);
+        }
+        let (taken, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(taken)
+    }
+
+    fn byte(&mut self) -> Result<u8, RecordingDecodeError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn address(&mut self) -> Result<B160, RecordingDecodeError> {
+        Ok(B160::from_slice(self.take(20)?))
+    }
+
+    fn b256(&mut self) -> Result<B256, RecordingDecodeError> {
+        Ok(B256::from_slice(self.take(32)?))
+    }
+
+    fn u256(&mut self) -> Result<U256, RecordingDecodeError> {
+        Ok(U256::from_be_bytes::<32>(
+            self.take(32)?.try_into().unwrap(),
+        ))
+    }
+
+    fn code(&mut self) -> Result<Bytecode, RecordingDecodeError> {
+        let hash = self.b256()?;
+        let len = u32::from_be_bytes(self.take(4)?.try_into().unwrap()) as usize;
+        let bytes = Bytes::copy_from_slice(self.take(len)?);
+        // SAFETY: hash is the one code was recorded with.
+        Ok(unsafe { Bytecode::new_raw_with_hash(bytes, hash) })
+    }
+}
+
+/// Database wrapper recording every query and its response, see the [module](self) docs.
+#[derive(Clone, Debug, Default)]
+pub struct RecordingDatabase<DB> {
+    pub db: DB,
+    recording: Recording,
+}
+
+impl<DB> RecordingDatabase<DB> {
+    pub fn new(db: DB) -> Self {
+        Self {
+            db,
+            recording: Recording::default(),
+        }
+    }
+
+    pub fn recording(&self) -> &Recording {
+        &self.recording
+    }
+
+    pub fn into_parts(self) -> (DB, Recording) {
+        (self.db, self.recording)
+    }
+}
+
+impl<DB: Database> Database for RecordingDatabase<DB> {
+    type Error = DB::Error;
+
+    fn basic(&mut self, address: B160) -> Result<Option<AccountInfo>, Self::Error> {
+        let info = self.db.basic(address)?;
+        self.recording
+            .records
+            .push(Record::Basic(address, info.clone()));
+        Ok(info)
+    }
+
+    fn code_by_hash(&mut self, code_hash: B256) -> Result<Bytecode, Self::Error> {
+        let code = self.db.code_by_hash(code_hash)?;
+        self.recording
+            .records
+            .push(Record::CodeByHash(code_hash, code.clone()));
+        Ok(code)
+    }
+
+    fn storage(&mut self, address: B160, index: U256) -> Result<U256, Self::Error> {
+        let value = self.db.storage(address, index)?;
+        self.recording
+            .records
+            .push(Record::Storage(address, index, value));
+        Ok(value)
+    }
+
+    fn block_hash(&mut self, number: U256) -> Result<B256, Self::Error> {
+        let hash = self.db.block_hash(number)?;
+        self.recording.records.push(Record::BlockHash(number, hash));
+        Ok(hash)
+    }
+}
+
+impl<DB: DatabaseCommit> DatabaseCommit for RecordingDatabase<DB> {
+    fn commit(&mut self, changes: HashMap<B160, Account>) {
+        self.db.commit(changes)
+    }
+}
+
+/// Error of [ReplayDatabase], execution doesn't query the database as it was recorded.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReplayError {
+    /// Query is not the next recorded one.
+    Mismatch { expected: Query, actual: Query },
+    /// All recorded queries were answered.
+    Exhausted(Query),
+}
+
+/// Database answering queries from a [Recording], in order they were recorded.
+///
+/// Commits are ignored, responses recorded after them already have the changes.
+#[derive(Clone, Debug, Default)]
+pub struct ReplayDatabase {
+    recording: Recording,
+    position: usize,
+}
+
+impl ReplayDatabase {
+    pub fn new(recording: Recording) -> Self {
+        Self {
+            recording,
+            position: 0,
+        }
+    }
+
+    /// All recorded queries were answered.
+    pub fn is_finished(&self) -> bool {
+        self.position == self.recording.records.len()
+    }
+
+    /// Start answering from the first recorded query again.
+    pub fn rewind(&mut self) {
+        self.position = 0;
+    }
+
+    fn next(&mut self, query: Query) -> Result<&Record, ReplayError> {
+        let record = self
+            .recording
+            .records
+            .get(self.position)
+            .ok_or(ReplayError::Exhausted(query))?;
+        if record.query() != query {
+            return Err(ReplayError::Mismatch {
+                expected: record.query(),
+                actual: query,
+            });
+        }
+        self.position += 1;
+        Ok(record)
+    }
+}
+
+impl Database for ReplayDatabase {
+    type Error = ReplayError;
+
+    fn basic(&mut self, address: B160) -> Result<Option<AccountInfo>, Self::Error> {
+        match self.next(Query::Basic(address))? {
+            Record::Basic(_, info) => Ok(info.clone()),
+            _ => unreachable!("record matches the query"),
+        }
+    }
+
+    fn code_by_hash(&mut self, code_hash: B256) -> Result<Bytecode, Self::Error> {
+        match self.next(Query::CodeByHash(code_hash))? {
+            Record::CodeByHash(_, code) => Ok(code.clone()),
+            _ => unreachable!("record matches the query"),
+        }
+    }
+
+    fn storage(&mut self, address: B160, index: U256) -> Result<U256, Self::Error> {
+        match self.next(Query::Storage(address, index))? {
+            Record::Storage(_, _, value) => Ok(*value),
+            _ => unreachable!("record matches the query"),
+        }
+    }
+
+    fn block_hash(&mut self, number: U256) -> Result<B256, Self::Error> {
+        match self.next(Query::BlockHash(number))? {
+            Record::BlockHash(_, hash) => Ok(*hash),
+            _ => unreachable!("record matches the query"),
+        }
+    }
+}
+
+impl DatabaseCommit for ReplayDatabase {
+    fn commit(&mut self, _changes: HashMap<B160, Account>) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primitives::{hex_literal::hex, EVMError, TransactTo};
+    use crate::{InMemoryDB, EVM};
+
+    #[test]
+    fn replays_recording() {
+        let caller = B160([0x10; 20]);
+        let contract = B160([0x20; 20]);
+        // SSTORE(0, SLOAD(1) + 1) MSTORE(0, BLOCKHASH(NUMBER - 1)) RETURN(0, 32)
+        let code = hex!("6001546001016000556001430340600052 60206000f3");
+        let mut db = InMemoryDB::default();
+        db.insert_account_info(caller, AccountInfo::from_balance(U256::from(10u64.pow(18))));
+        db.insert_account_info(
+            contract,
+            AccountInfo::new(U256::ZERO, 0, Bytecode::new_raw(code.to_vec().into())),
+        );
+        db.insert_account_storage(contract, U256::from(1), U256::from(41))
+            .unwrap();
+
+        let mut evm = EVM::new();
+        evm.database(RecordingDatabase::new(db));
+        evm.env.block.number = U256::from(10);
+        evm.env.tx.caller = caller;
+        evm.env.tx.transact_to = TransactTo::Call(contract);
+        let expected = evm.transact_commit().unwrap();
+        let (_, recording) = evm.take_db().into_parts();
+        assert!(recording.records.contains(&Record::Storage(
+            contract,
+            U256::from(1),
+            U256::from(41)
+        )));
+
+        let recording = Recording::decode(&recording.encode()).unwrap();
+        let mut evm = EVM::new();
+        evm.database(ReplayDatabase::new(recording));
+        evm.env.block.number = U256::from(10);
+        evm.env.tx.caller = caller;
+        evm.env.tx.transact_to = TransactTo::Call(contract);
+        assert_eq!(evm.transact_commit().unwrap(), expected);
+        assert!(evm.db().unwrap().is_finished());
+
+        evm.db().unwrap().rewind();
+        evm.env.tx.caller = contract;
+        assert_eq!(
+            evm.transact().unwrap_err(),
+            EVMError::Database(ReplayError::Mismatch {
+                expected: Query::Basic(caller),
+                actual: Query::Basic(contract),
+            })
+        );
+    }
+
+    #[test]
+    fn decode_errors() {
+        assert_eq!(
+            Recording::decode(b"revmrak\x01"),
+            Err(RecordingDecodeError::InvalidMagic)
+        );
+        assert_eq!(
+            Recording::decode(b"revmrec\x02"),
+            Err(RecordingDecodeError::UnsupportedVersion(2))
+        );
+        assert_eq!(
+            Recording::decode(b"revmrec\x01\x05"),
+            Err(RecordingDecodeError::UnknownRecord(5))
+        );
+        assert_eq!(
+            Recording::decode(b"revmrec\x01\x00\x01"),
+            Err(RecordingDecodeError::UnexpectedEnd)
+        );
+    }
+}
@@ -0,0 +1,188 @@
+//! Database recording the state that execution read, for stateless execution.
+use crate::db::{CacheDB, Database, DatabaseCommit, DbAccount, EmptyDB};
+use crate::primitives::{Account, AccountInfo, Bytecode, HashMap, HashSet, B160, B256, U256};
+use alloc::collections::BTreeMap;
+
+/// State read during execution, values are the ones from before the execution.
+///
+/// Executing the same transactions over [ExecutionWitness::to_db] gives the same results.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ExecutionWitness {
+    /// None if account didn't exist. Code is in `contracts`.
+    pub accounts: BTreeMap<B160, Option<AccountInfo>>,
+    pub contracts: BTreeMap<B256, Bytecode>,
+    pub storage: BTreeMap<B160, BTreeMap<U256, U256>>,
+    pub block_hashes: BTreeMap<U256, B256>,
+}
+
+impl ExecutionWitness {
+    /// Database answering every recorded read, the rest read as empty.
+    pub fn to_db(&self) -> CacheDB<EmptyDB> {
+        let mut db = CacheDB::new(EmptyDB::default());
+        for (address, info) in &self.accounts {
+            db.accounts.insert(*address, info.clone().into());
+        }
+        for (address, storage) in &self.storage {
+            let account = db
+                .accounts
+                .entry(*address)
+                .or_insert_with(DbAccount::new_not_existing);
+            account
+                .storage
+                .extend(storage.iter().map(|(slot, value)| (*slot, *value)));
+        }
+        for (code_hash, code) in &self.contracts {
+            db.contracts.insert(*code_hash, code.clone());
+        }
+        for (number, hash) in &self.block_hashes {
+            db.block_hashes.insert(*number, *hash);
+        }
+        db
+    }
+}
+
+/// Database wrapper recording every read into [ExecutionWitness].
+///
+/// Only first read of every value is recorded. Values written by committed changes are not
+/// recorded when read afterwards, so the witness of a block has the state from before the
+/// block even if the wrapped database is updated between transactions.
+#[derive(Clone, Debug, Default)]
+pub struct WitnessRecorder<DB> {
+    pub db: DB,
+    witness: ExecutionWitness,
+    written_accounts: HashSet<B160>,
+    written_storage: HashSet<(B160, U256)>,
+    /// Accounts that were created or destroyed, all their storage was written.
+    cleared_storage: HashSet<B160>,
+}
+
+impl<DB> WitnessRecorder<DB> {
+    pub fn new(db: DB) -> Self {
+        Self {
+            db,
+            witness: ExecutionWitness::default(),
+            written_accounts: HashSet::new(),
+            written_storage: HashSet::new(),
+            cleared_storage: HashSet::new(),
+        }
+    }
+
+    pub fn witness(&self) -> &ExecutionWitness {
+        &self.witness
+    }
+
+    pub fn into_parts(self) -> (DB, ExecutionWitness) {
+        (self.db, self.witness)
+    }
+}
+
+impl<DB: Database> Database for WitnessRecorder<DB> {
+    type Error = DB::Error;
+
+    fn basic(&mut self, address: B160) -> Result<Option<AccountInfo>, Self::Error> {
+        let info = self.db.basic(address)?;
+        if !self.written_accounts.contains(&address)
+            && !self.witness.accounts.contains_key(&address)
+        {
+            let mut recorded = info.clone();
+            if let Some(info) = &mut recorded {
+                if let Some(code) = info.code.take() {
+                    self.witness.contracts.insert(info.code_hash, code);
+                }
+            }
+            self.witness.accounts.insert(address, recorded);
+        }
+        Ok(info)
+    }
+
+    fn code_by_hash(&mut self, code_hash: B256) -> Result<Bytecode, Self::Error> {
+        let code = self.db.code_by_hash(code_hash)?;
+        self.witness
+            .contracts
+            .entry(code_hash)
+            .or_insert_with(|| code.clone());
+        Ok(code)
+    }
+
+    fn storage(&mut self, address: B160, index: U256) -> Result<U256, Self::Error> {
+        let value = self.db.storage(address, index)?;
+        if !self.cleared_storage.contains(&address)
+            && !self.written_storage.contains(&(address, index))
+        {
+            self.witness
+                .storage
+                .entry(address)
+                .or_default()
+                .entry(index)
+                .or_insert(value);
+        }
+        Ok(value)
+    }
+
+    fn block_hash(&mut self, number: U256) -> Result<B256, Self::Error> {
+        let hash = self.db.block_hash(number)?;
+        self.witness.block_hashes.entry(number).or_insert(hash);
+        Ok(hash)
+    }
+}
+
+impl<DB: DatabaseCommit> DatabaseCommit for WitnessRecorder<DB> {
+    fn commit(&mut self, changes: HashMap<B160, Account>) {
+        for (address, account) in &changes {
+            if !account.is_touched() {
+                continue;
+            }
+            self.written_accounts.insert(*address);
+            if account.is_selfdestructed() || account.is_newly_created() {
+                self.cleared_storage.insert(*address);
+            }
+            self.written_storage.extend(
+                account
+                    .storage
+                    .iter()
+                    .filter(|(_, slot)| slot.is_changed())
+                    .map(|(slot, _)| (*address, *slot)),
+            );
+        }
+        self.db.commit(changes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primitives::{hex_literal::hex, TransactTo};
+    use crate::InMemoryDB;
+
+    #[test]
+    fn records_state_before_block() {
+        let caller = B160([0x10; 20]);
+        let counter = B160([0x20; 20]);
+        // SSTORE(0, SLOAD(0) + 1)
+        let code = Bytecode::new_raw(hex!("600054600101600055").to_vec().into());
+        let mut db = InMemoryDB::default();
+        db.insert_account_info(counter, AccountInfo::new(U256::ZERO, 0, code.clone()));
+        db.insert_account_storage(counter, U256::ZERO, U256::from(5))
+            .unwrap();
+
+        let mut evm = crate::new();
+        evm.database(WitnessRecorder::new(db));
+        evm.env.tx.caller = caller;
+        evm.env.tx.transact_to = TransactTo::Call(counter);
+        let first = evm.transact_commit().unwrap();
+        let second = evm.transact_commit().unwrap();
+
+        let (_, witness) = evm.take_db().into_parts();
+        assert_eq!(witness.storage[&counter][&U256::ZERO], U256::from(5));
+        assert_eq!(witness.accounts[&caller], None);
+        assert_eq!(witness.contracts[&code.hash()], code);
+
+        // same block executes over the witness alone.
+        let mut stateless = crate::new();
+        stateless.env = evm.env.clone();
+        stateless.database(witness.to_db());
+        assert_eq!(stateless.transact_commit().unwrap(), first);
+        assert_eq!(stateless.transact_commit().unwrap(), second);
+    }
+}
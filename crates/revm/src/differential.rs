@@ -0,0 +1,358 @@
+//! Executor running transactions through revm and an external EVM, and comparing them.
+//!
+//! External implementation is plugged in with [ExternalExecutor], for example an adapter
+//! calling `evm t8n` of geth. It gets the environment and prestate of every account and
+//! slot revm accessed, enough to build `alloc` of the t8n protocol.
+use crate::db::{DatabaseCommit, DatabaseRef};
+use crate::primitives::{
+    AccountInfo, Bytes, EVMError, Env, Eval, ExecutionResult, Halt, HashMap, Log, ResultAndState,
+    State, B160, B256, KECCAK_EMPTY, U256,
+};
+use crate::EVM;
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::vec::Vec;
+
+/// State of the account before the transaction.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PreAccount {
+    /// Account info with the code loaded.
+    pub info: AccountInfo,
+    pub storage: HashMap<U256, U256>,
+}
+
+/// Accounts that existed before the transaction, with their accessed storage.
+pub type Prestate = HashMap<B160, PreAccount>;
+
+/// EVM implementation revm is compared against.
+pub trait ExternalExecutor {
+    type Error;
+
+    /// Execute transaction of `env` on top of `prestate`.
+    ///
+    /// Only accounts that were touched need to be in the returned state, storage of an
+    /// account needs only slots that were accessed.
+    fn execute(&mut self, env: &Env, prestate: &Prestate) -> Result<ResultAndState, Self::Error>;
+}
+
+/// How the transaction ended.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Status {
+    Success(Eval),
+    Revert,
+    Halt(Halt),
+}
+
+impl From<&ExecutionResult> for Status {
+    fn from(result: &ExecutionResult) -> Self {
+        match result {
+            ExecutionResult::Success { reason, .. } => Status::Success(*reason),
+            ExecutionResult::Revert { .. } => Status::Revert,
+            ExecutionResult::Halt { reason, .. } => Status::Halt(*reason),
+        }
+    }
+}
+
+/// Difference between revm and external execution, revm value comes first.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Mismatch {
+    Status(Status, Status),
+    GasUsed(u64, u64),
+    Output(Option<Bytes>, Option<Bytes>),
+    Logs(Vec<Log>, Vec<Log>),
+    Balance(B160, U256, U256),
+    Nonce(B160, u64, u64),
+    CodeHash(B160, B256, B256),
+    Storage(B160, U256, U256, U256),
+}
+
+/// Results of both executions and their differences.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DifferentialReport {
+    pub revm: ResultAndState,
+    pub external: ResultAndState,
+    /// Ordered by address and slot.
+    pub mismatches: Vec<Mismatch>,
+}
+
+impl DifferentialReport {
+    pub fn is_match(&self) -> bool {
+        self.mismatches.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DifferentialError<DBError, ExtError> {
+    Revm(EVMError<DBError>),
+    External(ExtError),
+    Database(DBError),
+}
+
+/// Runs every transaction through revm and [ExternalExecutor] over the same state.
+#[derive(Clone, Debug)]
+pub struct DifferentialExecutor<DB, E> {
+    pub db: DB,
+    pub external: E,
+}
+
+impl<DB: DatabaseRef, E: ExternalExecutor> DifferentialExecutor<DB, E> {
+    pub fn new(db: DB, external: E) -> Self {
+        Self { db, external }
+    }
+
+    /// Execute transaction with both implementations and compare them. Database is not
+    /// changed.
+    pub fn run(
+        &mut self,
+        env: &Env,
+    ) -> Result<DifferentialReport, DifferentialError<DB::Error, E::Error>> {
+        let mut evm = EVM::with_env(env.clone());
+        evm.database(&self.db);
+        let revm = evm.transact_ref().map_err(DifferentialError::Revm)?;
+
+        let prestate = self.prestate(&revm.state)?;
+        let external = self
+            .external
+            .execute(env, &prestate)
+            .map_err(DifferentialError::External)?;
+
+        let mut mismatches = Vec::new();
+        let (status, external_status) = (Status::from(&revm.result), (&external.result).into());
+        if status != external_status {
+            mismatches.push(Mismatch::Status(status, external_status));
+        }
+        let (gas_used, external_gas_used) = (revm.result.gas_used(), external.result.gas_used());
+        if gas_used != external_gas_used {
+            mismatches.push(Mismatch::GasUsed(gas_used, external_gas_used));
+        }
+        let (output, external_output) = (revm.result.output(), external.result.output());
+        if output != external_output {
+            mismatches.push(Mismatch::Output(output.cloned(), external_output.cloned()));
+        }
+        let (logs, external_logs) = (revm.result.logs(), external.result.logs());
+        if logs != external_logs {
+            mismatches.push(Mismatch::Logs(logs, external_logs));
+        }
+        self.compare_state(&revm.state, &external.state, &prestate, &mut mismatches)?;
+
+        Ok(DifferentialReport {
+            revm,
+            external,
+            mismatches,
+        })
+    }
+
+    /// Accounts and slots revm accessed, with values they had before the transaction.
+    fn prestate(&self, state: &State) -> Result<Prestate, DifferentialError<DB::Error, E::Error>> {
+        let mut prestate = Prestate::new();
+        for (address, account) in state {
+            let Some(mut info) = self
+                .db
+                .basic(*address)
+                .map_err(DifferentialError::Database)?
+            else {
+                continue;
+            };
+            if info.code.is_none() && info.code_hash != KECCAK_EMPTY {
+                info.code = Some(
+                    self.db
+                        .code_by_hash(info.code_hash)
+                        .map_err(DifferentialError::Database)?,
+                );
+            }
+            let storage = account
+                .storage
+                .iter()
+                .map(|(slot, value)| (*slot, value.original_value()))
+                .collect();
+            prestate.insert(*address, PreAccount { info, storage });
+        }
+        Ok(prestate)
+    }
+
+    fn compare_state(
+        &self,
+        state: &State,
+        external: &State,
+        prestate: &Prestate,
+        mismatches: &mut Vec<Mismatch>,
+    ) -> Result<(), DifferentialError<DB::Error, E::Error>> {
+        let addresses: BTreeSet<B160> = state.keys().chain(external.keys()).copied().collect();
+        for address in addresses {
+            let pre = prestate.get(&address);
+            let (info, storage) = post_state(state, address, pre);
+            let (external_info, external_storage) = post_state(external, address, pre);
+            if info.balance != external_info.balance {
+                mismatches.push(Mismatch::Balance(
+                    address,
+                    info.balance,
+                    external_info.balance,
+                ));
+            }
+            if info.nonce != external_info.nonce {
+                mismatches.push(Mismatch::Nonce(address, info.nonce, external_info.nonce));
+            }
+            if info.code_hash != external_info.code_hash {
+                mismatches.push(Mismatch::CodeHash(
+                    address,
+                    info.code_hash,
+                    external_info.code_hash,
+                ));
+            }
+
+            let slots: BTreeSet<U256> = storage
+                .iter()
+                .chain(external_storage.iter())
+                .flat_map(|storage| storage.keys().copied())
+                .collect();
+            for slot in slots {
+                let value = self.slot(address, slot, storage.as_ref(), pre)?;
+                let external_value = self.slot(address, slot, external_storage.as_ref(), pre)?;
+                if value != external_value {
+                    mismatches.push(Mismatch::Storage(address, slot, value, external_value));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Value of slot after the transaction, slot missing from the storage is not changed.
+    fn slot(
+        &self,
+        address: B160,
+        slot: U256,
+        storage: Option<&BTreeMap<U256, U256>>,
+        pre: Option<&PreAccount>,
+    ) -> Result<U256, DifferentialError<DB::Error, E::Error>> {
+        // storage of destroyed account is cleared.
+        let Some(storage) = storage else {
+            return Ok(U256::ZERO);
+        };
+        if let Some(value) = storage.get(&slot) {
+            return Ok(*value);
+        }
+        if let Some(value) = pre.and_then(|pre| pre.storage.get(&slot)) {
+            return Ok(*value);
+        }
+        if pre.is_none() {
+            return Ok(U256::ZERO);
+        }
+        self.db
+            .storage(address, slot)
+            .map_err(DifferentialError::Database)
+    }
+}
+
+impl<DB: DatabaseRef + DatabaseCommit, E: ExternalExecutor> DifferentialExecutor<DB, E> {
+    /// Same as [DifferentialExecutor::run], state changes of revm are committed to database.
+    pub fn run_commit(
+        &mut self,
+        env: &Env,
+    ) -> Result<DifferentialReport, DifferentialError<DB::Error, E::Error>> {
+        let report = self.run(env)?;
+        self.db.commit(report.revm.state.clone());
+        Ok(report)
+    }
+}
+
+/// Info and storage of the account after the transaction, `None` storage if it was cleared.
+fn post_state(
+    state: &State,
+    address: B160,
+    pre: Option<&PreAccount>,
+) -> (AccountInfo, Option<BTreeMap<U256, U256>>) {
+    match state.get(&address) {
+        Some(account) if account.is_selfdestructed() => (AccountInfo::default(), None),
+        Some(account) => {
+            let storage = account
+                .storage
+                .iter()
+                .map(|(slot, value)| (*slot, value.present_value()))
+                .collect();
+            (account.info.clone(), Some(storage))
+        }
+        None => (
+            pre.map(|pre| pre.info.clone()).unwrap_or_default(),
+            Some(BTreeMap::new()),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primitives::{hex_literal::hex, Bytecode, TransactTo};
+    use crate::InMemoryDB;
+
+    /// revm executing over database built from the prestate, gas and slot 0 are tampered.
+    struct Reference {
+        tamper: bool,
+    }
+
+    impl ExternalExecutor for Reference {
+        type Error = ();
+
+        fn execute(&mut self, env: &Env, prestate: &Prestate) -> Result<ResultAndState, ()> {
+            let mut db = InMemoryDB::default();
+            for (address, account) in prestate {
+                db.insert_account_info(*address, account.info.clone());
+                for (slot, value) in &account.storage {
+                    db.insert_account_storage(*address, *slot, *value).unwrap();
+                }
+            }
+            let mut evm = EVM::with_env(env.clone());
+            evm.database(db);
+            let mut out = evm.transact().map_err(|_| ())?;
+            if self.tamper {
+                if let ExecutionResult::Success { gas_used, .. } = &mut out.result {
+                    *gas_used += 1;
+                }
+                for account in out.state.values_mut() {
+                    if let Some(slot) = account.storage.get_mut(&U256::ZERO) {
+                        slot.present_value = U256::from(100);
+                    }
+                }
+            }
+            Ok(out)
+        }
+    }
+
+    #[test]
+    fn reports_mismatches() {
+        let caller = B160([0x10; 20]);
+        let contract = B160([0x20; 20]);
+        let mut db = InMemoryDB::default();
+        // SSTORE(0, SLOAD(0) + 1)
+        db.insert_account_info(
+            contract,
+            AccountInfo::new(
+                U256::ZERO,
+                0,
+                Bytecode::new_raw(hex!("60016000540160005500").to_vec().into()),
+            ),
+        );
+        db.insert_account_storage(contract, U256::ZERO, U256::from(5))
+            .unwrap();
+        let mut env = Env::default();
+        env.tx.caller = caller;
+        env.tx.transact_to = TransactTo::Call(contract);
+
+        let mut executor = DifferentialExecutor::new(db, Reference { tamper: false });
+        let report = executor.run_commit(&env).unwrap();
+        assert!(report.is_match());
+        assert_eq!(
+            DatabaseRef::storage(&executor.db, contract, U256::ZERO),
+            Ok(U256::from(6))
+        );
+
+        executor.external.tamper = true;
+        let report = executor.run(&env).unwrap();
+        let gas_used = report.revm.result.gas_used();
+        assert_eq!(
+            report.mismatches,
+            [
+                Mismatch::GasUsed(gas_used, gas_used + 1),
+                Mismatch::Storage(contract, U256::ZERO, U256::from(7), U256::from(100)),
+            ]
+        );
+    }
+}
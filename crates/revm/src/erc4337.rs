@@ -0,0 +1,569 @@
+//! ERC-4337 user operation validation.
+//!
+//! Validation of a user operation is a normal transaction calling the EntryPoint
+//! (`simulateValidation` or bundler specific helper) that is inspected by
+//! [ValidationInspector]. Frames are attributed to the entity (factory, account or
+//! paymaster) whose code started them and the ERC-7562 rules are checked on them:
+//!
+//! * banned opcodes, `GAS` that is not followed by a call and `CREATE`/`CREATE2`
+//!   other than the single `CREATE2` of the sender by the factory,
+//! * calls with value, calls into EntryPoint and calls to accounts without code,
+//! * out of gas inside of an entity,
+//! * storage access, depending on the slot being owned by or associated with the sender
+//!   or the entity and on the entity being staked.
+//!
+//! Slot is associated with an address `A` if it is `A` or in range
+//! `keccak256(A || x)..keccak256(A || x) + 128`, where hashes are the ones computed by
+//! `KECCAK256` during validation.
+use crate::interpreter::{
+    opcode, CallInputs, CallScheme, CreateInputs, Gas, InstructionResult, Interpreter,
+};
+use crate::journaled_state::is_precompile;
+use crate::primitives::{Bytes, EVMError, ExecutionResult, HashSet, B160, B256, U256};
+use crate::{Database, EVMData, Inspector, EVM};
+use alloc::vec::Vec;
+
+/// Selector of `depositTo(address)`, the only EntryPoint method entities can call.
+pub const DEPOSIT_TO_SELECTOR: [u8; 4] = [0xb7, 0x60, 0xfa, 0xf9];
+
+/// Number of slots following the hash that are associated with an address.
+const ASSOCIATED_SLOTS: u64 = 128;
+
+/// Entity of the user operation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Entity {
+    Factory,
+    Account,
+    Paymaster,
+}
+
+/// Addresses taking part in validation of the user operation.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ValidationConfig {
+    pub entry_point: B160,
+    pub sender: B160,
+    pub factory: Option<B160>,
+    pub paymaster: Option<B160>,
+    /// Entities that have enough stake in the EntryPoint.
+    pub staked: HashSet<B160>,
+}
+
+impl ValidationConfig {
+    pub fn new(entry_point: B160, sender: B160) -> Self {
+        Self {
+            entry_point,
+            sender,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_factory(mut self, factory: B160) -> Self {
+        self.factory = Some(factory);
+        self
+    }
+
+    pub fn with_paymaster(mut self, paymaster: B160) -> Self {
+        self.paymaster = Some(paymaster);
+        self
+    }
+
+    /// Mark entity as staked.
+    pub fn with_stake(mut self, entity: B160) -> Self {
+        self.staked.insert(entity);
+        self
+    }
+
+    /// Entity with given address.
+    pub fn entity(&self, address: B160) -> Option<Entity> {
+        if address == self.sender {
+            Some(Entity::Account)
+        } else if Some(address) == self.factory {
+            Some(Entity::Factory)
+        } else if Some(address) == self.paymaster {
+            Some(Entity::Paymaster)
+        } else {
+            None
+        }
+    }
+
+    /// Address of the entity.
+    pub fn address(&self, entity: Entity) -> Option<B160> {
+        match entity {
+            Entity::Factory => self.factory,
+            Entity::Account => Some(self.sender),
+            Entity::Paymaster => self.paymaster,
+        }
+    }
+
+    pub fn is_staked(&self, entity: Entity) -> bool {
+        self.address(entity)
+            .is_some_and(|address| self.staked.contains(&address))
+    }
+}
+
+/// Broken validation rule.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Rule {
+    /// Opcode that is not allowed during validation was executed.
+    BannedOpcode(u8),
+    /// `GAS` was not immediately followed by a call.
+    GasNotFollowedByCall,
+    /// Call with value to other address than EntryPoint.
+    CallWithValue { to: B160 },
+    /// Call to EntryPoint other than `depositTo`.
+    EntryPointCall,
+    /// Call to address that has no code.
+    CallWithoutCode { to: B160 },
+    /// Frame ran out of gas.
+    OutOfGas,
+    /// Storage access that is not allowed even to staked entity.
+    StorageAccess {
+        address: B160,
+        slot: U256,
+        write: bool,
+    },
+    /// Storage access that is allowed only if entity is staked.
+    StakeRequired {
+        address: B160,
+        slot: U256,
+        write: bool,
+    },
+}
+
+/// Rule broken by the entity.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Violation {
+    pub entity: Entity,
+    /// Address whose code was executing.
+    pub address: B160,
+    pub pc: usize,
+    pub depth: u64,
+    pub rule: Rule,
+}
+
+/// Outcome of validation run by [validate_user_op].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ValidationReport {
+    /// Result of the transaction. EntryPoint reports validation result by reverting so it
+    /// is not checked.
+    pub result: ExecutionResult,
+    pub violations: Vec<Violation>,
+}
+
+impl ValidationReport {
+    pub fn is_valid(&self) -> bool {
+        self.violations.is_empty()
+    }
+
+    /// Entities that broke a rule that would not be broken if they were staked.
+    pub fn stake_required(&self) -> Vec<Entity> {
+        let mut entities = Vec::new();
+        for violation in &self.violations {
+            if matches!(violation.rule, Rule::StakeRequired { .. })
+                && !entities.contains(&violation.entity)
+            {
+                entities.push(violation.entity);
+            }
+        }
+        entities
+    }
+}
+
+/// Inspector checking ERC-7562 validation rules.
+#[derive(Clone, Debug)]
+pub struct ValidationInspector {
+    config: ValidationConfig,
+    /// Entity of every executing frame, None for EntryPoint and its callees.
+    frames: Vec<Option<Entity>>,
+    /// Entity address and hash of `KECCAK256` that is executing.
+    pending_hash: Option<B160>,
+    /// Addresses of entities and hashes computed with them as first word.
+    hashes: Vec<(B160, U256)>,
+    sender_created: bool,
+    violations: Vec<Violation>,
+}
+
+impl ValidationInspector {
+    pub fn new(config: ValidationConfig) -> Self {
+        Self {
+            config,
+            frames: Vec::new(),
+            pending_hash: None,
+            hashes: Vec::new(),
+            sender_created: false,
+            violations: Vec::new(),
+        }
+    }
+
+    pub fn config(&self) -> &ValidationConfig {
+        &self.config
+    }
+
+    pub fn violations(&self) -> &[Violation] {
+        &self.violations
+    }
+
+    pub fn into_violations(self) -> Vec<Violation> {
+        self.violations
+    }
+
+    /// Entity of the frame that is executing.
+    fn entity(&self) -> Option<Entity> {
+        self.frames.last().copied().flatten()
+    }
+
+    /// If slot is associated with the address.
+    pub fn is_associated(&self, address: B160, slot: U256) -> bool {
+        let word = U256::from_be_bytes(B256::from(address).0);
+        slot == word
+            || self.hashes.iter().any(|(owner, hash)| {
+                *owner == address && slot >= *hash && slot - *hash < U256::from(ASSOCIATED_SLOTS)
+            })
+    }
+
+    /// Check access of `entity` to `slot` of `address`.
+    fn check_storage(
+        &self,
+        entity: Entity,
+        address: B160,
+        slot: U256,
+        write: bool,
+    ) -> Option<Rule> {
+        let sender = self.config.sender;
+        if address == sender {
+            return None;
+        }
+        let staked = self.config.is_staked(entity);
+        if self.is_associated(sender, slot) {
+            // sender is not deployed while factory runs.
+            return (entity == Entity::Factory && !staked).then_some(Rule::StakeRequired {
+                address,
+                slot,
+                write,
+            });
+        }
+        let entity_address = self.config.address(entity);
+        let requires_stake = Some(address) == entity_address
+            || entity_address.is_some_and(|entity| self.is_associated(entity, slot))
+            || !write;
+        if !requires_stake {
+            Some(Rule::StorageAccess {
+                address,
+                slot,
+                write,
+            })
+        } else if !staked {
+            Some(Rule::StakeRequired {
+                address,
+                slot,
+                write,
+            })
+        } else {
+            None
+        }
+    }
+
+    fn check_opcode(&mut self, entity: Entity, interp: &Interpreter) -> Option<Rule> {
+        match interp.current_opcode() {
+            op @ (opcode::GASPRICE
+            | opcode::GASLIMIT
+            | opcode::DIFFICULTY
+            | opcode::TIMESTAMP
+            | opcode::BASEFEE
+            | opcode::BLOCKHASH
+            | opcode::NUMBER
+            | opcode::SELFBALANCE
+            | opcode::BALANCE
+            | opcode::ORIGIN
+            | opcode::COINBASE
+            | opcode::SELFDESTRUCT
+            | opcode::INVALID
+            | opcode::CREATE) => Some(Rule::BannedOpcode(op)),
+            opcode::CREATE2 => {
+                if entity == Entity::Factory && !self.sender_created {
+                    self.sender_created = true;
+                    None
+                } else {
+                    Some(Rule::BannedOpcode(opcode::CREATE2))
+                }
+            }
+            opcode::GAS => {
+                let next = interp
+                    .contract
+                    .bytecode
+                    .bytecode()
+                    .get(interp.program_counter() + 1)
+                    .copied();
+                (!matches!(
+                    next,
+                    Some(
+                        opcode::CALL | opcode::CALLCODE | opcode::DELEGATECALL | opcode::STATICCALL
+                    )
+                ))
+                .then_some(Rule::GasNotFollowedByCall)
+            }
+            op @ (opcode::SLOAD | opcode::SSTORE) => {
+                let slot = interp.stack.peek(0).ok()?;
+                self.check_storage(entity, interp.contract.address, slot, op == opcode::SSTORE)
+            }
+            _ => None,
+        }
+    }
+
+    /// Remember entity address hashed by `KECCAK256`.
+    fn record_hash_input(&mut self, interp: &Interpreter) {
+        let (Ok(offset), Ok(len)) = (interp.stack.peek(0), interp.stack.peek(1)) else {
+            return;
+        };
+        if len < U256::from(32) {
+            return;
+        }
+        // memory that is not yet expanded is zero and can't hold an address.
+        let Some(word) = usize::try_from(offset)
+            .ok()
+            .and_then(|offset| interp.memory.data().get(offset..offset.checked_add(32)?))
+        else {
+            return;
+        };
+        if word[..12].iter().any(|byte| *byte != 0) {
+            return;
+        }
+        let address = B160::from_slice(&word[12..]);
+        if self.config.entity(address).is_some() {
+            self.pending_hash = Some(address);
+        }
+    }
+
+    fn violation<DB: Database>(
+        &mut self,
+        entity: Entity,
+        address: B160,
+        pc: usize,
+        data: &EVMData<'_, DB>,
+        rule: Rule,
+    ) {
+        self.violations.push(Violation {
+            entity,
+            address,
+            pc,
+            depth: data.journaled_state.depth(),
+            rule,
+        });
+    }
+
+    fn check_call<DB: Database>(
+        &mut self,
+        data: &mut EVMData<'_, DB>,
+        inputs: &CallInputs,
+    ) -> Option<Rule> {
+        let to = inputs.contract;
+        if to == self.config.entry_point {
+            let selector = inputs.input.get(..4);
+            return (!inputs.input.is_empty() && selector != Some(&DEPOSIT_TO_SELECTOR[..]))
+                .then_some(Rule::EntryPointCall);
+        }
+        if inputs.context.scheme == CallScheme::Call && inputs.transfer.value != U256::ZERO {
+            return Some(Rule::CallWithValue { to });
+        }
+        if to == self.config.sender || is_precompile(to, data.journaled_state.num_of_precompiles) {
+            return None;
+        }
+        // account is already loaded by the call instruction, errors are left to the call.
+        let (account, _) = data.journaled_state.load_code(to, data.db).ok()?;
+        account
+            .info
+            .code
+            .as_ref()
+            .is_some_and(|code| code.is_empty())
+            .then_some(Rule::CallWithoutCode { to })
+    }
+}
+
+impl<DB: Database> Inspector<DB> for ValidationInspector {
+    fn step(&mut self, interp: &mut Interpreter, data: &mut EVMData<'_, DB>) -> InstructionResult {
+        let Some(entity) = self.entity() else {
+            return InstructionResult::Continue;
+        };
+        if interp.current_opcode() == opcode::KECCAK256 {
+            self.record_hash_input(interp);
+        }
+        if let Some(rule) = self.check_opcode(entity, interp) {
+            let (address, pc) = (interp.contract.address, interp.program_counter());
+            self.violation(entity, address, pc, data, rule);
+        }
+        InstructionResult::Continue
+    }
+
+    fn step_end(
+        &mut self,
+        interp: &mut Interpreter,
+        _data: &mut EVMData<'_, DB>,
+        _eval: InstructionResult,
+    ) -> InstructionResult {
+        if let Some(address) = self.pending_hash.take() {
+            if let Ok(hash) = interp.stack.peek(0) {
+                self.hashes.push((address, hash));
+            }
+        }
+        InstructionResult::Continue
+    }
+
+    fn call(
+        &mut self,
+        data: &mut EVMData<'_, DB>,
+        inputs: &mut CallInputs,
+    ) -> (InstructionResult, Gas, Bytes) {
+        if let Some(entity) = self.entity() {
+            if let Some(rule) = self.check_call(data, inputs) {
+                // pc is already past the call instruction.
+                self.violation(entity, inputs.context.caller, 0, data, rule);
+            }
+        }
+        let address = inputs.context.address;
+        let entity = if address == self.config.entry_point {
+            None
+        } else {
+            self.config.entity(address).or(self.entity())
+        };
+        self.frames.push(entity);
+        (InstructionResult::Continue, Gas::new(0), Bytes::new())
+    }
+
+    fn call_end(
+        &mut self,
+        data: &mut EVMData<'_, DB>,
+        inputs: &CallInputs,
+        remaining_gas: Gas,
+        ret: InstructionResult,
+        out: Bytes,
+    ) -> (InstructionResult, Gas, Bytes) {
+        if let Some(Some(entity)) = self.frames.pop() {
+            if is_out_of_gas(ret) {
+                self.violation(entity, inputs.context.address, 0, data, Rule::OutOfGas);
+            }
+        }
+        (ret, remaining_gas, out)
+    }
+
+    fn create(
+        &mut self,
+        _data: &mut EVMData<'_, DB>,
+        _inputs: &mut CreateInputs,
+    ) -> (InstructionResult, Option<B160>, Gas, Bytes) {
+        self.frames.push(self.entity());
+        (InstructionResult::Continue, None, Gas::new(0), Bytes::new())
+    }
+
+    fn create_end(
+        &mut self,
+        data: &mut EVMData<'_, DB>,
+        inputs: &CreateInputs,
+        ret: InstructionResult,
+        address: Option<B160>,
+        remaining_gas: Gas,
+        out: Bytes,
+    ) -> (InstructionResult, Option<B160>, Gas, Bytes) {
+        if let Some(Some(entity)) = self.frames.pop() {
+            if is_out_of_gas(ret) {
+                let executing = address.unwrap_or(inputs.caller);
+                self.violation(entity, executing, 0, data, Rule::OutOfGas);
+            }
+        }
+        (ret, address, remaining_gas, out)
+    }
+}
+
+fn is_out_of_gas(ret: InstructionResult) -> bool {
+    matches!(
+        ret,
+        InstructionResult::OutOfGas
+            | InstructionResult::MemoryOOG
+            | InstructionResult::MemoryLimitOOG
+            | InstructionResult::PrecompileOOG
+            | InstructionResult::InvalidOperandOOG
+    )
+}
+
+/// Run transaction set in `evm` as validation of user operation described by `config`.
+///
+/// State is not committed.
+pub fn validate_user_op<DB: Database>(
+    evm: &mut EVM<DB>,
+    config: ValidationConfig,
+) -> Result<ValidationReport, EVMError<DB::Error>> {
+    let mut inspector = ValidationInspector::new(config);
+    let result = evm.inspect(&mut inspector)?.result;
+    Ok(ValidationReport {
+        result,
+        violations: inspector.into_violations(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primitives::{hex_literal::hex, AccountInfo, Bytecode, TransactTo};
+    use crate::InMemoryDB;
+
+    fn contract(db: &mut InMemoryDB, address: B160, code: Vec<u8>) {
+        db.insert_account_info(
+            address,
+            AccountInfo::new(U256::ZERO, 0, Bytecode::new_raw(code.into())),
+        );
+    }
+
+    #[test]
+    fn reports_violations_of_account() {
+        let entry_point = B160([0x10; 20]);
+        let sender = B160([0x20; 20]);
+        let other = B160([0x30; 20]);
+
+        // TIMESTAMP POP, SLOAD(0) POP, CALL(GAS, other, 0, 0, 0, 0, 0) POP STOP
+        let mut code = hex!("42506000545060006000600060006000").to_vec();
+        code.push(0x73);
+        code.extend_from_slice(&other.0);
+        code.extend_from_slice(&hex!("5af15000"));
+        let mut db = InMemoryDB::default();
+        contract(&mut db, sender, code);
+        // MSTORE(0, sender) SLOAD(KECCAK256(0, 64)) POP, SLOAD(1) POP STOP
+        let mut code = vec![0x73];
+        code.extend_from_slice(&sender.0);
+        code.extend_from_slice(&hex!("600052604060002054506001545000"));
+        contract(&mut db, other, code);
+
+        let mut evm = crate::new();
+        evm.database(db);
+        evm.env.tx.caller = entry_point;
+        evm.env.tx.transact_to = TransactTo::Call(sender);
+
+        let report =
+            validate_user_op(&mut evm, ValidationConfig::new(entry_point, sender)).unwrap();
+        assert!(report.result.is_success());
+        let rules: Vec<_> = report.violations.iter().map(|v| &v.rule).collect();
+        // own storage and storage associated with sender are allowed.
+        assert_eq!(
+            rules,
+            [
+                &Rule::BannedOpcode(opcode::TIMESTAMP),
+                &Rule::StakeRequired {
+                    address: other,
+                    slot: U256::from(1),
+                    write: false
+                }
+            ]
+        );
+        assert_eq!(report.violations[0].pc, 0);
+        assert_eq!(report.violations[1].address, other);
+        assert_eq!(report.violations[1].depth, 2);
+        assert_eq!(report.stake_required(), [Entity::Account]);
+
+        let config = ValidationConfig::new(entry_point, sender).with_stake(sender);
+        let report = validate_user_op(&mut evm, config).unwrap();
+        assert_eq!(report.violations.len(), 1);
+        assert!(report.stake_required().is_empty());
+    }
+}
@@ -0,0 +1,271 @@
+//! Gas estimation, same as `eth_estimateGas` of geth.
+//!
+//! Transaction is executed with the highest gas limit it can have: its own, capped by the
+//! block gas limit and by what the caller can pay for. Gas it used there is the lower bound,
+//! gas limit with allowance for gas kept by the 63/64 rule is tried next and the rest is
+//! binary searched down to the lowest gas limit the transaction succeeds with. Changes of
+//! the executions are discarded, database is not changed.
+use crate::interpreter::gas::CALL_STIPEND;
+use crate::primitives::{
+    AccountInfo, EVMError, ExecutionResult, InvalidTransaction, TransactTo, TxEnv, B160,
+    KECCAK_EMPTY, U256,
+};
+use crate::{Database, EVM};
+
+/// Gas of a transfer to an account without code.
+const TRANSFER_GAS: u64 = 21_000;
+
+/// Result of [estimate_gas].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GasEstimate {
+    /// Lowest gas limit the transaction succeeds with.
+    pub gas_limit: u64,
+    /// Result of the transaction with the estimated gas limit.
+    pub result: ExecutionResult,
+    /// Number of times the transaction was executed.
+    pub executions: u32,
+}
+
+/// Error of [estimate_gas].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EstimateGasError<DBError> {
+    /// Transaction is invalid or database failed.
+    Evm(EVMError<DBError>),
+    /// Transaction reverted or halted with the highest gas limit it can have, out of gas halt
+    /// means that the cap is too low.
+    Failed {
+        gas_limit: u64,
+        result: ExecutionResult,
+    },
+}
+
+impl<DBError> From<EVMError<DBError>> for EstimateGasError<DBError> {
+    fn from(error: EVMError<DBError>) -> Self {
+        Self::Evm(error)
+    }
+}
+
+/// Estimate gas limit of `tx`, see the [module](self) docs. Transaction env of `evm` is set
+/// to `tx` with the estimated gas limit.
+pub fn estimate_gas<DB: Database>(
+    evm: &mut EVM<DB>,
+    tx: TxEnv,
+) -> Result<GasEstimate, EstimateGasError<DB::Error>> {
+    evm.env.tx = tx;
+    evm.estimate_gas()
+}
+
+impl<DB: Database> EVM<DB> {
+    /// Estimate gas limit of the transaction of the environment and set it, see
+    /// [estimate_gas].
+    pub fn estimate_gas(&mut self) -> Result<GasEstimate, EstimateGasError<DB::Error>> {
+        let mut search = Search {
+            evm: self,
+            executions: 0,
+        };
+        let estimate = search.run()?;
+        self.env.tx.gas_limit = estimate.gas_limit;
+        Ok(estimate)
+    }
+}
+
+struct Search<'a, DB: Database> {
+    evm: &'a mut EVM<DB>,
+    executions: u32,
+}
+
+impl<DB: Database> Search<'_, DB> {
+    fn run(&mut self) -> Result<GasEstimate, EstimateGasError<DB::Error>> {
+        let env = &self.evm.env;
+        let block_gas_limit = u64::try_from(env.block.gas_limit).unwrap_or(u64::MAX);
+        let mut hi = env.tx.gas_limit.min(block_gas_limit);
+        let (caller, gas_price, value) = (env.tx.caller, env.tx.gas_price, env.tx.value);
+        let transfer_to = match env.tx.transact_to {
+            TransactTo::Call(to) if env.tx.data.is_empty() && env.tx.access_list.is_empty() => {
+                Some(to)
+            }
+            _ => None,
+        };
+        if gas_price != U256::ZERO {
+            let balance = self.basic(caller)?.map_or(U256::ZERO, |info| info.balance);
+            let allowance = balance.saturating_sub(value) / gas_price;
+            hi = hi.min(u64::try_from(allowance).unwrap_or(u64::MAX));
+        }
+
+        // transfers to accounts without code need no search.
+        if let Some(to) = transfer_to {
+            let has_code = self
+                .basic(to)?
+                .is_some_and(|info| info.code_hash != KECCAK_EMPTY);
+            if !has_code && hi >= TRANSFER_GAS {
+                if let Some(result) = self.execute(TRANSFER_GAS)? {
+                    if result.is_success() {
+                        return Ok(self.estimate(TRANSFER_GAS, result));
+                    }
+                }
+            }
+        }
+
+        let result = match self.execute(hi)? {
+            Some(result) if result.is_success() => result,
+            Some(result) => {
+                return Err(EstimateGasError::Failed {
+                    gas_limit: hi,
+                    result,
+                })
+            }
+            None => {
+                return Err(
+                    EVMError::Transaction(InvalidTransaction::CallGasCostMoreThanGasLimit).into(),
+                )
+            }
+        };
+        // gas used is after refunds, transaction fails with any gas limit below it.
+        let mut lo = result.gas_used().saturating_sub(1);
+        let refunded = match &result {
+            ExecutionResult::Success { gas_refunded, .. } => *gas_refunded,
+            _ => 0,
+        };
+        let mut best = result;
+
+        // most transactions succeed with gas they used and what 63/64 rule kept from them.
+        let optimistic = (best.gas_used() + refunded + CALL_STIPEND) * 64 / 63;
+        if optimistic < hi {
+            match self.execute(optimistic)? {
+                Some(result) if result.is_success() => {
+                    hi = optimistic;
+                    best = result;
+                }
+                _ => lo = optimistic,
+            }
+        }
+
+        while lo + 1 < hi {
+            // lower half is searched first, gas used is usually close to the lower bound.
+            let mid = ((hi + lo) / 2).min(lo.saturating_mul(2).max(lo + 1));
+            match self.execute(mid)? {
+                Some(result) if result.is_success() => {
+                    hi = mid;
+                    best = result;
+                }
+                _ => lo = mid,
+            }
+        }
+        Ok(self.estimate(hi, best))
+    }
+
+    /// Result of the transaction with `gas_limit`, None if intrinsic gas is above it.
+    fn execute(
+        &mut self,
+        gas_limit: u64,
+    ) -> Result<Option<ExecutionResult>, EstimateGasError<DB::Error>> {
+        self.executions += 1;
+        self.evm.env.tx.gas_limit = gas_limit;
+        match self.evm.transact() {
+            Ok(out) => Ok(Some(out.result)),
+            Err(EVMError::Transaction(InvalidTransaction::CallGasCostMoreThanGasLimit)) => Ok(None),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    fn basic(&mut self, address: B160) -> Result<Option<AccountInfo>, EstimateGasError<DB::Error>> {
+        let db = self.evm.db().expect("Database needs to be set");
+        db.basic(address)
+            .map_err(|error| EVMError::Database(error).into())
+    }
+
+    fn estimate(&self, gas_limit: u64, result: ExecutionResult) -> GasEstimate {
+        GasEstimate {
+            gas_limit,
+            result,
+            executions: self.executions,
+        }
+    }
+}
+
+#[cfg(all(test, not(feature = "no_gas_measuring")))]
+mod tests {
+    use super::*;
+    use crate::primitives::{hex_literal::hex, Bytecode, Halt};
+    use crate::InMemoryDB;
+
+    const CALLER: B160 = B160([0x10; 20]);
+    const CONTRACT: B160 = B160([0x20; 20]);
+    const FORWARDER: B160 = B160([0x30; 20]);
+
+    fn evm() -> EVM<InMemoryDB> {
+        let mut db = InMemoryDB::default();
+        db.insert_account_info(CALLER, AccountInfo::from_balance(U256::from(10u64.pow(18))));
+        // SSTORE(1, 1) REVERT if CALLDATASIZE is not zero.
+        let code = hex!("60016001553615600f5760006000fd5b00");
+        db.insert_account_info(
+            CONTRACT,
+            AccountInfo::new(U256::ZERO, 0, Bytecode::new_raw(code.to_vec().into())),
+        );
+        // POP(CALL(GAS, contract, 0, 0, 0, 0, 0)) but fail if call failed.
+        let mut code = hex!("600060006000600060007f").to_vec();
+        code.extend_from_slice(&[0; 12]);
+        code.extend_from_slice(&CONTRACT.0);
+        code.extend_from_slice(&hex!("5af1603157fe5b00"));
+        db.insert_account_info(
+            FORWARDER,
+            AccountInfo::new(U256::ZERO, 0, Bytecode::new_raw(code.into())),
+        );
+        let mut evm = EVM::new();
+        evm.database(db);
+        evm.env.block.gas_limit = U256::from(30_000_000);
+        evm
+    }
+
+    fn tx(to: B160) -> TxEnv {
+        TxEnv {
+            caller: CALLER,
+            transact_to: TransactTo::Call(to),
+            gas_price: U256::from(1),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn estimates_lowest_gas_limit() {
+        let mut evm = evm();
+        let estimate = estimate_gas(&mut evm, tx(FORWARDER)).unwrap();
+        assert!(estimate.result.is_success());
+        assert_eq!(evm.env.tx.gas_limit, estimate.gas_limit);
+        // the call needs more than the gas used because of the 63/64 rule.
+        assert!(estimate.gas_limit > estimate.result.gas_used());
+        evm.env.tx.gas_limit = estimate.gas_limit - 1;
+        assert!(!evm.transact().unwrap().result.is_success());
+
+        let estimate = estimate_gas(&mut evm, tx(B160([0x40; 20]))).unwrap();
+        assert_eq!((estimate.gas_limit, estimate.executions), (TRANSFER_GAS, 1));
+    }
+
+    #[test]
+    fn reports_failure() {
+        let mut evm = evm();
+        let mut reverting = tx(CONTRACT);
+        reverting.data = hex!("01").to_vec().into();
+        assert!(matches!(
+            estimate_gas(&mut evm, reverting),
+            Err(EstimateGasError::Failed {
+                gas_limit: 30_000_000,
+                result: ExecutionResult::Revert { .. }
+            })
+        ));
+
+        // caller can pay for 30_000 gas only.
+        let mut capped = tx(CONTRACT);
+        capped.gas_price = U256::from(10u64.pow(18) / 30_000);
+        assert!(matches!(
+            estimate_gas(&mut evm, capped),
+            Err(EstimateGasError::Failed {
+                gas_limit: 30_000,
+                result: ExecutionResult::Halt {
+                    reason: Halt::OutOfGas(_),
+                    ..
+                }
+            })
+        ));
+    }
+}
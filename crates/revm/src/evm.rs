@@ -1,10 +1,16 @@
-use crate::primitives::{specification, EVMError, EVMResult, Env, ExecutionResult, SpecId};
+use crate::primitives::{
+    specification, CfgEnv, CustomPrecompiles, EVMError, EVMResult, Env, ExecutionResult, SpecId,
+    TxEnv,
+};
 use crate::{
     db::{Database, DatabaseCommit, DatabaseRef, RefDBWrapper},
-    evm_impl::{EVMImpl, Transact},
+    evm_impl::{Driver, EVMImpl, Transact},
+    handler::Handler,
     inspectors::NoOpInspector,
+    interpreter::InstructionTable,
     Inspector,
 };
+use alloc::borrow::Cow;
 use alloc::boxed::Box;
 use revm_interpreter::primitives::ResultAndState;
 use revm_precompile::Precompiles;
@@ -20,15 +26,62 @@ use revm_precompile::Precompiles;
 /// Database, DatabaseRef or Database+DatabaseCommit and they enable functionality depending on what kind of
 /// handling of struct you want.
 /// * Database trait has mutable self in its functions. It is usefully if on get calls you want to modify
-/// your cache or update some statistics. They enable `transact` and `inspect` functions
+///   your cache or update some statistics. They enable `transact` and `inspect` functions
 /// * DatabaseRef takes reference on object, this is useful if you only have reference on state and dont
-/// want to update anything on it. It enabled `transact_ref` and `inspect_ref` functions
+///   want to update anything on it. It enabled `transact_ref` and `inspect_ref` functions
 /// * Database+DatabaseCommit allow directly committing changes of transaction. it enabled `transact_commit`
-/// and `inspect_commit`
+///   and `inspect_commit`
 #[derive(Clone)]
 pub struct EVM<DB> {
     pub env: Env,
     pub db: Option<DB>,
+    /// Handlers replacing built-in instructions, see [EVM::with_instruction_table].
+    pub(crate) instruction_table: Option<InstructionTable>,
+    /// Stages replacing the built-in ones, see [EVM::with_handler].
+    pub(crate) handler: Handler,
+    /// Precompiles with custom ones of the last transaction, reused while spec and custom
+    /// precompiles stay the same.
+    precompiles: Option<PrecompilesCache>,
+}
+
+#[derive(Clone)]
+struct PrecompilesCache {
+    spec_id: revm_precompile::SpecId,
+    custom: CustomPrecompiles,
+    precompiles: Precompiles,
+}
+
+/// Precompiles of `cfg` from `cache`, it is updated if spec or custom precompiles changed.
+fn cached_precompiles<'a>(
+    cache: &'a mut Option<PrecompilesCache>,
+    cfg: &CfgEnv,
+) -> Cow<'a, Precompiles> {
+    let spec_id = to_precompile_id(cfg.spec_id);
+    if cfg.custom_precompiles.is_empty() {
+        return Cow::Borrowed(Precompiles::new(spec_id));
+    }
+    let is_valid = cache
+        .as_ref()
+        .is_some_and(|cache| cache.spec_id == spec_id && cache.custom == cfg.custom_precompiles);
+    if !is_valid {
+        *cache = Some(PrecompilesCache {
+            spec_id,
+            custom: cfg.custom_precompiles.clone(),
+            precompiles: precompiles(cfg).into_owned(),
+        });
+    }
+    Cow::Borrowed(&cache.as_ref().unwrap().precompiles)
+}
+
+/// Precompiles of the spec with custom precompiles of `cfg`.
+fn precompiles(cfg: &CfgEnv) -> Cow<'static, Precompiles> {
+    let standard = Precompiles::new(to_precompile_id(cfg.spec_id));
+    if cfg.custom_precompiles.is_empty() {
+        return Cow::Borrowed(standard);
+    }
+    let mut precompiles = standard.clone();
+    precompiles.extend_custom(&cfg.custom_precompiles);
+    Cow::Owned(precompiles)
 }
 
 pub fn new<DB>() -> EVM<DB> {
@@ -44,7 +97,7 @@ impl<DB> Default for EVM<DB> {
 impl<DB: Database + DatabaseCommit> EVM<DB> {
     /// Execute transaction and apply result to database
     pub fn transact_commit(&mut self) -> Result<ExecutionResult, EVMError<DB::Error>> {
-        let ResultAndState { result, state } = self.transact()?;
+        let ResultAndState { result, state, .. } = self.transact()?;
         self.db.as_mut().unwrap().commit(state);
         Ok(result)
     }
@@ -53,10 +106,19 @@ impl<DB: Database + DatabaseCommit> EVM<DB> {
         &mut self,
         inspector: INSP,
     ) -> Result<ExecutionResult, EVMError<DB::Error>> {
-        let ResultAndState { result, state } = self.inspect(inspector)?;
+        let ResultAndState { result, state, .. } = self.inspect(inspector)?;
         self.db.as_mut().unwrap().commit(state);
         Ok(result)
     }
+
+    /// Execute `tx` and apply result to database, see [EVM::transact_with].
+    pub fn transact_commit_with(
+        &mut self,
+        tx: TxEnv,
+    ) -> Result<ExecutionResult, EVMError<DB::Error>> {
+        self.env.tx = tx;
+        self.transact_commit()
+    }
 }
 
 impl<DB: Database> EVM<DB> {
@@ -64,7 +126,17 @@ impl<DB: Database> EVM<DB> {
     pub fn transact(&mut self) -> EVMResult<DB::Error> {
         if let Some(db) = self.db.as_mut() {
             let mut noop = NoOpInspector {};
-            let out = evm_inner::<DB, false>(&mut self.env, db, &mut noop).transact();
+            let table = self.instruction_table.as_ref();
+            let precompiles = cached_precompiles(&mut self.precompiles, &self.env.cfg);
+            let out = evm_inner_with_precompiles::<DB, false>(
+                &mut self.env,
+                db,
+                &mut noop,
+                table,
+                self.handler,
+                precompiles,
+            )
+            .transact();
             out
         } else {
             panic!("Database needs to be set");
@@ -74,11 +146,61 @@ impl<DB: Database> EVM<DB> {
     /// Execute transaction with given inspector, without wring to DB. Return change state.
     pub fn inspect<INSP: Inspector<DB>>(&mut self, mut inspector: INSP) -> EVMResult<DB::Error> {
         if let Some(db) = self.db.as_mut() {
-            evm_inner::<DB, true>(&mut self.env, db, &mut inspector).transact()
+            let table = self.instruction_table.as_ref();
+            let precompiles = cached_precompiles(&mut self.precompiles, &self.env.cfg);
+            evm_inner_with_precompiles::<DB, true>(
+                &mut self.env,
+                db,
+                &mut inspector,
+                table,
+                self.handler,
+                precompiles,
+            )
+            .transact()
         } else {
             panic!("Database needs to be set");
         }
     }
+
+    /// Execute `tx` without writing to DB, it replaces transaction of the environment.
+    ///
+    /// Config and block of the environment, database and instruction table are kept, and
+    /// precompiles are prepared only when spec or custom precompiles change, so one EVM can
+    /// execute many transactions. Block can be replaced through `env.block` in between.
+    pub fn transact_with(&mut self, tx: TxEnv) -> EVMResult<DB::Error> {
+        self.env.tx = tx;
+        self.transact()
+    }
+
+    /// Execute `tx` with given inspector without writing to DB, see [EVM::transact_with].
+    pub fn inspect_with<INSP: Inspector<DB>>(
+        &mut self,
+        tx: TxEnv,
+        inspector: INSP,
+    ) -> EVMResult<DB::Error> {
+        self.env.tx = tx;
+        self.inspect(inspector)
+    }
+}
+
+impl<DB: Database> EVM<DB> {
+    /// Driver of the transaction executing on the caller's thread, see [crate::frame].
+    pub(crate) fn driver<'a, const INSPECT: bool>(
+        &'a mut self,
+        inspector: &'a mut dyn Inspector<DB>,
+    ) -> Box<dyn Driver<DB::Error> + 'a> {
+        let db = self.db.as_mut().expect("Database needs to be set");
+        let table = self.instruction_table.as_ref();
+        let precompiles = cached_precompiles(&mut self.precompiles, &self.env.cfg);
+        evm_driver_with_precompiles::<DB, INSPECT>(
+            &mut self.env,
+            db,
+            inspector,
+            table,
+            self.handler,
+            precompiles,
+        )
+    }
 }
 
 impl<'a, DB: DatabaseRef> EVM<DB> {
@@ -88,9 +210,17 @@ impl<'a, DB: DatabaseRef> EVM<DB> {
             let mut noop = NoOpInspector {};
             let mut db = RefDBWrapper::new(db);
             let db = &mut db;
-            let out =
-                evm_inner::<RefDBWrapper<DB::Error>, false>(&mut self.env.clone(), db, &mut noop)
-                    .transact();
+            let mut env = self.env.clone();
+            let precompiles = precompiles(&env.cfg);
+            let out = evm_inner_with_precompiles::<RefDBWrapper<DB::Error>, false>(
+                &mut env,
+                db,
+                &mut noop,
+                self.instruction_table.as_ref(),
+                self.handler,
+                precompiles,
+            )
+            .transact();
             out
         } else {
             panic!("Database needs to be set");
@@ -105,10 +235,15 @@ impl<'a, DB: DatabaseRef> EVM<DB> {
         if let Some(db) = self.db.as_ref() {
             let mut db = RefDBWrapper::new(db);
             let db = &mut db;
-            let out = evm_inner::<RefDBWrapper<DB::Error>, true>(
-                &mut self.env.clone(),
+            let mut env = self.env.clone();
+            let precompiles = precompiles(&env.cfg);
+            let out = evm_inner_with_precompiles::<RefDBWrapper<DB::Error>, true>(
+                &mut env,
                 db,
                 &mut inspector,
+                self.instruction_table.as_ref(),
+                self.handler,
+                precompiles,
             )
             .transact();
             out
@@ -126,7 +261,34 @@ impl<DB> EVM<DB> {
 
     /// Creates a new [EVM] instance with the given environment.
     pub fn with_env(env: Env) -> Self {
-        Self { env, db: None }
+        Self {
+            env,
+            db: None,
+            instruction_table: None,
+            handler: Handler::new(),
+            precompiles: None,
+        }
+    }
+
+    /// Execute with handlers of `table` replacing the built-in instructions.
+    pub fn with_instruction_table(mut self, table: InstructionTable) -> Self {
+        self.instruction_table = Some(table);
+        self
+    }
+
+    pub fn instruction_table(&self) -> Option<&InstructionTable> {
+        self.instruction_table.as_ref()
+    }
+
+    /// Execute transactions with stages of `handler` replacing the built-in ones, see
+    /// [crate::handler].
+    pub fn with_handler(mut self, handler: Handler) -> Self {
+        self.handler = handler;
+        self
+    }
+
+    pub fn handler(&self) -> &Handler {
+        &self.handler
     }
 
     pub fn database(&mut self, db: DB) {
@@ -143,16 +305,43 @@ impl<DB> EVM<DB> {
 }
 
 macro_rules! create_evm {
-    ($spec:ident, $db:ident,$env:ident,$inspector:ident) => {
-        Box::new(EVMImpl::<'a, $spec, DB, INSPECT>::new(
-            $db,
-            $env,
-            $inspector,
-            Precompiles::new(to_precompile_id($spec::SPEC_ID)).clone(),
-        )) as Box<dyn Transact<DB::Error> + 'a>
+    ($spec:ident, $trait:ident, ($db:ident, $env:ident, $inspector:ident, $table:ident, $handler:ident, $precompiles:ident)) => {
+        Box::new(
+            EVMImpl::<'a, $spec, DB, INSPECT>::new($db, $env, $inspector, $precompiles)
+                .with_instruction_table($table)
+                .with_handler($handler),
+        ) as Box<dyn $trait<DB::Error> + 'a>
     };
 }
 
+/// EVM of the spec of `env` as `$trait` object, `$args` are the arguments of `create_evm!`.
+macro_rules! create_evm_of_spec {
+    ($trait:ident, $env:ident, $args:tt) => {{
+        use specification::*;
+        match $env.cfg.spec_id {
+            SpecId::FRONTIER | SpecId::FRONTIER_THAWING => create_evm!(FrontierSpec, $trait, $args),
+            SpecId::HOMESTEAD | SpecId::DAO_FORK => create_evm!(HomesteadSpec, $trait, $args),
+            SpecId::TANGERINE => create_evm!(TangerineSpec, $trait, $args),
+            SpecId::SPURIOUS_DRAGON => create_evm!(SpuriousDragonSpec, $trait, $args),
+            SpecId::BYZANTIUM => create_evm!(ByzantiumSpec, $trait, $args),
+            SpecId::PETERSBURG | SpecId::CONSTANTINOPLE => {
+                create_evm!(PetersburgSpec, $trait, $args)
+            }
+            SpecId::ISTANBUL | SpecId::MUIR_GLACIER => create_evm!(IstanbulSpec, $trait, $args),
+            SpecId::BERLIN => create_evm!(BerlinSpec, $trait, $args),
+            SpecId::LONDON | SpecId::ARROW_GLACIER | SpecId::GRAY_GLACIER => {
+                create_evm!(LondonSpec, $trait, $args)
+            }
+            SpecId::MERGE => create_evm!(MergeSpec, $trait, $args),
+            SpecId::SHANGHAI => create_evm!(ShanghaiSpec, $trait, $args),
+            SpecId::CANCUN => create_evm!(CancunSpec, $trait, $args),
+            SpecId::PRAGUE => create_evm!(PragueSpec, $trait, $args),
+            SpecId::LATEST => create_evm!(LatestSpec, $trait, $args),
+            SpecId::OSAKA => create_evm!(OsakaSpec, $trait, $args),
+        }
+    }};
+}
+
 pub fn to_precompile_id(spec_id: SpecId) -> revm_precompile::SpecId {
     match spec_id {
         SpecId::FRONTIER
@@ -170,9 +359,9 @@ pub fn to_precompile_id(spec_id: SpecId) -> revm_precompile::SpecId {
         | SpecId::ARROW_GLACIER
         | SpecId::GRAY_GLACIER
         | SpecId::MERGE
-        | SpecId::SHANGHAI
-        | SpecId::CANCUN
-        | SpecId::LATEST => revm_precompile::SpecId::BERLIN,
+        | SpecId::SHANGHAI => revm_precompile::SpecId::BERLIN,
+        SpecId::CANCUN | SpecId::PRAGUE => revm_precompile::SpecId::CANCUN,
+        SpecId::LATEST | SpecId::OSAKA => revm_precompile::SpecId::LATEST,
     }
 }
 
@@ -181,22 +370,52 @@ pub fn evm_inner<'a, DB: Database, const INSPECT: bool>(
     db: &'a mut DB,
     insp: &'a mut dyn Inspector<DB>,
 ) -> Box<dyn Transact<DB::Error> + 'a> {
-    use specification::*;
-    match env.cfg.spec_id {
-        SpecId::FRONTIER | SpecId::FRONTIER_THAWING => create_evm!(FrontierSpec, db, env, insp),
-        SpecId::HOMESTEAD | SpecId::DAO_FORK => create_evm!(HomesteadSpec, db, env, insp),
-        SpecId::TANGERINE => create_evm!(TangerineSpec, db, env, insp),
-        SpecId::SPURIOUS_DRAGON => create_evm!(SpuriousDragonSpec, db, env, insp),
-        SpecId::BYZANTIUM => create_evm!(ByzantiumSpec, db, env, insp),
-        SpecId::PETERSBURG | SpecId::CONSTANTINOPLE => create_evm!(PetersburgSpec, db, env, insp),
-        SpecId::ISTANBUL | SpecId::MUIR_GLACIER => create_evm!(IstanbulSpec, db, env, insp),
-        SpecId::BERLIN => create_evm!(BerlinSpec, db, env, insp),
-        SpecId::LONDON | SpecId::ARROW_GLACIER | SpecId::GRAY_GLACIER => {
-            create_evm!(LondonSpec, db, env, insp)
-        }
-        SpecId::MERGE => create_evm!(MergeSpec, db, env, insp),
-        SpecId::SHANGHAI => create_evm!(ShanghaiSpec, db, env, insp),
-        SpecId::CANCUN => create_evm!(LatestSpec, db, env, insp),
-        SpecId::LATEST => create_evm!(LatestSpec, db, env, insp),
-    }
+    evm_inner_with_table::<DB, INSPECT>(env, db, insp, None)
+}
+
+/// Same as [evm_inner], handlers of `table` replace the built-in instructions.
+pub fn evm_inner_with_table<'a, DB: Database, const INSPECT: bool>(
+    env: &'a mut Env,
+    db: &'a mut DB,
+    insp: &'a mut dyn Inspector<DB>,
+    table: Option<&'a InstructionTable>,
+) -> Box<dyn Transact<DB::Error> + 'a> {
+    let precompiles = precompiles(&env.cfg);
+    evm_inner_with_precompiles::<DB, INSPECT>(env, db, insp, table, Handler::new(), precompiles)
+}
+
+/// Same as [evm_inner_with_table], stages of `handler` replace the built-in ones and
+/// `precompiles` have custom precompiles of `env` already.
+fn evm_inner_with_precompiles<'a, DB: Database, const INSPECT: bool>(
+    env: &'a mut Env,
+    db: &'a mut DB,
+    insp: &'a mut dyn Inspector<DB>,
+    table: Option<&'a InstructionTable>,
+    handler: Handler,
+    precompiles: Cow<'a, Precompiles>,
+) -> Box<dyn Transact<DB::Error> + 'a> {
+    create_evm_of_spec!(Transact, env, (db, env, insp, table, handler, precompiles))
+}
+
+/// Same as [evm_inner], the transaction is driven frame by frame.
+#[cfg_attr(not(feature = "async"), allow(dead_code))]
+pub(crate) fn evm_driver<'a, DB: Database, const INSPECT: bool>(
+    env: &'a mut Env,
+    db: &'a mut DB,
+    insp: &'a mut dyn Inspector<DB>,
+) -> Box<dyn Driver<DB::Error> + 'a> {
+    let precompiles = precompiles(&env.cfg);
+    evm_driver_with_precompiles::<DB, INSPECT>(env, db, insp, None, Handler::new(), precompiles)
+}
+
+/// Same as [evm_inner_with_precompiles], the transaction is driven frame by frame.
+fn evm_driver_with_precompiles<'a, DB: Database, const INSPECT: bool>(
+    env: &'a mut Env,
+    db: &'a mut DB,
+    insp: &'a mut dyn Inspector<DB>,
+    table: Option<&'a InstructionTable>,
+    handler: Handler,
+    precompiles: Cow<'a, Precompiles>,
+) -> Box<dyn Driver<DB::Error> + 'a> {
+    create_evm_of_spec!(Driver, env, (db, env, insp, table, handler, precompiles))
 }
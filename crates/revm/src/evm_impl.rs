@@ -1,20 +1,27 @@
+use crate::evm::to_precompile_id;
+use crate::frame::{Execution, Frame, FrameOutput, Next, PauseAt, StepSnapshot, TxGas};
+use crate::handler::Handler;
+use crate::instrument::phase;
 use crate::interpreter::{
-    analysis::to_analysed, gas, instruction_result::SuccessOrHalt, return_ok, return_revert,
-    CallContext, CallInputs, CallScheme, Contract, CreateInputs, CreateScheme, Gas, Host,
-    InstructionResult, Interpreter, SelfDestructResult, Transfer, CALL_STACK_LIMIT,
+    analysis::to_analysed, decode_valid_eof, gas, instruction_result::SuccessOrHalt, opcode,
+    return_ok, return_revert, CallContext, CallInputs, CallScheme, Contract, CreateInputs, Gas,
+    Host, InstructionResult, InstructionTable, Interpreter, InterpreterAction, SelfDestructResult,
+    Transfer, CALL_STACK_LIMIT,
 };
-use crate::journaled_state::{is_precompile, JournalCheckpoint};
+use crate::journaled_state::JournalCheckpoint;
 use crate::primitives::{
-    create2_address, create_address, keccak256, Account, AnalysisKind, Bytecode, Bytes, EVMError,
-    EVMResult, Env, ExecutionResult, HashMap, InvalidTransaction, Log, Output, ResultAndState,
-    Spec,
-    SpecId::{self, *},
-    TransactTo, B160, B256, U256,
+    Account, AccountChange, AddressFilterAction, AnalysisKind, BlockHashLookup, Bytecode, Bytes,
+    CfgEnv, EVMError, EVMResult, Env, EnvFrame, EnvValue, Eof, ExecutionResult, GasFrame,
+    HaltContext, HashMap, InvalidTransaction, Log, Output, PrecompileContext, PrecompileError,
+    PrecompileState, ResultAndState, Spec, SpecId::*, StorageDiff, TouchedAccounts, TransactTo,
+    B160, B256, U256,
 };
-use crate::{db::Database, journaled_state::JournaledState, precompile, Inspector};
+use crate::sandbox::SandboxCall;
+use crate::{db::Database, journaled_state::JournaledState, precompile, Inspector, StorageWrite};
+use alloc::borrow::Cow;
 use alloc::boxed::Box;
-use alloc::vec::Vec;
-use core::{cmp::min, marker::PhantomData};
+use alloc::{vec, vec::Vec};
+use core::{cmp::min, marker::PhantomData, mem};
 use revm_interpreter::gas::initial_tx_gas;
 use revm_interpreter::MAX_CODE_SIZE;
 use revm_precompile::{Precompile, Precompiles};
@@ -26,10 +33,51 @@ pub struct EVMData<'a, DB: Database> {
     pub error: Option<DB::Error>,
 }
 
+impl<DB: Database> BlockHashLookup for EVMData<'_, DB> {
+    fn database(&mut self, number: u64) -> Option<B256> {
+        self.db
+            .block_hash(U256::from(number))
+            .map_err(|e| self.error = Some(e))
+            .ok()
+    }
+
+    fn storage(&mut self, address: B160, index: U256) -> Option<U256> {
+        // read without loading the account, it stays cold for the transaction.
+        let slot = self
+            .journaled_state
+            .state
+            .get(&address)
+            .and_then(|account| account.storage.get(&index));
+        if let Some(slot) = slot {
+            return Some(slot.present_value);
+        }
+        self.db
+            .storage(address, index)
+            .map_err(|e| self.error = Some(e))
+            .ok()
+    }
+}
+
 pub struct EVMImpl<'a, GSPEC: Spec, DB: Database, const INSPECT: bool> {
     data: EVMData<'a, DB>,
-    precompiles: Precompiles,
+    /// Precompiles of the spec with custom ones.
+    precompiles: Cow<'a, Precompiles>,
     inspector: &'a mut dyn Inspector<DB>,
+    /// Data of all logs created in the transaction, including reverted ones.
+    log_data_size: usize,
+    /// L1 data fee paid by the transaction, see [crate::optimism].
+    #[cfg(feature = "optimism")]
+    l1_cost: U256,
+    /// Handlers replacing built-in instructions.
+    instruction_table: Option<&'a InstructionTable>,
+    /// Replaced stages of the transaction.
+    handler: Handler,
+    /// Opcode the last halted frame halted at.
+    halt: Option<HaltContext>,
+    /// Gas of frames that didn't finish yet, with their finished calls.
+    gas_frames: Vec<GasFrame>,
+    /// Gas of the first frame, when it is finished.
+    gas_frame: Option<Box<GasFrame>>,
     _phantomdata: PhantomData<GSPEC>,
 }
 
@@ -59,20 +107,69 @@ struct CallResult {
     return_value: Bytes,
 }
 
+/// State, logs, gas used, gas refunded and touched accounts of a finished transaction.
+type Finalized = (
+    HashMap<B160, Account>,
+    Vec<Log>,
+    u64,
+    u64,
+    Option<TouchedAccounts>,
+);
+
+/// Journal of a transaction.
+fn new_journal<GSPEC: Spec>(cfg: &CfgEnv) -> JournaledState {
+    // journal assumes standard precompiles are at addresses from 1 to N, `precompiles`
+    // has custom ones too.
+    let standard = Precompiles::new(to_precompile_id(GSPEC::SPEC_ID)).len();
+    let mut journaled_state = if cfg.is_state_clear_enabled() {
+        JournaledState::new(standard)
+    } else {
+        JournaledState::new_legacy(standard)
+    };
+    journaled_state.create_collision = cfg.create_collision;
+    journaled_state
+}
+
 pub trait Transact<DBError> {
     /// Do transaction.
     /// InstructionResult InstructionResult, Output for call or Address if we are creating
     /// contract, gas spend, gas refunded, State that needs to be applied.
     fn transact(&mut self) -> EVMResult<DBError>;
+
+    /// Execute call in a sandbox, see [crate::sandbox]. Changes are discarded.
+    fn sandbox_call(&mut self, call: &SandboxCall) -> Result<ExecutionResult, EVMError<DBError>>;
+
+    /// Execute call the same as [Transact::sandbox_call] but return its changes, see
+    /// [crate::system_call]. Account of the caller is not part of the changes.
+    fn system_call(&mut self, call: &SandboxCall) -> EVMResult<DBError>;
 }
 
 impl<'a, GSPEC: Spec, DB: Database, const INSPECT: bool> EVMImpl<'a, GSPEC, DB, INSPECT> {
     /// Load access list for berlin hardfork.
     ///
-    /// Loading of accounts/storages is needed to make them hot.
+    /// Loading of accounts/storages is needed to make them hot. Accounts of
+    /// [CfgEnv::warm_accounts](crate::primitives::CfgEnv::warm_accounts) and custom
+    /// precompiles are loaded too.
     #[inline]
     fn load_access_list(&mut self) -> Result<(), EVMError<DB::Error>> {
-        for (address, slots) in self.data.env.tx.access_list.iter() {
+        let env = &self.data.env;
+        for address in env
+            .cfg
+            .custom_precompiles
+            .iter()
+            .map(|(address, _)| address)
+        {
+            self.data
+                .journaled_state
+                .initial_account_load(*address, &[], self.data.db)
+                .map_err(EVMError::Database)?;
+        }
+        for (address, slots) in env
+            .cfg
+            .warm_accounts
+            .iter()
+            .chain(env.tx.access_list.iter())
+        {
             self.data
                 .journaled_state
                 .initial_account_load(*address, slots, self.data.db)
@@ -80,28 +177,129 @@ impl<'a, GSPEC: Spec, DB: Database, const INSPECT: bool> EVMImpl<'a, GSPEC, DB,
         }
         Ok(())
     }
+
+    /// Take fee of the gas limit and data fee of the blobs from the caller, built-in
+    /// [Handler::deduct_caller](crate::handler::Handler::deduct_caller).
+    fn deduct_caller(&mut self, tx_gas_limit: u64) -> Result<(), EVMError<DB::Error>> {
+        let env = &self.data.env;
+        #[cfg(feature = "optimism")]
+        let l1_cost = self.l1_cost;
+        let caller_account = self
+            .data
+            .journaled_state
+            .state()
+            .get_mut(&env.tx.caller)
+            .unwrap();
+
+        // Reduce gas_limit*gas_price amount of caller account.
+        // EIP-4844: data fee of the blobs is burned and it is not refunded.
+        // balance covers the cost, it is topped up if balance check is disabled.
+        let mut gas_cost = U256::ZERO;
+        if !env.cfg.is_gas_charging_disabled() {
+            gas_cost = U256::from(tx_gas_limit).saturating_mul(env.effective_gas_price());
+            if GSPEC::enabled(CANCUN) {
+                gas_cost = gas_cost.saturating_add(env.calc_data_fee().unwrap_or_default());
+            }
+        }
+
+        #[cfg(feature = "optimism")]
+        if env.is_deposit() {
+            // mint is not reverted if the deposit fails.
+            let mint = U256::from(env.tx.optimism.mint.unwrap_or_default());
+            caller_account.info.balance = caller_account.info.balance.saturating_add(mint);
+        } else {
+            // balance is checked by validation, unless it is replaced by the handler.
+            caller_account.info.balance = caller_account.info.balance.checked_sub(l1_cost).ok_or(
+                InvalidTransaction::LackOfFundForMaxFee {
+                    fee: l1_cost.saturating_add(gas_cost),
+                    balance: caller_account.info.balance,
+                },
+            )?;
+        }
+
+        caller_account.info.balance = caller_account
+            .info
+            .balance
+            .checked_sub(gas_cost)
+            .unwrap_or(U256::ZERO);
+
+        Ok(())
+    }
 }
 
 impl<'a, GSPEC: Spec, DB: Database, const INSPECT: bool> Transact<DB::Error>
     for EVMImpl<'a, GSPEC, DB, INSPECT>
 {
     fn transact(&mut self) -> EVMResult<DB::Error> {
-        self.env().validate_block_env::<GSPEC, DB::Error>()?;
-        self.env().validate_tx::<GSPEC>()?;
+        let _transact = phase!(
+            "transact",
+            caller = ?self.data.env.tx.caller,
+            gas_limit = self.data.env.tx.gas_limit
+        );
+        let mut tx = self.validate_transact()?;
+
+        let execute = phase!("execute");
+        // call inner handling of call/create
+        let (exit_reason, ret_gas, output) = match self.first_frame(&mut tx)? {
+            InterpreterAction::Call { mut inputs, .. } => {
+                let (exit, gas, bytes) = self.call(&mut inputs);
+                (exit, gas, Output::Call(bytes))
+            }
+            InterpreterAction::Create { mut inputs } => {
+                let (exit, address, ret_gas, bytes) = self.create(&mut inputs);
+                (exit, ret_gas, Output::Create(bytes, address))
+            }
+        };
+        execute.end_with_gas(exit_reason, tx.limit - tx.initial - ret_gas.remaining());
+
+        self.finish_transact(&tx, exit_reason, ret_gas, output)
+    }
+
+    fn sandbox_call(&mut self, call: &SandboxCall) -> Result<ExecutionResult, EVMError<DB::Error>> {
+        self.call_without_tx(call).map(|out| out.result)
+    }
+
+    fn system_call(&mut self, call: &SandboxCall) -> EVMResult<DB::Error> {
+        let mut out = self.call_without_tx(call)?;
+        out.state.remove(&call.caller);
+        Ok(out)
+    }
+}
+
+impl<'a, GSPEC: Spec, DB: Database, const INSPECT: bool> EVMImpl<'a, GSPEC, DB, INSPECT> {
+    /// Validate the transaction and take its fee from the caller.
+    fn validate_transact(&mut self) -> Result<TxGas, EVMError<DB::Error>> {
+        let validate = phase!("validate");
+        match self.handler.validate_env {
+            Some(validate_env) => validate_env(self.env())?,
+            None => {
+                self.env().validate_block_env::<GSPEC, DB::Error>()?;
+                self.env().validate_tx::<GSPEC>()?;
+            }
+        }
 
         let env = &self.data.env;
         let tx_caller = env.tx.caller;
-        let tx_value = env.tx.value;
-        let tx_data = env.tx.data.clone();
+        // without gas charging execution is only stopped by the cap of its spend.
+        #[cfg(feature = "optional_gas_charging")]
+        let tx_gas_limit = if env.cfg.disable_gas_charging {
+            env.cfg.unmetered_gas_cap
+        } else {
+            env.tx.gas_limit
+        };
+        #[cfg(not(feature = "optional_gas_charging"))]
         let tx_gas_limit = env.tx.gas_limit;
         let tx_is_create = env.tx.transact_to.is_create();
-        let effective_gas_price = env.effective_gas_price();
 
-        let initial_gas_spend =
-            initial_tx_gas::<GSPEC>(&tx_data, tx_is_create, &env.tx.access_list);
+        let initial_gas_spend = initial_tx_gas::<GSPEC>(
+            &env.tx.data,
+            tx_is_create,
+            &env.tx.access_list,
+            env.tx.authorization_list.len() as u64,
+        );
 
         // Additonal check to see if limit is big enought to cover initial gas.
-        if env.tx.gas_limit < initial_gas_spend {
+        if tx_gas_limit < initial_gas_spend {
             return Err(InvalidTransaction::CallGasCostMoreThanGasLimit.into());
         }
 
@@ -115,86 +313,154 @@ impl<'a, GSPEC: Spec, DB: Database, const INSPECT: bool> Transact<DB::Error>
         }
         self.load_access_list()?;
 
-        // load acc
-        let journal = &mut self.data.journaled_state;
-        let (caller_account, _) = journal
-            .load_account(tx_caller, self.data.db)
-            .map_err(EVMError::Database)?;
+        #[cfg(feature = "optimism")]
+        if self.data.env.cfg.optimism && !self.data.env.is_deposit() {
+            if let Some(enveloped_tx) = &self.data.env.tx.optimism.enveloped_tx {
+                let l1_block_info = crate::optimism::L1BlockInfo::try_fetch(self.data.db)
+                    .map_err(EVMError::Database)?;
+                self.l1_cost = l1_block_info.calculate_tx_l1_cost(enveloped_tx);
+            }
+        }
 
-        self.data.env.validate_tx_agains_state(caller_account)?;
+        // load acc, with code as delegating callers are allowed.
+        let journal = &mut self.data.journaled_state;
+        let (caller_account, _) = if GSPEC::enabled(PRAGUE) {
+            journal.load_code(tx_caller, self.data.db)
+        } else {
+            journal.load_account(tx_caller, self.data.db)
+        }
+        .map_err(EVMError::Database)?;
+
+        // L1 data fee is checked together with the rest of the cost.
+        #[cfg(feature = "optimism")]
+        let l1_cost = self.l1_cost;
+        #[cfg(not(feature = "optimism"))]
+        let l1_cost = U256::ZERO;
+        match self.handler.validate_tx_against_state {
+            Some(validate) => validate(self.data.env, caller_account)?,
+            None => self
+                .data
+                .env
+                .validate_tx_against_state_with_cost(caller_account, l1_cost)?,
+        }
 
-        // Reduce gas_limit*gas_price amount of caller account.
-        // unwrap_or can only occur if disable_balance_check is enabled
-        caller_account.info.balance = caller_account
-            .info
-            .balance
-            .checked_sub(U256::from(tx_gas_limit).saturating_mul(effective_gas_price))
-            .unwrap_or(U256::ZERO);
+        match self.handler.deduct_caller {
+            Some(deduct_caller) => deduct_caller(self.data.env, caller_account)?,
+            None => self.deduct_caller(tx_gas_limit)?,
+        }
+        let caller_account = self
+            .data
+            .journaled_state
+            .state()
+            .get_mut(&tx_caller)
+            .unwrap();
 
         // touch account so we know it is changed.
         caller_account.mark_touch();
+        validate.end();
 
-        let transact_gas_limit = tx_gas_limit - initial_gas_spend;
+        Ok(TxGas {
+            limit: tx_gas_limit,
+            initial: initial_gas_spend,
+            authorization_refund: 0,
+        })
+    }
 
-        // call inner handling of call/create
-        let (exit_reason, ret_gas, output) = match self.data.env.tx.transact_to {
+    /// Call or create of the transaction, nonce of the caller is increased for calls and
+    /// authorizations are applied.
+    fn first_frame(&mut self, tx: &mut TxGas) -> Result<InterpreterAction, EVMError<DB::Error>> {
+        let env = &self.data.env;
+        let tx_caller = env.tx.caller;
+        let tx_value = env.tx.value;
+        let tx_data = env.tx.data.clone();
+        let gas_limit = tx.limit - tx.initial;
+        match env.tx.transact_to {
             TransactTo::Call(address) => {
+                let caller_account = self
+                    .data
+                    .journaled_state
+                    .state()
+                    .get_mut(&tx_caller)
+                    .unwrap();
                 // Nonce is already checked
-                caller_account.info.nonce =
-                    caller_account.info.nonce.checked_add(1).unwrap_or(u64::MAX);
-
-                let (exit, gas, bytes) = self.call(&mut CallInputs {
-                    contract: address,
-                    transfer: Transfer {
-                        source: tx_caller,
-                        target: address,
-                        value: tx_value,
-                    },
-                    input: tx_data,
-                    gas_limit: transact_gas_limit,
-                    context: CallContext {
-                        caller: tx_caller,
-                        address,
-                        code_address: address,
-                        apparent_value: tx_value,
-                        scheme: CallScheme::Call,
-                    },
-                    is_static: false,
-                });
-                (exit, gas, Output::Call(bytes))
+                caller_account.info.nonce = caller_account.info.nonce.saturating_add(1);
+                if GSPEC::enabled(PRAGUE) {
+                    tx.authorization_refund = self.apply_authorization_list()?;
+                }
+
+                Ok(InterpreterAction::Call {
+                    inputs: Box::new(CallInputs {
+                        contract: address,
+                        transfer: Transfer {
+                            source: tx_caller,
+                            target: address,
+                            value: tx_value,
+                        },
+                        input: tx_data,
+                        gas_limit,
+                        context: CallContext {
+                            caller: tx_caller,
+                            address,
+                            code_address: address,
+                            apparent_value: tx_value,
+                            scheme: CallScheme::Call,
+                        },
+                        is_static: false,
+                    }),
+                    return_memory: 0..0,
+                })
             }
-            TransactTo::Create(scheme) => {
-                let (exit, address, ret_gas, bytes) = self.create(&mut CreateInputs {
+            TransactTo::Create(scheme) => Ok(InterpreterAction::Create {
+                inputs: Box::new(CreateInputs {
                     caller: tx_caller,
                     scheme,
                     value: tx_value,
                     init_code: tx_data,
-                    gas_limit: transact_gas_limit,
-                });
-                (exit, ret_gas, Output::Create(bytes, address))
-            }
-        };
+                    gas_limit,
+                }),
+            }),
+        }
+    }
 
+    /// Pay for gas of the finished transaction and build its result.
+    fn finish_transact(
+        &mut self,
+        tx: &TxGas,
+        exit_reason: InstructionResult,
+        ret_gas: Gas,
+        output: Output,
+    ) -> EVMResult<DB::Error> {
         // set gas with gas limit and spend it all. Gas is going to be reimbursed when
         // transaction is returned successfully.
-        let mut gas = Gas::new(tx_gas_limit);
-        gas.record_cost(tx_gas_limit);
+        let mut gas = Gas::new(tx.limit);
+        gas.record_cost(tx.limit);
 
         if crate::USE_GAS {
-            match exit_reason {
-                return_ok!() => {
-                    gas.erase_cost(ret_gas.remaining());
-                    gas.record_refund(ret_gas.refunded());
+            match self.handler.last_frame_return {
+                Some(last_frame_return) => {
+                    last_frame_return(self.data.env, exit_reason, &ret_gas, &mut gas)
                 }
-                return_revert!() => {
-                    gas.erase_cost(ret_gas.remaining());
-                }
-                _ => {}
+                None => match exit_reason {
+                    return_ok!() => {
+                        gas.erase_cost(ret_gas.remaining());
+                        gas.record_refund(ret_gas.refunded());
+                    }
+                    return_revert!() => {
+                        gas.erase_cost(ret_gas.remaining());
+                    }
+                    _ => {}
+                },
             }
+            // authorizations are applied even if execution fails.
+            gas.record_refund(tx.authorization_refund);
         }
 
-        let (state, logs, gas_used, gas_refunded) = self.finalize::<GSPEC>(&gas);
+        let finalize = phase!("finalize");
+        let (state, logs, gas_used, gas_refunded, touched) = self.finalize::<GSPEC>(&gas)?;
+        finalize.end_with_gas(exit_reason, gas_used);
+        let storage_diff = self.storage_diff(&state);
 
+        let mut halt = None;
         let result = match exit_reason.into() {
             SuccessOrHalt::Success(reason) => ExecutionResult::Success {
                 reason,
@@ -210,16 +476,106 @@ impl<'a, GSPEC: Spec, DB: Database, const INSPECT: bool> Transact<DB::Error>
                     Output::Create(return_value, _) => return_value,
                 },
             },
-            SuccessOrHalt::Halt(reason) => ExecutionResult::Halt { reason, gas_used },
+            SuccessOrHalt::Halt(reason) => {
+                // frame of the transaction halts last, unless the transaction halted outside
+                // of the interpreter.
+                halt = self.halt.filter(|halt| halt.depth == 1);
+                ExecutionResult::Halt { reason, gas_used }
+            }
             SuccessOrHalt::FatalExternalError => {
                 return Err(EVMError::Database(self.data.error.take().unwrap()))
             }
-            SuccessOrHalt::InternalContinue => {
+            SuccessOrHalt::InternalContinue | SuccessOrHalt::InternalCallOrCreate => {
                 panic!("Internal return flags should remain internal {exit_reason:?}")
             }
         };
 
-        Ok(ResultAndState { result, state })
+        Ok(ResultAndState {
+            result,
+            state,
+            touched,
+            halt,
+            gas_frame: self.gas_frame.take(),
+            storage_diff,
+        })
+    }
+
+    /// Execute call that is not a transaction: no intrinsic gas, fee, nonce or balance
+    /// check, only its own gas limit.
+    fn call_without_tx(&mut self, call: &SandboxCall) -> EVMResult<DB::Error> {
+        // caller and called account are warm, same as for a transaction.
+        for address in [call.caller, call.to] {
+            self.data
+                .journaled_state
+                .initial_account_load(address, &[], self.data.db)
+                .map_err(EVMError::Database)?;
+        }
+        let (exit_reason, gas, output) = if call.read_only && call.value != U256::ZERO {
+            (
+                InstructionResult::CallNotAllowedInsideStatic,
+                Gas::new(call.gas_limit),
+                Bytes::new(),
+            )
+        } else {
+            self.call(&mut CallInputs {
+                contract: call.to,
+                transfer: Transfer {
+                    source: call.caller,
+                    target: call.to,
+                    value: call.value,
+                },
+                input: call.input.clone(),
+                gas_limit: call.gas_limit,
+                context: CallContext {
+                    caller: call.caller,
+                    address: call.to,
+                    code_address: call.to,
+                    apparent_value: call.value,
+                    scheme: if call.read_only {
+                        CallScheme::StaticCall
+                    } else {
+                        CallScheme::Call
+                    },
+                },
+                is_static: call.read_only,
+            })
+        };
+        let (state, logs) = self.data.journaled_state.finalize();
+        let storage_diff = self.storage_diff(&state);
+        let gas_used = match exit_reason {
+            return_ok!() | return_revert!() if crate::USE_GAS => gas.spend(),
+            _ if crate::USE_GAS => call.gas_limit,
+            _ => 0,
+        };
+        let mut halt = None;
+        let result = match exit_reason.into() {
+            SuccessOrHalt::Success(reason) => ExecutionResult::Success {
+                reason,
+                gas_used,
+                gas_refunded: gas.refunded().max(0) as u64,
+                logs,
+                output: Output::Call(output),
+            },
+            SuccessOrHalt::Revert => ExecutionResult::Revert { gas_used, output },
+            SuccessOrHalt::Halt(reason) => {
+                halt = self.halt.filter(|halt| halt.depth == 1);
+                ExecutionResult::Halt { reason, gas_used }
+            }
+            SuccessOrHalt::FatalExternalError => {
+                return Err(EVMError::Database(self.data.error.take().unwrap()))
+            }
+            SuccessOrHalt::InternalContinue | SuccessOrHalt::InternalCallOrCreate => {
+                panic!("Internal return flags should remain internal {exit_reason:?}")
+            }
+        };
+        Ok(ResultAndState {
+            result,
+            state,
+            touched: None,
+            halt,
+            gas_frame: self.gas_frame.take(),
+            storage_diff,
+        })
     }
 }
 
@@ -228,13 +584,9 @@ impl<'a, GSPEC: Spec, DB: Database, const INSPECT: bool> EVMImpl<'a, GSPEC, DB,
         db: &'a mut DB,
         env: &'a mut Env,
         inspector: &'a mut dyn Inspector<DB>,
-        precompiles: Precompiles,
+        precompiles: Cow<'a, Precompiles>,
     ) -> Self {
-        let journaled_state = if GSPEC::enabled(SpecId::SPURIOUS_DRAGON) {
-            JournaledState::new(precompiles.len())
-        } else {
-            JournaledState::new_legacy(precompiles.len())
-        };
+        let journaled_state = new_journal::<GSPEC>(&env.cfg);
         Self {
             data: EVMData {
                 env,
@@ -244,52 +596,128 @@ impl<'a, GSPEC: Spec, DB: Database, const INSPECT: bool> EVMImpl<'a, GSPEC, DB,
             },
             precompiles,
             inspector,
+            log_data_size: 0,
+            #[cfg(feature = "optimism")]
+            l1_cost: U256::ZERO,
+            instruction_table: None,
+            handler: Handler::new(),
+            halt: None,
+            gas_frames: Vec::new(),
+            gas_frame: None,
             _phantomdata: PhantomData {},
         }
     }
 
-    fn finalize<SPEC: Spec>(&mut self, gas: &Gas) -> (HashMap<B160, Account>, Vec<Log>, u64, u64) {
+    /// Execute with handlers of `table` replacing the built-in instructions.
+    pub fn with_instruction_table(mut self, table: Option<&'a InstructionTable>) -> Self {
+        self.instruction_table = table;
+        self
+    }
+
+    /// Execute transactions with stages of `handler` replacing the built-in ones.
+    pub fn with_handler(mut self, handler: Handler) -> Self {
+        self.handler = handler;
+        self
+    }
+
+    fn finalize<SPEC: Spec>(&mut self, gas: &Gas) -> Result<Finalized, EVMError<DB::Error>> {
         let caller = self.data.env.tx.caller;
         let coinbase = self.data.env.block.coinbase;
+        let mut touched = self
+            .data
+            .env
+            .cfg
+            .touched_summary
+            .then(|| self.data.journaled_state.touched_summary());
+        // nonce of the caller is increased by every transaction.
+        if let Some(touched) = touched.as_mut() {
+            let change = touched.entry(caller).or_insert(AccountChange::BalanceOnly);
+            *change = (*change).max(AccountChange::BalanceOnly);
+        }
         let (gas_used, gas_refunded) = if crate::USE_GAS {
-            let effective_gas_price = self.data.env.effective_gas_price();
-            let basefee = self.data.env.block.basefee;
+            // gas is counted but nothing is paid for it if charging is disabled.
+            let (effective_gas_price, basefee) = if self.env().cfg.is_gas_charging_disabled() {
+                (U256::ZERO, U256::ZERO)
+            } else {
+                (
+                    self.data.env.effective_gas_price(),
+                    self.data.env.block.basefee,
+                )
+            };
 
             let gas_refunded = if self.env().cfg.is_gas_refund_disabled() {
                 0
+            } else if let Some(calculate_gas_refund) = self.handler.calculate_gas_refund {
+                calculate_gas_refund(self.data.env, gas)
             } else {
                 // EIP-3529: Reduction in refunds
                 let max_refund_quotient = if SPEC::enabled(LONDON) { 5 } else { 2 };
                 min(gas.refunded() as u64, gas.spend() / max_refund_quotient)
             };
 
+            let rewards = match self.handler.reward_beneficiary {
+                Some(reward_beneficiary) => reward_beneficiary(self.data.env, gas, gas_refunded),
+                None => {
+                    // EIP-1559 discard basefee for coinbase transfer. Basefee amount of gas is
+                    // discarded.
+                    let coinbase_gas_price = if SPEC::enabled(LONDON) {
+                        effective_gas_price.saturating_sub(basefee)
+                    } else {
+                        effective_gas_price
+                    };
+                    #[allow(unused_mut)]
+                    let mut rewards = vec![(
+                        coinbase,
+                        coinbase_gas_price * U256::from(gas.spend() - gas_refunded),
+                    )];
+                    // OP stack vaults get the L1 data fee and the base fee.
+                    #[cfg(feature = "optimism")]
+                    if self.data.env.cfg.optimism && !self.data.env.is_deposit() {
+                        let base_fee = basefee * U256::from(gas.spend() - gas_refunded);
+                        rewards.push((crate::optimism::L1_FEE_RECIPIENT, self.l1_cost));
+                        rewards.push((crate::optimism::BASE_FEE_RECIPIENT, base_fee));
+                    }
+                    rewards
+                }
+            };
+
+            // beneficiaries are loaded before any balance changes, so an error of the
+            // database leaves only journaled changes.
+            for (address, _) in &rewards {
+                self.data
+                    .journaled_state
+                    .load_account(*address, self.data.db)
+                    .map_err(EVMError::Database)?;
+            }
+
             // return balance of not spend gas.
             let caller_account = self.data.journaled_state.state().get_mut(&caller).unwrap();
-            caller_account.info.balance = caller_account
-                .info
-                .balance
-                .saturating_add(effective_gas_price * U256::from(gas.remaining() + gas_refunded));
-
-            // EIP-1559 discard basefee for coinbase transfer. Basefee amount of gas is discarded.
-            let coinbase_gas_price = if SPEC::enabled(LONDON) {
-                effective_gas_price.saturating_sub(basefee)
-            } else {
-                effective_gas_price
-            };
+            match self.handler.reimburse_caller {
+                Some(reimburse_caller) => {
+                    reimburse_caller(self.data.env, caller_account, gas, gas_refunded)
+                }
+                None => {
+                    caller_account.info.balance = caller_account.info.balance.saturating_add(
+                        effective_gas_price * U256::from(gas.remaining() + gas_refunded),
+                    );
+                }
+            }
 
             // transfer fee to coinbase/beneficiary.
-            let Ok((coinbase_account, _)) = self
-                .data
-                .journaled_state
-                .load_account(coinbase, self.data.db)
-            else {
-                panic!("coinbase account not found");
-            };
-            coinbase_account.mark_touch();
-            coinbase_account.info.balance = coinbase_account
-                .info
-                .balance
-                .saturating_add(coinbase_gas_price * U256::from(gas.spend() - gas_refunded));
+            for (address, reward) in rewards {
+                let account = self.data.journaled_state.state().get_mut(&address).unwrap();
+                account.mark_touch();
+                account.info.balance = account.info.balance.saturating_add(reward);
+                if let Some(touched) = touched.as_mut() {
+                    let change = if reward != U256::ZERO {
+                        AccountChange::BalanceOnly
+                    } else {
+                        AccountChange::Touched
+                    };
+                    let entry = touched.entry(address).or_insert(change);
+                    *entry = (*entry).max(change);
+                }
+            }
 
             (gas.spend() - gas_refunded, gas_refunded)
         } else {
@@ -301,48 +729,55 @@ impl<'a, GSPEC: Spec, DB: Database, const INSPECT: bool> EVMImpl<'a, GSPEC, DB,
             self.data.journaled_state.touch(&coinbase);
             (0, 0)
         };
+        if let Some(touched) = touched.as_mut() {
+            touched.entry(coinbase).or_insert(AccountChange::Touched);
+        }
         let (new_state, logs) = self.data.journaled_state.finalize();
-        (new_state, logs, gas_used, gas_refunded)
+        Ok((new_state, logs, gas_used, gas_refunded, touched))
     }
 
     fn prepare_create(&mut self, inputs: &CreateInputs) -> Result<PreparedCreate, CreateResult> {
         let gas = Gas::new(inputs.gas_limit);
 
-        // Check depth of calls
-        if self.data.journaled_state.depth() > CALL_STACK_LIMIT {
+        // Fetch balance of caller, it is loaded by the frame or transaction that creates.
+        let Some((caller_balance, _)) = self.balance(inputs.caller) else {
             return Err(CreateResult {
-                result: InstructionResult::CallTooDeep,
+                result: InstructionResult::FatalExternalError,
                 created_address: None,
                 gas,
                 return_value: Bytes::new(),
             });
-        }
+        };
 
-        // Fetch balance of caller.
-        let Some((caller_balance, _)) = self.balance(inputs.caller) else {
+        // Create address, it is returned even if creation fails before the nonce is increased.
+        // It is derived only when needed as CREATE2 hashes the init code.
+        let old_nonce = self.data.journaled_state.account(inputs.caller).info.nonce;
+        let derive_address = |cfg: &CfgEnv| {
+            cfg.created_address(inputs.scheme, inputs.caller, old_nonce, &inputs.init_code)
+        };
+
+        // Check depth of calls
+        if self.data.journaled_state.depth() > CALL_STACK_LIMIT {
             return Err(CreateResult {
-                result: InstructionResult::FatalExternalError,
-                created_address: None,
+                result: InstructionResult::CallTooDeep,
+                created_address: Some(derive_address(&self.data.env.cfg)),
                 gas,
                 return_value: Bytes::new(),
             });
-        };
+        }
 
         // Check if caller has enough balance to send to the crated contract.
         if caller_balance < inputs.value {
             return Err(CreateResult {
                 result: InstructionResult::OutOfFund,
-                created_address: None,
+                created_address: Some(derive_address(&self.data.env.cfg)),
                 gas,
                 return_value: Bytes::new(),
             });
         }
 
         // Increase nonce of caller and check if it overflows
-        let old_nonce;
-        if let Some(nonce) = self.data.journaled_state.inc_nonce(inputs.caller) {
-            old_nonce = nonce - 1;
-        } else {
+        if self.data.journaled_state.inc_nonce(inputs.caller).is_none() {
             return Err(CreateResult {
                 result: InstructionResult::Return,
                 created_address: None,
@@ -351,12 +786,16 @@ impl<'a, GSPEC: Spec, DB: Database, const INSPECT: bool> EVMImpl<'a, GSPEC, DB,
             });
         }
 
-        // Create address
-        let code_hash = keccak256(&inputs.init_code);
-        let created_address = match inputs.scheme {
-            CreateScheme::Create => create_address(inputs.caller, old_nonce),
-            CreateScheme::Create2 { salt } => create2_address(inputs.caller, code_hash, salt),
-        };
+        let created_address = derive_address(&self.data.env.cfg);
+        // Denied create doesn't create the account, it has no address even if it succeeds.
+        if let Some(result) = self.denied_result(&created_address) {
+            return Err(CreateResult {
+                result,
+                created_address: None,
+                gas,
+                return_value: Bytes::new(),
+            });
+        }
 
         // Load account so it needs to be marked as hot for access list.
         if self
@@ -384,14 +823,14 @@ impl<'a, GSPEC: Spec, DB: Database, const INSPECT: bool> EVMImpl<'a, GSPEC, DB,
             Err(e) => {
                 return Err(CreateResult {
                     result: e,
-                    created_address: None,
+                    created_address: Some(created_address),
                     gas,
                     return_value: Bytes::new(),
                 })
             }
         };
 
-        let contract = Box::new(Contract::new(
+        let mut contract = Box::new(Contract::new(
             Bytes::new(),
             Bytecode::new_raw(inputs.init_code.clone()),
             created_address,
@@ -399,6 +838,20 @@ impl<'a, GSPEC: Spec, DB: Database, const INSPECT: bool> EVMImpl<'a, GSPEC, DB,
             inputs.value,
         ));
 
+        // EOF init code has to be valid, it is not executed as legacy code.
+        if GSPEC::enabled(OSAKA) && Eof::is_eof(&inputs.init_code) {
+            contract.load_eof();
+            if contract.eof.is_none() {
+                self.data.journaled_state.checkpoint_revert(checkpoint);
+                return Err(CreateResult {
+                    result: InstructionResult::InvalidEOFCode,
+                    created_address: Some(created_address),
+                    gas,
+                    return_value: Bytes::new(),
+                });
+            }
+        }
+
         Ok(PreparedCreate {
             gas,
             created_address,
@@ -419,21 +872,54 @@ impl<'a, GSPEC: Spec, DB: Database, const INSPECT: bool> EVMImpl<'a, GSPEC, DB,
         // Create new interpreter and execute initcode
         let (exit_reason, mut interpreter) =
             self.run_interpreter(prepared_create.contract, prepared_create.gas.limit(), false);
+        self.create_return(
+            inputs,
+            prepared_create.created_address,
+            prepared_create.checkpoint,
+            exit_reason,
+            &mut interpreter,
+        )
+    }
 
+    /// Deposit code returned by init code of a finished create frame, or revert the frame.
+    fn create_return(
+        &mut self,
+        inputs: &CreateInputs,
+        created_address: B160,
+        checkpoint: JournalCheckpoint,
+        exit_reason: InstructionResult,
+        interpreter: &mut Interpreter,
+    ) -> CreateResult {
         // Host error if present on execution
         match exit_reason {
             return_ok!() => {
                 // if ok, check contract creation limit and calculate gas deduction on output len.
                 let mut bytes = interpreter.return_value();
 
-                // EIP-3541: Reject new contract code starting with the 0xEF byte
-                if GSPEC::enabled(LONDON) && !bytes.is_empty() && bytes.first() == Some(&0xEF) {
-                    self.data
-                        .journaled_state
-                        .checkpoint_revert(prepared_create.checkpoint);
+                // EOF init code deploys only valid EOF code.
+                if GSPEC::enabled(OSAKA)
+                    && Eof::is_eof(&inputs.init_code)
+                    && (!Eof::is_eof(&bytes) || decode_valid_eof(bytes.clone()).is_none())
+                {
+                    self.data.journaled_state.checkpoint_revert(checkpoint);
+                    return CreateResult {
+                        result: InstructionResult::InvalidEOFCode,
+                        created_address: Some(created_address),
+                        gas: interpreter.gas,
+                        return_value: bytes,
+                    };
+                }
+
+                // EIP-3541: Reject new contract code starting with the 0xEF byte, unless it is
+                // EOF deployed by EOF init code.
+                if GSPEC::enabled(LONDON)
+                    && !(GSPEC::enabled(OSAKA) && Eof::is_eof(&inputs.init_code))
+                    && bytes.first() == Some(&0xEF)
+                {
+                    self.data.journaled_state.checkpoint_revert(checkpoint);
                     return CreateResult {
                         result: InstructionResult::CreateContractStartingWithEF,
-                        created_address: Some(prepared_create.created_address),
+                        created_address: Some(created_address),
                         gas: interpreter.gas,
                         return_value: bytes,
                     };
@@ -450,12 +936,10 @@ impl<'a, GSPEC: Spec, DB: Database, const INSPECT: bool> EVMImpl<'a, GSPEC, DB,
                             .limit_contract_code_size
                             .unwrap_or(MAX_CODE_SIZE)
                 {
-                    self.data
-                        .journaled_state
-                        .checkpoint_revert(prepared_create.checkpoint);
+                    self.data.journaled_state.checkpoint_revert(checkpoint);
                     return CreateResult {
                         result: InstructionResult::CreateContractSizeLimit,
-                        created_address: Some(prepared_create.created_address),
+                        created_address: Some(created_address),
                         gas: interpreter.gas,
                         return_value: bytes,
                     };
@@ -468,12 +952,10 @@ impl<'a, GSPEC: Spec, DB: Database, const INSPECT: bool> EVMImpl<'a, GSPEC, DB,
                         // final gas fee for adding the contract code to the state, the contract
                         //  creation fails (i.e. goes out-of-gas) rather than leaving an empty contract.
                         if GSPEC::enabled(HOMESTEAD) {
-                            self.data
-                                .journaled_state
-                                .checkpoint_revert(prepared_create.checkpoint);
+                            self.data.journaled_state.checkpoint_revert(checkpoint);
                             return CreateResult {
                                 result: InstructionResult::OutOfGas,
-                                created_address: Some(prepared_create.created_address),
+                                created_address: Some(created_address),
                                 gas: interpreter.gas,
                                 return_value: bytes,
                             };
@@ -485,28 +967,28 @@ impl<'a, GSPEC: Spec, DB: Database, const INSPECT: bool> EVMImpl<'a, GSPEC, DB,
                 // if we have enough gas
                 self.data.journaled_state.checkpoint_commit();
                 // Do analysis of bytecode straight away.
+                let bytecode =
+                    Bytecode::new_raw_with_hasher(bytes.clone(), &self.data.env.cfg.code_hasher);
                 let bytecode = match self.data.env.cfg.perf_analyse_created_bytecodes {
-                    AnalysisKind::Raw => Bytecode::new_raw(bytes.clone()),
-                    AnalysisKind::Check => Bytecode::new_raw(bytes.clone()).to_checked(),
-                    AnalysisKind::Analyse => to_analysed(Bytecode::new_raw(bytes.clone())),
+                    AnalysisKind::Raw => bytecode,
+                    AnalysisKind::Check => bytecode.to_checked(),
+                    AnalysisKind::Analyse => to_analysed(bytecode),
                 };
                 self.data
                     .journaled_state
-                    .set_code(prepared_create.created_address, bytecode);
+                    .set_code(created_address, bytecode);
                 CreateResult {
                     result: InstructionResult::Return,
-                    created_address: Some(prepared_create.created_address),
+                    created_address: Some(created_address),
                     gas: interpreter.gas,
                     return_value: bytes,
                 }
             }
             _ => {
-                self.data
-                    .journaled_state
-                    .checkpoint_revert(prepared_create.checkpoint);
+                self.data.journaled_state.checkpoint_revert(checkpoint);
                 CreateResult {
                     result: exit_reason,
-                    created_address: Some(prepared_create.created_address),
+                    created_address: Some(created_address),
                     gas: interpreter.gas,
                     return_value: interpreter.return_value(),
                 }
@@ -522,7 +1004,27 @@ impl<'a, GSPEC: Spec, DB: Database, const INSPECT: bool> EVMImpl<'a, GSPEC, DB,
         gas_limit: u64,
         is_static: bool,
     ) -> (InstructionResult, Box<Interpreter>) {
-        // Create inspector
+        let mut interpreter = self.new_interpreter(contract, gas_limit, is_static);
+        let exit_reason = match (INSPECT, self.instruction_table) {
+            (true, None) => interpreter.run_inspect::<Self, GSPEC>(self),
+            (false, None) => interpreter.run::<Self, GSPEC>(self),
+            (true, Some(table)) => interpreter.run_inspect_with_table::<Self, GSPEC>(table, self),
+            (false, Some(table)) => interpreter.run_with_table::<Self, GSPEC>(table, self),
+        };
+        if matches!(SuccessOrHalt::from(exit_reason), SuccessOrHalt::Halt(_)) {
+            self.record_halt(&interpreter);
+        }
+
+        (exit_reason, interpreter)
+    }
+
+    /// Create interpreter of a frame, it is initialized by the inspector.
+    fn new_interpreter(
+        &mut self,
+        contract: Box<Contract>,
+        gas_limit: u64,
+        is_static: bool,
+    ) -> Box<Interpreter> {
         #[cfg(feature = "memory_limit")]
         let mut interpreter = Box::new(Interpreter::new_with_memory_limit(
             contract,
@@ -534,17 +1036,59 @@ impl<'a, GSPEC: Spec, DB: Database, const INSPECT: bool> EVMImpl<'a, GSPEC, DB,
         #[cfg(not(feature = "memory_limit"))]
         let mut interpreter = Box::new(Interpreter::new(contract, gas_limit, is_static));
 
+        if self.data.env.cfg.is_gas_charging_disabled() {
+            interpreter.gas = Gas::new_unmetered(gas_limit);
+        }
+
         if INSPECT {
             self.inspector
                 .initialize_interp(&mut interpreter, &mut self.data);
         }
-        let exit_reason = if INSPECT {
-            interpreter.run_inspect::<Self, GSPEC>(self)
-        } else {
-            interpreter.run::<Self, GSPEC>(self)
+        interpreter
+    }
+
+    /// Finish gas frame started last with gas the frame returned, it is added to calls of
+    /// its parent.
+    fn end_gas_frame(
+        &mut self,
+        address: B160,
+        is_create: bool,
+        result: InstructionResult,
+        gas: &Gas,
+    ) {
+        let Some(mut frame) = self.gas_frames.pop() else {
+            return;
         };
+        frame.address = address;
+        frame.is_create = is_create;
+        frame.success = matches!(result, return_ok!());
+        frame.gas_limit = gas.limit();
+        frame.gas_used = match result {
+            return_ok!() | return_revert!() => gas.spend(),
+            _ => gas.limit(),
+        };
+        frame.gas_refunded = if frame.success { gas.refunded() } else { 0 };
+        frame.memory_cost = gas.memory();
+        match self.gas_frames.last_mut() {
+            Some(parent) => parent.calls.push(frame),
+            None => self.gas_frame = Some(Box::new(frame)),
+        }
+    }
 
-        (exit_reason, interpreter)
+    /// Remember where `interpreter` halted.
+    fn record_halt(&mut self, interpreter: &Interpreter) {
+        // opcode is executed if the interpreter set the result, inspector halts before it.
+        let pc = match interpreter.instruction_result {
+            InstructionResult::Continue => interpreter.program_counter(),
+            _ => interpreter.program_counter().wrapping_sub(1),
+        };
+        let code = interpreter.contract.bytecode.original_bytecode_slice();
+        self.halt = code.get(pc).map(|opcode| HaltContext {
+            address: interpreter.contract.address,
+            depth: self.data.journaled_state.depth(),
+            pc,
+            opcode: *opcode,
+        });
     }
 
     /// Call precompile contract
@@ -559,6 +1103,21 @@ impl<'a, GSPEC: Spec, DB: Database, const INSPECT: bool> EVMImpl<'a, GSPEC, DB,
         let out = match precompile {
             Precompile::Standard(fun) => fun(&input_data, gas.limit()),
             Precompile::Custom(fun) => fun(&input_data, gas.limit()),
+            Precompile::Dynamic(fun) => fun(&input_data, gas.limit()),
+            Precompile::Stateful(fun) => {
+                let mut state = StatefulPrecompileState::<DB, INSPECT> {
+                    data: &mut self.data,
+                    inspector: &mut *self.inspector,
+                    log_data_size: &mut self.log_data_size,
+                    context: PrecompileContext {
+                        address: inputs.context.address,
+                        caller: inputs.context.caller,
+                        value: inputs.context.apparent_value,
+                        is_static: inputs.is_static,
+                    },
+                };
+                fun.call(&mut state, &input_data, gas.limit())
+            }
         };
         match out {
             Ok((gas_used, data)) => {
@@ -577,10 +1136,16 @@ impl<'a, GSPEC: Spec, DB: Database, const INSPECT: bool> EVMImpl<'a, GSPEC, DB,
                 }
             }
             Err(e) => {
-                let ret = if precompile::Error::OutOfGas == e {
-                    InstructionResult::PrecompileOOG
-                } else {
-                    InstructionResult::PrecompileError
+                let ret = match e {
+                    precompile::Error::OutOfGas => InstructionResult::PrecompileOOG,
+                    // error of the database is set only if it failed.
+                    precompile::Error::Database if self.data.error.is_some() => {
+                        InstructionResult::FatalExternalError
+                    }
+                    precompile::Error::StateChangeDuringStaticCall => {
+                        InstructionResult::StateChangeDuringStaticCall
+                    }
+                    _ => InstructionResult::PrecompileError,
                 };
                 CallResult {
                     result: ret,
@@ -591,21 +1156,111 @@ impl<'a, GSPEC: Spec, DB: Database, const INSPECT: bool> EVMImpl<'a, GSPEC, DB,
         }
     }
 
-    fn prepare_call(&mut self, inputs: &mut CallInputs) -> Result<PreparedCall, CallResult> {
-        let gas = Gas::new(inputs.gas_limit);
-        // Load account and get code. Account is now hot.
-        let Some((bytecode, _)) = self.code(inputs.contract) else {
-            return Err(CallResult {
-                result: InstructionResult::FatalExternalError,
-                gas,
-                return_value: Bytes::new(),
-            });
-        };
+    /// Changed storage of the finalized `state`, if it is enabled.
+    fn storage_diff(&self, state: &HashMap<B160, Account>) -> Option<Box<StorageDiff>> {
+        self.data
+            .env
+            .cfg
+            .storage_diff
+            .then(|| Box::new(crate::primitives::storage_diff(state)))
+    }
 
-        // Check depth
-        if self.data.journaled_state.depth() > CALL_STACK_LIMIT {
-            return Err(CallResult {
-                result: InstructionResult::CallTooDeep,
+    /// Result of the frame if `address` is denied by the address filter.
+    fn denied_result(&self, address: &B160) -> Option<InstructionResult> {
+        let action = self.data.env.cfg.address_filter.check(address)?;
+        Some(match action {
+            AddressFilterAction::Halt => InstructionResult::AddressDenied,
+            AddressFilterAction::NoOp => InstructionResult::Stop,
+        })
+    }
+
+    /// EIP-7702: Set code of authorities of valid authorizations of the transaction, invalid
+    /// ones are skipped. Returns gas refund of the authorities that already existed.
+    fn apply_authorization_list(&mut self) -> Result<i64, EVMError<DB::Error>> {
+        let chain_id = self.data.env.cfg.chain_id;
+        let mut refund = 0;
+        for authorization in self.data.env.tx.authorization_list.clone() {
+            if authorization.chain_id != U256::ZERO && authorization.chain_id != chain_id {
+                continue;
+            }
+            if authorization.nonce == u64::MAX {
+                continue;
+            }
+            let Some(authority) = authorization.signature().and_then(|signature| {
+                revm_precompile::recover_address(&signature, &authorization.signature_hash().0)
+            }) else {
+                continue;
+            };
+            let authority = B160(authority);
+
+            // authority is warm from now on.
+            let (account, _) = self
+                .data
+                .journaled_state
+                .load_code(authority, self.data.db)
+                .map_err(EVMError::Database)?;
+            let code = account.info.code.as_ref().unwrap();
+            if !code.is_empty() && code.delegation_address().is_none() {
+                continue;
+            }
+            if account.info.nonce != authorization.nonce {
+                continue;
+            }
+            if !(account.is_empty() && account.is_loaded_as_not_existing()) {
+                refund += (gas::PER_EMPTY_ACCOUNT_COST - gas::PER_AUTH_BASE_COST) as i64;
+            }
+
+            let code = if authorization.address == B160::zero() {
+                Bytecode::new()
+            } else {
+                Bytecode::new_delegation(authorization.address)
+            };
+            self.data.journaled_state.set_code(authority, code);
+            self.data.journaled_state.inc_nonce(authority);
+        }
+        Ok(refund)
+    }
+
+    fn prepare_call(&mut self, inputs: &mut CallInputs) -> Result<PreparedCall, CallResult> {
+        let gas = Gas::new(inputs.gas_limit);
+        if let Some(result) = self
+            .denied_result(&inputs.contract)
+            .or_else(|| self.denied_result(&inputs.transfer.target))
+        {
+            return Err(CallResult {
+                result,
+                gas,
+                return_value: Bytes::new(),
+            });
+        }
+
+        // Load account and get code. Account is now hot.
+        let Some((mut bytecode, _)) = self.code(inputs.contract) else {
+            return Err(CallResult {
+                result: InstructionResult::FatalExternalError,
+                gas,
+                return_value: Bytes::new(),
+            });
+        };
+        // EIP-7702: Code of the delegated address is executed, delegations are not followed
+        // further.
+        if GSPEC::enabled(PRAGUE) {
+            if let Some(delegated) = bytecode.delegation_address() {
+                let Some((delegated_code, _)) = self.code(delegated) else {
+                    return Err(CallResult {
+                        result: InstructionResult::FatalExternalError,
+                        gas,
+                        return_value: Bytes::new(),
+                    });
+                };
+                bytecode = delegated_code;
+            }
+        }
+
+        // Check depth
+        if self.data.journaled_state.depth() > CALL_STACK_LIMIT {
+            return Err(CallResult {
+                result: InstructionResult::CallTooDeep,
                 gas,
                 return_value: Bytes::new(),
             });
@@ -635,11 +1290,20 @@ impl<'a, GSPEC: Spec, DB: Database, const INSPECT: bool> EVMImpl<'a, GSPEC, DB,
             });
         }
 
-        let contract = Box::new(Contract::new_with_context(
+        // Reuse analysis of code called before.
+        #[cfg(feature = "std")]
+        let bytecode = match &self.data.env.cfg.analysis_cache {
+            Some(cache) => cache.get_or_analyse(bytecode, to_analysed),
+            None => bytecode,
+        };
+        let mut contract = Box::new(Contract::new_with_context(
             inputs.input.clone(),
             bytecode,
             &inputs.context,
         ));
+        if GSPEC::enabled(OSAKA) {
+            contract.load_eof();
+        }
 
         Ok(PreparedCall {
             gas,
@@ -648,48 +1312,638 @@ impl<'a, GSPEC: Spec, DB: Database, const INSPECT: bool> EVMImpl<'a, GSPEC, DB,
         })
     }
 
+    /// Prepare frame of the call, calls of precompiles and accounts without code are
+    /// executed and returned as the error.
+    fn enter_call(&mut self, inputs: &mut CallInputs) -> Result<PreparedCall, CallResult> {
+        let prepared_call = self.prepare_call(inputs)?;
+
+        if self.precompiles.contains(&inputs.contract) {
+            let precompile = phase!("precompile", address = ?inputs.contract);
+            let ret = self.call_precompile(inputs, prepared_call.gas);
+            precompile.end_with_gas(ret.result, ret.gas.spend());
+            Err(self.call_return(prepared_call.checkpoint, ret))
+        } else if prepared_call.contract.bytecode.is_empty() {
+            let ret = CallResult {
+                result: InstructionResult::Stop,
+                gas: prepared_call.gas,
+                return_value: Bytes::new(),
+            };
+            Err(self.call_return(prepared_call.checkpoint, ret))
+        } else {
+            Ok(prepared_call)
+        }
+    }
+
     /// Main contract call of the EVM.
     fn call_inner(&mut self, inputs: &mut CallInputs) -> CallResult {
-        let res = self.prepare_call(inputs);
-
-        let prepared_call = match res {
+        let prepared_call = match self.enter_call(inputs) {
             Ok(o) => o,
             Err(e) => return e,
         };
 
-        let ret = if is_precompile(inputs.contract, self.precompiles.len()) {
-            self.call_precompile(inputs, prepared_call.gas)
-        } else if !prepared_call.contract.bytecode.is_empty() {
-            // Create interpreter and execute subcall
-            let (exit_reason, interpreter) = self.run_interpreter(
-                prepared_call.contract,
-                prepared_call.gas.limit(),
-                inputs.is_static,
-            );
-            CallResult {
-                result: exit_reason,
-                gas: interpreter.gas,
-                return_value: interpreter.return_value(),
-            }
-        } else {
-            CallResult {
-                result: InstructionResult::Stop,
-                gas: prepared_call.gas,
-                return_value: Bytes::new(),
-            }
+        // Create interpreter and execute subcall
+        let (exit_reason, interpreter) = self.run_interpreter(
+            prepared_call.contract,
+            prepared_call.gas.limit(),
+            inputs.is_static,
+        );
+        let ret = CallResult {
+            result: exit_reason,
+            gas: interpreter.gas,
+            return_value: interpreter.return_value(),
         };
+        self.call_return(prepared_call.checkpoint, ret)
+    }
 
-        // revert changes or not.
+    /// Commit changes of a finished call frame, or revert them if it failed.
+    fn call_return(&mut self, checkpoint: JournalCheckpoint, ret: CallResult) -> CallResult {
         if matches!(ret.result, return_ok!()) {
             self.data.journaled_state.checkpoint_commit();
         } else {
-            self.data
-                .journaled_state
-                .checkpoint_revert(prepared_call.checkpoint);
+            self.data.journaled_state.checkpoint_revert(checkpoint);
         }
-
         ret
     }
+
+    /// Finish gas frame of the call and let the inspector change its result.
+    fn call_end(
+        &mut self,
+        inputs: &CallInputs,
+        ret: CallResult,
+    ) -> (InstructionResult, Gas, Bytes) {
+        if self.data.env.cfg.gas_frames {
+            self.end_gas_frame(inputs.contract, false, ret.result, &ret.gas);
+        }
+        if INSPECT {
+            self.inspector.call_end(
+                &mut self.data,
+                inputs,
+                ret.gas,
+                ret.result,
+                ret.return_value,
+            )
+        } else {
+            (ret.result, ret.gas, ret.return_value)
+        }
+    }
+
+    /// Finish gas frame of the create and let the inspector change its result.
+    fn create_end(
+        &mut self,
+        inputs: &CreateInputs,
+        ret: CreateResult,
+    ) -> (InstructionResult, Option<B160>, Gas, Bytes) {
+        if self.data.env.cfg.gas_frames {
+            let address = ret.created_address.unwrap_or_default();
+            self.end_gas_frame(address, true, ret.result, &ret.gas);
+        }
+        if INSPECT {
+            self.inspector.create_end(
+                &mut self.data,
+                inputs,
+                ret.result,
+                ret.created_address,
+                ret.gas,
+                ret.return_value,
+            )
+        } else {
+            (ret.result, ret.created_address, ret.gas, ret.return_value)
+        }
+    }
+}
+
+/// Transaction executed frame by frame on the caller's thread, see [crate::frame].
+pub(crate) trait Driver<DBError> {
+    /// Validate the transaction and prepare its first frame. Nothing is changed if it fails.
+    fn start(&mut self) -> Result<Execution, EVMError<DBError>>;
+
+    /// Continue `execution` until `pause`, result is returned once the transaction finishes.
+    ///
+    /// Frame or opcode that fails to read the database is rolled back before the error is
+    /// returned, so `execution` can be resumed again.
+    fn resume(
+        &mut self,
+        execution: &mut Execution,
+        pause: PauseAt,
+    ) -> Result<Option<ResultAndState>, EVMError<DBError>>;
+}
+
+impl<'a, GSPEC: Spec, DB: Database, const INSPECT: bool> Driver<DB::Error>
+    for EVMImpl<'a, GSPEC, DB, INSPECT>
+{
+    fn start(&mut self) -> Result<Execution, EVMError<DB::Error>> {
+        let started = self.validate_transact().and_then(|mut tx| {
+            let action = self.first_frame(&mut tx)?;
+            Ok((tx, action))
+        });
+        let (tx, action) = match started {
+            Ok(started) => started,
+            Err(e) => {
+                // caller is changed without the journal, transaction starts again from scratch.
+                self.data.journaled_state = new_journal::<GSPEC>(&self.data.env.cfg);
+                #[cfg(feature = "optimism")]
+                {
+                    self.l1_cost = U256::ZERO;
+                }
+                return Err(e);
+            }
+        };
+        let mut execution = Execution {
+            journaled_state: new_journal::<GSPEC>(&self.data.env.cfg),
+            frames: Vec::new(),
+            next: Next::Enter {
+                action,
+                inspected: false,
+            },
+            tx,
+            log_data_size: 0,
+            #[cfg(feature = "optimism")]
+            l1_cost: U256::ZERO,
+            halt: None,
+            gas_frames: Vec::new(),
+            gas_frame: None,
+            step_inspected: false,
+        };
+        self.swap_execution(&mut execution);
+        Ok(execution)
+    }
+
+    fn resume(
+        &mut self,
+        execution: &mut Execution,
+        pause: PauseAt,
+    ) -> Result<Option<ResultAndState>, EVMError<DB::Error>> {
+        self.swap_execution(execution);
+        let out = self.drive(execution, pause);
+        self.swap_execution(execution);
+        out
+    }
+}
+
+impl<'a, GSPEC: Spec, DB: Database, const INSPECT: bool> EVMImpl<'a, GSPEC, DB, INSPECT> {
+    /// Exchange state of the transaction with the one of `execution`.
+    fn swap_execution(&mut self, execution: &mut Execution) {
+        mem::swap(
+            &mut self.data.journaled_state,
+            &mut execution.journaled_state,
+        );
+        mem::swap(&mut self.log_data_size, &mut execution.log_data_size);
+        #[cfg(feature = "optimism")]
+        mem::swap(&mut self.l1_cost, &mut execution.l1_cost);
+        mem::swap(&mut self.halt, &mut execution.halt);
+        mem::swap(&mut self.gas_frames, &mut execution.gas_frames);
+        mem::swap(&mut self.gas_frame, &mut execution.gas_frame);
+    }
+
+    fn drive(
+        &mut self,
+        execution: &mut Execution,
+        pause: PauseAt,
+    ) -> Result<Option<ResultAndState>, EVMError<DB::Error>> {
+        loop {
+            match mem::replace(&mut execution.next, Next::Run) {
+                Next::Enter { action, inspected } => {
+                    self.enter_frame(execution, action, inspected)?
+                }
+                Next::Run => self.run_step(execution)?,
+                Next::Return(output) if execution.frames.is_empty() => {
+                    return self.finish_execution(execution, output).map(Some)
+                }
+                Next::Return(output) => self.return_to_parent(execution, output),
+            }
+            match pause {
+                PauseAt::Step => return Ok(None),
+                PauseAt::Frame if !matches!(execution.next, Next::Run) => return Ok(None),
+                _ => {}
+            }
+        }
+    }
+
+    /// Start frame of `action`, or finish it if it doesn't need an interpreter.
+    fn enter_frame(
+        &mut self,
+        execution: &mut Execution,
+        mut action: InterpreterAction,
+        inspected: bool,
+    ) -> Result<(), EVMError<DB::Error>> {
+        if INSPECT && !inspected {
+            let output = match &mut action {
+                InterpreterAction::Call {
+                    inputs,
+                    return_memory,
+                } => {
+                    let (result, gas, output) = self.inspector.call(&mut self.data, inputs);
+                    (result != InstructionResult::Continue).then(|| FrameOutput {
+                        result,
+                        gas,
+                        output,
+                        address: Some(inputs.context.address),
+                        return_memory: Some(return_memory.clone()),
+                    })
+                }
+                InterpreterAction::Create { inputs } => {
+                    let (result, address, gas, output) =
+                        self.inspector.create(&mut self.data, inputs);
+                    (result != InstructionResult::Continue).then_some(FrameOutput {
+                        result,
+                        gas,
+                        output,
+                        address,
+                        return_memory: None,
+                    })
+                }
+            };
+            if let Some(output) = output {
+                execution.next = Next::Return(output);
+                return Ok(());
+            }
+        }
+
+        let mark = self.data.journaled_state.mark();
+        let gas_frames = self.data.env.cfg.gas_frames;
+        if gas_frames {
+            self.gas_frames.push(GasFrame::default());
+        }
+        let entered = match &mut action {
+            InterpreterAction::Call { inputs, .. } => self
+                .enter_call(inputs)
+                .map(|prepared| (prepared.contract, prepared.gas, prepared.checkpoint, None))
+                .map_err(Ok),
+            InterpreterAction::Create { inputs } => self
+                .prepare_create(inputs)
+                .map(|prepared| {
+                    let address = Some(prepared.created_address);
+                    (
+                        prepared.contract,
+                        prepared.gas,
+                        prepared.checkpoint,
+                        address,
+                    )
+                })
+                .map_err(Err),
+        };
+        if let Some(error) = self.data.error.take() {
+            self.data.journaled_state.revert_to(mark);
+            if gas_frames {
+                self.gas_frames.pop();
+            }
+            execution.next = Next::Enter {
+                action,
+                inspected: true,
+            };
+            return Err(EVMError::Database(error));
+        }
+
+        match (entered, action) {
+            (Ok((contract, gas, checkpoint, created_address)), action) => {
+                let is_static = match &action {
+                    InterpreterAction::Call { inputs, .. } => inputs.is_static,
+                    InterpreterAction::Create { .. } => false,
+                };
+                let mut interpreter = self.new_interpreter(contract, gas.limit(), is_static);
+                interpreter.suspend_calls = true;
+                execution.frames.push(Frame {
+                    interpreter,
+                    action,
+                    checkpoint,
+                    created_address,
+                });
+            }
+            (
+                Err(Ok(ret)),
+                InterpreterAction::Call {
+                    inputs,
+                    return_memory,
+                },
+            ) => {
+                let (result, gas, output) = self.call_end(&inputs, ret);
+                execution.next = Next::Return(FrameOutput {
+                    result,
+                    gas,
+                    output,
+                    address: Some(inputs.context.address),
+                    return_memory: Some(return_memory),
+                });
+            }
+            (Err(Err(ret)), InterpreterAction::Create { inputs }) => {
+                let (result, address, gas, output) = self.create_end(&inputs, ret);
+                execution.next = Next::Return(FrameOutput {
+                    result,
+                    gas,
+                    output,
+                    address,
+                    return_memory: None,
+                });
+            }
+            _ => unreachable!("result is of the same frame kind"),
+        }
+        Ok(())
+    }
+
+    /// Execute next opcode of the last frame.
+    fn run_step(&mut self, execution: &mut Execution) -> Result<(), EVMError<DB::Error>> {
+        let interpreter = &mut execution
+            .frames
+            .last_mut()
+            .expect("frame is running")
+            .interpreter;
+        if INSPECT && !execution.step_inspected {
+            let ret = self.inspector.step(interpreter, &mut self.data);
+            if ret != InstructionResult::Continue {
+                self.finish_frame(execution, ret);
+                return Ok(());
+            }
+        }
+        execution.step_inspected = false;
+
+        let opcode = interpreter.current_opcode();
+        let is_custom = self
+            .instruction_table
+            .is_some_and(|table| table.get(opcode).is_some());
+        let snapshot = (is_custom || reads_state(opcode)).then(|| {
+            StepSnapshot::new(
+                interpreter,
+                &self.data.journaled_state,
+                self.log_data_size,
+                is_custom,
+            )
+        });
+        match self.instruction_table {
+            Some(table) => interpreter.step_with_table::<Self, GSPEC>(table, self),
+            None => interpreter.step::<Self, GSPEC>(self),
+        }
+        if let Some(snapshot) = snapshot {
+            if let Some(error) = self.data.error.take() {
+                snapshot.restore(
+                    interpreter,
+                    &mut self.data.journaled_state,
+                    &mut self.log_data_size,
+                );
+                execution.step_inspected = INSPECT;
+                return Err(EVMError::Database(error));
+            }
+        }
+
+        if interpreter.instruction_result == InstructionResult::CallOrCreate {
+            let action = interpreter
+                .next_action
+                .take()
+                .expect("call or create is suspended");
+            execution.next = Next::Enter {
+                action,
+                inspected: false,
+            };
+            return Ok(());
+        }
+        self.step_end(execution);
+        Ok(())
+    }
+
+    /// Let the inspector see the executed opcode and finish the frame if it stopped.
+    fn step_end(&mut self, execution: &mut Execution) {
+        let interpreter = &mut execution
+            .frames
+            .last_mut()
+            .expect("frame is running")
+            .interpreter;
+        let mut exit_reason = interpreter.instruction_result;
+        if INSPECT {
+            let ret = self
+                .inspector
+                .step_end(interpreter, &mut self.data, exit_reason);
+            if ret != InstructionResult::Continue {
+                exit_reason = ret;
+            }
+        }
+        if exit_reason != InstructionResult::Continue {
+            self.finish_frame(execution, exit_reason);
+        }
+    }
+
+    /// Finish the last frame, its output is returned next.
+    fn finish_frame(&mut self, execution: &mut Execution, exit_reason: InstructionResult) {
+        let Frame {
+            mut interpreter,
+            action,
+            checkpoint,
+            created_address,
+        } = execution.frames.pop().expect("frame is running");
+        if matches!(SuccessOrHalt::from(exit_reason), SuccessOrHalt::Halt(_)) {
+            self.record_halt(&interpreter);
+        }
+        let output = match action {
+            InterpreterAction::Call {
+                inputs,
+                return_memory,
+            } => {
+                let ret = CallResult {
+                    result: exit_reason,
+                    gas: interpreter.gas,
+                    return_value: interpreter.return_value(),
+                };
+                let ret = self.call_return(checkpoint, ret);
+                let (result, gas, output) = self.call_end(&inputs, ret);
+                FrameOutput {
+                    result,
+                    gas,
+                    output,
+                    address: Some(inputs.context.address),
+                    return_memory: Some(return_memory),
+                }
+            }
+            InterpreterAction::Create { inputs } => {
+                let created_address = created_address.expect("create frame has an address");
+                let ret = self.create_return(
+                    &inputs,
+                    created_address,
+                    checkpoint,
+                    exit_reason,
+                    &mut interpreter,
+                );
+                let (result, address, gas, output) = self.create_end(&inputs, ret);
+                FrameOutput {
+                    result,
+                    gas,
+                    output,
+                    address,
+                    return_memory: None,
+                }
+            }
+        };
+        execution.next = Next::Return(output);
+    }
+
+    /// Give output of a finished frame to the call or create opcode of its parent.
+    fn return_to_parent(&mut self, execution: &mut Execution, output: FrameOutput) {
+        let interpreter = &mut execution
+            .frames
+            .last_mut()
+            .expect("parent is running")
+            .interpreter;
+        match output.return_memory {
+            Some(return_memory) => interpreter.insert_call_output(
+                output.result,
+                output.gas,
+                output.output,
+                return_memory,
+            ),
+            None => interpreter.insert_create_output(
+                output.result,
+                output.address,
+                output.gas,
+                output.output,
+            ),
+        }
+        self.step_end(execution);
+    }
+
+    /// Finish the transaction with output of its first frame.
+    fn finish_execution(
+        &mut self,
+        execution: &mut Execution,
+        output: FrameOutput,
+    ) -> EVMResult<DB::Error> {
+        let mark = self.data.journaled_state.mark();
+        let result = match &output.return_memory {
+            Some(_) => Output::Call(output.output.clone()),
+            None => Output::Create(output.output.clone(), output.address),
+        };
+        let is_retryable = output.result != InstructionResult::FatalExternalError;
+        match self.finish_transact(&execution.tx, output.result, output.gas, result) {
+            // only rewards of the beneficiaries are loaded, nothing is finalized yet.
+            Err(EVMError::Database(error)) if is_retryable => {
+                self.data.journaled_state.revert_to(mark);
+                execution.next = Next::Return(output);
+                Err(EVMError::Database(error))
+            }
+            out => out,
+        }
+    }
+}
+
+/// Built-in opcodes that read the database.
+fn reads_state(opcode: u8) -> bool {
+    matches!(
+        opcode,
+        opcode::BALANCE
+            | opcode::EXTCODESIZE
+            | opcode::EXTCODECOPY
+            | opcode::EXTCODEHASH
+            | opcode::BLOCKHASH
+            | opcode::SELFBALANCE
+            | opcode::SLOAD
+            | opcode::SSTORE
+            | opcode::CREATE
+            | opcode::CREATE2
+            | opcode::CALL
+            | opcode::CALLCODE
+            | opcode::DELEGATECALL
+            | opcode::STATICCALL
+            | opcode::SELFDESTRUCT
+    )
+}
+
+/// State of the EVM given to a [StatefulPrecompile](crate::primitives::StatefulPrecompile),
+/// storage access and logs are inspected same as ones of the interpreter.
+struct StatefulPrecompileState<'a, 'b, DB: Database, const INSPECT: bool> {
+    data: &'a mut EVMData<'b, DB>,
+    inspector: &'a mut dyn Inspector<DB>,
+    log_data_size: &'a mut usize,
+    context: PrecompileContext,
+}
+
+impl<DB: Database, const INSPECT: bool> StatefulPrecompileState<'_, '_, DB, INSPECT> {
+    /// Load account, keeping error of the database.
+    fn load_account(&mut self, address: B160) -> Result<&mut Account, PrecompileError> {
+        match self
+            .data
+            .journaled_state
+            .load_account(address, self.data.db)
+        {
+            Ok((account, _)) => Ok(account),
+            Err(e) => {
+                self.data.error = Some(e);
+                Err(PrecompileError::Database)
+            }
+        }
+    }
+}
+
+impl<DB: Database, const INSPECT: bool> PrecompileState
+    for StatefulPrecompileState<'_, '_, DB, INSPECT>
+{
+    fn env(&self) -> &Env {
+        self.data.env
+    }
+
+    fn context(&self) -> &PrecompileContext {
+        &self.context
+    }
+
+    fn sload(&mut self, address: B160, index: U256) -> Result<U256, PrecompileError> {
+        self.load_account(address)?;
+        let (value, is_cold) = self
+            .data
+            .journaled_state
+            .sload(address, index, self.data.db)
+            .map_err(|e| {
+                self.data.error = Some(e);
+                PrecompileError::Database
+            })?;
+        if INSPECT {
+            self.inspector
+                .sload(self.data, &address, index, value, is_cold);
+        }
+        Ok(value)
+    }
+
+    fn sstore(&mut self, address: B160, index: U256, value: U256) -> Result<(), PrecompileError> {
+        if self.context.is_static {
+            return Err(PrecompileError::StateChangeDuringStaticCall);
+        }
+        self.load_account(address)?;
+        // touched account is part of the changed state.
+        self.data.journaled_state.touch(&address);
+        let (original, present, new, is_cold) = self
+            .data
+            .journaled_state
+            .sstore(address, index, value, self.data.db)
+            .map_err(|e| {
+                self.data.error = Some(e);
+                PrecompileError::Database
+            })?;
+        if INSPECT {
+            let write = StorageWrite {
+                original,
+                present,
+                new,
+                is_cold,
+            };
+            self.inspector.sstore(self.data, &address, index, &write);
+        }
+        Ok(())
+    }
+
+    fn balance(&mut self, address: B160) -> Result<U256, PrecompileError> {
+        Ok(self.load_account(address)?.info.balance)
+    }
+
+    fn log(&mut self, topics: Vec<B256>, data: Bytes) -> Result<(), PrecompileError> {
+        if self.context.is_static {
+            return Err(PrecompileError::StateChangeDuringStaticCall);
+        }
+        let address = self.context.address;
+        if INSPECT {
+            self.inspector.log(self.data, &address, &topics, &data);
+        }
+        *self.log_data_size += data.len();
+        self.data.journaled_state.log(Log {
+            address,
+            topics,
+            data,
+        });
+        Ok(())
+    }
 }
 
 impl<'a, GSPEC: Spec, DB: Database + 'a, const INSPECT: bool> Host
@@ -707,12 +1961,24 @@ impl<'a, GSPEC: Spec, DB: Database + 'a, const INSPECT: bool> Host
         self.data.env
     }
 
+    fn env_override(&mut self, value: EnvValue, address: B160, caller: B160) -> Option<U256> {
+        let frame = EnvFrame {
+            address,
+            caller,
+            depth: self.data.journaled_state.depth(),
+        };
+        self.data.env.cfg.env_overrides.env_value(value, &frame)
+    }
+
     fn block_hash(&mut self, number: U256) -> Option<B256> {
-        self.data
-            .db
-            .block_hash(number)
-            .map_err(|e| self.data.error = Some(e))
-            .ok()
+        let current = u64::try_from(self.data.env.block.number).unwrap_or(u64::MAX);
+        // blocks that are not before the current one have no hash.
+        let number = match u64::try_from(number) {
+            Ok(number) if number < current => number,
+            _ => return Some(B256::zero()),
+        };
+        let provider = self.data.env.cfg.block_hashes.clone();
+        provider.block_hash(&mut self.data, GSPEC::SPEC_ID, number, current)
     }
 
     fn load_account(&mut self, address: B160) -> Option<(bool, bool)> {
@@ -764,13 +2030,33 @@ impl<'a, GSPEC: Spec, DB: Database + 'a, const INSPECT: bool> Host
         Some((acc.info.code_hash, is_cold))
     }
 
+    fn delegation(&mut self, address: B160) -> Option<Option<(B160, bool)>> {
+        let (code, _) = self.code(address)?;
+        let Some(delegated) = code.delegation_address() else {
+            return Some(None);
+        };
+        let (_, is_cold) = self
+            .data
+            .journaled_state
+            .load_account(delegated, self.data.db)
+            .map_err(|e| self.data.error = Some(e))
+            .ok()?;
+        Some(Some((delegated, is_cold)))
+    }
+
     fn sload(&mut self, address: B160, index: U256) -> Option<(U256, bool)> {
         // account is always hot. reference on that statement https://eips.ethereum.org/EIPS/eip-2929 see `Note 2:`
-        self.data
+        let (value, is_cold) = self
+            .data
             .journaled_state
             .sload(address, index, self.data.db)
             .map_err(|e| self.data.error = Some(e))
-            .ok()
+            .ok()?;
+        if INSPECT {
+            self.inspector
+                .sload(&mut self.data, &address, index, value, is_cold);
+        }
+        Some((value, is_cold))
     }
 
     fn sstore(
@@ -779,17 +2065,46 @@ impl<'a, GSPEC: Spec, DB: Database + 'a, const INSPECT: bool> Host
         index: U256,
         value: U256,
     ) -> Option<(U256, U256, U256, bool)> {
-        self.data
+        let (original, present, new, is_cold) = self
+            .data
             .journaled_state
             .sstore(address, index, value, self.data.db)
             .map_err(|e| self.data.error = Some(e))
-            .ok()
+            .ok()?;
+        if INSPECT {
+            let write = StorageWrite {
+                original,
+                present,
+                new,
+                is_cold,
+            };
+            self.inspector
+                .sstore(&mut self.data, &address, index, &write);
+        }
+        Some((original, present, new, is_cold))
+    }
+
+    fn tload(&mut self, address: B160, index: U256) -> U256 {
+        let value = self.data.journaled_state.tload(address, index);
+        if INSPECT {
+            self.inspector.tload(&mut self.data, &address, index, value);
+        }
+        value
+    }
+
+    fn tstore(&mut self, address: B160, index: U256, value: U256) {
+        if INSPECT {
+            self.inspector
+                .tstore(&mut self.data, &address, index, value);
+        }
+        self.data.journaled_state.tstore(address, index, value)
     }
 
     fn log(&mut self, address: B160, topics: Vec<B256>, data: Bytes) {
         if INSPECT {
             self.inspector.log(&mut self.data, &address, &topics, &data);
         }
+        self.log_data_size += data.len();
         let log = Log {
             address,
             topics,
@@ -798,13 +2113,17 @@ impl<'a, GSPEC: Spec, DB: Database + 'a, const INSPECT: bool> Host
         self.data.journaled_state.log(log);
     }
 
+    fn log_data_size(&mut self) -> usize {
+        self.log_data_size
+    }
+
     fn selfdestruct(&mut self, address: B160, target: B160) -> Option<SelfDestructResult> {
         if INSPECT {
             self.inspector.selfdestruct(address, target);
         }
         self.data
             .journaled_state
-            .selfdestruct(address, target, self.data.db)
+            .selfdestruct::<GSPEC, _>(address, target, self.data.db)
             .map_err(|e| self.data.error = Some(e))
             .ok()
     }
@@ -820,19 +2139,18 @@ impl<'a, GSPEC: Spec, DB: Database + 'a, const INSPECT: bool> Host
                 return (ret, address, gas, out);
             }
         }
-        let ret = self.create_inner(inputs);
-        if INSPECT {
-            self.inspector.create_end(
-                &mut self.data,
-                inputs,
-                ret.result,
-                ret.created_address,
-                ret.gas,
-                ret.return_value,
-            )
-        } else {
-            (ret.result, ret.created_address, ret.gas, ret.return_value)
+        if self.data.env.cfg.gas_frames {
+            self.gas_frames.push(GasFrame::default());
         }
+        let frame = phase!(
+            "create",
+            caller = ?inputs.caller,
+            depth = self.data.journaled_state.depth(),
+            gas_limit = inputs.gas_limit
+        );
+        let ret = self.create_inner(inputs);
+        frame.end_with_gas(ret.result, ret.gas.spend());
+        self.create_end(inputs, ret)
     }
 
     fn call(&mut self, inputs: &mut CallInputs) -> (InstructionResult, Gas, Bytes) {
@@ -842,17 +2160,1224 @@ impl<'a, GSPEC: Spec, DB: Database + 'a, const INSPECT: bool> Host
                 return (ret, gas, out);
             }
         }
+        if self.data.env.cfg.gas_frames {
+            self.gas_frames.push(GasFrame::default());
+        }
+        let frame = phase!(
+            "call",
+            address = ?inputs.contract,
+            depth = self.data.journaled_state.depth(),
+            gas_limit = inputs.gas_limit
+        );
         let ret = self.call_inner(inputs);
-        if INSPECT {
-            self.inspector.call_end(
-                &mut self.data,
-                inputs,
-                ret.gas,
-                ret.result,
-                ret.return_value,
-            )
+        frame.end_with_gas(ret.result, ret.gas.spend());
+        self.call_end(inputs, ret)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::primitives::{
+        create2_address, create_address, hex_literal::hex, AccountChange, AccountInfo,
+        AddressFilter, AddressFilterAction, Bytecode, CodeHasher, CreateCollisionPolicy,
+        CreateScheme, EVMError, ExecutionResult, Halt, InvalidTransaction, Output,
+        SignedAuthorization, SpecId, StorageSlot, TransactTo, B160, B256, U256,
+    };
+    use crate::{Database, InMemoryDB};
+
+    const CALLER: B160 = B160([0x10; 20]);
+    const CONTRACT: B160 = B160([0x20; 20]);
+    const DENIED: B160 = B160([0x30; 20]);
+
+    /// Contract calls `DENIED` and returns call success flag, or selfdestructs to it.
+    fn run(filter: AddressFilter, selfdestruct: bool) -> ExecutionResult {
+        let code = if selfdestruct {
+            let mut code = hex!("73").to_vec();
+            code.extend_from_slice(&DENIED.0);
+            code.push(0xff);
+            code
         } else {
-            (ret.result, ret.gas, ret.return_value)
+            // CALL(gas, DENIED, 0, 0, 0, 0, 0) MSTORE(0, success) RETURN(0, 32)
+            let mut code = hex!("600060006000600060007f").to_vec();
+            code.extend_from_slice(&[0; 12]);
+            code.extend_from_slice(&DENIED.0);
+            code.extend_from_slice(&hex!("5af160005260206000f3"));
+            code
+        };
+        let mut db = InMemoryDB::default();
+        db.insert_account_info(
+            CONTRACT,
+            AccountInfo::new(U256::ZERO, 0, Bytecode::new_raw(code.into())),
+        );
+
+        let mut evm = crate::new();
+        evm.database(db);
+        evm.env.cfg.address_filter = filter;
+        evm.env.tx.caller = CALLER;
+        evm.env.tx.transact_to = TransactTo::Call(CONTRACT);
+        evm.transact().unwrap().result
+    }
+
+    #[test]
+    fn address_filter_call() {
+        let deny = |action| AddressFilter::deny([DENIED], action);
+        let success_flag = |result: ExecutionResult| result.output().unwrap()[31];
+
+        assert_eq!(success_flag(run(AddressFilter::default(), false)), 1);
+        assert_eq!(success_flag(run(deny(AddressFilterAction::NoOp), false)), 1);
+        assert_eq!(success_flag(run(deny(AddressFilterAction::Halt), false)), 0);
+        // first call is denied
+        let allow_only_denied = AddressFilter::allow([DENIED], AddressFilterAction::Halt);
+        assert!(matches!(
+            run(allow_only_denied, false),
+            ExecutionResult::Halt {
+                reason: Halt::AddressDenied,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn address_filter_selfdestruct() {
+        assert!(matches!(
+            run(AddressFilter::default(), true),
+            ExecutionResult::Success { .. }
+        ));
+        assert!(matches!(
+            run(
+                AddressFilter::deny([DENIED], AddressFilterAction::Halt),
+                true
+            ),
+            ExecutionResult::Halt {
+                reason: Halt::AddressDenied,
+                ..
+            }
+        ));
+        let result = run(
+            AddressFilter::deny([DENIED], AddressFilterAction::NoOp),
+            true,
+        );
+        assert!(matches!(result, ExecutionResult::Success { .. }));
+    }
+
+    #[test]
+    fn address_filter_create() {
+        // MSTORE(0, CREATE(0, 0, 0)) RETURN(0, 32)
+        let code = hex!("600060006000f060005260206000f3");
+        let mut db = InMemoryDB::default();
+        db.insert_account_info(
+            CONTRACT,
+            AccountInfo::new(U256::ZERO, 1, Bytecode::new_raw(code.to_vec().into())),
+        );
+        let mut evm = crate::new();
+        evm.database(db);
+        evm.env.tx.caller = CALLER;
+        evm.env.tx.transact_to = TransactTo::Call(CONTRACT);
+        let created = create_address(CONTRACT, 1);
+        let output = evm.transact().unwrap().result.into_output().unwrap();
+        assert_eq!(output[12..], created.0);
+
+        // denied create succeeds without an address.
+        evm.env.cfg.address_filter = AddressFilter::deny([created], AddressFilterAction::NoOp);
+        let result = evm.transact().unwrap().result;
+        assert!(result.is_success());
+        assert_eq!(result.output().unwrap()[..], [0; 32]);
+
+        evm.env.cfg.address_filter =
+            AddressFilter::deny([create_address(CALLER, 0)], AddressFilterAction::NoOp);
+        evm.env.tx.transact_to = TransactTo::Create(CreateScheme::Create);
+        let result = evm.transact().unwrap().result;
+        assert!(matches!(
+            result,
+            ExecutionResult::Success {
+                output: Output::Create(_, None),
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn halt_context() {
+        use crate::interpreter::opcode;
+        use crate::primitives::HaltContext;
+
+        let inner = B160([0x40; 20]);
+        // POP(CALL(GAS, inner, 0, 0, 0, 0, 0)) INVALID
+        let mut code = hex!("6000600060006000600073").to_vec();
+        code.extend_from_slice(&inner.0);
+        code.extend_from_slice(&hex!("5af150fe"));
+        let mut db = InMemoryDB::default();
+        db.insert_account_info(
+            CONTRACT,
+            AccountInfo::new(U256::ZERO, 0, Bytecode::new_raw(code.into())),
+        );
+        // JUMP(1)
+        db.insert_account_info(
+            inner,
+            AccountInfo::new(
+                U256::ZERO,
+                0,
+                Bytecode::new_raw(hex!("600156").to_vec().into()),
+            ),
+        );
+        let mut evm = crate::new();
+        evm.database(db);
+        evm.env.tx.caller = CALLER;
+        evm.env.tx.transact_to = TransactTo::Call(inner);
+        let out = evm.transact().unwrap();
+        assert!(matches!(out.result, ExecutionResult::Halt { .. }));
+        let jump = HaltContext {
+            address: inner,
+            depth: 1,
+            pc: 2,
+            opcode: opcode::JUMP,
+        };
+        assert_eq!(out.halt, Some(jump));
+
+        // halt of the inner call doesn't halt the transaction.
+        evm.env.tx.transact_to = TransactTo::Call(CONTRACT);
+        let out = evm.transact().unwrap();
+        let invalid = HaltContext {
+            address: CONTRACT,
+            depth: 1,
+            pc: 34,
+            opcode: opcode::INVALID,
+        };
+        assert_eq!(out.halt, Some(invalid));
+    }
+
+    #[test]
+    fn env_overrides() {
+        use crate::primitives::{EnvFrame, EnvOverrides, EnvValue};
+
+        let inner = B160([0x40; 20]);
+        // MSTORE(0, TIMESTAMP) CALL(GAS, inner, 0, 0, 0, 32, 32) RETURN(0, 64)
+        let mut code = hex!("4260005260206020600060006000 73").to_vec();
+        code.extend_from_slice(&inner.0);
+        code.extend_from_slice(&hex!("5af150 60406000f3"));
+        // MSTORE(0, TIMESTAMP) RETURN(0, 32)
+        let inner_code = hex!("42600052 60206000f3");
+        let mut db = InMemoryDB::default();
+        db.insert_account_info(
+            CONTRACT,
+            AccountInfo::new(U256::ZERO, 0, Bytecode::new_raw(code.into())),
+        );
+        db.insert_account_info(
+            inner,
+            AccountInfo::new(U256::ZERO, 0, Bytecode::new_raw(inner_code.to_vec().into())),
+        );
+        let mut evm = crate::new();
+        evm.database(db);
+        evm.env.block.timestamp = U256::from(1);
+        evm.env.tx.caller = CALLER;
+        evm.env.tx.transact_to = TransactTo::Call(CONTRACT);
+        let timestamps = |evm: &mut crate::EVM<InMemoryDB>| {
+            let output = evm.transact().unwrap().result.into_output().unwrap();
+            (
+                U256::from_be_slice(&output[..32]),
+                U256::from_be_slice(&output[32..]),
+            )
+        };
+        assert_eq!(timestamps(&mut evm), (U256::from(1), U256::from(1)));
+
+        // only the subcall sees another time.
+        evm.env.cfg.env_overrides = EnvOverrides::new(move |value, frame: &EnvFrame| {
+            (value == EnvValue::Timestamp && frame.address == inner).then(|| {
+                assert_eq!((frame.caller, frame.depth), (CONTRACT, 2));
+                U256::from(2)
+            })
+        });
+        assert_eq!(timestamps(&mut evm), (U256::from(1), U256::from(2)));
+    }
+
+    #[test]
+    fn custom_code_hasher() {
+        fn length_hash(code: &[u8]) -> B256 {
+            B256::from_low_u64_be(code.len() as u64)
+        }
+        // MSTORE8(0, 0xff) RETURN(0, 1)
+        let init_code = hex!("60ff60005360016000f3");
+        let salt = U256::from(7);
+
+        let mut evm = crate::new();
+        evm.database(InMemoryDB::default());
+        evm.env.cfg.code_hasher = CodeHasher(length_hash);
+        evm.env.tx.caller = CALLER;
+        evm.env.tx.transact_to = TransactTo::Create(CreateScheme::Create2 { salt });
+        evm.env.tx.data = init_code.to_vec().into();
+        let state = evm.transact().unwrap().state;
+
+        let address = create2_address(CALLER, length_hash(&init_code), salt);
+        assert_eq!(state[&address].info.code_hash, B256::from_low_u64_be(1));
+    }
+
+    #[test]
+    fn return_and_log_data_limits() {
+        // LOG0(0, 32) LOG0(0, 32) REVERT(0, 33)
+        let code = hex!("60206000a060206000a060216000fd");
+        let mut db = InMemoryDB::default();
+        db.insert_account_info(
+            CONTRACT,
+            AccountInfo::new(U256::ZERO, 0, Bytecode::new_raw(code.to_vec().into())),
+        );
+        let mut evm = crate::new();
+        evm.database(db);
+        evm.env.tx.caller = CALLER;
+        evm.env.tx.transact_to = TransactTo::Call(CONTRACT);
+        let mut run = |return_data, log_data| {
+            evm.env.cfg.limit_return_data_size = return_data;
+            evm.env.cfg.limit_log_data_size = log_data;
+            evm.transact().unwrap().result
+        };
+
+        assert!(matches!(run(None, None), ExecutionResult::Revert { .. }));
+        assert!(matches!(
+            run(Some(33), Some(64)),
+            ExecutionResult::Revert { .. }
+        ));
+        assert!(matches!(
+            run(Some(32), None),
+            ExecutionResult::Halt {
+                reason: Halt::ReturnDataLimit,
+                ..
+            }
+        ));
+        assert!(matches!(
+            run(None, Some(63)),
+            ExecutionResult::Halt {
+                reason: Halt::LogDataLimit,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn create_collision() {
+        let created = create_address(CALLER, 0);
+        let mut db = InMemoryDB::default();
+        let code = Bytecode::new_raw(hex!("6001").to_vec().into());
+        db.insert_account_info(created, AccountInfo::new(U256::from(3), 5, code.clone()));
+        db.insert_account_storage(created, U256::from(1), U256::from(7))
+            .unwrap();
+        let mut evm = crate::new();
+        evm.database(db);
+        evm.env.tx.caller = CALLER;
+        evm.env.tx.transact_to = TransactTo::Create(CreateScheme::Create);
+        // REVERT(0, 0)
+        evm.env.tx.data = hex!("60006000fd").to_vec().into();
+        let result = evm.transact().unwrap().result;
+        assert!(matches!(
+            result,
+            ExecutionResult::Halt {
+                reason: Halt::CreateCollision,
+                ..
+            }
+        ));
+
+        // reverted creation restores the account.
+        evm.env.cfg.create_collision = CreateCollisionPolicy::Overwrite;
+        let out = evm.transact().unwrap();
+        assert!(matches!(out.result, ExecutionResult::Revert { .. }));
+        let account = &out.state[&created];
+        assert!(!account.is_newly_created());
+        assert_eq!(account.info.nonce, 5);
+        assert_eq!(account.info.code_hash, code.hash());
+
+        // SSTORE(2, 1) RETURN(0, 1), code is `STOP`.
+        evm.env.tx.data = hex!("6001600255 60016000f3").to_vec().into();
+        assert!(evm.transact_commit().unwrap().is_success());
+        let db = evm.db().unwrap();
+        let info = db.basic(created).unwrap().unwrap();
+        assert_eq!(info.balance, U256::from(3));
+        assert_eq!(info.nonce, 1);
+        assert_eq!(info.code_hash, Bytecode::new_raw(vec![0].into()).hash());
+        assert_eq!(db.storage(created, U256::from(1)).unwrap(), U256::ZERO);
+        assert_eq!(db.storage(created, U256::from(2)).unwrap(), U256::from(1));
+    }
+
+    #[test]
+    fn touched_summary() {
+        let receiver = B160([0x40; 20]);
+        // SSTORE(0, 1) CALL(GAS, receiver, 1, 0, 0, 0, 0) CALL(GAS, DENIED, 0, 0, 0, 0, 0)
+        let mut code = hex!("6001600055600060006000600060017f").to_vec();
+        code.extend_from_slice(&[0; 12]);
+        code.extend_from_slice(&receiver.0);
+        code.extend_from_slice(&hex!("5af150600060006000600060007f"));
+        code.extend_from_slice(&[0; 12]);
+        code.extend_from_slice(&DENIED.0);
+        code.extend_from_slice(&hex!("5af15000"));
+        let mut db = InMemoryDB::default();
+        db.insert_account_info(
+            CONTRACT,
+            AccountInfo::new(U256::from(10), 0, Bytecode::new_raw(code.into())),
+        );
+        let mut evm = crate::new();
+        evm.database(db);
+        evm.env.tx.caller = CALLER;
+        evm.env.tx.transact_to = TransactTo::Call(CONTRACT);
+        assert_eq!(evm.transact().unwrap().touched, None);
+
+        evm.env.cfg.touched_summary = true;
+        let touched = evm.transact().unwrap().touched.unwrap();
+        assert_eq!(
+            touched.into_iter().collect::<Vec<_>>(),
+            [
+                (B160::zero(), AccountChange::Touched),
+                (CALLER, AccountChange::BalanceOnly),
+                (CONTRACT, AccountChange::StorageChanged),
+                (DENIED, AccountChange::Touched),
+                (receiver, AccountChange::BalanceOnly),
+            ]
+        );
+    }
+
+    #[test]
+    fn storage_diff() {
+        // SSTORE(0, 1) SLOAD(1) SSTORE(2, 3) SSTORE(2, 0) STOP
+        let code = hex!("6001600055600154 6003600255 6000600255 00");
+        let mut db = InMemoryDB::default();
+        db.insert_account_info(
+            CONTRACT,
+            AccountInfo::new(U256::ZERO, 0, Bytecode::new_raw(code.to_vec().into())),
+        );
+        db.insert_account_storage(CONTRACT, U256::from(1), U256::from(4))
+            .unwrap();
+        let mut evm = crate::new();
+        evm.database(db);
+        evm.env.tx.caller = CALLER;
+        evm.env.tx.transact_to = TransactTo::Call(CONTRACT);
+        assert_eq!(evm.transact().unwrap().storage_diff, None);
+
+        evm.env.cfg.storage_diff = true;
+        let diff = evm.transact().unwrap().storage_diff.unwrap();
+        // slot that was only read and slot that was restored are not changed.
+        assert_eq!(diff.keys().collect::<Vec<_>>(), [&CONTRACT]);
+        let changed = &diff[&CONTRACT];
+        assert!(!changed.destroyed);
+        assert_eq!(
+            changed.slots.iter().collect::<Vec<_>>(),
+            [(
+                &U256::ZERO,
+                &StorageSlot {
+                    original_value: U256::ZERO,
+                    present_value: U256::from(1),
+                }
+            )]
+        );
+    }
+
+    #[test]
+    #[cfg(not(feature = "no_gas_measuring"))]
+    fn gas_frames() {
+        let child = B160([0x40; 20]);
+        // MSTORE(0, 1) REVERT(0, 0)
+        let child_code = hex!("600160005260006000fd");
+        // CALL(GAS, child, 0, 0, 0, 0, 0)
+        let mut code = hex!("600060006000600060007f").to_vec();
+        code.extend_from_slice(&[0; 12]);
+        code.extend_from_slice(&child.0);
+        code.extend_from_slice(&hex!("5af15000"));
+        let mut db = InMemoryDB::default();
+        db.insert_account_info(
+            CONTRACT,
+            AccountInfo::new(U256::ZERO, 0, Bytecode::new_raw(code.into())),
+        );
+        db.insert_account_info(
+            child,
+            AccountInfo::new(U256::ZERO, 0, Bytecode::new_raw(child_code.to_vec().into())),
+        );
+        let mut evm = crate::new();
+        evm.database(db);
+        evm.env.tx.caller = CALLER;
+        evm.env.tx.transact_to = TransactTo::Call(CONTRACT);
+        evm.env.tx.gas_limit = 100_000;
+        assert_eq!(evm.transact().unwrap().gas_frame, None);
+
+        evm.env.cfg.gas_frames = true;
+        let out = evm.transact().unwrap();
+        let frame = out.gas_frame.unwrap();
+        assert_eq!(frame.address, CONTRACT);
+        assert!(frame.success && !frame.is_create);
+        assert_eq!(frame.gas_limit, 100_000 - 21_000);
+        assert_eq!(frame.gas_used + 21_000, out.result.gas_used());
+        assert_eq!(frame.memory_cost, 0);
+        assert_eq!(frame.calls.len(), 1);
+        let call = &frame.calls[0];
+        assert_eq!(call.address, child);
+        assert!(!call.success);
+        assert_eq!(call.gas_used, 4 * 3 + 3 + 3);
+        assert_eq!(call.memory_cost, 3);
+        assert!(call.calls.is_empty());
+        assert!(frame.gas_used > call.gas_used);
+    }
+
+    #[test]
+    #[cfg(not(feature = "no_gas_measuring"))]
+    fn warm_accounts() {
+        // SLOAD(1) BALANCE(DENIED)
+        let mut code = hex!("6001547f").to_vec();
+        code.extend_from_slice(&[0; 12]);
+        code.extend_from_slice(&DENIED.0);
+        code.extend_from_slice(&hex!("3100"));
+        let mut db = InMemoryDB::default();
+        db.insert_account_info(
+            CONTRACT,
+            AccountInfo::new(U256::ZERO, 0, Bytecode::new_raw(code.into())),
+        );
+        let mut evm = crate::new();
+        evm.database(db);
+        evm.env.tx.caller = CALLER;
+        evm.env.tx.transact_to = TransactTo::Call(CONTRACT);
+        let cold = evm.transact().unwrap().result.gas_used();
+
+        evm.env.cfg.warm_accounts = vec![(CONTRACT, vec![U256::from(1)]), (DENIED, vec![])];
+        let warm = evm.transact().unwrap().result.gas_used();
+        // cold SLOAD and account access cost 2100 and 2600 instead of 100.
+        assert_eq!(cold - warm, 2000 + 2500);
+
+        // slot stays warm without the other account.
+        evm.env.cfg.warm_accounts = vec![(CONTRACT, vec![U256::from(1)])];
+        assert_eq!(evm.transact().unwrap().result.gas_used(), warm + 2500);
+    }
+
+    #[test]
+    fn transient_storage() {
+        // TSTORE(1, 7), call itself with one byte of data that makes it TSTORE(1, 9) and
+        // revert, then return TLOAD(1).
+        let code = hex!(
+            "36602257600760015d60006000600160006000305af15060015c60005260206000f3"
+            "5b600960015d60006000fd"
+        );
+        let mut db = InMemoryDB::default();
+        db.insert_account_info(
+            CONTRACT,
+            AccountInfo::new(U256::ZERO, 0, Bytecode::new_raw(code.to_vec().into())),
+        );
+        let mut evm = crate::new();
+        evm.database(db);
+        evm.env.cfg.spec_id = SpecId::CANCUN;
+        evm.env.tx.caller = CALLER;
+        evm.env.tx.transact_to = TransactTo::Call(CONTRACT);
+        let out = evm.transact().unwrap().result;
+        assert_eq!(out.output().unwrap()[..], U256::from(7).to_be_bytes::<32>());
+
+        // TSTORE(1, 7) TLOAD(1) STOP, both cost as a warm read.
+        #[cfg(not(feature = "no_gas_measuring"))]
+        {
+            let code = Bytecode::new_raw(hex!("600760015d60015c00").to_vec().into());
+            let mut db = InMemoryDB::default();
+            db.insert_account_info(CONTRACT, AccountInfo::new(U256::ZERO, 0, code));
+            let mut evm = crate::new();
+            evm.database(db);
+            evm.env.cfg.spec_id = SpecId::CANCUN;
+            evm.env.tx.caller = CALLER;
+            evm.env.tx.transact_to = TransactTo::Call(CONTRACT);
+            let gas_used = evm.transact().unwrap().result.gas_used();
+            assert_eq!(gas_used, 21_000 + 3 * 3 + 2 * 100);
         }
+
+        evm.env.cfg.spec_id = SpecId::SHANGHAI;
+        assert!(matches!(
+            evm.transact().unwrap().result,
+            ExecutionResult::Halt {
+                reason: Halt::NotActivated,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    #[cfg(not(feature = "no_gas_measuring"))]
+    fn state_clear_rules() {
+        // CALL(0xffff, DENIED, 0, 0, 0, 0, 0) CREATE(0, 0, 0)
+        let mut code = hex!("60006000600060006000").to_vec();
+        code.push(0x73);
+        code.extend_from_slice(&DENIED.0);
+        code.extend_from_slice(&hex!("61fffff150600060006000f000"));
+        let mut db = InMemoryDB::default();
+        db.insert_account_info(
+            CONTRACT,
+            AccountInfo::new(U256::ZERO, 0, Bytecode::new_raw(code.into())),
+        );
+        let mut evm = crate::new();
+        evm.database(db);
+        evm.env.tx.caller = CALLER;
+        evm.env.tx.gas_limit = 1_000_000;
+        evm.env.tx.transact_to = TransactTo::Call(CONTRACT);
+        let run = |evm: &mut crate::EVM<InMemoryDB>| {
+            let out = evm.transact().unwrap();
+            let created = out
+                .state
+                .values()
+                .find(|account| account.is_newly_created())
+                .unwrap()
+                .info
+                .nonce;
+            (out.result.gas_used(), created)
+        };
+
+        let (gas_used, nonce) = run(&mut evm);
+        assert_eq!(nonce, 1);
+        evm.env.cfg.state_clear = Some(false);
+        // empty call target doesn't exist, so it is charged as new account.
+        assert_eq!(run(&mut evm), (gas_used + 25000, 0));
+
+        evm.env.cfg.spec_id = SpecId::HOMESTEAD;
+        let (gas_used, nonce) = run(&mut evm);
+        assert_eq!(nonce, 0);
+        evm.env.cfg.state_clear = Some(true);
+        assert_eq!(run(&mut evm), (gas_used - 25000, 1));
+    }
+
+    #[test]
+    fn selfdestruct_eip6780() {
+        // SELFDESTRUCT(DENIED)
+        let mut code = hex!("73").to_vec();
+        code.extend_from_slice(&DENIED.0);
+        code.push(0xff);
+        let balance = U256::from(100);
+        let mut db = InMemoryDB::default();
+        db.insert_account_info(
+            CONTRACT,
+            AccountInfo::new(balance, 0, Bytecode::new_raw(code.into())),
+        );
+        // SELFDESTRUCT(ADDRESS)
+        let to_self = B160([0x40; 20]);
+        db.insert_account_info(
+            to_self,
+            AccountInfo::new(balance, 0, Bytecode::new_raw(hex!("30ff").to_vec().into())),
+        );
+        let mut evm = crate::new();
+        evm.database(db);
+        evm.env.cfg.spec_id = SpecId::CANCUN;
+        evm.env.tx.caller = CALLER;
+        evm.env.tx.transact_to = TransactTo::Call(CONTRACT);
+
+        // only the balance is transferred from the existing contract.
+        let out = evm.transact().unwrap();
+        assert!(out.result.is_success());
+        assert!(!out.state[&CONTRACT].is_selfdestructed());
+        assert_eq!(out.state[&CONTRACT].info.balance, U256::ZERO);
+        assert_eq!(out.state[&DENIED].info.balance, balance);
+
+        evm.env.tx.transact_to = TransactTo::Call(to_self);
+        let out = evm.transact().unwrap();
+        assert!(!out.state[&to_self].is_selfdestructed());
+        assert_eq!(out.state[&to_self].info.balance, balance);
+
+        // contract created in the same transaction is still destroyed.
+        evm.env.tx.transact_to = TransactTo::Create(CreateScheme::Create);
+        evm.env.tx.data = hex!("30ff").to_vec().into();
+        let out = evm.transact().unwrap();
+        let created = create_address(CALLER, 0);
+        assert!(out.state[&created].is_selfdestructed());
+
+        evm.env.cfg.spec_id = SpecId::SHANGHAI;
+        evm.env.tx.transact_to = TransactTo::Call(CONTRACT);
+        evm.env.tx.data = Default::default();
+        let out = evm.transact().unwrap();
+        assert!(out.state[&CONTRACT].is_selfdestructed());
+        assert_eq!(out.state[&DENIED].info.balance, balance);
+    }
+
+    #[test]
+    fn selfdestruct_eip6780_touches_empty_target() {
+        // SELFDESTRUCT(DENIED) without balance.
+        let mut code = hex!("73").to_vec();
+        code.extend_from_slice(&DENIED.0);
+        code.push(0xff);
+        let mut db = InMemoryDB::default();
+        db.insert_account_info(
+            CONTRACT,
+            AccountInfo::new(U256::ZERO, 0, Bytecode::new_raw(code.into())),
+        );
+        db.insert_account_info(DENIED, AccountInfo::default());
+        let mut evm = crate::new();
+        evm.database(db);
+        evm.env.cfg.spec_id = SpecId::CANCUN;
+        evm.env.tx.caller = CALLER;
+        evm.env.tx.transact_to = TransactTo::Call(CONTRACT);
+
+        // empty target is touched, so EIP-161 removes it.
+        let out = evm.transact().unwrap();
+        assert!(out.result.is_success());
+        assert!(out.state[&DENIED].is_touched());
+        assert!(out.state[&DENIED].is_empty());
+    }
+
+    #[test]
+    #[cfg(not(feature = "no_gas_measuring"))]
+    fn custom_precompiles() {
+        use crate::primitives::PrecompileError;
+        use core::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        let reverse = B160([0xff; 20]);
+        let identity = B160::from_low_u64_be(4);
+        let calls = Arc::new(AtomicUsize::new(0));
+        let mut evm = crate::new();
+        evm.database(InMemoryDB::default());
+        let counter = calls.clone();
+        evm.env
+            .cfg
+            .custom_precompiles
+            .insert(reverse, move |input, gas_limit| {
+                counter.fetch_add(1, Ordering::Relaxed);
+                if gas_limit < 50 {
+                    return Err(PrecompileError::OutOfGas);
+                }
+                Ok((50, input.iter().rev().copied().collect()))
+            });
+        evm.env
+            .cfg
+            .custom_precompiles
+            .insert(identity, |_, _| Ok((0, vec![1])));
+        evm.env.tx.caller = CALLER;
+        evm.env.tx.data = hex!("0102").to_vec().into();
+
+        evm.env.tx.transact_to = TransactTo::Call(reverse);
+        let result = evm.transact().unwrap().result;
+        assert_eq!(result.output().unwrap()[..], hex!("0201"));
+        // intrinsic gas and gas of the precompile.
+        assert_eq!(result.gas_used(), 21000 + 2 * 16 + 50);
+        assert_eq!(calls.load(Ordering::Relaxed), 1);
+
+        evm.env.tx.transact_to = TransactTo::Call(identity);
+        let result = evm.transact().unwrap().result;
+        assert_eq!(result.output().unwrap()[..], [1]);
+    }
+
+    #[test]
+    fn transact_with() {
+        use crate::primitives::TxEnv;
+
+        let precompile = B160([0xff; 20]);
+        let mut db = InMemoryDB::default();
+        db.insert_account_info(CALLER, AccountInfo::from_balance(U256::from(100)));
+        let mut evm = crate::new();
+        evm.database(db);
+        evm.env
+            .cfg
+            .custom_precompiles
+            .insert(precompile, |_, _| Ok((0, vec![1])));
+        let tx = |nonce, to| TxEnv {
+            caller: CALLER,
+            transact_to: TransactTo::Call(to),
+            value: U256::from(10),
+            nonce: Some(nonce),
+            ..Default::default()
+        };
+
+        for nonce in 0..3 {
+            let result = evm.transact_commit_with(tx(nonce, CONTRACT)).unwrap();
+            assert!(result.is_success());
+        }
+        let out = evm.transact_with(tx(3, precompile)).unwrap();
+        assert_eq!(out.result.output().unwrap()[..], [1]);
+        assert_eq!(
+            evm.db().unwrap().accounts[&CONTRACT].info.balance,
+            U256::from(30)
+        );
+
+        // precompiles are prepared again when custom ones change.
+        evm.env
+            .cfg
+            .custom_precompiles
+            .insert(precompile, |_, _| Ok((0, vec![2])));
+        let out = evm.transact_with(tx(3, precompile)).unwrap();
+        assert_eq!(out.result.output().unwrap()[..], [2]);
+    }
+
+    #[test]
+    fn stateful_precompiles() {
+        use crate::primitives::{PrecompileError, PrecompileState};
+
+        // counter incremented by every call, it logs the new value.
+        let counter = B160([0xee; 20]);
+        let mut evm = crate::new();
+        let mut db = InMemoryDB::default();
+        // STATICCALL(GAS, counter, 0, 0, 0, 0) MSTORE(0, success) RETURN(0, 32)
+        let mut code = hex!("6000600060006000 73").to_vec();
+        code.extend_from_slice(&counter.0);
+        code.extend_from_slice(&hex!("5afa 600052 60206000f3"));
+        db.insert_account_info(
+            CONTRACT,
+            AccountInfo::new(U256::ZERO, 0, Bytecode::new_raw(code.into())),
+        );
+        evm.database(db);
+        evm.env.cfg.custom_precompiles.insert_stateful(
+            counter,
+            |state: &mut dyn PrecompileState, _: &[u8], _| {
+                let address = state.context().address;
+                let value = state.sload(address, U256::ZERO)? + U256::from(1);
+                state.sstore(address, U256::ZERO, value)?;
+                let output = value.to_be_bytes::<32>().to_vec();
+                state.log(Vec::new(), output.clone().into())?;
+                Ok::<_, PrecompileError>((5_000, output))
+            },
+        );
+        evm.env.tx.caller = CALLER;
+
+        evm.env.tx.transact_to = TransactTo::Call(counter);
+        let out = evm.transact_commit().unwrap();
+        assert_eq!(out.output().unwrap()[..], U256::from(1).to_be_bytes::<32>());
+        assert_eq!(out.logs().len(), 1);
+        let out = evm.transact().unwrap();
+        assert_eq!(
+            out.state[&counter].storage[&U256::ZERO].present_value,
+            U256::from(2)
+        );
+
+        // state can't be changed in static call.
+        evm.env.tx.transact_to = TransactTo::Call(CONTRACT);
+        let out = evm.transact().unwrap();
+        assert_eq!(out.result.output().unwrap()[..], [0; 32]);
+        assert!(out.result.logs().is_empty());
+    }
+
+    #[test]
+    #[cfg(not(feature = "no_gas_measuring"))]
+    fn blob_transaction() {
+        use crate::primitives::GAS_PER_BLOB;
+
+        // MSTORE(0, BLOBHASH(1)) MSTORE(32, BLOBBASEFEE) RETURN(0, 64)
+        let code = hex!("600149600052 4a602052 60406000f3");
+        let mut db = InMemoryDB::default();
+        db.insert_account_info(
+            CONTRACT,
+            AccountInfo::new(U256::ZERO, 0, Bytecode::new_raw(code.to_vec().into())),
+        );
+        let balance = U256::from(10u64.pow(18));
+        db.insert_account_info(CALLER, AccountInfo::new(balance, 0, Bytecode::new()));
+
+        let mut blob_hash = B256::repeat_byte(2);
+        blob_hash.0[0] = 1;
+        let mut evm = crate::new();
+        evm.database(db);
+        evm.env.block.set_blob_excess_gas_and_price(10_000_000);
+        let blob_gasprice = evm.env.block.get_blob_gasprice().unwrap();
+        assert!(blob_gasprice > 1);
+        evm.env.tx.caller = CALLER;
+        evm.env.tx.gas_limit = 100_000;
+        evm.env.tx.gas_price = U256::from(1);
+        evm.env.tx.transact_to = TransactTo::Call(CONTRACT);
+        evm.env.tx.blob_hashes = vec![B256::repeat_byte(1), blob_hash];
+        evm.env.tx.max_fee_per_blob_gas = Some(U256::from(blob_gasprice));
+
+        let out = evm.transact().unwrap();
+        let output = out.result.output().unwrap();
+        assert_eq!(output[..32], blob_hash.0);
+        assert_eq!(
+            U256::from_be_slice(&output[32..]),
+            U256::from(blob_gasprice)
+        );
+        // data fee is burned on top of gas.
+        let data_fee = U256::from(blob_gasprice * 2 * GAS_PER_BLOB);
+        assert_eq!(
+            out.state[&CALLER].info.balance,
+            balance - U256::from(out.result.gas_used()) - data_fee
+        );
+
+        evm.env.cfg.spec_id = SpecId::SHANGHAI;
+        assert!(evm.transact().is_err());
+    }
+
+    #[test]
+    #[cfg(all(feature = "optional_gas_charging", not(feature = "no_gas_measuring")))]
+    fn gas_charging_disabled() {
+        // MSTORE(0x100000, 1), memory of the first MiB alone costs more than two million gas.
+        let mut db = InMemoryDB::default();
+        db.insert_account_info(
+            CONTRACT,
+            AccountInfo::new(
+                U256::ZERO,
+                0,
+                Bytecode::new_raw(hex!("6001 62100000 52 00").to_vec().into()),
+            ),
+        );
+        let mut evm = crate::new();
+        evm.database(db);
+        evm.env.tx.caller = CALLER;
+        evm.env.tx.transact_to = TransactTo::Call(CONTRACT);
+        evm.env.tx.gas_price = U256::from(10);
+        evm.env.tx.gas_limit = 0;
+        assert!(evm.transact().is_err());
+
+        evm.env.cfg.disable_gas_charging = true;
+        let out = evm.transact().unwrap();
+        assert!(out.result.is_success());
+        assert_eq!(out.result.gas_used(), 21_000 + 3 + 3 + 3 + 2_195_587);
+        assert_eq!(out.state[&CALLER].info.balance, U256::ZERO);
+    }
+
+    #[test]
+    #[cfg(all(feature = "optional_gas_charging", not(feature = "no_gas_measuring")))]
+    fn gas_charging_disabled_frames() {
+        let target = B160([0x40; 20]);
+        // CALL(gas 100, target) STOP, the target writes SSTORE(0, 1) with more than 100 gas.
+        let mut code = hex!("5f5f5f5f5f 73").to_vec();
+        code.extend_from_slice(&target.0);
+        code.extend_from_slice(&hex!("6064 f1 00"));
+        let mut db = InMemoryDB::default();
+        db.insert_account_info(
+            CONTRACT,
+            AccountInfo::new(U256::ZERO, 0, Bytecode::new_raw(code.into())),
+        );
+        db.insert_account_info(
+            target,
+            AccountInfo::new(
+                U256::ZERO,
+                0,
+                Bytecode::new_raw(hex!("6001 5f 55 00").to_vec().into()),
+            ),
+        );
+        let mut evm = crate::new();
+        evm.database(db);
+        evm.env.tx.caller = CALLER;
+        evm.env.tx.transact_to = TransactTo::Call(CONTRACT);
+        let out = evm.transact().unwrap();
+        assert!(out.result.is_success());
+        assert!(!out.state[&target].storage.contains_key(&U256::ZERO));
+
+        // call is not limited by gas it is given.
+        evm.env.cfg.disable_gas_charging = true;
+        let out = evm.transact().unwrap();
+        assert!(out.result.is_success());
+        assert_eq!(
+            out.state[&target].storage[&U256::ZERO].present_value,
+            U256::from(1)
+        );
+
+        // runaway loop JUMPDEST PUSH0 JUMP is stopped by the cap.
+        evm.env.cfg.unmetered_gas_cap = 1_000_000;
+        let mut db = InMemoryDB::default();
+        db.insert_account_info(
+            CONTRACT,
+            AccountInfo::new(
+                U256::ZERO,
+                0,
+                Bytecode::new_raw(hex!("5b5f56").to_vec().into()),
+            ),
+        );
+        evm.database(db);
+        let out = evm.transact().unwrap();
+        assert!(matches!(
+            out.result,
+            ExecutionResult::Halt {
+                reason: Halt::OutOfGas(_),
+                gas_used: 1_000_000,
+            }
+        ));
+    }
+
+    #[test]
+    #[cfg(all(
+        feature = "optional_balance_check",
+        feature = "optional_block_gas_limit",
+        feature = "optional_nonce_check",
+        not(feature = "no_gas_measuring")
+    ))]
+    fn validation_disabled() {
+        let coinbase = B160([0x30; 20]);
+        let mut evm = crate::new();
+        evm.database(InMemoryDB::default());
+        evm.env.block.coinbase = coinbase;
+        evm.env.block.gas_limit = U256::from(30_000);
+        evm.env.tx.caller = CALLER;
+        evm.env.tx.transact_to = TransactTo::Call(CONTRACT);
+        evm.env.tx.value = U256::from(5);
+        evm.env.tx.gas_price = U256::from(10);
+        evm.env.tx.gas_limit = 50_000;
+        evm.env.tx.nonce = Some(7);
+        assert_eq!(
+            evm.transact().unwrap_err(),
+            EVMError::Transaction(InvalidTransaction::CallerGasLimitMoreThanBlock)
+        );
+        evm.env.cfg.disable_block_gas_limit = true;
+        assert_eq!(
+            evm.transact().unwrap_err(),
+            EVMError::Transaction(InvalidTransaction::NonceTooHigh { tx: 7, state: 0 })
+        );
+        evm.env.cfg.disable_nonce_check = true;
+        assert!(matches!(
+            evm.transact().unwrap_err(),
+            EVMError::Transaction(InvalidTransaction::LackOfFundForMaxFee { .. })
+        ));
+
+        // caller pays for gas at its price with balance it is given.
+        evm.env.cfg.disable_balance_check = true;
+        let out = evm.transact().unwrap();
+        assert!(out.result.is_success());
+        assert_eq!(out.state[&CALLER].info.nonce, 1);
+        assert_eq!(
+            out.state[&CALLER].info.balance,
+            U256::from((50_000 - 21_000) * 10)
+        );
+        assert_eq!(out.state[&CONTRACT].info.balance, U256::from(5));
+        assert_eq!(out.state[&coinbase].info.balance, U256::from(21_000 * 10));
+    }
+
+    #[test]
+    fn instruction_table() {
+        use crate::interpreter::{opcode, InstructionResult, InstructionTable};
+
+        // SELFDESTRUCT(DENIED)
+        let mut code = hex!("73").to_vec();
+        code.extend_from_slice(&DENIED.0);
+        code.push(opcode::SELFDESTRUCT);
+        let mut db = InMemoryDB::default();
+        db.insert_account_info(
+            CONTRACT,
+            AccountInfo::new(U256::from(10), 0, Bytecode::new_raw(code.into())),
+        );
+
+        let table = InstructionTable::new().with(opcode::SELFDESTRUCT, |interp, _| {
+            interp.instruction_result = InstructionResult::Stop;
+        });
+        let mut evm = crate::new().with_instruction_table(table);
+        evm.database(db);
+        evm.env.tx.caller = CALLER;
+        evm.env.tx.transact_to = TransactTo::Call(CONTRACT);
+        let state = evm.transact().unwrap().state;
+        assert!(!state[&CONTRACT].is_selfdestructed());
+        assert!(!state.contains_key(&DENIED));
+
+        // inspected execution uses the table too.
+        let state = evm
+            .inspect(crate::inspectors::NoOpInspector {})
+            .unwrap()
+            .state;
+        assert!(!state[&CONTRACT].is_selfdestructed());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn analysis_cache() {
+        use crate::primitives::AnalysisCache;
+
+        // JUMP(4) INVALID JUMPDEST STOP
+        let code = hex!("600456fe5b00");
+        let mut db = InMemoryDB::default();
+        db.insert_account_info(
+            CONTRACT,
+            AccountInfo::new(U256::ZERO, 0, Bytecode::new_raw(code.to_vec().into())),
+        );
+        let cache = AnalysisCache::new(8);
+        let mut evm = crate::new();
+        evm.database(db);
+        evm.env.cfg.analysis_cache = Some(cache.clone());
+        evm.env.tx.caller = CALLER;
+        evm.env.tx.transact_to = TransactTo::Call(CONTRACT);
+        for _ in 0..3 {
+            assert!(evm.transact().unwrap().result.is_success());
+        }
+        let stats = cache.stats();
+        assert_eq!((stats.misses, stats.hits), (1, 2));
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    fn block_hash_provider() {
+        use crate::primitives::{
+            block_hash_provider::{HISTORY_SERVE_WINDOW, HISTORY_STORAGE_ADDRESS},
+            BlockHashes, DatabaseBlockHashes, HistoryStorage,
+        };
+
+        // MSTORE(0, BLOCKHASH(10_000)) RETURN(0, 32)
+        let code = hex!("612710 40 600052 60206000f3");
+        let mut db = InMemoryDB::default();
+        db.insert_account_info(
+            CONTRACT,
+            AccountInfo::new(U256::ZERO, 0, Bytecode::new_raw(code.to_vec().into())),
+        );
+        db.block_hashes
+            .insert(U256::from(10_000), B256::from_low_u64_be(1));
+        let slot = U256::from(10_000 % HISTORY_SERVE_WINDOW);
+        db.insert_account_storage(HISTORY_STORAGE_ADDRESS, slot, U256::from(2))
+            .unwrap();
+        let mut evm = crate::new();
+        evm.database(db);
+        evm.env.block.number = U256::from(11_000);
+        evm.env.tx.caller = CALLER;
+        evm.env.tx.transact_to = TransactTo::Call(CONTRACT);
+        let block_hash = |evm: &mut crate::EVM<InMemoryDB>| {
+            let output = evm.transact().unwrap().result.into_output().unwrap();
+            U256::from_be_slice(&output)
+        };
+
+        // older than 256 blocks, also after EIP-2935.
+        evm.env.cfg.spec_id = SpecId::CANCUN;
+        assert_eq!(block_hash(&mut evm), U256::ZERO);
+        evm.env.cfg.spec_id = SpecId::PRAGUE;
+        assert_eq!(block_hash(&mut evm), U256::ZERO);
+        // chain serving the EIP-2935 history contract.
+        evm.env.cfg.block_hashes = BlockHashes::new(HistoryStorage::eip2935());
+        assert_eq!(block_hash(&mut evm), U256::from(2));
+        // chain with a longer window.
+        evm.env.cfg.block_hashes = BlockHashes::new(DatabaseBlockHashes { window: 1_000 });
+        assert_eq!(block_hash(&mut evm), U256::from(1));
+    }
+
+    #[test]
+    fn block_hash_window() {
+        let current = U256::from(300);
+        let big = U256::from(u64::MAX) + U256::from(1);
+        let mut db = InMemoryDB::default();
+        for number in [
+            U256::from(43),
+            U256::from(44),
+            current,
+            U256::from(u64::MAX),
+        ] {
+            db.block_hashes.insert(number, B256::from_low_u64_be(1));
+        }
+        let mut evm = crate::new();
+        evm.database(db);
+        evm.env.tx.caller = CALLER;
+        evm.env.tx.transact_to = TransactTo::Call(CONTRACT);
+        let mut block_hash = |block: U256, number: U256| {
+            // MSTORE(0, BLOCKHASH(number)) RETURN(0, 32)
+            let mut code = vec![0x7f];
+            code.extend_from_slice(&number.to_be_bytes::<32>());
+            code.extend_from_slice(&hex!("40 600052 60206000f3"));
+            let db = evm.db().unwrap();
+            db.insert_account_info(
+                CONTRACT,
+                AccountInfo::new(U256::ZERO, 0, Bytecode::new_raw(code.into())),
+            );
+            evm.env.block.number = block;
+            let output = evm.transact().unwrap().result.into_output().unwrap();
+            U256::from_be_slice(&output)
+        };
+
+        // 256th previous block is the oldest one.
+        assert_eq!(block_hash(current, U256::from(44)), U256::from(1));
+        assert_eq!(block_hash(current, U256::from(43)), U256::ZERO);
+        assert_eq!(block_hash(current, current), U256::ZERO);
+        // numbers that don't fit in u64 are not read as the last u64 block.
+        assert_eq!(block_hash(big + U256::from(10), big), U256::ZERO);
+    }
+
+    #[test]
+    fn eof() {
+        // `PUSH1 2 CALLF 1 MSTORE(0, _) RETURN(0, 32)`, section 1 doubles its input with
+        // `DUP1 ADD` and returns with `RJUMPI` over a `STOP` if it is not zero.
+        let runtime = hex!(
+            "ef0001 010008 020002 000d 0008 030000 00 00000002 01010002"
+            "6002 e30001 6000 52 6020 6000 f3"
+            "80 01 80 e10001 00 e4"
+        );
+        // `CODECOPY(0, 31, 46) RETURN(0, 46)` with the runtime container as data.
+        let mut init = hex!("ef0001 010004 020001 000c 03002e 00 00000003").to_vec();
+        init.extend_from_slice(&hex!("602e 601f 6000 39 602e 6000 f3"));
+        init.extend_from_slice(&runtime);
+
+        let mut evm = crate::new();
+        evm.database(InMemoryDB::default());
+        evm.env.cfg.spec_id = SpecId::OSAKA;
+        evm.env.tx.caller = CALLER;
+        evm.env.tx.transact_to = TransactTo::Create(CreateScheme::Create);
+        evm.env.tx.data = init.clone().into();
+        let ExecutionResult::Success { .. } = evm.transact_commit().unwrap() else {
+            panic!("EOF is deployed");
+        };
+        let created = create_address(CALLER, 0);
+        evm.env.tx.transact_to = TransactTo::Call(created);
+        evm.env.tx.data = Default::default();
+        let output = evm.transact().unwrap().result.into_output().unwrap();
+        assert_eq!(
+            U256::from_be_bytes::<32>(output[..].try_into().unwrap()),
+            U256::from(4)
+        );
+
+        // invalid init code, `RJUMP` into its own immediate.
+        evm.env.tx.transact_to = TransactTo::Create(CreateScheme::Create);
+        evm.env.tx.data = hex!("ef0001 010004 020001 0003 030000 00 00000000 e0fffe")
+            .to_vec()
+            .into();
+        let result = evm.transact().unwrap().result;
+        assert!(matches!(
+            result,
+            ExecutionResult::Halt {
+                reason: Halt::InvalidEOFCode,
+                ..
+            }
+        ));
+
+        // EOF is rejected by stable forks, the default LATEST included.
+        evm.env.tx.data = init.into();
+        for spec_id in [SpecId::CANCUN, SpecId::LATEST] {
+            evm.env.cfg.spec_id = spec_id;
+            let result = evm.transact().unwrap().result;
+            assert!(!result.is_success());
+        }
+    }
+
+    #[test]
+    fn set_code_transaction() {
+        // signed by key 0x1111..11 for chain 1 and nonce 0.
+        let authority = B160(hex!("19e7e376e7c213b7e7e7e46cc70a5dd086daff2a"));
+        let authorization = SignedAuthorization {
+            chain_id: U256::from(1),
+            address: CONTRACT,
+            nonce: 0,
+            y_parity: 0,
+            r: U256::from_be_bytes(hex!(
+                "aa32fbd0754ffec553cb3b5762dd2ac14510c6b9b145cb6f1329f37aecbb68d5"
+            )),
+            s: U256::from_be_bytes(hex!(
+                "4c53d2cda5f7f9d47252486deb8b4ff53adf6d04d8697b4c152f6f9eba165cee"
+            )),
+        };
+        // SSTORE(0, 42)
+        let mut db = InMemoryDB::default();
+        db.insert_account_info(
+            CONTRACT,
+            AccountInfo::new(
+                U256::ZERO,
+                0,
+                Bytecode::new_raw(hex!("602a600055").to_vec().into()),
+            ),
+        );
+        let mut evm = crate::new();
+        evm.database(db);
+        evm.env.cfg.spec_id = SpecId::PRAGUE;
+        evm.env.tx.caller = CALLER;
+        evm.env.tx.transact_to = TransactTo::Call(authority);
+        evm.env.tx.authorization_list = vec![authorization];
+        let result = evm.transact_commit().unwrap();
+        assert!(result.is_success());
+        // authorization, cold SSTORE and two PUSH1, the authority did not exist.
+        #[cfg(not(feature = "no_gas_measuring"))]
+        assert_eq!(result.gas_used(), 21000 + 25000 + 22100 + 6);
+
+        let db = evm.db().unwrap();
+        let account = db.load_account(authority).unwrap();
+        assert_eq!(account.info.nonce, 1);
+        assert_eq!(account.storage[&U256::ZERO], U256::from(42));
+        let code_hash = account.info.code_hash;
+        let code = db.code_by_hash(code_hash).unwrap();
+        assert_eq!(code.delegation_address(), Some(CONTRACT));
+
+        // nonce of the authorization is stale, it is skipped and delegation stays.
+        let result = evm.transact_commit().unwrap();
+        assert!(result.is_success());
+        assert_eq!(
+            evm.db()
+                .unwrap()
+                .load_account(authority)
+                .unwrap()
+                .info
+                .nonce,
+            1
+        );
+
+        evm.env.cfg.spec_id = SpecId::CANCUN;
+        assert!(matches!(
+            evm.transact(),
+            Err(EVMError::Transaction(
+                InvalidTransaction::AuthorizationListNotSupported
+            ))
+        ));
     }
 }
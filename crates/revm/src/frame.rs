@@ -0,0 +1,190 @@
+//! Execution of a transaction driven frame by frame on the caller's thread.
+//!
+//! Interpreters of the transaction run with [Interpreter::suspend_calls], so a call or create
+//! opcode stops its interpreter instead of executing the frame recursively. Frames that didn't
+//! finish are kept on a stack in [Execution], which the driver advances one unit at a time:
+//! entering a frame, executing one opcode or returning a frame to its parent. Execution can be
+//! paused between any two units, and a unit that fails to read the database is rolled back so
+//! it can be retried once the database has the data.
+//!
+//! With the `serde` feature, execution can be serialized while it is paused and resumed later
+//! by another EVM with the same environment and database.
+use crate::interpreter::{Gas, InstructionResult, Interpreter, InterpreterAction, Memory};
+use crate::journaled_state::{JournalCheckpoint, JournalMark, JournaledState};
+use crate::primitives::{Bytes, GasFrame, HaltContext, B160, U256};
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::ops::Range;
+
+/// Transaction in the middle of its execution, with the state of the EVM it changed.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Execution {
+    pub(crate) journaled_state: JournaledState,
+    /// Frames that didn't finish, the one executing is last.
+    pub(crate) frames: Vec<Frame>,
+    pub(crate) next: Next,
+    pub(crate) tx: TxGas,
+    pub(crate) log_data_size: usize,
+    #[cfg(feature = "optimism")]
+    pub(crate) l1_cost: crate::primitives::U256,
+    pub(crate) halt: Option<HaltContext>,
+    pub(crate) gas_frames: Vec<GasFrame>,
+    pub(crate) gas_frame: Option<Box<GasFrame>>,
+    /// Inspector already saw the next opcode, it is retried after an error of the database.
+    pub(crate) step_inspected: bool,
+}
+
+#[cfg(feature = "serde")]
+impl Execution {
+    /// Serialize the execution, so it can be resumed in another process.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, serde_json::Error> {
+        serde_json::to_vec(self)
+    }
+
+    /// Execution serialized by [Execution::to_bytes].
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, serde_json::Error> {
+        serde_json::from_slice(bytes)
+    }
+}
+
+/// Frame with a running interpreter.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) struct Frame {
+    pub(crate) interpreter: Box<Interpreter>,
+    /// Call or create that started the frame.
+    pub(crate) action: InterpreterAction,
+    pub(crate) checkpoint: JournalCheckpoint,
+    pub(crate) created_address: Option<B160>,
+}
+
+/// Unit of execution the driver does next.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) enum Next {
+    /// Start frame of the call or create. Inspector is not called again for it if the frame is
+    /// retried.
+    Enter {
+        action: InterpreterAction,
+        inspected: bool,
+    },
+    /// Execute the next opcode of the last frame.
+    Run,
+    /// Give result of a finished frame to its parent, or finish the transaction.
+    Return(FrameOutput),
+}
+
+/// Result of a finished frame.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FrameOutput {
+    pub result: InstructionResult,
+    pub gas: Gas,
+    #[cfg_attr(
+        feature = "serde",
+        serde(with = "crate::primitives::utilities::serde_hex_bytes")
+    )]
+    pub output: Bytes,
+    /// Called address, or created address of creates that created the account.
+    pub address: Option<B160>,
+    /// Memory of the parent the output is copied to, none for creates.
+    pub(crate) return_memory: Option<Range<usize>>,
+}
+
+/// Gas of the transaction outside of its first frame.
+#[derive(Clone, Copy, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) struct TxGas {
+    /// Gas limit of the transaction, or cap of the spend if gas is not charged.
+    pub(crate) limit: u64,
+    /// Intrinsic gas.
+    pub(crate) initial: u64,
+    /// EIP-7702: refund of authorities that already existed.
+    pub(crate) authorization_refund: i64,
+}
+
+/// Where the driver stops.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum PauseAt {
+    /// After every unit.
+    Step,
+    /// Before a frame is entered or returns.
+    Frame,
+    /// When the transaction is finished.
+    End,
+}
+
+/// Interpreter and journal before an opcode that reads the database, the opcode is rolled
+/// back to it if the read fails.
+pub(crate) struct StepSnapshot {
+    instruction_pointer: *const u8,
+    gas: Gas,
+    /// Stack items from `stack_from` up, items below are not popped by the opcode.
+    stack_from: usize,
+    stack: Vec<U256>,
+    /// Whole memory if the opcode can change it before it reads, otherwise only the size.
+    memory: Result<Memory, usize>,
+    return_data_buffer: Bytes,
+    journal: JournalMark,
+    log_data_size: usize,
+}
+
+impl StepSnapshot {
+    /// Most items a built-in opcode that reads the database pops, by `CALL`.
+    const POPPED: usize = 7;
+
+    /// Take snapshot before the next opcode. Custom instructions can do anything before they
+    /// read, so whole stack and memory are kept for them.
+    pub(crate) fn new(
+        interpreter: &Interpreter,
+        journal: &JournaledState,
+        log_data_size: usize,
+        is_custom: bool,
+    ) -> Self {
+        let stack = interpreter.stack.data();
+        let stack_from = if is_custom {
+            0
+        } else {
+            stack.len().saturating_sub(Self::POPPED)
+        };
+        Self {
+            instruction_pointer: interpreter.instruction_pointer,
+            gas: interpreter.gas,
+            stack_from,
+            stack: stack[stack_from..].to_vec(),
+            memory: if is_custom {
+                Ok(interpreter.memory.clone())
+            } else {
+                Err(interpreter.memory.len())
+            },
+            return_data_buffer: interpreter.return_data_buffer.clone(),
+            journal: journal.mark(),
+            log_data_size,
+        }
+    }
+
+    /// Roll back the opcode, it is executed again when the interpreter continues.
+    pub(crate) fn restore(
+        self,
+        interpreter: &mut Interpreter,
+        journal: &mut JournaledState,
+        log_data_size: &mut usize,
+    ) {
+        interpreter.instruction_pointer = self.instruction_pointer;
+        interpreter.instruction_result = InstructionResult::Continue;
+        interpreter.next_action = None;
+        interpreter.gas = self.gas;
+        while interpreter.stack.len() > self.stack_from {
+            let _ = interpreter.stack.pop();
+        }
+        for item in self.stack {
+            // items were on the stack, it has space for them.
+            let _ = interpreter.stack.push(item);
+        }
+        match self.memory {
+            Ok(memory) => interpreter.memory = memory,
+            Err(len) => interpreter.memory.resize(len),
+        }
+        interpreter.return_data_buffer = self.return_data_buffer;
+        journal.revert_to(self.journal);
+        *log_data_size = self.log_data_size;
+    }
+}
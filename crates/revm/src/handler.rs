@@ -0,0 +1,137 @@
+//! Stages of transaction execution that chains replace.
+//!
+//! [Handler] has a function for every stage a chain customizes: validation, fee deduction
+//! from the caller, gas of the finished transaction, refund, reimbursement of the caller
+//! and reward of the beneficiary. Stage that is not set runs the built-in logic of the
+//! spec, so a chain replaces only stages it changes. Handler is set with
+//! [EVM::with_handler](crate::EVM::with_handler).
+//!
+//! Replacements are not generic over the spec, they read it from
+//! [CfgEnv::spec_id](crate::primitives::CfgEnv::spec_id) of the environment.
+use crate::interpreter::{Gas, InstructionResult};
+use crate::primitives::{Account, Env, InvalidTransaction, B160, U256};
+use alloc::vec::Vec;
+
+/// Validate environment, [Handler::validate_env].
+pub type ValidateEnv = fn(&Env) -> Result<(), InvalidTransaction>;
+/// Check or change the caller account, [Handler::validate_tx_against_state] and
+/// [Handler::deduct_caller].
+pub type CallerStage = fn(&Env, &mut Account) -> Result<(), InvalidTransaction>;
+/// Gas of the transaction from its frame, [Handler::last_frame_return].
+pub type LastFrameReturn = fn(&Env, InstructionResult, &Gas, &mut Gas);
+/// Gas refund, [Handler::calculate_gas_refund].
+pub type CalculateGasRefund = fn(&Env, &Gas) -> u64;
+/// Reimbursement of the caller account, [Handler::reimburse_caller].
+pub type ReimburseCaller = fn(&Env, &mut Account, &Gas, u64);
+/// Credited balances, [Handler::reward_beneficiary].
+pub type RewardBeneficiary = fn(&Env, &Gas, u64) -> Vec<(B160, U256)>;
+
+/// Replacements of transaction execution stages, in order they are executed.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Handler {
+    /// Validate block and transaction environment, before anything is loaded.
+    pub validate_env: Option<ValidateEnv>,
+    /// Validate loaded caller account. The built-in check of code, nonce and balance
+    /// tops up the balance if balance check is disabled.
+    pub validate_tx_against_state: Option<CallerStage>,
+    /// Take fee for the gas limit from the caller account, before its nonce is increased.
+    pub deduct_caller: Option<CallerStage>,
+    /// Set gas of the transaction from the gas returned by its frame and the exit reason.
+    /// Transaction gas is passed fully spent.
+    pub last_frame_return: Option<LastFrameReturn>,
+    /// Gas refunded to the caller, not called if refund is disabled.
+    pub calculate_gas_refund: Option<CalculateGasRefund>,
+    /// Return fee of the remaining and refunded gas to the caller account.
+    pub reimburse_caller: Option<ReimburseCaller>,
+    /// Balances credited for the used gas, by default the priority fee goes to the
+    /// coinbase. Gas and the refunded gas are passed.
+    pub reward_beneficiary: Option<RewardBeneficiary>,
+}
+
+impl Handler {
+    /// Handler with the built-in logic for every stage.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// If every stage uses the built-in logic.
+    pub fn is_empty(&self) -> bool {
+        self.validate_env.is_none()
+            && self.validate_tx_against_state.is_none()
+            && self.deduct_caller.is_none()
+            && self.last_frame_return.is_none()
+            && self.calculate_gas_refund.is_none()
+            && self.reimburse_caller.is_none()
+            && self.reward_beneficiary.is_none()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primitives::{AccountInfo, Bytecode, ExecutionResult, TransactTo};
+    use crate::{InMemoryDB, EVM};
+
+    const VAULT: B160 = B160([0xee; 20]);
+
+    /// Every fee goes to the vault and nothing is refunded.
+    fn vault_handler() -> Handler {
+        Handler {
+            validate_env: Some(|env| match env.tx.gas_priority_fee {
+                Some(_) => Err(InvalidTransaction::GasMaxFeeGreaterThanPriorityFee),
+                None => Ok(()),
+            }),
+            calculate_gas_refund: Some(|_, _| 0),
+            reward_beneficiary: Some(|env, gas, _| {
+                vec![(VAULT, env.tx.gas_price * U256::from(gas.spend()))]
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn replaced_stages() {
+        let caller = B160([0x10; 20]);
+        let coinbase = B160([0x20; 20]);
+        let contract = B160([0x30; 20]);
+        // SSTORE(0, 0) of a set slot is refunded.
+        let code = Bytecode::new_raw(vec![0x5f, 0x5f, 0x55, 0x00].into());
+        let mut db = InMemoryDB::default();
+        db.insert_account_info(caller, AccountInfo::from_balance(U256::from(10_000_000)));
+        db.insert_account_info(contract, AccountInfo::new(U256::ZERO, 0, code));
+        db.insert_account_storage(contract, U256::ZERO, U256::from(1))
+            .unwrap();
+
+        let mut evm = EVM::new().with_handler(vault_handler());
+        assert!(!evm.handler().is_empty());
+        evm.database(db);
+        evm.env.block.coinbase = coinbase;
+        evm.env.block.basefee = U256::ZERO;
+        evm.env.tx.caller = caller;
+        evm.env.tx.transact_to = TransactTo::Call(contract);
+        evm.env.tx.gas_limit = 100_000;
+        evm.env.tx.gas_price = U256::from(2);
+        let out = evm.transact().unwrap();
+        let gas_used = out.result.gas_used();
+        if crate::USE_GAS {
+            let ExecutionResult::Success { gas_refunded, .. } = out.result else {
+                panic!("transaction failed");
+            };
+            assert!(gas_used > 21_000);
+            assert_eq!(gas_refunded, 0);
+            assert_eq!(out.state[&VAULT].info.balance, U256::from(2 * gas_used));
+            assert_eq!(out.state[&coinbase].info.balance, U256::ZERO);
+            // built-in reimbursement returns the fee of the unused gas.
+            assert_eq!(
+                out.state[&caller].info.balance,
+                U256::from(10_000_000 - 2 * gas_used)
+            );
+        }
+
+        evm.env.tx.gas_priority_fee = Some(U256::ZERO);
+        assert_eq!(
+            evm.transact().unwrap_err(),
+            InvalidTransaction::GasMaxFeeGreaterThanPriorityFee.into()
+        );
+    }
+}
@@ -1,26 +1,72 @@
 use crate::evm_impl::EVMData;
 use crate::interpreter::{CallInputs, CreateInputs, Gas, InstructionResult, Interpreter};
-use crate::primitives::{db::Database, Bytes, B160, B256};
+use crate::primitives::{db::Database, Bytes, B160, B256, U256};
 
 use auto_impl::auto_impl;
 
+pub mod access_list;
+pub mod call_graph;
+pub mod call_tracer;
+pub mod coverage;
 #[cfg(feature = "std")]
 pub mod customprinter;
+pub mod early_stop;
+pub mod four_byte;
 pub mod gas;
+pub mod gas_profiler;
+pub mod hooks;
+pub mod limits;
+pub mod multi;
 pub mod noop;
+pub mod parity_tracer;
+pub mod policy;
+pub mod refunds;
+pub mod stipend;
+pub mod struct_logger;
 #[cfg(all(feature = "std", feature = "serde"))]
 pub mod tracer_eip3155;
 
 /// All Inspectors implementations that revm has.
 pub mod inspectors {
+    pub use super::access_list::AccessListInspector;
+    pub use super::call_graph::CallGraphInspector;
+    pub use super::call_tracer::CallTracer;
+    pub use super::coverage::{CodeCoverage, CoverageInspector, CoverageMap};
     #[cfg(feature = "std")]
     pub use super::customprinter::CustomPrintTracer;
+    pub use super::early_stop::EarlyStopInspector;
+    pub use super::four_byte::FourByteInspector;
     pub use super::gas::GasInspector;
+    pub use super::gas_profiler::GasProfiler;
+    pub use super::hooks::HookInspector;
+    pub use super::limits::ResourceLimiter;
+    pub use super::multi::MultiInspector;
     pub use super::noop::NoOpInspector;
+    pub use super::parity_tracer::{state_diff, ParityTracer, StateDiff, TraceResults};
+    pub use super::policy::PolicyInspector;
+    pub use super::refunds::RefundInspector;
+    pub use super::stipend::StipendInspector;
+    #[cfg(all(feature = "std", feature = "serde"))]
+    pub use super::struct_logger::JsonLines;
+    pub use super::struct_logger::{
+        StructLog, StructLogStream, StructLogWriter, StructLogger, StructLoggerConfig,
+    };
     #[cfg(all(feature = "std", feature = "serde"))]
     pub use super::tracer_eip3155::TracerEip3155;
 }
 
+/// Write of a storage slot by `SSTORE`, see [Inspector::sstore].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StorageWrite {
+    /// Value of the slot at the start of the transaction.
+    pub original: U256,
+    /// Value of the slot before the write.
+    pub present: U256,
+    pub new: U256,
+    /// If slot was accessed for the first time in the transaction.
+    pub is_cold: bool,
+}
+
 #[auto_impl(&mut, Box)]
 pub trait Inspector<DB: Database> {
     /// Called Before the interpreter is initialized.
@@ -61,6 +107,36 @@ pub trait Inspector<DB: Database> {
     ) {
     }
 
+    /// Called when `SLOAD` reads `slot` of `address`. Depth of the frame is
+    /// `data.journaled_state.depth()`.
+    fn sload(
+        &mut self,
+        _data: &mut EVMData<'_, DB>,
+        _address: &B160,
+        _slot: U256,
+        _value: U256,
+        _is_cold: bool,
+    ) {
+    }
+
+    /// Called when `SSTORE` writes `slot` of `address`, before gas of the write is charged.
+    ///
+    /// Write is reverted if the frame runs out of gas.
+    fn sstore(
+        &mut self,
+        _data: &mut EVMData<'_, DB>,
+        _address: &B160,
+        _slot: U256,
+        _write: &StorageWrite,
+    ) {
+    }
+
+    /// Called when `TLOAD` reads transient `slot` of `address`.
+    fn tload(&mut self, _data: &mut EVMData<'_, DB>, _address: &B160, _slot: U256, _value: U256) {}
+
+    /// Called when `TSTORE` writes transient `slot` of `address`.
+    fn tstore(&mut self, _data: &mut EVMData<'_, DB>, _address: &B160, _slot: U256, _value: U256) {}
+
     /// Called after `step` when the instruction has been executed.
     ///
     /// InstructionResulting anything other than [InstructionResult::Continue] alters the execution of the interpreter.
@@ -117,6 +193,11 @@ pub trait Inspector<DB: Database> {
 
     /// Called when a contract has been created.
     ///
+    /// `address` is the derived address of the contract even if creation failed, for example
+    /// with [InstructionResult::OutOfFund] or [InstructionResult::CallTooDeep]. It is None
+    /// if the caller can't be loaded, its nonce overflows or the address filter denies the
+    /// creation.
+    ///
     /// InstructionResulting anything other than the values passed to this function (`(ret, remaining_gas,
     /// address, out)`) will alter the result of the create.
     fn create_end(
@@ -134,3 +215,128 @@ pub trait Inspector<DB: Database> {
     /// Called when a contract has been self-destructed with funds transferred to target.
     fn selfdestruct(&mut self, _contract: B160, _target: B160) {}
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primitives::{
+        create2_address, hex_literal::hex, AccountInfo, Bytecode, TransactTo, KECCAK_EMPTY,
+    };
+    use crate::InMemoryDB;
+    use alloc::vec::Vec;
+
+    #[derive(Default)]
+    struct StorageRecorder {
+        events: Vec<(&'static str, U256, U256, bool)>,
+    }
+
+    impl<DB: Database> Inspector<DB> for StorageRecorder {
+        fn sload(
+            &mut self,
+            data: &mut EVMData<'_, DB>,
+            _address: &B160,
+            slot: U256,
+            value: U256,
+            is_cold: bool,
+        ) {
+            assert_eq!(data.journaled_state.depth(), 1);
+            self.events.push(("sload", slot, value, is_cold));
+        }
+
+        fn sstore(
+            &mut self,
+            _data: &mut EVMData<'_, DB>,
+            _address: &B160,
+            slot: U256,
+            write: &StorageWrite,
+        ) {
+            assert_eq!(write.original, write.present);
+            self.events.push(("sstore", slot, write.new, write.is_cold));
+        }
+
+        fn tload(&mut self, _data: &mut EVMData<'_, DB>, _address: &B160, slot: U256, value: U256) {
+            self.events.push(("tload", slot, value, false));
+        }
+
+        fn tstore(
+            &mut self,
+            _data: &mut EVMData<'_, DB>,
+            _address: &B160,
+            slot: U256,
+            value: U256,
+        ) {
+            self.events.push(("tstore", slot, value, false));
+        }
+    }
+
+    #[test]
+    fn storage_hooks() {
+        let contract = B160([0x10; 20]);
+        // SSTORE(0, 1) SLOAD(0) TSTORE(1, 2) TLOAD(1) STOP
+        let code = hex!("6001600055600054 600260015d 60015c 00");
+        let mut db = InMemoryDB::default();
+        db.insert_account_info(
+            contract,
+            AccountInfo::new(U256::ZERO, 0, Bytecode::new_raw(code.to_vec().into())),
+        );
+        let mut evm = crate::new();
+        evm.database(db);
+        evm.env.tx.transact_to = TransactTo::Call(contract);
+
+        let mut recorder = StorageRecorder::default();
+        assert!(evm.inspect(&mut recorder).unwrap().result.is_success());
+        let (zero, one, two) = (U256::ZERO, U256::from(1), U256::from(2));
+        assert_eq!(
+            recorder.events,
+            [
+                ("sstore", zero, one, true),
+                ("sload", zero, one, false),
+                ("tstore", one, two, false),
+                ("tload", one, two, false),
+            ]
+        );
+    }
+
+    #[derive(Default)]
+    struct CreateRecorder {
+        created: Vec<(InstructionResult, Option<B160>)>,
+    }
+
+    impl<DB: Database> Inspector<DB> for CreateRecorder {
+        fn create_end(
+            &mut self,
+            _data: &mut EVMData<'_, DB>,
+            _inputs: &CreateInputs,
+            ret: InstructionResult,
+            address: Option<B160>,
+            remaining_gas: Gas,
+            out: Bytes,
+        ) -> (InstructionResult, Option<B160>, Gas, Bytes) {
+            self.created.push((ret, address));
+            (ret, address, remaining_gas, out)
+        }
+    }
+
+    #[test]
+    fn failed_create_address() {
+        let contract = B160([0x10; 20]);
+        // CREATE2(value 1, empty init code, salt 7) without balance, POP STOP
+        let code = hex!("6007 6000 6000 6001 f5 50 00");
+        let mut db = InMemoryDB::default();
+        db.insert_account_info(
+            contract,
+            AccountInfo::new(U256::ZERO, 1, Bytecode::new_raw(code.to_vec().into())),
+        );
+        let mut evm = crate::new();
+        evm.database(db);
+        evm.env.tx.transact_to = TransactTo::Call(contract);
+
+        let mut recorder = CreateRecorder::default();
+        assert!(evm.inspect(&mut recorder).unwrap().result.is_success());
+        let address = create2_address(contract, KECCAK_EMPTY, U256::from(7));
+        assert_eq!(
+            recorder.created,
+            [(InstructionResult::OutOfFund, Some(address))]
+        );
+    }
+}
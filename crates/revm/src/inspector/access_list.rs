@@ -0,0 +1,211 @@
+//! Access list generation, same as `eth_createAccessList` of geth.
+//!
+//! [AccessListInspector] records accounts and storage slots accessed by the opcodes and
+//! [EVM::create_access_list] executes the transaction with the recorded list until it
+//! stops changing, gas used is then the gas of the transaction with that list.
+use crate::evm::to_precompile_id;
+use crate::interpreter::{opcode, InstructionResult, Interpreter};
+use crate::precompile::Precompiles;
+use crate::primitives::{
+    create_address, EVMError, ExecutionResult, HashSet, TransactTo, B160, B256, U256,
+};
+use crate::{Database, EVMData, Inspector, EVM};
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::vec::Vec;
+
+/// Inspector recording the access list of the transaction.
+///
+/// Accounts only accessed by address are not recorded if they are excluded, accounts whose
+/// storage was accessed always are.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AccessListInspector {
+    excluded: HashSet<B160>,
+    access_list: BTreeMap<B160, BTreeSet<U256>>,
+}
+
+impl AccessListInspector {
+    /// Inspector starting with `access_list`.
+    pub fn new(
+        access_list: &[(B160, Vec<U256>)],
+        excluded: impl IntoIterator<Item = B160>,
+    ) -> Self {
+        let mut inspector = Self {
+            excluded: excluded.into_iter().collect(),
+            access_list: BTreeMap::new(),
+        };
+        for (address, slots) in access_list {
+            if slots.is_empty() {
+                inspector.add_address(*address);
+            }
+            for slot in slots {
+                inspector.add_slot(*address, *slot);
+            }
+        }
+        inspector
+    }
+
+    /// Recorded access list in the format of [crate::primitives::TxEnv::access_list].
+    pub fn access_list(&self) -> Vec<(B160, Vec<U256>)> {
+        self.access_list
+            .iter()
+            .map(|(address, slots)| (*address, slots.iter().copied().collect()))
+            .collect()
+    }
+
+    fn add_address(&mut self, address: B160) {
+        if !self.excluded.contains(&address) {
+            self.access_list.entry(address).or_default();
+        }
+    }
+
+    fn add_slot(&mut self, address: B160, slot: U256) {
+        self.access_list.entry(address).or_default().insert(slot);
+    }
+}
+
+impl<DB: Database> Inspector<DB> for AccessListInspector {
+    fn step(&mut self, interp: &mut Interpreter, _data: &mut EVMData<'_, DB>) -> InstructionResult {
+        let stack = &interp.stack;
+        match interp.current_opcode() {
+            opcode::SLOAD | opcode::SSTORE => {
+                if let Ok(slot) = stack.peek(0) {
+                    self.add_slot(interp.contract.address, slot);
+                }
+            }
+            opcode::EXTCODECOPY
+            | opcode::EXTCODEHASH
+            | opcode::EXTCODESIZE
+            | opcode::BALANCE
+            | opcode::SELFDESTRUCT => {
+                if let Ok(address) = stack.peek(0) {
+                    self.add_address(B160::from(B256::from(address)));
+                }
+            }
+            // checked for stack underflow, same as geth.
+            opcode::CALL | opcode::CALLCODE | opcode::DELEGATECALL | opcode::STATICCALL
+                if stack.len() >= 5 =>
+            {
+                if let Ok(address) = stack.peek(1) {
+                    self.add_address(B160::from(B256::from(address)));
+                }
+            }
+            _ => {}
+        }
+        InstructionResult::Continue
+    }
+}
+
+/// Result of [EVM::create_access_list].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AccessListResult {
+    pub access_list: Vec<(B160, Vec<U256>)>,
+    /// Gas used by the transaction with the access list.
+    pub gas_used: u64,
+    /// Result of the transaction with the access list, state is not changed.
+    pub result: ExecutionResult,
+}
+
+impl<DB: Database> EVM<DB> {
+    /// Create access list of the transaction, same as `eth_createAccessList`.
+    ///
+    /// Caller, called or created address and precompiles are excluded unless their storage
+    /// is accessed. Transaction is executed until the access list it uses is the one it
+    /// accesses, access list of the transaction is then set to it. Database is not changed.
+    pub fn create_access_list(&mut self) -> Result<AccessListResult, EVMError<DB::Error>> {
+        let caller = self.env.tx.caller;
+        let to = match self.env.tx.transact_to {
+            TransactTo::Call(address) => address,
+            TransactTo::Create(_) => {
+                let nonce = match self.env.tx.nonce {
+                    Some(nonce) => nonce,
+                    None => self
+                        .db()
+                        .expect("Database needs to be set")
+                        .basic(caller)
+                        .map_err(EVMError::Database)?
+                        .map_or(0, |info| info.nonce),
+                };
+                create_address(caller, nonce)
+            }
+        };
+        let precompiles = Precompiles::new(to_precompile_id(self.env.cfg.spec_id));
+        let excluded: Vec<_> = [caller, to]
+            .into_iter()
+            .chain(
+                precompiles
+                    .addresses()
+                    .into_iter()
+                    .map(|address| B160(*address)),
+            )
+            .chain(
+                self.env
+                    .cfg
+                    .custom_precompiles
+                    .iter()
+                    .map(|(address, _)| *address),
+            )
+            .collect();
+
+        let mut previous = AccessListInspector::new(&[], excluded.iter().copied());
+        loop {
+            let access_list = previous.access_list();
+            self.env.tx.access_list = access_list.clone();
+            let mut inspector = AccessListInspector::new(&access_list, excluded.iter().copied());
+            let result = self.inspect(&mut inspector)?.result;
+            if inspector == previous {
+                return Ok(AccessListResult {
+                    access_list,
+                    gas_used: result.gas_used(),
+                    result,
+                });
+            }
+            previous = inspector;
+        }
+    }
+}
+
+#[cfg(all(test, not(feature = "no_gas_measuring")))]
+mod tests {
+    use super::*;
+    use crate::primitives::{hex_literal::hex, AccountInfo, Bytecode};
+    use crate::InMemoryDB;
+
+    #[test]
+    fn creates_access_list() {
+        let caller = B160([0x10; 20]);
+        let contract = B160([0x20; 20]);
+        let other = B160([0x30; 20]);
+
+        // POP(SLOAD(1)) POP(BALANCE(other)) POP(BALANCE(caller))
+        // POP(STATICCALL(GAS, 4, 0, 0, 0, 0)) STOP
+        let mut code = hex!("6001545073").to_vec();
+        code.extend_from_slice(&other.0);
+        code.extend_from_slice(&hex!("315073"));
+        code.extend_from_slice(&caller.0);
+        code.extend_from_slice(&hex!("3150600060006000600060045afa5000"));
+        let mut db = InMemoryDB::default();
+        db.insert_account_info(
+            contract,
+            AccountInfo::new(U256::ZERO, 0, Bytecode::new_raw(code.into())),
+        );
+
+        let mut evm = crate::new();
+        evm.database(db);
+        evm.env.tx.caller = caller;
+        evm.env.tx.transact_to = TransactTo::Call(contract);
+        let out = evm.create_access_list().unwrap();
+
+        let expected = vec![(contract, vec![U256::from(1)]), (other, vec![])];
+        assert_eq!(out.access_list, expected);
+        assert_eq!(evm.env.tx.access_list, expected);
+        assert!(out.result.is_success());
+
+        let with_list = evm.transact().unwrap().result.gas_used();
+        assert_eq!(out.gas_used, with_list);
+        // list has the called contract, which is warm anyway, same as in geth.
+        evm.env.tx.access_list.clear();
+        let without_list = evm.transact().unwrap().result.gas_used();
+        let listed = 2 * 2400 + 1900 + 2 * 100;
+        assert_eq!(with_list - without_list, listed - 2100 - 2600);
+    }
+}
@@ -0,0 +1,318 @@
+//! Call graph inspector.
+//!
+//! Records every call and create of the transaction together with the gas it used,
+//! and renders them as Graphviz DOT or Mermaid flowchart.
+use crate::interpreter::{return_ok, CallInputs, CallScheme, CreateInputs, Gas, InstructionResult};
+use crate::primitives::{Bytes, CreateScheme, B160};
+use crate::{Database, EVMData, Inspector};
+use alloc::{string::String, vec::Vec};
+use core::fmt::{self, Write};
+
+/// Kind of the frame.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "UPPERCASE"))]
+pub enum CallKind {
+    Call,
+    CallCode,
+    DelegateCall,
+    StaticCall,
+    Create,
+    Create2,
+}
+
+impl From<CallScheme> for CallKind {
+    fn from(scheme: CallScheme) -> Self {
+        match scheme {
+            CallScheme::Call => Self::Call,
+            CallScheme::CallCode => Self::CallCode,
+            CallScheme::DelegateCall => Self::DelegateCall,
+            CallScheme::StaticCall => Self::StaticCall,
+        }
+    }
+}
+
+impl From<CreateScheme> for CallKind {
+    fn from(scheme: CreateScheme) -> Self {
+        match scheme {
+            CreateScheme::Create => Self::Create,
+            CreateScheme::Create2 { .. } => Self::Create2,
+        }
+    }
+}
+
+impl fmt::Display for CallKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Call => "CALL",
+            Self::CallCode => "CALLCODE",
+            Self::DelegateCall => "DELEGATECALL",
+            Self::StaticCall => "STATICCALL",
+            Self::Create => "CREATE",
+            Self::Create2 => "CREATE2",
+        })
+    }
+}
+
+/// Edge of the call graph, one for every call or create in execution order.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CallEdge {
+    /// Address whose code made the call, transaction caller for the first edge.
+    pub from: B160,
+    /// Address whose code is executed. None if create failed before address was known.
+    pub to: Option<B160>,
+    pub kind: CallKind,
+    pub depth: usize,
+    pub gas_limit: u64,
+    /// Gas charged to the caller. Exceptional halts consume the whole limit.
+    pub gas_used: u64,
+    pub result: InstructionResult,
+}
+
+impl CallEdge {
+    /// If frame returned successfully.
+    pub fn is_success(&self) -> bool {
+        matches!(self.result, return_ok!())
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct CallGraphInspector {
+    edges: Vec<CallEdge>,
+    /// Indices of edges of frames that are currently executing.
+    stack: Vec<usize>,
+}
+
+impl CallGraphInspector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Recorded edges in execution order.
+    pub fn edges(&self) -> &[CallEdge] {
+        &self.edges
+    }
+
+    /// Render call graph in Graphviz DOT format.
+    ///
+    /// Edges are numbered in execution order and annotated with gas used, failed
+    /// frames are drawn dashed.
+    pub fn to_dot(&self) -> String {
+        let mut out = String::from("digraph calls {\n    node [shape=box];\n");
+        for (i, edge) in self.edges.iter().enumerate() {
+            let _ = write!(
+                out,
+                "    \"{}\" -> \"{}\" [label=\"{}\"",
+                Node(Some(edge.from), i),
+                Node(edge.to, i),
+                Label(i, edge),
+            );
+            if !edge.is_success() {
+                out.push_str(", style=dashed");
+            }
+            out.push_str("];\n");
+        }
+        out.push_str("}\n");
+        out
+    }
+
+    /// Render call graph as Mermaid flowchart.
+    pub fn to_mermaid(&self) -> String {
+        let mut out = String::from("flowchart TD\n");
+        // mermaid node ids can't be addresses, map them to their order of appearance.
+        let mut nodes: Vec<Option<B160>> = Vec::new();
+        let mut node_id = |out: &mut String, address: Option<B160>, i: usize| -> usize {
+            // failed creates don't have an address, each gets its own node.
+            if let Some(id) = address.and_then(|_| nodes.iter().position(|n| *n == address)) {
+                return id;
+            }
+            nodes.push(address);
+            let id = nodes.len() - 1;
+            let _ = writeln!(out, "    n{id}[\"{}\"]", Node(address, i));
+            id
+        };
+        for (i, edge) in self.edges.iter().enumerate() {
+            let from = node_id(&mut out, Some(edge.from), i);
+            let to = node_id(&mut out, edge.to, i);
+            let arrow = if edge.is_success() { "-->" } else { "-.->" };
+            let _ = writeln!(out, "    n{from} {arrow}|\"{}\"| n{to}", Label(i, edge));
+        }
+        out
+    }
+
+    fn push(&mut self, caller: B160, to: Option<B160>, kind: CallKind, gas_limit: u64) {
+        let depth = self.stack.len();
+        // caller of a nested frame is the code that is currently executing, it differs
+        // from `caller` for delegate calls. Address of running init code is not known yet.
+        let from = self
+            .stack
+            .last()
+            .and_then(|&parent| self.edges[parent].to)
+            .unwrap_or(caller);
+        self.stack.push(self.edges.len());
+        self.edges.push(CallEdge {
+            from,
+            to,
+            kind,
+            depth,
+            gas_limit,
+            gas_used: 0,
+            result: InstructionResult::Continue,
+        });
+    }
+
+    fn pop(&mut self, result: InstructionResult, gas: &Gas) -> Option<&mut CallEdge> {
+        let index = self.stack.pop()?;
+        let edge = &mut self.edges[index];
+        edge.result = result;
+        edge.gas_used = match result {
+            return_ok!() | InstructionResult::Revert => gas.spend(),
+            // calls that fail before executing return all gas.
+            InstructionResult::CallTooDeep | InstructionResult::OutOfFund => 0,
+            _ => edge.gas_limit,
+        };
+        Some(edge)
+    }
+}
+
+/// Node label, address or placeholder for failed create.
+struct Node(Option<B160>, usize);
+
+impl fmt::Display for Node {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Some(address) => write!(f, "{address:#x}"),
+            None => write!(f, "failed create #{}", self.1 + 1),
+        }
+    }
+}
+
+/// Edge label, order, kind, gas used and result if it was not successful.
+struct Label<'a>(usize, &'a CallEdge);
+
+impl fmt::Display for Label<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Label(i, edge) = self;
+        write!(f, "{}: {} gas {}", i + 1, edge.kind, edge.gas_used)?;
+        if !edge.is_success() {
+            write!(f, " {:?}", edge.result)?;
+        }
+        Ok(())
+    }
+}
+
+impl<DB: Database> Inspector<DB> for CallGraphInspector {
+    fn call(
+        &mut self,
+        _data: &mut EVMData<'_, DB>,
+        inputs: &mut CallInputs,
+    ) -> (InstructionResult, Gas, Bytes) {
+        self.push(
+            inputs.context.caller,
+            Some(inputs.contract),
+            inputs.context.scheme.into(),
+            inputs.gas_limit,
+        );
+        (InstructionResult::Continue, Gas::new(0), Bytes::new())
+    }
+
+    fn call_end(
+        &mut self,
+        _data: &mut EVMData<'_, DB>,
+        _inputs: &CallInputs,
+        remaining_gas: Gas,
+        ret: InstructionResult,
+        out: Bytes,
+    ) -> (InstructionResult, Gas, Bytes) {
+        self.pop(ret, &remaining_gas);
+        (ret, remaining_gas, out)
+    }
+
+    fn create(
+        &mut self,
+        _data: &mut EVMData<'_, DB>,
+        inputs: &mut CreateInputs,
+    ) -> (InstructionResult, Option<B160>, Gas, Bytes) {
+        // address is known only after the create is done.
+        self.push(inputs.caller, None, inputs.scheme.into(), inputs.gas_limit);
+        (InstructionResult::Continue, None, Gas::new(0), Bytes::new())
+    }
+
+    fn create_end(
+        &mut self,
+        _data: &mut EVMData<'_, DB>,
+        _inputs: &CreateInputs,
+        ret: InstructionResult,
+        address: Option<B160>,
+        remaining_gas: Gas,
+        out: Bytes,
+    ) -> (InstructionResult, Option<B160>, Gas, Bytes) {
+        if let Some(edge) = self.pop(ret, &remaining_gas) {
+            edge.to = address;
+        }
+        (ret, address, remaining_gas, out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primitives::{hex_literal::hex, AccountInfo, Bytecode, TransactTo};
+    use crate::InMemoryDB;
+
+    #[test]
+    fn records_nested_calls() {
+        let caller = B160([0x10; 20]);
+        let outer = B160([0x20; 20]);
+        let inner = B160([0x30; 20]);
+
+        // CALL(gas, inner, 0, 0, 0, 0, 0) STOP
+        let mut code = hex!("600060006000600060007f").to_vec();
+        code.extend_from_slice(&[0; 12]);
+        code.extend_from_slice(&inner.0);
+        code.extend_from_slice(&hex!("5af100"));
+
+        let mut db = InMemoryDB::default();
+        db.insert_account_info(
+            outer,
+            AccountInfo::new(Default::default(), 0, Bytecode::new_raw(code.into())),
+        );
+        // PUSH1 0 PUSH1 0 REVERT
+        db.insert_account_info(
+            inner,
+            AccountInfo::new(
+                Default::default(),
+                0,
+                Bytecode::new_raw(hex!("60006000fd").to_vec().into()),
+            ),
+        );
+
+        let mut evm = crate::new();
+        evm.database(db);
+        evm.env.tx.caller = caller;
+        evm.env.tx.transact_to = TransactTo::Call(outer);
+
+        let mut inspector = CallGraphInspector::new();
+        evm.inspect(&mut inspector).unwrap();
+
+        let edges = inspector.edges();
+        assert_eq!(edges.len(), 2);
+        assert_eq!((edges[0].from, edges[0].to), (caller, Some(outer)));
+        assert_eq!((edges[1].from, edges[1].to), (outer, Some(inner)));
+        assert_eq!(edges[1].depth, 1);
+        assert!(edges[0].is_success());
+        assert_eq!(edges[1].result, InstructionResult::Revert);
+        if crate::USE_GAS {
+            assert!(edges[0].gas_used > edges[1].gas_used);
+        }
+
+        let dot = inspector.to_dot();
+        assert!(dot.contains(&format!(
+            "\"{outer:#x}\" -> \"{inner:#x}\" [label=\"2: CALL gas {} Revert\", style=dashed];",
+            edges[1].gas_used
+        )));
+        let mermaid = inspector.to_mermaid();
+        assert!(mermaid.contains(&format!("n1[\"{outer:#x}\"]")));
+        assert!(mermaid.contains("n1 -.->|\"2: CALL gas"));
+    }
+}
@@ -0,0 +1,454 @@
+//! Inspector producing call frames of geth `callTracer`.
+//!
+//! With `serde` feature [CallFrame] serializes to the same JSON as `debug_traceTransaction`
+//! with `{"tracer": "callTracer"}`, plus code provenance of every frame: address whose
+//! code was executed, address whose storage it used and hash of the executed code.
+use super::call_graph::CallKind;
+use crate::interpreter::{
+    return_ok, CallInputs, CallScheme, CreateInputs, Gas, InstructionResult, Interpreter,
+};
+use crate::primitives::{Bytes, RevertReason, SpecId, B160, B256, U256};
+use crate::{Database, EVMData, Inspector};
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+/// Call or create with its subcalls.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+pub struct CallFrame {
+    #[cfg_attr(feature = "serde", serde(rename = "type"))]
+    pub kind: CallKind,
+    pub from: B160,
+    /// Created address, none if create failed.
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub to: Option<B160>,
+    /// None for `DELEGATECALL` and `STATICCALL`.
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub value: Option<U256>,
+    /// Address whose code is executed, differs from `context_address` for `DELEGATECALL`
+    /// and `CALLCODE`. None if create failed.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub code_address: Option<B160>,
+    /// Address whose storage and balance the code uses. None if create failed.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub context_address: Option<B160>,
+    /// Hash of the executed code, init code for creates. None for precompiles and frames
+    /// that did not execute.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub code_hash: Option<B256>,
+    #[cfg_attr(feature = "serde", serde(with = "hex_u64"))]
+    pub gas: u64,
+    #[cfg_attr(feature = "serde", serde(with = "hex_u64"))]
+    pub gas_used: u64,
+    #[cfg_attr(
+        feature = "serde",
+        serde(with = "crate::primitives::utilities::serde_hex_bytes")
+    )]
+    pub input: Bytes,
+    #[cfg_attr(
+        feature = "serde",
+        serde(
+            default,
+            skip_serializing_if = "Bytes::is_empty",
+            with = "crate::primitives::utilities::serde_hex_bytes"
+        )
+    )]
+    pub output: Bytes,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub error: Option<String>,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub revert_reason: Option<String>,
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Vec::is_empty")
+    )]
+    pub calls: Vec<CallFrame>,
+}
+
+/// Inspector building [CallFrame] of the transaction.
+///
+/// Gas of the outermost frame is gas limit of the transaction and its gas used is gas used
+/// by the transaction, intrinsic gas and refund included, same as in geth.
+#[derive(Clone, Debug, Default)]
+pub struct CallTracer {
+    stack: Vec<CallFrame>,
+    root: Option<CallFrame>,
+}
+
+impl CallTracer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Outermost frame, set once the transaction is done.
+    pub fn frame(&self) -> Option<&CallFrame> {
+        self.root.as_ref()
+    }
+
+    pub fn into_frame(self) -> Option<CallFrame> {
+        self.root
+    }
+
+    fn push<DB: Database>(&mut self, data: &EVMData<'_, DB>, mut frame: CallFrame) {
+        if self.stack.is_empty() {
+            self.root = None;
+            frame.gas = data.env.tx.gas_limit;
+        }
+        self.stack.push(frame);
+    }
+
+    fn pop<DB: Database>(
+        &mut self,
+        data: &EVMData<'_, DB>,
+        gas_limit: u64,
+        ret: InstructionResult,
+        gas: &Gas,
+        out: &Bytes,
+    ) -> Option<&mut CallFrame> {
+        let mut frame = self.stack.pop()?;
+        let spent = match ret {
+            return_ok!() | InstructionResult::Revert => gas.spend(),
+            // calls that fail before executing return all gas.
+            InstructionResult::CallTooDeep | InstructionResult::OutOfFund => 0,
+            _ => gas_limit,
+        };
+        frame.gas_used = if self.stack.is_empty() {
+            // intrinsic gas is what the transaction didn't give to the frame.
+            let total = frame.gas - gas_limit + spent;
+            let refund = if matches!(ret, return_ok!()) {
+                let quotient = if SpecId::enabled(data.env.cfg.spec_id, SpecId::LONDON) {
+                    5
+                } else {
+                    2
+                };
+                (gas.refunded().max(0) as u64).min(total / quotient)
+            } else {
+                0
+            };
+            total - refund
+        } else {
+            spent
+        };
+        frame.output = out.clone();
+        if !matches!(ret, return_ok!()) {
+            frame.error = Some(error_message(ret).to_string());
+        }
+        if ret == InstructionResult::Revert {
+            frame.revert_reason = revert_reason(out);
+        }
+        let calls = match self.stack.last_mut() {
+            Some(parent) => &mut parent.calls,
+            None => {
+                self.root = Some(frame);
+                return self.root.as_mut();
+            }
+        };
+        calls.push(frame);
+        calls.last_mut()
+    }
+}
+
+impl<DB: Database> Inspector<DB> for CallTracer {
+    fn initialize_interp(
+        &mut self,
+        _interp: &mut Interpreter,
+        data: &mut EVMData<'_, DB>,
+    ) -> InstructionResult {
+        // code of the call is loaded by now, creates have the hash of init code already.
+        if let Some(frame) = self.stack.last_mut() {
+            if frame.code_hash.is_none() {
+                frame.code_hash = frame
+                    .code_address
+                    .and_then(|address| data.journaled_state.state.get(&address))
+                    .map(|account| account.info.code_hash);
+            }
+        }
+        InstructionResult::Continue
+    }
+
+    fn call(
+        &mut self,
+        data: &mut EVMData<'_, DB>,
+        inputs: &mut CallInputs,
+    ) -> (InstructionResult, Gas, Bytes) {
+        let context = &inputs.context;
+        // address whose code makes the call is caller of the frame, except for delegate
+        // calls and callcodes that execute in its context.
+        let (from, to, value) = match context.scheme {
+            CallScheme::Call => (context.caller, context.address, Some(inputs.transfer.value)),
+            CallScheme::StaticCall => (context.caller, context.address, None),
+            CallScheme::CallCode => (
+                context.address,
+                inputs.contract,
+                Some(inputs.transfer.value),
+            ),
+            CallScheme::DelegateCall => (context.address, inputs.contract, None),
+        };
+        self.push(
+            data,
+            CallFrame {
+                kind: context.scheme.into(),
+                from,
+                to: Some(to),
+                value,
+                code_address: Some(inputs.contract),
+                context_address: Some(context.address),
+                code_hash: None,
+                gas: inputs.gas_limit,
+                gas_used: 0,
+                input: inputs.input.clone(),
+                output: Bytes::new(),
+                error: None,
+                revert_reason: None,
+                calls: Vec::new(),
+            },
+        );
+        (InstructionResult::Continue, Gas::new(0), Bytes::new())
+    }
+
+    fn call_end(
+        &mut self,
+        data: &mut EVMData<'_, DB>,
+        inputs: &CallInputs,
+        remaining_gas: Gas,
+        ret: InstructionResult,
+        out: Bytes,
+    ) -> (InstructionResult, Gas, Bytes) {
+        self.pop(data, inputs.gas_limit, ret, &remaining_gas, &out);
+        (ret, remaining_gas, out)
+    }
+
+    fn create(
+        &mut self,
+        data: &mut EVMData<'_, DB>,
+        inputs: &mut CreateInputs,
+    ) -> (InstructionResult, Option<B160>, Gas, Bytes) {
+        self.push(
+            data,
+            CallFrame {
+                kind: inputs.scheme.into(),
+                from: inputs.caller,
+                to: None,
+                value: Some(inputs.value),
+                code_address: None,
+                context_address: None,
+                code_hash: Some(data.env.cfg.code_hasher.hash(&inputs.init_code)),
+                gas: inputs.gas_limit,
+                gas_used: 0,
+                input: inputs.init_code.clone(),
+                output: Bytes::new(),
+                error: None,
+                revert_reason: None,
+                calls: Vec::new(),
+            },
+        );
+        (InstructionResult::Continue, None, Gas::new(0), Bytes::new())
+    }
+
+    fn create_end(
+        &mut self,
+        data: &mut EVMData<'_, DB>,
+        inputs: &CreateInputs,
+        ret: InstructionResult,
+        address: Option<B160>,
+        remaining_gas: Gas,
+        out: Bytes,
+    ) -> (InstructionResult, Option<B160>, Gas, Bytes) {
+        if let Some(frame) = self.pop(data, inputs.gas_limit, ret, &remaining_gas, &out) {
+            frame.to = address;
+            frame.code_address = address;
+            frame.context_address = address;
+        }
+        (ret, address, remaining_gas, out)
+    }
+}
+
+/// Error of the frame as geth reports it.
+pub(crate) fn error_message(ret: InstructionResult) -> &'static str {
+    use InstructionResult::*;
+    match ret {
+        Revert => "execution reverted",
+        OutOfGas | MemoryOOG | MemoryLimitOOG | PrecompileOOG | InvalidOperandOOG => "out of gas",
+        OpcodeNotFound | InvalidFEOpcode | NotActivated => "invalid opcode",
+        CallNotAllowedInsideStatic | StateChangeDuringStaticCall => "write protection",
+        InvalidJump => "invalid jump destination",
+        StackUnderflow => "stack underflow",
+        StackOverflow => "stack limit reached 1024 (1023)",
+        OutOfOffset => "return data out of bounds",
+        CreateCollision => "contract address collision",
+        OutOfFund => "insufficient balance for transfer",
+        CallTooDeep => "max call depth exceeded",
+        NonceOverflow => "nonce uint64 overflow",
+        CreateContractSizeLimit => "max code size exceeded",
+        CreateContractStartingWithEF => "invalid code: must not begin with 0xef",
+        CreateInitcodeSizeLimit => "max initcode size exceeded",
+        InvalidEOFCode => "invalid eof code",
+        PrecompileError => "precompiled contract failed",
+        _ => "execution halted",
+    }
+}
+
+/// Message of `Error(string)` revert data.
+fn revert_reason(out: &[u8]) -> Option<String> {
+    match RevertReason::decode(out)? {
+        RevertReason::Error(message) => Some(message),
+        RevertReason::Panic(_) => None,
+    }
+}
+
+/// Serde of u64 as hex quantity.
+#[cfg(feature = "serde")]
+pub(crate) mod hex_u64 {
+    use alloc::string::{String, ToString};
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(value: &u64, s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_str(&alloc::format!("{value:#x}"))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<u64, D::Error> {
+        let value = String::deserialize(d)?;
+        u64::from_str_radix(value.trim_start_matches("0x"), 16)
+            .map_err(|e| serde::de::Error::custom(e.to_string()))
+    }
+}
+
+#[cfg(all(test, not(feature = "no_gas_measuring")))]
+mod tests {
+    use super::*;
+    use crate::primitives::{hex_literal::hex, AccountInfo, Bytecode, TransactTo};
+    use crate::InMemoryDB;
+
+    /// Revert data of `revert("nope")`.
+    const NOPE: [u8; 100] = hex!("08c379a0000000000000000000000000000000000000000000000000000000000000002000000000000000000000000000000000000000000000000000000000000000046e6f706500000000000000000000000000000000000000000000000000000000");
+
+    #[test]
+    fn decodes_revert_reason() {
+        assert_eq!(revert_reason(&NOPE), Some("nope".to_string()));
+        assert_eq!(revert_reason(&NOPE[..50]), None);
+        assert_eq!(revert_reason(&[]), None);
+    }
+
+    #[test]
+    fn traces_nested_calls() {
+        let caller = B160([0x10; 20]);
+        let outer = B160([0x20; 20]);
+        let inner = B160([0x30; 20]);
+
+        // STATICCALL(GAS, inner, 0, 4, 0, 0) STOP, with 0xaabbccdd as call data.
+        let mut code = hex!("63aabbccdd60e01b6000526000600060046000").to_vec();
+        code.push(0x7f);
+        code.extend_from_slice(&[0; 12]);
+        code.extend_from_slice(&inner.0);
+        code.extend_from_slice(&hex!("5afa00"));
+        let mut db = InMemoryDB::default();
+        db.insert_account_info(
+            outer,
+            AccountInfo::new(U256::ZERO, 0, Bytecode::new_raw(code.into())),
+        );
+        // revert with `NOPE` stored in memory.
+        let mut revert = Vec::new();
+        for (i, chunk) in NOPE.chunks(32).enumerate() {
+            let mut word = [0u8; 32];
+            word[..chunk.len()].copy_from_slice(chunk);
+            revert.push(0x7f);
+            revert.extend_from_slice(&word);
+            revert.extend_from_slice(&[0x60, (i * 32) as u8, 0x52]);
+        }
+        revert.extend_from_slice(&[0x60, NOPE.len() as u8, 0x60, 0x00, 0xfd]);
+        db.insert_account_info(
+            inner,
+            AccountInfo::new(U256::ZERO, 0, Bytecode::new_raw(revert.into())),
+        );
+
+        let mut evm = crate::new();
+        evm.database(db);
+        evm.env.tx.caller = caller;
+        evm.env.tx.gas_limit = 100_000;
+        evm.env.tx.data = hex!("01").to_vec().into();
+        evm.env.tx.transact_to = TransactTo::Call(outer);
+        let mut tracer = CallTracer::new();
+        let result = evm.inspect(&mut tracer).unwrap().result;
+
+        let frame = tracer.into_frame().unwrap();
+        assert_eq!(frame.kind, CallKind::Call);
+        assert_eq!((frame.from, frame.to), (caller, Some(outer)));
+        assert_eq!(frame.value, Some(U256::ZERO));
+        assert_eq!(frame.gas, 100_000);
+        assert_eq!(frame.gas_used, result.gas_used());
+        assert_eq!(frame.error, None);
+        assert_eq!(frame.calls.len(), 1);
+
+        let call = &frame.calls[0];
+        assert_eq!(call.kind, CallKind::StaticCall);
+        assert_eq!((call.from, call.to, call.value), (outer, Some(inner), None));
+        assert_eq!(call.input, Bytes::from_static(&hex!("aabbccdd")));
+        assert_eq!(call.output, Bytes::from_static(&NOPE));
+        assert_eq!(call.error.as_deref(), Some("execution reverted"));
+        assert_eq!(call.revert_reason.as_deref(), Some("nope"));
+
+        #[cfg(feature = "serde")]
+        {
+            let json = serde_json::to_value(&frame).unwrap();
+            assert_eq!(json["type"], "CALL");
+            assert_eq!(json["gas"], "0x186a0");
+            assert_eq!(json["value"], "0x0");
+            assert_eq!(json["input"], "0x01");
+            assert!(json.get("output").is_none());
+            assert!(json.get("error").is_none());
+            let call = &json["calls"][0];
+            assert_eq!(call["type"], "STATICCALL");
+            assert_eq!(call["to"], format!("{inner:#x}"));
+            assert_eq!(call["input"], "0xaabbccdd");
+            assert!(call.get("value").is_none());
+            assert!(call.get("calls").is_none());
+            assert_eq!(call["revertReason"], "nope");
+            assert_eq!(call["gasUsed"], format!("{:#x}", frame.calls[0].gas_used));
+        }
+    }
+
+    #[test]
+    fn records_code_provenance() {
+        let proxy = B160([0x20; 20]);
+        let library = B160([0x30; 20]);
+
+        // DELEGATECALL(GAS, library, 0, 0, 0, 0) STOP
+        let mut code = hex!("600060006000600073").to_vec();
+        code.extend_from_slice(&library.0);
+        code.extend_from_slice(&hex!("5af400"));
+        let library_code = Bytecode::new_raw(hex!("600160005500").to_vec().into());
+        let library_hash = library_code.hash();
+        let mut db = InMemoryDB::default();
+        db.insert_account_info(
+            proxy,
+            AccountInfo::new(U256::ZERO, 0, Bytecode::new_raw(code.into())),
+        );
+        db.insert_account_info(library, AccountInfo::new(U256::ZERO, 0, library_code));
+
+        let mut evm = crate::new();
+        evm.database(db);
+        evm.env.tx.gas_limit = 100_000;
+        evm.env.tx.transact_to = TransactTo::Call(proxy);
+        let mut tracer = CallTracer::new();
+        assert!(evm.inspect(&mut tracer).unwrap().result.is_success());
+
+        let frame = tracer.into_frame().unwrap();
+        assert_eq!(frame.code_address, Some(proxy));
+        assert_eq!(frame.context_address, Some(proxy));
+        let call = &frame.calls[0];
+        assert_eq!(call.kind, CallKind::DelegateCall);
+        assert_eq!(call.code_address, Some(library));
+        assert_eq!(call.context_address, Some(proxy));
+        assert_eq!(call.code_hash, Some(library_hash));
+    }
+}
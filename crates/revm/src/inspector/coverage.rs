@@ -0,0 +1,256 @@
+//! Code coverage inspector for fuzzers.
+//!
+//! Executed program counters and taken and not taken `JUMPI` branches are recorded per
+//! code hash in bitmaps. Coverage of runs is combined with [CoverageMap::merge], which
+//! tells if a run reached anything new, and compared with [CoverageMap::diff].
+use crate::interpreter::{opcode, InstructionResult, Interpreter};
+use crate::primitives::bitvec::vec::BitVec;
+use crate::primitives::{HashMap, B256, U256};
+use crate::{Database, EVMData, Inspector};
+use alloc::vec::Vec;
+
+/// Coverage of one code.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CodeCoverage {
+    /// Bit per program counter, set if the opcode at it was executed.
+    pcs: BitVec,
+    /// Two bits per program counter of `JUMPI`, the first is set if the branch was not
+    /// taken and the second if it was.
+    branches: BitVec,
+}
+
+impl CodeCoverage {
+    pub fn is_executed(&self, pc: usize) -> bool {
+        self.pcs.get(pc).is_some_and(|bit| *bit)
+    }
+
+    /// If the branch of `JUMPI` at `pc` was (not taken, taken).
+    pub fn branch(&self, pc: usize) -> (bool, bool) {
+        let bit = |at: usize| self.branches.get(at).is_some_and(|bit| *bit);
+        (bit(2 * pc), bit(2 * pc + 1))
+    }
+
+    /// Executed program counters, in ascending order.
+    pub fn executed(&self) -> impl Iterator<Item = usize> + '_ {
+        self.pcs.iter_ones()
+    }
+
+    /// Number of executed opcodes and branch directions.
+    pub fn count(&self) -> usize {
+        self.pcs.count_ones() + self.branches.count_ones()
+    }
+
+    fn set_pc(&mut self, pc: usize) {
+        set(&mut self.pcs, pc);
+    }
+
+    fn set_branch(&mut self, pc: usize, taken: bool) {
+        set(&mut self.branches, 2 * pc + taken as usize);
+    }
+
+    /// Set bits of `other`, number of bits that were not set is returned.
+    fn merge(&mut self, other: &Self) -> usize {
+        or(&mut self.pcs, &other.pcs) + or(&mut self.branches, &other.branches)
+    }
+
+    /// Bits of self that are not set in `other`.
+    fn diff(&self, other: &Self) -> Self {
+        Self {
+            pcs: and_not(&self.pcs, &other.pcs),
+            branches: and_not(&self.branches, &other.branches),
+        }
+    }
+}
+
+fn set(bits: &mut BitVec, at: usize) {
+    if at >= bits.len() {
+        bits.resize(at + 1, false);
+    }
+    bits.set(at, true);
+}
+
+fn or(bits: &mut BitVec, other: &BitVec) -> usize {
+    if other.len() > bits.len() {
+        bits.resize(other.len(), false);
+    }
+    let mut new = 0;
+    for at in other.iter_ones() {
+        if !bits.replace(at, true) {
+            new += 1;
+        }
+    }
+    new
+}
+
+fn and_not(bits: &BitVec, other: &BitVec) -> BitVec {
+    let mut out = bits.clone();
+    for at in other.iter_ones().take_while(|at| *at < bits.len()) {
+        out.set(at, false);
+    }
+    out
+}
+
+/// Coverage of codes, by code hash.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CoverageMap {
+    codes: Vec<(B256, CodeCoverage)>,
+    index: HashMap<B256, usize>,
+}
+
+impl CoverageMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, code_hash: &B256) -> Option<&CodeCoverage> {
+        self.index.get(code_hash).map(|at| &self.codes[*at].1)
+    }
+
+    /// Coverage of codes in order they were first executed.
+    pub fn iter(&self) -> impl Iterator<Item = (&B256, &CodeCoverage)> {
+        self.codes.iter().map(|(hash, coverage)| (hash, coverage))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.codes.is_empty()
+    }
+
+    /// Number of executed opcodes and branch directions of all codes.
+    pub fn count(&self) -> usize {
+        self.codes
+            .iter()
+            .map(|(_, coverage)| coverage.count())
+            .sum()
+    }
+
+    /// Add coverage of `other`, number of opcodes and branch directions that were not
+    /// covered before is returned.
+    pub fn merge(&mut self, other: &CoverageMap) -> usize {
+        other
+            .iter()
+            .map(|(hash, coverage)| {
+                let at = self.entry(*hash);
+                self.codes[at].1.merge(coverage)
+            })
+            .sum()
+    }
+
+    /// Coverage of self that `other` doesn't have, codes with nothing new are left out.
+    pub fn diff(&self, other: &CoverageMap) -> CoverageMap {
+        let mut out = CoverageMap::new();
+        for (hash, coverage) in self.iter() {
+            let diff = match other.get(hash) {
+                Some(other) => coverage.diff(other),
+                None => coverage.clone(),
+            };
+            if diff.count() != 0 {
+                let at = out.entry(*hash);
+                out.codes[at].1 = diff;
+            }
+        }
+        out
+    }
+
+    fn entry(&mut self, code_hash: B256) -> usize {
+        *self.index.entry(code_hash).or_insert_with(|| {
+            self.codes.push((code_hash, CodeCoverage::default()));
+            self.codes.len() - 1
+        })
+    }
+}
+
+/// Inspector recording [CoverageMap] of executed code.
+///
+/// Map lookup is done only when executed code changes, every other step sets a bit.
+#[derive(Clone, Debug, Default)]
+pub struct CoverageInspector {
+    map: CoverageMap,
+    /// Hash of code of the last step and its index in the map.
+    current: Option<(B256, usize)>,
+}
+
+impl CoverageInspector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn coverage(&self) -> &CoverageMap {
+        &self.map
+    }
+
+    pub fn into_coverage(self) -> CoverageMap {
+        self.map
+    }
+
+    /// Take coverage recorded so far and start an empty one.
+    pub fn take_coverage(&mut self) -> CoverageMap {
+        self.current = None;
+        core::mem::take(&mut self.map)
+    }
+}
+
+impl<DB: Database> Inspector<DB> for CoverageInspector {
+    fn step(&mut self, interp: &mut Interpreter, _data: &mut EVMData<'_, DB>) -> InstructionResult {
+        let hash = interp.contract.bytecode.hash();
+        let at = match self.current {
+            Some((current, at)) if current == hash => at,
+            _ => {
+                let at = self.map.entry(hash);
+                self.current = Some((hash, at));
+                at
+            }
+        };
+        let coverage = &mut self.map.codes[at].1;
+        let pc = interp.program_counter();
+        coverage.set_pc(pc);
+        if interp.current_opcode() == opcode::JUMPI {
+            if let Ok(condition) = interp.stack.peek(1) {
+                coverage.set_branch(pc, condition != U256::ZERO);
+            }
+        }
+        InstructionResult::Continue
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primitives::{hex_literal::hex, keccak256, AccountInfo, Bytecode, TransactTo, B160};
+    use crate::InMemoryDB;
+
+    #[test]
+    fn records_coverage() {
+        let contract = B160([0x20; 20]);
+        // JUMPI(5, CALLDATASIZE) STOP JUMPDEST STOP
+        let code = hex!("3660055700 5b00");
+        let mut db = InMemoryDB::default();
+        db.insert_account_info(
+            contract,
+            AccountInfo::new(U256::ZERO, 0, Bytecode::new_raw(code.to_vec().into())),
+        );
+        let mut evm = crate::new();
+        evm.database(db);
+        evm.env.tx.transact_to = TransactTo::Call(contract);
+        let hash = keccak256(&code);
+
+        let mut inspector = CoverageInspector::new();
+        evm.inspect(&mut inspector).unwrap();
+        let mut total = inspector.take_coverage();
+        let coverage = total.get(&hash).unwrap();
+        assert_eq!(coverage.executed().collect::<Vec<_>>(), [0, 1, 3, 4]);
+        assert_eq!(coverage.branch(3), (true, false));
+        assert_eq!(total.count(), 5);
+
+        evm.env.tx.data = hex!("01").to_vec().into();
+        evm.inspect(&mut inspector).unwrap();
+        let taken = inspector.into_coverage();
+        let diff = taken.diff(&total);
+        let new = diff.get(&hash).unwrap();
+        assert_eq!(new.executed().collect::<Vec<_>>(), [5, 6]);
+        assert_eq!(new.branch(3), (false, true));
+        assert_eq!(total.merge(&taken), 3);
+        assert_eq!(total.merge(&taken), 0);
+        assert_eq!(total.get(&hash).unwrap().branch(3), (true, true));
+        assert!(total.diff(&taken).diff(&total).is_empty());
+    }
+}
@@ -0,0 +1,147 @@
+//! Inspector stopping execution once a log or a storage write matches a predicate.
+//!
+//! Every frame that is executing is stopped as if it ran `STOP`, so changes made so far
+//! are kept and the transaction succeeds with partial state.
+use crate::interpreter::{opcode, InstructionResult, Interpreter};
+use crate::primitives::{Bytes, B160, B256, U256};
+use crate::{Database, EVMData, Inspector};
+
+/// Event the predicate of [EarlyStopInspector] is called with.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExecutionEvent<'a> {
+    Log {
+        address: B160,
+        topics: &'a [B256],
+        data: &'a Bytes,
+    },
+    StorageWrite {
+        address: B160,
+        slot: U256,
+        value: U256,
+    },
+}
+
+#[derive(Clone, Debug)]
+pub struct EarlyStopInspector<F> {
+    predicate: F,
+    /// SSTORE that is executing, predicate is called after it is done.
+    pending_write: Option<(B160, U256, U256)>,
+    matched: bool,
+}
+
+impl<F> EarlyStopInspector<F>
+where
+    F: FnMut(&ExecutionEvent<'_>) -> bool,
+{
+    pub fn new(predicate: F) -> Self {
+        Self {
+            predicate,
+            pending_write: None,
+            matched: false,
+        }
+    }
+
+    /// If predicate matched and execution was stopped.
+    pub fn is_stopped(&self) -> bool {
+        self.matched
+    }
+
+    fn status(&self) -> InstructionResult {
+        if self.matched {
+            InstructionResult::Stop
+        } else {
+            InstructionResult::Continue
+        }
+    }
+}
+
+impl<DB, F> Inspector<DB> for EarlyStopInspector<F>
+where
+    DB: Database,
+    F: FnMut(&ExecutionEvent<'_>) -> bool,
+{
+    fn step(&mut self, interp: &mut Interpreter, _data: &mut EVMData<'_, DB>) -> InstructionResult {
+        if !self.matched && interp.current_opcode() == opcode::SSTORE {
+            if let (Ok(slot), Ok(value)) = (interp.stack.peek(0), interp.stack.peek(1)) {
+                self.pending_write = Some((interp.contract.address, slot, value));
+            }
+        }
+        // frames that were waiting for the stopped subcall end on their next step.
+        self.status()
+    }
+
+    fn log(&mut self, _data: &mut EVMData<'_, DB>, address: &B160, topics: &[B256], data: &Bytes) {
+        if !self.matched {
+            self.matched = (self.predicate)(&ExecutionEvent::Log {
+                address: *address,
+                topics,
+                data,
+            });
+        }
+    }
+
+    fn step_end(
+        &mut self,
+        _interp: &mut Interpreter,
+        _data: &mut EVMData<'_, DB>,
+        eval: InstructionResult,
+    ) -> InstructionResult {
+        if let Some((address, slot, value)) = self.pending_write.take() {
+            // failed write is reverted, it is not reported.
+            if eval == InstructionResult::Continue {
+                self.matched = (self.predicate)(&ExecutionEvent::StorageWrite {
+                    address,
+                    slot,
+                    value,
+                });
+            }
+        }
+        self.status()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primitives::{hex_literal::hex, AccountInfo, Bytecode, TransactTo};
+    use crate::InMemoryDB;
+
+    #[test]
+    fn stops_on_match() {
+        let contract = B160([0x10; 20]);
+        // SSTORE(0, 1) LOG0(0, 0) SSTORE(1, 2) SSTORE(2, 3)
+        let code = hex!("600160005560006000a06002600155600360025500");
+        let mut db = InMemoryDB::default();
+        db.insert_account_info(
+            contract,
+            AccountInfo::new(U256::ZERO, 0, Bytecode::new_raw(code.to_vec().into())),
+        );
+        let mut evm = crate::new();
+        evm.database(db);
+        evm.env.tx.caller = B160([0x20; 20]);
+        evm.env.tx.transact_to = TransactTo::Call(contract);
+
+        let mut inspector = EarlyStopInspector::new(
+            |event: &ExecutionEvent<'_>| matches!(event, ExecutionEvent::StorageWrite { slot, .. } if *slot == U256::from(1)),
+        );
+        let result = evm.inspect(&mut inspector).unwrap();
+        assert!(inspector.is_stopped());
+        assert!(result.result.is_success());
+        let storage = &result.state[&contract].storage;
+        assert_eq!(storage[&U256::from(1)].present_value, U256::from(2));
+        assert!(!storage.contains_key(&U256::from(2)));
+
+        let mut inspector = EarlyStopInspector::new(|event: &ExecutionEvent<'_>| {
+            matches!(event, ExecutionEvent::Log { .. })
+        });
+        let result = evm.inspect(&mut inspector).unwrap();
+        assert_eq!(result.result.logs().len(), 1);
+        assert!(!result.state[&contract].storage.contains_key(&U256::from(1)));
+
+        // without match transaction runs to the end.
+        let mut inspector = EarlyStopInspector::new(|_: &ExecutionEvent<'_>| false);
+        let result = evm.inspect(&mut inspector).unwrap();
+        assert!(!inspector.is_stopped());
+        assert!(result.state[&contract].storage.contains_key(&U256::from(2)));
+    }
+}
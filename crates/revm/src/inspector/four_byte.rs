@@ -0,0 +1,108 @@
+//! Inspector counting selectors of calls, same as the geth `4byteTracer`.
+//!
+//! Calls with at least 4 bytes of input, other than to precompiles, are counted by selector
+//! and size of the arguments that follow it. Failed calls are counted too, contract
+//! creations are not.
+use crate::evm::to_precompile_id;
+use crate::interpreter::{CallInputs, Gas, InstructionResult};
+use crate::precompile::Precompiles;
+use crate::primitives::{hex, Bytes};
+use crate::{Database, EVMData, Inspector};
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::String;
+
+/// Inspector collecting the `4byteTracer` output.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FourByteInspector {
+    /// Calls by selector and size of the arguments.
+    counts: BTreeMap<([u8; 4], usize), u64>,
+}
+
+impl FourByteInspector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of calls by selector and size of the arguments.
+    pub fn counts(&self) -> &BTreeMap<([u8; 4], usize), u64> {
+        &self.counts
+    }
+
+    /// Counts keyed as in the geth output, `0x` prefixed selector and size of the arguments,
+    /// e.g. `0x27dc297e-128`.
+    pub fn result(&self) -> BTreeMap<String, u64> {
+        self.counts
+            .iter()
+            .map(|((selector, size), count)| {
+                (format!("0x{}-{size}", hex::encode(selector)), *count)
+            })
+            .collect()
+    }
+}
+
+impl<DB: Database> Inspector<DB> for FourByteInspector {
+    fn call(
+        &mut self,
+        data: &mut EVMData<'_, DB>,
+        inputs: &mut CallInputs,
+    ) -> (InstructionResult, Gas, Bytes) {
+        let is_precompile = Precompiles::new(to_precompile_id(data.env.cfg.spec_id))
+            .contains(&inputs.contract.0)
+            || data
+                .env
+                .cfg
+                .custom_precompiles
+                .get(&inputs.contract)
+                .is_some();
+        if inputs.input.len() >= 4 && !is_precompile {
+            let selector = inputs.input[..4].try_into().unwrap();
+            *self
+                .counts
+                .entry((selector, inputs.input.len() - 4))
+                .or_default() += 1;
+        }
+        (InstructionResult::Continue, Gas::new(0), Bytes::new())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primitives::{hex_literal::hex, AccountInfo, Bytecode, TransactTo, B160, U256};
+    use crate::InMemoryDB;
+
+    #[test]
+    fn counts_selectors() {
+        let caller = B160([0x10; 20]);
+        let contract = B160([0x20; 20]);
+        let callee = B160([0x30; 20]);
+        // MSTORE(0, 0xaabbccdd << 224) then CALL(gas, callee, 0, 0, 4 + 32, 0, 0) twice,
+        // STATICCALL(gas, 2, 0, 4, 0, 0) to the sha256 precompile.
+        let mut code = hex!("63aabbccdd60e01b600052").to_vec();
+        for _ in 0..2 {
+            code.extend_from_slice(&hex!("6000600060246000600073"));
+            code.extend_from_slice(&callee.0);
+            code.extend_from_slice(&hex!("5af150"));
+        }
+        code.extend_from_slice(&hex!("600060006004600060025afa5000"));
+        let mut db = InMemoryDB::default();
+        db.insert_account_info(
+            contract,
+            AccountInfo::new(U256::ZERO, 0, Bytecode::new_raw(code.into())),
+        );
+
+        let mut evm = crate::new();
+        evm.database(db);
+        evm.env.tx.caller = caller;
+        evm.env.tx.transact_to = TransactTo::Call(contract);
+        evm.env.tx.data = hex!("0102030405").to_vec().into();
+
+        let mut inspector = FourByteInspector::new();
+        evm.inspect(&mut inspector).unwrap();
+        assert_eq!(
+            inspector.result(),
+            BTreeMap::from([("0x01020304-1".into(), 1), ("0xaabbccdd-32".into(), 2)])
+        );
+    }
+}
@@ -0,0 +1,282 @@
+//! Gas profiler inspector.
+//!
+//! Aggregates gas used and execution counts per opcode and per call frame path, and
+//! exports them as folded stacks (`frame;frame;OPCODE gas` lines), the input format of
+//! flamegraph tools such as `inferno-flamegraph` and `flamegraph.pl`.
+use crate::interpreter::{
+    return_ok, CallInputs, CreateInputs, Gas, InstructionResult, Interpreter, OPCODE_JUMPMAP,
+};
+use crate::primitives::{Bytes, CreateScheme, B160};
+use crate::{Database, EVMData, Inspector};
+use alloc::collections::BTreeMap;
+use alloc::{format, string::String, vec::Vec};
+use core::fmt::Write;
+
+/// Executions of an opcode and gas they used.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct OpcodeStats {
+    pub count: u64,
+    /// Gas of the opcode itself, gas of the frames it started is not included.
+    pub gas: u64,
+}
+
+/// Frames executed at a call path and gas they used.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FrameStats {
+    pub calls: u64,
+    /// Gas used by the frames, including their nested frames.
+    pub gas: u64,
+}
+
+/// Frame that is currently executing.
+#[derive(Clone, Debug)]
+struct Frame {
+    path: usize,
+    /// Opcode being executed and gas remaining before it.
+    step: Option<(u8, u64)>,
+    /// Gas used by nested frames started by the opcode being executed.
+    step_children_gas: u64,
+    children_gas: u64,
+    opcodes_gas: u64,
+}
+
+/// Inspector profiling where gas of the transaction goes.
+///
+/// Frames are named by the address whose code runs, creates by their scheme as the
+/// address is not known until they return. Intrinsic gas of the transaction is not part
+/// of any frame.
+#[derive(Clone, Debug, Default)]
+pub struct GasProfiler {
+    opcodes: BTreeMap<u8, OpcodeStats>,
+    /// Call paths, joined by `;`, and their stats, indexed by path id.
+    paths: Vec<(String, FrameStats)>,
+    path_ids: BTreeMap<String, usize>,
+    /// Self gas per path and opcode, gas not spent by opcodes (precompiles, code deposit)
+    /// is recorded without opcode.
+    folded: BTreeMap<(usize, Option<u8>), u64>,
+    stack: Vec<Frame>,
+}
+
+impl GasProfiler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Stats of executed opcodes, ordered by opcode.
+    pub fn opcodes(&self) -> impl Iterator<Item = (u8, &OpcodeStats)> {
+        self.opcodes.iter().map(|(opcode, stats)| (*opcode, stats))
+    }
+
+    /// Stats of executed call paths, in order of first execution.
+    pub fn frames(&self) -> impl Iterator<Item = (&str, &FrameStats)> {
+        self.paths
+            .iter()
+            .map(|(path, stats)| (path.as_str(), stats))
+    }
+
+    /// Profile as folded stacks, one `path;OPCODE gas` line for every opcode executed in a
+    /// call path. Lines with zero gas are skipped.
+    pub fn to_folded(&self) -> String {
+        let mut out = String::new();
+        for ((path, opcode), gas) in &self.folded {
+            if *gas == 0 {
+                continue;
+            }
+            out.push_str(&self.paths[*path].0);
+            if let Some(opcode) = opcode {
+                out.push(';');
+                out.push_str(OPCODE_JUMPMAP[*opcode as usize].unwrap_or("UNKNOWN"));
+            }
+            let _ = writeln!(out, " {gas}");
+        }
+        out
+    }
+
+    fn push(&mut self, name: String) {
+        let path = match self.stack.last() {
+            Some(parent) => format!("{};{name}", self.paths[parent.path].0),
+            None => name,
+        };
+        let path = match self.path_ids.get(&path) {
+            Some(id) => *id,
+            None => {
+                self.path_ids.insert(path.clone(), self.paths.len());
+                self.paths.push((path, FrameStats::default()));
+                self.paths.len() - 1
+            }
+        };
+        self.paths[path].1.calls += 1;
+        self.stack.push(Frame {
+            path,
+            step: None,
+            step_children_gas: 0,
+            children_gas: 0,
+            opcodes_gas: 0,
+        });
+    }
+
+    fn pop(&mut self, result: InstructionResult, gas: &Gas) {
+        let Some(frame) = self.stack.pop() else {
+            return;
+        };
+        let gas_used = match result {
+            return_ok!() | InstructionResult::Revert => gas.spend(),
+            // calls that fail before executing return all gas.
+            InstructionResult::CallTooDeep | InstructionResult::OutOfFund => 0,
+            _ => gas.limit(),
+        };
+        self.paths[frame.path].1.gas += gas_used;
+        let rest = gas_used.saturating_sub(frame.opcodes_gas + frame.children_gas);
+        *self.folded.entry((frame.path, None)).or_default() += rest;
+        if let Some(parent) = self.stack.last_mut() {
+            parent.step_children_gas += gas_used;
+            parent.children_gas += gas_used;
+        }
+    }
+}
+
+impl<DB: Database> Inspector<DB> for GasProfiler {
+    fn step(&mut self, interp: &mut Interpreter, _data: &mut EVMData<'_, DB>) -> InstructionResult {
+        if let Some(frame) = self.stack.last_mut() {
+            frame.step = Some((interp.current_opcode(), interp.gas.remaining()));
+            frame.step_children_gas = 0;
+        }
+        InstructionResult::Continue
+    }
+
+    fn step_end(
+        &mut self,
+        interp: &mut Interpreter,
+        _data: &mut EVMData<'_, DB>,
+        _eval: InstructionResult,
+    ) -> InstructionResult {
+        let Some(frame) = self.stack.last_mut() else {
+            return InstructionResult::Continue;
+        };
+        let Some((opcode, remaining)) = frame.step.take() else {
+            return InstructionResult::Continue;
+        };
+        let gas = remaining
+            .saturating_sub(interp.gas.remaining())
+            .saturating_sub(frame.step_children_gas);
+        frame.opcodes_gas += gas;
+        let stats = self.opcodes.entry(opcode).or_default();
+        stats.count += 1;
+        stats.gas += gas;
+        *self.folded.entry((frame.path, Some(opcode))).or_default() += gas;
+        InstructionResult::Continue
+    }
+
+    fn call(
+        &mut self,
+        _data: &mut EVMData<'_, DB>,
+        inputs: &mut CallInputs,
+    ) -> (InstructionResult, Gas, Bytes) {
+        self.push(format!("{:#x}", inputs.contract));
+        (InstructionResult::Continue, Gas::new(0), Bytes::new())
+    }
+
+    fn call_end(
+        &mut self,
+        _data: &mut EVMData<'_, DB>,
+        _inputs: &CallInputs,
+        remaining_gas: Gas,
+        ret: InstructionResult,
+        out: Bytes,
+    ) -> (InstructionResult, Gas, Bytes) {
+        self.pop(ret, &remaining_gas);
+        (ret, remaining_gas, out)
+    }
+
+    fn create(
+        &mut self,
+        _data: &mut EVMData<'_, DB>,
+        inputs: &mut CreateInputs,
+    ) -> (InstructionResult, Option<B160>, Gas, Bytes) {
+        let name = match inputs.scheme {
+            CreateScheme::Create => "CREATE",
+            CreateScheme::Create2 { .. } => "CREATE2",
+        };
+        self.push(name.into());
+        (InstructionResult::Continue, None, Gas::new(0), Bytes::new())
+    }
+
+    fn create_end(
+        &mut self,
+        _data: &mut EVMData<'_, DB>,
+        _inputs: &CreateInputs,
+        ret: InstructionResult,
+        address: Option<B160>,
+        remaining_gas: Gas,
+        out: Bytes,
+    ) -> (InstructionResult, Option<B160>, Gas, Bytes) {
+        self.pop(ret, &remaining_gas);
+        (ret, address, remaining_gas, out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::opcode;
+    use crate::primitives::{hex_literal::hex, AccountInfo, Bytecode, TransactTo};
+    use crate::InMemoryDB;
+
+    #[test]
+    fn profiles_nested_calls() {
+        let outer = B160([0x20; 20]);
+        let inner = B160([0x30; 20]);
+
+        // CALL(gas, inner, 0, 0, 0, 0, 0) SSTORE(0, 1) STOP
+        let mut code = hex!("600060006000600060007f").to_vec();
+        code.extend_from_slice(&[0; 12]);
+        code.extend_from_slice(&inner.0);
+        code.extend_from_slice(&hex!("5af1 6001600055 00"));
+
+        let mut db = InMemoryDB::default();
+        db.insert_account_info(
+            outer,
+            AccountInfo::new(Default::default(), 0, Bytecode::new_raw(code.into())),
+        );
+        // SSTORE(0, 1) STOP
+        db.insert_account_info(
+            inner,
+            AccountInfo::new(
+                Default::default(),
+                0,
+                Bytecode::new_raw(hex!("600160005500").to_vec().into()),
+            ),
+        );
+
+        let mut evm = crate::new();
+        evm.database(db);
+        evm.env.tx.caller = B160([0x10; 20]);
+        evm.env.tx.transact_to = TransactTo::Call(outer);
+
+        let mut profiler = GasProfiler::new();
+        evm.inspect(&mut profiler).unwrap();
+
+        let opcodes: BTreeMap<_, _> = profiler.opcodes().collect();
+        assert_eq!(opcodes[&opcode::SSTORE].count, 2);
+        assert_eq!(opcodes[&opcode::CALL].count, 1);
+        let frames: Vec<_> = profiler.frames().collect();
+        let inner_path = format!("{outer:#x};{inner:#x}");
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[1].0, inner_path);
+        assert_eq!(frames[1].1.calls, 1);
+
+        if crate::USE_GAS {
+            // both slots are cold and set from zero.
+            assert_eq!(opcodes[&opcode::SSTORE].gas, 2 * 22_100);
+            let folded = profiler.to_folded();
+            assert!(folded.contains(&format!("{inner_path};SSTORE 22100\n")));
+            // folded stacks add up to the gas of the outermost frame.
+            let total: u64 = folded
+                .lines()
+                .map(|line| line.rsplit_once(' ').unwrap().1.parse::<u64>().unwrap())
+                .sum();
+            assert_eq!(total, frames[0].1.gas);
+            assert!(frames[0].1.gas > frames[1].1.gas);
+        }
+    }
+}
@@ -0,0 +1,282 @@
+//! Inspector calling hooks registered for addresses or code hashes.
+//!
+//! Hooks are looked up once when the frame starts, frames without a hook are not checked
+//! again on exit, so watching a few contracts is cheap even when whole blocks are executed.
+use super::call_graph::CallKind;
+use crate::interpreter::{CallInputs, CreateInputs, Gas, InstructionResult};
+use crate::primitives::{Bytes, HashMap, B160, B256, U256};
+use crate::{Database, EVMData, Inspector};
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+
+/// Frame that matched a hook.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FrameContext {
+    pub kind: CallKind,
+    pub caller: B160,
+    /// Called address, none for creates.
+    pub target: Option<B160>,
+    /// Address whose storage and balance the code uses, none for creates.
+    pub context_address: Option<B160>,
+    /// Hash of the executed code, init code for creates.
+    pub code_hash: B256,
+    pub value: U256,
+    /// Call data, init code for creates.
+    pub input: Bytes,
+    pub depth: u64,
+}
+
+/// Event the hook is called with.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FrameEvent<'a> {
+    Enter(&'a FrameContext),
+    Exit {
+        frame: &'a FrameContext,
+        result: InstructionResult,
+        /// Created address for creates.
+        address: Option<B160>,
+        gas_used: u64,
+        output: &'a Bytes,
+    },
+}
+
+pub type FrameHook = Box<dyn FnMut(&FrameEvent<'_>)>;
+
+/// Inspector calling hooks of frames that execute code of watched addresses or code hashes.
+///
+/// Address hooks match called address, which is the address whose code is executed for
+/// `DELEGATECALL` and `CALLCODE`. Creates are only matched by hash of init code.
+#[derive(Default)]
+pub struct HookInspector {
+    hooks: Vec<FrameHook>,
+    by_address: HashMap<B160, Vec<usize>>,
+    by_code_hash: HashMap<B256, Vec<usize>>,
+    /// Context and matched hooks of executing frames, none if frame has no hook.
+    frames: Vec<Option<(FrameContext, Vec<usize>)>>,
+}
+
+impl core::fmt::Debug for HookInspector {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("HookInspector")
+            .field("addresses", &self.by_address.keys())
+            .field("code_hashes", &self.by_code_hash.keys())
+            .finish_non_exhaustive()
+    }
+}
+
+impl HookInspector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Call `hook` for frames executing code of `address`.
+    pub fn on_address(
+        mut self,
+        address: B160,
+        hook: impl FnMut(&FrameEvent<'_>) + 'static,
+    ) -> Self {
+        let id = self.push(hook);
+        self.by_address.entry(address).or_default().push(id);
+        self
+    }
+
+    /// Call `hook` for frames executing code with `code_hash`.
+    pub fn on_code_hash(
+        mut self,
+        code_hash: B256,
+        hook: impl FnMut(&FrameEvent<'_>) + 'static,
+    ) -> Self {
+        let id = self.push(hook);
+        self.by_code_hash.entry(code_hash).or_default().push(id);
+        self
+    }
+
+    fn push(&mut self, hook: impl FnMut(&FrameEvent<'_>) + 'static) -> usize {
+        self.hooks.push(Box::new(hook));
+        self.hooks.len() - 1
+    }
+
+    fn matching(&self, address: Option<B160>, code_hash: Option<B256>) -> Vec<usize> {
+        let mut ids = Vec::new();
+        if let Some(matched) = address.and_then(|address| self.by_address.get(&address)) {
+            ids.extend_from_slice(matched);
+        }
+        if let Some(matched) = code_hash.and_then(|hash| self.by_code_hash.get(&hash)) {
+            ids.extend_from_slice(matched);
+        }
+        ids
+    }
+
+    fn enter(&mut self, frame: Option<(FrameContext, Vec<usize>)>) {
+        if let Some((context, ids)) = &frame {
+            for id in ids {
+                (self.hooks[*id])(&FrameEvent::Enter(context));
+            }
+        }
+        self.frames.push(frame);
+    }
+
+    fn exit(&mut self, result: InstructionResult, address: Option<B160>, gas: &Gas, out: &Bytes) {
+        let Some(Some((frame, ids))) = self.frames.pop() else {
+            return;
+        };
+        let event = FrameEvent::Exit {
+            frame: &frame,
+            result,
+            address,
+            gas_used: gas.spend(),
+            output: out,
+        };
+        for id in ids {
+            (self.hooks[id])(&event);
+        }
+    }
+}
+
+impl<DB: Database> Inspector<DB> for HookInspector {
+    fn call(
+        &mut self,
+        data: &mut EVMData<'_, DB>,
+        inputs: &mut CallInputs,
+    ) -> (InstructionResult, Gas, Bytes) {
+        // called account is already loaded when its call starts.
+        let loaded_hash = || {
+            data.journaled_state
+                .state
+                .get(&inputs.contract)
+                .map_or(B256::zero(), |account| account.info.code_hash)
+        };
+        let code_hash = (!self.by_code_hash.is_empty()).then(loaded_hash);
+        let ids = self.matching(Some(inputs.contract), code_hash);
+        let frame = (!ids.is_empty()).then(|| {
+            let context = &inputs.context;
+            let frame = FrameContext {
+                kind: context.scheme.into(),
+                caller: context.caller,
+                target: Some(inputs.contract),
+                context_address: Some(context.address),
+                code_hash: code_hash.unwrap_or_else(loaded_hash),
+                value: context.apparent_value,
+                input: inputs.input.clone(),
+                depth: data.journaled_state.depth(),
+            };
+            (frame, ids)
+        });
+        self.enter(frame);
+        (InstructionResult::Continue, Gas::new(0), Bytes::new())
+    }
+
+    fn call_end(
+        &mut self,
+        _data: &mut EVMData<'_, DB>,
+        _inputs: &CallInputs,
+        remaining_gas: Gas,
+        ret: InstructionResult,
+        out: Bytes,
+    ) -> (InstructionResult, Gas, Bytes) {
+        self.exit(ret, None, &remaining_gas, &out);
+        (ret, remaining_gas, out)
+    }
+
+    fn create(
+        &mut self,
+        data: &mut EVMData<'_, DB>,
+        inputs: &mut CreateInputs,
+    ) -> (InstructionResult, Option<B160>, Gas, Bytes) {
+        let frame = (!self.by_code_hash.is_empty())
+            .then(|| {
+                let code_hash = data.env.cfg.code_hasher.hash(&inputs.init_code);
+                let ids = self.matching(None, Some(code_hash));
+                (!ids.is_empty()).then(|| {
+                    let frame = FrameContext {
+                        kind: inputs.scheme.into(),
+                        caller: inputs.caller,
+                        target: None,
+                        context_address: None,
+                        code_hash,
+                        value: inputs.value,
+                        input: inputs.init_code.clone(),
+                        depth: data.journaled_state.depth(),
+                    };
+                    (frame, ids)
+                })
+            })
+            .flatten();
+        self.enter(frame);
+        (InstructionResult::Continue, None, Gas::new(0), Bytes::new())
+    }
+
+    fn create_end(
+        &mut self,
+        _data: &mut EVMData<'_, DB>,
+        _inputs: &CreateInputs,
+        ret: InstructionResult,
+        address: Option<B160>,
+        remaining_gas: Gas,
+        out: Bytes,
+    ) -> (InstructionResult, Option<B160>, Gas, Bytes) {
+        self.exit(ret, address, &remaining_gas, &out);
+        (ret, address, remaining_gas, out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primitives::{hex_literal::hex, AccountInfo, Bytecode, TransactTo};
+    use crate::InMemoryDB;
+    use alloc::rc::Rc;
+    use core::cell::RefCell;
+
+    #[test]
+    fn calls_hooks_of_matching_frames() {
+        let proxy = B160([0x20; 20]);
+        let library = B160([0x30; 20]);
+
+        // DELEGATECALL(GAS, library, 0, 0, 0, 0) STOP
+        let mut code = hex!("600060006000600073").to_vec();
+        code.extend_from_slice(&library.0);
+        code.extend_from_slice(&hex!("5af400"));
+        // RETURN(0, 32) of memory, zero.
+        let library_code = Bytecode::new_raw(hex!("60206000f3").to_vec().into());
+        let library_hash = library_code.hash();
+        let mut db = InMemoryDB::default();
+        db.insert_account_info(
+            proxy,
+            AccountInfo::new(U256::ZERO, 0, Bytecode::new_raw(code.into())),
+        );
+        db.insert_account_info(library, AccountInfo::new(U256::ZERO, 0, library_code));
+
+        let events = Rc::new(RefCell::new(Vec::new()));
+        let by_address = events.clone();
+        let by_hash = events.clone();
+        let hooks = HookInspector::new()
+            .on_address(proxy, move |event| {
+                if let FrameEvent::Enter(frame) = event {
+                    by_address.borrow_mut().push(("proxy", frame.depth));
+                }
+            })
+            .on_code_hash(library_hash, move |event| match event {
+                FrameEvent::Enter(frame) => {
+                    assert_eq!(frame.kind, CallKind::DelegateCall);
+                    assert_eq!(frame.target, Some(library));
+                    assert_eq!(frame.context_address, Some(proxy));
+                    by_hash.borrow_mut().push(("enter", frame.depth));
+                }
+                FrameEvent::Exit { result, output, .. } => {
+                    assert_eq!(*result, InstructionResult::Return);
+                    assert_eq!(output.len(), 32);
+                    by_hash.borrow_mut().push(("exit", 0));
+                }
+            });
+
+        let mut evm = crate::new();
+        evm.database(db);
+        evm.env.tx.transact_to = TransactTo::Call(proxy);
+        assert!(evm.inspect(hooks).unwrap().result.is_success());
+        assert_eq!(
+            *events.borrow(),
+            vec![("proxy", 0), ("enter", 1), ("exit", 0)]
+        );
+    }
+}
@@ -0,0 +1,238 @@
+//! Inspector capping resources used by execution.
+//!
+//! Used to run untrusted bytecode where memory is scarce, for example in wasm sandboxes or
+//! provers. Every cap halts the frame with its own [InstructionResult] so it is known
+//! which one was hit.
+use crate::interpreter::{
+    opcode, CallInputs, CreateInputs, Gas, InstructionResult, Interpreter, CALL_STACK_LIMIT,
+    STACK_LIMIT,
+};
+use crate::primitives::{Bytes, B160, U256};
+use crate::{Database, EVMData, Inspector};
+use alloc::vec::Vec;
+
+/// Caps on resources, default values are limits of Ethereum and unlimited memory.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ResourceLimits {
+    /// Memory of a single frame in bytes, [InstructionResult::MemoryLimitOOG] when exceeded.
+    pub memory: usize,
+    /// Stack items of a single frame, [InstructionResult::StackLimit] when exceeded.
+    pub stack: usize,
+    /// Call depth, calls and creates deeper than this fail with
+    /// [InstructionResult::CallDepthLimit].
+    pub call_depth: u64,
+    /// Bytes returned or reverted with, [InstructionResult::ReturnDataLimit] when exceeded.
+    pub return_data: usize,
+    /// Memory and stack in bytes of all frames that are executing together,
+    /// [InstructionResult::AllocationLimit] when exceeded.
+    pub allocated: usize,
+}
+
+impl Default for ResourceLimits {
+    fn default() -> Self {
+        Self {
+            memory: usize::MAX,
+            stack: STACK_LIMIT as usize,
+            call_depth: CALL_STACK_LIMIT,
+            return_data: usize::MAX,
+            allocated: usize::MAX,
+        }
+    }
+}
+
+/// Inspector enforcing [ResourceLimits].
+#[derive(Clone, Debug, Default)]
+pub struct ResourceLimiter {
+    limits: ResourceLimits,
+    /// Bytes allocated by frames that are executing.
+    frames: Vec<usize>,
+    allocated: usize,
+}
+
+impl ResourceLimiter {
+    pub fn new(limits: ResourceLimits) -> Self {
+        Self {
+            limits,
+            frames: Vec::new(),
+            allocated: 0,
+        }
+    }
+
+    pub fn limits(&self) -> &ResourceLimits {
+        &self.limits
+    }
+
+    fn pop(&mut self) {
+        if let Some(frame) = self.frames.pop() {
+            self.allocated -= frame;
+        }
+    }
+}
+
+impl<DB: Database> Inspector<DB> for ResourceLimiter {
+    fn step(&mut self, interp: &mut Interpreter, _data: &mut EVMData<'_, DB>) -> InstructionResult {
+        // returned data is checked before the frame returns, so its changes are reverted.
+        let op = interp.current_opcode();
+        if op == opcode::RETURN || op == opcode::REVERT {
+            let len = interp.stack.peek(1).unwrap_or_default();
+            if len > U256::from(self.limits.return_data) {
+                return InstructionResult::ReturnDataLimit;
+            }
+        }
+        InstructionResult::Continue
+    }
+
+    fn step_end(
+        &mut self,
+        interp: &mut Interpreter,
+        _data: &mut EVMData<'_, DB>,
+        _eval: InstructionResult,
+    ) -> InstructionResult {
+        if interp.memory.len() > self.limits.memory {
+            return InstructionResult::MemoryLimitOOG;
+        }
+        if interp.stack.len() > self.limits.stack {
+            return InstructionResult::StackLimit;
+        }
+        let Some(frame) = self.frames.last_mut() else {
+            return InstructionResult::Continue;
+        };
+        let size = interp.memory.len() + interp.stack.len() * 32;
+        self.allocated = self.allocated - *frame + size;
+        *frame = size;
+        if self.allocated > self.limits.allocated {
+            return InstructionResult::AllocationLimit;
+        }
+        InstructionResult::Continue
+    }
+
+    fn call(
+        &mut self,
+        data: &mut EVMData<'_, DB>,
+        _inputs: &mut CallInputs,
+    ) -> (InstructionResult, Gas, Bytes) {
+        if data.journaled_state.depth() > self.limits.call_depth {
+            return (InstructionResult::CallDepthLimit, Gas::new(0), Bytes::new());
+        }
+        self.frames.push(0);
+        (InstructionResult::Continue, Gas::new(0), Bytes::new())
+    }
+
+    fn call_end(
+        &mut self,
+        _data: &mut EVMData<'_, DB>,
+        _inputs: &CallInputs,
+        remaining_gas: Gas,
+        ret: InstructionResult,
+        out: Bytes,
+    ) -> (InstructionResult, Gas, Bytes) {
+        self.pop();
+        (ret, remaining_gas, out)
+    }
+
+    fn create(
+        &mut self,
+        data: &mut EVMData<'_, DB>,
+        _inputs: &mut CreateInputs,
+    ) -> (InstructionResult, Option<B160>, Gas, Bytes) {
+        if data.journaled_state.depth() > self.limits.call_depth {
+            return (
+                InstructionResult::CallDepthLimit,
+                None,
+                Gas::new(0),
+                Bytes::new(),
+            );
+        }
+        self.frames.push(0);
+        (InstructionResult::Continue, None, Gas::new(0), Bytes::new())
+    }
+
+    fn create_end(
+        &mut self,
+        _data: &mut EVMData<'_, DB>,
+        _inputs: &CreateInputs,
+        ret: InstructionResult,
+        address: Option<B160>,
+        remaining_gas: Gas,
+        out: Bytes,
+    ) -> (InstructionResult, Option<B160>, Gas, Bytes) {
+        self.pop();
+        (ret, address, remaining_gas, out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primitives::{
+        hex_literal::hex, AccountInfo, Bytecode, ExecutionResult, Halt, OutOfGasError, TransactTo,
+    };
+    use crate::InMemoryDB;
+
+    fn run(code: &[u8], limits: ResourceLimits) -> ExecutionResult {
+        let contract = B160([0x10; 20]);
+        let mut db = InMemoryDB::default();
+        db.insert_account_info(
+            contract,
+            AccountInfo::new(U256::ZERO, 0, Bytecode::new_raw(code.to_vec().into())),
+        );
+        let mut evm = crate::new();
+        evm.database(db);
+        evm.env.tx.caller = B160([0x20; 20]);
+        evm.env.tx.transact_to = TransactTo::Call(contract);
+        evm.env.tx.gas_limit = 1_000_000;
+        evm.inspect(ResourceLimiter::new(limits)).unwrap().result
+    }
+
+    fn halt(code: &[u8], limits: ResourceLimits) -> Option<Halt> {
+        match run(code, limits) {
+            ExecutionResult::Halt { reason, .. } => Some(reason),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn halts_on_limits() {
+        // MSTORE(0x100, 1) RETURN(0, 0x120)
+        let code = hex!("6001610100526101206000f3");
+        assert_eq!(halt(&code, ResourceLimits::default()), None);
+
+        let limits = ResourceLimits {
+            memory: 0x100,
+            ..Default::default()
+        };
+        assert_eq!(
+            halt(&code, limits),
+            Some(Halt::OutOfGas(OutOfGasError::MemoryLimit))
+        );
+        let limits = ResourceLimits {
+            return_data: 0x100,
+            ..Default::default()
+        };
+        assert_eq!(halt(&code, limits), Some(Halt::ReturnDataLimit));
+        let limits = ResourceLimits {
+            stack: 1,
+            ..Default::default()
+        };
+        assert_eq!(halt(&code, limits), Some(Halt::StackLimit));
+        let limits = ResourceLimits {
+            allocated: 0x100,
+            ..Default::default()
+        };
+        assert_eq!(halt(&code, limits), Some(Halt::AllocationLimit));
+    }
+
+    #[test]
+    fn fails_deep_calls() {
+        // RETURN(CALL(GAS, ADDRESS, 0, 0, 0, 0, 0)) returns 1 only if calls succeed.
+        let code = hex!("6000600060006000600030 5af1 600052 60206000f3");
+        let limits = ResourceLimits {
+            call_depth: 0,
+            ..Default::default()
+        };
+        let result = run(&code, limits);
+        assert!(result.is_success());
+        assert_eq!(result.output().unwrap()[..], [0; 32]);
+    }
+}
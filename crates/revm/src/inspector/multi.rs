@@ -0,0 +1,305 @@
+//! Inspector running several inspectors, in the order they were added.
+//!
+//! Results that change execution are merged: the first inspector returning something other
+//! than [InstructionResult::Continue] decides it, the others are still called. The result of
+//! `call_end` and `create_end` is passed through all inspectors, so each one sees and can
+//! change what the previous one returned.
+use crate::interpreter::{CallInputs, CreateInputs, Gas, InstructionResult, Interpreter};
+use crate::primitives::{Bytes, B160, B256, U256};
+use crate::{Database, EVMData, Inspector, StorageWrite};
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+
+pub struct MultiInspector<'a, DB: Database> {
+    inspectors: Vec<Box<dyn Inspector<DB> + 'a>>,
+}
+
+impl<'a, DB: Database> Default for MultiInspector<'a, DB> {
+    fn default() -> Self {
+        Self {
+            inspectors: Vec::new(),
+        }
+    }
+}
+
+impl<'a, DB: Database> MultiInspector<'a, DB> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add `inspector` after the others, pass `&mut inspector` to keep using it afterwards.
+    pub fn with(mut self, inspector: impl Inspector<DB> + 'a) -> Self {
+        self.push(inspector);
+        self
+    }
+
+    pub fn push(&mut self, inspector: impl Inspector<DB> + 'a) {
+        self.inspectors.push(Box::new(inspector));
+    }
+
+    pub fn len(&self) -> usize {
+        self.inspectors.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.inspectors.is_empty()
+    }
+
+    /// Call `f` for every inspector, result is the first one that is not `Continue`.
+    fn merge(
+        &mut self,
+        mut f: impl FnMut(&mut dyn Inspector<DB>) -> InstructionResult,
+    ) -> InstructionResult {
+        let mut result = InstructionResult::Continue;
+        for inspector in self.inspectors.iter_mut() {
+            let ret = f(inspector.as_mut());
+            if result == InstructionResult::Continue {
+                result = ret;
+            }
+        }
+        result
+    }
+}
+
+impl<'a, DB: Database> Inspector<DB> for MultiInspector<'a, DB> {
+    fn initialize_interp(
+        &mut self,
+        interp: &mut Interpreter,
+        data: &mut EVMData<'_, DB>,
+    ) -> InstructionResult {
+        self.merge(|inspector| inspector.initialize_interp(interp, data))
+    }
+
+    fn step(&mut self, interp: &mut Interpreter, data: &mut EVMData<'_, DB>) -> InstructionResult {
+        self.merge(|inspector| inspector.step(interp, data))
+    }
+
+    fn log(&mut self, data: &mut EVMData<'_, DB>, address: &B160, topics: &[B256], out: &Bytes) {
+        for inspector in self.inspectors.iter_mut() {
+            inspector.log(data, address, topics, out);
+        }
+    }
+
+    fn sload(
+        &mut self,
+        data: &mut EVMData<'_, DB>,
+        address: &B160,
+        slot: U256,
+        value: U256,
+        is_cold: bool,
+    ) {
+        for inspector in self.inspectors.iter_mut() {
+            inspector.sload(data, address, slot, value, is_cold);
+        }
+    }
+
+    fn sstore(
+        &mut self,
+        data: &mut EVMData<'_, DB>,
+        address: &B160,
+        slot: U256,
+        write: &StorageWrite,
+    ) {
+        for inspector in self.inspectors.iter_mut() {
+            inspector.sstore(data, address, slot, write);
+        }
+    }
+
+    fn tload(&mut self, data: &mut EVMData<'_, DB>, address: &B160, slot: U256, value: U256) {
+        for inspector in self.inspectors.iter_mut() {
+            inspector.tload(data, address, slot, value);
+        }
+    }
+
+    fn tstore(&mut self, data: &mut EVMData<'_, DB>, address: &B160, slot: U256, value: U256) {
+        for inspector in self.inspectors.iter_mut() {
+            inspector.tstore(data, address, slot, value);
+        }
+    }
+
+    fn step_end(
+        &mut self,
+        interp: &mut Interpreter,
+        data: &mut EVMData<'_, DB>,
+        eval: InstructionResult,
+    ) -> InstructionResult {
+        self.merge(|inspector| inspector.step_end(interp, data, eval))
+    }
+
+    /// Inspectors after the one overriding the call don't see it, the ones before it get
+    /// `call_end` with the override, as the EVM doesn't call it for overridden calls.
+    fn call(
+        &mut self,
+        data: &mut EVMData<'_, DB>,
+        inputs: &mut CallInputs,
+    ) -> (InstructionResult, Gas, Bytes) {
+        for index in 0..self.inspectors.len() {
+            let (ret, gas, out) = self.inspectors[index].call(data, inputs);
+            if ret != InstructionResult::Continue {
+                return self.inspectors[..index]
+                    .iter_mut()
+                    .fold((ret, gas, out), |(ret, gas, out), inspector| {
+                        inspector.call_end(data, inputs, gas, ret, out)
+                    });
+            }
+        }
+        (InstructionResult::Continue, Gas::new(0), Bytes::new())
+    }
+
+    fn call_end(
+        &mut self,
+        data: &mut EVMData<'_, DB>,
+        inputs: &CallInputs,
+        remaining_gas: Gas,
+        ret: InstructionResult,
+        out: Bytes,
+    ) -> (InstructionResult, Gas, Bytes) {
+        self.inspectors
+            .iter_mut()
+            .fold((ret, remaining_gas, out), |(ret, gas, out), inspector| {
+                inspector.call_end(data, inputs, gas, ret, out)
+            })
+    }
+
+    /// Overridden creates are handled as calls are, see [MultiInspector::call].
+    fn create(
+        &mut self,
+        data: &mut EVMData<'_, DB>,
+        inputs: &mut CreateInputs,
+    ) -> (InstructionResult, Option<B160>, Gas, Bytes) {
+        for index in 0..self.inspectors.len() {
+            let (ret, address, gas, out) = self.inspectors[index].create(data, inputs);
+            if ret != InstructionResult::Continue {
+                return self.inspectors[..index].iter_mut().fold(
+                    (ret, address, gas, out),
+                    |(ret, address, gas, out), inspector| {
+                        inspector.create_end(data, inputs, ret, address, gas, out)
+                    },
+                );
+            }
+        }
+        (
+            InstructionResult::Continue,
+            None,
+            Gas::new(0),
+            Bytes::default(),
+        )
+    }
+
+    fn create_end(
+        &mut self,
+        data: &mut EVMData<'_, DB>,
+        inputs: &CreateInputs,
+        ret: InstructionResult,
+        address: Option<B160>,
+        remaining_gas: Gas,
+        out: Bytes,
+    ) -> (InstructionResult, Option<B160>, Gas, Bytes) {
+        self.inspectors.iter_mut().fold(
+            (ret, address, remaining_gas, out),
+            |(ret, address, gas, out), inspector| {
+                inspector.create_end(data, inputs, ret, address, gas, out)
+            },
+        )
+    }
+
+    fn selfdestruct(&mut self, contract: B160, target: B160) {
+        for inspector in self.inspectors.iter_mut() {
+            inspector.selfdestruct(contract, target);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primitives::{hex_literal::hex, AccountInfo, Bytecode, TransactTo, U256};
+    use crate::InMemoryDB;
+
+    #[derive(Default)]
+    struct Counter {
+        steps: usize,
+        calls: usize,
+        call_ends: usize,
+    }
+
+    impl<DB: Database> Inspector<DB> for Counter {
+        fn step(&mut self, _: &mut Interpreter, _: &mut EVMData<'_, DB>) -> InstructionResult {
+            self.steps += 1;
+            InstructionResult::Continue
+        }
+
+        fn call(
+            &mut self,
+            _: &mut EVMData<'_, DB>,
+            _: &mut CallInputs,
+        ) -> (InstructionResult, Gas, Bytes) {
+            self.calls += 1;
+            (InstructionResult::Continue, Gas::new(0), Bytes::new())
+        }
+
+        fn call_end(
+            &mut self,
+            _: &mut EVMData<'_, DB>,
+            _: &CallInputs,
+            remaining_gas: Gas,
+            ret: InstructionResult,
+            out: Bytes,
+        ) -> (InstructionResult, Gas, Bytes) {
+            self.call_ends += 1;
+            (ret, remaining_gas, out)
+        }
+    }
+
+    /// Reverts calls to `0x30..30` without executing them.
+    struct Blocker;
+
+    impl<DB: Database> Inspector<DB> for Blocker {
+        fn call(
+            &mut self,
+            _: &mut EVMData<'_, DB>,
+            inputs: &mut CallInputs,
+        ) -> (InstructionResult, Gas, Bytes) {
+            if inputs.contract == B160([0x30; 20]) {
+                return (
+                    InstructionResult::Revert,
+                    Gas::new(inputs.gas_limit),
+                    Bytes::new(),
+                );
+            }
+            (InstructionResult::Continue, Gas::new(0), Bytes::new())
+        }
+    }
+
+    #[test]
+    fn fans_out_callbacks() {
+        let contract = B160([0x20; 20]);
+        // CALL(GAS, 0x30..30, 0, 0, 0, 0, 0) MSTORE(0, success) RETURN(0, 32)
+        let mut code = hex!("600060006000600060007f").to_vec();
+        code.extend_from_slice(&[0; 12]);
+        code.extend_from_slice(&[0x30; 20]);
+        code.extend_from_slice(&hex!("5af160005260206000f3"));
+        let mut db = InMemoryDB::default();
+        db.insert_account_info(
+            contract,
+            AccountInfo::new(U256::ZERO, 0, Bytecode::new_raw(code.into())),
+        );
+        let mut evm = crate::new();
+        evm.database(db);
+        evm.env.tx.transact_to = TransactTo::Call(contract);
+
+        let (mut first, mut last) = (Counter::default(), Counter::default());
+        let inspector = MultiInspector::new()
+            .with(&mut first)
+            .with(Blocker)
+            .with(&mut last);
+        let output = evm.inspect(inspector).unwrap().result.into_output();
+        // inner call failed.
+        assert_eq!(output, Some(Bytes::from(vec![0; 32])));
+
+        assert_eq!(first.steps, last.steps);
+        assert_eq!((first.calls, first.call_ends), (2, 2));
+        // overridden call is not seen after the blocker.
+        assert_eq!((last.calls, last.call_ends), (1, 1));
+    }
+}
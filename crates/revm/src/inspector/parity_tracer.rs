@@ -0,0 +1,811 @@
+//! Inspector producing traces of Parity/OpenEthereum `trace_` RPC methods.
+//!
+//! [ParityTracer] records flat call traces addressed by `traceAddress` and, if enabled, the
+//! `vmTrace` of executed instructions. [state_diff] builds the `stateDiff` section from the
+//! changes of the transaction before they are committed. With `serde` feature
+//! [TraceResults] serializes to the result of `trace_replayTransaction`, entries of its
+//! `trace` are the ones of `trace_transaction` without block and transaction fields.
+use crate::db::DatabaseRef;
+use crate::interpreter::{
+    opcode, return_ok, stack_io, CallInputs, CallScheme, CreateInputs, Gas, InstructionResult,
+    Interpreter,
+};
+use crate::primitives::{AccountInfo, Bytes, State, B160, B256, KECCAK_EMPTY, U256};
+use crate::{Database, EVMData, Inspector};
+use alloc::collections::BTreeMap;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+/// Call, create or selfdestruct of the transaction.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+pub struct TransactionTrace {
+    pub action: Action,
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub error: Option<String>,
+    /// None if frame failed, and for selfdestructs.
+    pub result: Option<TraceOutput>,
+    /// Number of traces nested directly in this one.
+    pub subtraces: usize,
+    /// Indices of the trace in subtraces of its parents, empty for the transaction call.
+    pub trace_address: Vec<usize>,
+    #[cfg_attr(feature = "serde", serde(rename = "type"))]
+    pub kind: TraceKind,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum TraceKind {
+    Call,
+    Create,
+    Suicide,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(untagged))]
+pub enum Action {
+    Call(CallAction),
+    Create(CreateAction),
+    Selfdestruct(SelfdestructAction),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum CallType {
+    Call,
+    CallCode,
+    DelegateCall,
+    StaticCall,
+}
+
+impl From<CallScheme> for CallType {
+    fn from(scheme: CallScheme) -> Self {
+        match scheme {
+            CallScheme::Call => Self::Call,
+            CallScheme::CallCode => Self::CallCode,
+            CallScheme::DelegateCall => Self::DelegateCall,
+            CallScheme::StaticCall => Self::StaticCall,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+pub struct CallAction {
+    /// Address whose code made the call, it is also the caller of delegate calls.
+    pub from: B160,
+    /// Address whose code is executed.
+    pub to: B160,
+    pub value: U256,
+    #[cfg_attr(feature = "serde", serde(with = "super::call_tracer::hex_u64"))]
+    pub gas: u64,
+    #[cfg_attr(
+        feature = "serde",
+        serde(with = "crate::primitives::utilities::serde_hex_bytes")
+    )]
+    pub input: Bytes,
+    pub call_type: CallType,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+pub struct CreateAction {
+    pub from: B160,
+    pub value: U256,
+    #[cfg_attr(feature = "serde", serde(with = "super::call_tracer::hex_u64"))]
+    pub gas: u64,
+    #[cfg_attr(
+        feature = "serde",
+        serde(with = "crate::primitives::utilities::serde_hex_bytes")
+    )]
+    pub init: Bytes,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+pub struct SelfdestructAction {
+    pub address: B160,
+    pub refund_address: B160,
+    pub balance: U256,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(untagged))]
+pub enum TraceOutput {
+    Call(CallOutput),
+    Create(CreateOutput),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+pub struct CallOutput {
+    #[cfg_attr(feature = "serde", serde(with = "super::call_tracer::hex_u64"))]
+    pub gas_used: u64,
+    #[cfg_attr(
+        feature = "serde",
+        serde(with = "crate::primitives::utilities::serde_hex_bytes")
+    )]
+    pub output: Bytes,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+pub struct CreateOutput {
+    #[cfg_attr(feature = "serde", serde(with = "super::call_tracer::hex_u64"))]
+    pub gas_used: u64,
+    #[cfg_attr(
+        feature = "serde",
+        serde(with = "crate::primitives::utilities::serde_hex_bytes")
+    )]
+    pub code: Bytes,
+    pub address: B160,
+}
+
+/// Instructions executed by a frame.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct VmTrace {
+    #[cfg_attr(
+        feature = "serde",
+        serde(with = "crate::primitives::utilities::serde_hex_bytes")
+    )]
+    pub code: Bytes,
+    pub ops: Vec<VmInstruction>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct VmInstruction {
+    pub pc: usize,
+    /// Gas charged by the instruction, gas used by the frame it started is not included.
+    pub cost: u64,
+    /// None if the instruction halted the frame exceptionally.
+    pub ex: Option<VmExecutedOperation>,
+    /// Trace of the frame started by the instruction.
+    pub sub: Option<VmTrace>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct VmExecutedOperation {
+    /// Gas remaining after the instruction.
+    pub used: u64,
+    /// Stack items written by the instruction, bottom first.
+    pub push: Vec<U256>,
+    pub mem: Option<MemoryDelta>,
+    pub store: Option<StorageDelta>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MemoryDelta {
+    pub off: usize,
+    #[cfg_attr(
+        feature = "serde",
+        serde(with = "crate::primitives::utilities::serde_hex_bytes")
+    )]
+    pub data: Bytes,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StorageDelta {
+    pub key: U256,
+    pub val: U256,
+}
+
+/// Change of a value, serialized as `"="`, `{"+": new}`, `{"-": old}` or
+/// `{"*": {"from": old, "to": new}}`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Delta<T> {
+    #[cfg_attr(feature = "serde", serde(rename = "="))]
+    Unchanged,
+    #[cfg_attr(feature = "serde", serde(rename = "+"))]
+    Added(T),
+    #[cfg_attr(feature = "serde", serde(rename = "-"))]
+    Removed(T),
+    #[cfg_attr(feature = "serde", serde(rename = "*"))]
+    Changed(ChangedType<T>),
+}
+
+impl<T: PartialEq> Delta<T> {
+    fn new(from: T, to: T) -> Self {
+        if from == to {
+            Self::Unchanged
+        } else {
+            Self::Changed(ChangedType { from, to })
+        }
+    }
+
+    pub fn is_unchanged(&self) -> bool {
+        matches!(self, Self::Unchanged)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ChangedType<T> {
+    pub from: T,
+    pub to: T,
+}
+
+/// Account code, serialized as hex.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
+pub struct Code(
+    #[cfg_attr(
+        feature = "serde",
+        serde(with = "crate::primitives::utilities::serde_hex_bytes")
+    )]
+    pub Bytes,
+);
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AccountDiff {
+    pub balance: Delta<U256>,
+    pub nonce: Delta<U256>,
+    pub code: Delta<Code>,
+    pub storage: BTreeMap<B256, Delta<B256>>,
+}
+
+/// Changed accounts of the transaction.
+pub type StateDiff = BTreeMap<B160, AccountDiff>;
+
+/// Result of `trace_replayTransaction`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+pub struct TraceResults {
+    #[cfg_attr(
+        feature = "serde",
+        serde(with = "crate::primitives::utilities::serde_hex_bytes")
+    )]
+    pub output: Bytes,
+    pub state_diff: Option<StateDiff>,
+    pub trace: Vec<TransactionTrace>,
+    pub vm_trace: Option<VmTrace>,
+}
+
+/// Instruction being executed and what it writes.
+#[derive(Clone, Debug)]
+struct PendingStep {
+    pc: usize,
+    opcode: u8,
+    gas_remaining: u64,
+    /// Memory range the instruction writes.
+    mem: Option<(usize, usize)>,
+    store: Option<StorageDelta>,
+    children_gas: u64,
+}
+
+#[derive(Clone, Debug, Default)]
+struct VmFrame {
+    trace: VmTrace,
+    step: Option<PendingStep>,
+    sub: Option<VmTrace>,
+}
+
+/// Inspector building Parity traces of the transaction.
+///
+/// Gas of a trace is gas limit of the frame and gas used is gas it spent, intrinsic gas of
+/// the transaction and refunds are not included.
+#[derive(Clone, Debug, Default)]
+pub struct ParityTracer {
+    vm_trace: bool,
+    traces: Vec<TransactionTrace>,
+    /// Indices of traces of frames that are currently executing.
+    stack: Vec<usize>,
+    vm_stack: Vec<VmFrame>,
+    vm_root: Option<VmTrace>,
+    /// Balance of the contract executing `SELFDESTRUCT`.
+    selfdestruct_balance: U256,
+}
+
+impl ParityTracer {
+    /// Tracer of call traces.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Tracer of call traces and `vmTrace`.
+    pub fn with_vm_trace() -> Self {
+        Self {
+            vm_trace: true,
+            ..Default::default()
+        }
+    }
+
+    /// Traces in order their frames started.
+    pub fn traces(&self) -> &[TransactionTrace] {
+        &self.traces
+    }
+
+    /// Trace of instructions of the transaction, set once it is done if enabled.
+    pub fn vm_trace(&self) -> Option<&VmTrace> {
+        self.vm_root.as_ref()
+    }
+
+    /// Result of `trace_replayTransaction` with `output` of the transaction.
+    pub fn into_results(self, output: Bytes, state_diff: Option<StateDiff>) -> TraceResults {
+        TraceResults {
+            output,
+            state_diff,
+            trace: self.traces,
+            vm_trace: self.vm_root,
+        }
+    }
+
+    /// Address of the next trace nested in the executing frame.
+    fn next_address(&mut self) -> Vec<usize> {
+        let Some(&parent) = self.stack.last() else {
+            // transaction starts.
+            self.traces.clear();
+            self.vm_root = None;
+            return Vec::new();
+        };
+        let parent = &mut self.traces[parent];
+        let mut address = parent.trace_address.clone();
+        address.push(parent.subtraces);
+        parent.subtraces += 1;
+        address
+    }
+
+    fn push(&mut self, action: Action, kind: TraceKind) {
+        let trace_address = self.next_address();
+        self.stack.push(self.traces.len());
+        self.traces.push(TransactionTrace {
+            action,
+            error: None,
+            result: None,
+            subtraces: 0,
+            trace_address,
+            kind,
+        });
+        if self.vm_trace {
+            self.vm_stack.push(VmFrame::default());
+        }
+    }
+
+    fn pop(
+        &mut self,
+        ret: InstructionResult,
+        gas: &Gas,
+        gas_limit: u64,
+        output: impl FnOnce(u64) -> TraceOutput,
+    ) {
+        let Some(index) = self.stack.pop() else {
+            return;
+        };
+        let gas_used = match ret {
+            return_ok!() | InstructionResult::Revert => gas.spend(),
+            // calls that fail before executing return all gas.
+            InstructionResult::CallTooDeep | InstructionResult::OutOfFund => 0,
+            _ => gas_limit,
+        };
+        let trace = &mut self.traces[index];
+        if matches!(ret, return_ok!()) {
+            trace.result = Some(output(gas_used));
+        } else {
+            trace.error = Some(error_message(ret).to_string());
+        }
+        if let Some(frame) = self.vm_stack.pop() {
+            match self.vm_stack.last_mut() {
+                Some(parent) => {
+                    parent.sub = Some(frame.trace);
+                    if let Some(step) = &mut parent.step {
+                        step.children_gas += gas_used;
+                    }
+                }
+                None => self.vm_root = Some(frame.trace),
+            }
+        }
+    }
+}
+
+impl<DB: Database> Inspector<DB> for ParityTracer {
+    fn initialize_interp(
+        &mut self,
+        interp: &mut Interpreter,
+        _data: &mut EVMData<'_, DB>,
+    ) -> InstructionResult {
+        if let Some(frame) = self.vm_stack.last_mut() {
+            frame.trace.code = interp.contract.bytecode.original_bytes();
+        }
+        InstructionResult::Continue
+    }
+
+    fn step(&mut self, interp: &mut Interpreter, data: &mut EVMData<'_, DB>) -> InstructionResult {
+        let op = interp.current_opcode();
+        if op == opcode::SELFDESTRUCT {
+            self.selfdestruct_balance = data
+                .journaled_state
+                .state
+                .get(&interp.contract.address)
+                .map_or(U256::ZERO, |account| account.info.balance);
+        }
+        let Some(frame) = self.vm_stack.last_mut() else {
+            return InstructionResult::Continue;
+        };
+        let stack = &interp.stack;
+        let offset = |n: usize| stack.peek(n).ok().and_then(|v| usize::try_from(v).ok());
+        let range = |off: usize, len: usize| offset(off).zip(offset(len));
+        let mem = match op {
+            opcode::MSTORE => offset(0).map(|off| (off, 32)),
+            opcode::MSTORE8 => offset(0).map(|off| (off, 1)),
+            opcode::CALLDATACOPY | opcode::CODECOPY | opcode::RETURNDATACOPY | opcode::MCOPY => {
+                range(0, 2)
+            }
+            opcode::EXTCODECOPY => range(1, 3),
+            opcode::CALL | opcode::CALLCODE => range(5, 6),
+            opcode::DELEGATECALL | opcode::STATICCALL => range(4, 5),
+            _ => None,
+        };
+        let store = match (op, stack.peek(0), stack.peek(1)) {
+            (opcode::SSTORE, Ok(key), Ok(val)) => Some(StorageDelta { key, val }),
+            _ => None,
+        };
+        frame.step = Some(PendingStep {
+            pc: interp.program_counter(),
+            opcode: op,
+            gas_remaining: interp.gas.remaining(),
+            mem: mem.filter(|(_, len)| *len > 0),
+            store,
+            children_gas: 0,
+        });
+        frame.sub = None;
+        InstructionResult::Continue
+    }
+
+    fn step_end(
+        &mut self,
+        interp: &mut Interpreter,
+        _data: &mut EVMData<'_, DB>,
+        _eval: InstructionResult,
+    ) -> InstructionResult {
+        let Some(frame) = self.vm_stack.last_mut() else {
+            return InstructionResult::Continue;
+        };
+        let Some(step) = frame.step.take() else {
+            return InstructionResult::Continue;
+        };
+        let executed = matches!(
+            interp.instruction_result,
+            InstructionResult::Continue | return_ok!() | InstructionResult::Revert
+        );
+        let ex = executed.then(|| {
+            let stack = interp.stack.data();
+            let pushed = (stack_io(step.opcode).1 as usize).min(stack.len());
+            let mem = step.mem.and_then(|(off, len)| {
+                let end = off.checked_add(len)?;
+                (end <= interp.memory.len()).then(|| MemoryDelta {
+                    off,
+                    data: Bytes::copy_from_slice(interp.memory.get_slice(off, len)),
+                })
+            });
+            VmExecutedOperation {
+                used: interp.gas.remaining(),
+                push: stack[stack.len() - pushed..].to_vec(),
+                mem,
+                store: step.store,
+            }
+        });
+        let cost = step
+            .gas_remaining
+            .saturating_sub(interp.gas.remaining())
+            .saturating_sub(step.children_gas);
+        frame.trace.ops.push(VmInstruction {
+            pc: step.pc,
+            cost,
+            ex,
+            sub: frame.sub.take(),
+        });
+        InstructionResult::Continue
+    }
+
+    fn call(
+        &mut self,
+        _data: &mut EVMData<'_, DB>,
+        inputs: &mut CallInputs,
+    ) -> (InstructionResult, Gas, Bytes) {
+        let context = &inputs.context;
+        let from = match context.scheme {
+            CallScheme::Call | CallScheme::StaticCall => context.caller,
+            // code runs in context of the address making the call.
+            CallScheme::CallCode | CallScheme::DelegateCall => context.address,
+        };
+        let action = Action::Call(CallAction {
+            from,
+            to: inputs.contract,
+            value: inputs.transfer.value,
+            gas: inputs.gas_limit,
+            input: inputs.input.clone(),
+            call_type: context.scheme.into(),
+        });
+        self.push(action, TraceKind::Call);
+        (InstructionResult::Continue, Gas::new(0), Bytes::new())
+    }
+
+    fn call_end(
+        &mut self,
+        _data: &mut EVMData<'_, DB>,
+        inputs: &CallInputs,
+        remaining_gas: Gas,
+        ret: InstructionResult,
+        out: Bytes,
+    ) -> (InstructionResult, Gas, Bytes) {
+        let output = out.clone();
+        self.pop(ret, &remaining_gas, inputs.gas_limit, |gas_used| {
+            TraceOutput::Call(CallOutput { gas_used, output })
+        });
+        (ret, remaining_gas, out)
+    }
+
+    fn create(
+        &mut self,
+        _data: &mut EVMData<'_, DB>,
+        inputs: &mut CreateInputs,
+    ) -> (InstructionResult, Option<B160>, Gas, Bytes) {
+        let action = Action::Create(CreateAction {
+            from: inputs.caller,
+            value: inputs.value,
+            gas: inputs.gas_limit,
+            init: inputs.init_code.clone(),
+        });
+        self.push(action, TraceKind::Create);
+        (InstructionResult::Continue, None, Gas::new(0), Bytes::new())
+    }
+
+    fn create_end(
+        &mut self,
+        _data: &mut EVMData<'_, DB>,
+        inputs: &CreateInputs,
+        ret: InstructionResult,
+        address: Option<B160>,
+        remaining_gas: Gas,
+        out: Bytes,
+    ) -> (InstructionResult, Option<B160>, Gas, Bytes) {
+        let code = out.clone();
+        self.pop(ret, &remaining_gas, inputs.gas_limit, |gas_used| {
+            TraceOutput::Create(CreateOutput {
+                gas_used,
+                code,
+                address: address.unwrap_or_default(),
+            })
+        });
+        (ret, address, remaining_gas, out)
+    }
+
+    fn selfdestruct(&mut self, contract: B160, target: B160) {
+        if self.stack.is_empty() {
+            return;
+        }
+        let trace_address = self.next_address();
+        self.traces.push(TransactionTrace {
+            action: Action::Selfdestruct(SelfdestructAction {
+                address: contract,
+                refund_address: target,
+                balance: self.selfdestruct_balance,
+            }),
+            error: None,
+            result: None,
+            subtraces: 0,
+            trace_address,
+            kind: TraceKind::Suicide,
+        });
+    }
+}
+
+/// Error of the frame as OpenEthereum reports it.
+fn error_message(ret: InstructionResult) -> &'static str {
+    use InstructionResult::*;
+    match ret {
+        Revert => "Reverted",
+        OutOfGas | MemoryOOG | MemoryLimitOOG | PrecompileOOG | InvalidOperandOOG => "Out of gas",
+        OpcodeNotFound | InvalidFEOpcode | NotActivated => "Bad instruction",
+        InvalidJump => "Bad jump destination",
+        StackUnderflow => "Stack underflow",
+        StackOverflow => "Out of stack",
+        CallNotAllowedInsideStatic | StateChangeDuringStaticCall => {
+            "Mutable Call In Static Context"
+        }
+        OutOfOffset => "Out of bounds",
+        PrecompileError => "Built-in failed",
+        CallTooDeep | OutOfFund => "Internal error",
+        _ => "Halted",
+    }
+}
+
+/// Changes of `state` against `db`, the state the transaction executed on.
+///
+/// `state` has to be the state of the transaction before it is committed to `db`. Storage
+/// slots that were only read are not included, accounts that did not change are skipped.
+pub fn state_diff<DB: DatabaseRef>(db: &DB, state: &State) -> Result<StateDiff, DB::Error> {
+    let mut diff = StateDiff::new();
+    for (address, account) in state {
+        if !account.is_touched() {
+            continue;
+        }
+        let slot = |value: &U256| B256(value.to_be_bytes());
+        let account_diff = match db.basic(*address)? {
+            None if account.is_selfdestructed() || account.info.is_empty() => continue,
+            None => AccountDiff {
+                balance: Delta::Added(account.info.balance),
+                nonce: Delta::Added(U256::from(account.info.nonce)),
+                code: Delta::Added(Code(code(db, &account.info)?)),
+                storage: account
+                    .storage
+                    .iter()
+                    .filter(|(_, value)| value.present_value != U256::ZERO)
+                    .map(|(key, value)| (slot(key), Delta::Added(slot(&value.present_value))))
+                    .collect(),
+            },
+            Some(pre) if account.is_selfdestructed() => AccountDiff {
+                balance: Delta::Removed(pre.balance),
+                nonce: Delta::Removed(U256::from(pre.nonce)),
+                code: Delta::Removed(Code(code(db, &pre)?)),
+                storage: account
+                    .storage
+                    .iter()
+                    .filter(|(_, value)| value.original_value != U256::ZERO)
+                    .map(|(key, value)| (slot(key), Delta::Removed(slot(&value.original_value))))
+                    .collect(),
+            },
+            Some(pre) => {
+                let code = if pre.code_hash == account.info.code_hash {
+                    Delta::Unchanged
+                } else {
+                    Delta::new(Code(code(db, &pre)?), Code(code(db, &account.info)?))
+                };
+                let account_diff = AccountDiff {
+                    balance: Delta::new(pre.balance, account.info.balance),
+                    nonce: Delta::new(U256::from(pre.nonce), U256::from(account.info.nonce)),
+                    code,
+                    storage: account
+                        .storage
+                        .iter()
+                        .filter(|(_, value)| value.is_changed())
+                        .map(|(key, value)| {
+                            let delta =
+                                Delta::new(slot(&value.original_value), slot(&value.present_value));
+                            (slot(key), delta)
+                        })
+                        .collect(),
+                };
+                if account_diff.balance.is_unchanged()
+                    && account_diff.nonce.is_unchanged()
+                    && account_diff.code.is_unchanged()
+                    && account_diff.storage.is_empty()
+                {
+                    continue;
+                }
+                account_diff
+            }
+        };
+        diff.insert(*address, account_diff);
+    }
+    Ok(diff)
+}
+
+fn code<DB: DatabaseRef>(db: &DB, info: &AccountInfo) -> Result<Bytes, DB::Error> {
+    if info.code_hash == KECCAK_EMPTY {
+        return Ok(Bytes::new());
+    }
+    match &info.code {
+        Some(code) => Ok(code.original_bytes()),
+        None => Ok(db.code_by_hash(info.code_hash)?.original_bytes()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primitives::{hex_literal::hex, Bytecode, TransactTo};
+    use crate::InMemoryDB;
+
+    #[test]
+    fn traces_replayed_transaction() {
+        let caller = B160([0x10; 20]);
+        let outer = B160([0x20; 20]);
+        let inner = B160([0x30; 20]);
+
+        // CALL(gas, inner, 0, 0, 0, 0, 0) STOP
+        let mut code = hex!("600060006000600060007f").to_vec();
+        code.extend_from_slice(&[0; 12]);
+        code.extend_from_slice(&inner.0);
+        code.extend_from_slice(&hex!("5af100"));
+        let mut db = InMemoryDB::default();
+        db.insert_account_info(caller, AccountInfo::new(U256::from(1), 0, Bytecode::new()));
+        db.insert_account_info(
+            outer,
+            AccountInfo::new(U256::ZERO, 0, Bytecode::new_raw(code.into())),
+        );
+        // SSTORE(0, 1) STOP
+        db.insert_account_info(
+            inner,
+            AccountInfo::new(
+                U256::ZERO,
+                0,
+                Bytecode::new_raw(hex!("600160005500").to_vec().into()),
+            ),
+        );
+
+        let mut evm = crate::new();
+        evm.database(db);
+        evm.env.tx.caller = caller;
+        evm.env.tx.transact_to = TransactTo::Call(outer);
+        let mut tracer = ParityTracer::with_vm_trace();
+        let out = evm.inspect(&mut tracer).unwrap();
+
+        let traces = tracer.traces();
+        assert_eq!(traces.len(), 2);
+        assert_eq!((traces[0].subtraces, traces[1].subtraces), (1, 0));
+        assert_eq!(traces[1].trace_address, vec![0]);
+        let Action::Call(call) = &traces[1].action else {
+            panic!("call action");
+        };
+        assert_eq!((call.from, call.to), (outer, inner));
+        assert_eq!(call.call_type, CallType::Call);
+        assert!(matches!(traces[1].result, Some(TraceOutput::Call(_))));
+
+        let vm_trace = tracer.vm_trace().unwrap();
+        let call_op = vm_trace.ops.iter().find(|op| op.sub.is_some()).unwrap();
+        let sub = call_op.sub.as_ref().unwrap();
+        assert_eq!(sub.code[..], hex!("600160005500"));
+        let sstore = &sub.ops[2];
+        assert_eq!(sstore.pc, 4);
+        let ex = sstore.ex.as_ref().unwrap();
+        assert_eq!(
+            ex.store,
+            Some(StorageDelta {
+                key: U256::ZERO,
+                val: U256::from(1)
+            })
+        );
+        assert_eq!(sub.ops[0].ex.as_ref().unwrap().push, vec![U256::from(1)]);
+        if crate::USE_GAS {
+            assert_eq!(sstore.cost, 22_100);
+        }
+
+        let diff = state_diff(evm.db.as_ref().unwrap(), &out.state).unwrap();
+        assert_eq!(diff[&caller].nonce, Delta::new(U256::ZERO, U256::from(1)));
+        assert_eq!(diff[&caller].balance, Delta::Unchanged);
+        assert_eq!(
+            diff[&inner].storage[&B256::zero()],
+            Delta::new(B256::zero(), B256(U256::from(1).to_be_bytes()))
+        );
+        assert!(!diff.contains_key(&outer));
+
+        #[cfg(feature = "serde")]
+        {
+            let results = tracer.into_results(Bytes::new(), Some(diff));
+            let json = serde_json::to_value(&results).unwrap();
+            let trace = &json["trace"][1];
+            assert_eq!(trace["type"], "call");
+            assert_eq!(trace["traceAddress"], serde_json::json!([0]));
+            assert_eq!(trace["action"]["callType"], "call");
+            assert_eq!(trace["result"]["output"], "0x");
+            assert_eq!(json["stateDiff"][format!("{caller:#x}")]["balance"], "=");
+            assert_eq!(
+                json["stateDiff"][format!("{caller:#x}")]["nonce"]["*"]["to"],
+                "0x1"
+            );
+            assert_eq!(json["vmTrace"]["code"].as_str().unwrap().len(), 2 + 2 * 46);
+        }
+    }
+}
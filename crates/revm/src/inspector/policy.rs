@@ -0,0 +1,316 @@
+//! Inspector enforcing execution policy on call subtrees.
+//!
+//! Policy is set for entities, contracts whose call starts a subtree where opcode bans and
+//! storage allowlist of the entity apply. Calls from the subtree into other entity start
+//! a subtree of that entity. Code outside of all subtrees is not restricted.
+use super::call_graph::CallKind;
+use crate::interpreter::{opcode, CallInputs, CreateInputs, Gas, InstructionResult, Interpreter};
+use crate::primitives::{Bytes, HashMap, HashSet, B160, U256};
+use crate::{Database, EVMData, Inspector};
+use alloc::vec::Vec;
+
+/// Storage access allowed to the entity.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Access {
+    Read,
+    ReadWrite,
+}
+
+/// Restrictions applied to subtree of the entity.
+#[derive(Clone, Debug, Default)]
+pub struct EntityPolicy {
+    pub banned_opcodes: HashSet<u8>,
+    /// Allowed storage by contract, `None` slot applies to all slots of the contract.
+    /// Slot entry takes precedence over contract entry.
+    pub storage: HashMap<(B160, Option<U256>), Access>,
+}
+
+impl EntityPolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn ban(mut self, opcode: u8) -> Self {
+        self.banned_opcodes.insert(opcode);
+        self
+    }
+
+    /// Allow access to all storage of the contract.
+    pub fn allow_contract(mut self, address: B160, access: Access) -> Self {
+        self.storage.insert((address, None), access);
+        self
+    }
+
+    pub fn allow_slot(mut self, address: B160, slot: U256, access: Access) -> Self {
+        self.storage.insert((address, Some(slot)), access);
+        self
+    }
+
+    /// If storage access is allowed.
+    pub fn is_allowed(&self, address: B160, slot: U256, write: bool) -> bool {
+        let access = self
+            .storage
+            .get(&(address, Some(slot)))
+            .or_else(|| self.storage.get(&(address, None)));
+        match access {
+            Some(Access::ReadWrite) => true,
+            Some(Access::Read) => !write,
+            None => false,
+        }
+    }
+}
+
+/// What the policy forbids.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PolicyRule {
+    BannedOpcode(u8),
+    StorageAccess {
+        address: B160,
+        slot: U256,
+        write: bool,
+    },
+}
+
+/// Violation of the policy with the frame where it happened.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PolicyViolation {
+    /// Entity whose subtree was executing.
+    pub entity: B160,
+    /// Address whose storage and balance were used by the frame.
+    pub address: B160,
+    pub caller: B160,
+    pub kind: CallKind,
+    pub depth: u64,
+    pub pc: usize,
+    pub rule: PolicyRule,
+}
+
+#[derive(Clone, Debug)]
+struct Frame {
+    entity: Option<B160>,
+    caller: B160,
+    kind: CallKind,
+}
+
+/// Inspector checking [EntityPolicy] of every entity.
+///
+/// Violations are recorded and execution continues, unless [PolicyInspector::revert_on_violation]
+/// is set, in which case frame that broke the policy is reverted.
+#[derive(Clone, Debug, Default)]
+pub struct PolicyInspector {
+    entities: HashMap<B160, EntityPolicy>,
+    revert_on_violation: bool,
+    frames: Vec<Frame>,
+    violations: Vec<PolicyViolation>,
+}
+
+impl PolicyInspector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set policy of the entity, replacing previous one.
+    pub fn with_entity(mut self, entity: B160, policy: EntityPolicy) -> Self {
+        self.entities.insert(entity, policy);
+        self
+    }
+
+    pub fn revert_on_violation(mut self, revert: bool) -> Self {
+        self.revert_on_violation = revert;
+        self
+    }
+
+    pub fn violations(&self) -> &[PolicyViolation] {
+        &self.violations
+    }
+
+    pub fn into_violations(self) -> Vec<PolicyViolation> {
+        self.violations
+    }
+
+    fn push(&mut self, address: Option<B160>, caller: B160, kind: CallKind) {
+        let entity = address
+            .filter(|address| self.entities.contains_key(address))
+            .or_else(|| self.frames.last().and_then(|frame| frame.entity));
+        self.frames.push(Frame {
+            entity,
+            caller,
+            kind,
+        });
+    }
+
+    fn check(&self, policy: &EntityPolicy, interp: &Interpreter) -> Option<PolicyRule> {
+        let op = interp.current_opcode();
+        if policy.banned_opcodes.contains(&op) {
+            return Some(PolicyRule::BannedOpcode(op));
+        }
+        if op != opcode::SLOAD && op != opcode::SSTORE {
+            return None;
+        }
+        let slot = interp.stack.peek(0).ok()?;
+        let address = interp.contract.address;
+        let write = op == opcode::SSTORE;
+        (!policy.is_allowed(address, slot, write)).then_some(PolicyRule::StorageAccess {
+            address,
+            slot,
+            write,
+        })
+    }
+}
+
+impl<DB: Database> Inspector<DB> for PolicyInspector {
+    fn step(&mut self, interp: &mut Interpreter, data: &mut EVMData<'_, DB>) -> InstructionResult {
+        let Some(frame) = self.frames.last() else {
+            return InstructionResult::Continue;
+        };
+        let Some(entity) = frame.entity else {
+            return InstructionResult::Continue;
+        };
+        let Some(rule) = self.check(&self.entities[&entity], interp) else {
+            return InstructionResult::Continue;
+        };
+        self.violations.push(PolicyViolation {
+            entity,
+            address: interp.contract.address,
+            caller: frame.caller,
+            kind: frame.kind,
+            depth: data.journaled_state.depth(),
+            pc: interp.program_counter(),
+            rule,
+        });
+        if self.revert_on_violation {
+            InstructionResult::Revert
+        } else {
+            InstructionResult::Continue
+        }
+    }
+
+    fn call(
+        &mut self,
+        _data: &mut EVMData<'_, DB>,
+        inputs: &mut CallInputs,
+    ) -> (InstructionResult, Gas, Bytes) {
+        self.push(
+            Some(inputs.context.address),
+            inputs.context.caller,
+            inputs.context.scheme.into(),
+        );
+        (InstructionResult::Continue, Gas::new(0), Bytes::new())
+    }
+
+    fn call_end(
+        &mut self,
+        _data: &mut EVMData<'_, DB>,
+        _inputs: &CallInputs,
+        remaining_gas: Gas,
+        ret: InstructionResult,
+        out: Bytes,
+    ) -> (InstructionResult, Gas, Bytes) {
+        self.frames.pop();
+        (ret, remaining_gas, out)
+    }
+
+    fn create(
+        &mut self,
+        _data: &mut EVMData<'_, DB>,
+        inputs: &mut CreateInputs,
+    ) -> (InstructionResult, Option<B160>, Gas, Bytes) {
+        // created contract can't be an entity, it is part of subtree of its creator.
+        self.push(None, inputs.caller, inputs.scheme.into());
+        (InstructionResult::Continue, None, Gas::new(0), Bytes::new())
+    }
+
+    fn create_end(
+        &mut self,
+        _data: &mut EVMData<'_, DB>,
+        _inputs: &CreateInputs,
+        ret: InstructionResult,
+        address: Option<B160>,
+        remaining_gas: Gas,
+        out: Bytes,
+    ) -> (InstructionResult, Option<B160>, Gas, Bytes) {
+        self.frames.pop();
+        (ret, address, remaining_gas, out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primitives::{hex_literal::hex, AccountInfo, Bytecode, ExecutionResult, TransactTo};
+    use crate::InMemoryDB;
+
+    #[test]
+    fn checks_entity_subtree() {
+        let caller = B160([0x10; 20]);
+        let entity = B160([0x20; 20]);
+        let inner = B160([0x30; 20]);
+
+        // SLOAD(0) POP, CALL(GAS, inner, 0, 0, 0, 0, 0) POP, NUMBER POP STOP
+        let mut code = hex!("600054506000600060006000600073").to_vec();
+        code.extend_from_slice(&inner.0);
+        code.extend_from_slice(&hex!("5af150435000"));
+        let mut db = InMemoryDB::default();
+        db.insert_account_info(
+            entity,
+            AccountInfo::new(U256::ZERO, 0, Bytecode::new_raw(code.into())),
+        );
+        // SLOAD(1) POP, SSTORE(1, 1) STOP
+        db.insert_account_info(
+            inner,
+            AccountInfo::new(
+                U256::ZERO,
+                0,
+                Bytecode::new_raw(hex!("600154506001600155").to_vec().into()),
+            ),
+        );
+
+        let mut evm = crate::new();
+        evm.database(db);
+        evm.env.tx.caller = caller;
+        evm.env.tx.transact_to = TransactTo::Call(entity);
+
+        let policy = EntityPolicy::new()
+            .ban(opcode::NUMBER)
+            .allow_contract(entity, Access::ReadWrite)
+            .allow_slot(inner, U256::from(1), Access::Read);
+        let mut inspector = PolicyInspector::new().with_entity(entity, policy);
+        evm.inspect(&mut inspector).unwrap();
+
+        let violations = inspector.violations();
+        assert_eq!(violations.len(), 2);
+        assert_eq!(
+            violations[0].rule,
+            PolicyRule::StorageAccess {
+                address: inner,
+                slot: U256::from(1),
+                write: true
+            }
+        );
+        assert_eq!(
+            (
+                violations[0].caller,
+                violations[0].kind,
+                violations[0].depth
+            ),
+            (entity, CallKind::Call, 2)
+        );
+        assert_eq!(violations[0].pc, 8);
+        assert_eq!(violations[1].rule, PolicyRule::BannedOpcode(opcode::NUMBER));
+        assert_eq!(violations[1].address, entity);
+
+        // write reverts `inner` and entity continues until it reverts on NUMBER.
+        let policy = inspector.entities[&entity].clone();
+        let mut inspector = PolicyInspector::new()
+            .with_entity(entity, policy)
+            .revert_on_violation(true);
+        let result = evm.inspect(&mut inspector).unwrap().result;
+        assert!(matches!(result, ExecutionResult::Revert { .. }));
+        assert_eq!(inspector.violations().len(), 2);
+
+        // without policy nothing is restricted.
+        let mut inspector = PolicyInspector::new();
+        evm.inspect(&mut inspector).unwrap();
+        assert!(inspector.violations().is_empty());
+    }
+}
@@ -0,0 +1,264 @@
+//! Inspector attributing gas refunds of the transaction to storage slots.
+//!
+//! Refund of every SSTORE is added to its slot. Refunds of frames that reverted or halted are
+//! discarded same as EVM discards them, so the sum of all slots is the refund of the
+//! transaction before it is capped by EIP-3529 (or half of gas used before London).
+use crate::interpreter::{
+    opcode, return_ok, CallInputs, CreateInputs, Gas, InstructionResult, Interpreter,
+};
+use crate::primitives::{Bytes, HashMap, B160, U256};
+use crate::{Database, EVMData, Inspector};
+use alloc::vec::Vec;
+
+/// What the writes to the slot did, as far as refunds are concerned.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum RefundKind {
+    /// No refund.
+    None,
+    /// Nonzero slot was cleared.
+    Clear,
+    /// Slot was changed and then set back to its original value.
+    ResetToOriginal,
+    /// Refund that none of above explains, as when it was reduced by later writes.
+    Other,
+}
+
+/// Storage change of the slot with refund it produced.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SlotRefund {
+    /// Value at the start of the transaction.
+    pub original: U256,
+    /// Value after the last write.
+    pub present: U256,
+    /// Net refund of all writes, it can be negative when a write undid refund of the
+    /// previous one.
+    pub refund: i64,
+}
+
+impl SlotRefund {
+    pub fn kind(&self) -> RefundKind {
+        if self.refund == 0 {
+            RefundKind::None
+        } else if self.present == self.original {
+            RefundKind::ResetToOriginal
+        } else if self.present == U256::ZERO {
+            RefundKind::Clear
+        } else {
+            RefundKind::Other
+        }
+    }
+}
+
+/// Slot refunds of a frame, they are discarded if the frame fails.
+type FrameRefunds = HashMap<(B160, U256), SlotRefund>;
+
+/// Inspector collecting [SlotRefund] of every written slot.
+#[derive(Clone, Debug, Default)]
+pub struct RefundInspector {
+    /// SSTORE that is executing with refund counter before it.
+    pending: Option<(B160, U256, i64)>,
+    frames: Vec<FrameRefunds>,
+    slots: FrameRefunds,
+}
+
+impl RefundInspector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Written slots of the transaction, including ones without refund.
+    pub fn slots(&self) -> &HashMap<(B160, U256), SlotRefund> {
+        &self.slots
+    }
+
+    /// Refund of the transaction before the cap.
+    pub fn total_refund(&self) -> i64 {
+        self.slots.values().map(|slot| slot.refund).sum()
+    }
+
+    /// Take slots of the transaction and start a new one.
+    pub fn take_slots(&mut self) -> HashMap<(B160, U256), SlotRefund> {
+        self.pending = None;
+        self.frames.clear();
+        core::mem::take(&mut self.slots)
+    }
+
+    fn frame_end(&mut self, ret: InstructionResult) {
+        let Some(frame) = self.frames.pop() else {
+            return;
+        };
+        if !matches!(ret, return_ok!()) {
+            return;
+        }
+        let parent = self.frames.last_mut().unwrap_or(&mut self.slots);
+        for (key, slot) in frame {
+            parent
+                .entry(key)
+                .and_modify(|parent| {
+                    parent.present = slot.present;
+                    parent.refund += slot.refund;
+                })
+                .or_insert(slot);
+        }
+    }
+}
+
+impl<DB: Database> Inspector<DB> for RefundInspector {
+    fn step(&mut self, interp: &mut Interpreter, _data: &mut EVMData<'_, DB>) -> InstructionResult {
+        if interp.current_opcode() == opcode::SSTORE {
+            if let Ok(slot) = interp.stack.peek(0) {
+                self.pending = Some((interp.contract.address, slot, interp.gas.refunded()));
+            }
+        }
+        InstructionResult::Continue
+    }
+
+    fn step_end(
+        &mut self,
+        interp: &mut Interpreter,
+        data: &mut EVMData<'_, DB>,
+        eval: InstructionResult,
+    ) -> InstructionResult {
+        let Some((address, slot, refunded)) = self.pending.take() else {
+            return InstructionResult::Continue;
+        };
+        if eval != InstructionResult::Continue {
+            return InstructionResult::Continue;
+        }
+        let Some(value) = data
+            .journaled_state
+            .state
+            .get(&address)
+            .and_then(|account| account.storage.get(&slot))
+        else {
+            return InstructionResult::Continue;
+        };
+        let (original, present) = (value.original_value(), value.present_value());
+        if let Some(frame) = self.frames.last_mut() {
+            let entry = frame.entry((address, slot)).or_insert(SlotRefund {
+                original,
+                present,
+                refund: 0,
+            });
+            entry.present = present;
+            entry.refund += interp.gas.refunded() - refunded;
+        }
+        InstructionResult::Continue
+    }
+
+    fn call(
+        &mut self,
+        _data: &mut EVMData<'_, DB>,
+        _inputs: &mut CallInputs,
+    ) -> (InstructionResult, Gas, Bytes) {
+        self.frames.push(FrameRefunds::new());
+        (InstructionResult::Continue, Gas::new(0), Bytes::new())
+    }
+
+    fn call_end(
+        &mut self,
+        _data: &mut EVMData<'_, DB>,
+        _inputs: &CallInputs,
+        remaining_gas: Gas,
+        ret: InstructionResult,
+        out: Bytes,
+    ) -> (InstructionResult, Gas, Bytes) {
+        self.frame_end(ret);
+        (ret, remaining_gas, out)
+    }
+
+    fn create(
+        &mut self,
+        _data: &mut EVMData<'_, DB>,
+        _inputs: &mut CreateInputs,
+    ) -> (InstructionResult, Option<B160>, Gas, Bytes) {
+        self.frames.push(FrameRefunds::new());
+        (
+            InstructionResult::Continue,
+            None,
+            Gas::new(0),
+            Bytes::default(),
+        )
+    }
+
+    fn create_end(
+        &mut self,
+        _data: &mut EVMData<'_, DB>,
+        _inputs: &CreateInputs,
+        ret: InstructionResult,
+        address: Option<B160>,
+        remaining_gas: Gas,
+        out: Bytes,
+    ) -> (InstructionResult, Option<B160>, Gas, Bytes) {
+        self.frame_end(ret);
+        (ret, address, remaining_gas, out)
+    }
+}
+
+#[cfg(all(test, not(feature = "no_gas_measuring")))]
+mod tests {
+    use super::*;
+    use crate::primitives::{hex_literal::hex, AccountInfo, Bytecode, TransactTo};
+    use crate::InMemoryDB;
+
+    #[test]
+    fn attributes_refunds_to_slots() {
+        let caller = B160([0x10; 20]);
+        let contract = B160([0x20; 20]);
+        let reverting = B160([0x30; 20]);
+        // SSTORE(0, 0) SSTORE(1, 5) SSTORE(1, 1) SSTORE(2, 3)
+        // CALL(GAS, reverting, 0, 0, 0, 0, 0)
+        let mut code =
+            hex!("6000600055600560015560016001556003600255600060006000600060007f").to_vec();
+        code.extend_from_slice(&[0; 12]);
+        code.extend_from_slice(&reverting.0);
+        code.extend_from_slice(&hex!("5af100"));
+        let mut db = InMemoryDB::default();
+        db.insert_account_info(
+            contract,
+            AccountInfo::new(U256::ZERO, 0, Bytecode::new_raw(code.into())),
+        );
+        for slot in 0..2 {
+            db.insert_account_storage(contract, U256::from(slot), U256::from(1))
+                .unwrap();
+        }
+        // SSTORE(0, 0) REVERT(0, 0)
+        db.insert_account_info(
+            reverting,
+            AccountInfo::new(
+                U256::ZERO,
+                0,
+                Bytecode::new_raw(hex!("600060005560006000fd").to_vec().into()),
+            ),
+        );
+        db.insert_account_storage(reverting, U256::ZERO, U256::from(1))
+            .unwrap();
+
+        let mut evm = crate::new();
+        evm.database(db);
+        evm.env.tx.caller = caller;
+        evm.env.tx.transact_to = TransactTo::Call(contract);
+        let mut inspector = RefundInspector::new();
+        evm.inspect(&mut inspector).unwrap();
+
+        let slots = inspector.slots();
+        assert_eq!(slots.len(), 3);
+        let slot = |index: u64| slots[&(contract, U256::from(index))];
+        assert_eq!(slot(0).kind(), RefundKind::Clear);
+        assert_eq!(slot(0).refund, 4800);
+        assert_eq!(slot(1).kind(), RefundKind::ResetToOriginal);
+        assert_eq!(slot(1).refund, 2800);
+        assert_eq!(slot(2).kind(), RefundKind::None);
+        assert_eq!(
+            slot(2),
+            SlotRefund {
+                original: U256::ZERO,
+                present: U256::from(3),
+                refund: 0,
+            }
+        );
+        assert_eq!(inspector.total_refund(), 7600);
+    }
+}
@@ -0,0 +1,216 @@
+//! Inspector reporting gas sensitive storage writes and calls.
+//!
+//! SSTORE fails when gas left is at or below EIP-2200 sentinel and calls with value forward
+//! only the 2300 stipend when caller gives them no gas. Code relying on either breaks when
+//! opcodes are repriced, the report shows where a transaction does so.
+use crate::interpreter::{
+    gas::CALL_STIPEND, opcode, CallInputs, CallScheme, Gas, InstructionResult, Interpreter,
+};
+use crate::primitives::{Bytes, B160, U256};
+use crate::{Database, EVMData, Inspector};
+use alloc::vec::Vec;
+
+/// SSTORE executed with gas left at or below the sentinel.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LowGasSstore {
+    pub address: B160,
+    pub slot: U256,
+    pub pc: usize,
+    pub depth: u64,
+    /// Gas left before the SSTORE.
+    pub gas_remaining: u64,
+}
+
+/// Call transferring value that was given only the stipend.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StipendCall {
+    pub caller: B160,
+    pub address: B160,
+    pub value: U256,
+    pub depth: u64,
+    pub gas_limit: u64,
+    /// Gas used by the callee.
+    pub gas_used: u64,
+    pub success: bool,
+}
+
+/// Findings of one transaction.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StipendReport {
+    pub low_gas_sstores: Vec<LowGasSstore>,
+    pub stipend_calls: Vec<StipendCall>,
+}
+
+impl StipendReport {
+    pub fn is_empty(&self) -> bool {
+        self.low_gas_sstores.is_empty() && self.stipend_calls.is_empty()
+    }
+}
+
+/// Inspector collecting [StipendReport].
+#[derive(Clone, Debug)]
+pub struct StipendInspector {
+    sentinel: u64,
+    report: StipendReport,
+    /// Index of stipend call in the report for every call that is executing.
+    calls: Vec<Option<usize>>,
+}
+
+impl Default for StipendInspector {
+    fn default() -> Self {
+        Self::new(CALL_STIPEND)
+    }
+}
+
+impl StipendInspector {
+    /// SSTOREs with gas left at or below `sentinel` are reported. EIP-2200 sentinel is
+    /// [CALL_STIPEND], higher value shows writes that would fail if sentinel was raised.
+    pub fn new(sentinel: u64) -> Self {
+        Self {
+            sentinel,
+            report: StipendReport::default(),
+            calls: Vec::new(),
+        }
+    }
+
+    pub fn report(&self) -> &StipendReport {
+        &self.report
+    }
+
+    /// Take report of the transaction and start a new one.
+    pub fn take_report(&mut self) -> StipendReport {
+        self.calls.clear();
+        core::mem::take(&mut self.report)
+    }
+}
+
+impl<DB: Database> Inspector<DB> for StipendInspector {
+    fn step(&mut self, interp: &mut Interpreter, data: &mut EVMData<'_, DB>) -> InstructionResult {
+        if interp.current_opcode() == opcode::SSTORE && interp.gas.remaining() <= self.sentinel {
+            if let Ok(slot) = interp.stack.peek(0) {
+                self.report.low_gas_sstores.push(LowGasSstore {
+                    address: interp.contract.address,
+                    slot,
+                    pc: interp.program_counter(),
+                    depth: data.journaled_state.depth(),
+                    gas_remaining: interp.gas.remaining(),
+                });
+            }
+        }
+        InstructionResult::Continue
+    }
+
+    fn call(
+        &mut self,
+        data: &mut EVMData<'_, DB>,
+        inputs: &mut CallInputs,
+    ) -> (InstructionResult, Gas, Bytes) {
+        let transfers = matches!(
+            inputs.context.scheme,
+            CallScheme::Call | CallScheme::CallCode
+        ) && inputs.transfer.value != U256::ZERO;
+        let index = (transfers && inputs.gas_limit <= CALL_STIPEND).then(|| {
+            self.report.stipend_calls.push(StipendCall {
+                caller: inputs.context.caller,
+                address: inputs.contract,
+                value: inputs.transfer.value,
+                depth: data.journaled_state.depth() + 1,
+                gas_limit: inputs.gas_limit,
+                gas_used: 0,
+                success: false,
+            });
+            self.report.stipend_calls.len() - 1
+        });
+        self.calls.push(index);
+        (InstructionResult::Continue, Gas::new(0), Bytes::new())
+    }
+
+    fn call_end(
+        &mut self,
+        _data: &mut EVMData<'_, DB>,
+        _inputs: &CallInputs,
+        remaining_gas: Gas,
+        ret: InstructionResult,
+        out: Bytes,
+    ) -> (InstructionResult, Gas, Bytes) {
+        if let Some(Some(index)) = self.calls.pop() {
+            let call = &mut self.report.stipend_calls[index];
+            call.success = matches!(
+                ret,
+                InstructionResult::Return
+                    | InstructionResult::Stop
+                    | InstructionResult::SelfDestruct
+            );
+            // halted call consumes all of its gas.
+            call.gas_used = if call.success || ret == InstructionResult::Revert {
+                call.gas_limit.saturating_sub(remaining_gas.remaining())
+            } else {
+                call.gas_limit
+            };
+        }
+        (ret, remaining_gas, out)
+    }
+}
+
+#[cfg(all(test, not(feature = "no_gas_measuring")))]
+mod tests {
+    use super::*;
+    use crate::primitives::{hex_literal::hex, AccountInfo, Bytecode, TransactTo};
+    use crate::InMemoryDB;
+
+    #[test]
+    fn reports_stipend_call_and_low_gas_sstore() {
+        let caller = B160([0x10; 20]);
+        let wallet = B160([0x20; 20]);
+        let receiver = B160([0x30; 20]);
+        // CALL(0, receiver, 1, 0, 0, 0, 0)
+        let mut code = hex!("60006000600060006001600073").to_vec();
+        code.extend_from_slice(&receiver.0);
+        // swap address and gas pushed above so that gas is on top.
+        code.extend_from_slice(&hex!("90f100"));
+        let mut db = InMemoryDB::default();
+        db.insert_account_info(
+            wallet,
+            AccountInfo::new(U256::from(10), 0, Bytecode::new_raw(code.into())),
+        );
+        // SSTORE(0, CALLVALUE)
+        db.insert_account_info(
+            receiver,
+            AccountInfo::new(
+                U256::ZERO,
+                0,
+                Bytecode::new_raw(hex!("3460005500").to_vec().into()),
+            ),
+        );
+
+        let mut evm = crate::new();
+        evm.database(db);
+        evm.env.tx.caller = caller;
+        evm.env.tx.transact_to = TransactTo::Call(wallet);
+
+        let mut inspector = StipendInspector::default();
+        evm.inspect(&mut inspector).unwrap();
+        let report = inspector.take_report();
+        assert_eq!(
+            report.stipend_calls,
+            [StipendCall {
+                caller: wallet,
+                address: receiver,
+                value: U256::from(1),
+                depth: 2,
+                gas_limit: CALL_STIPEND,
+                gas_used: CALL_STIPEND,
+                success: false,
+            }]
+        );
+        // stipend is below sentinel, the write halted.
+        assert_eq!(report.low_gas_sstores.len(), 1);
+        assert_eq!(report.low_gas_sstores[0].address, receiver);
+        assert_eq!(report.low_gas_sstores[0].depth, 2);
+
+        assert!(inspector.report().is_empty());
+    }
+}
@@ -0,0 +1,630 @@
+//! Inspector producing opcode logs of geth default tracer.
+//!
+//! With `serde` feature [ExecutionTrace] serializes to the same JSON as
+//! `debug_traceTransaction` without a tracer, and [StructLoggerConfig] deserializes from
+//! its options.
+//!
+//! [StructLogStream] writes logs to a [StructLogWriter] as they are finished instead of
+//! keeping them, so memory stays bounded however long the transaction is.
+use super::call_tracer::error_message;
+use crate::interpreter::{
+    opcode, return_ok, CallInputs, CreateInputs, Gas, InstructionResult, Interpreter,
+};
+use crate::primitives::{Bytes, ExecutionResult, HashMap, B160, B256, U256};
+use crate::{Database, EVMData, Inspector};
+use alloc::borrow::Cow;
+use alloc::collections::BTreeMap;
+use alloc::vec::{Drain, Vec};
+
+/// Options of [StructLogger], same as the ones of geth.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default, rename_all = "camelCase"))]
+pub struct StructLoggerConfig {
+    pub disable_stack: bool,
+    pub disable_storage: bool,
+    pub enable_memory: bool,
+    pub enable_return_data: bool,
+}
+
+/// State of the interpreter before an opcode is executed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+pub struct StructLog {
+    pub pc: u64,
+    pub op: Cow<'static, str>,
+    pub gas: u64,
+    /// Gas charged by the opcode, with gas used by the frame it called.
+    pub gas_cost: u64,
+    /// Depth of the frame, one for the transaction.
+    pub depth: u64,
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub error: Option<Cow<'static, str>>,
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub stack: Option<Vec<U256>>,
+    /// Memory, in 32 byte words when serialized.
+    #[cfg_attr(
+        feature = "serde",
+        serde(
+            default,
+            skip_serializing_if = "Option::is_none",
+            with = "memory_words"
+        )
+    )]
+    pub memory: Option<Bytes>,
+    #[cfg_attr(
+        feature = "serde",
+        serde(
+            default,
+            skip_serializing_if = "Option::is_none",
+            with = "hex_bytes_opt"
+        )
+    )]
+    pub return_data: Option<Bytes>,
+    /// Slots of the executing contract read or written so far, set for `SLOAD` and `SSTORE`.
+    #[cfg_attr(
+        feature = "serde",
+        serde(
+            default,
+            skip_serializing_if = "Option::is_none",
+            with = "storage_words"
+        )
+    )]
+    pub storage: Option<BTreeMap<B256, B256>>,
+}
+
+/// Logs of the transaction with its outcome.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+pub struct ExecutionTrace {
+    /// Gas used by the transaction.
+    pub gas: u64,
+    pub failed: bool,
+    /// Output of the transaction, hex without prefix when serialized.
+    #[cfg_attr(feature = "serde", serde(with = "unprefixed_hex"))]
+    pub return_value: Bytes,
+    pub struct_logs: Vec<StructLog>,
+}
+
+/// Inspector logging every executed opcode, see [StructLog].
+///
+/// Memory is copied only if [StructLoggerConfig::enable_memory] is set and storage only
+/// for `SLOAD` and `SSTORE`, so logging stack alone is cheap.
+#[derive(Clone, Debug, Default)]
+pub struct StructLogger {
+    config: StructLoggerConfig,
+    logs: Vec<StructLog>,
+    /// Logs of opcodes that are executing, the last one is of the innermost frame. None if
+    /// the log was already finished by [StructLogStream].
+    pending: Vec<Option<usize>>,
+    storage: HashMap<B160, BTreeMap<B256, B256>>,
+    /// Slot of the executing `SLOAD`.
+    sload: Option<U256>,
+}
+
+impl StructLogger {
+    pub fn new(config: StructLoggerConfig) -> Self {
+        Self {
+            config,
+            ..Default::default()
+        }
+    }
+
+    pub fn logs(&self) -> &[StructLog] {
+        &self.logs
+    }
+
+    /// Logs with outcome of the transaction they were logged for.
+    pub fn into_trace(self, result: &ExecutionResult) -> ExecutionTrace {
+        ExecutionTrace {
+            gas: result.gas_used(),
+            failed: !result.is_success(),
+            return_value: result.output().cloned().unwrap_or_default(),
+            struct_logs: self.logs,
+        }
+    }
+
+    /// Remember slot of the executing contract and show slots of it in the last log.
+    fn record_slot(&mut self, address: B160, slot: U256, value: U256) {
+        if self.config.disable_storage {
+            return;
+        }
+        let storage = self.storage.entry(address).or_default();
+        storage.insert(slot.to_be_bytes().into(), value.to_be_bytes().into());
+        if let Some(at) = self.pending.last().copied().flatten() {
+            self.logs[at].storage = Some(storage.clone());
+        }
+    }
+
+    /// Finish log of the opcode starting a frame with gas given to the frame as its cost,
+    /// gas used by the frame is known only after logs of the frame.
+    fn start_frame(&mut self, gas_limit: u64) {
+        if let Some(at) = self.pending.last_mut().and_then(Option::take) {
+            self.logs[at].gas_cost = gas_limit;
+        }
+    }
+
+    /// Remove logs that are finished and that no unfinished log precedes.
+    fn drain_finished(&mut self) -> Drain<'_, StructLog> {
+        let end = self
+            .pending
+            .iter()
+            .flatten()
+            .min()
+            .copied()
+            .unwrap_or(self.logs.len());
+        for at in self.pending.iter_mut().flatten() {
+            *at -= end;
+        }
+        self.logs.drain(..end)
+    }
+}
+
+impl<DB: Database> Inspector<DB> for StructLogger {
+    fn step(&mut self, interp: &mut Interpreter, data: &mut EVMData<'_, DB>) -> InstructionResult {
+        let op = interp.current_opcode();
+        let name = match opcode::OPCODE_JUMPMAP[op as usize] {
+            Some(name) => Cow::Borrowed(name),
+            None => Cow::Owned(alloc::format!("opcode {op:#x} not defined")),
+        };
+        let config = &self.config;
+        self.pending.push(Some(self.logs.len()));
+        self.logs.push(StructLog {
+            pc: interp.program_counter() as u64,
+            op: name,
+            gas: interp.gas.remaining(),
+            gas_cost: 0,
+            depth: data.journaled_state.depth(),
+            error: None,
+            stack: (!config.disable_stack).then(|| interp.stack.data().clone()),
+            memory: config
+                .enable_memory
+                .then(|| Bytes::copy_from_slice(interp.memory.data())),
+            return_data: config
+                .enable_return_data
+                .then(|| interp.return_data_buffer.clone()),
+            storage: None,
+        });
+        // value is known once the opcode is executed for `SLOAD`.
+        match op {
+            opcode::SLOAD => self.sload = interp.stack.peek(0).ok(),
+            opcode::SSTORE => {
+                if let (Ok(slot), Ok(value)) = (interp.stack.peek(0), interp.stack.peek(1)) {
+                    self.record_slot(interp.contract.address, slot, value);
+                }
+            }
+            _ => {}
+        }
+        InstructionResult::Continue
+    }
+
+    fn step_end(
+        &mut self,
+        interp: &mut Interpreter,
+        _data: &mut EVMData<'_, DB>,
+        eval: InstructionResult,
+    ) -> InstructionResult {
+        if self.pending.is_empty() {
+            return InstructionResult::Continue;
+        }
+        if let Some(slot) = self.sload.take() {
+            if let (InstructionResult::Continue, Ok(value)) = (eval, interp.stack.peek(0)) {
+                self.record_slot(interp.contract.address, slot, value);
+            }
+        }
+        let Some(at) = self.pending.pop().flatten() else {
+            return InstructionResult::Continue;
+        };
+        let log = &mut self.logs[at];
+        log.gas_cost = log.gas.saturating_sub(interp.gas.remaining());
+        if !matches!(eval, InstructionResult::Continue | return_ok!()) {
+            log.error = Some(Cow::Borrowed(error_message(eval)));
+        }
+        InstructionResult::Continue
+    }
+}
+
+/// Destination of logs of [StructLogStream].
+pub trait StructLogWriter {
+    type Error;
+
+    /// Write the log, waiting until the destination can take it.
+    fn write_log(&mut self, log: StructLog) -> Result<(), Self::Error>;
+}
+
+/// Logs are sent over the bounded channel, tracing waits while the channel is full.
+#[cfg(feature = "std")]
+impl StructLogWriter for std::sync::mpsc::SyncSender<StructLog> {
+    type Error = std::sync::mpsc::SendError<StructLog>;
+
+    fn write_log(&mut self, log: StructLog) -> Result<(), Self::Error> {
+        self.send(log)
+    }
+}
+
+/// Writer of logs as JSON, one log per line.
+#[cfg(all(feature = "std", feature = "serde"))]
+#[derive(Clone, Debug, Default)]
+pub struct JsonLines<W>(pub W);
+
+#[cfg(all(feature = "std", feature = "serde"))]
+impl<W: std::io::Write> StructLogWriter for JsonLines<W> {
+    type Error = std::io::Error;
+
+    fn write_log(&mut self, log: StructLog) -> Result<(), Self::Error> {
+        serde_json::to_writer(&mut self.0, &log)?;
+        self.0.write_all(b"\n")
+    }
+}
+
+/// [StructLogger] that writes each log to `W` once it is finished.
+///
+/// Logs are written in the order opcodes were executed. Log of an opcode starting a frame
+/// is written when the frame starts, so its gas cost is the gas given to the frame. After
+/// the first failed write, logs are dropped and the error is returned by
+/// [StructLogStream::into_writer].
+#[derive(Clone, Debug)]
+pub struct StructLogStream<W: StructLogWriter> {
+    logger: StructLogger,
+    writer: W,
+    error: Option<W::Error>,
+}
+
+impl<W: StructLogWriter> StructLogStream<W> {
+    pub fn new(config: StructLoggerConfig, writer: W) -> Self {
+        Self {
+            logger: StructLogger::new(config),
+            writer,
+            error: None,
+        }
+    }
+
+    /// Writer, or the error of the first failed write.
+    pub fn into_writer(self) -> Result<W, W::Error> {
+        match self.error {
+            Some(error) => Err(error),
+            None => Ok(self.writer),
+        }
+    }
+
+    fn flush(&mut self) {
+        for log in self.logger.drain_finished() {
+            if self.error.is_none() {
+                if let Err(error) = self.writer.write_log(log) {
+                    self.error = Some(error);
+                }
+            }
+        }
+    }
+}
+
+impl<DB: Database, W: StructLogWriter> Inspector<DB> for StructLogStream<W> {
+    fn step(&mut self, interp: &mut Interpreter, data: &mut EVMData<'_, DB>) -> InstructionResult {
+        self.logger.step(interp, data)
+    }
+
+    fn step_end(
+        &mut self,
+        interp: &mut Interpreter,
+        data: &mut EVMData<'_, DB>,
+        eval: InstructionResult,
+    ) -> InstructionResult {
+        self.logger.step_end(interp, data, eval);
+        self.flush();
+        InstructionResult::Continue
+    }
+
+    fn call(
+        &mut self,
+        _data: &mut EVMData<'_, DB>,
+        inputs: &mut CallInputs,
+    ) -> (InstructionResult, Gas, Bytes) {
+        self.logger.start_frame(inputs.gas_limit);
+        self.flush();
+        (InstructionResult::Continue, Gas::new(0), Bytes::new())
+    }
+
+    fn create(
+        &mut self,
+        _data: &mut EVMData<'_, DB>,
+        inputs: &mut CreateInputs,
+    ) -> (InstructionResult, Option<B160>, Gas, Bytes) {
+        self.logger.start_frame(inputs.gas_limit);
+        self.flush();
+        (
+            InstructionResult::Continue,
+            None,
+            Gas::new(0),
+            Bytes::default(),
+        )
+    }
+}
+
+/// Serde of memory as 32 byte words in hex without prefix.
+#[cfg(feature = "serde")]
+mod memory_words {
+    use crate::primitives::{hex, Bytes};
+    use alloc::string::{String, ToString};
+    use alloc::vec::Vec;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer>(memory: &Option<Bytes>, s: S) -> Result<S::Ok, S::Error> {
+        memory
+            .as_ref()
+            .map(|memory| memory.chunks(32).map(hex::encode).collect::<Vec<_>>())
+            .serialize(s)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Option<Bytes>, D::Error> {
+        let Some(words) = Option::<Vec<String>>::deserialize(d)? else {
+            return Ok(None);
+        };
+        let mut memory = Vec::with_capacity(words.len() * 32);
+        for word in words {
+            memory.extend(hex::decode(word).map_err(|e| serde::de::Error::custom(e.to_string()))?);
+        }
+        Ok(Some(memory.into()))
+    }
+}
+
+/// Serde of storage slots as 32 byte words in hex without prefix.
+#[cfg(feature = "serde")]
+mod storage_words {
+    use crate::primitives::{hex, B256};
+    use alloc::collections::BTreeMap;
+    use alloc::string::{String, ToString};
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        storage: &Option<BTreeMap<B256, B256>>,
+        s: S,
+    ) -> Result<S::Ok, S::Error> {
+        storage
+            .as_ref()
+            .map(|storage| {
+                storage
+                    .iter()
+                    .map(|(slot, value)| (hex::encode(slot), hex::encode(value)))
+                    .collect::<BTreeMap<_, _>>()
+            })
+            .serialize(s)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        d: D,
+    ) -> Result<Option<BTreeMap<B256, B256>>, D::Error> {
+        let Some(words) = Option::<BTreeMap<String, String>>::deserialize(d)? else {
+            return Ok(None);
+        };
+        let word = |word: &str| -> Result<B256, D::Error> {
+            let mut out = B256::zero();
+            hex::decode_to_slice(word, &mut out.0)
+                .map_err(|e| serde::de::Error::custom(e.to_string()))?;
+            Ok(out)
+        };
+        words
+            .iter()
+            .map(|(slot, value)| Ok((word(slot)?, word(value)?)))
+            .collect::<Result<_, _>>()
+            .map(Some)
+    }
+}
+
+/// Serde of optional bytes as hex.
+#[cfg(feature = "serde")]
+mod hex_bytes_opt {
+    use crate::primitives::{utilities::serde_hex_bytes, Bytes};
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(value: &Option<Bytes>, s: S) -> Result<S::Ok, S::Error> {
+        match value {
+            Some(value) => serde_hex_bytes::serialize(value, s),
+            None => s.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Option<Bytes>, D::Error> {
+        #[derive(Deserialize)]
+        struct Hex(#[serde(with = "serde_hex_bytes")] Bytes);
+        Ok(Option::<Hex>::deserialize(d)?.map(|hex| hex.0))
+    }
+}
+
+/// Serde of bytes as hex without prefix.
+#[cfg(feature = "serde")]
+mod unprefixed_hex {
+    use crate::primitives::{hex, Bytes};
+    use alloc::string::{String, ToString};
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(value: &Bytes, s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_str(&hex::encode(value))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Bytes, D::Error> {
+        let value = String::deserialize(d)?;
+        hex::decode(value.trim_start_matches("0x"))
+            .map(Into::into)
+            .map_err(|e| serde::de::Error::custom(e.to_string()))
+    }
+}
+
+#[cfg(all(test, not(feature = "no_gas_measuring")))]
+mod tests {
+    use super::*;
+    use crate::primitives::{hex_literal::hex, AccountInfo, Bytecode, TransactTo};
+    use crate::InMemoryDB;
+
+    #[test]
+    fn logs_opcodes() {
+        let contract = B160([0x20; 20]);
+        // SSTORE(1, 2) SLOAD(1) MSTORE(0) INVALID
+        let code = hex!("6002600155600154600052fe");
+        let mut db = InMemoryDB::default();
+        db.insert_account_info(
+            contract,
+            AccountInfo::new(U256::ZERO, 0, Bytecode::new_raw(code.to_vec().into())),
+        );
+        let mut evm = crate::new();
+        evm.database(db);
+        evm.env.tx.gas_limit = 100_000;
+        evm.env.tx.transact_to = TransactTo::Call(contract);
+
+        let mut logger = StructLogger::new(StructLoggerConfig {
+            enable_memory: true,
+            ..Default::default()
+        });
+        let result = evm.inspect(&mut logger).unwrap().result;
+        let trace = logger.into_trace(&result);
+        assert!(trace.failed);
+        let logs = &trace.struct_logs;
+        let ops: Vec<_> = logs.iter().map(|log| log.op.as_ref()).collect();
+        assert_eq!(
+            ops,
+            ["PUSH1", "PUSH1", "SSTORE", "PUSH1", "SLOAD", "PUSH1", "MSTORE", "INVALID"]
+        );
+        assert!(logs.iter().all(|log| log.depth == 1));
+        assert_eq!(logs[0].gas, 100_000 - 21_000);
+        assert_eq!(logs[0].gas_cost, 3);
+        assert_eq!(logs[1].gas, logs[0].gas - 3);
+        assert_eq!(logs[2].stack, Some(vec![U256::from(2), U256::from(1)]));
+        assert_eq!(logs[2].memory, Some(Bytes::new()));
+        assert_eq!(logs[7].memory.as_ref().unwrap()[31], 2);
+        assert_eq!(logs[7].error.as_deref(), Some("invalid opcode"));
+        assert_eq!(logs[6].error, None);
+
+        let slots: BTreeMap<B256, B256> = [(
+            U256::from(1).to_be_bytes().into(),
+            U256::from(2).to_be_bytes().into(),
+        )]
+        .into();
+        assert_eq!(logs[2].storage, Some(slots.clone()));
+        assert_eq!(logs[4].storage, Some(slots));
+        assert_eq!(logs[3].storage, None);
+
+        #[cfg(feature = "serde")]
+        {
+            let json = serde_json::to_value(&trace).unwrap();
+            assert_eq!(json["failed"], true);
+            assert_eq!(json["returnValue"], "");
+            let log = &json["structLogs"][2];
+            assert_eq!(log["op"], "SSTORE");
+            assert_eq!(log["gas"], logs[2].gas);
+            assert_eq!(log["stack"][0], "0x2");
+            assert_eq!(log["memory"].as_array().unwrap().len(), 0);
+            assert_eq!(log["storage"][format!("{:064x}", 1)], format!("{:064x}", 2));
+            assert!(log.get("returnData").is_none());
+            assert_eq!(json["structLogs"][7]["memory"][0], format!("{:064x}", 2));
+            let decoded: ExecutionTrace = serde_json::from_value(json).unwrap();
+            assert_eq!(decoded, trace);
+        }
+    }
+
+    #[test]
+    fn logs_nested_frames() {
+        let outer = B160([0x20; 20]);
+        let inner = B160([0x30; 20]);
+        // CALL(GAS, inner, 0, 0, 0, 0, 0) STOP
+        let mut code = hex!("6000600060006000600073").to_vec();
+        code.extend_from_slice(&inner.0);
+        code.extend_from_slice(&hex!("5af100"));
+        let mut db = InMemoryDB::default();
+        db.insert_account_info(
+            outer,
+            AccountInfo::new(U256::ZERO, 0, Bytecode::new_raw(code.into())),
+        );
+        db.insert_account_info(
+            inner,
+            AccountInfo::new(U256::ZERO, 0, Bytecode::new_raw(hex!("00").to_vec().into())),
+        );
+        let mut evm = crate::new();
+        evm.database(db);
+        evm.env.tx.transact_to = TransactTo::Call(outer);
+
+        let mut logger = StructLogger::new(StructLoggerConfig {
+            disable_stack: true,
+            ..Default::default()
+        });
+        assert!(evm.inspect(&mut logger).unwrap().result.is_success());
+        let logs = logger.logs();
+        let call = logs.iter().position(|log| log.op == "CALL").unwrap();
+        assert_eq!(logs[call + 1].depth, 2);
+        assert_eq!(logs[call + 1].op, "STOP");
+        assert_eq!(logs[call + 2].depth, 1);
+        // call charges the gas it gave and gets back what was not used.
+        assert!(logs[call].gas_cost >= 100);
+        assert_eq!(logs[call + 2].gas, logs[call].gas - logs[call].gas_cost);
+        assert!(logs
+            .iter()
+            .all(|log| log.stack.is_none() && log.memory.is_none()));
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn streams_logs() {
+        let outer = B160([0x20; 20]);
+        let inner = B160([0x30; 20]);
+        // CALL(GAS, inner, 0, 0, 0, 0, 0) SSTORE(1, 1)
+        let mut code = hex!("6000600060006000600073").to_vec();
+        code.extend_from_slice(&inner.0);
+        code.extend_from_slice(&hex!("5af1 6001600155"));
+        let mut db = InMemoryDB::default();
+        db.insert_account_info(
+            outer,
+            AccountInfo::new(U256::ZERO, 0, Bytecode::new_raw(code.into())),
+        );
+        // SLOAD(1) POP
+        db.insert_account_info(
+            inner,
+            AccountInfo::new(
+                U256::ZERO,
+                0,
+                Bytecode::new_raw(hex!("60015450").to_vec().into()),
+            ),
+        );
+        let mut evm = crate::new();
+        evm.database(db);
+        evm.env.tx.transact_to = TransactTo::Call(outer);
+        let mut logger = StructLogger::default();
+        evm.inspect(&mut logger).unwrap();
+        let expected = logger.logs();
+
+        // channel of one log makes tracing wait for the receiver.
+        let (sender, receiver) = std::sync::mpsc::sync_channel(1);
+        let receiver = std::thread::spawn(move || receiver.iter().collect::<Vec<StructLog>>());
+        let mut stream = StructLogStream::new(StructLoggerConfig::default(), sender);
+        evm.inspect(&mut stream).unwrap();
+        assert!(stream.logger.logs.is_empty());
+        drop(stream.into_writer().unwrap());
+        let logs = receiver.join().unwrap();
+        assert_eq!(logs.len(), expected.len());
+        let call = expected.iter().position(|log| log.op == "CALL").unwrap();
+        // gas cost of the call is the gas given to the inner frame.
+        let mut streamed = logs.clone();
+        streamed[call].gas_cost = expected[call].gas_cost;
+        assert_eq!(streamed, expected);
+        assert_eq!(logs[call].gas_cost, logs[call + 1].gas);
+
+        #[cfg(feature = "serde")]
+        {
+            let mut stream =
+                StructLogStream::new(StructLoggerConfig::default(), JsonLines(Vec::new()));
+            evm.inspect(&mut stream).unwrap();
+            let JsonLines(out) = stream.into_writer().unwrap();
+            let lines: Vec<StructLog> = std::str::from_utf8(&out)
+                .unwrap()
+                .lines()
+                .map(|line| serde_json::from_str(line).unwrap())
+                .collect();
+            assert_eq!(lines, logs);
+        }
+    }
+}
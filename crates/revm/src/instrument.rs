@@ -0,0 +1,165 @@
+//! Spans and events of execution phases, with the `tracing` feature.
+//!
+//! Transaction runs in a `transact` span with `validate`, `execute` and `finalize` spans in
+//! it, every call frame gets a `call` or `create` span and precompile a `precompile` span.
+//! Changes committed to [CacheDB](crate::db::CacheDB) are in a `commit` span. Event at the end
+//! of a phase has its duration in `elapsed_us` and, for execution, its result and used gas.
+//! Spans and events have the `revm` target and debug level.
+//!
+//! Without the feature [phase] and [Phase] compile to nothing.
+use crate::interpreter::InstructionResult;
+
+/// Enter a span of a phase, arguments are the name and fields of [tracing::debug_span].
+#[cfg(feature = "tracing")]
+macro_rules! phase {
+    ($name:literal $(, $($fields:tt)+)?) => {
+        $crate::instrument::Phase::new(
+            $name,
+            tracing::debug_span!(target: "revm", $name $(, $($fields)+)?),
+        )
+    };
+}
+
+#[cfg(not(feature = "tracing"))]
+macro_rules! phase {
+    ($name:literal $(, $($fields:tt)+)?) => {
+        $crate::instrument::Phase
+    };
+}
+
+pub(crate) use phase;
+
+/// Entered span of a phase, it is exited when dropped.
+#[cfg(feature = "tracing")]
+pub(crate) struct Phase {
+    name: &'static str,
+    start: std::time::Instant,
+    _span: tracing::span::EnteredSpan,
+}
+
+#[cfg(feature = "tracing")]
+impl Phase {
+    pub(crate) fn new(name: &'static str, span: tracing::Span) -> Self {
+        Self {
+            name,
+            start: std::time::Instant::now(),
+            _span: span.entered(),
+        }
+    }
+
+    fn elapsed_us(&self) -> u64 {
+        self.start.elapsed().as_micros() as u64
+    }
+
+    /// End the phase with an event of its duration.
+    pub(crate) fn end(self) {
+        tracing::debug!(target: "revm", elapsed_us = self.elapsed_us(), "{} done", self.name);
+    }
+
+    /// End the phase with an event of its result, used gas and duration.
+    pub(crate) fn end_with_gas(self, result: InstructionResult, gas_used: u64) {
+        tracing::debug!(
+            target: "revm",
+            ?result,
+            gas_used,
+            elapsed_us = self.elapsed_us(),
+            "{} done",
+            self.name
+        );
+    }
+}
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) struct Phase;
+
+#[cfg(not(feature = "tracing"))]
+impl Phase {
+    #[inline]
+    pub(crate) fn end(self) {}
+
+    #[inline]
+    pub(crate) fn end_with_gas(self, _result: InstructionResult, _gas_used: u64) {}
+}
+
+#[cfg(all(test, feature = "tracing"))]
+mod tests {
+    use crate::primitives::{AccountInfo, Bytecode, TransactTo, B160, U256};
+    use crate::{DatabaseCommit, InMemoryDB, EVM};
+    use std::sync::{Arc, Mutex};
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::{Event, Metadata, Subscriber};
+
+    /// Names of created spans and messages of events.
+    #[derive(Clone, Default)]
+    struct Recorder(Arc<Mutex<Vec<String>>>);
+
+    impl Subscriber for Recorder {
+        fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+            metadata.target() == "revm"
+        }
+
+        fn new_span(&self, span: &Attributes<'_>) -> Id {
+            let mut names = self.0.lock().unwrap();
+            names.push(span.metadata().name().to_string());
+            Id::from_u64(names.len() as u64)
+        }
+
+        fn record(&self, _span: &Id, _values: &Record<'_>) {}
+
+        fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+        fn event(&self, event: &Event<'_>) {
+            let mut fields = Vec::new();
+            event.record(
+                &mut |field: &tracing::field::Field, value: &dyn core::fmt::Debug| {
+                    fields.push(format!("{}={:?}", field.name(), value));
+                },
+            );
+            self.0.lock().unwrap().push(fields.join(" "));
+        }
+
+        fn enter(&self, _span: &Id) {}
+
+        fn exit(&self, _span: &Id) {}
+    }
+
+    #[test]
+    fn traces_phases() {
+        let contract = B160([0x10; 20]);
+        // CALL(gas, identity precompile, 0, 0, 0, 0, 0) STOP
+        let code = vec![0x5f, 0x5f, 0x5f, 0x5f, 0x5f, 0x60, 0x04, 0x5a, 0xf1, 0x00];
+        let mut db = InMemoryDB::default();
+        db.insert_account_info(
+            contract,
+            AccountInfo::new(U256::ZERO, 0, Bytecode::new_raw(code.into())),
+        );
+        let mut evm = EVM::new();
+        evm.database(db);
+        evm.env.tx.transact_to = TransactTo::Call(contract);
+
+        let recorder = Recorder::default();
+        tracing::subscriber::with_default(recorder.clone(), || {
+            let out = evm.transact().unwrap();
+            assert!(out.result.is_success());
+            evm.db().unwrap().commit(out.state);
+        });
+        let names = recorder.0.lock().unwrap();
+        let spans: Vec<_> = names.iter().filter(|name| !name.contains('=')).collect();
+        assert_eq!(
+            spans,
+            [
+                "transact",
+                "validate",
+                "execute",
+                "call",
+                "call",
+                "precompile",
+                "finalize",
+                "commit"
+            ]
+        );
+        assert!(names
+            .iter()
+            .any(|event| event.contains("execute done") && event.contains("result=Stop")));
+    }
+}
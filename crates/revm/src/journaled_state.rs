@@ -1,18 +1,24 @@
 use crate::interpreter::{inner_models::SelfDestructResult, InstructionResult};
 use crate::primitives::{
-    db::Database, hash_map::Entry, Account, Bytecode, HashMap, Log, State, StorageSlot, B160,
+    db::Database, hash_map::Entry, Account, AccountChange, AccountInfo, AccountStatus, Bytecode,
+    CreateCollisionPolicy, HashMap, Log, SpecId, State, StorageSlot, TouchedAccounts, B160,
     KECCAK_EMPTY, U256,
 };
 use alloc::{vec, vec::Vec};
 use core::mem::{self};
 use revm_interpreter::primitives::Spec;
-use revm_interpreter::primitives::SpecId::SPURIOUS_DRAGON;
+use revm_precompile::Precompiles;
+
+use crate::evm::to_precompile_id;
 
 #[derive(Debug, Clone, Eq, PartialEq)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct JournaledState {
     /// Current state.
     pub state: State,
+    /// EIP-1153 transient storage, discarded at the end of transaction.
+    #[cfg_attr(feature = "serde", serde(with = "serde_transient_storage"))]
+    pub transient_storage: TransientStorage,
     /// logs
     pub logs: Vec<Log>,
     /// how deep are we in call stack.
@@ -26,6 +32,38 @@ pub struct JournaledState {
     /// It is assumed that precompiles start from 0x1 address and spand next N addresses.
     /// we are using that assumption here
     pub num_of_precompiles: usize,
+    /// Creation at an address that already has code or nonce.
+    pub create_collision: CreateCollisionPolicy,
+    /// Checkpoints of frames that are executing, outermost first.
+    checkpoints: Vec<JournalCheckpoint>,
+}
+
+/// Transient storage of all accounts, slots that are zero are not included.
+pub type TransientStorage = HashMap<(B160, U256), U256>;
+
+/// Transient storage as list of entries, formats like JSON only have keys that are strings.
+#[cfg(feature = "serde")]
+mod serde_transient_storage {
+    use super::TransientStorage;
+    use crate::primitives::{B160, U256};
+    use alloc::vec::Vec;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(storage: &TransientStorage, s: S) -> Result<S::Ok, S::Error> {
+        s.collect_seq(
+            storage
+                .iter()
+                .map(|((address, key), value)| (address, key, value)),
+        )
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<TransientStorage, D::Error> {
+        let entries = Vec::<(B160, U256, U256)>::deserialize(d)?;
+        Ok(entries
+            .into_iter()
+            .map(|(address, key, value)| ((address, key), value))
+            .collect())
+    }
 }
 
 #[derive(Debug, Clone, Eq, PartialEq)]
@@ -68,18 +106,59 @@ pub enum JournalEntry {
         key: U256,
         had_value: Option<U256>, //if none, storage slot was cold loaded from db and needs to be removed
     },
+    /// Account with code or nonce was replaced by created contract, see
+    /// [CreateCollisionPolicy::Overwrite].
+    /// Action: Clear code and nonce of the account
+    /// Revert: Restore code and nonce, and created flag if it wasn't set
+    AccountReplaced {
+        address: B160,
+        had_info: AccountInfo,
+        was_created: bool,
+    },
     /// Code changed
     /// Action: Account code changed
     /// Revert: Revert to previous bytecode.
     CodeChange { address: B160, had_code: Bytecode },
+    /// Transient storage changed, EIP-1153.
+    /// Action: Transient storage changed.
+    /// Revert: Revert to previous value.
+    TransientStorageChange {
+        address: B160,
+        key: U256,
+        had_value: U256,
+    },
 }
 
 /// SubRoutine checkpoint that will help us to go back from this
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct JournalCheckpoint {
     log_i: usize,
     journal_i: usize,
 }
 
+/// Position in the journal that changes made after it are reverted to, including ones of
+/// frames started and finished after it, see [JournaledState::revert_to].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct JournalMark {
+    depth: usize,
+    journal_i: usize,
+    entries: usize,
+    log_i: usize,
+}
+
+impl JournalCheckpoint {
+    /// Number of logs before the checkpoint.
+    pub fn log_index(&self) -> usize {
+        self.log_i
+    }
+
+    /// Index of the first journal set reverted by the checkpoint.
+    pub fn journal_index(&self) -> usize {
+        self.journal_i
+    }
+}
+
 impl JournaledState {
     /// Create new JournaledState.
     ///
@@ -91,11 +170,14 @@ impl JournaledState {
     pub fn new(num_of_precompiles: usize) -> JournaledState {
         Self {
             state: HashMap::new(),
+            transient_storage: TransientStorage::new(),
             logs: Vec::new(),
             journal: vec![vec![]],
             depth: 0,
             is_before_spurious_dragon: false,
             num_of_precompiles,
+            create_collision: CreateCollisionPolicy::Fail,
+            checkpoints: Vec::new(),
         }
     }
 
@@ -132,13 +214,79 @@ impl JournaledState {
     /// do cleanup and return modified state
     pub fn finalize(&mut self) -> (State, Vec<Log>) {
         let state = mem::take(&mut self.state);
+        self.transient_storage.clear();
 
         let logs = mem::take(&mut self.logs);
         self.journal = vec![vec![]];
         self.depth = 0;
+        self.checkpoints.clear();
         (state, logs)
     }
 
+    /// Checkpoints of frames that are executing, outermost first.
+    pub fn checkpoints(&self) -> &[JournalCheckpoint] {
+        &self.checkpoints
+    }
+
+    /// Entries that are reverted if the frame that is executing fails, including entries of
+    /// its finished subcalls.
+    pub fn frame_entries(&self) -> impl Iterator<Item = &JournalEntry> {
+        let start = self.checkpoints.last().map_or(0, |c| c.journal_i);
+        self.journal[start..].iter().flatten()
+    }
+
+    /// Logs that are reverted if the frame that is executing fails.
+    pub fn frame_logs(&self) -> &[Log] {
+        let start = self.checkpoints.last().map_or(0, |c| c.log_i);
+        &self.logs[start..]
+    }
+
+    /// Accounts that are touched and will be part of the changed state.
+    pub fn touched_accounts(&self) -> impl Iterator<Item = &B160> {
+        self.state
+            .iter()
+            .filter(|(_, account)| account.is_touched())
+            .map(|(address, _)| address)
+    }
+
+    /// Change of every touched account made by entries in the journal. Changes that are not
+    /// journaled, as gas payment of the transaction, are not included.
+    pub fn touched_summary(&self) -> TouchedAccounts {
+        let mut summary: TouchedAccounts = self
+            .touched_accounts()
+            .map(|address| (*address, AccountChange::Touched))
+            .collect();
+        let mut mark = |address: B160, change: AccountChange| {
+            let entry = summary.entry(address).or_insert(change);
+            *entry = (*entry).max(change);
+        };
+        for entry in self.journal.iter().flatten() {
+            match *entry {
+                JournalEntry::BalanceTransfer { from, to, balance }
+                    if from != to && balance != U256::ZERO =>
+                {
+                    mark(from, AccountChange::BalanceOnly);
+                    mark(to, AccountChange::BalanceOnly);
+                }
+                JournalEntry::NonceChange { address } => mark(address, AccountChange::BalanceOnly),
+                JournalEntry::CodeChange { address, .. } => {
+                    mark(address, AccountChange::CodeChanged)
+                }
+                _ => (),
+            }
+        }
+        for (address, account) in self.state.iter().filter(|(_, a)| a.is_touched()) {
+            if account.is_selfdestructed() {
+                mark(*address, AccountChange::Destroyed);
+            } else if account.is_newly_created() {
+                mark(*address, AccountChange::Created);
+            } else if account.storage.values().any(|slot| slot.is_changed()) {
+                mark(*address, AccountChange::StorageChanged);
+            }
+        }
+        summary
+    }
+
     /// Use it with load_account function.
     pub fn account(&self, address: B160) -> &Account {
         self.state.get(&address).unwrap() // Always assume that acc is already loaded
@@ -230,7 +378,7 @@ impl JournaledState {
     ///
     /// There are few steps done:
     /// 1. Make created account hot loaded (AccessList) and this should
-    ///     be done before subrouting checkpoint is created.
+    ///    be done before subrouting checkpoint is created.
     /// 2. Check if there is colission of newly created account with existing one.
     /// 3. Mark created account as created.
     /// 4. Add fund to created account
@@ -253,24 +401,32 @@ impl JournaledState {
         let last_journal = self.journal.last_mut().unwrap();
 
         // check if it is possible to create this account.
-        if Self::check_account_collision(address, account, self.num_of_precompiles) {
+        let replaced = Self::check_account_collision(address, account, self.num_of_precompiles);
+        if replaced
+            && (self.create_collision == CreateCollisionPolicy::Fail
+                || is_precompile(address, self.num_of_precompiles))
+        {
             self.checkpoint_revert(checkpoint);
             return Err(InstructionResult::CreateCollision);
         }
 
+        if replaced {
+            Self::replace_account(last_journal, address, account);
+        } else {
+            // Set all storages to default value. They need to be present to act as accessed slots in access list.
+            // it shouldn't be possible for them to have different values then zero as code is not existing for this account,
+            // but because tests can change that assumption we are doing it.
+            let empty = StorageSlot::default();
+            account
+                .storage
+                .iter_mut()
+                .for_each(|(_, slot)| *slot = empty.clone());
+        }
+
         // set account status to created.
         account.mark_created();
         account.info.code = None;
 
-        // Set all storages to default value. They need to be present to act as accessed slots in access list.
-        // it shouldn't be possible for them to have different values then zero as code is not existing for this account,
-        // but because tests can change that assumption we are doing it.
-        let empty = StorageSlot::default();
-        account
-            .storage
-            .iter_mut()
-            .for_each(|(_, slot)| *slot = empty.clone());
-
         // touch account. This is important as for pre SpuriousDragon account could be
         // saved even empty.
         Self::touch_account(last_journal, &address, account);
@@ -283,7 +439,7 @@ impl JournaledState {
         account.info.balance = new_balance;
 
         // EIP-161: State trie clearing (invariant-preserving alternative)
-        if SPEC::enabled(SPURIOUS_DRAGON) {
+        if !self.is_before_spurious_dragon {
             account.info.nonce = 1;
             last_journal.push(JournalEntry::NonceChange { address });
         }
@@ -303,31 +459,40 @@ impl JournaledState {
         Ok(checkpoint)
     }
 
+    /// Clear code, nonce and storage of the account the contract is created over. Storage
+    /// that is not loaded is read as zero because the account is created.
+    fn replace_account(journal: &mut Vec<JournalEntry>, address: B160, account: &mut Account) {
+        journal.push(JournalEntry::AccountReplaced {
+            address,
+            had_info: account.info.clone(),
+            was_created: account.is_newly_created(),
+        });
+        for (key, slot) in account.storage.iter_mut() {
+            if slot.present_value != U256::ZERO {
+                journal.push(JournalEntry::StorageChange {
+                    address,
+                    key: *key,
+                    had_value: Some(slot.present_value),
+                });
+                slot.present_value = U256::ZERO;
+            }
+        }
+        account.info.nonce = 0;
+        account.info.code_hash = KECCAK_EMPTY;
+    }
+
     #[inline(always)]
     pub fn check_account_collision(
         address: B160,
         account: &mut Account,
         num_of_precompiles: usize,
     ) -> bool {
-        // Check collision. Bytecode needs to be empty.
-        if account.info.code_hash != KECCAK_EMPTY {
-            return true;
-        }
-        // Check collision. Nonce is not zero
-        if account.info.nonce != 0 {
-            return true;
-        }
-
-        // Check collision. New account address is precompile.
-        if is_precompile(address, num_of_precompiles) {
-            return true;
-        }
-
-        false
+        is_collision(address, &account.info, num_of_precompiles)
     }
 
     fn journal_revert(
         state: &mut State,
+        transient_storage: &mut TransientStorage,
         journal_entries: Vec<JournalEntry>,
         is_spurious_dragon_enabled: bool,
     ) {
@@ -393,11 +558,35 @@ impl JournaledState {
                         storage.remove(&key);
                     }
                 }
+                JournalEntry::AccountReplaced {
+                    address,
+                    had_info,
+                    was_created,
+                } => {
+                    let acc = state.get_mut(&address).unwrap();
+                    acc.info.nonce = had_info.nonce;
+                    acc.info.code_hash = had_info.code_hash;
+                    acc.info.code = had_info.code;
+                    if !was_created {
+                        acc.status.remove(AccountStatus::Created);
+                    }
+                }
                 JournalEntry::CodeChange { address, had_code } => {
                     let acc = state.get_mut(&address).unwrap();
                     acc.info.code_hash = had_code.hash();
                     acc.info.code = Some(had_code);
                 }
+                JournalEntry::TransientStorageChange {
+                    address,
+                    key,
+                    had_value,
+                } => {
+                    if had_value == U256::ZERO {
+                        transient_storage.remove(&(address, key));
+                    } else {
+                        transient_storage.insert((address, key), had_value);
+                    }
+                }
             }
         }
     }
@@ -409,39 +598,125 @@ impl JournaledState {
         };
         self.depth += 1;
         self.journal.push(Default::default());
+        self.checkpoints.push(checkpoint);
         checkpoint
     }
 
     pub fn checkpoint_commit(&mut self) {
         self.depth -= 1;
+        self.checkpoints.pop();
     }
 
     pub fn checkpoint_revert(&mut self, checkpoint: JournalCheckpoint) {
         let is_spurious_dragon_enabled = !self.is_before_spurious_dragon;
         let state = &mut self.state;
+        let transient_storage = &mut self.transient_storage;
         self.depth -= 1;
+        self.checkpoints.pop();
         // iterate over last N journals sets and revert our global state
         let leng = self.journal.len();
         self.journal
             .iter_mut()
             .rev()
             .take(leng - checkpoint.journal_i)
-            .for_each(|cs| Self::journal_revert(state, mem::take(cs), is_spurious_dragon_enabled));
+            .for_each(|cs| {
+                Self::journal_revert(
+                    state,
+                    transient_storage,
+                    mem::take(cs),
+                    is_spurious_dragon_enabled,
+                )
+            });
 
         self.logs.truncate(checkpoint.log_i);
         self.journal.truncate(checkpoint.journal_i);
     }
 
+    /// Mark current position of the journal.
+    pub(crate) fn mark(&self) -> JournalMark {
+        JournalMark {
+            depth: self.depth,
+            journal_i: self.journal.len(),
+            entries: self.journal.last().map_or(0, Vec::len),
+            log_i: self.logs.len(),
+        }
+    }
+
+    /// Revert all changes made after `mark`, checkpoints made after it are reverted too.
+    pub(crate) fn revert_to(&mut self, mark: JournalMark) {
+        debug_assert!(
+            self.depth >= mark.depth,
+            "checkpoint of the mark is finished"
+        );
+        while self.depth > mark.depth {
+            let checkpoint = *self.checkpoints.last().unwrap();
+            self.checkpoint_revert(checkpoint);
+        }
+        let is_spurious_dragon_enabled = !self.is_before_spurious_dragon;
+        // sets of checkpoints committed after the mark, then entries of the marked set.
+        for entries in self.journal.drain(mark.journal_i..).rev() {
+            Self::journal_revert(
+                &mut self.state,
+                &mut self.transient_storage,
+                entries,
+                is_spurious_dragon_enabled,
+            );
+        }
+        if let Some(last) = self.journal.last_mut() {
+            let entries = last.split_off(mark.entries);
+            Self::journal_revert(
+                &mut self.state,
+                &mut self.transient_storage,
+                entries,
+                is_spurious_dragon_enabled,
+            );
+        }
+        self.logs.truncate(mark.log_i);
+    }
+
     /// transfer balance from address to target. Check if target exist/is_cold
-    pub fn selfdestruct<DB: Database>(
+    ///
+    /// After Cancun (EIP-6780) account is destroyed only if it was created in the same
+    /// transaction, otherwise only its balance is transferred to the target.
+    pub fn selfdestruct<SPEC: Spec, DB: Database>(
         &mut self,
         address: B160,
         target: B160,
         db: &mut DB,
     ) -> Result<SelfDestructResult, DB::Error> {
         let (is_cold, target_exists) = self.load_account_exist(target, db)?;
-        // transfer all the balance
         let acc = self.state.get_mut(&address).unwrap();
+        if SPEC::enabled(SpecId::CANCUN) && !acc.is_newly_created() {
+            let previously_destroyed = acc.is_selfdestructed();
+            // balance stays with the account if it is the target.
+            let balance = if address != target {
+                mem::take(&mut acc.info.balance)
+            } else {
+                U256::ZERO
+            };
+            // target is touched even without balance, so it is removed if it is empty.
+            let target_account = self.state.get_mut(&target).unwrap();
+            Self::touch_account(self.journal.last_mut().unwrap(), &target, target_account);
+            if balance != U256::ZERO {
+                target_account.info.balance += balance;
+                self.journal
+                    .last_mut()
+                    .unwrap()
+                    .push(JournalEntry::BalanceTransfer {
+                        from: address,
+                        to: target,
+                        balance,
+                    });
+            }
+            return Ok(SelfDestructResult {
+                had_value: balance != U256::ZERO,
+                is_cold,
+                target_exists,
+                previously_destroyed,
+            });
+        }
+
+        // transfer all the balance
         let balance = mem::take(&mut acc.info.balance);
         let previously_destroyed = acc.is_selfdestructed();
         acc.mark_selfdestruct();
@@ -502,7 +777,12 @@ impl JournaledState {
         match self.state.entry(address) {
             Entry::Occupied(entry) => {
                 let account = entry.into_mut();
-
+                // account can be listed more than once, as coinbase or in access list.
+                for slot in slots {
+                    if let Entry::Vacant(vac) = account.storage.entry(*slot) {
+                        vac.insert(StorageSlot::new(db.storage(address, *slot)?));
+                    }
+                }
                 Ok(account)
             }
             Entry::Vacant(vac) => {
@@ -657,6 +937,35 @@ impl JournaledState {
         Ok((slot.original_value, present, new, is_cold))
     }
 
+    /// Read transient storage slot, EIP-1153.
+    pub fn tload(&self, address: B160, key: U256) -> U256 {
+        self.transient_storage
+            .get(&(address, key))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Write transient storage slot, EIP-1153. Change is journaled so it is reverted together
+    /// with the frame.
+    pub fn tstore(&mut self, address: B160, key: U256, new: U256) {
+        let had_value = if new == U256::ZERO {
+            self.transient_storage.remove(&(address, key))
+        } else {
+            self.transient_storage.insert((address, key), new)
+        }
+        .unwrap_or_default();
+        if had_value != new {
+            self.journal
+                .last_mut()
+                .unwrap()
+                .push(JournalEntry::TransientStorageChange {
+                    address,
+                    key,
+                    had_value,
+                });
+        }
+    }
+
     /// push log into subroutine
     pub fn log(&mut self, log: Log) {
         self.logs.push(log);
@@ -674,6 +983,35 @@ pub fn is_precompile(address: B160, num_of_precompiles: usize) -> bool {
     num.wrapping_sub(1) < num_of_precompiles as u16
 }
 
+fn is_collision(address: B160, info: &AccountInfo, num_of_precompiles: usize) -> bool {
+    // Check collision. Bytecode needs to be empty.
+    if info.code_hash != KECCAK_EMPTY {
+        return true;
+    }
+    // Check collision. Nonce is not zero
+    if info.nonce != 0 {
+        return true;
+    }
+
+    // Check collision. New account address is precompile.
+    is_precompile(address, num_of_precompiles)
+}
+
+/// Check if contract can't be created at `address` in database state, same as EVM checks it
+/// on `CREATE`, `CREATE2` and create transaction.
+///
+/// Account collides if it has code, nonce, or is precompile of `spec_id`. Storage is not
+/// checked by EVM.
+pub fn is_create_collision<DB: Database>(
+    db: &mut DB,
+    address: B160,
+    spec_id: SpecId,
+) -> Result<bool, DB::Error> {
+    let num_of_precompiles = Precompiles::new(to_precompile_id(spec_id)).len();
+    let info = db.basic(address)?.unwrap_or_default();
+    Ok(is_collision(address, &info, num_of_precompiles))
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -720,4 +1058,102 @@ mod test {
             "0x000..3 is precompile"
         );
     }
+
+    #[test]
+    fn frame_entries() {
+        let address = B160([0x10; 20]);
+        let mut db = crate::InMemoryDB::default();
+        let mut journal = JournaledState::new(0);
+        journal.load_account(address, &mut db).unwrap();
+
+        let outer = journal.checkpoint();
+        journal.touch(&address);
+        let inner = journal.checkpoint();
+        assert_eq!(journal.checkpoints(), [outer, inner]);
+        assert_eq!(journal.frame_entries().count(), 0);
+        journal.inc_nonce(address);
+        journal.checkpoint_commit();
+
+        // committed subcall is reverted together with the frame.
+        assert_eq!(
+            journal.frame_entries().collect::<Vec<_>>(),
+            [
+                &JournalEntry::AccountTouched { address },
+                &JournalEntry::NonceChange { address }
+            ]
+        );
+        assert_eq!(journal.touched_accounts().collect::<Vec<_>>(), [&address]);
+        journal.checkpoint_revert(outer);
+        assert!(journal.checkpoints().is_empty());
+        assert_eq!(journal.touched_accounts().count(), 0);
+    }
+
+    #[test]
+    fn revert_to_mark() {
+        let address = B160([0x10; 20]);
+        let loaded = B160([0x20; 20]);
+        let mut db = crate::InMemoryDB::default();
+        let mut journal = JournaledState::new(0);
+        journal.load_account(address, &mut db).unwrap();
+        let outer = journal.checkpoint();
+        journal.touch(&address);
+        let before = journal.clone();
+
+        let mark = journal.mark();
+        journal.load_account(loaded, &mut db).unwrap();
+        journal.checkpoint();
+        journal.inc_nonce(address);
+        journal.checkpoint_commit();
+        journal.checkpoint();
+        journal.tstore(address, U256::from(1), U256::from(2));
+        journal.revert_to(mark);
+        assert_eq!(journal, before);
+
+        // checkpoint made before the mark still reverts the frame.
+        journal.checkpoint_revert(outer);
+        assert_eq!(journal.touched_accounts().count(), 0);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_roundtrip() {
+        let address = B160([0x10; 20]);
+        let mut db = crate::InMemoryDB::default();
+        let mut journal = JournaledState::new(0);
+        journal.load_account(address, &mut db).unwrap();
+        journal.checkpoint();
+        journal.tstore(address, U256::from(1), U256::from(2));
+        journal
+            .sstore(address, U256::from(3), U256::from(4), &mut db)
+            .unwrap();
+
+        let json = serde_json::to_string(&journal).unwrap();
+        assert_eq!(
+            serde_json::from_str::<JournaledState>(&json).unwrap(),
+            journal
+        );
+    }
+
+    #[test]
+    fn create_collision() {
+        let mut db = crate::InMemoryDB::default();
+        let deployer = B160([0x10; 20]);
+        let fresh = B160([0x20; 20]);
+        db.insert_account_info(deployer, AccountInfo::from_balance(U256::from(1)));
+        db.insert_account_info(
+            fresh,
+            AccountInfo {
+                nonce: 1,
+                ..Default::default()
+            },
+        );
+        let spec = SpecId::LATEST;
+        assert!(!is_create_collision(&mut db, deployer, spec).unwrap());
+        assert!(is_create_collision(&mut db, fresh, spec).unwrap());
+        assert!(!is_create_collision(&mut db, B160([0x30; 20]), spec).unwrap());
+        assert!(is_create_collision(&mut db, B160::from_low_u64_be(9), spec).unwrap());
+        assert!(
+            !is_create_collision(&mut db, B160::from_low_u64_be(9), SpecId::BYZANTIUM).unwrap()
+        );
+    }
 }
@@ -1,10 +1,36 @@
 #![cfg_attr(not(feature = "std"), no_std)]
 
+#[cfg(feature = "async")]
+mod async_evm;
+#[cfg(feature = "bench")]
+#[doc(hidden)]
+pub mod bench;
+pub mod block_executor;
 pub mod db;
+pub mod differential;
+pub mod erc4337;
+pub mod estimate_gas;
 mod evm;
 mod evm_impl;
+mod frame;
+pub mod handler;
 mod inspector;
+mod instrument;
 mod journaled_state;
+#[cfg(feature = "optimism")]
+pub mod optimism;
+#[cfg(feature = "parallel")]
+pub mod parallel;
+pub mod receipt;
+pub mod replay;
+pub mod sandbox;
+pub mod simulate;
+#[cfg(feature = "std")]
+pub mod simulation;
+pub mod stepper;
+pub mod system_call;
+#[cfg(feature = "rlp")]
+pub mod transaction;
 
 #[cfg(all(feature = "with-serde", not(feature = "serde")))]
 compile_error!("`with-serde` feature has been renamed to `serde`.");
@@ -12,10 +38,13 @@ compile_error!("`with-serde` feature has been renamed to `serde`.");
 pub(crate) const USE_GAS: bool = !cfg!(feature = "no_gas_measuring");
 pub type DummyStateDB = InMemoryDB;
 
+#[cfg(feature = "async")]
+pub use async_evm::{AsyncEvm, Read, Unfetched};
 pub use db::{Database, DatabaseCommit, InMemoryDB};
-pub use evm::{evm_inner, new, EVM};
+pub use estimate_gas::estimate_gas;
+pub use evm::{evm_inner, evm_inner_with_table, new, EVM};
 pub use evm_impl::EVMData;
-pub use journaled_state::{JournalEntry, JournaledState};
+pub use journaled_state::{is_create_collision, JournalEntry, JournaledState, TransientStorage};
 
 extern crate alloc;
 
@@ -30,4 +59,4 @@ pub use revm_interpreter::primitives;
 
 /// Reexport Inspector implementations
 pub use inspector::inspectors;
-pub use inspector::Inspector;
+pub use inspector::{Inspector, StorageWrite};
@@ -0,0 +1,201 @@
+//! OP stack execution, enabled with [CfgEnv::optimism](crate::primitives::CfgEnv::optimism).
+//!
+//! Deposit transactions get their mint before execution and skip nonce and balance checks.
+//! Other transactions pay the L1 data fee, computed from the L1 block info contract, it is
+//! sent to the L1 fee vault and the base fee to the base fee vault.
+use crate::primitives::{hex_literal::hex, B160, U256};
+use crate::Database;
+
+/// Predeploy with the L1 block attributes, set by the first deposit of every block.
+pub const L1_BLOCK_CONTRACT: B160 = B160(hex!("4200000000000000000000000000000000000015"));
+/// Vault receiving the L1 data fees.
+pub const L1_FEE_RECIPIENT: B160 = B160(hex!("420000000000000000000000000000000000001A"));
+/// Vault receiving the base fees, which are not burned.
+pub const BASE_FEE_RECIPIENT: B160 = B160(hex!("4200000000000000000000000000000000000019"));
+
+const L1_BASE_FEE_SLOT: U256 = U256::from_limbs([1, 0, 0, 0]);
+const L1_OVERHEAD_SLOT: U256 = U256::from_limbs([5, 0, 0, 0]);
+const L1_SCALAR_SLOT: U256 = U256::from_limbs([6, 0, 0, 0]);
+
+/// First byte of encoded deposit transactions.
+const DEPOSIT_TX_TYPE: u8 = 0x7e;
+
+/// L1 fee parameters of the block.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct L1BlockInfo {
+    pub l1_base_fee: U256,
+    pub l1_fee_overhead: U256,
+    /// Scalar with six decimals.
+    pub l1_fee_scalar: U256,
+}
+
+impl L1BlockInfo {
+    /// Read the parameters from the [L1_BLOCK_CONTRACT] storage.
+    pub fn try_fetch<DB: Database>(db: &mut DB) -> Result<Self, DB::Error> {
+        Ok(Self {
+            l1_base_fee: db.storage(L1_BLOCK_CONTRACT, L1_BASE_FEE_SLOT)?,
+            l1_fee_overhead: db.storage(L1_BLOCK_CONTRACT, L1_OVERHEAD_SLOT)?,
+            l1_fee_scalar: db.storage(L1_BLOCK_CONTRACT, L1_SCALAR_SLOT)?,
+        })
+    }
+
+    /// Gas the encoded transaction costs as L1 calldata.
+    pub fn data_gas(enveloped_tx: &[u8]) -> U256 {
+        let zeros = enveloped_tx.iter().filter(|byte| **byte == 0).count() as u64;
+        let non_zeros = enveloped_tx.len() as u64 - zeros;
+        U256::from(zeros * 4 + non_zeros * 16)
+    }
+
+    /// L1 data fee of the encoded transaction, zero for deposits.
+    pub fn calculate_tx_l1_cost(&self, enveloped_tx: &[u8]) -> U256 {
+        if matches!(enveloped_tx.first(), None | Some(&DEPOSIT_TX_TYPE)) {
+            return U256::ZERO;
+        }
+        (Self::data_gas(enveloped_tx) + self.l1_fee_overhead)
+            .saturating_mul(self.l1_base_fee)
+            .saturating_mul(self.l1_fee_scalar)
+            / U256::from(1_000_000)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{CacheDB, DatabaseRef};
+    use crate::primitives::{
+        AccountInfo, Bytecode, EVMError, InvalidTransaction, TransactTo, B256,
+    };
+    use crate::InMemoryDB;
+
+    const CALLER: B160 = B160([0x10; 20]);
+    const RECEIVER: B160 = B160([0x20; 20]);
+
+    fn evm() -> crate::EVM<InMemoryDB> {
+        let mut db = InMemoryDB::default();
+        db.insert_account_info(CALLER, AccountInfo::from_balance(U256::from(10_000_000)));
+        for (slot, value) in [(1, 100), (5, 188), (6, 1_000_000)] {
+            db.insert_account_storage(L1_BLOCK_CONTRACT, U256::from(slot), U256::from(value))
+                .unwrap();
+        }
+        let mut evm = crate::new();
+        evm.database(db);
+        evm.env.cfg.optimism = true;
+        evm.env.block.basefee = U256::from(2);
+        evm.env.tx.caller = CALLER;
+        evm.env.tx.transact_to = TransactTo::Call(RECEIVER);
+        evm.env.tx.gas_limit = 100_000;
+        evm.env.tx.gas_price = U256::from(3);
+        evm
+    }
+
+    #[test]
+    fn l1_cost() {
+        let info = L1BlockInfo {
+            l1_base_fee: U256::from(1_000),
+            l1_fee_overhead: U256::from(188),
+            l1_fee_scalar: U256::from(684_000),
+        };
+        assert_eq!(L1BlockInfo::data_gas(&[0, 1, 0, 2]), U256::from(40));
+        // (40 + 188) * 1000 * 0.684
+        assert_eq!(
+            info.calculate_tx_l1_cost(&[0, 1, 0, 2]),
+            U256::from(155_952)
+        );
+        assert_eq!(info.calculate_tx_l1_cost(&[DEPOSIT_TX_TYPE, 1]), U256::ZERO);
+        assert_eq!(info.calculate_tx_l1_cost(&[]), U256::ZERO);
+    }
+
+    #[test]
+    #[cfg(not(feature = "no_gas_measuring"))]
+    fn charges_l1_fee() {
+        let mut evm = evm();
+        evm.env.tx.optimism.enveloped_tx = Some(vec![1; 10].into());
+        let out = evm.transact().unwrap();
+        assert_eq!(out.result.gas_used(), 21_000);
+        // (10 * 16 + 188) * 100
+        let l1_cost = U256::from(34_800);
+        let balance = |address| out.state[&address].info.balance;
+        assert_eq!(
+            balance(CALLER),
+            U256::from(10_000_000 - 21_000 * 3) - l1_cost
+        );
+        assert_eq!(balance(L1_FEE_RECIPIENT), l1_cost);
+        assert_eq!(balance(BASE_FEE_RECIPIENT), U256::from(21_000 * 2));
+        assert_eq!(balance(B160::zero()), U256::from(21_000));
+    }
+
+    #[test]
+    fn lack_of_funds_for_l1_fee() {
+        let mut evm = evm();
+        evm.env.tx.optimism.enveloped_tx = Some(vec![1; 10].into());
+        // covers the gas limit, but not the L1 fee on top of it.
+        let balance = U256::from(100_000 * 3 + 34_800 - 1);
+        evm.db()
+            .unwrap()
+            .insert_account_info(CALLER, AccountInfo::from_balance(balance));
+        assert_eq!(
+            evm.transact().unwrap_err(),
+            EVMError::Transaction(InvalidTransaction::LackOfFundForMaxFee {
+                fee: balance + U256::from(1),
+                balance,
+            })
+        );
+    }
+
+    #[test]
+    #[cfg(not(feature = "no_gas_measuring"))]
+    fn vault_database_error() {
+        struct FailingVault;
+        impl DatabaseRef for FailingVault {
+            type Error = B160;
+            fn basic(&self, address: B160) -> Result<Option<AccountInfo>, B160> {
+                if address == L1_FEE_RECIPIENT {
+                    return Err(address);
+                }
+                Ok(None)
+            }
+            fn code_by_hash(&self, _code_hash: B256) -> Result<Bytecode, B160> {
+                Ok(Bytecode::new())
+            }
+            fn storage(&self, _address: B160, _index: U256) -> Result<U256, B160> {
+                Ok(U256::ZERO)
+            }
+            fn block_hash(&self, _number: U256) -> Result<B256, B160> {
+                Ok(B256::zero())
+            }
+        }
+
+        let mut db = CacheDB::new(FailingVault);
+        db.insert_account_info(CALLER, AccountInfo::from_balance(U256::from(10_000_000)));
+        let mut evm = crate::new();
+        evm.database(db);
+        evm.env.cfg.optimism = true;
+        evm.env.tx.caller = CALLER;
+        evm.env.tx.transact_to = TransactTo::Call(RECEIVER);
+        evm.env.tx.gas_limit = 100_000;
+        evm.env.tx.optimism.enveloped_tx = Some(vec![1; 10].into());
+        assert_eq!(
+            evm.transact().unwrap_err(),
+            EVMError::Database(L1_FEE_RECIPIENT)
+        );
+    }
+
+    #[test]
+    fn deposit() {
+        let mut evm = evm();
+        evm.env.tx.caller = B160([0x30; 20]);
+        evm.env.tx.gas_price = U256::ZERO;
+        evm.env.tx.value = U256::from(40);
+        evm.env.tx.nonce = Some(5);
+        evm.env.tx.optimism.source_hash = Some([1; 32].into());
+        evm.env.tx.optimism.mint = Some(100);
+        let out = evm.transact().unwrap();
+        assert!(out.result.is_success());
+        assert_eq!(out.state[&B160([0x30; 20])].info.balance, U256::from(60));
+        assert_eq!(out.state[&RECEIVER].info.balance, U256::from(40));
+
+        // same transaction is rejected if it is not a deposit.
+        evm.env.tx.optimism.source_hash = None;
+        assert!(evm.transact().is_err());
+    }
+}
@@ -0,0 +1,339 @@
+//! Parallel execution of the transactions of a block, in the style of Block-STM.
+//!
+//! Transactions are executed optimistically in rounds. Every transaction reads from a
+//! multi-version memory holding the writes of all transactions before it, falling back to
+//! the base database, and records what it read. After a round, writes are published and
+//! reads of every transaction that is not yet final are validated in block order, the ones
+//! that read a value that changed since are executed again in the next round.
+//!
+//! The lowest invalid transaction only depends on final transactions when it is executed
+//! again, so every round makes at least one more transaction final and results are always
+//! the same as of sequential execution.
+//!
+//! Fees paid to the block beneficiary are a write like any other, so transactions paying
+//! nonzero fees depend on each other through the beneficiary balance.
+use crate::db::multi_version::Writes;
+use crate::db::{DatabaseRef, MultiVersionState, Versioned};
+use crate::evm::evm_inner;
+use crate::inspectors::NoOpInspector;
+use crate::primitives::{
+    AccountInfo, Bytecode, EVMError, EVMResult, Env, HashMap, B160, B256, U256,
+};
+use crate::Database;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::vec::Vec;
+
+/// Executes transactions of a block in parallel over shared base state.
+#[derive(Clone, Debug)]
+pub struct ParallelExecutor {
+    threads: usize,
+}
+
+impl Default for ParallelExecutor {
+    /// One thread per available core.
+    fn default() -> Self {
+        Self::new(thread::available_parallelism().map_or(1, |n| n.get()))
+    }
+}
+
+impl ParallelExecutor {
+    /// # Panics
+    ///
+    /// If `threads` is zero.
+    pub fn new(threads: usize) -> Self {
+        assert!(threads > 0, "parallel executor needs at least one thread");
+        Self { threads }
+    }
+
+    pub fn threads(&self) -> usize {
+        self.threads
+    }
+
+    /// Execute transactions of `envs` one after another over `db`.
+    ///
+    /// Every result has state changed by its transaction only, same as if transactions were
+    /// executed sequentially and each one committed before the next one. Database errors
+    /// stop the execution.
+    pub fn execute<DB>(&self, db: &DB, envs: &[Env]) -> Result<ParallelOutput<DB::Error>, DB::Error>
+    where
+        DB: DatabaseRef + Sync,
+        DB::Error: Send,
+    {
+        let mut memory = MultiVersionState::new();
+        let mut done: Vec<Option<Execution<DB::Error>>> = envs.iter().map(|_| None).collect();
+        let mut pending: Vec<usize> = (0..envs.len()).collect();
+        let mut first_not_final = 0;
+        let mut rounds = 0;
+        let mut executions = 0;
+
+        while !pending.is_empty() {
+            rounds += 1;
+            executions += pending.len();
+            for (index, execution) in self.execute_round(db, envs, &memory, &pending) {
+                let mut execution = execution?;
+                memory.publish(index, core::mem::take(&mut execution.writes));
+                done[index] = Some(execution);
+            }
+
+            pending.clear();
+            for (index, execution) in done.iter().enumerate().skip(first_not_final) {
+                let execution = execution.as_ref().expect("every transaction was executed");
+                if !validate(&memory, index, &execution.reads) {
+                    pending.push(index);
+                }
+            }
+            first_not_final = pending.first().copied().unwrap_or(envs.len());
+        }
+
+        Ok(ParallelOutput {
+            results: done
+                .into_iter()
+                .map(|execution| execution.expect("every transaction is final").result)
+                .collect(),
+            rounds,
+            executions,
+        })
+    }
+
+    /// Execute transactions at `indices` reading from `memory`, it is not changed meanwhile.
+    fn execute_round<DB>(
+        &self,
+        db: &DB,
+        envs: &[Env],
+        memory: &MultiVersionState,
+        indices: &[usize],
+    ) -> Vec<(usize, ExecutionOutcome<DB::Error>)>
+    where
+        DB: DatabaseRef + Sync,
+        DB::Error: Send,
+    {
+        let next = AtomicUsize::new(0);
+        let out = Mutex::new(Vec::with_capacity(indices.len()));
+        thread::scope(|scope| {
+            for _ in 0..self.threads.min(indices.len()) {
+                scope.spawn(|| loop {
+                    let Some(&index) = indices.get(next.fetch_add(1, Ordering::Relaxed)) else {
+                        return;
+                    };
+                    let execution = execute_tx(db, &envs[index], memory, index);
+                    out.lock().unwrap().push((index, execution));
+                });
+            }
+        });
+        let mut out = out.into_inner().unwrap();
+        // report the database error of the first failing transaction.
+        out.sort_unstable_by_key(|(index, _)| *index);
+        out
+    }
+}
+
+/// Results of [ParallelExecutor::execute].
+#[derive(Debug)]
+pub struct ParallelOutput<E> {
+    /// Results in block order. There are no [EVMError::Database] errors.
+    pub results: Vec<EVMResult<E>>,
+    /// Number of rounds it took, one if there were no conflicts.
+    pub rounds: usize,
+    /// Number of executed transactions, re-executions included.
+    pub executions: usize,
+}
+
+#[derive(Clone, Debug, Default)]
+struct Reads {
+    accounts: HashMap<B160, Versioned<Option<AccountInfo>>>,
+    storage: HashMap<(B160, U256), Versioned<U256>>,
+}
+
+type ExecutionOutcome<E> = Result<Execution<E>, E>;
+
+struct Execution<E> {
+    result: EVMResult<E>,
+    reads: Reads,
+    writes: Writes,
+}
+
+/// If everything the transaction at `index` read is still the same.
+fn validate(memory: &MultiVersionState, index: usize, reads: &Reads) -> bool {
+    reads
+        .accounts
+        .iter()
+        .all(|(address, read)| memory.account(index, *address) == *read)
+        && reads
+            .storage
+            .iter()
+            .all(|((address, slot), read)| memory.storage(index, *address, *slot) == *read)
+}
+
+/// Database of a single transaction, reading from [MultiVersionState] and the base
+/// database.
+struct MvView<'a, DB> {
+    db: &'a DB,
+    memory: &'a MultiVersionState,
+    index: usize,
+    reads: Reads,
+    /// Accounts as they were returned.
+    accounts: HashMap<B160, Option<AccountInfo>>,
+}
+
+impl<DB: DatabaseRef> Database for MvView<'_, DB> {
+    type Error = DB::Error;
+
+    fn basic(&mut self, address: B160) -> Result<Option<AccountInfo>, Self::Error> {
+        let read = self.memory.account(self.index, address);
+        let info = match &read {
+            Versioned::Written(info) => info.clone(),
+            Versioned::Base => self.db.basic(address)?,
+        };
+        self.reads.accounts.insert(address, read);
+        self.accounts.insert(address, info.clone());
+        Ok(info)
+    }
+
+    fn code_by_hash(&mut self, code_hash: B256) -> Result<Bytecode, Self::Error> {
+        match self.memory.code(&code_hash) {
+            Some(code) => Ok(code.clone()),
+            None => self.db.code_by_hash(code_hash),
+        }
+    }
+
+    fn storage(&mut self, address: B160, index: U256) -> Result<U256, Self::Error> {
+        let read = self.memory.storage(self.index, address, index);
+        let value = match read {
+            Versioned::Written(value) => value,
+            Versioned::Base => self.db.storage(address, index)?,
+        };
+        self.reads.storage.insert((address, index), read);
+        Ok(value)
+    }
+
+    fn block_hash(&mut self, number: U256) -> Result<B256, Self::Error> {
+        self.db.block_hash(number)
+    }
+}
+
+fn execute_tx<DB: DatabaseRef>(
+    db: &DB,
+    env: &Env,
+    memory: &MultiVersionState,
+    index: usize,
+) -> Result<Execution<DB::Error>, DB::Error> {
+    let mut env = env.clone();
+    let mut view = MvView {
+        db,
+        memory,
+        index,
+        reads: Reads::default(),
+        accounts: HashMap::new(),
+    };
+    let result = evm_inner::<_, false>(&mut env, &mut view, &mut NoOpInspector {}).transact();
+    let writes = match &result {
+        Ok(out) => Writes::new(&out.state, &view.accounts),
+        // rejected transactions don't change anything, they could be valid once their
+        // reads are.
+        Err(
+            EVMError::Transaction(_) | EVMError::PrevrandaoNotSet | EVMError::ExcessBlobGasNotSet,
+        ) => Writes::default(),
+        Err(EVMError::Database(_)) => match result {
+            Err(EVMError::Database(e)) => return Err(e),
+            _ => unreachable!(),
+        },
+    };
+    Ok(Execution {
+        result,
+        reads: view.reads,
+        writes,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primitives::{hex_literal::hex, ResultAndState, TransactTo};
+    use crate::{DatabaseCommit, InMemoryDB, EVM};
+
+    const COUNTER: B160 = B160([0x10; 20]);
+
+    fn db() -> InMemoryDB {
+        let mut db = InMemoryDB::default();
+        // beneficiary exists and gets no fees, so it is not changed.
+        db.insert_account_info(B160::zero(), AccountInfo::from_balance(U256::from(1)));
+        // SSTORE(0, SLOAD(0) + 1)
+        db.insert_account_info(
+            COUNTER,
+            AccountInfo::new(
+                U256::ZERO,
+                0,
+                Bytecode::new_raw(hex!("600054600101600055").to_vec().into()),
+            ),
+        );
+        db
+    }
+
+    fn env(caller: u8, to: B160) -> Env {
+        let mut env = Env::default();
+        env.tx.caller = B160([caller; 20]);
+        env.tx.transact_to = TransactTo::Call(to);
+        env
+    }
+
+    fn sequential(db: &InMemoryDB, envs: &[Env]) -> Vec<ResultAndState> {
+        let mut evm = EVM::new();
+        evm.database(db.clone());
+        envs.iter()
+            .map(|env| {
+                evm.env = env.clone();
+                let out = evm.transact().unwrap();
+                evm.db().unwrap().commit(out.state.clone());
+                out
+            })
+            .collect()
+    }
+
+    #[test]
+    fn independent_transactions() {
+        let db = db();
+        // plain transfers of different callers.
+        let envs: Vec<_> = (1..=8).map(|i| env(i, B160([0x80 + i; 20]))).collect();
+        let out = ParallelExecutor::new(4).execute(&db, &envs).unwrap();
+        assert_eq!(out.rounds, 1);
+        assert_eq!(out.executions, envs.len());
+        let expected = sequential(&db, &envs);
+        for (result, expected) in out.results.into_iter().zip(expected) {
+            assert_eq!(result.unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn conflicting_transactions() {
+        let db = db();
+        // every transaction increments the counter, and the last caller sends twice.
+        let mut envs: Vec<_> = (1..=6).map(|i| env(i, COUNTER)).collect();
+        let mut env = env(6, COUNTER);
+        env.tx.nonce = Some(1);
+        envs.push(env);
+        let out = ParallelExecutor::new(4).execute(&db, &envs).unwrap();
+        assert!(out.rounds > 1);
+        assert!(out.executions > envs.len());
+        let expected = sequential(&db, &envs);
+        for (result, expected) in out.results.into_iter().zip(expected) {
+            assert_eq!(result.unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn invalid_transaction() {
+        let db = db();
+        // nonce of the second transaction is stale once the first one is executed.
+        let mut envs = vec![env(1, COUNTER), env(1, COUNTER), env(2, COUNTER)];
+        envs[1].tx.nonce = Some(0);
+        let out = ParallelExecutor::new(2).execute(&db, &envs).unwrap();
+        assert!(matches!(out.results[1], Err(EVMError::Transaction(_))));
+        let last = out.results[2].as_ref().unwrap();
+        assert_eq!(
+            last.state[&COUNTER].storage[&U256::ZERO].present_value(),
+            U256::from(2)
+        );
+    }
+}
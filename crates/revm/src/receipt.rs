@@ -0,0 +1,112 @@
+//! Receipts of executed transactions, as they are put in the receipts trie of a block.
+//!
+//! [BlockReceipts] keeps the gas used by the block so far, so results of the transactions
+//! can be pushed in the order they were executed.
+use crate::primitives::{Bloom, ExecutionResult, Log};
+use alloc::vec::Vec;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+pub struct Receipt {
+    /// If transaction succeeded, reverted and halted transactions fail.
+    pub success: bool,
+    /// Gas used by this and the previous transactions of the block.
+    pub cumulative_gas_used: u64,
+    /// Logs of the transaction, failed transactions have none.
+    pub logs: Vec<Log>,
+    pub logs_bloom: Bloom,
+}
+
+impl Receipt {
+    /// Receipt of `result`, `gas_used_before` is gas used by the previous transactions.
+    pub fn new(result: &ExecutionResult, gas_used_before: u64) -> Self {
+        let logs = result.logs();
+        Self {
+            success: result.is_success(),
+            cumulative_gas_used: gas_used_before + result.gas_used(),
+            logs_bloom: Bloom::from_logs(&logs),
+            logs,
+        }
+    }
+}
+
+/// Receipts of the transactions of a block, in order.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BlockReceipts {
+    receipts: Vec<Receipt>,
+}
+
+impl BlockReceipts {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add receipt of the next transaction.
+    pub fn push(&mut self, result: &ExecutionResult) -> &Receipt {
+        let receipt = Receipt::new(result, self.cumulative_gas_used());
+        self.receipts.push(receipt);
+        self.receipts.last().unwrap()
+    }
+
+    /// Gas used by the transactions so far.
+    pub fn cumulative_gas_used(&self) -> u64 {
+        self.receipts
+            .last()
+            .map_or(0, |receipt| receipt.cumulative_gas_used)
+    }
+
+    /// Bloom of the block header, of all logs of the block.
+    pub fn logs_bloom(&self) -> Bloom {
+        let mut bloom = Bloom::zero();
+        for receipt in &self.receipts {
+            bloom.accrue_bloom(&receipt.logs_bloom);
+        }
+        bloom
+    }
+
+    pub fn receipts(&self) -> &[Receipt] {
+        &self.receipts
+    }
+
+    pub fn into_receipts(self) -> Vec<Receipt> {
+        self.receipts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primitives::{Bytes, Eval, Output, B160, B256};
+
+    #[test]
+    fn cumulative_receipts() {
+        let log = Log {
+            address: B160([1; 20]),
+            topics: vec![B256([2; 32])],
+            data: Bytes::new(),
+        };
+        let success = ExecutionResult::Success {
+            reason: Eval::Stop,
+            gas_used: 30_000,
+            gas_refunded: 0,
+            logs: vec![log.clone()],
+            output: Output::Call(Bytes::new()),
+        };
+        let revert = ExecutionResult::Revert {
+            gas_used: 25_000,
+            output: Bytes::new(),
+        };
+
+        let mut receipts = BlockReceipts::new();
+        assert_eq!(receipts.push(&success).cumulative_gas_used, 30_000);
+        let receipt = receipts.push(&revert);
+        assert!(!receipt.success);
+        assert_eq!(receipt.cumulative_gas_used, 55_000);
+        assert_eq!(receipt.logs_bloom, Bloom::zero());
+
+        assert_eq!(receipts.logs_bloom(), Bloom::from_logs([&log]));
+        assert!(receipts.logs_bloom().contains_input(&[1; 20]));
+        assert_eq!(receipts.receipts()[0].logs, [log]);
+    }
+}
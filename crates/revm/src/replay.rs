@@ -0,0 +1,211 @@
+//! Self-contained bundles replaying a transaction deterministically.
+//!
+//! [ReplayBundle] has the environment, every database read the transaction made and the
+//! outcome it had. Executing it needs nothing else, so it can be attached to a bug report
+//! as a single JSON file (with `serde` feature) and replayed with `revme replay`.
+use crate::db::{Database, ExecutionWitness, WitnessRecorder};
+use crate::evm::evm_inner;
+use crate::inspectors::NoOpInspector;
+use crate::primitives::{EVMError, EVMResult, Env, InvalidTransaction, ResultAndState};
+use crate::EVM;
+use alloc::boxed::Box;
+
+/// Outcome of the transaction.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Expected {
+    /// Transaction was executed, with its result and changed state.
+    Executed(ResultAndState),
+    /// Transaction was rejected.
+    Invalid(InvalidTransaction),
+    PrevrandaoNotSet,
+    ExcessBlobGasNotSet,
+}
+
+impl Expected {
+    /// Outcome of executed transaction, None on database errors.
+    pub fn from_result<E>(result: &EVMResult<E>) -> Option<Self> {
+        match result {
+            Ok(out) => Some(Self::Executed(out.clone())),
+            Err(EVMError::Transaction(e)) => Some(Self::Invalid(*e)),
+            Err(EVMError::PrevrandaoNotSet) => Some(Self::PrevrandaoNotSet),
+            Err(EVMError::ExcessBlobGasNotSet) => Some(Self::ExcessBlobGasNotSet),
+            Err(EVMError::Database(_)) => None,
+        }
+    }
+
+    /// Transaction was executed successfully.
+    pub fn is_success(&self) -> bool {
+        matches!(self, Self::Executed(out) if out.result.is_success())
+    }
+}
+
+/// Replay of the transaction that did not match [ReplayBundle::expected].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReplayMismatch {
+    pub expected: Expected,
+    pub actual: Expected,
+}
+
+/// Transaction with everything needed to execute it again.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ReplayBundle {
+    pub env: Env,
+    pub reads: ExecutionWitness,
+    pub expected: Expected,
+}
+
+impl ReplayBundle {
+    /// Execute transaction of `env` over `db` and capture it.
+    pub fn capture<DB: Database>(env: &Env, db: DB) -> Result<Self, DB::Error> {
+        let mut env = env.clone();
+        let mut db = WitnessRecorder::new(db);
+        let result = evm_inner::<_, false>(&mut env, &mut db, &mut NoOpInspector {}).transact();
+        let expected = match Expected::from_result(&result) {
+            Some(expected) => expected,
+            None => match result {
+                Err(EVMError::Database(e)) => return Err(e),
+                _ => unreachable!("only database errors have no outcome"),
+            },
+        };
+        Ok(Self {
+            env,
+            reads: db.into_parts().1,
+            expected,
+        })
+    }
+
+    /// Execute transaction over recorded reads.
+    pub fn replay(&self) -> Expected {
+        let mut evm = EVM::with_env(self.env.clone());
+        evm.database(self.reads.to_db());
+        Expected::from_result(&evm.transact()).expect("database is infallible")
+    }
+
+    /// Replay transaction and check it has the expected outcome.
+    pub fn check(&self) -> Result<(), Box<ReplayMismatch>> {
+        let actual = self.replay();
+        if actual != self.expected {
+            return Err(Box::new(ReplayMismatch {
+                expected: self.expected.clone(),
+                actual,
+            }));
+        }
+        Ok(())
+    }
+}
+
+impl<DB: Database> EVM<DB> {
+    /// Same as [EVM::transact], additionally returns [ReplayBundle] if transaction was
+    /// rejected, reverted or halted.
+    pub fn transact_capture(&mut self) -> (EVMResult<DB::Error>, Option<ReplayBundle>) {
+        let Some(db) = self.db.as_mut() else {
+            panic!("Database needs to be set");
+        };
+        let env = self.env.clone();
+        let mut db = WitnessRecorder::new(db);
+        let result =
+            evm_inner::<_, false>(&mut self.env, &mut db, &mut NoOpInspector {}).transact();
+        let bundle = Expected::from_result(&result)
+            .filter(|expected| !expected.is_success())
+            .map(|expected| ReplayBundle {
+                env,
+                reads: db.into_parts().1,
+                expected,
+            });
+        (result, bundle)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primitives::{hex_literal::hex, AccountInfo, Bytecode, TransactTo, B160, U256};
+    use crate::InMemoryDB;
+
+    const CALLER: B160 = B160([0x10; 20]);
+    const CONTRACT: B160 = B160([0x20; 20]);
+
+    /// Contract reverting if slot 0 is nonzero, incrementing it otherwise.
+    fn evm() -> EVM<InMemoryDB> {
+        // JUMPI(12, SLOAD(0)) SSTORE(0, 1) STOP JUMPDEST POP(BLOCKHASH(0)) REVERT(0, 0)
+        let code = hex!("600054600c576001600055005b6000405060006000fd");
+        let mut db = InMemoryDB::default();
+        db.insert_account_info(
+            CONTRACT,
+            AccountInfo::new(U256::ZERO, 0, Bytecode::new_raw(code.to_vec().into())),
+        );
+        let mut evm = EVM::new();
+        evm.database(db);
+        evm.env.block.number = U256::from(10);
+        evm.env.tx.caller = CALLER;
+        evm.env.tx.transact_to = TransactTo::Call(CONTRACT);
+        evm
+    }
+
+    #[test]
+    fn captures_failing_execution() {
+        let mut evm = evm();
+        let (result, bundle) = evm.transact_capture();
+        assert!(result.unwrap().result.is_success());
+        assert!(bundle.is_none());
+        evm.transact_commit().unwrap();
+
+        let (result, bundle) = evm.transact_capture();
+        let bundle = bundle.unwrap();
+        assert!(!result.unwrap().result.is_success());
+        assert!(!bundle.expected.is_success());
+        assert_eq!(bundle.reads.storage[&CONTRACT][&U256::ZERO], U256::from(1));
+        assert_eq!(bundle.reads.accounts[&CALLER].as_ref().unwrap().nonce, 1);
+        assert!(bundle.reads.block_hashes.contains_key(&U256::ZERO));
+        assert_eq!(bundle.check(), Ok(()));
+
+        let mut tampered = bundle.clone();
+        tampered
+            .reads
+            .storage
+            .get_mut(&CONTRACT)
+            .unwrap()
+            .insert(U256::ZERO, U256::ZERO);
+        let mismatch = tampered.check().unwrap_err();
+        assert!(mismatch.actual.is_success());
+
+        let mut env = evm.env.clone();
+        env.block.prevrandao = None;
+        env.cfg.spec_id = crate::primitives::SpecId::MERGE;
+        let bundle = ReplayBundle::capture(&env, evm.db().unwrap()).unwrap();
+        assert_eq!(bundle.expected, Expected::PrevrandaoNotSet);
+        assert_eq!(bundle.check(), Ok(()));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn json_roundtrip() {
+        let mut evm = evm();
+        evm.transact_commit().unwrap();
+        let bundle = ReplayBundle::capture(&evm.env, evm.db.as_mut().unwrap()).unwrap();
+        let json = serde_json::to_string(&bundle).unwrap();
+        let decoded: ReplayBundle = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded.reads, bundle.reads);
+        assert_eq!(decoded.expected, bundle.expected);
+        assert_eq!(decoded.check(), Ok(()));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn block_without_blob_gas() {
+        use crate::primitives::{BlockEnv, CancunSpec, Env};
+
+        let mut json = serde_json::to_value(BlockEnv::default()).unwrap();
+        json.as_object_mut()
+            .unwrap()
+            .remove("blob_excess_gas_and_price");
+        let env = Env {
+            block: serde_json::from_value(json).unwrap(),
+            ..Default::default()
+        };
+        assert_eq!(env.block, BlockEnv::default());
+        assert_eq!(env.validate_block_env::<CancunSpec, ()>(), Ok(()));
+    }
+}
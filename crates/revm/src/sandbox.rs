@@ -0,0 +1,164 @@
+//! Calls executed in a sandbox over the current state.
+//!
+//! Sandboxed call is not a transaction: there is no intrinsic gas, fee, nonce or balance
+//! check, it is bounded only by its own gas limit. All changes it makes are discarded and
+//! the database is never written to, so it can be used for `eth_call` style queries and for
+//! evaluating view functions between transactions.
+use crate::evm::evm_inner_with_table;
+use crate::inspectors::NoOpInspector;
+use crate::primitives::{Bytes, EVMError, ExecutionResult, B160, U256};
+use crate::{Database, EVM};
+
+/// Call to execute in the sandbox.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SandboxCall {
+    pub caller: B160,
+    pub to: B160,
+    pub input: Bytes,
+    pub value: U256,
+    pub gas_limit: u64,
+    /// Execute with `STATICCALL` semantics, nothing can be changed and value has to be zero.
+    pub read_only: bool,
+}
+
+impl SandboxCall {
+    /// Read only call with gas limit of [SandboxCall::DEFAULT_GAS_LIMIT].
+    pub fn new(caller: B160, to: B160, input: Bytes) -> Self {
+        Self {
+            caller,
+            to,
+            input,
+            value: U256::ZERO,
+            gas_limit: Self::DEFAULT_GAS_LIMIT,
+            read_only: true,
+        }
+    }
+
+    /// Same as default gas cap of `eth_call` in geth.
+    pub const DEFAULT_GAS_LIMIT: u64 = 50_000_000;
+
+    pub fn value(mut self, value: U256) -> Self {
+        self.value = value;
+        self
+    }
+
+    pub fn gas_limit(mut self, gas_limit: u64) -> Self {
+        self.gas_limit = gas_limit;
+        self
+    }
+
+    pub fn read_only(mut self, read_only: bool) -> Self {
+        self.read_only = read_only;
+        self
+    }
+}
+
+impl<DB: Database> EVM<DB> {
+    /// Execute `call` in the sandbox with block and configuration of the environment.
+    ///
+    /// Gas used is what the call spent, refunds are not applied. Read only call with value
+    /// halts with [crate::primitives::Halt::CallNotAllowedInsideStatic].
+    pub fn sandbox_call(
+        &mut self,
+        call: &SandboxCall,
+    ) -> Result<ExecutionResult, EVMError<DB::Error>> {
+        let Some(db) = self.db.as_mut() else {
+            panic!("Database needs to be set");
+        };
+        let table = self.instruction_table.as_ref();
+        evm_inner_with_table::<_, false>(&mut self.env, db, &mut NoOpInspector {}, table)
+            .sandbox_call(call)
+    }
+}
+
+#[cfg(all(test, not(feature = "no_gas_measuring")))]
+mod tests {
+    use super::*;
+    use crate::primitives::{hex_literal::hex, AccountInfo, Bytecode, Halt, Output, TransactTo};
+    use crate::InMemoryDB;
+
+    const CONTRACT: B160 = B160([0x20; 20]);
+
+    /// Contract returning slot 0, incrementing it if called with data.
+    fn evm() -> EVM<InMemoryDB> {
+        // JUMPI(15, ISZERO(CALLDATASIZE)) SSTORE(0, SLOAD(0) + 1) STOP JUMPDEST
+        // MSTORE(0, SLOAD(0)) RETURN(0, 32)
+        let code = hex!("3615600f57600054600101600055005b60005460005260206000f3");
+        let mut db = InMemoryDB::default();
+        db.insert_account_info(
+            CONTRACT,
+            AccountInfo::new(U256::ZERO, 0, Bytecode::new_raw(code.to_vec().into())),
+        );
+        db.insert_account_storage(CONTRACT, U256::ZERO, U256::from(7))
+            .unwrap();
+        let mut evm = EVM::new();
+        evm.database(db);
+        evm
+    }
+
+    #[test]
+    fn sandbox_call() {
+        let mut evm = evm();
+        let caller = B160([0x10; 20]);
+        let view = SandboxCall::new(caller, CONTRACT, Bytes::new());
+        let ExecutionResult::Success {
+            output: Output::Call(output),
+            gas_used,
+            ..
+        } = evm.sandbox_call(&view).unwrap()
+        else {
+            panic!("view call failed");
+        };
+        assert_eq!(
+            U256::from_be_bytes::<32>(output[..].try_into().unwrap()),
+            U256::from(7)
+        );
+        // no intrinsic gas, cold SLOAD is the most of it.
+        assert!(gas_used > 2100 && gas_used < 2300);
+
+        // writes are not allowed in read only calls, and are discarded otherwise.
+        let write = SandboxCall::new(caller, CONTRACT, Bytes::from_static(&[1]));
+        let result = evm.sandbox_call(&write).unwrap();
+        assert!(matches!(
+            result,
+            ExecutionResult::Halt {
+                reason: Halt::StateChangeDuringStaticCall,
+                ..
+            }
+        ));
+        assert!(evm
+            .sandbox_call(&write.clone().read_only(false))
+            .unwrap()
+            .is_success());
+        let result = evm
+            .sandbox_call(&view.clone().value(U256::from(1)))
+            .unwrap();
+        assert!(matches!(
+            result,
+            ExecutionResult::Halt {
+                reason: Halt::CallNotAllowedInsideStatic,
+                ..
+            }
+        ));
+        assert_eq!(
+            evm.db().unwrap().accounts[&CONTRACT].storage[&U256::ZERO],
+            U256::from(7)
+        );
+        // caller is not charged and its nonce is not changed.
+        assert_eq!(evm.db().unwrap().basic(caller).unwrap(), None);
+
+        // gas limit bounds the call.
+        let result = evm.sandbox_call(&view.gas_limit(1000)).unwrap();
+        assert!(matches!(
+            result,
+            ExecutionResult::Halt {
+                reason: Halt::OutOfGas(_),
+                gas_used: 1000,
+            }
+        ));
+
+        evm.env.tx.transact_to = TransactTo::Call(CONTRACT);
+        evm.env.tx.data = Bytes::from_static(&[1]);
+        assert!(evm.transact_commit().unwrap().is_success());
+    }
+}
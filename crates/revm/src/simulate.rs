@@ -0,0 +1,306 @@
+//! Simulation of calls in a sequence of blocks, in the style of `eth_simulateV1`.
+//!
+//! Every [SimulatedBlock] applies its state overrides, then executes its calls in order over
+//! the same [CacheDB], committing each one, so later calls and blocks see changes of the
+//! earlier ones. Blocks follow `env.block` one after another, fields that are not
+//! overridden advance by one block number and [BLOCK_TIME] seconds.
+use crate::db::{AccountState, CacheDB, DatabaseCommit, DatabaseRef};
+use crate::evm::evm_inner;
+use crate::inspectors::NoOpInspector;
+use crate::primitives::{
+    BlobExcessGasAndPrice, BlockEnv, Bytecode, EVMError, Env, ExecutionResult, HashMap, Log, State,
+    TxEnv, B160, B256, U256,
+};
+use alloc::vec::Vec;
+
+/// Seconds between simulated blocks whose timestamp is not overridden.
+pub const BLOCK_TIME: u64 = 12;
+
+/// Changes applied to an account before the job is executed.
+#[derive(Clone, Debug, Default)]
+pub struct AccountOverride {
+    pub balance: Option<U256>,
+    pub nonce: Option<u64>,
+    pub code: Option<Bytecode>,
+    /// Slots to set, other slots are read from the base state.
+    pub storage: HashMap<U256, U256>,
+    /// If set, storage of the account is replaced by `storage` instead of patched.
+    pub replace_storage: bool,
+}
+
+/// Apply `overrides` to the cached accounts of `db`, the underlying database is not changed.
+pub fn apply_overrides<ExtDB: DatabaseRef>(
+    db: &mut CacheDB<ExtDB>,
+    overrides: HashMap<B160, AccountOverride>,
+) -> Result<(), ExtDB::Error> {
+    for (address, account) in overrides {
+        let code = account.code.inspect(|code| {
+            db.contracts
+                .entry(code.hash())
+                .or_insert_with(|| code.clone());
+        });
+        let db_account = db.load_account(address)?;
+        if matches!(db_account.account_state, AccountState::NotExisting) {
+            // overridden account exists, it has no storage in the base state.
+            db_account.account_state = AccountState::StorageCleared;
+        }
+        if let Some(balance) = account.balance {
+            db_account.info.balance = balance;
+        }
+        if let Some(nonce) = account.nonce {
+            db_account.info.nonce = nonce;
+        }
+        if let Some(code) = code {
+            // code loaded with the account is used over its hash, it is replaced too.
+            db_account.info.code_hash = code.hash();
+            db_account.info.code = Some(code);
+        }
+        if account.replace_storage {
+            db_account.account_state = AccountState::StorageCleared;
+            db_account.storage.clear();
+        }
+        db_account.storage.extend(account.storage);
+    }
+    Ok(())
+}
+
+/// Fields of the block env to set instead of following the previous block.
+#[derive(Clone, Debug, Default)]
+pub struct BlockOverrides {
+    pub number: Option<U256>,
+    pub timestamp: Option<U256>,
+    pub gas_limit: Option<U256>,
+    pub coinbase: Option<B160>,
+    pub basefee: Option<U256>,
+    pub prevrandao: Option<B256>,
+    pub excess_blob_gas: Option<u64>,
+}
+
+impl BlockOverrides {
+    /// Block following `parent`, with the overridden fields.
+    pub fn next_block(&self, parent: &BlockEnv) -> BlockEnv {
+        let mut block = parent.clone();
+        block.number = self
+            .number
+            .unwrap_or_else(|| parent.number.saturating_add(U256::from(1)));
+        block.timestamp = self
+            .timestamp
+            .unwrap_or_else(|| parent.timestamp.saturating_add(U256::from(BLOCK_TIME)));
+        if let Some(gas_limit) = self.gas_limit {
+            block.gas_limit = gas_limit;
+        }
+        if let Some(coinbase) = self.coinbase {
+            block.coinbase = coinbase;
+        }
+        if let Some(basefee) = self.basefee {
+            block.basefee = basefee;
+        }
+        if let Some(prevrandao) = self.prevrandao {
+            block.prevrandao = Some(prevrandao);
+        }
+        if let Some(excess_blob_gas) = self.excess_blob_gas {
+            block.blob_excess_gas_and_price = Some(BlobExcessGasAndPrice::new(excess_blob_gas));
+        }
+        block
+    }
+}
+
+/// Block of calls to simulate.
+#[derive(Clone, Debug, Default)]
+pub struct SimulatedBlock {
+    pub block_overrides: BlockOverrides,
+    /// Applied before the first call of the block.
+    pub state_overrides: HashMap<B160, AccountOverride>,
+    /// Executed in order, each one sees changes of the previous ones.
+    pub calls: Vec<TxEnv>,
+}
+
+/// Outcome of a simulated block.
+#[derive(Clone, Debug)]
+pub struct SimulatedBlockResult {
+    /// Block env the calls were executed in.
+    pub block: BlockEnv,
+    /// Results of the calls, in order.
+    pub results: Vec<ExecutionResult>,
+    /// Accounts changed by the calls of the block, original values of storage slots are
+    /// the ones before the block. State overrides are not included.
+    pub state_diff: State,
+    /// Gas used by all calls of the block.
+    pub gas_used: u64,
+}
+
+impl SimulatedBlockResult {
+    /// Logs of successful calls of the block, in order.
+    pub fn logs(&self) -> impl Iterator<Item = &Log> {
+        self.results.iter().flat_map(|result| match result {
+            ExecutionResult::Success { logs, .. } => logs.iter(),
+            _ => [].iter(),
+        })
+    }
+}
+
+/// Simulate `blocks` one after another over `db`, with cfg of `env` and blocks following
+/// `env.block`.
+///
+/// Changes of the calls are committed to `db`. Invalid calls and database errors stop the
+/// simulation.
+pub fn simulate<ExtDB: DatabaseRef>(
+    db: &mut CacheDB<ExtDB>,
+    env: &Env,
+    blocks: Vec<SimulatedBlock>,
+) -> Result<Vec<SimulatedBlockResult>, EVMError<ExtDB::Error>> {
+    let mut env = env.clone();
+    let mut out = Vec::with_capacity(blocks.len());
+    for block in blocks {
+        env.block = block.block_overrides.next_block(&env.block);
+        apply_overrides(db, block.state_overrides).map_err(EVMError::Database)?;
+
+        let mut results = Vec::with_capacity(block.calls.len());
+        let mut state_diff = State::new();
+        let mut gas_used = 0;
+        for call in block.calls {
+            env.tx = call;
+            let out = evm_inner::<_, false>(&mut env, db, &mut NoOpInspector {}).transact()?;
+            gas_used += out.result.gas_used();
+            merge_state(&mut state_diff, &out.state);
+            db.commit(out.state);
+            results.push(out.result);
+        }
+        out.push(SimulatedBlockResult {
+            block: env.block.clone(),
+            results,
+            state_diff,
+            gas_used,
+        });
+    }
+    Ok(out)
+}
+
+/// Add `changes` of a later call to `diff`, keeping original values of the slots.
+pub(crate) fn merge_state(diff: &mut State, changes: &State) {
+    for (address, account) in changes.iter().filter(|(_, account)| account.is_touched()) {
+        let Some(merged) = diff.get_mut(address) else {
+            diff.insert(*address, account.clone());
+            continue;
+        };
+        merged.info = account.info.clone();
+        merged.status |= account.status;
+        if account.is_selfdestructed() || account.is_newly_created() {
+            // storage of the earlier calls is gone, their original values are kept.
+            for slot in merged.storage.values_mut() {
+                slot.present_value = U256::ZERO;
+            }
+        }
+        for (key, slot) in &account.storage {
+            merged
+                .storage
+                .entry(*key)
+                .and_modify(|merged| merged.present_value = slot.present_value)
+                .or_insert_with(|| slot.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primitives::{hex_literal::hex, AccountInfo, TransactTo};
+    use crate::InMemoryDB;
+
+    #[test]
+    fn simulates_blocks() {
+        let counter = B160([0x10; 20]);
+        let caller = B160([0x20; 20]);
+        // SSTORE(0, SLOAD(0) + 1) LOG0(0, 0) MSTORE(0, NUMBER) RETURN(0, 32)
+        let code = hex!("60005460010160005560006000a04360005260206000f3");
+        let mut db = InMemoryDB::default();
+        db.insert_account_info(
+            counter,
+            AccountInfo::new(U256::ZERO, 0, Bytecode::new_raw(code.to_vec().into())),
+        );
+        let mut env = Env::default();
+        env.block.number = U256::from(100);
+        let call = TxEnv {
+            caller,
+            transact_to: TransactTo::Call(counter),
+            ..Default::default()
+        };
+
+        let mut storage = HashMap::new();
+        storage.insert(U256::ZERO, U256::from(10));
+        let blocks = vec![
+            SimulatedBlock {
+                calls: vec![call.clone(), call.clone()],
+                ..Default::default()
+            },
+            SimulatedBlock {
+                block_overrides: BlockOverrides {
+                    number: Some(U256::from(200)),
+                    ..Default::default()
+                },
+                state_overrides: HashMap::from([(
+                    counter,
+                    AccountOverride {
+                        storage,
+                        ..Default::default()
+                    },
+                )]),
+                calls: vec![call],
+            },
+        ];
+        let out = simulate(&mut db, &env, blocks).unwrap();
+
+        assert_eq!(out[0].block.number, U256::from(101));
+        assert_eq!(out[0].block.timestamp, env.block.timestamp + U256::from(12));
+        let output = out[0].results[1].output().unwrap();
+        assert_eq!(output[..], U256::from(101).to_be_bytes::<32>());
+        assert_eq!(out[0].logs().count(), 2);
+        // diff of the block spans both calls.
+        let slot = &out[0].state_diff[&counter].storage[&U256::ZERO];
+        assert_eq!(
+            (slot.original_value, slot.present_value),
+            (U256::ZERO, U256::from(2))
+        );
+
+        assert_eq!(out[1].block.number, U256::from(200));
+        let slot = &out[1].state_diff[&counter].storage[&U256::ZERO];
+        assert_eq!(
+            (slot.original_value, slot.present_value),
+            (U256::from(10), U256::from(11))
+        );
+        assert_eq!(db.accounts[&counter].storage[&U256::ZERO], U256::from(11));
+    }
+
+    #[test]
+    fn overrides_existing_code() {
+        let contract = B160([0x10; 20]);
+        // MSTORE(0, 1) RETURN(0, 32)
+        let code = hex!("600160005260206000f3");
+        let mut db = InMemoryDB::default();
+        db.insert_account_info(
+            contract,
+            AccountInfo::new(U256::ZERO, 0, Bytecode::new_raw(code.to_vec().into())),
+        );
+        // MSTORE(0, 2) RETURN(0, 32)
+        let code = Bytecode::new_raw(hex!("600260005260206000f3").to_vec().into());
+        let block = SimulatedBlock {
+            state_overrides: HashMap::from([(
+                contract,
+                AccountOverride {
+                    code: Some(code.clone()),
+                    ..Default::default()
+                },
+            )]),
+            calls: vec![TxEnv {
+                transact_to: TransactTo::Call(contract),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let out = simulate(&mut db, &Env::default(), vec![block]).unwrap();
+
+        let output = out[0].results[0].output().unwrap();
+        assert_eq!(output[..], U256::from(2).to_be_bytes::<32>());
+        assert_eq!(db.accounts[&contract].info.code_hash, code.hash());
+    }
+}
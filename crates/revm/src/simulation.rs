@@ -0,0 +1,267 @@
+//! Pool of EVMs running simulations concurrently over shared state.
+//!
+//! Workers read from the same base database through an [Arc]. Each job gets its own
+//! [CacheDB] layered over the base, so state overrides and changes made by the job are
+//! never seen by other jobs and the base is never written to.
+//!
+//! A job that panics is reported as [JobPanicked], its worker keeps running other jobs.
+use crate::db::{CacheDB, DatabaseRef};
+use crate::primitives::{EVMError, EVMResult, Env, HashMap, B160};
+use crate::simulate::apply_overrides;
+pub use crate::simulate::AccountOverride;
+use crate::EVM;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::vec::Vec;
+
+/// Transaction to simulate with state overrides.
+#[derive(Clone, Debug, Default)]
+pub struct SimulationJob {
+    pub env: Env,
+    pub overrides: HashMap<B160, AccountOverride>,
+}
+
+impl SimulationJob {
+    pub fn new(env: Env) -> Self {
+        Self {
+            env,
+            overrides: HashMap::new(),
+        }
+    }
+
+    /// Add override for the account, replacing previous one.
+    pub fn with_override(mut self, address: B160, account: AccountOverride) -> Self {
+        self.overrides.insert(address, account);
+        self
+    }
+}
+
+/// Job panicked while it was executed, it has no result.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct JobPanicked;
+
+type Task<DB> = (SimulationJob, Sender<EVMResult<<DB as DatabaseRef>::Error>>);
+
+/// Fixed number of worker threads executing [SimulationJob]s over shared base state.
+///
+/// Results are sent back on the channel returned by [SimulationPool::submit]. Dropping the
+/// pool waits for already submitted jobs to finish.
+pub struct SimulationPool<DB: DatabaseRef> {
+    sender: Option<Sender<Task<DB>>>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl<DB> SimulationPool<DB>
+where
+    DB: DatabaseRef + Send + Sync + 'static,
+    DB::Error: Send + 'static,
+{
+    /// Spawn `workers` threads reading from `base`.
+    ///
+    /// # Panics
+    ///
+    /// If `workers` is zero.
+    pub fn new(base: Arc<DB>, workers: usize) -> Self {
+        assert!(workers > 0, "simulation pool needs at least one worker");
+        let (sender, receiver) = mpsc::channel::<Task<DB>>();
+        let receiver = Arc::new(Mutex::new(receiver));
+        let workers = (0..workers)
+            .map(|_| {
+                let base = base.clone();
+                let receiver = receiver.clone();
+                thread::spawn(move || worker(base, receiver))
+            })
+            .collect();
+        Self {
+            sender: Some(sender),
+            workers,
+        }
+    }
+
+    /// Queue the job, result is received on returned channel.
+    ///
+    /// Channel is disconnected without a result if the job panics.
+    pub fn submit(&self, job: SimulationJob) -> Receiver<EVMResult<DB::Error>> {
+        let (sender, receiver) = mpsc::channel();
+        if let Some(tasks) = &self.sender {
+            // result sender is dropped with the task if it can't be queued.
+            let _ = tasks.send((job, sender));
+        }
+        receiver
+    }
+
+    /// Run all jobs and wait for them, results are in the same order as jobs.
+    pub fn simulate_all(
+        &self,
+        jobs: impl IntoIterator<Item = SimulationJob>,
+    ) -> Vec<Result<EVMResult<DB::Error>, JobPanicked>> {
+        let receivers: Vec<_> = jobs.into_iter().map(|job| self.submit(job)).collect();
+        receivers
+            .into_iter()
+            .map(|receiver| receiver.recv().map_err(|_| JobPanicked))
+            .collect()
+    }
+
+    pub fn workers(&self) -> usize {
+        self.workers.len()
+    }
+}
+
+impl<DB: DatabaseRef> Drop for SimulationPool<DB> {
+    fn drop(&mut self) {
+        // closing the channel stops workers once the queue is empty.
+        drop(self.sender.take());
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+fn worker<DB: DatabaseRef>(base: Arc<DB>, tasks: Arc<Mutex<Receiver<Task<DB>>>>) {
+    let mut evm = EVM::new();
+    loop {
+        // lock is released before the job is executed.
+        let task = tasks.lock().map(|tasks| tasks.recv());
+        let Ok(Ok((job, result))) = task else {
+            return;
+        };
+        let mut db = CacheDB::new(base.clone());
+        let out = panic::catch_unwind(AssertUnwindSafe(|| {
+            apply_overrides(&mut db, job.overrides)
+                .map_err(EVMError::Database)
+                .and_then(|_| {
+                    evm.env = job.env;
+                    evm.database(db);
+                    evm.transact()
+                })
+        }));
+        match out {
+            // receiver is allowed to drop the channel if it is not interested in result.
+            Ok(out) => {
+                let _ = result.send(out);
+            }
+            // dropping the result sender tells the receiver, EVM is replaced as the
+            // panic could leave it in any state.
+            Err(_) => evm = EVM::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primitives::{hex_literal::hex, AccountInfo, Bytecode, TransactTo, B256, U256};
+    use crate::InMemoryDB;
+
+    #[test]
+    fn simulates_with_overrides() {
+        let contract = B160([0x10; 20]);
+        let caller = B160([0x20; 20]);
+        let mut base = InMemoryDB::default();
+        // return storage slot 0
+        base.insert_account_info(
+            contract,
+            AccountInfo::new(
+                U256::ZERO,
+                0,
+                Bytecode::new_raw(hex!("60005460005260206000f3").to_vec().into()),
+            ),
+        );
+        base.insert_account_storage(contract, U256::ZERO, U256::from(1))
+            .unwrap();
+        let pool = SimulationPool::new(Arc::new(base), 2);
+        assert_eq!(pool.workers(), 2);
+
+        let mut env = Env::default();
+        env.tx.caller = caller;
+        env.tx.transact_to = TransactTo::Call(contract);
+        let jobs = (0..8u64).map(|i| {
+            let mut storage = HashMap::new();
+            storage.insert(U256::ZERO, U256::from(i));
+            let job = SimulationJob::new(env.clone());
+            // odd jobs run against base state.
+            if i % 2 == 0 {
+                job.with_override(
+                    contract,
+                    AccountOverride {
+                        storage,
+                        ..Default::default()
+                    },
+                )
+            } else {
+                job
+            }
+        });
+
+        for (i, result) in pool.simulate_all(jobs).into_iter().enumerate() {
+            let result = result.unwrap().unwrap().result;
+            let expected = if i % 2 == 0 { i as u64 } else { 1 };
+            assert_eq!(
+                result.output().unwrap()[..],
+                U256::from(expected).to_be_bytes::<32>()[..]
+            );
+        }
+    }
+
+    #[test]
+    fn overrides_not_existing_account() {
+        let caller = B160([0x20; 20]);
+        let pool = SimulationPool::new(Arc::new(InMemoryDB::default()), 1);
+
+        let mut env = Env::default();
+        env.tx.caller = caller;
+        env.tx.gas_price = U256::from(1);
+        env.tx.gas_limit = 100_000;
+        env.tx.transact_to = TransactTo::Call(B160([0x30; 20]));
+
+        // caller can't pay for gas without override.
+        let job = SimulationJob::new(env.clone());
+        assert!(pool.submit(job).recv().unwrap().is_err());
+
+        let job = SimulationJob::new(env).with_override(
+            caller,
+            AccountOverride {
+                balance: Some(U256::from(1_000_000_000)),
+                ..Default::default()
+            },
+        );
+        let state = pool.submit(job).recv().unwrap().unwrap().state;
+        assert!(state[&caller].info.balance < U256::from(1_000_000_000));
+    }
+
+    #[test]
+    fn worker_survives_panic() {
+        struct PanickingDb;
+        impl DatabaseRef for PanickingDb {
+            type Error = ();
+            fn basic(&self, address: B160) -> Result<Option<AccountInfo>, ()> {
+                assert_ne!(address, B160([0x30; 20]), "database panicked");
+                Ok(None)
+            }
+            fn code_by_hash(&self, _code_hash: B256) -> Result<Bytecode, ()> {
+                Ok(Bytecode::new())
+            }
+            fn storage(&self, _address: B160, _index: U256) -> Result<U256, ()> {
+                Ok(U256::ZERO)
+            }
+            fn block_hash(&self, _number: U256) -> Result<B256, ()> {
+                Ok(B256::zero())
+            }
+        }
+
+        let pool = SimulationPool::new(Arc::new(PanickingDb), 1);
+        let job = |to| {
+            let mut env = Env::default();
+            env.tx.caller = B160([0x20; 20]);
+            env.tx.transact_to = TransactTo::Call(to);
+            SimulationJob::new(env)
+        };
+        let results = pool.simulate_all([job(B160([0x30; 20])), job(B160([0x40; 20]))]);
+        assert_eq!(results[0], Err(JobPanicked));
+        assert!(results[1].as_ref().unwrap().is_ok());
+        // the only worker is still running.
+        assert!(pool.submit(job(B160([0x40; 20]))).recv().unwrap().is_ok());
+    }
+}
@@ -0,0 +1,531 @@
+//! Debugger API driving execution of a transaction step by step.
+//!
+//! [ExecutionHandle] executes the transaction on the caller's thread. Interpreters don't run
+//! nested calls themselves, frames that didn't finish are kept by the handle, so execution
+//! can be paused before any opcode and at frame boundaries and resumed later. While it is
+//! paused, the interpreter, inputs and outputs of frames and the state can be read and
+//! changed.
+//!
+//! Paused execution can be suspended to release the [EVM], and resumed later. With the
+//! `serde` feature the suspended [Execution] can be serialized, so long simulations can move
+//! between processes.
+pub use crate::frame::{Execution, FrameOutput};
+use crate::frame::{Next, PauseAt};
+use crate::inspector::call_graph::CallKind;
+use crate::inspectors::NoOpInspector;
+use crate::interpreter::{
+    CallInputs, CreateInputs, InstructionResult, Interpreter, InterpreterAction,
+};
+use crate::primitives::{Bytes, EVMError, EVMResult, ResultAndState, B160, U256};
+use crate::{Database, Inspector, JournaledState, EVM};
+use alloc::vec::Vec;
+
+/// Point where execution is paused.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Pause {
+    /// Before opcode is executed.
+    Step {
+        depth: u64,
+        /// Address whose storage the frame uses.
+        address: B160,
+        pc: usize,
+        opcode: u8,
+        gas_remaining: u64,
+        /// Stack from bottom to top.
+        stack: Vec<U256>,
+    },
+    /// Before a frame is entered. Address of creates is not known yet.
+    FrameStart {
+        depth: u64,
+        kind: CallKind,
+        caller: B160,
+        address: Option<B160>,
+        gas_limit: u64,
+    },
+    /// After a frame returned, before its parent gets the output.
+    FrameEnd {
+        depth: u64,
+        address: Option<B160>,
+        result: InstructionResult,
+        output: Bytes,
+    },
+}
+
+/// Transaction of an [EVM] executing step by step, paused until it is resumed.
+///
+/// Execution starts paused before the first frame. An error of the database leaves the handle
+/// paused where it was, execution can be resumed again once the database has the data.
+pub struct ExecutionHandle<'a, DB: Database> {
+    evm: &'a mut EVM<DB>,
+    inspector: Option<&'a mut dyn Inspector<DB>>,
+    execution: Execution,
+    result: Option<ResultAndState>,
+}
+
+impl<'a, DB: Database> ExecutionHandle<'a, DB> {
+    /// Validate transaction of `evm` and pause before its first frame.
+    pub fn new(evm: &'a mut EVM<DB>) -> Result<Self, EVMError<DB::Error>> {
+        let mut noop = NoOpInspector {};
+        let execution = evm.driver::<false>(&mut noop).start()?;
+        Ok(Self {
+            evm,
+            inspector: None,
+            execution,
+            result: None,
+        })
+    }
+
+    /// Same as [ExecutionHandle::new], `inspector` is called as by [EVM::inspect].
+    pub fn with_inspector(
+        evm: &'a mut EVM<DB>,
+        inspector: &'a mut dyn Inspector<DB>,
+    ) -> Result<Self, EVMError<DB::Error>> {
+        let execution = evm.driver::<true>(&mut *inspector).start()?;
+        Ok(Self {
+            evm,
+            inspector: Some(inspector),
+            execution,
+            result: None,
+        })
+    }
+
+    /// Continue execution suspended by [ExecutionHandle::suspend], paused where it was.
+    ///
+    /// `evm` needs environment of the transaction and database with the state it was started
+    /// over. State the transaction already read or changed is part of the execution.
+    pub fn resume(evm: &'a mut EVM<DB>, execution: Execution) -> Self {
+        Self {
+            evm,
+            inspector: None,
+            execution,
+            result: None,
+        }
+    }
+
+    /// Same as [ExecutionHandle::resume], `inspector` is called as by [EVM::inspect]. It
+    /// only sees what is executed after the execution is resumed.
+    pub fn resume_with_inspector(
+        evm: &'a mut EVM<DB>,
+        inspector: &'a mut dyn Inspector<DB>,
+        execution: Execution,
+    ) -> Self {
+        Self {
+            evm,
+            inspector: Some(inspector),
+            execution,
+            result: None,
+        }
+    }
+
+    /// Stop execution where it is paused and release the EVM, none if execution finished.
+    pub fn suspend(self) -> Option<Execution> {
+        self.result.is_none().then_some(self.execution)
+    }
+
+    /// Point where execution is paused, none if it finished.
+    pub fn pause(&self) -> Option<Pause> {
+        if self.result.is_some() {
+            return None;
+        }
+        let depth = self.execution.journaled_state.depth();
+        Some(match &self.execution.next {
+            Next::Enter {
+                action: InterpreterAction::Call { inputs, .. },
+                ..
+            } => Pause::FrameStart {
+                depth,
+                kind: inputs.context.scheme.into(),
+                caller: inputs.context.caller,
+                address: Some(inputs.context.address),
+                gas_limit: inputs.gas_limit,
+            },
+            Next::Enter {
+                action: InterpreterAction::Create { inputs },
+                ..
+            } => Pause::FrameStart {
+                depth,
+                kind: inputs.scheme.into(),
+                caller: inputs.caller,
+                address: None,
+                gas_limit: inputs.gas_limit,
+            },
+            Next::Run => {
+                let interp = &self.execution.frames.last()?.interpreter;
+                Pause::Step {
+                    depth,
+                    address: interp.contract.address,
+                    pc: interp.program_counter(),
+                    opcode: interp.current_opcode(),
+                    gas_remaining: interp.gas.remaining(),
+                    stack: interp.stack.data().clone(),
+                }
+            }
+            Next::Return(output) => Pause::FrameEnd {
+                depth,
+                address: output.address,
+                result: output.result,
+                output: output.output.clone(),
+            },
+        })
+    }
+
+    /// Resume and pause before the next opcode or frame boundary. None if execution
+    /// finished.
+    pub fn step(&mut self) -> Result<Option<Pause>, EVMError<DB::Error>> {
+        self.drive(PauseAt::Step)
+    }
+
+    /// Resume and pause at the next frame boundary, opcodes in between are not paused at.
+    pub fn next_frame(&mut self) -> Result<Option<Pause>, EVMError<DB::Error>> {
+        self.drive(PauseAt::Frame)
+    }
+
+    /// Run to the end without pausing.
+    pub fn finish(mut self) -> EVMResult<DB::Error> {
+        if self.result.is_none() {
+            self.drive(PauseAt::End)?;
+        }
+        Ok(self.result.expect("execution finished"))
+    }
+
+    /// Interpreter of the last frame that didn't finish, to read or change its stack and
+    /// memory. It is the frame paused before an opcode, or the parent of a frame that starts
+    /// or returns.
+    pub fn interpreter_mut(&mut self) -> Option<&mut Interpreter> {
+        if self.result.is_some() {
+            return None;
+        }
+        self.execution
+            .frames
+            .last_mut()
+            .map(|frame| &mut *frame.interpreter)
+    }
+
+    /// Inputs of the call that starts next.
+    pub fn call_inputs_mut(&mut self) -> Option<&mut CallInputs> {
+        match &mut self.execution.next {
+            Next::Enter {
+                action: InterpreterAction::Call { inputs, .. },
+                ..
+            } => Some(inputs),
+            _ => None,
+        }
+    }
+
+    /// Inputs of the create that starts next.
+    pub fn create_inputs_mut(&mut self) -> Option<&mut CreateInputs> {
+        match &mut self.execution.next {
+            Next::Enter {
+                action: InterpreterAction::Create { inputs },
+                ..
+            } => Some(inputs),
+            _ => None,
+        }
+    }
+
+    /// Output of the frame that returned, before its parent gets it.
+    pub fn frame_output_mut(&mut self) -> Option<&mut FrameOutput> {
+        match &mut self.execution.next {
+            Next::Return(output) => Some(output),
+            _ => None,
+        }
+    }
+
+    /// State changed by the transaction so far.
+    pub fn journaled_state_mut(&mut self) -> &mut JournaledState {
+        &mut self.execution.journaled_state
+    }
+
+    pub fn db_mut(&mut self) -> &mut DB {
+        self.evm.db.as_mut().expect("Database needs to be set")
+    }
+
+    fn drive(&mut self, pause: PauseAt) -> Result<Option<Pause>, EVMError<DB::Error>> {
+        if self.result.is_some() {
+            return Ok(None);
+        }
+        let mut noop = NoOpInspector {};
+        let mut driver = match self.inspector.as_deref_mut() {
+            Some(inspector) => self.evm.driver::<true>(inspector),
+            None => self.evm.driver::<false>(&mut noop),
+        };
+        self.result = driver.resume(&mut self.execution, pause)?;
+        drop(driver);
+        Ok(self.pause())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::opcode;
+    use crate::primitives::{hex_literal::hex, AccountInfo, Bytecode, HashSet, TransactTo, B256};
+    use crate::InMemoryDB;
+
+    const CALLER: B160 = B160([0x10; 20]);
+    const CONTRACT: B160 = B160([0x20; 20]);
+    const CALLEE: B160 = B160([0x30; 20]);
+
+    fn evm(code: &[u8]) -> EVM<InMemoryDB> {
+        let mut db = InMemoryDB::default();
+        db.insert_account_info(
+            CONTRACT,
+            AccountInfo::new(U256::ZERO, 0, Bytecode::new_raw(code.to_vec().into())),
+        );
+        // return 1 as a word
+        db.insert_account_info(
+            CALLEE,
+            AccountInfo::new(
+                U256::ZERO,
+                0,
+                Bytecode::new_raw(hex!("600160005260206000f3").to_vec().into()),
+            ),
+        );
+        let mut evm = crate::new();
+        evm.database(db);
+        evm.env.tx.caller = CALLER;
+        evm.env.tx.transact_to = TransactTo::Call(CONTRACT);
+        evm
+    }
+
+    /// CALL(gas, CALLEE, 0, 0, 0, 0, 32) RETURN(0, 32)
+    fn call_callee() -> Vec<u8> {
+        let mut code = hex!("6020600060006000600073").to_vec();
+        code.extend_from_slice(&CALLEE.0);
+        code.extend_from_slice(&hex!("5af150 60206000f3"));
+        code
+    }
+
+    #[test]
+    fn pauses_and_edits_execution() {
+        // MSTORE(0, ADD(1, 2)) RETURN(0, 32)
+        let mut evm = evm(&hex!("6002600101 600052 60206000f3"));
+        let mut handle = ExecutionHandle::new(&mut evm).unwrap();
+        // starts paused before the first frame, there is no interpreter yet.
+        assert!(matches!(
+            handle.pause(),
+            Some(Pause::FrameStart { depth: 0, .. })
+        ));
+        assert!(handle.interpreter_mut().is_none());
+        let mut pause = handle.step().unwrap();
+        assert!(matches!(pause, Some(Pause::Step { pc: 0, .. })));
+        while let Some(Pause::Step { opcode, .. }) = pause {
+            if opcode == opcode::ADD {
+                break;
+            }
+            pause = handle.step().unwrap();
+        }
+        let Some(Pause::Step { stack, .. }) = pause else {
+            panic!("paused before ADD");
+        };
+        assert_eq!(stack, vec![U256::from(2), U256::from(1)]);
+        // replace 1 on top of the stack with 40.
+        let interp = handle.interpreter_mut().unwrap();
+        assert_eq!(interp.stack.pop(), Ok(U256::from(1)));
+        interp.stack.push(U256::from(40)).unwrap();
+
+        let pause = handle.next_frame().unwrap();
+        assert!(matches!(
+            pause,
+            Some(Pause::FrameEnd {
+                address: Some(address),
+                result: InstructionResult::Return,
+                ..
+            }) if address == CONTRACT
+        ));
+        let output = handle.finish().unwrap().result.into_output().unwrap();
+        assert_eq!(output[..], U256::from(42).to_be_bytes::<32>());
+        assert!(evm.db.is_some());
+    }
+
+    #[test]
+    fn edits_at_frame_boundaries() {
+        let mut evm = evm(&call_callee());
+        let mut handle = ExecutionHandle::new(&mut evm).unwrap();
+        assert!(handle.call_inputs_mut().is_some());
+        // first frame runs to the call of the callee.
+        let pause = handle.next_frame().unwrap();
+        assert!(matches!(
+            pause,
+            Some(Pause::FrameStart { depth: 1, address: Some(address), .. }) if address == CALLEE
+        ));
+        // parent is suspended at the call.
+        assert_eq!(handle.interpreter_mut().unwrap().contract.address, CONTRACT);
+        handle.call_inputs_mut().unwrap().input = Bytes::from_static(&[1]);
+
+        let pause = handle.next_frame().unwrap();
+        assert!(matches!(
+            pause,
+            Some(Pause::FrameEnd {
+                depth: 1,
+                result: InstructionResult::Return,
+                ..
+            })
+        ));
+        let output = handle.frame_output_mut().unwrap();
+        assert_eq!(output.output[..], U256::from(1).to_be_bytes::<32>());
+        output.output = U256::from(7).to_be_bytes::<32>().to_vec().into();
+
+        let output = handle.finish().unwrap().result.into_output().unwrap();
+        assert_eq!(output[..], U256::from(7).to_be_bytes::<32>());
+    }
+
+    /// SSTORE(0, CREATE(0, 0, 13)) with init code returning CALLEE's code, then call the
+    /// callee and log its output.
+    fn create_call_log() -> Vec<u8> {
+        let init = hex!("69600160005260206000f3 600052 600a6016f3");
+        let mut code = Vec::new();
+        // MSTORE(0, init) at the end of the word
+        code.push(0x60 + init.len() as u8 - 1);
+        code.extend_from_slice(&init);
+        code.extend_from_slice(&hex!("600052"));
+        // CREATE(0, 32 - len, len)
+        code.extend_from_slice(&[0x60, init.len() as u8, 0x60, 32 - init.len() as u8]);
+        code.extend_from_slice(&hex!("6000f0 600055"));
+        code.extend_from_slice(&call_callee()[..call_callee().len() - 5]);
+        // LOG0(0, 32) RETURN(0, 32)
+        code.extend_from_slice(&hex!("60206000a0 60206000f3"));
+        code
+    }
+
+    #[test]
+    fn steps_same_as_transact() {
+        let mut evm = evm(&create_call_log());
+        evm.env.cfg.gas_frames = true;
+        let expected = evm.transact().unwrap();
+        assert!(expected.result.is_success());
+        assert_eq!(expected.result.logs().len(), 1);
+        assert_ne!(
+            expected.state[&CONTRACT].storage[&U256::ZERO].present_value,
+            U256::ZERO
+        );
+
+        let mut handle = ExecutionHandle::new(&mut evm).unwrap();
+        let mut steps = 0;
+        let mut max_depth = 0;
+        while let Some(pause) = handle.step().unwrap() {
+            if let Pause::Step { depth, .. } = pause {
+                max_depth = max_depth.max(depth);
+            }
+            steps += 1;
+        }
+        assert!(steps > 20);
+        assert_eq!(max_depth, 2);
+        assert_eq!(handle.finish().unwrap(), expected);
+    }
+
+    #[test]
+    fn suspends_and_resumes() {
+        let mut evm = evm(&create_call_log());
+        let expected = evm.transact().unwrap();
+
+        let mut handle = ExecutionHandle::new(&mut evm).unwrap();
+        while !matches!(handle.step().unwrap(), Some(Pause::Step { depth: 2, .. })) {}
+        let execution = handle.suspend().unwrap();
+        // EVM is released while execution is suspended.
+        assert!(evm.db.is_some());
+
+        let handle = ExecutionHandle::resume(&mut evm, execution);
+        assert!(matches!(handle.pause(), Some(Pause::Step { depth: 2, .. })));
+        assert_eq!(handle.finish().unwrap(), expected);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn resumes_serialized_execution() {
+        let code = create_call_log();
+        let mut evm = evm(&code);
+        evm.env.cfg.gas_frames = true;
+        let expected = evm.transact().unwrap();
+
+        // every step is executed by a new EVM from serialized execution.
+        let execution = ExecutionHandle::new(&mut evm).unwrap().suspend().unwrap();
+        let mut bytes = execution.to_bytes().unwrap();
+        let mut steps = 0;
+        let result = loop {
+            let mut evm = self::evm(&code);
+            evm.env.cfg.gas_frames = true;
+            let execution = Execution::from_bytes(&bytes).unwrap();
+            let mut handle = ExecutionHandle::resume(&mut evm, execution);
+            if handle.step().unwrap().is_none() {
+                break handle.finish().unwrap();
+            }
+            bytes = handle.suspend().unwrap().to_bytes().unwrap();
+            steps += 1;
+        };
+        assert!(steps > 20);
+        assert_eq!(result, expected);
+    }
+
+    /// Database failing every first read of an account, slot or block hash.
+    #[derive(Default)]
+    struct FlakyDb {
+        db: InMemoryDB,
+        read: HashSet<(B160, U256)>,
+        failures: usize,
+    }
+
+    impl FlakyDb {
+        fn read(&mut self, key: (B160, U256)) -> Result<(), ()> {
+            if self.read.insert(key) {
+                self.failures += 1;
+                return Err(());
+            }
+            Ok(())
+        }
+    }
+
+    impl Database for FlakyDb {
+        type Error = ();
+
+        fn basic(&mut self, address: B160) -> Result<Option<AccountInfo>, ()> {
+            self.read((address, U256::MAX))?;
+            Ok(self.db.basic(address).unwrap())
+        }
+
+        fn code_by_hash(&mut self, code_hash: B256) -> Result<Bytecode, ()> {
+            Ok(self.db.code_by_hash(code_hash).unwrap())
+        }
+
+        fn storage(&mut self, address: B160, index: U256) -> Result<U256, ()> {
+            self.read((address, index))?;
+            Ok(self.db.storage(address, index).unwrap())
+        }
+
+        fn block_hash(&mut self, number: U256) -> Result<B256, ()> {
+            Ok(self.db.block_hash(number).unwrap())
+        }
+    }
+
+    #[test]
+    fn retries_after_database_error() {
+        // SSTORE(1, SLOAD(0)) CALL(callee) RETURN(0, 32)
+        let mut code = hex!("600054 600155").to_vec();
+        code.extend_from_slice(&call_callee());
+        let mut evm = evm(&code);
+        let expected = evm.transact().unwrap();
+
+        let mut flaky = crate::new();
+        flaky.env = evm.env.clone();
+        flaky.database(FlakyDb {
+            db: evm.take_db(),
+            ..Default::default()
+        });
+        let mut handle = loop {
+            match ExecutionHandle::new(&mut flaky) {
+                Ok(handle) => break handle,
+                Err(e) => assert_eq!(e, EVMError::Database(())),
+            }
+        };
+        let result = loop {
+            match handle.next_frame() {
+                Ok(Some(_)) => {}
+                Ok(None) => break handle.finish().unwrap(),
+                Err(e) => assert_eq!(e, EVMError::Database(())),
+            }
+        };
+        assert_eq!(result, expected);
+        // caller, contract, its slots, callee and coinbase.
+        assert_eq!(flaky.db.unwrap().failures, 6);
+    }
+}
@@ -0,0 +1,132 @@
+//! Calls the client executes at block boundaries as the system.
+//!
+//! System call is executed as [SYSTEM_ADDRESS] with gas limit of [SYSTEM_CALL_GAS_LIMIT].
+//! Like a [sandboxed call](crate::sandbox) there is no intrinsic gas, fee, nonce or balance
+//! check and gas it uses doesn't count against the block, but its changes are returned to
+//! be committed.
+use crate::evm::{evm_inner, evm_inner_with_table};
+use crate::inspectors::NoOpInspector;
+use crate::primitives::{
+    hex_literal::hex, Bytes, EVMError, EVMResult, Env, ResultAndState, SpecId, B160, B256,
+    KECCAK_EMPTY,
+};
+use crate::sandbox::SandboxCall;
+use crate::{Database, EVM};
+
+/// Caller of system calls.
+pub const SYSTEM_ADDRESS: B160 = B160(hex!("fffffffffffffffffffffffffffffffffffffffe"));
+/// Gas limit of system calls.
+pub const SYSTEM_CALL_GAS_LIMIT: u64 = 30_000_000;
+/// Address of the EIP-4788 beacon roots contract.
+pub const BEACON_ROOTS_ADDRESS: B160 = B160(hex!("000f3df6d732807ef1319fb7b8bb8522d0beac02"));
+
+/// EIP-4788: store `parent_beacon_block_root` in the beacon roots contract, executed at the
+/// start of the block.
+///
+/// None if Cancun is not enabled or the contract has no code, then the call is skipped.
+pub fn beacon_root_contract_call<DB: Database>(
+    env: &mut Env,
+    db: &mut DB,
+    parent_beacon_block_root: B256,
+) -> Result<Option<ResultAndState>, EVMError<DB::Error>> {
+    if !SpecId::enabled(env.cfg.spec_id, SpecId::CANCUN) {
+        return Ok(None);
+    }
+    let has_code = db
+        .basic(BEACON_ROOTS_ADDRESS)
+        .map_err(EVMError::Database)?
+        .is_some_and(|info| info.code_hash != KECCAK_EMPTY);
+    if !has_code {
+        return Ok(None);
+    }
+    let input = Bytes::copy_from_slice(&parent_beacon_block_root.0);
+    let call = system_call(BEACON_ROOTS_ADDRESS, input);
+    evm_inner::<_, false>(env, db, &mut NoOpInspector {})
+        .system_call(&call)
+        .map(Some)
+}
+
+fn system_call(to: B160, input: Bytes) -> SandboxCall {
+    SandboxCall::new(SYSTEM_ADDRESS, to, input)
+        .gas_limit(SYSTEM_CALL_GAS_LIMIT)
+        .read_only(false)
+}
+
+impl<DB: Database> EVM<DB> {
+    /// Execute system call to `to` with `input`, with block and configuration of the
+    /// environment. Changed state is returned, account of [SYSTEM_ADDRESS] is not part of it.
+    pub fn system_call(&mut self, to: B160, input: Bytes) -> EVMResult<DB::Error> {
+        let Some(db) = self.db.as_mut() else {
+            panic!("Database needs to be set");
+        };
+        let table = self.instruction_table.as_ref();
+        evm_inner_with_table::<_, false>(&mut self.env, db, &mut NoOpInspector {}, table)
+            .system_call(&system_call(to, input))
+    }
+
+    /// Same as [beacon_root_contract_call], with environment and database of the EVM.
+    pub fn beacon_root_contract_call(
+        &mut self,
+        parent_beacon_block_root: B256,
+    ) -> Result<Option<ResultAndState>, EVMError<DB::Error>> {
+        let Some(db) = self.db.as_mut() else {
+            panic!("Database needs to be set");
+        };
+        beacon_root_contract_call(&mut self.env, db, parent_beacon_block_root)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block_executor::BlockExecutor;
+    use crate::primitives::{AccountInfo, Bytecode, U256};
+    use crate::sandbox::SandboxCall;
+    use crate::InMemoryDB;
+
+    /// Beacon roots contract of EIP-4788.
+    const BEACON_ROOTS_CODE: [u8; 97] = hex!("3373fffffffffffffffffffffffffffffffffffffffe14604d57602036146024575f5ffd5b5f35801560495762001fff810690815414603c575f5ffd5b62001fff01545f5260205ff35b5f5ffd5b62001fff42064281555f359062001fff015500");
+
+    #[test]
+    fn beacon_root() {
+        let root = B256::repeat_byte(7);
+        let mut evm = EVM::new();
+        evm.database(InMemoryDB::default());
+        // no contract.
+        assert_eq!(evm.beacon_root_contract_call(root).unwrap(), None);
+
+        let code = Bytecode::new_raw(BEACON_ROOTS_CODE.to_vec().into());
+        let mut db = InMemoryDB::default();
+        db.insert_account_info(BEACON_ROOTS_ADDRESS, AccountInfo::new(U256::ZERO, 1, code));
+        evm.database(db);
+        evm.env.block.timestamp = U256::from(12);
+        evm.env.cfg.spec_id = SpecId::SHANGHAI;
+        assert_eq!(evm.beacon_root_contract_call(root).unwrap(), None);
+
+        evm.env.cfg.spec_id = SpecId::CANCUN;
+        let out = evm.beacon_root_contract_call(root).unwrap().unwrap();
+        assert!(out.result.is_success());
+        assert!(!out.state.contains_key(&SYSTEM_ADDRESS));
+        let storage = &out.state[&BEACON_ROOTS_ADDRESS].storage;
+        assert_eq!(storage[&U256::from(12)].present_value, U256::from(12));
+        assert_eq!(
+            storage[&U256::from(12 + 8191)].present_value,
+            U256::from_be_bytes(root.0)
+        );
+
+        // executor commits the changes without using gas of the block.
+        let mut db = evm.take_db();
+        let mut executor = BlockExecutor::new(&mut db, evm.env.cfg.clone(), evm.env.block.clone());
+        let result = executor.apply_beacon_root_contract_call(root).unwrap();
+        assert!(result.unwrap().is_success());
+        assert_eq!(executor.gas_used(), 0);
+        evm.database(db);
+        let get = SandboxCall::new(
+            B160([0x10; 20]),
+            BEACON_ROOTS_ADDRESS,
+            Bytes::from(U256::from(12).to_be_bytes_vec()),
+        );
+        let output = evm.sandbox_call(&get).unwrap().into_output().unwrap();
+        assert_eq!(output[..], root.0);
+    }
+}
@@ -0,0 +1,385 @@
+//! Decoding of signed transactions into [TxEnv].
+//!
+//! [decode_signed_transaction] reads a transaction as it is sent to `eth_sendRawTransaction`
+//! or included in a block: legacy transactions are RLP lists, typed transactions are the
+//! type byte followed by the RLP list of EIP-2718. Blob transactions in the network form,
+//! with blobs, commitments and proofs, are accepted too. The sender is recovered from the
+//! signature and set as caller.
+use crate::precompile::recover_address;
+use crate::primitives::eip7702::{SECP256K1N_HALF, SET_CODE_TX_TYPE};
+use crate::primitives::{
+    keccak256, Bytes, CreateScheme, SignedAuthorization, TransactTo, TxEnv, B160, B256, U256,
+};
+use alloc::vec::Vec;
+use rlp::{DecoderError, Rlp, RlpStream};
+
+pub const LEGACY_TX_TYPE: u8 = 0x00;
+/// EIP-2930: Optional access lists.
+pub const ACCESS_LIST_TX_TYPE: u8 = 0x01;
+/// EIP-1559: Fee market change.
+pub const DYNAMIC_FEE_TX_TYPE: u8 = 0x02;
+/// EIP-4844: Shard blob transactions.
+pub const BLOB_TX_TYPE: u8 = 0x03;
+
+/// Error of [decode_signed_transaction].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TxDecodeError {
+    Rlp(DecoderError),
+    /// Transaction type is not known.
+    UnknownType(u8),
+    /// Encoding has bytes after the transaction.
+    TrailingBytes,
+    /// Signature is malformed, malleable or doesn't recover to an address.
+    InvalidSignature,
+}
+
+impl From<DecoderError> for TxDecodeError {
+    fn from(error: DecoderError) -> Self {
+        Self::Rlp(error)
+    }
+}
+
+/// Transaction with its recovered sender.
+#[derive(Clone, Debug)]
+pub struct DecodedTransaction {
+    pub tx_type: u8,
+    /// Hash of the transaction, of its encoding without blobs for blob transactions.
+    pub hash: B256,
+    pub sender: B160,
+    /// Transaction env, caller is the sender.
+    pub tx: TxEnv,
+}
+
+/// Decode signed transaction `raw` and recover its sender.
+pub fn decode_signed_transaction(raw: &[u8]) -> Result<DecodedTransaction, TxDecodeError> {
+    let Some(&first) = raw.first() else {
+        return Err(DecoderError::RlpIsTooShort.into());
+    };
+    if first >= 0xc0 {
+        return decode_legacy(raw);
+    }
+    let tx_type = first;
+    let mut rlp = Rlp::new(&raw[1..]);
+    check_length(&rlp, raw.len() - 1)?;
+    // network form of blob transaction, [tx, blobs, commitments, proofs].
+    if tx_type == BLOB_TX_TYPE && rlp.at(0)?.is_list() {
+        rlp = rlp.at(0)?;
+    }
+    let payload = rlp.as_raw();
+    let (mut tx, fields) = match tx_type {
+        ACCESS_LIST_TX_TYPE => {
+            let tx = TxEnv {
+                chain_id: Some(rlp.val_at(0)?),
+                nonce: Some(rlp.val_at(1)?),
+                gas_price: rlp.val_at(2)?,
+                gas_limit: rlp.val_at(3)?,
+                transact_to: transact_to(&rlp.at(4)?)?,
+                value: rlp.val_at(5)?,
+                data: bytes(&rlp.at(6)?)?,
+                access_list: access_list(&rlp.at(7)?)?,
+                ..Default::default()
+            };
+            (tx, 8)
+        }
+        DYNAMIC_FEE_TX_TYPE | BLOB_TX_TYPE | SET_CODE_TX_TYPE => {
+            let mut tx = TxEnv {
+                chain_id: Some(rlp.val_at(0)?),
+                nonce: Some(rlp.val_at(1)?),
+                gas_priority_fee: Some(rlp.val_at(2)?),
+                gas_price: rlp.val_at(3)?,
+                gas_limit: rlp.val_at(4)?,
+                transact_to: transact_to(&rlp.at(5)?)?,
+                value: rlp.val_at(6)?,
+                data: bytes(&rlp.at(7)?)?,
+                access_list: access_list(&rlp.at(8)?)?,
+                ..Default::default()
+            };
+            let fields = match tx_type {
+                BLOB_TX_TYPE => {
+                    tx.max_fee_per_blob_gas = Some(rlp.val_at(9)?);
+                    tx.blob_hashes = rlp
+                        .at(10)?
+                        .iter()
+                        .map(|hash| hash_data(&hash))
+                        .collect::<Result<_, _>>()?;
+                    11
+                }
+                SET_CODE_TX_TYPE => {
+                    tx.authorization_list = rlp
+                        .at(9)?
+                        .iter()
+                        .map(|authorization| authorization_data(&authorization))
+                        .collect::<Result<_, _>>()?;
+                    10
+                }
+                _ => 9,
+            };
+            (tx, fields)
+        }
+        _ => return Err(TxDecodeError::UnknownType(tx_type)),
+    };
+    if rlp.item_count()? != fields + 3 {
+        return Err(DecoderError::RlpIncorrectListLen.into());
+    }
+    let y_parity: u8 = rlp.val_at(fields)?;
+    let signature = signature(y_parity, rlp.val_at(fields + 1)?, rlp.val_at(fields + 2)?)?;
+
+    let mut message = RlpStream::new();
+    message.append_raw(&[tx_type], 0);
+    message.begin_list(fields);
+    for index in 0..fields {
+        message.append_raw(rlp.at(index)?.as_raw(), 1);
+    }
+    let sender = recover(&signature, &keccak256(&message.out()))?;
+    tx.caller = sender;
+
+    let mut encoded = Vec::with_capacity(payload.len() + 1);
+    encoded.push(tx_type);
+    encoded.extend_from_slice(payload);
+    Ok(DecodedTransaction {
+        tx_type,
+        hash: keccak256(&encoded),
+        sender,
+        tx,
+    })
+}
+
+fn decode_legacy(raw: &[u8]) -> Result<DecodedTransaction, TxDecodeError> {
+    let rlp = Rlp::new(raw);
+    check_length(&rlp, raw.len())?;
+    if rlp.item_count()? != 9 {
+        return Err(DecoderError::RlpIncorrectListLen.into());
+    }
+    let v: u64 = rlp.val_at(6)?;
+    // EIP-155: chain id is part of v of replay protected transactions.
+    let (chain_id, y_parity) = match v {
+        27 | 28 => (None, v - 27),
+        35.. => (Some((v - 35) / 2), (v - 35) % 2),
+        _ => return Err(TxDecodeError::InvalidSignature),
+    };
+    let signature = signature(y_parity as u8, rlp.val_at(7)?, rlp.val_at(8)?)?;
+
+    let mut message = RlpStream::new_list(if chain_id.is_some() { 9 } else { 6 });
+    for index in 0..6 {
+        message.append_raw(rlp.at(index)?.as_raw(), 1);
+    }
+    if let Some(chain_id) = chain_id {
+        message.append(&chain_id).append(&0u8).append(&0u8);
+    }
+    let sender = recover(&signature, &keccak256(&message.out()))?;
+
+    let tx = TxEnv {
+        caller: sender,
+        nonce: Some(rlp.val_at(0)?),
+        gas_price: rlp.val_at(1)?,
+        gas_limit: rlp.val_at(2)?,
+        transact_to: transact_to(&rlp.at(3)?)?,
+        value: rlp.val_at(4)?,
+        data: bytes(&rlp.at(5)?)?,
+        chain_id,
+        ..Default::default()
+    };
+    Ok(DecodedTransaction {
+        tx_type: LEGACY_TX_TYPE,
+        hash: keccak256(raw),
+        sender,
+        tx,
+    })
+}
+
+fn check_length(rlp: &Rlp<'_>, len: usize) -> Result<(), TxDecodeError> {
+    if !rlp.is_list() {
+        return Err(DecoderError::RlpExpectedToBeList.into());
+    }
+    if rlp.payload_info()?.total() != len {
+        return Err(TxDecodeError::TrailingBytes);
+    }
+    Ok(())
+}
+
+/// Signature as `r | s | recovery id`, `s` above [SECP256K1N_HALF] is malleable (EIP-2).
+fn signature(y_parity: u8, r: U256, s: U256) -> Result<[u8; 65], TxDecodeError> {
+    if y_parity > 1 || s > SECP256K1N_HALF {
+        return Err(TxDecodeError::InvalidSignature);
+    }
+    let mut signature = [0; 65];
+    signature[..32].copy_from_slice(&r.to_be_bytes::<32>());
+    signature[32..64].copy_from_slice(&s.to_be_bytes::<32>());
+    signature[64] = y_parity;
+    Ok(signature)
+}
+
+fn recover(signature: &[u8; 65], message: &B256) -> Result<B160, TxDecodeError> {
+    recover_address(signature, &message.0)
+        .map(B160)
+        .ok_or(TxDecodeError::InvalidSignature)
+}
+
+fn bytes(rlp: &Rlp<'_>) -> Result<Bytes, DecoderError> {
+    Ok(Bytes::copy_from_slice(rlp.data()?))
+}
+
+fn address(rlp: &Rlp<'_>) -> Result<B160, DecoderError> {
+    let data = rlp.data()?;
+    if data.len() != 20 {
+        return Err(DecoderError::RlpInvalidLength);
+    }
+    Ok(B160::from_slice(data))
+}
+
+fn hash_data(rlp: &Rlp<'_>) -> Result<B256, DecoderError> {
+    let data = rlp.data()?;
+    if data.len() != 32 {
+        return Err(DecoderError::RlpInvalidLength);
+    }
+    Ok(B256::from_slice(data))
+}
+
+/// Empty destination creates a contract.
+fn transact_to(rlp: &Rlp<'_>) -> Result<TransactTo, DecoderError> {
+    if rlp.is_empty() {
+        return Ok(TransactTo::Create(CreateScheme::Create));
+    }
+    Ok(TransactTo::Call(address(rlp)?))
+}
+
+fn access_list(rlp: &Rlp<'_>) -> Result<Vec<(B160, Vec<U256>)>, DecoderError> {
+    rlp.iter()
+        .map(|item| {
+            if item.item_count()? != 2 {
+                return Err(DecoderError::RlpIncorrectListLen);
+            }
+            let keys = item
+                .at(1)?
+                .iter()
+                .map(|key| Ok(U256::from_be_bytes(hash_data(&key)?.0)))
+                .collect::<Result<_, _>>()?;
+            Ok((address(&item.at(0)?)?, keys))
+        })
+        .collect()
+}
+
+fn authorization_data(rlp: &Rlp<'_>) -> Result<SignedAuthorization, DecoderError> {
+    if rlp.item_count()? != 6 {
+        return Err(DecoderError::RlpIncorrectListLen);
+    }
+    Ok(SignedAuthorization {
+        chain_id: rlp.val_at(0)?,
+        address: address(&rlp.at(1)?)?,
+        nonce: rlp.val_at(2)?,
+        y_parity: rlp.val_at(3)?,
+        r: rlp.val_at(4)?,
+        s: rlp.val_at(5)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primitives::hex_literal::hex;
+    use k256::ecdsa::SigningKey;
+
+    #[test]
+    fn decodes_legacy_eip155() {
+        // example of EIP-155.
+        let raw = hex!("f86c098504a817c800825208943535353535353535353535353535353535353535880de0b6b3a76400008025a028ef61340bd939bc2195fe537567866003e1a15d3c71ff63e1590620aa636276a067cbe9d8997f761aecb703304b3800ccf555c9f3dc64214b297fb1966a3b6d83");
+        let decoded = decode_signed_transaction(&raw).unwrap();
+        assert_eq!(
+            decoded.sender,
+            B160(hex!("9d8a62f656a8d1615c1294fd71e9cfb3e4855a4f"))
+        );
+        assert_eq!(decoded.tx.chain_id, Some(1));
+        assert_eq!(decoded.tx.nonce, Some(9));
+        assert_eq!(decoded.tx.gas_price, U256::from(20_000_000_000u64));
+        assert_eq!(decoded.tx.gas_limit, 21_000);
+        assert!(matches!(decoded.tx.transact_to, TransactTo::Call(to) if to == B160([0x35; 20])));
+        assert_eq!(decoded.tx.value, U256::from(10u64.pow(18)));
+
+        let mut truncated = raw.to_vec();
+        truncated.push(0);
+        assert_eq!(
+            decode_signed_transaction(&truncated).unwrap_err(),
+            TxDecodeError::TrailingBytes
+        );
+    }
+
+    #[test]
+    fn decodes_typed_transactions() {
+        let key = SigningKey::from_slice(&[0x46; 32]).unwrap();
+        let sign = |tx_type: u8, fields: &RlpStream, count: usize| {
+            let mut message = RlpStream::new();
+            message.append_raw(&[tx_type], 0);
+            message.begin_list(count);
+            message.append_raw(fields.as_raw(), count);
+            let (signature, recovery_id) = key
+                .sign_prehash_recoverable(&keccak256(&message.out()).0)
+                .unwrap();
+            let mut signed = RlpStream::new_list(count + 3);
+            signed.append_raw(fields.as_raw(), count);
+            signed.append(&recovery_id.to_byte());
+            signed.append(&U256::from_be_slice(&signature.r().to_bytes()));
+            signed.append(&U256::from_be_slice(&signature.s().to_bytes()));
+            [&[tx_type][..], &signed.out()].concat()
+        };
+        let common = |fields: &mut RlpStream| {
+            fields
+                .append(&1u64)
+                .append(&7u64)
+                .append(&2u64)
+                .append(&100u64);
+            fields.append(&50_000u64).append(&[0x35u8; 20].to_vec());
+            fields.append(&3u64).append(&vec![1u8, 2]);
+            // access list [[0x36..36, [1]]].
+            fields
+                .begin_list(1)
+                .begin_list(2)
+                .append(&[0x36u8; 20].to_vec());
+            fields.begin_list(1).append(&[1u8; 32].to_vec());
+        };
+
+        let mut fields = RlpStream::new();
+        common(&mut fields);
+        fields.append(&5u64);
+        fields.begin_list(1).append(&[1u8; 32].to_vec());
+        let decoded = decode_signed_transaction(&sign(BLOB_TX_TYPE, &fields, 11)).unwrap();
+        let sender = decoded.sender;
+        assert_eq!(decoded.tx.caller, sender);
+        assert_eq!(decoded.tx.gas_priority_fee, Some(U256::from(2)));
+        assert_eq!(decoded.tx.gas_price, U256::from(100));
+        assert_eq!(
+            decoded.tx.access_list,
+            [(B160([0x36; 20]), vec![U256::from_be_bytes([1; 32])])]
+        );
+        assert_eq!(decoded.tx.max_fee_per_blob_gas, Some(U256::from(5)));
+        assert_eq!(decoded.tx.blob_hashes, [B256([1; 32])]);
+
+        let mut fields = RlpStream::new();
+        common(&mut fields);
+        let authorization = SignedAuthorization {
+            chain_id: U256::from(1),
+            address: B160([0x37; 20]),
+            nonce: 8,
+            y_parity: 1,
+            r: U256::from(9),
+            s: U256::from(10),
+        };
+        fields
+            .begin_list(1)
+            .begin_list(6)
+            .append(&authorization.chain_id);
+        fields.append(&authorization.address.0.to_vec());
+        fields
+            .append(&authorization.nonce)
+            .append(&authorization.y_parity);
+        fields.append(&authorization.r).append(&authorization.s);
+        let decoded = decode_signed_transaction(&sign(SET_CODE_TX_TYPE, &fields, 10)).unwrap();
+        assert_eq!(decoded.sender, sender);
+        assert_eq!(decoded.tx.authorization_list, [authorization]);
+        assert_eq!(decoded.tx.data, Bytes::from_static(&[1, 2]));
+
+        assert_eq!(
+            decode_signed_transaction(&[0x05, 0xc0]).unwrap_err(),
+            TxDecodeError::UnknownType(5)
+        );
+    }
+}